@@ -7,6 +7,7 @@ import ora from 'ora';
 import * as fs from 'fs';
 import * as path from 'path';
 import * as crypto from 'crypto';
+import { LANGUAGES, Language, scaffold } from './scaffold';
 
 /**
  * Certus CLI
@@ -18,6 +19,7 @@ import * as crypto from 'crypto';
  * - certus exec accept --jobId <id> --deposit <amount>
  * - certus exec submit --jobId <id> --output <file> --sig <signature>
  * - certus fraud submit --jobId <id> --wasm <file> --input <file> --claimedOutput <file>
+ * - certus new <name> --lang rust|assemblyscript
  */
 
 const program = new Command();
@@ -304,5 +306,45 @@ fraudCmd
     }
   });
 
+// Project Commands
+
+program
+  .command('new')
+  .description('Create a job project for the Certus Wasm ABI')
+  .argument('<name>', 'Project name, also used as the directory')
+  .option('--lang <lang>', `Job language (${LANGUAGES.join('|')})`, 'rust')
+  .action((name: string, options) => {
+    try {
+      if (!LANGUAGES.includes(options.lang)) {
+        throw new Error(`Unsupported language: ${options.lang} (expected ${LANGUAGES.join(' or ')})`);
+      }
+      const lang = options.lang as Language;
+
+      const dir = path.resolve(name);
+      const files = scaffold(dir, path.basename(name), lang);
+
+      console.log(chalk.green(`Created ${lang} job in ${dir}`));
+      for (const file of files) {
+        console.log('  ' + file);
+      }
+
+      console.log(chalk.cyan('\nNext steps:'));
+      console.log(`  cd ${name}`);
+      if (lang === 'rust') {
+        console.log('  cargo test');
+        console.log('  cargo build --release --target wasm32-unknown-unknown');
+        console.log('  cargo run -p harness');
+      } else {
+        console.log('  npm install');
+        console.log('  npm test');
+      }
+
+    } catch (error: any) {
+      console.error(chalk.red('Failed to create project'));
+      console.error(chalk.red(error.message));
+      process.exit(1);
+    }
+  });
+
 // Parse and execute
 program.parse();
@@ -0,0 +1,615 @@
+import * as fs from 'fs';
+import * as path from 'path';
+
+/**
+ * Job templates for `certus new`
+ *
+ * Both templates target the ABI the verifier applies to raw Wasm jobs:
+ * - memory is imported as env.memory (1 page minimum); env.abort is the only other import
+ * - the host writes the input's length (little-endian u32) at INPUT_RECORD and the JSON input
+ *   right after it, at INPUT_OFFSET, then calls python_main(input_ptr, input_len)
+ * - python_main returns a pointer to an output record in the same shape: the length as a
+ *   little-endian u32, then that many bytes of UTF-8, which must not contain a NUL
+ * - no floating point, SIMD, threads or reference types
+ *
+ * Everything fits in the first page; the input record is 0x1000..0x2000.
+ */
+
+export const LANGUAGES = ['rust', 'assemblyscript'] as const;
+export type Language = typeof LANGUAGES[number];
+
+const INPUT_RECORD = 0x1000;
+const INPUT_OFFSET = INPUT_RECORD + 4;
+const INPUT_MAX = 4092;
+// The templates' output record, header included; the job's maxOutputSize bounds it on-chain
+const OUTPUT_RECORD = 4096;
+const MAX_OUTPUT_BYTES = 102400;
+const SAMPLE_INPUT = '{"values": [1, -2, 30]}';
+const SAMPLE_OUTPUT = '{"sum":29}';
+
+/**
+ * Helper: Check a project name is usable as a directory, crate and package name
+ */
+export function validateName(name: string): void {
+  if (!/^[a-z][a-z0-9_-]*$/.test(name)) {
+    throw new Error(`Invalid project name "${name}": use lowercase letters, digits, "-" and "_"`);
+  }
+}
+
+/**
+ * Helper: Files of a new project, keyed by path relative to the project root
+ */
+export function projectFiles(name: string, lang: Language): Record<string, string> {
+  validateName(name);
+  return lang === 'rust' ? rustFiles(name) : assemblyScriptFiles(name);
+}
+
+/**
+ * Write a new project into `dir`, refusing to touch a non-empty directory
+ */
+export function scaffold(dir: string, name: string, lang: Language): string[] {
+  if (fs.existsSync(dir) && fs.readdirSync(dir).length > 0) {
+    throw new Error(`Directory not empty: ${dir}`);
+  }
+
+  const files = projectFiles(name, lang);
+  for (const [file, contents] of Object.entries(files)) {
+    const target = path.join(dir, file);
+    fs.mkdirSync(path.dirname(target), { recursive: true });
+    fs.writeFileSync(target, contents);
+  }
+  return Object.keys(files);
+}
+
+// Rust: no_std cdylib built for wasm32-unknown-unknown, plus a wasmtime harness crate
+
+function rustFiles(name: string): Record<string, string> {
+  const crate = name.replace(/-/g, '_');
+  const wasmPath = `target/wasm32-unknown-unknown/release/${crate}.wasm`;
+
+  return {
+    'Cargo.toml': `[package]
+name = "${name}"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib"]
+
+[workspace]
+members = ["harness"]
+
+[profile.release]
+opt-level = "s"
+lto = true
+panic = "abort"
+codegen-units = 1
+`,
+
+    '.cargo/config.toml': `# Certus jobs import their memory and must fit its first 64 KiB page. The stack takes
+# the first 16 KiB and grows down toward the input region, which python_main copies out
+# on entry; statics follow the stack.
+# target-cpu=mvp keeps post-MVP features such as reference types out of the module.
+[target.wasm32-unknown-unknown]
+rustflags = [
+  "-C", "target-cpu=mvp",
+  "-C", "link-arg=--import-memory",
+  "-C", "link-arg=-zstack-size=16384",
+]
+`,
+
+    'src/abi.rs': `//! Certus job ABI
+//!
+//! The host writes the job input (UTF-8 JSON) at INPUT_OFFSET, its length as a little-endian
+//! u32 just before it, and calls python_main(input_ptr, input_len). The returned pointer must
+//! address an output record: the output's length as a little-endian u32, then the UTF-8 bytes.
+
+use core::ptr::addr_of_mut;
+
+pub const INPUT_OFFSET: usize = 0x${INPUT_OFFSET.toString(16)};
+pub const INPUT_MAX: usize = ${INPUT_MAX};
+/// Output bytes, not counting the length header
+pub const OUTPUT_MAX: usize = ${OUTPUT_RECORD - 4};
+
+static mut INPUT: [u8; INPUT_MAX] = [0; INPUT_MAX];
+static mut OUTPUT: [u8; OUTPUT_MAX + 4] = [0; OUTPUT_MAX + 4];
+
+/// Abort the job; the executor reports a trap
+pub fn trap() -> ! {
+    panic!()
+}
+
+/// Bounded output writer; overflowing the buffer traps instead of truncating
+pub struct Output<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Output<'a> {
+    /// The first four bytes of buf are reserved for the length header
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        // the verifier rejects outputs holding a NUL
+        if bytes.len() > self.buf.len() - 4 - self.len || bytes.contains(&0) {
+            trap();
+        }
+        self.buf[4 + self.len..4 + self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.push_bytes(text.as_bytes());
+    }
+
+    pub fn push_i64(&mut self, value: i64) {
+        let mut digits = [0u8; 20];
+        let mut n = value.unsigned_abs();
+        let mut start = digits.len();
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        if value < 0 {
+            self.push_bytes(b"-");
+        }
+        self.push_bytes(&digits[start..]);
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[4..4 + self.len]
+    }
+
+    fn finish(self) -> *const u8 {
+        self.buf[..4].copy_from_slice(&(self.len as u32).to_le_bytes());
+        self.buf.as_ptr()
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn python_main(input_ptr: *const u8, input_len: usize) -> *const u8 {
+    if input_len > INPUT_MAX {
+        trap();
+    }
+    // Safety: the host wrote input_len bytes at input_ptr, and python_main is the only
+    // code touching the static buffers
+    let (input, mut output) = unsafe {
+        let input = &mut *addr_of_mut!(INPUT);
+        core::ptr::copy_nonoverlapping(input_ptr, input.as_mut_ptr(), input_len);
+        (&input[..input_len], Output::new(&mut *addr_of_mut!(OUTPUT)))
+    };
+
+    crate::run(input, &mut output);
+    output.finish()
+}
+
+#[cfg(all(target_arch = "wasm32", not(test)))]
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    core::arch::wasm32::unreachable()
+}
+`,
+
+    'src/lib.rs': `#![cfg_attr(not(test), no_std)]
+
+pub mod abi;
+
+use abi::Output;
+
+/// Sum every integer in the input: ${SAMPLE_INPUT} -> ${SAMPLE_OUTPUT}
+///
+/// One pass over the input with no allocation, so fuel grows linearly with input size.
+/// Integer arithmetic only: floats are rejected by the verifier.
+pub fn run(input: &[u8], output: &mut Output) {
+    let mut sum: i64 = 0;
+    let mut current: i64 = 0;
+    let mut negative = false;
+    let mut in_number = false;
+
+    // the trailing space flushes a number at the very end
+    for &byte in input.iter().chain(b" ") {
+        if byte.is_ascii_digit() {
+            current = current.saturating_mul(10).saturating_add((byte - b'0') as i64);
+            in_number = true;
+            continue;
+        }
+        if in_number {
+            sum = sum.saturating_add(if negative { -current } else { current });
+        }
+        negative = byte == b'-';
+        current = 0;
+        in_number = false;
+    }
+
+    output.push_str("{\\"sum\\":");
+    output.push_i64(sum);
+    output.push_str("}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_str(input: &str) -> String {
+        let mut buf = [0u8; 64];
+        let mut output = Output::new(&mut buf);
+        run(input.as_bytes(), &mut output);
+        String::from_utf8(output.as_bytes().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn sums_integers() {
+        assert_eq!(run_str(r#"${SAMPLE_INPUT}"#), r#"${SAMPLE_OUTPUT}"#);
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(run_str("{}"), r#"{"sum":0}"#);
+    }
+}
+`,
+
+    'harness/Cargo.toml': `[package]
+name = "harness"
+version = "0.1.0"
+edition = "2021"
+
+# same engine versions as the Certus verifier
+[dependencies]
+anyhow = "1.0"
+wasmtime = "15.0.1"
+wasmparser = "0.118"
+`,
+
+    'harness/src/main.rs': `//! Run the built job the way a Certus verifier does:
+//!
+//!     cargo build --release --target wasm32-unknown-unknown
+//!     cargo run -p harness -- [module.wasm] [input.json]
+
+use anyhow::{Context, Result, bail};
+use wasmparser::{Parser, Payload, Validator, WasmFeatures};
+use wasmtime::*;
+
+const INPUT_RECORD: usize = 0x${INPUT_RECORD.toString(16)};
+const INPUT_OFFSET: usize = 0x${INPUT_OFFSET.toString(16)};
+const MAX_OUTPUT_BYTES: u32 = ${MAX_OUTPUT_BYTES};
+const MAX_MEMORY_PAGES: u32 = 256;
+const FUEL_LIMIT: u64 = 100_000_000;
+const ALLOWED_IMPORTS: &[(&str, &str)] = &[("env", "memory"), ("env", "abort")];
+
+// Same policy the verifier applies before metering a raw Wasm job
+fn check_determinism(wasm: &[u8]) -> Result<()> {
+    let features = WasmFeatures {
+        floats: false,
+        saturating_float_to_int: false,
+        simd: false,
+        relaxed_simd: false,
+        threads: false,
+        reference_types: false,
+        multi_memory: false,
+        memory64: false,
+        tail_call: false,
+        exceptions: false,
+        function_references: false,
+        gc: false,
+        memory_control: false,
+        component_model: false,
+        ..Default::default()
+    };
+    if let Err(e) = Validator::new_with_features(features).validate_all(wasm) {
+        bail!("module is not deterministic: {}", e);
+    }
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    let import = import?;
+                    if !ALLOWED_IMPORTS.contains(&(import.module, import.name)) {
+                        bail!("import {}.{} not allowed", import.module, import.name);
+                    }
+                }
+            }
+            Payload::MemorySection(reader) if reader.count() > 0 => {
+                bail!("module must import env.memory instead of defining a memory");
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let wasm_path = args.next().unwrap_or_else(|| "${wasmPath}".to_string());
+    let input = match args.next() {
+        Some(path) => std::fs::read_to_string(&path).with_context(|| format!("reading {}", path))?,
+        None => r#"${SAMPLE_INPUT}"#.to_string(),
+    };
+
+    let wasm = std::fs::read(&wasm_path).with_context(|| format!("reading {}", wasm_path))?;
+    check_determinism(&wasm)?;
+
+    let mut config = Config::new();
+    config.wasm_threads(false);
+    config.wasm_reference_types(false);
+    config.cranelift_nan_canonicalization(true);
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)?;
+
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(FUEL_LIMIT)?;
+
+    let mut linker = Linker::new(&engine);
+    let memory = Memory::new(&mut store, MemoryType::new(1, Some(MAX_MEMORY_PAGES)))?;
+    linker.define(&mut store, "env", "memory", memory)?;
+    linker.func_wrap("env", "abort", |_msg: i32| -> Result<()> { bail!("abort called") })?;
+
+    let module = Module::new(&engine, &wasm)?;
+    let instance = linker.instantiate(&mut store, &module).context("failed to instantiate module")?;
+    let run = instance
+        .get_typed_func::<(i32, i32), i32>(&mut store, "python_main")
+        .context("missing python_main export")?;
+
+    memory.write(&mut store, INPUT_RECORD, &(input.len() as u32).to_le_bytes())?;
+    memory.write(&mut store, INPUT_OFFSET, input.as_bytes())?;
+    let output_ptr = run.call(&mut store, (INPUT_OFFSET as i32, input.len() as i32))? as u32 as usize;
+
+    let mut header = [0u8; 4];
+    memory.read(&store, output_ptr, &mut header)?;
+    let len = u32::from_le_bytes(header);
+    if len > MAX_OUTPUT_BYTES {
+        bail!("output exceeds {} bytes", MAX_OUTPUT_BYTES);
+    }
+    let mut output = vec![0u8; len as usize];
+    memory.read(&store, output_ptr + 4, &mut output)?;
+    if output.contains(&0) {
+        bail!("output contains a NUL byte");
+    }
+
+    println!("{}", String::from_utf8(output).context("invalid utf-8 in output")?);
+    eprintln!("fuel consumed: {}", FUEL_LIMIT - store.get_fuel()?);
+    Ok(())
+}
+`,
+
+    '.gitignore': `/target
+`,
+
+    'README.md': `# ${name}
+
+Certus job written in Rust.
+
+\`\`\`bash
+# unit tests on the host
+cargo test
+
+# build the job module
+cargo build --release --target wasm32-unknown-unknown
+
+# run it as a Certus verifier would: determinism checks, fuel, the job ABI
+cargo run -p harness -- ${wasmPath} input.json
+\`\`\`
+
+${abiSection('src/abi.rs')}
+`,
+  };
+}
+
+// AssemblyScript: stub runtime, imported memory, statics placed after the input region
+
+function assemblyScriptFiles(name: string): Record<string, string> {
+  const wasmPath = `build/${name}.wasm`;
+
+  return {
+    'package.json': JSON.stringify({
+      name,
+      version: '0.1.0',
+      private: true,
+      type: 'module',
+      scripts: {
+        asbuild: 'asc assembly/index.ts --config asconfig.json --target release',
+        test: 'npm run asbuild && node tests/harness.js',
+      },
+      devDependencies: {
+        assemblyscript: '^0.27.0',
+      },
+    }, null, 2) + '\n',
+
+    'asconfig.json': JSON.stringify({
+      targets: {
+        release: {
+          outFile: wasmPath,
+          optimizeLevel: 3,
+          shrinkLevel: 1,
+        },
+      },
+      options: {
+        runtime: 'stub',
+        importMemory: true,
+        initialMemory: 1,
+        memoryBase: INPUT_OFFSET + INPUT_MAX,
+        use: ['abort=assembly/abi/abort'],
+        disable: ['nontrapping-f2i'],
+      },
+    }, null, 2) + '\n',
+
+    'assembly/abi.ts': `// Certus job ABI
+//
+// The host writes the job input (UTF-8 JSON) at INPUT_OFFSET, its length as a little-endian
+// u32 just before it, and calls python_main(input_ptr, input_len). The returned pointer must
+// address an output record: the output's length as a little-endian u32, then the UTF-8 bytes.
+
+export const INPUT_OFFSET: usize = 0x${INPUT_OFFSET.toString(16)};
+export const INPUT_MAX: usize = ${INPUT_MAX};
+// Output bytes, not counting the length header
+export const OUTPUT_MAX: usize = ${OUTPUT_RECORD - 4};
+
+const OUTPUT: usize = memory.data(${OUTPUT_RECORD});
+
+// Replaces the default env.abort import (see asconfig.json)
+export function abort(message: string | null, fileName: string | null, line: u32, column: u32): void {
+  unreachable();
+}
+
+// Bounded output writer; overflowing the buffer traps instead of truncating
+export class Output {
+  len: usize = 0;
+
+  pushByte(byte: u8): void {
+    // the verifier rejects outputs holding a NUL
+    if (this.len >= OUTPUT_MAX || byte == 0) unreachable();
+    store<u8>(OUTPUT + 4 + this.len, byte);
+    this.len++;
+  }
+
+  // Literal text only: each UTF-16 unit must be ASCII
+  pushAscii(text: string): void {
+    for (let i = 0; i < text.length; i++) {
+      const unit = text.charCodeAt(i);
+      if (unit > 0x7f) unreachable();
+      this.pushByte(<u8>unit);
+    }
+  }
+
+  pushI64(value: i64): void {
+    if (value < 0) this.pushByte(0x2d); // '-'
+    let n: u64 = value < 0 ? <u64>(0 - value) : <u64>value;
+    let divisor: u64 = 1;
+    while (n / divisor >= 10) divisor *= 10;
+    while (divisor > 0) {
+      this.pushByte(<u8>(0x30 + (n / divisor) % 10));
+      divisor /= 10;
+    }
+  }
+
+  finish(): usize {
+    store<u32>(OUTPUT, <u32>this.len);
+    return OUTPUT;
+  }
+}
+`,
+
+    'assembly/index.ts': `import { INPUT_MAX, Output } from './abi';
+
+export function python_main(inputPtr: usize, inputLen: usize): usize {
+  if (inputLen > INPUT_MAX) unreachable();
+  const output = new Output();
+  run(inputPtr, inputLen, output);
+  return output.finish();
+}
+
+// Sum every integer in the input: ${SAMPLE_INPUT} -> ${SAMPLE_OUTPUT}
+//
+// One pass over the input with no allocation, so fuel grows linearly with input size.
+// Integer arithmetic only: floats are rejected by the verifier.
+function run(input: usize, len: usize, output: Output): void {
+  let sum: i64 = 0;
+  let current: i64 = 0;
+  let negative = false;
+  let inNumber = false;
+
+  // one step past the end flushes a number at the very end
+  for (let i: usize = 0; i <= len; i++) {
+    const byte: u8 = i < len ? load<u8>(input + i) : 0x20;
+    if (byte >= 0x30 && byte <= 0x39) {
+      current = current * 10 + <i64>(byte - 0x30);
+      inNumber = true;
+      continue;
+    }
+    if (inNumber) sum += negative ? -current : current;
+    negative = byte == 0x2d; // '-'
+    current = 0;
+    inNumber = false;
+  }
+
+  output.pushAscii('{"sum":');
+  output.pushI64(sum);
+  output.pushAscii('}');
+}
+`,
+
+    'tests/harness.js': `// Run the built job the way a Certus verifier does: same imports, same memory, same ABI.
+// The verifier additionally rejects floats, SIMD and threads and meters fuel.
+
+import assert from 'node:assert/strict';
+import { readFileSync } from 'node:fs';
+
+const INPUT_RECORD = 0x${INPUT_RECORD.toString(16)};
+const INPUT_OFFSET = 0x${INPUT_OFFSET.toString(16)};
+const MAX_OUTPUT_BYTES = ${MAX_OUTPUT_BYTES};
+const MAX_MEMORY_PAGES = 256;
+const ALLOWED_IMPORTS = ['env.memory', 'env.abort'];
+
+const wasm = readFileSync(new URL('../${wasmPath}', import.meta.url));
+const module = await WebAssembly.compile(wasm);
+
+const imports = WebAssembly.Module.imports(module).map((i) => \`\${i.module}.\${i.name}\`);
+for (const name of imports) {
+  assert.ok(ALLOWED_IMPORTS.includes(name), \`import \${name} not allowed\`);
+}
+assert.ok(imports.includes('env.memory'), 'module must import env.memory instead of defining a memory');
+
+function run(input) {
+  const memory = new WebAssembly.Memory({ initial: 1, maximum: MAX_MEMORY_PAGES });
+  const abort = () => { throw new Error('abort called'); };
+  const instance = new WebAssembly.Instance(module, { env: { memory, abort } });
+
+  const bytes = new TextEncoder().encode(input);
+  new DataView(memory.buffer).setUint32(INPUT_RECORD, bytes.length, true);
+  new Uint8Array(memory.buffer, INPUT_OFFSET, bytes.length).set(bytes);
+  const ptr = instance.exports.python_main(INPUT_OFFSET, bytes.length) >>> 0;
+
+  const len = new DataView(memory.buffer).getUint32(ptr, true);
+  assert.ok(len <= MAX_OUTPUT_BYTES, \`output exceeds \${MAX_OUTPUT_BYTES} bytes\`);
+  const output = new Uint8Array(memory.buffer, ptr + 4, len);
+  assert.ok(!output.includes(0), 'output contains a NUL byte');
+  return new TextDecoder().decode(output);
+}
+
+assert.equal(run('${SAMPLE_INPUT}'), '${SAMPLE_OUTPUT}');
+assert.equal(run('{}'), '{"sum":0}');
+console.log('ok');
+`,
+
+    '.gitignore': `/node_modules
+/build
+`,
+
+    'README.md': `# ${name}
+
+Certus job written in AssemblyScript.
+
+\`\`\`bash
+npm install
+
+# build ${wasmPath} and run it under the Certus job ABI
+npm test
+\`\`\`
+
+${abiSection('assembly/abi.ts')}
+`,
+  };
+}
+
+function abiSection(abiFile: string): string {
+  return `## Job ABI
+
+Defined in \`${abiFile}\`:
+
+- The module imports \`env.memory\` and must fit its first 64 KiB page; \`env.abort\` is the only other import allowed.
+- The host writes the JSON input (at most ${INPUT_MAX} bytes) at \`0x${INPUT_OFFSET.toString(16)}\`, its length as a little-endian u32 just before it, and calls \`python_main(input_ptr, input_len)\`.
+- \`python_main\` returns a pointer to the output record: the output's length as a little-endian u32, then that many bytes of UTF-8 with no NUL. The job's \`maxOutputSize\` bounds the length.
+- No floating point, SIMD, threads or reference types. The verifier rejects such modules before running them.
+- Fuel is charged per function entry, loop iteration, call and memory access, so prefer single passes over the input and avoid recursion.
+
+## Submitting
+
+Send the module base64-encoded as \`wasm_b64\` in place of \`python_code\` to the verifier's \`POST /api/submit\`.`;
+}
@@ -95,6 +95,10 @@ contract CertusJobs is CertusBase, ReentrancyGuard, Ownable {
     // Emergency pause
     bool public paused;
 
+    // Content-addressed module registry keyed by sha256; each module is stored once and
+    // shared by every job that runs it
+    mapping(bytes32 => bytes) public wasmModules;
+
     // Fee parameters
     uint256 public minClientDepositUsd = 5 * 10**6; // $5
     uint256 public maxClientDepositUsd = 1000 * 10**6; // $1000
@@ -121,6 +125,26 @@ contract CertusJobs is CertusBase, ReentrancyGuard, Ownable {
         vrfSubId = _vrfSubId;
     }
 
+    /**
+     * Store a module so executors and verifiers can fetch it by hash
+     */
+    function registerWasm(bytes calldata wasm) external whenNotPaused returns (bytes32 wasmHash) {
+        require(wasm.length > 0 && wasm.length <= MAX_WASM_SIZE, "Invalid wasm size");
+        wasmHash = sha256(wasm);
+        require(wasmModules[wasmHash].length == 0, "Wasm already registered");
+
+        wasmModules[wasmHash] = wasm;
+        emit WasmRegistered(wasmHash, msg.sender, wasm.length);
+    }
+
+    /**
+     * Size of a registered module, 0 if unknown; lets clients skip re-uploading without
+     * fetching the module itself
+     */
+    function wasmModuleSize(bytes32 wasmHash) external view returns (uint256) {
+        return wasmModules[wasmHash].length;
+    }
+
     /**
      * Create new job with escrow
      */
@@ -539,6 +563,7 @@ contract CertusJobs is CertusBase, ReentrancyGuard, Ownable {
 
     // Events
     event TokenRegistered(address indexed token, uint8 decimals);
+    event WasmRegistered(bytes32 indexed wasmHash, address indexed uploader, uint256 size);
     event FallbackVerifierSelection(bytes32 indexed jobId, uint256 blocksSinceReceipt);
     event VRFLinkLow(uint256 balance, uint256 threshold);
     event Paused(address indexed by);
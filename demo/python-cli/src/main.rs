@@ -2,30 +2,70 @@ use anyhow::{Result, anyhow};
 use std::io::{self, Read};
 use serde_json::json;
 use base64::Engine;
+use python_verifier::compiler::CompileOptions;
+use python_verifier::profiles::ExecutionProfile;
+use python_verifier::PythonExecutor;
 
 fn main() -> Result<()> {
     let args: Vec<String> = std::env::args().collect();
 
     if args.len() < 2 {
-        eprintln!("Usage: python-cli <compile|execute>");
+        eprintln!("Usage: python-cli <compile|execute [--fuel N] [--optimize size|fuel] [--target latest|on-chain-v1]|run-wasm <module.wasm|module.wat> [--fuel N]|capabilities [--markdown]|vectors verify [file.json]|vectors bless <file.json>>");
         std::process::exit(1);
     }
 
     let command = &args[1];
 
     match command.as_str() {
-        "compile" => handle_compile(),
-        "execute" => handle_execute(),
+        "compile" => {
+            let (profile, options) = compile_flags(&args[2..])?;
+            handle_compile(&profile, options)
+        }
+        "execute" => {
+            let (profile, options) = compile_flags(&args[2..])?;
+            handle_execute(&profile, options)
+        }
+        "run-wasm" => handle_run_wasm(&args[2..]),
+        "capabilities" => handle_capabilities(&args[2..]),
+        "vectors" => handle_vectors(&args[2..]),
         _ => {
             eprintln!("Unknown command: {}", command);
-            eprintln!("Available commands: compile, execute");
+            eprintln!("Available commands: compile, execute, run-wasm, capabilities, vectors");
             std::process::exit(1);
         }
     }
 }
 
+/// The profile and compiler flags following the command: `--fuel N` for the job's fuel limit,
+/// which the module is built for, `--optimize size` or `--optimize fuel` (the default), and
+/// `--target on-chain-v1` for a module the on-chain interpreter can replay. Without flags the
+/// module is the one an executor builds for a job under the default profile.
+fn compile_flags(flags: &[String]) -> Result<(ExecutionProfile, CompileOptions)> {
+    let mut profile = ExecutionProfile::default();
+    let mut options = CompileOptions::default();
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "--fuel" => {
+                let fuel = flags.next().ok_or_else(|| anyhow!("--fuel needs a limit"))?;
+                profile.fuel_limit = fuel.parse()?;
+            }
+            "--optimize" => {
+                let target = flags.next().ok_or_else(|| anyhow!("--optimize needs size or fuel"))?;
+                options.optimize = target.parse()?;
+            }
+            "--target" => {
+                let target = flags.next().ok_or_else(|| anyhow!("--target needs latest or on-chain-v1"))?;
+                options.target = target.parse()?;
+            }
+            _ => return Err(anyhow!("Unknown flag: {}", flag)),
+        }
+    }
+    Ok((profile, options))
+}
+
 /// Read Python code from stdin, compile to Wasm, output JSON with base64
-fn handle_compile() -> Result<()> {
+fn handle_compile(profile: &ExecutionProfile, options: CompileOptions) -> Result<()> {
     let mut python_code = String::new();
     io::stdin().read_to_string(&mut python_code)?;
 
@@ -33,11 +73,12 @@ fn handle_compile() -> Result<()> {
         return Err(anyhow!("No Python code provided"));
     }
 
-    // Just compile, don't execute
-    use python_verifier::python_compiler::PythonCompiler;
-    let mut compiler = PythonCompiler::new();
+    // Just compile, don't execute; validated and built exactly as an executor builds a job
+    let mut executor = PythonExecutor::new()?;
+    let compiled = executor.validate_python(&python_code)
+        .and_then(|()| executor.compile(&python_code, profile, options));
 
-    match compiler.compile(&python_code) {
+    match compiled {
         Ok(wasm_bytes) => {
             let wasm_b64 = base64::engine::general_purpose::STANDARD.encode(&wasm_bytes);
             let result = json!({
@@ -55,46 +96,99 @@ fn handle_compile() -> Result<()> {
     }
 }
 
-/// Execute Wasm using the same pattern as tests
-fn handle_execute() -> Result<()> {
-    let mut python_code = String::new();
-    io::stdin().read_to_string(&mut python_code)?;
+/// Print the supported Python subset as JSON, or as a Markdown table with --markdown
+fn handle_capabilities(flags: &[String]) -> Result<()> {
+    use python_verifier::compiler::capabilities;
 
-    if python_code.trim().is_empty() {
-        return Err(anyhow!("No Python code provided"));
+    match flags {
+        [] => println!("{}", serde_json::to_string(capabilities::CAPABILITIES)?),
+        [flag] if flag == "--markdown" => print!("{}", capabilities::markdown()),
+        [flag, ..] => return Err(anyhow!("Unknown flag: {}", flag)),
+    }
+    Ok(())
+}
+
+/// Check this build against a test vector file, the shipped v1 set by default, printing each
+/// mismatch as JSON; or pin a file's module hashes and fuel to what this build produces
+fn handle_vectors(args: &[String]) -> Result<()> {
+    use python_verifier::vectors::VectorSet;
+
+    match args {
+        [command] if command == "verify" => verify_vectors(&VectorSet::v1()),
+        [command, path] if command == "verify" => verify_vectors(&VectorSet::parse(&std::fs::read_to_string(path)?)?),
+        [command, path] if command == "bless" => {
+            let mut set = VectorSet::parse(&std::fs::read_to_string(path)?)?;
+            set.bless()?;
+            std::fs::write(path, serde_json::to_string_pretty(&set)? + "\n")?;
+            eprintln!("Pinned {} vectors in {}", set.vectors.len(), path);
+            Ok(())
+        }
+        _ => Err(anyhow!("vectors needs verify [file.json] or bless <file.json>")),
     }
+}
 
-    use python_verifier::python_compiler::PythonCompiler;
-    use wasmtime::*;
+fn verify_vectors(set: &python_verifier::vectors::VectorSet) -> Result<()> {
+    let mismatches = set.verify()?;
+    for mismatch in &mismatches {
+        println!("{}", serde_json::to_string(mismatch)?);
+    }
+    if !mismatches.is_empty() {
+        eprintln!("{} mismatches against vector set v{}", mismatches.len(), set.version);
+        std::process::exit(1);
+    }
+    eprintln!("All {} vectors of set v{} match", set.vectors.len(), set.version);
+    Ok(())
+}
 
-    let mut compiler = PythonCompiler::new();
-    let wasm_bytes = compiler.compile(&python_code)?;
+/// Run a client module, binary or WAT, through the executor's own pipeline: determinism checks,
+/// fuel instrumentation and the sandbox. Input JSON comes from stdin.
+fn handle_run_wasm(args: &[String]) -> Result<()> {
+    let (path, flags) = args.split_first().ok_or_else(|| anyhow!("run-wasm needs a .wasm or .wat file"))?;
+    let mut profile = ExecutionProfile::default();
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "--fuel" => {
+                let fuel = flags.next().ok_or_else(|| anyhow!("--fuel needs a limit"))?;
+                profile.fuel_limit = fuel.parse()?;
+            }
+            _ => return Err(anyhow!("Unknown flag: {}", flag)),
+        }
+    }
 
-    // Execute using test pattern
-    let engine = Engine::default();
-    let mut store = Store::new(&engine, ());
+    let wasm = if path.ends_with(".wat") {
+        python_verifier::assemble_wat(&std::fs::read_to_string(path)?)?
+    } else {
+        std::fs::read(path)?
+    };
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    if input.trim().is_empty() {
+        input = "{}".to_string();
+    }
 
-    let memory_type = MemoryType::new(16, Some(256));
-    let memory = Memory::new(&mut store, memory_type)?;
+    let output = PythonExecutor::new()?.execute_wasm_with_profile(&wasm, &input, &profile)?;
+    println!("{}", serde_json::to_string(&output)?);
+    Ok(())
+}
 
-    let module = Module::new(&engine, &wasm_bytes)?;
-    let imports = [memory.into()];
-    let instance = Instance::new(&mut store, &module, &imports)?;
+/// Compile and run Python from stdin as an executor runs a job under `profile`, with an empty
+/// input
+fn handle_execute(profile: &ExecutionProfile, options: CompileOptions) -> Result<()> {
+    let mut python_code = String::new();
+    io::stdin().read_to_string(&mut python_code)?;
 
-    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
-    let output_val = main.call(&mut store, ())?;
+    if python_code.trim().is_empty() {
+        return Err(anyhow!("No Python code provided"));
+    }
 
-    // Hash the output
-    use sha2::{Sha256, Digest};
-    let output_str = output_val.to_string();
-    let mut hasher = Sha256::new();
-    hasher.update(output_str.as_bytes());
-    let output_hash = hex::encode(hasher.finalize());
+    let output = PythonExecutor::new()?.execute_with_options(&python_code, "{}", profile, options)?;
 
+    // the output hash covers the output together with stdout
     let result = json!({
-        "output": output_str,
-        "output_hash": output_hash,
-        "stdout": []
+        "output": output.result,
+        "output_hash": output.output_hash,
+        "stdout": output.stdout
     });
     println!("{}", serde_json::to_string(&result)?);
     Ok(())
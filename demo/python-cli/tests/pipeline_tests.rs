@@ -0,0 +1,81 @@
+// The CLI must emit the module an executor builds for the same job: a module that differs
+// between them would hash differently on-chain. Runs the built binary and compares its bytes
+// and outputs with the executor's own.
+
+use anyhow::Result;
+use base64::Engine;
+use python_verifier::compiler::CompileOptions;
+use python_verifier::profiles::ExecutionProfile;
+use python_verifier::PythonExecutor;
+use serde_json::Value;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const PROGRAMS: &[&str] = &[
+    "OUTPUT = 6 * 7",
+    r#"
+def fib(n):
+    if n < 2:
+        return n
+    return fib(n - 1) + fib(n - 2)
+OUTPUT = fib(7)
+"#,
+    r#"
+counts = {}
+for n in [3, 5, 3, 7, 3]:
+    if n in counts:
+        counts[n] += 1
+    else:
+        counts[n] = 1
+print("distinct", len(counts))
+OUTPUT = counts[3] * 10 + len(counts)
+"#,
+];
+
+fn cli(args: &[&str], stdin: &str) -> Result<Value> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_python-cli"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(stdin.as_bytes())?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success(), "python-cli {:?} failed", args);
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+fn cli_wasm(args: &[&str], code: &str) -> Result<Vec<u8>> {
+    let result = cli(args, code)?;
+    let wasm = result["wasm"].as_str().unwrap_or_else(|| panic!("{}", result));
+    Ok(base64::engine::general_purpose::STANDARD.decode(wasm)?)
+}
+
+#[test]
+fn test_compile_matches_the_executor() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let fueled = ExecutionProfile { fuel_limit: 5_000_000, ..Default::default() };
+    for code in PROGRAMS {
+        let default = executor.compile(code, &ExecutionProfile::default(), CompileOptions::default())?;
+        assert_eq!(cli_wasm(&["compile"], code)?, default, "{}", code);
+
+        // the module is built for the job's fuel, so a different limit is a different module
+        let expected = executor.compile(code, &fueled, CompileOptions::default())?;
+        assert_ne!(expected, default);
+        assert_eq!(cli_wasm(&["compile", "--fuel", "5000000"], code)?, expected, "{}", code);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_execute_matches_the_executor() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    for code in PROGRAMS {
+        let expected = executor.execute_with_profile(code, "{}", &ExecutionProfile::default())?;
+        let result = cli(&["execute"], code)?;
+        assert_eq!(result["output"], expected.result, "{}", code);
+        assert_eq!(result["output_hash"], expected.output_hash, "{}", code);
+        assert_eq!(result["stdout"], serde_json::to_value(&expected.stdout)?, "{}", code);
+    }
+    assert_eq!(cli(&["execute"], PROGRAMS[2])?["output"], "33");
+    Ok(())
+}
@@ -0,0 +1,51 @@
+// Gas schedule shared by the off-chain metering and the on-chain interpreter. Every opcode has
+// one cost here. The Stylus interpreter charges it as it executes each instruction; compiled and
+// instrumented modules charge a basic block's total on entering the block. Either way a job burns
+// the same gas, so executors and the fraud-proof contract agree on which jobs run out.
+//
+// Structural opcodes (nop, block, loop, else, end) are free: engines differ on whether the `end`
+// a branch lands on, or the `else` a finished then-arm jumps over, counts as executed, and a free
+// opcode makes the answer irrelevant.
+
+#![no_std]
+
+/// Bumped whenever a cost changes; metered modules record the version they were metered under
+pub const SCHEDULE_VERSION: u8 = 2;
+
+pub const BASE_COST: u64 = 1;
+pub const MEMORY_ACCESS_COST: u64 = 2;
+pub const DIVISION_COST: u64 = 4;
+/// Covers the callee's frame setup; its instructions are charged as they run
+pub const CALL_COST: u64 = 10;
+/// memory.copy, memory.fill and memory.init cost the same whatever their length, which the heap
+/// limit bounds
+pub const BULK_MEMORY_COST: u64 = 20;
+pub const MEMORY_GROW_COST: u64 = 1000;
+
+/// Gas of one instruction, by its first byte; an 0xFC-prefixed instruction costs its prefix
+pub const fn opcode_cost(opcode: u8) -> u64 {
+    match opcode {
+        // nop, block, loop, else, end
+        0x01..=0x03 | 0x05 | 0x0B => 0,
+        // call, call_indirect
+        0x10 | 0x11 => CALL_COST,
+        // loads and stores
+        0x28..=0x3E => MEMORY_ACCESS_COST,
+        0x40 => MEMORY_GROW_COST,
+        // i32 and i64 div_s, div_u, rem_s, rem_u
+        0x6D..=0x70 | 0x7F..=0x82 => DIVISION_COST,
+        0xFC => BULK_MEMORY_COST,
+        _ => BASE_COST,
+    }
+}
+
+/// Gas of a sequence of instructions, by their first bytes
+pub const fn sequence_cost(opcodes: &[u8]) -> u64 {
+    let mut total = 0;
+    let mut i = 0;
+    while i < opcodes.len() {
+        total += opcode_cost(opcodes[i]);
+        i += 1;
+    }
+    total
+}
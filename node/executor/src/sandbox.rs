@@ -36,26 +36,9 @@ impl WasmSandbox {
             bail!("Module exceeds 24KB limit: {} bytes", wasm.len());
         }
 
-        // Check magic and version
-        if wasm.len() < 8 {
-            bail!("Module too small: {} bytes", wasm.len());
-        }
-
-        if &wasm[0..4] != b"\0asm" {
-            bail!("Invalid Wasm magic");
-        }
-
-        if &wasm[4..8] != &[1, 0, 0, 0] {
-            bail!("Unsupported Wasm version");
-        }
-
-        // Scan for float opcodes (comprehensive check)
-        for (i, &byte) in wasm.iter().enumerate().skip(8) {
-            match byte {
-                0x43..=0x98 => bail!("f32 opcode 0x{:02x} at offset {}", byte, i),
-                0x99..=0xBF => bail!("f64 opcode 0x{:02x} at offset {}", byte, i),
-                _ => {}
-            }
+        // Header, opcodes and imports, by the policy the verifier and the contract apply
+        if let Err(violation) = certus_wasm_policy::validate(wasm) {
+            bail!("Module is not deterministic: {}", violation);
         }
 
         // Verify module compiles with deterministic config
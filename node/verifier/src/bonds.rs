@@ -0,0 +1,121 @@
+use ethers::types::{Address, H256, U256};
+use serde::{Deserialize, Serialize};
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// How a dispute ended for the bond posted on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisputeOutcome {
+    /// Bond still locked in the escrow
+    Pending,
+    /// Fraud proven; the bond came back
+    Won,
+    /// No fraud found; the bond went to the executor
+    Lost,
+}
+
+/// Bond posted to dispute one job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dispute {
+    pub job_id: H256,
+    pub token: Address,
+    pub bond: U256,
+    pub opened_at: u64,
+    pub outcome: DisputeOutcome,
+}
+
+/// Bonds this verifier has posted, persisted so exposure survives restarts
+pub struct BondLedger {
+    path: PathBuf,
+    max_exposure: U256,
+    disputes: BTreeMap<H256, Dispute>,
+}
+
+impl BondLedger {
+    /// Load the ledger at `path`, starting empty if it doesn't exist yet
+    pub fn open(path: impl AsRef<Path>, max_exposure: U256) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let disputes = match std::fs::read(&path) {
+            Ok(bytes) => {
+                let list: Vec<Dispute> = serde_json::from_slice(&bytes)
+                    .with_context(|| format!("Corrupt bond ledger {}", path.display()))?;
+                list.into_iter().map(|d| (d.job_id, d)).collect()
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e).with_context(|| format!("Cannot read bond ledger {}", path.display())),
+        };
+
+        Ok(Self {
+            path,
+            max_exposure,
+            disputes,
+        })
+    }
+
+    /// Bonds locked in disputes that haven't resolved
+    pub fn exposure(&self) -> U256 {
+        self.disputes.values()
+            .filter(|d| d.outcome == DisputeOutcome::Pending)
+            .fold(U256::zero(), |acc, d| acc + d.bond)
+    }
+
+    /// Whether another bond of this size stays within the exposure cap
+    pub fn can_post(&self, bond: U256) -> bool {
+        self.exposure().saturating_add(bond) <= self.max_exposure
+    }
+
+    /// Record a bond before it is posted; refuses once exposure would exceed the cap
+    pub fn post(&mut self, job_id: H256, token: Address, bond: U256) -> Result<()> {
+        if self.disputes.contains_key(&job_id) {
+            bail!("Dispute already open for job {:?}", job_id);
+        }
+        if !self.can_post(bond) {
+            bail!(
+                "Bond of {} would raise exposure to {} over cap {}",
+                bond,
+                self.exposure().saturating_add(bond),
+                self.max_exposure,
+            );
+        }
+
+        let opened_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        self.disputes.insert(job_id, Dispute {
+            job_id,
+            token,
+            bond,
+            opened_at,
+            outcome: DisputeOutcome::Pending,
+        });
+        self.save()
+    }
+
+    /// Record how a dispute ended, releasing its bond from exposure
+    pub fn resolve(&mut self, job_id: H256, outcome: DisputeOutcome) -> Result<()> {
+        let dispute = self.disputes.get_mut(&job_id)
+            .ok_or_else(|| anyhow::anyhow!("No dispute for job {:?}", job_id))?;
+        dispute.outcome = outcome;
+        self.save()
+    }
+
+    /// Totals of bonds won back and forfeited
+    pub fn settled(&self) -> (U256, U256) {
+        self.disputes.values().fold((U256::zero(), U256::zero()), |(won, lost), d| match d.outcome {
+            DisputeOutcome::Won => (won + d.bond, lost),
+            DisputeOutcome::Lost => (won, lost + d.bond),
+            DisputeOutcome::Pending => (won, lost),
+        })
+    }
+
+    // Write then rename, so a crash leaves either the old ledger or the new one
+    fn save(&self) -> Result<()> {
+        let list: Vec<&Dispute> = self.disputes.values().collect();
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&list)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
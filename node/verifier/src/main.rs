@@ -1,6 +1,8 @@
+mod bonds;
 mod verifier;
 
 use anyhow::Result;
+use ethers::types::U256;
 use tracing::info;
 use tracing_subscriber;
 
@@ -20,13 +22,29 @@ async fn main() -> Result<()> {
     let private_key = &args[2];
     let contract_address = &args[3];
 
+    // Dispute bonds default to the escrow's $100 challenge stake, capped at ten open disputes
+    let ledger_path = std::env::var("CERTUS_BOND_LEDGER").unwrap_or_else(|_| "bonds.json".into());
+    let dispute_bond = env_amount("CERTUS_DISPUTE_BOND", 100_000_000)?;
+    let max_exposure = env_amount("CERTUS_MAX_BOND_EXPOSURE", 1_000_000_000)?;
+    let bonds = bonds::BondLedger::open(&ledger_path, max_exposure)?;
+
     let verifier = verifier::VerifierNode::new(
         rpc_url,
         private_key,
         contract_address,
+        bonds,
+        dispute_bond,
     ).await?;
 
     verifier.run().await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Token amount (6 decimals) from the environment
+fn env_amount(name: &str, default: u64) -> Result<U256> {
+    match std::env::var(name) {
+        Ok(value) => Ok(U256::from_dec_str(&value)?),
+        Err(_) => Ok(U256::from(default)),
+    }
+}
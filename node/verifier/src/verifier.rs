@@ -13,14 +13,17 @@ use wasmtime::*;
 use anyhow::Result;
 use tracing::{info, error, warn};
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use hex;
+use crate::bonds::{BondLedger, DisputeOutcome};
 
 /// Verifier node
 pub struct VerifierNode {
     escrow: EscrowClient,
     engine: Engine,
     address: Address,
+    bonds: Mutex<BondLedger>,
+    dispute_bond: U256,
 }
 
 impl VerifierNode {
@@ -29,6 +32,8 @@ impl VerifierNode {
         rpc_url: &str,
         private_key: &str,
         contract_addr: &str,
+        bonds: BondLedger,
+        dispute_bond: U256,
     ) -> Result<Self> {
         let provider = Provider::<Http>::try_from(rpc_url)?;
         let wallet = private_key.parse::<LocalWallet>()?.with_chain_id(421614u64);
@@ -57,12 +62,19 @@ impl VerifierNode {
             escrow,
             engine,
             address,
+            bonds: Mutex::new(bonds),
+            dispute_bond,
         })
     }
 
     /// Main verification loop
     pub async fn run(&self) -> Result<()> {
         info!("Verifier running: {}", self.address);
+        {
+            let bonds = self.bonds.lock().unwrap();
+            let (won, lost) = bonds.settled();
+            info!("Bond exposure: {} (won {}, lost {})", bonds.exposure(), won, lost);
+        }
 
         // Spawn heartbeat task
         let escrow = self.escrow.clone();
@@ -93,6 +105,14 @@ impl VerifierNode {
                         info!("Receipt valid");
                     }
                     Ok(VerificationResult::Fraud { claimed: _, computed: _ }) => {
+                        let job_id = H256::from(job.job_id);
+
+                        // Post the bond only while total exposure stays under the cap
+                        if let Err(e) = self.bonds.lock().unwrap().post(job_id, job.pay_token, self.dispute_bond) {
+                            warn!("Fraud detected but not disputed: {}", e);
+                            continue;
+                        }
+
                         warn!("Fraud detected, submitting proof");
 
                         // Get the actual output for fraud proof
@@ -103,12 +123,29 @@ impl VerifierNode {
                             job.mem_limit,
                         )?;
 
-                        self.submit_fraud(
-                            H256::from(job.job_id),
+                        let outcome = match self.submit_fraud(
+                            job_id,
                             &wasm,
                             &input,
                             &actual_output,
-                        ).await?;
+                        ).await {
+                            Ok(()) => Some(DisputeOutcome::Won),
+                            Err(e) if e.to_string().contains("No fraud detected") => {
+                                error!("Fraud proof rejected: {}", e);
+                                Some(DisputeOutcome::Lost)
+                            }
+                            Err(e) => {
+                                // Outcome unknown; the bond stays counted against exposure
+                                error!("Fraud proof submission failed: {}", e);
+                                None
+                            }
+                        };
+
+                        if let Some(outcome) = outcome {
+                            if let Err(e) = self.bonds.lock().unwrap().resolve(job_id, outcome) {
+                                error!("Could not record dispute outcome: {}", e);
+                            }
+                        }
                     }
                     Ok(VerificationResult::Error(msg)) => {
                         error!("Verification error: {}", msg);
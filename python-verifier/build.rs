@@ -0,0 +1,50 @@
+// Build provenance: the git commit and the hash of the workspace Cargo.lock are baked into the
+// binary so receipts and /status can say exactly which source and dependency set a node runs.
+// Builds outside a checkout (e.g. from a source tarball in Docker) pass CERTUS_GIT_COMMIT.
+
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let manifest_dir = Path::new(&manifest_dir);
+
+    println!("cargo:rerun-if-env-changed=CERTUS_GIT_COMMIT");
+    let commit = std::env::var("CERTUS_GIT_COMMIT").ok().or_else(git_commit).unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=CERTUS_GIT_COMMIT={}", commit);
+
+    // the workspace lock; a crate built on its own keeps one next to its manifest
+    let lock = [manifest_dir.join("../Cargo.lock"), manifest_dir.join("Cargo.lock")]
+        .into_iter()
+        .find(|path| path.exists());
+    let lock_hash = match &lock {
+        Some(path) => {
+            println!("cargo:rerun-if-changed={}", path.display());
+            hex::encode(Sha256::digest(std::fs::read(path).unwrap()))
+        }
+        None => "unknown".to_string(),
+    };
+    println!("cargo:rustc-env=CERTUS_LOCK_HASH={}", lock_hash);
+
+    let git_dir = manifest_dir.join("../.git");
+    if git_dir.exists() {
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+        println!("cargo:rerun-if-changed={}", git_dir.join("index").display());
+    }
+}
+
+// HEAD, marked dirty when the tree has uncommitted changes so it can never match an allowlist entry
+fn git_commit() -> Option<String> {
+    let head = Command::new("git").args(["rev-parse", "HEAD"]).output().ok()?;
+    if !head.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(head.stdout).ok()?.trim().to_string();
+    let dirty = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .output()
+        .map(|out| !out.stdout.is_empty())
+        .unwrap_or(false);
+    Some(if dirty { format!("{}-dirty", commit) } else { commit })
+}
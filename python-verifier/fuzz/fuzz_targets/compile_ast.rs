@@ -0,0 +1,233 @@
+// Arbitrary programs in the subset the compiler lowers, built as an AST and printed as source,
+// so inputs get past the parser and exercise lowering, the optimizer and codegen. Programs may
+// still be rejected (a type error, `break` outside a loop); they must never panic, and what
+// compiles must be valid, byte-stable Wasm.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use python_verifier::python_compiler::PythonCompiler;
+
+// Deeper expressions and blocks print as a literal or nothing, keeping the parser's recursion
+// and the input size bounded
+const MAX_DEPTH: usize = 6;
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum Var {
+    A,
+    B,
+    Text,
+    Items,
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    FloorDiv,
+    Mod,
+    Pow,
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum CmpOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtE,
+    Gt,
+    GtE,
+    In,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Expr {
+    Int(i16),
+    Bool(bool),
+    Str(u8),
+    Var(Var),
+    List(Vec<Expr>),
+    Dict(Vec<(u8, Expr)>),
+    Bin(Box<Expr>, BinOp, Box<Expr>),
+    Compare(Box<Expr>, CmpOp, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    IfExp(Box<Expr>, Box<Expr>, Box<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    Len(Box<Expr>),
+    ToStr(Box<Expr>),
+    Call(Box<Expr>),
+    ListComp(Box<Expr>, u8),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Stmt {
+    Assign(Var, Expr),
+    AugAssign(Var, BinOp, Expr),
+    SetItem(Var, Expr, Expr),
+    Append(Var, Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    For(Var, u8, Vec<Stmt>),
+    Break,
+    Continue,
+    Assert(Expr),
+    Print(Expr),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Program {
+    helper: Vec<Stmt>,
+    returns: Expr,
+    body: Vec<Stmt>,
+    output: Expr,
+}
+
+impl Var {
+    fn name(self) -> &'static str {
+        match self {
+            Var::A => "a",
+            Var::B => "b",
+            Var::Text => "text",
+            Var::Items => "items",
+        }
+    }
+}
+
+impl BinOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::FloorDiv => "//",
+            BinOp::Mod => "%",
+            BinOp::Pow => "**",
+        }
+    }
+}
+
+impl CmpOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            CmpOp::Eq => "==",
+            CmpOp::NotEq => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::LtE => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::GtE => ">=",
+            CmpOp::In => "in",
+        }
+    }
+}
+
+impl Expr {
+    fn print(&self, depth: usize) -> String {
+        if depth > MAX_DEPTH {
+            return "0".to_string();
+        }
+        let p = |e: &Expr| e.print(depth + 1);
+        match self {
+            Expr::Int(n) => n.to_string(),
+            Expr::Bool(b) => if *b { "True" } else { "False" }.to_string(),
+            Expr::Str(n) => format!("\"s{}\"", n % 8),
+            Expr::Var(v) => v.name().to_string(),
+            Expr::List(items) => format!("[{}]", items.iter().map(p).collect::<Vec<_>>().join(", ")),
+            Expr::Dict(items) => format!(
+                "{{{}}}",
+                items.iter().map(|(k, v)| format!("\"k{}\": {}", k % 8, p(v))).collect::<Vec<_>>().join(", ")
+            ),
+            Expr::Bin(l, op, r) => format!("({} {} {})", p(l), op.symbol(), p(r)),
+            Expr::Compare(l, op, r) => format!("({} {} {})", p(l), op.symbol(), p(r)),
+            Expr::And(l, r) => format!("({} and {})", p(l), p(r)),
+            Expr::Or(l, r) => format!("({} or {})", p(l), p(r)),
+            Expr::Not(e) => format!("(not {})", p(e)),
+            Expr::Neg(e) => format!("(-{})", p(e)),
+            Expr::IfExp(c, t, f) => format!("({} if {} else {})", p(t), p(c), p(f)),
+            Expr::Index(e, i) => format!("{}[{}]", p(e), p(i)),
+            Expr::Len(e) => format!("len({})", p(e)),
+            Expr::ToStr(e) => format!("str({})", p(e)),
+            Expr::Call(e) => format!("helper({})", p(e)),
+            Expr::ListComp(e, n) => format!("[{} for i in range({})]", p(e), n % 16),
+        }
+    }
+}
+
+impl Stmt {
+    fn print(&self, out: &mut String, indent: usize, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let pad = "    ".repeat(indent);
+        let e = |e: &Expr| e.print(0);
+        match self {
+            Stmt::Assign(v, value) => out.push_str(&format!("{}{} = {}\n", pad, v.name(), e(value))),
+            Stmt::AugAssign(v, op, value) => {
+                out.push_str(&format!("{}{} {}= {}\n", pad, v.name(), op.symbol(), e(value)))
+            }
+            Stmt::SetItem(v, index, value) => {
+                out.push_str(&format!("{}{}[{}] = {}\n", pad, v.name(), e(index), e(value)))
+            }
+            Stmt::Append(v, value) => out.push_str(&format!("{}{}.append({})\n", pad, v.name(), e(value))),
+            Stmt::If(cond, then, otherwise) => {
+                out.push_str(&format!("{}if {}:\n", pad, e(cond)));
+                block(out, then, indent + 1, depth + 1);
+                if !otherwise.is_empty() {
+                    out.push_str(&format!("{}else:\n", pad));
+                    block(out, otherwise, indent + 1, depth + 1);
+                }
+            }
+            Stmt::While(cond, body) => {
+                out.push_str(&format!("{}while {}:\n", pad, e(cond)));
+                block(out, body, indent + 1, depth + 1);
+            }
+            Stmt::For(v, n, body) => {
+                out.push_str(&format!("{}for {} in range({}):\n", pad, v.name(), n % 16));
+                block(out, body, indent + 1, depth + 1);
+            }
+            Stmt::Break => out.push_str(&format!("{}break\n", pad)),
+            Stmt::Continue => out.push_str(&format!("{}continue\n", pad)),
+            Stmt::Assert(cond) => out.push_str(&format!("{}assert {}\n", pad, e(cond))),
+            Stmt::Print(value) => out.push_str(&format!("{}print({})\n", pad, e(value))),
+        }
+    }
+}
+
+// A block that prints nothing still needs a statement
+fn block(out: &mut String, body: &[Stmt], indent: usize, depth: usize) {
+    let start = out.len();
+    for stmt in body {
+        stmt.print(out, indent, depth);
+    }
+    if out.len() == start {
+        out.push_str(&format!("{}pass\n", "    ".repeat(indent)));
+    }
+}
+
+impl Program {
+    fn source(&self) -> String {
+        let mut out = String::from("def helper(a):\n    b = 1\n    text = \"t\"\n    items = [a]\n");
+        block(&mut out, &self.helper, 1, 0);
+        out.push_str(&format!("    return {}\n\n", self.returns.print(0)));
+        out.push_str("a = 0\nb = 1\ntext = \"t\"\nitems = [1, 2, 3]\n");
+        block(&mut out, &self.body, 0, 0);
+        out.push_str(&format!("OUTPUT = {}\n", self.output.print(0)));
+        out
+    }
+}
+
+fuzz_target!(|program: Program| {
+    let source = program.source();
+    let Ok(wasm) = PythonCompiler::new().compile(&source) else {
+        return;
+    };
+    if let Err(err) = wasmparser::validate(&wasm) {
+        panic!("invalid module for\n{}\n{}", source, err);
+    }
+    let again = PythonCompiler::new().compile(&source).expect("compiled once, failed the second time");
+    assert!(wasm == again, "output differs between compiles of\n{}", source);
+});
@@ -0,0 +1,19 @@
+// Arbitrary text as Python source. Most of it doesn't parse; the compiler must reject it with
+// an error, never a panic, and whatever it accepts must come out as valid, byte-stable Wasm.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use python_verifier::python_compiler::PythonCompiler;
+
+fuzz_target!(|source: &str| {
+    let Ok(wasm) = PythonCompiler::new().compile(source) else {
+        return;
+    };
+    if let Err(err) = wasmparser::validate(&wasm) {
+        panic!("invalid module for {:?}: {}", source, err);
+    }
+    // a fresh compiler, so the cache can't hand back the same bytes
+    let again = PythonCompiler::new().compile(source).expect("compiled once, failed the second time");
+    assert!(wasm == again, "output differs between compiles of {:?}", source);
+});
@@ -1,40 +1,70 @@
 use axum::{
-    extract::{Path, State, Json},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{ConnectInfo, Path, Query, State, Json},
+    http::{header, HeaderValue, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
     routing::{post, get},
     Router,
 };
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use tokio::sync::RwLock;
 use tower_http::cors::CorsLayer;
 use std::collections::HashMap;
-use sha2::Digest;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use crate::artifacts::ArtifactStore;
+use crate::canary::CanaryState;
+use crate::compiler::CompileOptions;
+use crate::executor_error::ExecutorError;
 use crate::certus_integration::CertusIntegration;
+use crate::indexer::ProtocolIndexer;
+use crate::queue::JobQueue;
+use crate::websocket::verify_history;
+use crate::rate_limit::RateLimiter;
+use crate::receipts::{self, Finalization, ReceiptStore};
 
 /// API server - all ops through Certus contracts
 pub struct ApiServer {
     certus: Arc<CertusIntegration>,
     jobs: Arc<RwLock<HashMap<String, CertusJobRecord>>>,
+    receipts: Arc<ReceiptStore>,
+    history: Option<Arc<JobQueue>>,
 }
 
 impl ApiServer {
-    pub async fn new(
-        executor: Arc<Mutex<crate::PythonExecutor>>,
-        rpc_url: &str,
-        private_key: &str,
-        escrow_addr: &str,
-        jobs_addr: &str,
-    ) -> anyhow::Result<Self> {
-        let certus = Arc::new(
-            CertusIntegration::new(executor, rpc_url, private_key, escrow_addr, jobs_addr).await?
-        );
-
-        Ok(Self {
+    /// Serve `certus`, the integration the node's workers use, so submissions see the same profiles
+    pub fn new(certus: Arc<CertusIntegration>, receipts: Arc<ReceiptStore>) -> Self {
+        Self {
             certus,
             jobs: Arc::new(RwLock::new(HashMap::new())),
-        })
+            receipts,
+            history: None,
+        }
+    }
+
+    /// Serve the hash-chained status history the queue records for each job
+    pub fn with_history(mut self, queue: Arc<JobQueue>) -> Self {
+        self.history = Some(queue);
+        self
+    }
+
+    /// Poll for finalized jobs, issue their signed receipts and push them to client webhooks
+    pub fn spawn_finalization_watcher(&self, interval: std::time::Duration) {
+        let certus = self.certus.clone();
+        let receipts = self.receipts.clone();
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = process_finalizations(&certus, &receipts, &http).await {
+                    log::error!("Finalization watcher failed: {}", e);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
     }
 
     pub fn routes(self) -> Router {
@@ -45,17 +75,263 @@ impl ApiServer {
             .route("/api/execute/:id", post(execute_job))
             .route("/api/verify/:id", post(verify_job))
             .route("/api/job/:id", get(get_job))
+            .route("/jobs/:id/receipt", get(get_receipt))
+            .route("/jobs/:id/output", get(get_output))
+            .route("/jobs/:id/history", get(get_history))
             .route("/api/jobs", get(list_jobs))
             .route("/api/examples", get(get_examples))
+            .route("/capabilities", get(get_capabilities))
+            .route("/status", get(get_status))
+            .layer(CorsLayer::permissive())
+            .with_state(state)
+    }
+
+    /// Read-only routes safe to expose without auth: job status and receipts, rate limited per
+    /// client address and cacheable. Needs a server built with connect info.
+    pub fn public_routes(&self, config: PublicApiConfig) -> Router {
+        let state = PublicState {
+            jobs: self.jobs.clone(),
+            receipts: self.receipts.clone(),
+            limiter: Arc::new(RateLimiter::new(config.requests_per_minute, config.burst)),
+            cache_seconds: config.cache_seconds,
+        };
+
+        Router::new()
+            .route("/public/jobs/:id", get(public_job))
+            .route("/public/jobs/:id/receipt", get(public_receipt))
+            .route("/public/capabilities", get(get_capabilities))
+            .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit))
             .layer(CorsLayer::permissive())
             .with_state(state)
     }
 }
 
+/// Limits of the public read-only API
+#[derive(Debug, Clone, Copy)]
+pub struct PublicApiConfig {
+    /// Sustained requests per client address
+    pub requests_per_minute: u32,
+    /// Requests a client may make at once
+    pub burst: u32,
+    /// max-age of job status responses; receipts never change and are cached for a day
+    pub cache_seconds: u64,
+}
+
+impl Default for PublicApiConfig {
+    fn default() -> Self {
+        Self { requests_per_minute: 30, burst: 10, cache_seconds: 10 }
+    }
+}
+
+#[derive(Clone)]
+struct PublicState {
+    jobs: Arc<RwLock<HashMap<String, CertusJobRecord>>>,
+    receipts: Arc<ReceiptStore>,
+    limiter: Arc<RateLimiter>,
+    cache_seconds: u64,
+}
+
+const RECEIPT_CACHE_SECONDS: u64 = 86_400;
+
+/// What the public may see of a job: no code, module, input or tenant data
+#[derive(Debug, Serialize)]
+struct PublicJobStatus {
+    job_id: String,
+    status: CertusJobStatus,
+    tx_hash: Option<String>,
+    output_hash: Option<String>,
+    created_at: u64,
+}
+
+async fn rate_limit<B>(
+    State(state): State<PublicState>,
+    ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    match state.limiter.check(addr.ip()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, (wait.as_secs() + 1).to_string())],
+            "Rate limit exceeded",
+        ).into_response(),
+    }
+}
+
+fn cached(response: impl IntoResponse, max_age: u64) -> Response {
+    let mut response = response.into_response();
+    if let Ok(value) = HeaderValue::from_str(&format!("public, max-age={}", max_age)) {
+        response.headers_mut().insert(header::CACHE_CONTROL, value);
+    }
+    response
+}
+
+async fn public_job(
+    State(state): State<PublicState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let jobs = state.jobs.read().await;
+    match jobs.get(&receipts::normalize_job_id(&id)) {
+        Some(job) => cached(Json(PublicJobStatus {
+            job_id: job.job_id.clone(),
+            status: job.status.clone(),
+            tx_hash: job.tx_hash.clone(),
+            output_hash: job.output_hash.clone(),
+            created_at: job.created_at,
+        }), state.cache_seconds),
+        None => cached((StatusCode::NOT_FOUND, "Job not found"), state.cache_seconds),
+    }
+}
+
+async fn public_receipt(
+    State(state): State<PublicState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.receipts.receipt(&id) {
+        Ok(Some(receipt)) => cached(Json(receipt), RECEIPT_CACHE_SECONDS),
+        Ok(None) => cached((StatusCode::NOT_FOUND, "No receipt: job unknown or not finalized"), state.cache_seconds),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+// Receipts are issued at most once per job, so a redelivery after a crash or a lost
+// webhook response carries the same payload and signature
+async fn process_finalizations(
+    certus: &CertusIntegration,
+    receipts: &ReceiptStore,
+    http: &reqwest::Client,
+) -> anyhow::Result<()> {
+    let (events, head) = certus.finalized_jobs(receipts.cursor()?).await?;
+    for event in events {
+        let job_id = format!("0x{}", hex::encode(event.job_id));
+        if receipts.receipt(&job_id)?.is_some() {
+            continue;
+        }
+        let Some(job) = receipts.tracked(&job_id)? else {
+            continue;
+        };
+        let (Some(output_hash), Some(fuel_used)) = (job.output_hash, job.fuel_used) else {
+            continue;
+        };
+
+        let receipt = certus.sign_receipt(&Finalization {
+            job_id: event.job_id,
+            tx_hash: event.tx_hash,
+            output_hash: receipts::parse_bytes32(&output_hash)?,
+            fuel_used,
+            finalized_at: event.finalized_at,
+        })?;
+        // never hand out a receipt the client could not verify
+        receipt.verify()?;
+        receipts.issue(receipt)?;
+        log::info!("Issued receipt for finalized job {}", job_id);
+    }
+    receipts.set_cursor(head + 1)?;
+
+    for (receipt, url) in receipts.pending_deliveries()? {
+        match receipts::deliver_webhook(http, &url, &receipt).await {
+            Ok(()) => receipts.mark_delivered(&receipt.job_id)?,
+            Err(e) => {
+                log::warn!("Receipt delivery for job {} failed: {}", receipt.job_id, e);
+                receipts.record_failed_delivery(&receipt.job_id, &e.to_string())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+struct HealthState {
+    queue: Arc<JobQueue>,
+    artifacts: Arc<ArtifactStore>,
+    canary: Arc<CanaryState>,
+    protocol: Arc<ProtocolIndexer>,
+}
+
+/// Health routes: JSON at /admin/queue/stats, /admin/artifacts/stats, /admin/canary and
+/// /stats/protocol, Prometheus text for all but the canary at /metrics
+pub fn health_routes(
+    queue: Arc<JobQueue>,
+    artifacts: Arc<ArtifactStore>,
+    canary: Arc<CanaryState>,
+    protocol: Arc<ProtocolIndexer>,
+) -> Router {
+    Router::new()
+        .route("/admin/queue/stats", get(queue_stats))
+        .route("/admin/artifacts/stats", get(artifact_stats))
+        .route("/admin/canary", get(canary_status))
+        .route("/stats/protocol", get(protocol_stats))
+        .route("/metrics", get(metrics))
+        .with_state(HealthState { queue, artifacts, canary, protocol })
+}
+
+// 503 once the canary halted the node, so load balancers stop routing jobs to it
+async fn canary_status(State(state): State<HealthState>) -> impl IntoResponse {
+    let status = state.canary.status();
+    let code = if status.halted.is_some() { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK };
+    (code, Json(serde_json::to_value(status).unwrap_or_default()))
+}
+
+async fn queue_stats(State(state): State<HealthState>) -> impl IntoResponse {
+    match state.queue.stats() {
+        Ok(stats) => (StatusCode::OK, Json(serde_json::to_value(stats).unwrap_or_default())),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e.to_string() }))),
+    }
+}
+
+async fn artifact_stats(State(state): State<HealthState>) -> impl IntoResponse {
+    match state.artifacts.stats() {
+        Ok(stats) => (StatusCode::OK, Json(serde_json::to_value(stats).unwrap_or_default())),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e.to_string() }))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProtocolStatsQuery {
+    /// Seconds of history to cover, 0 for all of it; the node's configured window when unset
+    window: Option<u64>,
+}
+
+async fn protocol_stats(
+    State(state): State<HealthState>,
+    Query(query): Query<ProtocolStatsQuery>,
+) -> impl IntoResponse {
+    match state.protocol.stats(chrono::Utc::now().timestamp() as u64, query.window) {
+        Ok(stats) => (StatusCode::OK, Json(serde_json::to_value(stats).unwrap_or_default())),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e.to_string() }))),
+    }
+}
+
+async fn metrics(State(state): State<HealthState>) -> impl IntoResponse {
+    let exposition = state.queue.stats()
+        .and_then(|queue| Ok(
+            queue.to_prometheus()
+                + &state.artifacts.stats()?.to_prometheus()
+                + &state.protocol.stats(chrono::Utc::now().timestamp() as u64, None)?.to_prometheus()
+        ));
+    match exposition {
+        Ok(text) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            text,
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(header::CONTENT_TYPE, "text/plain")],
+            e.to_string(),
+        ),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct CertusJobRecord {
     job_id: String, // bytes32 on chain
-    python_code: String,
+    python_code: Option<String>,
+    wasm_b64: Option<String>,
+    #[serde(default)]
+    wat: Option<String>,
+    compile_options: CompileOptions,
     input: serde_json::Value,
     tx_hash: Option<String>,
     output_hash: Option<String>,
@@ -72,23 +348,33 @@ enum CertusJobStatus {
     Fraudulent,   // fraud proven
 }
 
+/// Exactly one of `python_code`, `wasm_b64` and `wat` must be set
 #[derive(Debug, Deserialize)]
 struct SubmitJobRequest {
-    python_code: String,
+    python_code: Option<String>,
+    wasm_b64: Option<String>, // base64 module compiled from Rust, C, ...
+    wat: Option<String>,      // text of a hand-written module; needs the wat feature
     input: serde_json::Value,
     payment_amount: String, // payment amount in token units (e.g., USDC with 6 decimals)
     pay_token: String,      // ERC20 token address (USDC/USDT/DAI)
+    webhook_url: Option<String>, // receives the signed receipt once the job finalizes
+    profile: Option<String>, // execution profile; the default one when unset
+    #[serde(default)]
+    compile_options: CompileOptions, // how python_code is compiled
 }
 
 #[derive(Debug, Serialize)]
 struct SubmitJobResponse {
     job_id: String,
     tx_hash: String,
+    wasm_hash: String,
+    // false when the job reuses a module already in the on-chain registry
+    wasm_uploaded: bool,
     escrow_address: String,
     jobs_address: String,
 }
 
-/// Submit Python job to Certus
+/// Submit a Python or raw Wasm job to Certus
 async fn submit_python_job(
     State(state): State<Arc<ApiServer>>,
     Json(req): Json<SubmitJobRequest>,
@@ -105,23 +391,54 @@ async fn submit_python_job(
         Err(_) => return (StatusCode::BAD_REQUEST, "Invalid token address").into_response(),
     };
 
+    if let Some(url) = &req.webhook_url {
+        let valid = reqwest::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"));
+        if !valid {
+            return (StatusCode::BAD_REQUEST, "Invalid webhook_url").into_response();
+        }
+    }
+
+    let profile = match state.certus.profiles().get(req.profile.as_deref()) {
+        Ok(profile) => profile.clone(),
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    let input = serde_json::to_string(&req.input).unwrap();
+    let submitted = match (&req.python_code, &req.wasm_b64, &req.wat) {
+        (Some(code), None, None) => state.certus.create_python_job(code, &input, payment, pay_token, &profile, req.compile_options).await,
+        (None, Some(wasm_b64), None) => {
+            let wasm = match BASE64.decode(wasm_b64) {
+                Ok(w) => w,
+                Err(_) => return (StatusCode::BAD_REQUEST, "Invalid wasm_b64").into_response(),
+            };
+            state.certus.create_wasm_job(&wasm, &input, payment, pay_token, &profile).await
+        }
+        (None, None, Some(wat)) => {
+            let wasm = match crate::assemble_wat(wat) {
+                Ok(w) => w,
+                Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+            };
+            state.certus.create_wasm_job(&wasm, &input, payment, pay_token, &profile).await
+        }
+        _ => return (StatusCode::BAD_REQUEST, "Provide exactly one of python_code, wasm_b64 and wat").into_response(),
+    };
+
     // Submit to Certus contracts with token parameter
-    match state.certus.create_python_job(
-        &req.python_code,
-        &serde_json::to_string(&req.input).unwrap(),
-        payment,
-        pay_token,
-    ).await {
-        Ok(tx_hash) => {
-            // generate job ID (would get from tx receipt)
-            let job_id = format!("0x{}", hex::encode(sha2::Sha256::digest(
-                format!("{}{}", req.python_code, req.input).as_bytes()
-            )));
+    match submitted {
+        Ok(submitted) => {
+            let job_id = format!("0x{}", hex::encode(submitted.job_id));
+            let tx_hash = submitted.tx_hash;
+            if let Err(e) = state.receipts.track(&job_id, req.webhook_url) {
+                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+            }
 
             // store locally
             let record = CertusJobRecord {
                 job_id: job_id.clone(),
                 python_code: req.python_code,
+                wasm_b64: req.wasm_b64,
+                wat: req.wat,
+                compile_options: req.compile_options,
                 input: req.input,
                 tx_hash: Some(format!("{:?}", tx_hash)),
                 output_hash: None,
@@ -134,6 +451,8 @@ async fn submit_python_job(
             Json(SubmitJobResponse {
                 job_id,
                 tx_hash: format!("{:?}", tx_hash),
+                wasm_hash: format!("0x{}", hex::encode(submitted.wasm_hash)),
+                wasm_uploaded: submitted.wasm_tx.is_some(),
                 escrow_address: format!("{:?}", state.certus.escrow_contract),
                 jobs_address: format!("{:?}", state.certus.jobs_contract),
             }).into_response()
@@ -165,13 +484,27 @@ async fn execute_job(
                 record.output_hash = Some(result.output_hash.clone());
                 record.status = CertusJobStatus::Executed;
             }
+            if let Err(e) = state.receipts.record_execution(&id, &result.output_hash, result.fuel_used) {
+                log::warn!("Could not record execution of job {} for its receipt: {}", id, e);
+            }
 
             Json(result).into_response()
         }
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        Err(e) => job_failure(e),
     }
 }
 
+// A job the executor refused is the client's to fix; anything else, a trap included, is a
+// failed execution. `kind` says which, when the executor knew.
+fn job_failure(e: anyhow::Error) -> Response {
+    let kind = ExecutorError::of(&e);
+    let status = match kind {
+        Some(ExecutorError::ValidationFailed { .. } | ExecutorError::CompileError { .. }) => StatusCode::UNPROCESSABLE_ENTITY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(serde_json::json!({ "error": e.to_string(), "kind": kind }))).into_response()
+}
+
 /// Verify job (via Certus verifier flow)
 async fn verify_job(
     State(state): State<Arc<ApiServer>>,
@@ -218,6 +551,67 @@ async fn get_job(
     }
 }
 
+/// Signed receipt of a finalized job; the same payload the client's webhook receives
+async fn get_receipt(
+    State(state): State<Arc<ApiServer>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.receipts.receipt(&id) {
+        Ok(Some(receipt)) => Json(receipt).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "No receipt: job unknown or not finalized").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Every status update broadcast for the job, each chained to the one before. The chain is
+/// checked with `websocket::verify_history` before it is served, as clients check it again.
+async fn get_history(
+    State(state): State<Arc<ApiServer>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let Some(history) = &state.history else {
+        return (StatusCode::NOT_FOUND, "This node keeps no job history").into_response();
+    };
+    match history.history_of(&id) {
+        Ok(updates) if updates.is_empty() => (StatusCode::NOT_FOUND, "No updates recorded for job").into_response(),
+        Ok(updates) => match verify_history(&id, &updates) {
+            Ok(()) => Json(serde_json::json!({ "job_id": id, "updates": updates })).into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Recorded history does not verify: {}", e)).into_response(),
+        },
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// The build this node runs, signed with its executor key so peers can check it against their allowlist
+async fn get_status(State(state): State<Arc<ApiServer>>) -> impl IntoResponse {
+    match state.certus.build_report() {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// The job's output and printed lines, fetched from IPFS and checked against the on-chain hash
+async fn get_output(
+    State(state): State<Arc<ApiServer>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let job_id_bytes = match receipts::parse_bytes32(&id) {
+        Ok(arr) => arr,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid job ID").into_response(),
+    };
+    match state.certus.job_output(job_id_bytes).await {
+        Ok(Some(blob)) => Json(serde_json::json!({
+            "job_id": receipts::normalize_job_id(&id),
+            "output_hash": blob.hash(),
+            "cid": blob.cid(),
+            "output": blob.output,
+            "stdout": blob.stdout,
+        })).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "No output: job unknown or no receipt yet").into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+    }
+}
+
 /// List all jobs
 async fn list_jobs(
     State(state): State<Arc<ApiServer>>,
@@ -228,6 +622,11 @@ async fn list_jobs(
     Json(job_list)
 }
 
+/// The supported Python subset, so clients can check code before paying to submit it
+async fn get_capabilities() -> impl IntoResponse {
+    Json(crate::compiler::capabilities::CAPABILITIES)
+}
+
 /// Get example scripts
 async fn get_examples() -> impl IntoResponse {
     Json(serde_json::json!([
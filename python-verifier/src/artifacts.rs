@@ -0,0 +1,420 @@
+// Disk quotas for executor artifacts: fetched modules and inputs, compiled-module caches
+// and execution traces. Each class is capped in bytes; a write that would pass the cap
+// first prunes that class's least recently used files. Artifacts a receipt commits to are
+// pinned until the job's dispute window closes and are never pruned before then.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+const PINS_FILE: &str = "pins.json";
+const TMP_SUFFIX: &str = ".tmp";
+const MAX_KEY_LEN: usize = 128;
+const MIB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactClass {
+    Wasm,
+    Input,
+    AotCache,
+    Trace,
+}
+
+impl ArtifactClass {
+    pub const ALL: [ArtifactClass; 4] = [Self::Wasm, Self::Input, Self::AotCache, Self::Trace];
+
+    /// Subdirectory and metric label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Wasm => "wasm",
+            Self::Input => "input",
+            Self::AotCache => "aot_cache",
+            Self::Trace => "trace",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactQuotas {
+    pub wasm_bytes: u64,
+    pub input_bytes: u64,
+    pub aot_cache_bytes: u64,
+    pub trace_bytes: u64,
+    /// Below this much free space on the artifact volume, unpinned artifacts of every class are pruned
+    pub min_free_bytes: u64,
+}
+
+impl Default for ArtifactQuotas {
+    fn default() -> Self {
+        Self {
+            wasm_bytes: 512 * MIB,
+            input_bytes: 1024 * MIB,
+            aot_cache_bytes: 2048 * MIB,
+            trace_bytes: 4096 * MIB,
+            min_free_bytes: 1024 * MIB,
+        }
+    }
+}
+
+impl ArtifactQuotas {
+    pub fn cap(&self, class: ArtifactClass) -> u64 {
+        match class {
+            ArtifactClass::Wasm => self.wasm_bytes,
+            ArtifactClass::Input => self.input_bytes,
+            ArtifactClass::AotCache => self.aot_cache_bytes,
+            ArtifactClass::Trace => self.trace_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClassUsage {
+    pub bytes: u64,
+    pub files: u64,
+    pub quota_bytes: u64,
+    pub pinned_bytes: u64,
+    pub pruned_total: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactStats {
+    pub classes: BTreeMap<String, ClassUsage>,
+    pub free_bytes: u64,
+    pub min_free_bytes: u64,
+    pub low_space: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub removed_files: u64,
+    pub removed_bytes: u64,
+    pub expired_pins: u64,
+    pub free_bytes: u64,
+    /// Free space is still under the minimum: what is left is pinned
+    pub low_space: bool,
+}
+
+type ArtifactId = (ArtifactClass, String);
+
+#[derive(Serialize, Deserialize)]
+struct Pin {
+    class: ArtifactClass,
+    key: String,
+    until: u64,
+}
+
+struct Entry {
+    size: u64,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Index {
+    entries: BTreeMap<ArtifactId, Entry>,
+    pins: BTreeMap<ArtifactId, u64>,
+    pruned: BTreeMap<ArtifactClass, u64>,
+    // LRU order; seeded from file mtimes on open
+    clock: u64,
+}
+
+impl Index {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn pinned(&self, id: &ArtifactId, now: u64) -> bool {
+        self.pins.get(id).is_some_and(|&until| until > now)
+    }
+
+    fn usage(&self, class: ArtifactClass) -> u64 {
+        self.entries.iter().filter(|((c, _), _)| *c == class).map(|(_, e)| e.size).sum()
+    }
+
+    // Unpinned artifacts, least recently used first
+    fn eviction_order(&self, class: Option<ArtifactClass>, now: u64) -> Vec<ArtifactId> {
+        let mut candidates: Vec<(&ArtifactId, &Entry)> = self.entries.iter()
+            .filter(|(id, _)| class.is_none_or(|c| id.0 == c) && !self.pinned(id, now))
+            .collect();
+        candidates.sort_by_key(|(_, e)| e.last_used);
+        candidates.into_iter().map(|(id, _)| id.clone()).collect()
+    }
+}
+
+pub struct ArtifactStore {
+    root: PathBuf,
+    quotas: ArtifactQuotas,
+    index: Mutex<Index>,
+}
+
+impl ArtifactStore {
+    /// Open or create a store, indexing artifacts left by a previous run
+    pub fn open(root: impl AsRef<Path>, quotas: ArtifactQuotas) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let mut found = Vec::new();
+        for class in ArtifactClass::ALL {
+            let dir = root.join(class.as_str());
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("creating artifact directory {}", dir.display()))?;
+
+            for file in std::fs::read_dir(&dir)? {
+                let file = file?;
+                let name = file.file_name().to_string_lossy().into_owned();
+                // interrupted writes
+                if name.ends_with(TMP_SUFFIX) {
+                    let _ = std::fs::remove_file(file.path());
+                    continue;
+                }
+                let meta = file.metadata()?;
+                if meta.is_file() {
+                    found.push((meta.modified().unwrap_or(SystemTime::UNIX_EPOCH), class, name, meta.len()));
+                }
+            }
+        }
+
+        let mut index = Index::default();
+        found.sort_by_key(|(modified, ..)| *modified);
+        for (_, class, key, size) in found {
+            let last_used = index.tick();
+            index.entries.insert((class, key), Entry { size, last_used });
+        }
+
+        let pins_path = root.join(PINS_FILE);
+        if pins_path.exists() {
+            let pins: Vec<Pin> = serde_json::from_slice(&std::fs::read(&pins_path)?)
+                .context("corrupt artifact pins file")?;
+            index.pins = pins.into_iter().map(|p| ((p.class, p.key), p.until)).collect();
+        }
+
+        Ok(Self { root, quotas, index: Mutex::new(index) })
+    }
+
+    /// Store an artifact, pruning the class's least recently used unpinned files to make room
+    pub fn put(&self, class: ArtifactClass, key: &str, data: &[u8]) -> Result<()> {
+        validate_key(key)?;
+        let size = data.len() as u64;
+        let cap = self.quotas.cap(class);
+        if size > cap {
+            bail!("{} artifact of {} bytes exceeds the {} byte quota", class.as_str(), size, cap);
+        }
+
+        let mut index = self.index.lock().unwrap();
+        let id = (class, key.to_string());
+        let replaced = index.entries.get(&id).map_or(0, |e| e.size);
+        let now = unix_now();
+
+        let mut usage = index.usage(class) - replaced;
+        for victim in index.eviction_order(Some(class), now) {
+            if usage + size <= cap {
+                break;
+            }
+            if victim != id {
+                usage -= self.remove(&mut index, &victim)?;
+            }
+        }
+        if usage + size > cap {
+            bail!("{} quota exhausted: {} of {} bytes are pinned", class.as_str(), usage, cap);
+        }
+
+        // write then rename, so a crash never leaves a truncated artifact under its real name
+        let path = self.path(class, key);
+        let tmp = path.with_file_name(format!("{}{}", key, TMP_SUFFIX));
+        std::fs::write(&tmp, data).with_context(|| format!("writing {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)?;
+
+        let last_used = index.tick();
+        index.entries.insert(id, Entry { size, last_used });
+        Ok(())
+    }
+
+    pub fn get(&self, class: ArtifactClass, key: &str) -> Result<Option<Vec<u8>>> {
+        validate_key(key)?;
+        let mut index = self.index.lock().unwrap();
+        let last_used = index.tick();
+        let Some(entry) = index.entries.get_mut(&(class, key.to_string())) else {
+            return Ok(None);
+        };
+        entry.last_used = last_used;
+
+        // mtime carries the LRU order across restarts
+        let path = self.path(class, key);
+        let data = std::fs::read(&path)?;
+        std::fs::File::options().write(true).open(&path)?.set_modified(SystemTime::now())?;
+        Ok(Some(data))
+    }
+
+    /// Keep an artifact until `until` (unix seconds); pinning an absent key protects it once stored
+    pub fn pin(&self, class: ArtifactClass, key: &str, until: u64) -> Result<()> {
+        validate_key(key)?;
+        let mut index = self.index.lock().unwrap();
+        let pin = index.pins.entry((class, key.to_string())).or_insert(0);
+        *pin = (*pin).max(until);
+        self.save_pins(&index)
+    }
+
+    /// Drop expired pins, bring every class under its quota, then prune across classes while
+    /// free disk space is below the minimum
+    pub fn prune(&self) -> Result<PruneReport> {
+        let mut index = self.index.lock().unwrap();
+        let now = unix_now();
+        let mut report = PruneReport::default();
+
+        let before = index.pins.len();
+        index.pins.retain(|_, until| *until > now);
+        report.expired_pins = (before - index.pins.len()) as u64;
+        if report.expired_pins > 0 {
+            self.save_pins(&index)?;
+        }
+
+        for class in ArtifactClass::ALL {
+            let cap = self.quotas.cap(class);
+            let mut usage = index.usage(class);
+            for victim in index.eviction_order(Some(class), now) {
+                if usage <= cap {
+                    break;
+                }
+                let size = self.remove(&mut index, &victim)?;
+                usage -= size;
+                report.removed_files += 1;
+                report.removed_bytes += size;
+            }
+        }
+
+        let mut free = self.free_bytes()?;
+        if free < self.quotas.min_free_bytes {
+            let mut freed = 0;
+            for victim in index.eviction_order(None, now) {
+                if free + freed >= self.quotas.min_free_bytes {
+                    break;
+                }
+                let size = self.remove(&mut index, &victim)?;
+                freed += size;
+                report.removed_files += 1;
+                report.removed_bytes += size;
+            }
+            free = self.free_bytes()?;
+        }
+
+        report.free_bytes = free;
+        report.low_space = free < self.quotas.min_free_bytes;
+        if report.low_space {
+            log::warn!(
+                "artifact volume low on space: {} bytes free, minimum {}; remaining artifacts are pinned",
+                free, self.quotas.min_free_bytes
+            );
+        }
+        Ok(report)
+    }
+
+    pub fn stats(&self) -> Result<ArtifactStats> {
+        let index = self.index.lock().unwrap();
+        let now = unix_now();
+
+        let mut classes = BTreeMap::new();
+        for class in ArtifactClass::ALL {
+            let mut usage = ClassUsage {
+                quota_bytes: self.quotas.cap(class),
+                pruned_total: index.pruned.get(&class).copied().unwrap_or(0),
+                ..Default::default()
+            };
+            for (id, entry) in index.entries.iter().filter(|((c, _), _)| *c == class) {
+                usage.bytes += entry.size;
+                usage.files += 1;
+                if index.pinned(id, now) {
+                    usage.pinned_bytes += entry.size;
+                }
+            }
+            classes.insert(class.as_str().to_string(), usage);
+        }
+
+        let free_bytes = self.free_bytes()?;
+        Ok(ArtifactStats {
+            classes,
+            free_bytes,
+            min_free_bytes: self.quotas.min_free_bytes,
+            low_space: free_bytes < self.quotas.min_free_bytes,
+        })
+    }
+
+    fn path(&self, class: ArtifactClass, key: &str) -> PathBuf {
+        self.root.join(class.as_str()).join(key)
+    }
+
+    fn free_bytes(&self) -> Result<u64> {
+        fs2::available_space(&self.root)
+            .with_context(|| format!("reading free space of {}", self.root.display()))
+    }
+
+    // Returns the bytes released
+    fn remove(&self, index: &mut Index, id: &ArtifactId) -> Result<u64> {
+        let Some(entry) = index.entries.remove(id) else {
+            return Ok(0);
+        };
+        match std::fs::remove_file(self.path(id.0, &id.1)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        *index.pruned.entry(id.0).or_default() += 1;
+        log::debug!("pruned {} artifact {} ({} bytes)", id.0.as_str(), id.1, entry.size);
+        Ok(entry.size)
+    }
+
+    fn save_pins(&self, index: &Index) -> Result<()> {
+        let pins: Vec<Pin> = index.pins.iter()
+            .map(|((class, key), &until)| Pin { class: *class, key: key.clone(), until })
+            .collect();
+        let path = self.root.join(PINS_FILE);
+        let tmp = self.root.join(format!("{}{}", PINS_FILE, TMP_SUFFIX));
+        std::fs::write(&tmp, serde_json::to_vec(&pins)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+impl ArtifactStats {
+    /// Prometheus text exposition
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+            out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+            for (labels, value) in samples {
+                out.push_str(&format!("{}{} {}\n", name, labels, value));
+            }
+        };
+
+        let per_class = |f: fn(&ClassUsage) -> u64| -> Vec<(String, String)> {
+            self.classes.iter()
+                .map(|(class, usage)| (format!("{{class=\"{}\"}}", class), f(usage).to_string()))
+                .collect()
+        };
+        let plain = |v: u64| vec![(String::new(), v.to_string())];
+        metric("certus_artifact_bytes", "gauge", "Bytes stored per artifact class", per_class(|u| u.bytes));
+        metric("certus_artifact_files", "gauge", "Files stored per artifact class", per_class(|u| u.files));
+        metric("certus_artifact_quota_bytes", "gauge", "Configured quota per artifact class", per_class(|u| u.quota_bytes));
+        metric("certus_artifact_pinned_bytes", "gauge", "Bytes pinned for open dispute windows", per_class(|u| u.pinned_bytes));
+        metric("certus_artifact_pruned_total", "counter", "Artifacts pruned to stay within quota or free space", per_class(|u| u.pruned_total));
+        metric("certus_artifact_disk_free_bytes", "gauge", "Free space on the artifact volume", plain(self.free_bytes));
+        metric("certus_artifact_disk_low", "gauge", "1 while free space is below the configured minimum", plain(self.low_space as u64));
+
+        out
+    }
+}
+
+// Keys become file names: content hashes and job ids, never paths
+fn validate_key(key: &str) -> Result<()> {
+    let valid = !key.is_empty()
+        && key.len() <= MAX_KEY_LEN
+        && key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+    if !valid {
+        bail!("invalid artifact key: {:?}", key);
+    }
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp() as u64
+}
@@ -0,0 +1,142 @@
+// Determinism canary. A fixed program whose output hash is committed below is compiled and run
+// on this node from time to time; a different hash means the compiler or the Wasm runtime no
+// longer behaves like the one every other executor and verifier runs, so the node must stop
+// taking jobs before it posts a receipt the network would call fraud.
+
+use anyhow::{Context, Result, bail};
+use std::sync::Mutex;
+use wasmtime::*;
+
+use crate::compiler::STDOUT_EXPORT;
+use crate::{captured_stdout, output_hash};
+
+/// Touches the integer, string, container, hashing, exception and print paths of the compiler
+pub const CANARY_SOURCE: &str = r#"import hashlib
+
+def fib(n):
+    if n < 2:
+        return n
+    return fib(n - 1) + fib(n - 2)
+
+squares = [i * i for i in range(12) if i % 3 != 1]
+last_by_residue = {}
+for sq in squares:
+    last_by_residue[sq % 7] = sq
+ordered = sorted([-5, 17, 3, -11, 8])
+label = "canary-" + str(fib(15))
+digest = hashlib.sha256(label.encode()).digest()
+try:
+    broken = 1 // (len(label) - len(label))
+except ZeroDivisionError:
+    broken = -1
+print(label, len(squares))
+print(ordered[0], ordered[-1], pow(7, 123, 1009))
+OUTPUT = sum(squares) * 31 + last_by_residue[2] + digest[0] * 257 + digest[31] + broken - 17 % 5
+"#;
+
+/// Output hash of CANARY_SOURCE as CPython runs it: OUTPUT 12522, two printed lines
+pub const CANARY_OUTPUT_HASH: &str = "18a83f59192a8395ae7c41395c513fd29e4105b27034a315009199cfa0e11cd6";
+
+const CANARY_FUEL: u64 = 10_000_000;
+
+/// What one canary run produced
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CanaryRun {
+    pub output: i32,
+    pub stdout: Vec<String>,
+    pub output_hash: String,
+    pub fuel_consumed: u64,
+}
+
+impl CanaryRun {
+    pub fn matches(&self) -> bool {
+        self.output_hash == CANARY_OUTPUT_HASH
+    }
+}
+
+/// Run a compiled canary's `main` export on `engine`
+pub fn run(engine: &Engine, wasm: &[u8]) -> Result<CanaryRun> {
+    let module = Module::new(engine, wasm)?;
+    let memory_ty = module
+        .imports()
+        .find_map(|import| match (import.module(), import.name(), import.ty()) {
+            ("env", "memory", ExternType::Memory(ty)) => Some(ty),
+            _ => None,
+        })
+        .context("module does not import env.memory")?;
+
+    let mut store = Store::new(engine, ());
+    store.set_fuel(CANARY_FUEL)?;
+    store.set_epoch_deadline(100);
+    let memory = Memory::new(&mut store, memory_ty)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let output = main.call(&mut store, ()).context("canary trapped")?;
+
+    let stdout = match instance.get_global(&mut store, STDOUT_EXPORT) {
+        Some(global) => {
+            let base = global.get(&mut store).i32().context("stdout export is not an i32")?;
+            captured_stdout(memory.data(&store), base as u32)
+        }
+        None => Vec::new(),
+    };
+
+    Ok(CanaryRun {
+        output_hash: output_hash(&output.to_string(), &stdout),
+        output,
+        stdout,
+        fuel_consumed: CANARY_FUEL - store.get_fuel()?,
+    })
+}
+
+/// Outcome of the latest check; once tripped the node stays halted until restarted
+#[derive(Default)]
+pub struct CanaryState {
+    inner: Mutex<CanaryStatus>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct CanaryStatus {
+    pub checks: u64,
+    /// Unix seconds of the latest check
+    pub last_checked_at: Option<u64>,
+    pub last_run: Option<CanaryRun>,
+    /// Why the node stopped accepting jobs
+    pub halted: Option<String>,
+}
+
+impl CanaryState {
+    /// Record a check; a failed or mismatching run halts the node
+    pub fn record(&self, run: Result<CanaryRun>) -> Result<()> {
+        let mut status = self.inner.lock().unwrap();
+        status.checks += 1;
+        status.last_checked_at = Some(chrono::Utc::now().timestamp() as u64);
+        let failure = match run {
+            Ok(run) if run.matches() => {
+                status.last_run = Some(run);
+                return Ok(());
+            }
+            Ok(run) => {
+                let failure = format!("canary output hash {} != reference {}", run.output_hash, CANARY_OUTPUT_HASH);
+                status.last_run = Some(run);
+                failure
+            }
+            Err(e) => format!("canary failed to run: {:#}", e),
+        };
+        status.halted.get_or_insert(failure.clone());
+        bail!(failure)
+    }
+
+    /// Err while halted, for every path that would take on a job
+    pub fn ensure_healthy(&self) -> Result<()> {
+        match &self.inner.lock().unwrap().halted {
+            Some(reason) => bail!("node halted by determinism canary: {}", reason),
+            None => Ok(()),
+        }
+    }
+
+    pub fn status(&self) -> CanaryStatus {
+        self.inner.lock().unwrap().clone()
+    }
+}
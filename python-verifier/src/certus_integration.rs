@@ -2,24 +2,42 @@ use anyhow::{Result, Context, bail};
 use ethers::prelude::*;
 use ethers::abi::{encode, decode, Token, ParamType};
 use ethers::signers::Signer as EthersSigner;
-use std::sync::{Arc, Mutex};
-use crate::PythonExecutor;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, RwLock};
+use crate::{ExecutionOutput, PythonExecutor, MAX_WASM_STACK};
+use crate::pool::ExecutorPool;
+use crate::compiler::CompileOptions;
 use crate::reliability::{retry_with_backoff, RetryConfig, validate_address};
+use crate::artifacts::{ArtifactClass, ArtifactStore};
+use crate::receipts::{Finalization, SignedReceipt};
+use crate::indexer::{IndexedEvent, INDEXED_EVENTS};
+use crate::profiles::{ExecutionProfile, ProfileSet};
+use crate::chain_params::{ChainParams, SYNCED_GETTERS};
+use crate::outputs::{OutputBlob, OutputStore};
+use crate::canary::CanaryState;
+use crate::provenance::{BuildInfo, BuildReport};
 use ed25519_dalek::Signer;
 
 /// Integrates Python execution with Certus protocol contracts
 pub struct CertusIntegration {
-    executor: Arc<Mutex<PythonExecutor>>,
+    executor: Arc<ExecutorPool>,
     pub escrow_contract: H160,
     pub jobs_contract: H160,
     provider: Arc<Provider<Http>>,
     signer: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
     wallet: LocalWallet,
+    artifacts: Option<Arc<ArtifactStore>>,
+    profiles: Arc<ProfileSet>,
+    params: RwLock<ChainParams>,
+    // Module hashes seen in the on-chain registry; registrations are permanent
+    known_wasm: Mutex<HashSet<[u8; 32]>>,
+    outputs: Option<Arc<OutputStore>>,
+    canary: Arc<CanaryState>,
 }
 
 impl CertusIntegration {
     pub async fn new(
-        executor: Arc<Mutex<PythonExecutor>>,
+        executor: Arc<ExecutorPool>,
         rpc_url: &str,
         private_key: &str,
         escrow_addr: &str,
@@ -54,9 +72,75 @@ impl CertusIntegration {
             provider: Arc::new(provider),
             signer,
             wallet,
+            artifacts: None,
+            profiles: Arc::new(ProfileSet::default()),
+            params: RwLock::new(ChainParams::default()),
+            known_wasm: Mutex::new(HashSet::new()),
+            outputs: None,
+            canary: Arc::new(CanaryState::default()),
         })
     }
 
+    /// Keep fetched modules and inputs on disk, pinned until each job's dispute window closes
+    pub fn with_artifacts(mut self, artifacts: Arc<ArtifactStore>) -> Self {
+        self.artifacts = Some(artifacts);
+        self
+    }
+
+    /// Execution profiles jobs are submitted under; chain jobs run under the default one
+    pub fn with_profiles(mut self, profiles: Arc<ProfileSet>) -> Self {
+        self.profiles = profiles;
+        self
+    }
+
+    /// Publish each output this executor produces and check those of jobs it verifies
+    pub fn with_outputs(mut self, outputs: Arc<OutputStore>) -> Self {
+        self.outputs = Some(outputs);
+        self
+    }
+
+    /// Refuse to execute jobs once this canary trips
+    pub fn with_canary(mut self, canary: Arc<CanaryState>) -> Self {
+        self.canary = canary;
+        self
+    }
+
+    pub fn profiles(&self) -> &ProfileSet {
+        &self.profiles
+    }
+
+    /// Protocol parameters as of the last sync
+    pub fn chain_params(&self) -> ChainParams {
+        self.params.read().unwrap().clone()
+    }
+
+    /// Re-read governance parameters from CertusJobs; returns what changed since the last sync
+    pub async fn sync_chain_params(&self) -> Result<Vec<String>> {
+        let mut values = [U256::zero(); SYNCED_GETTERS.len()];
+        for (value, getter) in values.iter_mut().zip(SYNCED_GETTERS) {
+            let calldata = ethers::utils::id(getter)[0..4].to_vec();
+            let result = retry_with_backoff(
+                || async {
+                    self.provider
+                        .call(&TransactionRequest::new().to(self.jobs_contract).data(calldata.clone()).into(), None)
+                        .await
+                        .map_err(Into::into)
+                },
+                &RetryConfig::default(),
+            ).await?;
+            if result.len() != 32 {
+                bail!("{} returned {} bytes", getter, result.len());
+            }
+            *value = U256::from_big_endian(&result);
+        }
+
+        let mut params = self.params.write().unwrap();
+        let synced = params.with_synced(values)?;
+        let changes = params.changes(&synced);
+        *params = synced;
+        Ok(changes)
+    }
+
     /// Submit Python job through CertusJobs contract
     pub async fn create_python_job(
         &self,
@@ -64,35 +148,64 @@ impl CertusIntegration {
         input: &str,
         payment: U256,
         pay_token: H160, // USDC/USDT/DAI address
-    ) -> Result<H256> {
+        profile: &ExecutionProfile,
+        options: CompileOptions,
+    ) -> Result<SubmittedJob> {
         // Validate payment amount (assuming 6 decimals for USDC)
-        if payment < U256::from(5_000_000u128) { // $5 minimum
-            bail!("payment too low: minimum $5 USDC");
-        }
+        self.chain_params().check_payment(payment)?;
 
         // Compile Python to Wasm with embedded interpreter
-        let wasm_bytes = self.compile_python_to_wasm(python_code).await?;
+        let wasm_bytes = self.compile_python_to_wasm(python_code, profile, options).await?;
+        self.submit_job(wasm_bytes, input, payment, pay_token, profile).await
+    }
 
-        // Verify size limit
-        if wasm_bytes.len() > 24 * 1024 {
-            bail!("wasm exceeds 24KB limit");
-        }
+    /// Submit a client-compiled Wasm job; it must pass the determinism policy and is metered before upload
+    pub async fn create_wasm_job(
+        &self,
+        wasm: &[u8],
+        input: &str,
+        payment: U256,
+        pay_token: H160,
+        profile: &ExecutionProfile,
+    ) -> Result<SubmittedJob> {
+        self.chain_params().check_payment(payment)?;
+
+        let wasm = wasm.to_vec();
+        let wasm_bytes = self.with_executor(move |executor| executor.prepare_wasm(&wasm)).await?;
+        Self::check_module_limits(&wasm_bytes, profile)?;
+        self.submit_job(wasm_bytes, input, payment, pay_token, profile).await
+    }
+
+    async fn submit_job(
+        &self,
+        wasm_bytes: Vec<u8>,
+        input: &str,
+        payment: U256,
+        pay_token: H160,
+        profile: &ExecutionProfile,
+    ) -> Result<SubmittedJob> {
+        // Verify the job is within what createJob accepts
+        let params = self.chain_params();
+        let input_bytes = input.as_bytes();
+        params.check_job(wasm_bytes.len(), input_bytes.len(), profile)?;
 
         let wasm_hash = self.hash_bytes(&wasm_bytes);
 
-        // prepare and validate input
-        let input_bytes = input.as_bytes();
-        if input_bytes.len() > 100 * 1024 {
-            bail!("input exceeds 100KB limit");
-        }
+        // modules are content-addressed on-chain; popular code is stored once and shared
+        let wasm_tx = if self.wasm_registered(wasm_hash).await? {
+            log::info!("wasm {} already registered, reusing it", hex::encode(wasm_hash));
+            None
+        } else {
+            Some(self.register_wasm(&wasm_bytes).await?)
+        };
 
         let input_hash = self.hash_bytes(input_bytes);
 
         // generate job ID
         let job_id = self.compute_job_id(wasm_hash, input_hash, self.signer.address());
 
-        // calculate client deposit (5% of payment, min $5, max $1000)
-        let client_deposit = self.calculate_client_deposit(payment);
+        // calculate client deposit at the contract's current rate and bounds
+        let client_deposit = params.client_deposit(payment);
         let total_payment = payment + client_deposit;
 
         // approve token transfer
@@ -105,11 +218,11 @@ impl CertusIntegration {
             input_hash,
             pay_token,
             payment,
-            3600, // accept window
-            3600, // challenge window
-            100_000, // fuel limit
-            1_000_000, // mem limit
-            1024 * 100, // max output size
+            params.accept_window,
+            params.challenge_window,
+            profile.fuel_limit,
+            profile.mem_limit(),
+            profile.max_output_bytes,
         )?;
 
         // submit with retry
@@ -136,11 +249,13 @@ impl CertusIntegration {
             &RetryConfig::default(),
         ).await?;
 
-        Ok(tx.transaction_hash)
+        Ok(SubmittedJob { job_id, tx_hash: tx.transaction_hash, wasm_hash, wasm_tx })
     }
 
     /// Execute job as executor following Certus protocol flow
     pub async fn execute_job(&self, job_id: [u8; 32]) -> Result<ExecutionResult> {
+        self.canary.ensure_healthy()?;
+
         // Step 1: Fetch job details from chain
         let job = self.fetch_job_from_chain(job_id).await?;
 
@@ -148,16 +263,20 @@ impl CertusIntegration {
         let accept_tx = self.accept_job(job_id, job.pay_token, job.pay_amount).await?;
         log::info!("Job accepted with 2x collateral: {}", accept_tx);
 
-        // Step 3: Retrieve wasm and input data
-        let wasm = self.fetch_wasm(job.wasm_hash).await?;
+        // Step 3: Retrieve wasm and input data; modules are content-addressed, so a stored copy is reused
+        let wasm = match self.stored_wasm(job.wasm_hash) {
+            Some(wasm) => wasm,
+            None => self.fetch_wasm(job.wasm_hash).await?,
+        };
         let input = self.fetch_input(job_id).await?;
+        if let Err(e) = self.retain_for_dispute(job_id, &job, &wasm, &input) {
+            log::warn!("could not retain artifacts for job {}: {}", hex::encode(job_id), e);
+        }
 
-        // Execute with mutex lock
-        let output = self.executor.lock().unwrap().execute(
-            &String::from_utf8(wasm)?,
-            &String::from_utf8(input)?,
-            job.fuel_limit,
-        )?;
+        let output = self.run_job(&job, &wasm, &input).await?;
+
+        // Step 4: Publish the output, so clients and verifiers can fetch it by its hash
+        let output_cid = self.publish_output(&output).await;
 
         // Step 5: Submit execution receipt with output hash
         let receipt_tx = self.submit_receipt(
@@ -169,11 +288,62 @@ impl CertusIntegration {
         Ok(ExecutionResult {
             job_id: hex::encode(job_id),
             output: output.result,
+            stdout: output.stdout,
             output_hash: output.output_hash,
+            fuel_used: output.fuel_consumed,
             receipt_tx: receipt_tx.to_string(),
+            output_cid,
         })
     }
 
+    // A failed upload is logged rather than failing the job: the receipt still binds the
+    // executor to the hash, and the output can be re-published later
+    async fn publish_output(&self, output: &ExecutionOutput) -> Option<String> {
+        let outputs = self.outputs.as_ref()?;
+        let blob = OutputBlob::new(&output.result, &output.stdout);
+        match retry_with_backoff(|| outputs.upload(&blob), &RetryConfig::default()).await {
+            Ok(cid) => Some(cid),
+            Err(e) => {
+                log::warn!("could not publish output {}: {}", output.output_hash, e);
+                None
+            }
+        }
+    }
+
+    /// The output a job's on-chain receipt commits to, fetched and checked against its hash;
+    /// `None` before a receipt is posted
+    pub async fn job_output(&self, job_id: [u8; 32]) -> Result<Option<OutputBlob>> {
+        let outputs = self.outputs.as_ref().context("no output store configured")?;
+        let receipt = self.fetch_receipt(job_id).await?;
+        if receipt.output_hash == hex::encode([0u8; 32]) {
+            return Ok(None);
+        }
+        outputs.fetch(&receipt.output_hash).await.map(Some)
+    }
+
+    fn stored_wasm(&self, wasm_hash: [u8; 32]) -> Option<Vec<u8>> {
+        let artifacts = self.artifacts.as_ref()?;
+        match artifacts.get(ArtifactClass::Wasm, &hex::encode(wasm_hash)) {
+            Ok(wasm) => wasm,
+            Err(e) => {
+                log::warn!("could not read stored module {}: {}", hex::encode(wasm_hash), e);
+                None
+            }
+        }
+    }
+
+    // A fraud proof replays the module on the input, so both must outlive the dispute window
+    fn retain_for_dispute(&self, job_id: [u8; 32], job: &JobData, wasm: &[u8], input: &[u8]) -> Result<()> {
+        let Some(artifacts) = &self.artifacts else {
+            return Ok(());
+        };
+        let (wasm_key, input_key) = (hex::encode(job.wasm_hash), hex::encode(job_id));
+        artifacts.pin(ArtifactClass::Wasm, &wasm_key, job.finalize_deadline)?;
+        artifacts.pin(ArtifactClass::Input, &input_key, job.finalize_deadline)?;
+        artifacts.put(ArtifactClass::Wasm, &wasm_key, wasm)?;
+        artifacts.put(ArtifactClass::Input, &input_key, input)
+    }
+
     /// Accept job by depositing 2x collateral per Certus protocol
     async fn accept_job(&self, job_id: [u8; 32], pay_token: H160, pay_amount: U256) -> Result<H256> {
         // Calculate 2x collateral requirement
@@ -216,6 +386,34 @@ impl CertusIntegration {
         Ok(tx.transaction_hash)
     }
 
+    // Execute on a pooled executor; Python jobs store their source, foreign jobs a module.
+    // The limits the job was posted with bind, whatever the local default allows.
+    async fn run_job(&self, job: &JobData, wasm: &[u8], input: &[u8]) -> Result<ExecutionOutput> {
+        let profile = self.profiles.default_profile()
+            .with_chain_limits(job.fuel_limit, job.mem_limit, job.max_output_size);
+        let (wasm, input) = (wasm.to_vec(), input.to_vec());
+        self.with_executor(move |executor| {
+            let input = std::str::from_utf8(&input)?;
+            if wasm.starts_with(b"\0asm") {
+                executor.execute_wasm_with_profile(&wasm, input, &profile)
+            } else {
+                executor.execute_with_profile(std::str::from_utf8(&wasm)?, input, &profile)
+            }
+        }).await
+    }
+
+    // Run `work` on a pooled executor off the async runtime: checking one out waits while all
+    // are busy, and a run holds its thread until it returns
+    async fn with_executor<T: Send + 'static>(
+        &self,
+        work: impl FnOnce(&mut PythonExecutor) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let pool = self.executor.clone();
+        tokio::task::spawn_blocking(move || work(&mut pool.get()))
+            .await
+            .context("executor task failed")?
+    }
+
     /// Verify job as verifier
     pub async fn verify_job(&self, job_id: [u8; 32]) -> Result<VerificationResult> {
         // fetch job and receipt
@@ -226,15 +424,23 @@ impl CertusIntegration {
         let wasm = self.fetch_wasm(job.wasm_hash).await?;
         let input = self.fetch_input(job_id).await?;
 
-        let output = self.executor.lock().unwrap().execute(
-            &String::from_utf8(wasm.clone())?,
-            &String::from_utf8(input.clone())?,
-            job.fuel_limit,
-        )?;
+        let output = self.run_job(&job, &wasm, &input).await?;
 
         // check if matches
         let matches = output.output_hash == receipt.output_hash;
 
+        // an honest hash whose preimage was never published leaves the client without its output
+        let output_available = match &self.outputs {
+            Some(outputs) if matches => Some(match outputs.fetch(&receipt.output_hash).await {
+                Ok(blob) => blob.output == output.result && blob.stdout == output.stdout,
+                Err(e) => {
+                    log::warn!("output of job {} is not retrievable: {}", hex::encode(job_id), e);
+                    false
+                }
+            }),
+            _ => None,
+        };
+
         if !matches {
             // submit fraud proof via CertusEscrow
             let fraud_tx = self.submit_fraud_proof(
@@ -249,6 +455,7 @@ impl CertusIntegration {
                 verified: false,
                 fraud_detected: true,
                 fraud_tx: Some(fraud_tx.to_string()),
+                output_available,
             })
         } else {
             Ok(VerificationResult {
@@ -256,6 +463,7 @@ impl CertusIntegration {
                 verified: true,
                 fraud_detected: false,
                 fraud_tx: None,
+                output_available,
             })
         }
     }
@@ -318,21 +526,34 @@ impl CertusIntegration {
     }
 
     /// Compile Python to deterministic Wasm module
-    async fn compile_python_to_wasm(&self, code: &str) -> Result<Vec<u8>> {
-        // Validate determinism constraints
-        self.executor.lock().unwrap().validate_python(code)?;
-
-        // Compile to Wasm bytecode
-        let mut compiler = crate::compiler::PythonCompiler::new();
-        let wasm_module = compiler.compile(code)?;
+    async fn compile_python_to_wasm(&self, code: &str, profile: &ExecutionProfile, options: CompileOptions) -> Result<Vec<u8>> {
+        // Validate determinism constraints, then compile with the executor's own compiler so
+        // the uploaded module is the one it runs
+        let (code, compile_profile) = (code.to_string(), profile.clone());
+        let wasm_module = self.with_executor(move |executor| {
+            executor.validate_python(&code)?;
+            executor.compile(&code, &compile_profile, options)
+        }).await?;
 
         // Verify module is valid Wasm
         wasmparser::validate(&wasm_module)
             .context("generated Wasm module is invalid")?;
 
+        Self::check_module_limits(&wasm_module, profile)?;
         Ok(wasm_module)
     }
 
+    // Don't commit to a job the executor couldn't run under its profile
+    fn check_module_limits(wasm: &[u8], profile: &ExecutionProfile) -> Result<()> {
+        crate::compiler::ModuleLimits::read(wasm)?
+            .context("Wasm module declares no resource limits")?
+            .check(profile.max_memory_pages, MAX_WASM_STACK as u64)?;
+        if let Some(limits) = crate::compiler::abi::ExportedLimits::read(wasm)? {
+            limits.check(profile.fuel_limit, profile.max_memory_pages)?;
+        }
+        Ok(())
+    }
+
     fn hash_bytes(&self, data: &[u8]) -> [u8; 32] {
         use sha2::{Sha256, Digest};
         let mut hasher = Sha256::new();
@@ -405,8 +626,10 @@ impl CertusIntegration {
         Ok(JobData {
             wasm_hash: decoded[8].clone().into_fixed_bytes().unwrap().try_into().unwrap(),
             _input_hash: decoded[9].clone().into_fixed_bytes().unwrap().try_into().unwrap(),
+            finalize_deadline: decoded[13].clone().into_uint().unwrap().as_u64(),
             fuel_limit: decoded[14].clone().into_uint().unwrap().as_u64(),
-            _mem_limit: decoded[15].clone().into_uint().unwrap().as_u64(),
+            mem_limit: decoded[15].clone().into_uint().unwrap().as_u64(),
+            max_output_size: decoded[16].clone().into_uint().unwrap().as_u32(),
             pay_token: decoded[3].clone().into_address().unwrap(),
             pay_amount: decoded[4].clone().into_uint().unwrap(),
         })
@@ -443,7 +666,57 @@ impl CertusIntegration {
             .call(&TransactionRequest::new().to(self.jobs_contract).data(data).into(), None)
             .await?;
 
-        Ok(result.to_vec())
+        let wasm = decode(&[ParamType::Bytes], &result)?
+            .remove(0)
+            .into_bytes()
+            .context("invalid wasmModules response")?;
+        if wasm.is_empty() {
+            bail!("wasm {} is not registered", hex::encode(wasm_hash));
+        }
+        Ok(wasm)
+    }
+
+    /// Whether the registry already holds the module; a hit is remembered for the process lifetime
+    async fn wasm_registered(&self, wasm_hash: [u8; 32]) -> Result<bool> {
+        if self.known_wasm.lock().unwrap().contains(&wasm_hash) {
+            return Ok(true);
+        }
+        let data = [
+            &ethers::utils::id("wasmModuleSize(bytes32)")[0..4],
+            &wasm_hash[..],
+        ].concat();
+
+        let result = self.provider
+            .call(&TransactionRequest::new().to(self.jobs_contract).data(data).into(), None)
+            .await?;
+
+        let registered = !U256::from_big_endian(&result).is_zero();
+        if registered {
+            self.known_wasm.lock().unwrap().insert(wasm_hash);
+        }
+        Ok(registered)
+    }
+
+    /// Store a module in the registry under its sha256
+    async fn register_wasm(&self, wasm: &[u8]) -> Result<H256> {
+        let calldata = [
+            &ethers::utils::id("registerWasm(bytes)")[0..4],
+            &encode(&[Token::Bytes(wasm.to_vec())])[..],
+        ].concat();
+
+        let tx = self.signer
+            .send_transaction(
+                TransactionRequest::new()
+                    .to(self.jobs_contract)
+                    .data(calldata),
+                None,
+            )
+            .await?
+            .await?
+            .context("wasm registration failed")?;
+
+        self.known_wasm.lock().unwrap().insert(self.hash_bytes(wasm));
+        Ok(tx.transaction_hash)
     }
 
     async fn fetch_input(&self, job_id: [u8; 32]) -> Result<Vec<u8>> {
@@ -539,22 +812,6 @@ impl CertusIntegration {
         hasher.finalize().into()
     }
 
-    /// Calculate client deposit per Certus economic model
-    /// deposit = max(min($5, 5% of payment), $1000)
-    fn calculate_client_deposit(&self, payment: U256) -> U256 {
-        let five_percent = payment / 20; // 5% = payment / 20
-        let min_deposit = U256::from(5_000_000u128); // $5 in USDC (6 decimals)
-        let max_deposit = U256::from(1_000_000_000u128); // $1000 in USDC
-
-        if five_percent < min_deposit {
-            min_deposit
-        } else if five_percent > max_deposit {
-            max_deposit
-        } else {
-            five_percent
-        }
-    }
-
     /// Approve ERC20 token spending per EIP-20 standard
     async fn approve_token(&self, token: H160, spender: H160, amount: U256) -> Result<()> {
         let approve_data = encode(&[
@@ -659,8 +916,10 @@ impl CertusIntegration {
 struct JobData {
     wasm_hash: [u8; 32],
     _input_hash: [u8; 32],
+    finalize_deadline: u64,
     fuel_limit: u64,
-    _mem_limit: u64,
+    mem_limit: u64,
+    max_output_size: u32,
     pay_token: H160,
     pay_amount: U256,
 }
@@ -671,12 +930,25 @@ struct ReceiptData {
     _executor: H160,
 }
 
+#[derive(Debug)]
+pub struct SubmittedJob {
+    pub job_id: [u8; 32],
+    pub tx_hash: H256,
+    pub wasm_hash: [u8; 32],
+    /// registerWasm transaction; `None` when the module was already on-chain
+    pub wasm_tx: Option<H256>,
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct ExecutionResult {
     pub job_id: String,
     pub output: String,
+    pub stdout: Vec<String>,
     pub output_hash: String,
+    pub fuel_used: u64,
     pub receipt_tx: String,
+    /// IPFS block holding the output; `None` when it wasn't published
+    pub output_cid: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -685,6 +957,15 @@ pub struct VerificationResult {
     pub verified: bool,
     pub fraud_detected: bool,
     pub fraud_tx: Option<String>,
+    /// Whether the published output matched; `None` when there was nothing to check
+    pub output_available: Option<bool>,
+}
+
+#[derive(Debug)]
+pub struct FinalizedEvent {
+    pub job_id: [u8; 32],
+    pub tx_hash: H256,
+    pub finalized_at: u64,
 }
 
 pub struct VrfStatus {
@@ -751,6 +1032,98 @@ impl CertusIntegration {
         Ok(VrfStatus { fulfilled: false, elapsed: 0 })
     }
 
+    /// JobFinalized events for jobs this node executed, from `from_block` (the head when None)
+    /// up to the current head; also returns the head so the caller can advance its cursor
+    pub async fn finalized_jobs(&self, from_block: Option<u64>) -> Result<(Vec<FinalizedEvent>, u64)> {
+        let head = self.provider.get_block_number().await?.as_u64();
+        let from_block = from_block.unwrap_or(head);
+        if from_block > head {
+            return Ok((Vec::new(), head));
+        }
+
+        // JobFinalized(bytes32 indexed jobId, address indexed executor, uint256 payment)
+        let filter = Filter::new()
+            .address(self.jobs_contract)
+            .event("JobFinalized(bytes32,address,uint256)")
+            .topic2(H256::from(self.signer.address()))
+            .from_block(from_block)
+            .to_block(head);
+
+        let mut events = Vec::new();
+        for log in self.provider.get_logs(&filter).await? {
+            let (Some(job_id), Some(tx_hash), Some(block)) =
+                (log.topics.get(1), log.transaction_hash, log.block_number) else {
+                continue;
+            };
+            let finalized_at = self.provider.get_block(block).await?
+                .map(|b| b.timestamp.as_u64())
+                .unwrap_or_default();
+            events.push(FinalizedEvent { job_id: job_id.0, tx_hash, finalized_at });
+        }
+        Ok((events, head))
+    }
+
+    pub async fn block_number(&self) -> Result<u64> {
+        Ok(self.provider.get_block_number().await?.as_u64())
+    }
+
+    /// This node's executor address
+    pub fn address(&self) -> Address {
+        self.signer.address()
+    }
+
+    /// Lifecycle and dispute events of the jobs, escrow and bisection contracts in
+    /// `from_block..=to_block`, for the protocol indexer
+    pub async fn protocol_events(&self, from_block: u64, to_block: u64) -> Result<Vec<IndexedEvent>> {
+        let calldata = ethers::utils::id("bisectionModule()")[0..4].to_vec();
+        let result = self.provider
+            .call(&TransactionRequest::new().to(self.escrow_contract).data(calldata).into(), None)
+            .await?;
+        if result.len() < 32 {
+            bail!("escrow returned no bisection module");
+        }
+        let bisection = Address::from_slice(&result[12..32]);
+
+        let signatures: Vec<H256> = INDEXED_EVENTS.iter()
+            .map(|sig| H256(ethers::utils::keccak256(sig.as_bytes())))
+            .collect();
+        let filter = Filter::new()
+            .address(vec![self.jobs_contract, self.escrow_contract, bisection])
+            .topic0(signatures)
+            .from_block(from_block)
+            .to_block(to_block);
+
+        let mut timestamps = std::collections::HashMap::new();
+        let mut events = Vec::new();
+        for log in self.provider.get_logs(&filter).await? {
+            let Some(block) = log.block_number else {
+                continue;
+            };
+            let timestamp = match timestamps.get(&block) {
+                Some(&timestamp) => timestamp,
+                None => {
+                    let timestamp = self.provider.get_block(block).await?
+                        .map(|b| b.timestamp.as_u64())
+                        .unwrap_or_default();
+                    timestamps.insert(block, timestamp);
+                    timestamp
+                }
+            };
+            events.extend(IndexedEvent::decode(&log, timestamp));
+        }
+        Ok(events)
+    }
+
+    /// Sign a completion receipt with the executor key
+    pub fn sign_receipt(&self, finalization: &Finalization) -> Result<SignedReceipt> {
+        SignedReceipt::sign(finalization, &self.wallet)
+    }
+
+    /// This node's build, signed with the executor key
+    pub fn build_report(&self) -> Result<BuildReport> {
+        BuildReport::sign(BuildInfo::current(), &self.wallet)
+    }
+
     /// Trigger fallback verifier selection
     pub async fn trigger_fallback_selection(&self, job_id: [u8; 32]) -> Result<H256> {
         let calldata = [
@@ -774,9 +1147,23 @@ impl CertusIntegration {
     }
 
 
-    pub async fn execute_python_job(&self, job_id: &str, code: &str, input: &str) -> Result<ExecutionResult> {
+    pub async fn execute_python_job(&self, job_id: &str, code: &str, input: &str, profile: &ExecutionProfile, options: CompileOptions) -> Result<ExecutionResult> {
+        self.canary.ensure_healthy()?;
         // execute locally first
-        let output = self.executor.lock().unwrap().execute(code, input, 1_000_000)?;
+        let (code, input, profile) = (code.to_string(), input.to_string(), profile.clone());
+        let output = self.with_executor(move |executor| executor.execute_with_options(&code, &input, &profile, options)).await?;
+        self.post_local_receipt(job_id, output).await
+    }
+
+    pub async fn execute_wasm_job(&self, job_id: &str, wasm: &[u8], input: &str, profile: &ExecutionProfile) -> Result<ExecutionResult> {
+        self.canary.ensure_healthy()?;
+        let (wasm, input, profile) = (wasm.to_vec(), input.to_string(), profile.clone());
+        let output = self.with_executor(move |executor| executor.execute_wasm_with_profile(&wasm, &input, &profile)).await?;
+        self.post_local_receipt(job_id, output).await
+    }
+
+    async fn post_local_receipt(&self, job_id: &str, output: ExecutionOutput) -> Result<ExecutionResult> {
+        let output_cid = self.publish_output(&output).await;
 
         // submit receipt to chain
         let job_id_bytes: [u8; 32] = hex::decode(job_id.trim_start_matches("0x"))?
@@ -803,8 +1190,11 @@ impl CertusIntegration {
         Ok(ExecutionResult {
             job_id: job_id.to_string(),
             output: output.result,
+            stdout: output.stdout,
             output_hash: output.output_hash,
+            fuel_used: output.fuel_consumed,
             receipt_tx: format!("0x{}", hex::encode(receipt_tx.transaction_hash)),
+            output_cid,
         })
     }
 }
\ No newline at end of file
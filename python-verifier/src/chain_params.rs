@@ -0,0 +1,138 @@
+// Protocol parameters the contracts enforce. Deposit bounds and rate are governance-settable
+// on CertusJobs; size and resource ceilings are CertusBase constants an upgrade can change.
+// Both are read from chain at startup and periodically, so job validation and deposit math
+// follow what createJob will actually accept. The minimum payment and the accept/challenge
+// windows aren't exposed on-chain and stay local settings.
+
+use anyhow::{Result, bail};
+use ethers::types::U256;
+
+use crate::profiles::ExecutionProfile;
+
+/// Getters read on every sync, in the order `with_synced` takes their values
+pub const SYNCED_GETTERS: [&str; 7] = [
+    "minClientDepositUsd()",
+    "maxClientDepositUsd()",
+    "clientDepositBasisPoints()",
+    "MAX_WASM_SIZE()",
+    "MAX_INPUT_ON_CHAIN()",
+    "MAX_FUEL_LIMIT()",
+    "MAX_MEM_LIMIT()",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainParams {
+    /// Smallest payment this node submits, in USDC units (6 decimals)
+    pub min_payment: U256,
+    pub min_client_deposit: U256,
+    pub max_client_deposit: U256,
+    /// Client deposit as a share of the payment, in basis points
+    pub client_deposit_bps: U256,
+    pub max_wasm_size: u64,
+    pub max_input_size: u64,
+    pub max_fuel_limit: u64,
+    /// createJob memLimit ceiling, in bytes
+    pub max_mem_limit: u64,
+    /// Seconds an executor has to accept a new job
+    pub accept_window: u64,
+    /// Seconds a receipt can be challenged; the contract requires at least an hour
+    pub challenge_window: u64,
+}
+
+impl Default for ChainParams {
+    /// The values the contracts were deployed with
+    fn default() -> Self {
+        Self {
+            min_payment: U256::from(5_000_000u64),
+            min_client_deposit: U256::from(5_000_000u64),
+            max_client_deposit: U256::from(1_000_000_000u64),
+            client_deposit_bps: U256::from(500u64),
+            max_wasm_size: 24 * 1024,
+            max_input_size: 100 * 1024,
+            max_fuel_limit: 10_000_000,
+            max_mem_limit: 128 * 1024 * 1024,
+            accept_window: 3600,
+            challenge_window: 3600,
+        }
+    }
+}
+
+impl ChainParams {
+    /// These parameters with the synced ones replaced by `values`, one per `SYNCED_GETTERS` entry
+    pub fn with_synced(&self, values: [U256; 7]) -> Result<Self> {
+        let [min_deposit, max_deposit, bps, wasm, input, fuel, mem] = values;
+        if min_deposit > max_deposit {
+            bail!("chain reports min client deposit {} above max {}", min_deposit, max_deposit);
+        }
+        if bps > U256::from(10_000u64) {
+            bail!("chain reports client deposit of {} basis points", bps);
+        }
+        let small = |name: &str, value: U256| -> Result<u64> {
+            if value > U256::from(u64::MAX) {
+                bail!("chain reports {} of {}", name, value);
+            }
+            Ok(value.as_u64())
+        };
+        Ok(Self {
+            min_client_deposit: min_deposit,
+            max_client_deposit: max_deposit,
+            client_deposit_bps: bps,
+            max_wasm_size: small("MAX_WASM_SIZE", wasm)?,
+            max_input_size: small("MAX_INPUT_ON_CHAIN", input)?,
+            max_fuel_limit: small("MAX_FUEL_LIMIT", fuel)?,
+            max_mem_limit: small("MAX_MEM_LIMIT", mem)?,
+            ..self.clone()
+        })
+    }
+
+    /// Deposit createJob pulls on top of the payment: the basis-point share, clamped to the bounds
+    pub fn client_deposit(&self, payment: U256) -> U256 {
+        let proportional = payment * self.client_deposit_bps / U256::from(10_000u64);
+        proportional.max(self.min_client_deposit).min(self.max_client_deposit)
+    }
+
+    pub fn check_payment(&self, payment: U256) -> Result<()> {
+        if payment < self.min_payment {
+            bail!("payment too low: minimum {} token units", self.min_payment);
+        }
+        Ok(())
+    }
+
+    /// Reject a job createJob would revert on
+    pub fn check_job(&self, wasm_len: usize, input_len: usize, profile: &ExecutionProfile) -> Result<()> {
+        if wasm_len as u64 > self.max_wasm_size {
+            bail!("wasm exceeds {} byte limit", self.max_wasm_size);
+        }
+        if input_len as u64 > self.max_input_size {
+            bail!("input exceeds {} byte limit", self.max_input_size);
+        }
+        if profile.fuel_limit > self.max_fuel_limit {
+            bail!("fuel limit {} exceeds on-chain maximum {}", profile.fuel_limit, self.max_fuel_limit);
+        }
+        if profile.mem_limit() > self.max_mem_limit {
+            bail!("memory limit {} exceeds on-chain maximum {}", profile.mem_limit(), self.max_mem_limit);
+        }
+        Ok(())
+    }
+
+    /// One line per parameter that differs in `new`
+    pub fn changes(&self, new: &ChainParams) -> Vec<String> {
+        let mut changes = Vec::new();
+        let mut diff = |name: &str, old: String, new: String| {
+            if old != new {
+                changes.push(format!("{}: {} -> {}", name, old, new));
+            }
+        };
+        diff("min_payment", self.min_payment.to_string(), new.min_payment.to_string());
+        diff("min_client_deposit", self.min_client_deposit.to_string(), new.min_client_deposit.to_string());
+        diff("max_client_deposit", self.max_client_deposit.to_string(), new.max_client_deposit.to_string());
+        diff("client_deposit_bps", self.client_deposit_bps.to_string(), new.client_deposit_bps.to_string());
+        diff("max_wasm_size", self.max_wasm_size.to_string(), new.max_wasm_size.to_string());
+        diff("max_input_size", self.max_input_size.to_string(), new.max_input_size.to_string());
+        diff("max_fuel_limit", self.max_fuel_limit.to_string(), new.max_fuel_limit.to_string());
+        diff("max_mem_limit", self.max_mem_limit.to_string(), new.max_mem_limit.to_string());
+        diff("accept_window", self.accept_window.to_string(), new.accept_window.to_string());
+        diff("challenge_window", self.challenge_window.to_string(), new.challenge_window.to_string());
+        changes
+    }
+}
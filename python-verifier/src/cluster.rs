@@ -0,0 +1,238 @@
+// Verifier clusters. One leader polls the chain for jobs awaiting verification and feeds them
+// into a verification queue; every machine, the leader included, runs workers that claim a
+// job, verify it and report back. Remote workers reach the leader's queue over HTTP, so a job
+// is verified by one machine at a time and a worker that dies mid-job only delays it until
+// its claim's visibility timeout passes.
+
+use anyhow::{Context, Result, bail};
+use axum::{
+    extract::{Path, State, Json},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    Router,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::certus_integration::CertusIntegration;
+use crate::executor_error::ExecutorError;
+use crate::queue::{JobQueue, QueueBackend, QueuedJob};
+use crate::verifier::PythonVerifier;
+
+/// Attempts a verification gets before it is dead-lettered
+const VERIFY_RETRIES: u8 = 3;
+/// VRF grace period after which fallback verifier selection is triggered
+const VRF_GRACE_SECS: u64 = 1800;
+
+/// One leader pass: trigger fallback selection where VRF stalled and queue every pending job
+/// that isn't already queued or being verified. Returns how many jobs were queued.
+pub async fn feed(integration: &CertusIntegration, queue: &JobQueue) -> Result<usize> {
+    let mut queued = 0;
+    for job_id in integration.get_pending_verification_jobs().await? {
+        let id = format!("0x{}", hex::encode(job_id));
+        if queue.is_queued(&id)? {
+            continue;
+        }
+
+        match integration.check_vrf_status(job_id).await {
+            Ok(vrf) if !vrf.fulfilled && vrf.elapsed > VRF_GRACE_SECS => {
+                log::info!("Triggering fallback selection for job {}", id);
+                if let Err(e) = integration.trigger_fallback_selection(job_id).await {
+                    log::error!("Fallback selection failed: {}", e);
+                    continue;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("Failed to check VRF status: {}", e);
+                continue;
+            }
+        }
+
+        queue.submit(verification_job(&id)).await?;
+        queued += 1;
+    }
+    Ok(queued)
+}
+
+fn verification_job(id: &str) -> QueuedJob {
+    QueuedJob {
+        id: id.to_string(),
+        code: String::new(),
+        wasm_b64: None,
+        input: serde_json::json!({ "job_id": id }),
+        priority: 5,
+        created_at: chrono::Utc::now().timestamp() as u64,
+        retry_count: 0,
+        max_retries: VERIFY_RETRIES,
+        available_at: 0,
+        claimed_at: None,
+        depends_on: vec![],
+        on_dependency_failure: Default::default(),
+        tenant: None,
+        profile: None,
+        compile_options: Default::default(),
+    }
+}
+
+/// Claim verification jobs from `queue` and verify them, forever
+pub async fn run_worker<Q: QueueBackend>(queue: &Q, verifier: &PythonVerifier) {
+    loop {
+        let job = match queue.claim().await {
+            Ok(Some(job)) => job,
+            Ok(None) => continue,
+            Err(e) => {
+                log::error!("Could not claim a verification job: {}", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        let reported = match verify(&job, verifier).await {
+            Ok(()) => {
+                log::debug!("Processed job: {}", job.id);
+                queue.complete(&job.id, serde_json::json!({
+                    "timestamp": chrono::Utc::now().timestamp(),
+                })).await
+            }
+            Err(e) => {
+                log::error!("Verification failed for job {}: {}", job.id, e);
+                queue.fail(&job.id, &e.to_string(), ExecutorError::of(&e).as_ref()).await
+            }
+        };
+        if let Err(e) = reported {
+            log::error!("Could not report verification of job {}: {}", job.id, e);
+        }
+    }
+}
+
+async fn verify(job: &QueuedJob, verifier: &PythonVerifier) -> Result<()> {
+    let job_id = crate::receipts::parse_bytes32(&job.id)?;
+    verifier.verify_certus_job(job_id).await
+}
+
+/// A leader's verification queue, used by workers on other machines
+pub struct RemoteQueue {
+    client: reqwest::Client,
+    leader: String,
+    token: String,
+}
+
+impl RemoteQueue {
+    pub fn new(leader: &str, token: &str) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            // claims long-poll on the leader for up to 20s
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        Ok(Self {
+            client,
+            leader: leader.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        })
+    }
+
+    async fn post(&self, path: &str, body: serde_json::Value) -> Result<reqwest::Response> {
+        let url = format!("{}{}", self.leader, path);
+        let response = self.client.post(&url)
+            .bearer_auth(&self.token)
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("cluster leader {} unreachable", url))?;
+        if !response.status().is_success() {
+            bail!("cluster leader {} answered {}", url, response.status());
+        }
+        Ok(response)
+    }
+}
+
+impl QueueBackend for RemoteQueue {
+    async fn claim(&self) -> Result<Option<QueuedJob>> {
+        let response = self.post("/cluster/claim", serde_json::json!({})).await?;
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        Ok(Some(response.json().await.context("leader sent no job")?))
+    }
+
+    async fn complete(&self, job_id: &str, result: serde_json::Value) -> Result<()> {
+        self.post(&format!("/cluster/jobs/{}/complete", job_id), result).await?;
+        Ok(())
+    }
+
+    async fn fail(&self, job_id: &str, error: &str, kind: Option<&ExecutorError>) -> Result<()> {
+        self.post(&format!("/cluster/jobs/{}/fail", job_id), serde_json::json!({ "error": error, "kind": kind })).await?;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct ClusterState {
+    queue: Arc<JobQueue>,
+    token: Arc<str>,
+}
+
+/// Leader routes remote workers claim from and report to; every request needs the cluster token
+pub fn cluster_routes(queue: Arc<JobQueue>, token: &str) -> Router {
+    Router::new()
+        .route("/cluster/claim", post(claim))
+        .route("/cluster/jobs/:id/complete", post(complete))
+        .route("/cluster/jobs/:id/fail", post(fail))
+        .with_state(ClusterState { queue, token: token.into() })
+}
+
+fn authorized(state: &ClusterState, headers: &HeaderMap) -> bool {
+    headers.get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| token == &*state.token)
+}
+
+async fn claim(State(state): State<ClusterState>, headers: HeaderMap) -> impl IntoResponse {
+    if !authorized(&state, &headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    match QueueBackend::claim(&*state.queue).await {
+        Ok(Some(job)) => Json(job).into_response(),
+        Ok(None) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn complete(
+    State(state): State<ClusterState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(result): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    if !authorized(&state, &headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    match state.queue.complete(&id, result).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct FailRequest {
+    error: String,
+    #[serde(default)]
+    kind: Option<ExecutorError>,
+}
+
+async fn fail(
+    State(state): State<ClusterState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<FailRequest>,
+) -> impl IntoResponse {
+    if !authorized(&state, &headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    match state.queue.fail_as(&id, &req.error, req.kind.as_ref()).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,101 @@
+// Limits a compiled module was built for, exported as immutable i32 globals. The heap limit is
+// the address the allocator traps at, the gas limit the fuel counter's trap threshold, and the
+// ABI version the calling convention of main and the memory layout around it. A host reads them
+// back before running a job and turns the module away if the job asks for more than it was built
+// to allow. Client modules carry none of the exports and are judged by their limits section alone.
+
+use anyhow::{Result, bail};
+use wasmparser::{ExternalKind, Operator, Parser, Payload, TypeRef};
+
+pub const HEAP_LIMIT_EXPORT: &str = "__certus_heap_limit";
+pub const GAS_LIMIT_EXPORT: &str = "__certus_gas_limit";
+pub const ABI_VERSION_EXPORT: &str = "__certus_abi_version";
+
+/// Bumped whenever main's calling convention or the fixed memory layout changes
+pub const ABI_VERSION: u32 = 1;
+
+const WASM_PAGE_SIZE: u64 = 65536;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportedLimits {
+    /// Heap addresses stay below this
+    pub heap_limit: u32,
+    /// The fuel counter traps once it passes this
+    pub gas_limit: u32,
+    pub abi_version: u32,
+}
+
+impl ExportedLimits {
+    /// Limits exported by a module: None if it exports none of them, an error if only some
+    pub fn read(wasm: &[u8]) -> Result<Option<Self>> {
+        let mut imported = 0;
+        let mut values = Vec::new();
+        let mut exported = [None; 3];
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        if let TypeRef::Global(_) = import?.ty {
+                            imported += 1;
+                        }
+                    }
+                }
+                Payload::GlobalSection(reader) => {
+                    for global in reader {
+                        let global = global?;
+                        // only an immutable i32.const says anything before the module runs
+                        let value = match global.init_expr.get_operators_reader().read()? {
+                            Operator::I32Const { value } if !global.ty.mutable => Some(value as u32),
+                            _ => None,
+                        };
+                        values.push(value);
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        let export = export?;
+                        let slot = match export.name {
+                            HEAP_LIMIT_EXPORT => 0,
+                            GAS_LIMIT_EXPORT => 1,
+                            ABI_VERSION_EXPORT => 2,
+                            _ => continue,
+                        };
+                        if export.kind != ExternalKind::Global {
+                            bail!("{} must be a global", export.name);
+                        }
+                        exported[slot] = Some(export.index);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if exported.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+        let mut limits = [0; 3];
+        for (slot, name) in [HEAP_LIMIT_EXPORT, GAS_LIMIT_EXPORT, ABI_VERSION_EXPORT].into_iter().enumerate() {
+            let Some(index) = exported[slot] else {
+                bail!("module exports some resource limits but not {}", name);
+            };
+            limits[slot] = index.checked_sub(imported)
+                .and_then(|defined| values.get(defined as usize).copied().flatten())
+                .ok_or_else(|| anyhow::anyhow!("{} must be an immutable i32 constant defined by the module", name))?;
+        }
+        Ok(Some(Self { heap_limit: limits[0], gas_limit: limits[1], abi_version: limits[2] }))
+    }
+
+    /// Reject a module built for another ABI, or for less fuel or more memory than the job declares
+    pub fn check(&self, fuel_limit: u64, memory_pages: u32) -> Result<()> {
+        if self.abi_version != ABI_VERSION {
+            bail!("module was compiled for ABI version {}, host speaks {}", self.abi_version, ABI_VERSION);
+        }
+        if self.heap_limit as u64 > memory_pages as u64 * WASM_PAGE_SIZE {
+            bail!("module heap reaches {:#x}, job allows {} memory pages", self.heap_limit, memory_pages);
+        }
+        if fuel_limit > self.gas_limit as u64 {
+            bail!("job declares {} fuel, module was compiled to stop at {}", fuel_limit, self.gas_limit);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+//! The Python subset the compiler accepts, construct by construct. Every entry carries an
+//! example that compiles (or, for an unsupported construct, is rejected), and
+//! tests/capabilities_tests.rs compiles each one, so the matrix can't drift from the lowering.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Support {
+    Supported,
+    /// Compiles in the common forms; `rejected` shows one that doesn't
+    Partial,
+    Unsupported,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Capability {
+    pub construct: &'static str,
+    pub support: Support,
+    /// Crate version that first compiled the construct; none while it is unsupported
+    pub since: Option<&'static str>,
+    pub notes: &'static str,
+    /// Compiles unless the construct is unsupported
+    pub example: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejected: Option<&'static str>,
+}
+
+const fn supported(construct: &'static str, since: &'static str, notes: &'static str, example: &'static str) -> Capability {
+    Capability { construct, support: Support::Supported, since: Some(since), notes, example, rejected: None }
+}
+
+const fn partial(construct: &'static str, since: &'static str, notes: &'static str, example: &'static str, rejected: &'static str) -> Capability {
+    Capability { construct, support: Support::Partial, since: Some(since), notes, example, rejected: Some(rejected) }
+}
+
+const fn unsupported(construct: &'static str, notes: &'static str, example: &'static str) -> Capability {
+    Capability { construct, support: Support::Unsupported, since: None, notes, example, rejected: None }
+}
+
+pub const CAPABILITIES: &[Capability] = &[
+    supported("integer arithmetic", "0.1.0",
+        "+ - * / // % ** and unary minus on 32-bit integers; / divides like //, and dividing by zero raises ZeroDivisionError",
+        "OUTPUT = 7 // 2 + 3 ** 2 % 5 - -1"),
+    unsupported("floats", "float literals are rejected since their rounding isn't reproducible across executors",
+        "OUTPUT = 1.5"),
+    unsupported("bitwise operators", "& | ^ << >> and ~",
+        "OUTPUT = 6 & 3"),
+    supported("comparisons", "0.1.0",
+        "== != < <= > >=, chained comparisons, in and not in",
+        "OUTPUT = 1 < 2 <= 3 and 4 not in [1, 2]"),
+    unsupported("identity comparisons", "is and is not",
+        "x = 0\nOUTPUT = x is None"),
+    supported("boolean operators", "0.1.0", "and, or, not",
+        "OUTPUT = (1 and 0) or not 0"),
+    supported("conditional expressions", "0.1.0", "a if cond else b",
+        "x = 1\nOUTPUT = 2 if x else 3"),
+    supported("if / elif / else", "0.1.0", "dense elif ladders compile to jump tables",
+        "x = 2\nif x == 1:\n    OUTPUT = 10\nelif x == 2:\n    OUTPUT = 20\nelse:\n    OUTPUT = 0"),
+    partial("while loops", "0.1.0", "with break and continue; no else clause",
+        "i = 0\nwhile True:\n    i += 1\n    if i > 3:\n        break\nOUTPUT = i",
+        "while False:\n    OUTPUT = 1\nelse:\n    OUTPUT = 2"),
+    partial("for loops", "0.1.0", "over range(), lists, tuples, strings, enumerate() and zip(), with tuple targets; no else clause",
+        "t = 0\nfor i, x in enumerate([4, 5]):\n    t += i * x\nOUTPUT = t",
+        "for i in range(2):\n    OUTPUT = i\nelse:\n    OUTPUT = 5"),
+    supported("continue", "0.1.0", "in while and for loops; a for loop still steps its counter",
+        "t = 0\nfor i in range(4):\n    if i == 2:\n        continue\n    t += i\nOUTPUT = t"),
+    unsupported("pass", "only accepted as a class body",
+        "if 1:\n    pass\nOUTPUT = 0"),
+    partial("augmented assignment", "0.1.0", "+= -= *= /= //= %= **= on names and attributes",
+        "x = 5\nx -= 1\nx *= 2\nx //= 3\nOUTPUT = x",
+        "x = 5\nx <<= 1\nOUTPUT = x"),
+    supported("functions", "0.1.0", "positional parameters, recursion up to the call depth limit, tuple returns",
+        "def fact(n):\n    if n <= 1:\n        return 1\n    return n * fact(n - 1)\nOUTPUT = fact(5)"),
+    unsupported("default and keyword arguments", "parameters are positional only",
+        "def f(a, b=1):\n    return a + b\nOUTPUT = f(1)"),
+    partial("nested functions", "0.1.0", "one level deep; they read the enclosing function's locals and can only be called",
+        "def f(k):\n    def g(v):\n        return v * k\n    return g(3)\nOUTPUT = f(2)",
+        "def f():\n    x = 1\n    def g():\n        nonlocal x\n        x = 2\n        return 0\n    return g()\nOUTPUT = f()"),
+    supported("global statement", "0.1.0", "functions read module names and assign those they declare global",
+        "n = 1\ndef bump():\n    global n\n    n += 1\n    return n\nOUTPUT = bump()"),
+    unsupported("lambda", "define a function instead",
+        "f = lambda x: x\nOUTPUT = 0"),
+    partial("classes", "0.1.0", "module-level classes with __init__, instance attributes and methods; no inheritance or class attributes",
+        "class P:\n    def __init__(self, x):\n        self.x = x\n    def double(self):\n        return self.x * 2\nOUTPUT = P(4).double()",
+        "class A:\n    pass\nclass B(A):\n    pass\nOUTPUT = 0"),
+    unsupported("generators", "yield and generator expressions; use a list comprehension",
+        "OUTPUT = sum(x for x in [1, 2])"),
+    supported("lists", "0.1.0", "literals, negative indexing, append, pop, insert, * repetition",
+        "xs = [1, 2]\nxs.append(3)\nOUTPUT = xs[-1] + len(xs * 2)"),
+    supported("slicing", "0.1.0", "start, stop and step on lists, tuples and strings",
+        "xs = [1, 2, 3, 4]\nOUTPUT = len(xs[1:]) + xs[::2][1]"),
+    supported("tuples and unpacking", "0.1.0", "tuple literals, multiple assignment and unpacking a returned tuple",
+        "a, b = 1, 2\nt = (a, b)\nc, d = t\nOUTPUT = c + d"),
+    supported("dicts", "0.1.0", "literals, subscript get and set, pop; a missing key raises KeyError",
+        "d = {1: 2}\nd[3] = 4\nOUTPUT = d[1] + len(d)"),
+    supported("sets", "0.1.0", "literals, set(), add and in",
+        "s = {1, 2}\ns.add(3)\nOUTPUT = len(s)"),
+    supported("comprehensions", "0.1.0", "list, set and dict comprehensions with if clauses, over range() and the sequences for loops take",
+        "OUTPUT = len([x for x in range(3) if x]) + len({x: x for x in range(2)})"),
+    partial("del", "0.1.0", "del of a dict key or list index; deleting a missing key raises KeyError",
+        "d = {1: 2, 3: 4}\ndel d[1]\nxs = [1, 2]\ndel xs[0]\nOUTPUT = len(d) + xs[0]",
+        "x = 1\ndel x\nOUTPUT = 0"),
+    supported("strings", "0.1.0", "literals, concatenation, indexing, comparison, substring in, str(), startswith, endswith, encode",
+        "s = str(12) + 'ab'\nOUTPUT = len(s) + (s[0] == '1') + ('2a' in s) + s.endswith('b')"),
+    partial("f-strings", "0.1.0", "interpolated values go through str(); no format specs or !r/!a",
+        "x = 3\nOUTPUT = len(f'x={x}')",
+        "OUTPUT = len(f'{1:>3}')"),
+    supported("str.format", "0.1.0", "on a string literal, resolved at compile time",
+        "OUTPUT = len('{}-{}'.format(1, 2))"),
+    supported("bytes", "0.1.0", "literals, bytes() of a list or tuple of ints, indexing, slicing, len, + and ==",
+        "b = b'ab' + bytes([99])\nOUTPUT = b[2] + len(b) + (b[:1] == b'a')"),
+    supported("hashing", "0.1.0", "hashlib.sha256, hashlib.sha3_256 and keccak256",
+        "import hashlib\nh = hashlib.sha256(b'x').digest()\nOUTPUT = len(h) + len(keccak256(b'x'))"),
+    supported("builtins", "0.1.0", "len, str, abs, min, max, sum, sorted, pow, set, bytes, keccak256, print, type, isinstance; execution profiles may narrow this",
+        "OUTPUT = abs(-1) + min(1, 2) + max([1, 2]) + sum([1]) + len(sorted([2, 1])) + pow(2, 3, 5)"),
+    partial("type checks", "0.1.0", "type(x) compares against int, str, bytes, list, tuple, dict and set; bools, None and instances read as int or dict",
+        "x = [1]\nOUTPUT = isinstance(x, (int, list)) + (type('a') == str)",
+        "OUTPUT = isinstance(1, bool)"),
+    supported("print", "0.1.0", "captured into the job's stdout, which is hashed with the output",
+        "print('total', 1)\nOUTPUT = 0"),
+    partial("exceptions", "0.1.0", "try / except / else / finally and raise for ValueError, KeyError and ZeroDivisionError",
+        "try:\n    raise ValueError('bad')\nexcept ValueError:\n    OUTPUT = 1",
+        "try:\n    OUTPUT = 1\nexcept OSError:\n    OUTPUT = 2"),
+    supported("assert", "0.1.0", "a failure records the message hash for the executor",
+        "assert 1 == 1, 'ok'\nOUTPUT = 1"),
+    unsupported("with", "context managers",
+        "with x:\n    OUTPUT = 0"),
+    unsupported("async", "async def, await and async for",
+        "async def f():\n    return 1\nOUTPUT = 0"),
+];
+
+/// The matrix as a Markdown table, for docs and `python-cli capabilities --markdown`
+pub fn markdown() -> String {
+    let mut out = String::from("| Construct | Support | Since | Notes |\n|---|---|---|---|\n");
+    for cap in CAPABILITIES {
+        let support = match cap.support {
+            Support::Supported => "supported",
+            Support::Partial => "partial",
+            Support::Unsupported => "unsupported",
+        };
+        out.push_str(&format!("| {} | {} | {} | {} |\n", cap.construct, support, cap.since.unwrap_or("-"), cap.notes.replace('|', "\\|")));
+    }
+    out
+}
@@ -1,43 +1,232 @@
 use anyhow::{Result, bail};
 use wasm_encoder::*;
 use std::collections::BTreeMap;
+use sha2::{Sha256, Digest};
+use sha3::Sha3_256;
 
 use super::ir::*;
-use super::memory;
-
-const GAS_LIMIT: i32 = 100_000_000;
-const HEAP_START: i32 = 0x10000;
-const HEAP_LIMIT: i32 = 0x400000;
+use super::memory::{self, HashAlgorithm};
+use super::runtime::Runtime;
+use super::optimize::for_each_stmt_expr;
+use super::region;
+use super::{ASSERT_SLOT, ASSERTION_FAILED, ERROR_SLOT, STDOUT_EXPORT};
+use super::errors::RuntimeError;
+use super::fuel;
+use super::abi;
+use super::estimate::{LoopKind, SourceLoop};
+use super::source_map::{FunctionLines, SourceMap};
+use super::target::{self, Target};
+use super::resources::ResourceLimits;
+
+pub(crate) const HEAP_START: i32 = 0x10000;
+// The allocator's ceiling; a job with less memory gets its heap cut at the end of it
+pub(crate) const HEAP_LIMIT: i32 = 0x400000;
+// Pages the memory import asks for up front, or all of the job's if it has fewer
+const INITIAL_MEMORY_PAGES: u32 = 16;
+// Slots of the smallest dict or set; tables rehash into more as they fill
+const MIN_TABLE_CAPACITY: u32 = 8;
+// Live user-function frames; entry past the limit traps before the engine's own stack does
+const CALL_DEPTH_GLOBAL: u32 = 3;
+// Code of the exception being unwound (ExceptionKind), 0 when none is pending
+pub(crate) const ERROR_GLOBAL: u32 = 4;
+// Address of the stdout buffer, exported only by modules that print
+const STDOUT_GLOBAL: u32 = 5;
+// Passive segment of the first distinct string or bytes literal; sha256's constants come before
+const FIRST_LITERAL_SEGMENT: u32 = memory::SHA256_K_SEGMENT + 1;
 
 pub(crate) struct WasmCodegen {
     function_indices: BTreeMap<String, u32>,
     gas_global: u32,
+    max_call_depth: u32,
+    // Set when the module catches exceptions; otherwise every raise traps on the spot
+    exceptions: bool,
+    // Locals holding the codes caught by the enclosing except blocks, innermost last
+    handling: Vec<u32>,
+    // Set when the module prints; it then exports the stdout buffer's address
+    stdout: bool,
+    // Wasm global index of each module-level Python global, allocated after the runtime's
+    module_globals: BTreeMap<String, u32>,
+    // Shared runtime functions in order of first use; they follow the user functions
+    runtime: Vec<Runtime>,
+    user_functions: u32,
+    // Contents of each distinct literal, in segment order from FIRST_LITERAL_SEGMENT
+    literals: Vec<Vec<u8>>,
+    // Python loops in emission order, kept only for fuel estimates
+    loops: Option<Vec<SourceLoop>>,
+    // (body offset, Python line) per user function, kept only for source maps
+    lines: Option<Vec<Vec<(u32, u32)>>>,
+    target: Target,
+    // Fuel the counter traps past and the memory the module imports and allocates within
+    limits: ResourceLimits,
+    // Names of the functions downgrading for the target appended after the runtime's
+    target_helpers: Vec<String>,
+}
+
+// Where a statement sends control when an exception is pending
+#[derive(Debug, Clone, Copy)]
+enum Unwind {
+    // branch to the label this many levels out, the dispatch or done block of a try
+    Handler(u32),
+    // leave the function: return to the caller, or trap in main
+    Exit,
+    // inside an expression: leave the error for the enclosing statement's check
+    Deferred,
+}
+
+// Branch targets of the statement being generated
+#[derive(Debug, Clone, Copy)]
+struct Depth {
+    // labels between here and the innermost loop body
+    in_loop: u32,
+    // the loop body is wrapped in a block that continue leaves, one label inside the loop's
+    in_step: bool,
+    unwind: Unwind,
+}
+
+impl Depth {
+    const TOP: Depth = Depth { in_loop: 0, in_step: false, unwind: Unwind::Exit };
+    const DEFERRED: Depth = Depth { in_loop: 0, in_step: false, unwind: Unwind::Deferred };
+
+    /// Inside `labels` more blocks
+    fn nested(self, labels: u32) -> Self {
+        let unwind = match self.unwind {
+            Unwind::Handler(d) => Unwind::Handler(d + labels),
+            other => other,
+        };
+        Depth { in_loop: self.in_loop + labels, unwind, ..self }
+    }
+
+    /// Inside the body of a new loop: its block and loop labels
+    fn loop_body(self) -> Self {
+        Depth { in_loop: 0, in_step: false, ..self.nested(2) }
+    }
+
+    /// Inside the body of a new for loop whose body continue leaves for the counter step
+    fn stepped_loop_body(self) -> Self {
+        Depth { in_loop: 0, in_step: true, ..self.nested(3) }
+    }
+
+    /// Label of the block around the innermost loop
+    fn break_label(self) -> u32 {
+        self.in_loop + 1 + self.in_step as u32
+    }
 }
 
 impl WasmCodegen {
-    pub fn new() -> Self {
+    pub fn new(max_call_depth: u32) -> Self {
         Self {
             function_indices: BTreeMap::new(),
             gas_global: 0,
+            max_call_depth,
+            exceptions: false,
+            handling: Vec::new(),
+            stdout: false,
+            module_globals: BTreeMap::new(),
+            runtime: Vec::new(),
+            user_functions: 0,
+            literals: Vec::new(),
+            loops: None,
+            lines: None,
+            target: Target::Latest,
+            limits: ResourceLimits::default(),
+            target_helpers: Vec::new(),
         }
     }
 
+    /// Emit only the instructions `target` supports
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Trap at the job's fuel and keep the heap within its memory
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Record where each Python loop starts and how often it runs, for `take_loops`
+    pub fn with_loop_records(mut self) -> Self {
+        self.loops = Some(Vec::new());
+        self
+    }
+
+    pub fn take_loops(&mut self) -> Vec<SourceLoop> {
+        self.loops.take().unwrap_or_default()
+    }
+
+    /// Record the Python line of each statement, for `take_source_map`
+    pub fn with_source_map(mut self) -> Self {
+        self.lines = Some(Vec::new());
+        self
+    }
+
+    /// Lines of the module `generate` returned, at their metered offsets
+    pub fn take_source_map(&mut self) -> SourceMap {
+        let lines = self.lines.take().unwrap_or_default();
+        let mut functions: Vec<FunctionLines> = self.function_indices.iter()
+            .filter_map(|(name, &index)| {
+                let lines = lines.get(index as usize).filter(|l| !l.is_empty())?;
+                Some(FunctionLines { index, name: name.clone(), lines: lines.clone() })
+            })
+            .collect();
+        functions.sort_by_key(|f| f.index);
+        SourceMap { functions }
+    }
+
     pub fn generate(&mut self, ir: &IR) -> Result<Vec<u8>> {
-        let IR::Module { functions, .. } = ir;
+        let wasm = self.generate_unmetered(ir)?;
+        let (mut wasm, charges) = fuel::meter(&wasm, self.gas_global, self.limits.gas_limit())?;
+        if let Some(lines) = &mut self.lines {
+            for (lines, charges) in lines.iter_mut().zip(&charges) {
+                for (offset, _) in lines.iter_mut() {
+                    *offset = fuel::metered_offset(charges, *offset as usize) as u32;
+                }
+            }
+        }
+        let names = self.names(ir);
+        wasm.push(Section::id(&names));
+        names.encode(&mut wasm);
+        Ok(wasm)
+    }
+
+    /// The module before `fuel::meter` charges its basic blocks
+    pub fn generate_unmetered(&mut self, ir: &IR) -> Result<Vec<u8>> {
+        let IR::Module { functions, globals: module_globals } = ir;
 
         for (idx, func) in functions.iter().enumerate() {
             self.function_indices.insert(func.name.clone(), idx as u32);
         }
+        self.user_functions = functions.len() as u32;
+        if let Some(lines) = &mut self.lines {
+            *lines = vec![Vec::new(); functions.len()];
+        }
+        self.exceptions = functions.iter().any(|f| f.body.iter().any(contains_try));
+        self.stdout = functions.iter().any(|f| f.body.iter().any(prints));
+        let first_module_global = STDOUT_GLOBAL + self.stdout as u32;
+        self.module_globals = module_globals.iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), first_module_global + i as u32))
+            .collect();
+
+        // bodies first: they decide which runtime functions the module needs
+        let mut bodies = Vec::with_capacity(functions.len());
+        for func in functions {
+            bodies.push(self.generate_function(func)?);
+        }
 
         let mut module = Module::new();
 
-        // create types for each function based on parameter count
+        // create types for each function based on parameter count, then one per runtime function
         let mut types = TypeSection::new();
         for func in functions.iter() {
             let param_count = func._params.len();
             let params = vec![ValType::I32; param_count];
             types.function(params, vec![ValType::I32]);
         }
+        for runtime in &self.runtime {
+            types.function(vec![ValType::I32; runtime.params() as usize], vec![ValType::I32]);
+        }
         module.section(&types);
 
         // Import section
@@ -46,8 +235,8 @@ impl WasmCodegen {
             "env",
             "memory",
             MemoryType {
-                minimum: 16,
-                maximum: Some(256),
+                minimum: INITIAL_MEMORY_PAGES.min(self.limits.memory_pages) as u64,
+                maximum: Some(self.limits.memory_pages as u64),
                 memory64: false,
                 shared: false,
             },
@@ -56,12 +245,13 @@ impl WasmCodegen {
 
         // Function section
         let mut funcs = FunctionSection::new();
-        for idx in 0..functions.len() {
+        for idx in 0..functions.len() + self.runtime.len() {
             funcs.function(idx as u32);
         }
         module.section(&funcs);
 
-        // Global section: gas counter, heap pointer, heap limit
+        // Global section: gas counter, heap pointer, heap limit, call depth, pending error, then the
+        // optional stdout address, the Python globals and the exported gas limit and ABI version
         let mut globals = GlobalSection::new();
         globals.global(
             GlobalType {
@@ -82,25 +272,126 @@ impl WasmCodegen {
                 val_type: ValType::I32,
                 mutable: false,
             },
-            &ConstExpr::i32_const(HEAP_LIMIT),
+            &ConstExpr::i32_const(self.limits.heap_limit()),
+        );
+        globals.global(
+            GlobalType {
+                val_type: ValType::I32,
+                mutable: true,
+            },
+            &ConstExpr::i32_const(0),
         );
+        globals.global(
+            GlobalType {
+                val_type: ValType::I32,
+                mutable: true,
+            },
+            &ConstExpr::i32_const(0),
+        );
+        if self.stdout {
+            globals.global(
+                GlobalType {
+                    val_type: ValType::I32,
+                    mutable: false,
+                },
+                &ConstExpr::i32_const(memory::StdoutLayout::BASE),
+            );
+        }
+        // Python globals start out 0 like any unassigned local
+        for _ in module_globals {
+            globals.global(
+                GlobalType {
+                    val_type: ValType::I32,
+                    mutable: true,
+                },
+                &ConstExpr::i32_const(0),
+            );
+        }
+        // the limits the module was built for, read back by hosts before they run it
+        let gas_limit_global = first_module_global + module_globals.len() as u32;
+        for value in [self.limits.gas_limit(), abi::ABI_VERSION as i32] {
+            globals.global(
+                GlobalType {
+                    val_type: ValType::I32,
+                    mutable: false,
+                },
+                &ConstExpr::i32_const(value),
+            );
+        }
         module.section(&globals);
 
         // Export section
         let mut exports = ExportSection::new();
         exports.export("main", ExportKind::Func, 0);
         exports.export("memory", ExportKind::Memory, 0);
+        if self.stdout {
+            exports.export(STDOUT_EXPORT, ExportKind::Global, STDOUT_GLOBAL);
+        }
+        // the counter a trap ran past the gas limit, as instrumented client modules export it
+        exports.export(fuel::FUEL_EXPORT, ExportKind::Global, self.gas_global);
+        exports.export(abi::HEAP_LIMIT_EXPORT, ExportKind::Global, 2);
+        exports.export(abi::GAS_LIMIT_EXPORT, ExportKind::Global, gas_limit_global);
+        exports.export(abi::ABI_VERSION_EXPORT, ExportKind::Global, gas_limit_global + 1);
         module.section(&exports);
 
+        // sha256's round constants and the literals are passive segments, which memory.init needs
+        // declared up front
+        let sha256 = self.runtime.contains(&Runtime::Digest(HashAlgorithm::Sha256));
+        let segments = if self.literals.is_empty() {
+            sha256 as u32
+        } else {
+            FIRST_LITERAL_SEGMENT + self.literals.len() as u32
+        };
+        if segments > 0 {
+            module.section(&DataCountSection { count: segments });
+        }
+
         // Code section
         let mut code = CodeSection::new();
-        for func in functions {
-            let wasm_func = self.generate_function(func)?;
-            code.function(&wasm_func);
+        for body in &bodies {
+            code.function(body);
+        }
+        let index = |callee: Runtime| self.user_functions + self.runtime.iter().position(|&r| r == callee).unwrap() as u32;
+        for runtime in &self.runtime {
+            code.function(&runtime.body(&index));
         }
         module.section(&code);
 
-        Ok(module.finish())
+        if segments > 0 {
+            let mut data = DataSection::new();
+            // the constants' slot stays, empty, when only literals need segments
+            data.passive(if sha256 { memory::sha256_k_table() } else { Vec::new() });
+            for literal in &self.literals {
+                data.passive(literal.iter().copied());
+            }
+            module.section(&data);
+        }
+
+        let wasm = module.finish();
+        if self.target == Target::Latest {
+            return Ok(wasm);
+        }
+        // the rewrite moves code, and with it the offsets noted for loops and lines
+        let downgraded = target::downgrade(&wasm)?;
+        if let Some(loops) = &mut self.loops {
+            for l in loops.iter_mut() {
+                l.offset = target::downgraded_offset(&downgraded.edits[l.function_index as usize], l.offset);
+            }
+        }
+        if let Some(lines) = &mut self.lines {
+            for (lines, edits) in lines.iter_mut().zip(&downgraded.edits) {
+                for (offset, _) in lines.iter_mut() {
+                    *offset = target::downgraded_offset(edits, *offset as usize) as u32;
+                }
+            }
+        }
+        self.target_helpers = downgraded.helpers;
+        Ok(downgraded.wasm)
+    }
+
+    fn module_global(&self, var: &str) -> Result<u32> {
+        self.module_globals.get(var).copied()
+            .ok_or_else(|| anyhow::anyhow!("Variable '{}' not a module global", var))
     }
 
     fn generate_function(&mut self, func: &IRFunction) -> Result<Function> {
@@ -117,27 +408,30 @@ impl WasmCodegen {
             Function::new(vec![])
         };
 
-        // Gas temp local is the last scratch local
-        let gas_temp_local = func.locals.len() as u32 + scratch_locals - 1;
-
-        self.meter_gas(&mut wasm_func, 10, gas_temp_local);
+        // main is the entry point; every other function is a user function
+        if func.name != "main" {
+            self.enter_frame(&mut wasm_func);
+        }
 
         let base_scratch = func.locals.len() as u32;
 
         for stmt in &func.body {
             let mut next_scratch = base_scratch;
-            self.generate_stmt_with_scratch(&mut wasm_func, stmt, func, gas_temp_local, &mut next_scratch)?;
+            self.generate_stmt_with_scratch(&mut wasm_func, stmt, func, &mut next_scratch)?;
         }
 
         // For main function, return OUTPUT variable if it exists, otherwise 0
         if func.name == "main" {
             if let Some(&output_idx) = func.local_map.get("OUTPUT") {
                 wasm_func.instruction(&Instruction::LocalGet(output_idx));
+            } else if let Some(&output_idx) = self.module_globals.get("OUTPUT") {
+                wasm_func.instruction(&Instruction::GlobalGet(output_idx));
             } else {
                 wasm_func.instruction(&Instruction::I32Const(0));
             }
         } else {
             // For non-main functions, return 0 if no explicit return
+            self.leave_frame(&mut wasm_func);
             wasm_func.instruction(&Instruction::I32Const(0));
         }
         wasm_func.instruction(&Instruction::End);
@@ -145,46 +439,403 @@ impl WasmCodegen {
         Ok(wasm_func)
     }
 
-    fn meter_gas(&self, func: &mut Function, cost: i32, gas_temp_local: u32) {
-        func.instruction(&Instruction::GlobalGet(self.gas_global));
-        func.instruction(&Instruction::I32Const(cost));
+    // Note a Python loop whose `loop` instruction comes next, at the body's current length
+    fn record_loop(&mut self, func: &Function, ir_func: &IRFunction, kind: LoopKind, iterations: Option<u64>) {
+        let Some(loops) = &mut self.loops else {
+            return;
+        };
+        loops.push(SourceLoop {
+            function_index: self.function_indices[&ir_func.name],
+            function: ir_func.name.clone(),
+            // the locals and code so far, without the body's size prefix
+            offset: func.byte_len(),
+            kind,
+            iterations,
+        });
+    }
+
+    // Note the Python line of the code generated next, at the body's current length
+    fn record_line(&mut self, func: &Function, ir_func: &IRFunction, line: u32) {
+        let Some(lines) = &mut self.lines else {
+            return;
+        };
+        let offset = func.byte_len() as u32;
+        let lines = &mut lines[self.function_indices[&ir_func.name] as usize];
+        // a statement that generated nothing leaves its offset to the next one
+        match lines.last_mut() {
+            Some(last) if last.0 == offset => last.1 = line,
+            _ => lines.push((offset, line)),
+        }
+    }
+
+    // Names of every function, runtime functions included, and of the locals the Python code
+    // named; engines print them in backtraces. Compiler temps ("__" prefix) stay unnamed.
+    fn names(&self, ir: &IR) -> NameSection {
+        let IR::Module { functions, .. } = ir;
+        let mut function_names = NameMap::new();
+        let mut local_names = IndirectNameMap::new();
+        for (index, func) in functions.iter().enumerate() {
+            function_names.append(index as u32, &func.name);
+            let mut named: Vec<(u32, &str)> = func.local_map.iter()
+                .filter(|(name, _)| !name.starts_with("__"))
+                .map(|(name, &local)| (local, name.as_str()))
+                .collect();
+            if named.is_empty() {
+                continue;
+            }
+            named.sort();
+            let mut locals = NameMap::new();
+            for (local, name) in named {
+                locals.append(local, name);
+            }
+            local_names.append(index as u32, &locals);
+        }
+        for (position, runtime) in self.runtime.iter().enumerate() {
+            function_names.append(self.user_functions + position as u32, runtime.name());
+        }
+        let first_helper = self.user_functions + self.runtime.len() as u32;
+        for (position, helper) in self.target_helpers.iter().enumerate() {
+            function_names.append(first_helper + position as u32, helper);
+        }
+        function_names.append(first_helper + self.target_helpers.len() as u32, fuel::CHARGE_FUNCTION);
+
+        let mut names = NameSection::new();
+        names.functions(&function_names);
+        names.locals(&local_names);
+        names
+    }
+
+    // Raise ValueError and clamp the exponent to 0 when it is negative: the result would be a float
+    fn check_exponent(&self, func: &mut Function, exp: u32) {
+        func.instruction(&Instruction::LocalGet(exp));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32LtS);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        self.raise(func, ExceptionKind::ValueError);
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(exp));
+        func.instruction(&Instruction::End);
+    }
+
+    // Square-and-multiply over the exponent's bits, metered per bit; `step(func, true)` emits
+    // acc = acc * b and `step(func, false)` emits b = b * b
+    fn square_and_multiply(&self, func: &mut Function, exp: u32, step: impl Fn(&mut Function, bool)) {
+        func.instruction(&Instruction::Block(BlockType::Empty));
+        func.instruction(&Instruction::Loop(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(exp));
+        func.instruction(&Instruction::I32Eqz);
+        func.instruction(&Instruction::BrIf(1));
+
+        func.instruction(&Instruction::LocalGet(exp));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32And);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        step(func, true);
+        func.instruction(&Instruction::End);
+        step(func, false);
+
+        func.instruction(&Instruction::LocalGet(exp));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32ShrU);
+        func.instruction(&Instruction::LocalSet(exp));
+        func.instruction(&Instruction::Br(0));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+    }
+
+    /// Base in `base`, exponent in `base + 1`; pushes base ** exp with wrapping multiplication.
+    /// Uses 3 scratch locals from base
+    fn generate_pow(&self, func: &mut Function, base: u32) {
+        let (b, exp, acc) = (base, base + 1, base + 2);
+        self.check_exponent(func, exp);
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::LocalSet(acc));
+
+        self.square_and_multiply(func, exp, |func, multiply| {
+            let target = if multiply { acc } else { b };
+            func.instruction(&Instruction::LocalGet(target));
+            func.instruction(&Instruction::LocalGet(b));
+            func.instruction(&Instruction::I32Mul);
+            func.instruction(&Instruction::LocalSet(target));
+        });
+        func.instruction(&Instruction::LocalGet(acc));
+    }
+
+    /// Base, exponent and modulus in `base`..`base + 3`; pushes pow(base, exp, mod) with Python's
+    /// sign convention. Products are formed in i64 so nothing wraps. Uses 5 scratch locals from base
+    fn generate_pow_mod(&self, func: &mut Function, base: u32) {
+        let (b, exp, m, acc, rem) = (base, base + 1, base + 2, base + 3, base + 4);
+
+        func.instruction(&Instruction::LocalGet(m));
+        func.instruction(&Instruction::I32Eqz);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        self.raise(func, ExceptionKind::ValueError);
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::LocalSet(m));
+        func.instruction(&Instruction::End);
+        self.check_exponent(func, exp);
+
+        // acc = x * y mod m, remainder taking the sign of m
+        let mul_mod = |func: &mut Function, target: u32, x: u32, y: u32| {
+            func.instruction(&Instruction::LocalGet(x));
+            func.instruction(&Instruction::I64ExtendI32S);
+            func.instruction(&Instruction::LocalGet(y));
+            func.instruction(&Instruction::I64ExtendI32S);
+            func.instruction(&Instruction::I64Mul);
+            func.instruction(&Instruction::LocalGet(m));
+            func.instruction(&Instruction::I64ExtendI32S);
+            func.instruction(&Instruction::I64RemS);
+            func.instruction(&Instruction::I32WrapI64);
+            func.instruction(&Instruction::LocalSet(rem));
+
+            func.instruction(&Instruction::LocalGet(rem));
+            func.instruction(&Instruction::LocalGet(m));
+            func.instruction(&Instruction::I32Add);
+            func.instruction(&Instruction::LocalGet(rem));
+            func.instruction(&Instruction::LocalGet(rem));
+            func.instruction(&Instruction::I32Const(0));
+            func.instruction(&Instruction::I32Ne);
+            func.instruction(&Instruction::LocalGet(rem));
+            func.instruction(&Instruction::LocalGet(m));
+            func.instruction(&Instruction::I32Xor);
+            func.instruction(&Instruction::I32Const(0));
+            func.instruction(&Instruction::I32LtS);
+            func.instruction(&Instruction::I32And);
+            func.instruction(&Instruction::Select);
+            func.instruction(&Instruction::LocalSet(target));
+        };
+
+        // reduce the base, and start from 1 mod m (0 when m is 1 or -1)
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::LocalSet(acc));
+        mul_mod(func, b, b, acc);
+        mul_mod(func, acc, acc, acc);
+
+        self.square_and_multiply(func, exp, |func, multiply| {
+            if multiply {
+                mul_mod(func, acc, acc, b);
+            } else {
+                mul_mod(func, b, b, b);
+            }
+        });
+        func.instruction(&Instruction::LocalGet(acc));
+    }
+
+    /// Increment the call depth, trapping once it passes the limit
+    fn enter_frame(&self, func: &mut Function) {
+        func.instruction(&Instruction::GlobalGet(CALL_DEPTH_GLOBAL));
+        func.instruction(&Instruction::I32Const(1));
         func.instruction(&Instruction::I32Add);
-        func.instruction(&Instruction::LocalTee(gas_temp_local));
-        func.instruction(&Instruction::I32Const(GAS_LIMIT));
-        func.instruction(&Instruction::I32GtS);
+        func.instruction(&Instruction::GlobalSet(CALL_DEPTH_GLOBAL));
+        func.instruction(&Instruction::GlobalGet(CALL_DEPTH_GLOBAL));
+        func.instruction(&Instruction::I32Const(self.max_call_depth as i32));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        memory::trap(func, RuntimeError::RecursionLimit);
+        func.instruction(&Instruction::End);
+    }
+
+    // Traps abort the whole run, so only normal returns need to unwind
+    fn leave_frame(&self, func: &mut Function) {
+        func.instruction(&Instruction::GlobalGet(CALL_DEPTH_GLOBAL));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::GlobalSet(CALL_DEPTH_GLOBAL));
+    }
+
+    /// Record an exception raised mid-expression; the first one wins. The caller carries on
+    /// with a harmless value until the statement's check unwinds. Traps when nothing catches.
+    fn raise(&self, func: &mut Function, kind: ExceptionKind) {
+        if !self.exceptions {
+            memory::trap(func, RuntimeError::uncaught(kind));
+            return;
+        }
+        func.instruction(&Instruction::GlobalGet(ERROR_GLOBAL));
+        func.instruction(&Instruction::I32Eqz);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::I32Const(kind.code()));
+        func.instruction(&Instruction::GlobalSet(ERROR_GLOBAL));
+        func.instruction(&Instruction::End);
+    }
+
+    /// Leave the current statement with the pending error
+    fn unwind(&self, func: &mut Function, depth: Depth, ir_func: &IRFunction) {
+        match depth.unwind {
+            Unwind::Handler(label) => {
+                func.instruction(&Instruction::Br(label));
+            }
+            Unwind::Exit if ir_func.name != "main" => {
+                // the caller's check picks the error up
+                self.leave_frame(func);
+                func.instruction(&Instruction::I32Const(0));
+                func.instruction(&Instruction::Return);
+            }
+            // uncaught: the pending ExceptionKind code is also its RuntimeError code
+            Unwind::Exit => {
+                func.instruction(&Instruction::I32Const(ERROR_SLOT));
+                func.instruction(&Instruction::GlobalGet(ERROR_GLOBAL));
+                func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+                func.instruction(&Instruction::Unreachable);
+            }
+            Unwind::Deferred => {}
+        }
+    }
+
+    /// Unwind if an exception is pending
+    fn check_pending(&self, func: &mut Function, depth: Depth, ir_func: &IRFunction) {
+        if !self.exceptions || matches!(depth.unwind, Unwind::Deferred) {
+            return;
+        }
+        func.instruction(&Instruction::GlobalGet(ERROR_GLOBAL));
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
+        self.unwind(func, depth.nested(1), ir_func);
         func.instruction(&Instruction::End);
-        func.instruction(&Instruction::LocalGet(gas_temp_local));
-        func.instruction(&Instruction::GlobalSet(self.gas_global));
     }
 
-    fn generate_stmt_with_scratch(&mut self, func: &mut Function, stmt: &IRStmt, ir_func: &IRFunction, gas_temp_local: u32, next_scratch: &mut u32) -> Result<()> {
-        self.generate_stmt_with_loop_depth(func, stmt, ir_func, gas_temp_local, next_scratch, 0)
+    /// Check after evaluating `expr` only if it can raise
+    fn check_after(&self, func: &mut Function, expr: &IRExpr, depth: Depth, ir_func: &IRFunction) {
+        if self.may_raise(expr) {
+            self.check_pending(func, depth, ir_func);
+        }
+    }
+
+    fn may_raise(&self, expr: &IRExpr) -> bool {
+        match expr {
+            IRExpr::BinOp { op: BinOp::Div | BinOp::FloorDiv | BinOp::Mod, right, .. } if !matches!(right.const_value(), Some(c) if c != 0) => true,
+            IRExpr::BinOp { op: BinOp::Pow, right, .. } if !matches!(right.const_value(), Some(c) if c >= 0) => true,
+            IRExpr::Call { func, .. } if func == "pow" || func == "bytes" => true,
+            IRExpr::Subscript { .. } | IRExpr::Block { .. } => true,
+            // d.pop(key) raises KeyError
+            IRExpr::MethodCall { method, .. } if method == "pop" => true,
+            IRExpr::Call { func, .. } if self.function_indices.contains_key(func) => true,
+            _ => expr.children().into_iter().any(|e| self.may_raise(e)),
+        }
+    }
+
+    /// Slot count for a dict or set literal with `entries` initial entries
+    fn table_capacity(entries: usize) -> u32 {
+        (entries as u32 * 2).max(MIN_TABLE_CAPACITY)
+    }
+
+    // Call a shared runtime function on the operands on the stack, emitting it on first use
+    fn call_runtime(&mut self, func: &mut Function, runtime: Runtime) {
+        let index = self.runtime_function(runtime);
+        func.instruction(&Instruction::Call(index));
+    }
+
+    // Function index of a shared runtime function, emitting it, and those it calls, on first use
+    fn runtime_function(&mut self, runtime: Runtime) -> u32 {
+        let position = match self.runtime.iter().position(|&r| r == runtime) {
+            Some(position) => position,
+            None => {
+                let position = self.runtime.len();
+                self.runtime.push(runtime);
+                for &callee in runtime.calls() {
+                    self.runtime_function(callee);
+                }
+                position
+            }
+        };
+        self.user_functions + position as u32
+    }
+
+    // Push the slice locals adjust_slice leaves at base..=base+5, the slice runtime's params
+    fn slice_operands(func: &mut Function, base: u32) {
+        for local in base..base + 6 {
+            func.instruction(&Instruction::LocalGet(local));
+        }
+    }
+
+    // For a loop that frees its allocations each iteration (region::rewinds_heap), save the heap
+    // pointer in the next scratch local and return that local
+    fn mark_heap(&self, func: &mut Function, stmt: &IRStmt, ir_func: &IRFunction, next_scratch: &mut u32) -> Option<u32> {
+        if !region::rewinds_heap(stmt, ir_func) {
+            return None;
+        }
+        let mark = *next_scratch;
+        *next_scratch = mark + 1;
+        func.instruction(&Instruction::GlobalGet(memory::HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalSet(mark));
+        Some(mark)
+    }
+
+    // Free everything allocated since the loop's mark, if it has one
+    fn rewind_heap(func: &mut Function, mark: Option<u32>) {
+        if let Some(mark) = mark {
+            func.instruction(&Instruction::LocalGet(mark));
+            func.instruction(&Instruction::GlobalSet(memory::HEAP_PTR_GLOBAL));
+        }
     }
 
-    fn generate_stmt_with_loop_depth(&mut self, func: &mut Function, stmt: &IRStmt, ir_func: &IRFunction, gas_temp_local: u32, next_scratch: &mut u32, loop_depth: u32) -> Result<()> {
+    // Allocate a bytes object with literal contents; uses one scratch local at base
+    fn generate_bytes(&mut self, func: &mut Function, bytes: &[u8], base: u32) {
+        memory::BytesLayout::alloc(func, bytes);
+        func.instruction(&Instruction::LocalSet(base));
+        self.init_literal(func, bytes, base);
+        func.instruction(&Instruction::LocalGet(base));
+    }
+
+    // Fill the string or bytes object at local `ptr` from the literal's passive segment, so a
+    // literal costs the same code whatever its length; identical literals share a segment
+    fn init_literal(&mut self, func: &mut Function, bytes: &[u8], ptr: u32) {
+        if bytes.is_empty() {
+            return;
+        }
+        let index = match self.literals.iter().position(|literal| literal == bytes) {
+            Some(index) => index,
+            None => {
+                self.literals.push(bytes.to_vec());
+                self.literals.len() - 1
+            }
+        };
+
+        func.instruction(&Instruction::LocalGet(ptr));
+        func.instruction(&Instruction::I32Const(8));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32Const(bytes.len() as i32));
+        func.instruction(&Instruction::MemoryInit { mem: 0, data_index: FIRST_LITERAL_SEGMENT + index as u32 });
+    }
+
+    fn generate_stmt_with_scratch(&mut self, func: &mut Function, stmt: &IRStmt, ir_func: &IRFunction, next_scratch: &mut u32) -> Result<()> {
+        self.generate_stmt_with_depth(func, stmt, ir_func, next_scratch, Depth::TOP)
+    }
+
+    fn generate_stmt_with_depth(&mut self, func: &mut Function, stmt: &IRStmt, ir_func: &IRFunction, next_scratch: &mut u32, depth: Depth) -> Result<()> {
         match stmt {
             IRStmt::Assign { var, value } => {
-                self.generate_expr(func, value, ir_func, gas_temp_local, next_scratch)?;
+                self.generate_expr(func, value, ir_func, next_scratch)?;
+                self.check_after(func, value, depth, ir_func);
                 let local_idx = ir_func.local_map.get(var)
                     .ok_or_else(|| anyhow::anyhow!("Variable '{}' not in local_map", var))?;
                 func.instruction(&Instruction::LocalSet(*local_idx));
             }
+            IRStmt::AssignGlobal { var, value } => {
+                self.generate_expr(func, value, ir_func, next_scratch)?;
+                self.check_after(func, value, depth, ir_func);
+                func.instruction(&Instruction::GlobalSet(self.module_global(var)?));
+            }
             IRStmt::SubscriptAssign { target, index, value } => {
                 // generate target, index, value on stack
-                self.generate_expr(func, target, ir_func, gas_temp_local, next_scratch)?;
-                self.generate_expr(func, index, ir_func, gas_temp_local, next_scratch)?;
-                self.generate_expr(func, value, ir_func, gas_temp_local, next_scratch)?;
+                self.generate_expr(func, target, ir_func, next_scratch)?;
+                self.generate_expr(func, index, ir_func, next_scratch)?;
+                self.generate_expr(func, value, ir_func, next_scratch)?;
+                if [target, index, value].into_iter().any(|e| self.may_raise(e)) {
+                    self.check_pending(func, depth, ir_func);
+                }
 
                 let base = *next_scratch;
-                *next_scratch = base + 11;
+                *next_scratch = base + 12;
 
                 // save to locals: target, index, value
                 func.instruction(&Instruction::LocalSet(base + 2));
                 func.instruction(&Instruction::LocalSet(base + 1));
                 func.instruction(&Instruction::LocalSet(base));
 
+                // tuples are immutable
+                memory::TupleLayout::check_mutable(func, base);
+                memory::normalize_index(func, base, base + 1);
+
                 // load type tag from target[0]
                 func.instruction(&Instruction::LocalGet(base));
                 func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
@@ -206,100 +857,323 @@ impl WasmCodegen {
                 func.instruction(&Instruction::LocalGet(base));
                 func.instruction(&Instruction::LocalGet(base + 1));
                 func.instruction(&Instruction::LocalGet(base + 2));
-                memory::DictLayout::insert(func, base + 3, base + 4, base + 5, base + 6, base + 7, base + 8, base + 9, base + 10);
+                let grow = self.runtime_function(Runtime::DictGrow);
+                memory::DictLayout::insert(func, base + 3, grow);
 
                 func.instruction(&Instruction::End);
             }
             IRStmt::Return(expr) => {
-                self.generate_expr(func, expr, ir_func, gas_temp_local, next_scratch)?;
+                self.generate_expr(func, expr, ir_func, next_scratch)?;
+                self.check_after(func, expr, depth, ir_func);
+                if ir_func.name != "main" {
+                    self.leave_frame(func);
+                }
                 func.instruction(&Instruction::Return);
             }
             IRStmt::If { cond, then_block, else_block } => {
-                self.generate_expr(func, cond, ir_func, gas_temp_local, next_scratch)?;
+                self.generate_expr(func, cond, ir_func, next_scratch)?;
+                self.check_after(func, cond, depth, ir_func);
                 func.instruction(&Instruction::If(BlockType::Empty));
                 for s in then_block {
-                    self.generate_stmt_with_loop_depth(func, s, ir_func, gas_temp_local, next_scratch, loop_depth + 1)?;
+                    self.generate_stmt_with_depth(func, s, ir_func, next_scratch, depth.nested(1))?;
                 }
                 if !else_block.is_empty() {
                     func.instruction(&Instruction::Else);
                     for s in else_block {
-                        self.generate_stmt_with_loop_depth(func, s, ir_func, gas_temp_local, next_scratch, loop_depth + 1)?;
+                        self.generate_stmt_with_depth(func, s, ir_func, next_scratch, depth.nested(1))?;
+                    }
+                }
+                func.instruction(&Instruction::End);
+            }
+            IRStmt::IfChain { branches, else_block } => {
+                // One block with a flat run of ifs; a taken branch jumps to its end.
+                // Branch conditions are dead once tested, so each reuses the same scratch.
+                let base_scratch = *next_scratch;
+                func.instruction(&Instruction::Block(BlockType::Empty));
+                for (cond, body) in branches {
+                    *next_scratch = base_scratch;
+                    self.generate_expr(func, cond, ir_func, next_scratch)?;
+                    self.check_after(func, cond, depth.nested(1), ir_func);
+                    func.instruction(&Instruction::If(BlockType::Empty));
+                    for s in body {
+                        self.generate_stmt_with_depth(func, s, ir_func, next_scratch, depth.nested(2))?;
+                    }
+                    func.instruction(&Instruction::Br(1));
+                    func.instruction(&Instruction::End);
+                }
+                *next_scratch = base_scratch;
+                for s in else_block {
+                    self.generate_stmt_with_depth(func, s, ir_func, next_scratch, depth.nested(1))?;
+                }
+                func.instruction(&Instruction::End);
+            }
+            IRStmt::Switch { value, low, targets, arms, default } => {
+                // block $exit { block $default { block $arm_n-1 { ... block $arm_0 { br_table } arm_0 } ... } default }
+                // br_table depth i lands just after $arm_i's end, where arm i's body sits
+                let n = arms.len() as u32;
+                for _ in 0..n + 2 {
+                    func.instruction(&Instruction::Block(BlockType::Empty));
+                }
+
+                let base_scratch = *next_scratch;
+                self.generate_expr(func, value, ir_func, next_scratch)?;
+                self.check_after(func, value, depth.nested(n + 2), ir_func);
+                *next_scratch = base_scratch;
+                if *low != 0 {
+                    func.instruction(&Instruction::I32Const(*low));
+                    func.instruction(&Instruction::I32Sub);
+                }
+                let table: Vec<u32> = targets.iter().map(|t| t.map_or(n, |arm| arm as u32)).collect();
+                func.instruction(&Instruction::BrTable(table.into(), n));
+                func.instruction(&Instruction::End);
+
+                for (i, body) in arms.iter().enumerate() {
+                    // enclosing: $arm_i+1..$arm_n-1, $default, $exit
+                    let exit_depth = n - i as u32;
+                    for s in body {
+                        self.generate_stmt_with_depth(func, s, ir_func, next_scratch, depth.nested(exit_depth + 1))?;
                     }
+                    func.instruction(&Instruction::Br(exit_depth));
+                    func.instruction(&Instruction::End);
+                }
+
+                for s in default {
+                    self.generate_stmt_with_depth(func, s, ir_func, next_scratch, depth.nested(1))?;
                 }
                 func.instruction(&Instruction::End);
             }
             IRStmt::While { cond, body } => {
+                let mark = self.mark_heap(func, stmt, ir_func, next_scratch);
                 func.instruction(&Instruction::Block(BlockType::Empty));
+                self.record_loop(func, ir_func, LoopKind::While, None);
                 func.instruction(&Instruction::Loop(BlockType::Empty));
-                self.meter_gas(func, 1, gas_temp_local);
-                self.generate_expr(func, cond, ir_func, gas_temp_local, next_scratch)?;
+                Self::rewind_heap(func, mark);
+                self.generate_expr(func, cond, ir_func, next_scratch)?;
+                self.check_after(func, cond, depth.nested(2), ir_func);
                 func.instruction(&Instruction::I32Eqz);
                 func.instruction(&Instruction::BrIf(1));
                 for s in body {
-                    // Start with loop_depth = 0 for loop body, increments for nested control structures
-                    self.generate_stmt_with_loop_depth(func, s, ir_func, gas_temp_local, next_scratch, 0)?;
+                    self.generate_stmt_with_depth(func, s, ir_func, next_scratch, depth.loop_body())?;
                 }
                 func.instruction(&Instruction::Br(0));
                 func.instruction(&Instruction::End);
                 func.instruction(&Instruction::End);
+                Self::rewind_heap(func, mark);
             }
-            IRStmt::For { var, iter, body } => {
+            IRStmt::For { var, start, stop, step, body } => {
                 let loop_var = ir_func.local_map.get(var)
                     .ok_or_else(|| anyhow::anyhow!("Loop variable '{}' not in local_map", var))?;
 
+                // range() arguments are evaluated once, before the first iteration
                 let counter = *next_scratch;
-                let body_scratch_base = counter + 1;
+                let stop_local = counter + 1;
+                let step_local = counter + 2;
+                let mut body_scratch_base = counter + 3;
 
-                func.instruction(&Instruction::I32Const(0));
+                let mut arg_scratch = body_scratch_base;
+                self.generate_expr(func, start, ir_func, &mut arg_scratch)?;
                 func.instruction(&Instruction::LocalSet(counter));
+                self.generate_expr(func, stop, ir_func, &mut arg_scratch)?;
+                func.instruction(&Instruction::LocalSet(stop_local));
+                if self.may_raise(start) || self.may_raise(stop) {
+                    self.check_pending(func, depth, ir_func);
+                }
+
+                // Constant steps pick the comparison direction at compile time
+                let const_step = step.const_value();
+                if const_step.is_none() {
+                    self.generate_expr(func, step, ir_func, &mut arg_scratch)?;
+                    func.instruction(&Instruction::LocalTee(step_local));
+                    self.check_after(func, step, depth, ir_func);
+
+                    // range() with a zero step is a ValueError in Python
+                    func.instruction(&Instruction::I32Eqz);
+                    func.instruction(&Instruction::If(BlockType::Empty));
+                    self.raise(func, ExceptionKind::ValueError);
+                    self.unwind(func, depth.nested(1), ir_func);
+                    func.instruction(&Instruction::End);
+                }
+
+                let mark = self.mark_heap(func, stmt, ir_func, &mut body_scratch_base);
 
                 func.instruction(&Instruction::Block(BlockType::Empty));
+                let iterations = match (start.const_value(), stop.const_value(), const_step) {
+                    (Some(start), Some(stop), Some(step)) => Some(trip_count(start, stop, step)),
+                    _ => None,
+                };
+                self.record_loop(func, ir_func, LoopKind::For, iterations);
                 func.instruction(&Instruction::Loop(BlockType::Empty));
-                self.meter_gas(func, 1, gas_temp_local);
-
-                func.instruction(&Instruction::LocalGet(counter));
-                let mut iter_scratch = body_scratch_base;
-                self.generate_expr(func, iter, ir_func, gas_temp_local, &mut iter_scratch)?;
-                func.instruction(&Instruction::I32GeS);
+                Self::rewind_heap(func, mark);
+
+                // Exit when counter reaches stop in the direction of travel
+                match const_step {
+                    Some(c) => {
+                        func.instruction(&Instruction::LocalGet(counter));
+                        func.instruction(&Instruction::LocalGet(stop_local));
+                        func.instruction(&if c > 0 { Instruction::I32GeS } else { Instruction::I32LeS });
+                    }
+                    None => {
+                        func.instruction(&Instruction::LocalGet(step_local));
+                        func.instruction(&Instruction::I32Const(0));
+                        func.instruction(&Instruction::I32GtS);
+                        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+                        func.instruction(&Instruction::LocalGet(counter));
+                        func.instruction(&Instruction::LocalGet(stop_local));
+                        func.instruction(&Instruction::I32GeS);
+                        func.instruction(&Instruction::Else);
+                        func.instruction(&Instruction::LocalGet(counter));
+                        func.instruction(&Instruction::LocalGet(stop_local));
+                        func.instruction(&Instruction::I32LeS);
+                        func.instruction(&Instruction::End);
+                    }
+                }
                 func.instruction(&Instruction::BrIf(1));
 
                 func.instruction(&Instruction::LocalGet(counter));
                 func.instruction(&Instruction::LocalSet(*loop_var));
+                // only a body that continues gets the block continue leaves
+                let continues = body.iter().any(IRStmt::continues);
+                let body_depth = if continues {
+                    func.instruction(&Instruction::Block(BlockType::Empty));
+                    depth.stepped_loop_body()
+                } else {
+                    depth.loop_body()
+                };
                 for s in body {
                     let mut body_scratch = body_scratch_base;
-                    // Start with loop_depth = 0 for loop body, increments for nested control structures
-                    self.generate_stmt_with_loop_depth(func, s, ir_func, gas_temp_local, &mut body_scratch, 0)?;
+                    self.generate_stmt_with_depth(func, s, ir_func, &mut body_scratch, body_depth)?;
+                }
+                if continues {
+                    func.instruction(&Instruction::End);
                 }
 
                 func.instruction(&Instruction::LocalGet(counter));
-                func.instruction(&Instruction::I32Const(1));
+                match const_step {
+                    Some(c) => func.instruction(&Instruction::I32Const(c)),
+                    None => func.instruction(&Instruction::LocalGet(step_local)),
+                };
                 func.instruction(&Instruction::I32Add);
                 func.instruction(&Instruction::LocalSet(counter));
 
                 func.instruction(&Instruction::Br(0));
                 func.instruction(&Instruction::End);
                 func.instruction(&Instruction::End);
+                Self::rewind_heap(func, mark);
             }
             IRStmt::Break => {
                 // Break out of innermost loop
-                // loop_depth tracks nested control structures (If, etc.)
-                // We need to break to the Block surrounding the Loop, which is at depth loop_depth + 1
-                func.instruction(&Instruction::Br(loop_depth + 1));
+                // depth.in_loop tracks nested control structures (If, etc.)
+                // We need to break to the Block surrounding the Loop
+                func.instruction(&Instruction::Br(depth.break_label()));
+            }
+            IRStmt::Continue => {
+                // the while loop's label re-tests its condition; a for loop's body block ends
+                // at the counter step
+                func.instruction(&Instruction::Br(depth.in_loop));
             }
             IRStmt::Expr(expr) => {
-                self.generate_expr(func, expr, ir_func, gas_temp_local, next_scratch)?;
+                self.generate_expr(func, expr, ir_func, next_scratch)?;
+                self.check_after(func, expr, depth, ir_func);
                 func.instruction(&Instruction::Drop);
             }
             IRStmt::Block(stmts) => {
                 for s in stmts {
-                    self.generate_stmt_with_loop_depth(func, s, ir_func, gas_temp_local, next_scratch, loop_depth)?;
+                    self.generate_stmt_with_depth(func, s, ir_func, next_scratch, depth)?;
                 }
             }
+            IRStmt::Raise(Some(kind)) if !self.exceptions => memory::trap(func, RuntimeError::uncaught(*kind)),
+            IRStmt::Raise(kind) => {
+                match kind {
+                    Some(kind) => func.instruction(&Instruction::I32Const(kind.code())),
+                    // re-raise what the innermost handler caught
+                    None => func.instruction(&Instruction::LocalGet(*self.handling.last()
+                        .ok_or_else(|| anyhow::anyhow!("bare raise outside an except block"))?)),
+                };
+                func.instruction(&Instruction::GlobalSet(ERROR_GLOBAL));
+                self.unwind(func, depth, ir_func);
+            }
+            IRStmt::Line(line) => self.record_line(func, ir_func, *line),
+            IRStmt::AssertFail { msg_hash } => {
+                // not an exception: try/except can't swallow it, the run just stops
+                func.instruction(&Instruction::I32Const(ASSERT_SLOT));
+                func.instruction(&Instruction::I32Const(ASSERTION_FAILED));
+                func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+                func.instruction(&Instruction::I32Const(ASSERT_SLOT));
+                func.instruction(&Instruction::I32Const(*msg_hash as i32));
+                func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+                memory::trap(func, RuntimeError::AssertionFailed);
+            }
+            IRStmt::Try { body, handlers, else_block, finally } => {
+                // block $done { block $dispatch { body; else; br $done } dispatch } finally
+                // A failing body branches to $dispatch; handlers and else unwind to $done,
+                // leaving the error pending for finally and the enclosing scope.
+                let code = *next_scratch;
+                *next_scratch = code + 1;
+
+                func.instruction(&Instruction::Block(BlockType::Empty));
+                func.instruction(&Instruction::Block(BlockType::Empty));
+                let in_body = Depth { in_loop: depth.in_loop + 2, unwind: Unwind::Handler(0), ..depth };
+                for s in body {
+                    self.generate_stmt_with_depth(func, s, ir_func, next_scratch, in_body)?;
+                }
+                let in_else = Depth { in_loop: depth.in_loop + 2, unwind: Unwind::Handler(1), ..depth };
+                for s in else_block {
+                    self.generate_stmt_with_depth(func, s, ir_func, next_scratch, in_else)?;
+                }
+                func.instruction(&Instruction::Br(1));
+                func.instruction(&Instruction::End);
+
+                // take the pending error so handlers start clean
+                func.instruction(&Instruction::GlobalGet(ERROR_GLOBAL));
+                func.instruction(&Instruction::LocalSet(code));
+                func.instruction(&Instruction::I32Const(0));
+                func.instruction(&Instruction::GlobalSet(ERROR_GLOBAL));
+
+                self.handling.push(code);
+                let in_handler = Depth { in_loop: depth.in_loop + 2, unwind: Unwind::Handler(1), ..depth };
+                for (kinds, handler) in handlers {
+                    for (i, kind) in kinds.iter().enumerate() {
+                        func.instruction(&Instruction::LocalGet(code));
+                        func.instruction(&Instruction::I32Const(kind.code()));
+                        func.instruction(&Instruction::I32Eq);
+                        if i > 0 {
+                            func.instruction(&Instruction::I32Or);
+                        }
+                    }
+                    func.instruction(&Instruction::If(BlockType::Empty));
+                    for s in handler {
+                        self.generate_stmt_with_depth(func, s, ir_func, next_scratch, in_handler)?;
+                    }
+                    func.instruction(&Instruction::Br(1));
+                    func.instruction(&Instruction::End);
+                }
+                self.handling.pop();
+
+                // no handler matched: keep unwinding
+                func.instruction(&Instruction::LocalGet(code));
+                func.instruction(&Instruction::GlobalSet(ERROR_GLOBAL));
+                func.instruction(&Instruction::End);
+
+                if !finally.is_empty() {
+                    // set the pending error aside; one raised by finally replaces it
+                    func.instruction(&Instruction::GlobalGet(ERROR_GLOBAL));
+                    func.instruction(&Instruction::LocalSet(code));
+                    func.instruction(&Instruction::I32Const(0));
+                    func.instruction(&Instruction::GlobalSet(ERROR_GLOBAL));
+                    for s in finally {
+                        self.generate_stmt_with_depth(func, s, ir_func, next_scratch, depth)?;
+                    }
+                    func.instruction(&Instruction::LocalGet(code));
+                    func.instruction(&Instruction::GlobalSet(ERROR_GLOBAL));
+                }
+                self.check_pending(func, depth, ir_func);
+                *next_scratch = code;
+            }
         }
         Ok(())
     }
 
-    fn generate_expr(&mut self, func: &mut Function, expr: &IRExpr, ir_func: &IRFunction, gas_temp_local: u32, next_scratch: &mut u32) -> Result<()> {
+    fn generate_expr(&mut self, func: &mut Function, expr: &IRExpr, ir_func: &IRFunction, next_scratch: &mut u32) -> Result<()> {
         match expr {
             IRExpr::Const(val) => {
                 func.instruction(&Instruction::I32Const(*val));
@@ -309,15 +1183,18 @@ impl WasmCodegen {
                     .ok_or_else(|| anyhow::anyhow!("Variable '{}' not in local_map", var))?;
                 func.instruction(&Instruction::LocalGet(*idx));
             }
+            IRExpr::LoadGlobal(var) => {
+                func.instruction(&Instruction::GlobalGet(self.module_global(var)?));
+            }
             IRExpr::UnaryOp { op, operand } => {
                 match op {
                     UnaryOp::Neg => {
                         func.instruction(&Instruction::I32Const(0));
-                        self.generate_expr(func, operand, ir_func, gas_temp_local, next_scratch)?;
+                        self.generate_expr(func, operand, ir_func, next_scratch)?;
                         func.instruction(&Instruction::I32Sub);
                     }
                     UnaryOp::Not => {
-                        self.generate_expr(func, operand, ir_func, gas_temp_local, next_scratch)?;
+                        self.generate_expr(func, operand, ir_func, next_scratch)?;
                         func.instruction(&Instruction::I32Eqz);
                     }
                 }
@@ -326,44 +1203,10 @@ impl WasmCodegen {
                 // Runtime type dispatch for string operations
                 match op {
                     BinOp::Add => {
-                        // Type-aware addition: string concatenation or integer addition
-                        let saved_scratch = *next_scratch;
-                        let left_local = *next_scratch;
-                        let right_local = left_local + 1;
-                        *next_scratch = right_local + 1;
-
-                        self.generate_expr(func, left, ir_func, gas_temp_local, next_scratch)?;
-                        func.instruction(&Instruction::LocalSet(left_local));
-
-                        self.generate_expr(func, right, ir_func, gas_temp_local, next_scratch)?;
-                        func.instruction(&Instruction::LocalSet(right_local));
-
-                        // Check if left is heap pointer (>= 1024) AND is string (type tag == 3)
-                        func.instruction(&Instruction::LocalGet(left_local));
-                        func.instruction(&Instruction::I32Const(1024));
-                        func.instruction(&Instruction::I32GeU);
-
-                        func.instruction(&Instruction::LocalGet(left_local));
-                        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
-                        func.instruction(&Instruction::I32Const(3)); // TYPE_STRING
-                        func.instruction(&Instruction::I32Eq);
-
-                        func.instruction(&Instruction::I32And);
-
-                        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
-                        // String concatenation path
-                        func.instruction(&Instruction::LocalGet(left_local));
-                        func.instruction(&Instruction::LocalGet(right_local));
-                        let base = *next_scratch;
-                        memory::StringLayout::concat(func, base, base + 1, base + 2, base + 3, base + 4, base + 5, base + 6);
-                        func.instruction(&Instruction::Else);
-                        // Integer addition path
-                        func.instruction(&Instruction::LocalGet(left_local));
-                        func.instruction(&Instruction::LocalGet(right_local));
-                        func.instruction(&Instruction::I32Add);
-                        func.instruction(&Instruction::End);
-
-                        *next_scratch = saved_scratch;
+                        // Type-aware addition: string or bytes concatenation or integer addition
+                        self.generate_expr(func, left, ir_func, next_scratch)?;
+                        self.generate_expr(func, right, ir_func, next_scratch)?;
+                        self.call_runtime(func, Runtime::Add);
                     }
                     BinOp::Eq => {
                         // Type-aware equality: string equals or integer equals
@@ -372,42 +1215,73 @@ impl WasmCodegen {
                         let right_local = left_local + 1;
                         *next_scratch = right_local + 1;
 
-                        self.generate_expr(func, left, ir_func, gas_temp_local, next_scratch)?;
+                        self.generate_expr(func, left, ir_func, next_scratch)?;
                         func.instruction(&Instruction::LocalSet(left_local));
 
-                        self.generate_expr(func, right, ir_func, gas_temp_local, next_scratch)?;
+                        self.generate_expr(func, right, ir_func, next_scratch)?;
                         func.instruction(&Instruction::LocalSet(right_local));
 
-                        // Check if left is heap pointer (>= 1024) AND is string (type tag == 3)
-                        func.instruction(&Instruction::LocalGet(left_local));
-                        func.instruction(&Instruction::I32Const(1024));
-                        func.instruction(&Instruction::I32GeU);
-
-                        func.instruction(&Instruction::LocalGet(left_local));
-                        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
-                        func.instruction(&Instruction::I32Const(3)); // TYPE_STRING
-                        func.instruction(&Instruction::I32Eq);
-
-                        func.instruction(&Instruction::I32And);
+                        // Check if left is a string (heap pointer with type tag 3)
+                        memory::StringLayout::is_string(func, left_local);
 
                         func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
                         // String equality path
                         func.instruction(&Instruction::LocalGet(left_local));
                         func.instruction(&Instruction::LocalGet(right_local));
-                        let base = *next_scratch;
-                        memory::StringLayout::equals(func, base, base + 1, base + 2, base + 3, base + 4);
+                        self.call_runtime(func, Runtime::StringEquals);
+                        func.instruction(&Instruction::Else);
+                        memory::BytesLayout::is_bytes(func, left_local);
+                        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+                        // Bytes equality path: bytes only ever equal bytes
+                        memory::BytesLayout::is_bytes(func, right_local);
+                        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+                        func.instruction(&Instruction::LocalGet(left_local));
+                        func.instruction(&Instruction::LocalGet(right_local));
+                        self.call_runtime(func, Runtime::StringEquals);
+                        func.instruction(&Instruction::Else);
+                        func.instruction(&Instruction::I32Const(0));
+                        func.instruction(&Instruction::End);
                         func.instruction(&Instruction::Else);
                         // Integer equality path
                         func.instruction(&Instruction::LocalGet(left_local));
                         func.instruction(&Instruction::LocalGet(right_local));
                         func.instruction(&Instruction::I32Eq);
                         func.instruction(&Instruction::End);
+                        func.instruction(&Instruction::End);
+
+                        *next_scratch = saved_scratch;
+                    }
+                    BinOp::In => {
+                        let base = *next_scratch;
+                        *next_scratch = base + 7;
 
-                        *next_scratch = saved_scratch;
+                        // container first so the probe pops [container, item]
+                        self.generate_expr(func, right, ir_func, next_scratch)?;
+                        func.instruction(&Instruction::LocalSet(base));
+                        self.generate_expr(func, left, ir_func, next_scratch)?;
+                        func.instruction(&Instruction::LocalSet(base + 1));
+
+                        func.instruction(&Instruction::LocalGet(base));
+                        func.instruction(&Instruction::LocalGet(base + 1));
+                        self.call_runtime(func, Runtime::Contains);
+
+                        *next_scratch = base;
+                    }
+                    BinOp::Pow => {
+                        let base = *next_scratch;
+                        *next_scratch = base + 3;
+
+                        self.generate_expr(func, left, ir_func, next_scratch)?;
+                        func.instruction(&Instruction::LocalSet(base));
+                        self.generate_expr(func, right, ir_func, next_scratch)?;
+                        func.instruction(&Instruction::LocalSet(base + 1));
+                        self.generate_pow(func, base);
+
+                        *next_scratch = base;
                     }
                     _ => {
-                        self.generate_expr(func, left, ir_func, gas_temp_local, next_scratch)?;
-                        self.generate_expr(func, right, ir_func, gas_temp_local, next_scratch)?;
+                        self.generate_expr(func, left, ir_func, next_scratch)?;
+                        self.generate_expr(func, right, ir_func, next_scratch)?;
 
                         match op {
                             BinOp::FloorDiv => {
@@ -422,7 +1296,9 @@ impl WasmCodegen {
                         func.instruction(&Instruction::LocalGet(scratch1));
                         func.instruction(&Instruction::I32Eqz);
                         func.instruction(&Instruction::If(BlockType::Empty));
-                        func.instruction(&Instruction::Unreachable);
+                        self.raise(func, ExceptionKind::ZeroDivisionError);
+                        func.instruction(&Instruction::I32Const(1));
+                        func.instruction(&Instruction::LocalSet(scratch1));
                         func.instruction(&Instruction::End);
 
                         func.instruction(&Instruction::LocalGet(scratch0));
@@ -470,7 +1346,9 @@ impl WasmCodegen {
                         func.instruction(&Instruction::LocalGet(scratch1));
                         func.instruction(&Instruction::I32Eqz);
                         func.instruction(&Instruction::If(BlockType::Empty));
-                        func.instruction(&Instruction::Unreachable);
+                        self.raise(func, ExceptionKind::ZeroDivisionError);
+                        func.instruction(&Instruction::I32Const(1));
+                        func.instruction(&Instruction::LocalSet(scratch1));
                         func.instruction(&Instruction::End);
 
                         // Compute C-style remainder: r = a % b
@@ -505,6 +1383,18 @@ impl WasmCodegen {
                     }
                     BinOp::Div => {
                         // Integer division only (no floats for determinism)
+                        if self.exceptions && !matches!(right.const_value(), Some(c) if c != 0) {
+                            // a zero divisor must raise instead of trapping in i32.div_s
+                            let divisor = *next_scratch;
+                            func.instruction(&Instruction::LocalTee(divisor));
+                            func.instruction(&Instruction::I32Eqz);
+                            func.instruction(&Instruction::If(BlockType::Empty));
+                            self.raise(func, ExceptionKind::ZeroDivisionError);
+                            func.instruction(&Instruction::I32Const(1));
+                            func.instruction(&Instruction::LocalSet(divisor));
+                            func.instruction(&Instruction::End);
+                            func.instruction(&Instruction::LocalGet(divisor));
+                        }
                         func.instruction(&Instruction::I32DivS);
                     }
                             _ => {
@@ -516,6 +1406,9 @@ impl WasmCodegen {
                                     BinOp::Le => Instruction::I32LeS,
                                     BinOp::Gt => Instruction::I32GtS,
                                     BinOp::Ge => Instruction::I32GeS,
+                                    BinOp::Shl => Instruction::I32Shl,
+                                    BinOp::ShrS => Instruction::I32ShrS,
+                                    BinOp::BitAnd => Instruction::I32And,
                                     _ => unreachable!(),
                                 };
                                 func.instruction(&instr);
@@ -533,110 +1426,353 @@ impl WasmCodegen {
                     let base = *next_scratch;
                     *next_scratch = base + 8;
 
-                    self.generate_expr(func, &args[0], ir_func, gas_temp_local, next_scratch)?;
-                    memory::StringLayout::from_int(func, base, base + 1, base + 2, base + 3, base + 4, base + 5, base + 6, base + 7);
+                    self.generate_expr(func, &args[0], ir_func, next_scratch)?;
+                    self.call_runtime(func, Runtime::FromInt);
 
                     *next_scratch = base;
                     return Ok(());
                 }
 
-                // Handle hashlib.sha256() function
-                if fname == "hashlib.sha256" {
+                // print(a, b) appends "a b\n" to the stdout buffer and returns None
+                if fname == "print" {
+                    let base = *next_scratch;
+                    let values = base + 14;
+                    *next_scratch = values + args.len() as u32;
+
+                    // evaluate every argument before writing, as Python does
+                    for (i, arg) in args.iter().enumerate() {
+                        self.generate_expr(func, arg, ir_func, next_scratch)?;
+                        func.instruction(&Instruction::LocalSet(values + i as u32));
+                    }
+                    // a raised argument leaves nothing printed
+                    let guarded = self.exceptions && args.iter().any(|a| self.may_raise(a));
+                    if guarded {
+                        func.instruction(&Instruction::GlobalGet(ERROR_GLOBAL));
+                        func.instruction(&Instruction::I32Eqz);
+                        func.instruction(&Instruction::If(BlockType::Empty));
+                    }
+                    let (text, len, counter, byte, pos) = (base, base + 1, base + 2, base + 3, base + 4);
+                    for i in 0..args.len() as u32 {
+                        if i > 0 {
+                            memory::StdoutLayout::write_const(func, b' ', byte, pos);
+                        }
+                        // strings print as they are; anything else the way str() renders it
+                        memory::StringLayout::is_string(func, values + i);
+                        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+                        func.instruction(&Instruction::LocalGet(values + i));
+                        func.instruction(&Instruction::Else);
+                        func.instruction(&Instruction::LocalGet(values + i));
+                        self.call_runtime(func, Runtime::FromInt);
+                        func.instruction(&Instruction::End);
+                        func.instruction(&Instruction::LocalSet(text));
+                        memory::StdoutLayout::write_string(func, text, len, counter, byte, pos);
+                    }
+                    memory::StdoutLayout::write_const(func, b'\n', byte, pos);
+                    if guarded {
+                        func.instruction(&Instruction::End);
+                    }
+                    func.instruction(&Instruction::I32Const(0));
+
+                    *next_scratch = base;
+                    return Ok(());
+                }
+
+                // Handle builtin len() function
+                if fname == "len" {
                     if args.len() != 1 {
-                        bail!("hashlib.sha256() takes exactly 1 argument");
+                        bail!("len() takes exactly 1 argument");
+                    }
+                    let base = *next_scratch;
+                    *next_scratch = base + 1;
+
+                    self.generate_expr(func, &args[0], ir_func, next_scratch)?;
+                    memory::len(func, base);
+
+                    *next_scratch = base;
+                    return Ok(());
+                }
+
+                // bytes(seq) from a list or tuple of ints; one out of range raises ValueError
+                if fname == "bytes" {
+                    let base = *next_scratch;
+                    *next_scratch = base + 5;
+
+                    self.generate_expr(func, &args[0], ir_func, next_scratch)?;
+                    func.instruction(&Instruction::LocalSet(base));
+                    memory::BytesLayout::from_sequence(func, base, &|func| self.raise(func, ExceptionKind::ValueError));
+
+                    *next_scratch = base;
+                    return Ok(());
+                }
+
+                // type(x): the tag of a heap object, 0 for an int
+                if fname == "type" {
+                    let base = *next_scratch;
+                    *next_scratch = base + 1;
+
+                    self.generate_expr(func, &args[0], ir_func, next_scratch)?;
+                    memory::type_of(func, base);
+
+                    *next_scratch = base;
+                    return Ok(());
+                }
+
+                // abs(x) for ints
+                if fname == "abs" {
+                    let base = *next_scratch;
+                    *next_scratch = base + 1;
+
+                    self.generate_expr(func, &args[0], ir_func, next_scratch)?;
+                    func.instruction(&Instruction::LocalTee(base));
+                    func.instruction(&Instruction::I32Const(0));
+                    func.instruction(&Instruction::LocalGet(base));
+                    func.instruction(&Instruction::I32Sub);
+                    func.instruction(&Instruction::LocalGet(base));
+                    func.instruction(&Instruction::I32Const(0));
+                    func.instruction(&Instruction::I32GeS);
+                    func.instruction(&Instruction::Select);
+
+                    *next_scratch = base;
+                    return Ok(());
+                }
+
+                // pow(base, exp, mod); two-argument pow is lowered to **
+                if fname == "pow" {
+                    let base = *next_scratch;
+                    *next_scratch = base + 5;
+
+                    for (i, arg) in args.iter().enumerate() {
+                        self.generate_expr(func, arg, ir_func, next_scratch)?;
+                        func.instruction(&Instruction::LocalSet(base + i as u32));
+                    }
+                    self.generate_pow_mod(func, base);
+
+                    *next_scratch = base;
+                    return Ok(());
+                }
+
+                // min()/max(): one argument folds a list/tuple, several compare ints
+                if fname == "min" || fname == "max" {
+                    let op = if fname == "min" { memory::Reduce::Min } else { memory::Reduce::Max };
+                    let base = *next_scratch;
+                    *next_scratch = base + 5;
+
+                    if args.len() == 1 {
+                        self.generate_expr(func, &args[0], ir_func, next_scratch)?;
+                        func.instruction(&Instruction::LocalSet(base));
+                        memory::reduce(func, base, op);
+                    } else {
+                        // acc in base, candidate in base + 1; first of equal values wins
+                        self.generate_expr(func, &args[0], ir_func, next_scratch)?;
+                        func.instruction(&Instruction::LocalSet(base));
+                        for arg in &args[1..] {
+                            self.generate_expr(func, arg, ir_func, next_scratch)?;
+                            func.instruction(&Instruction::LocalTee(base + 1));
+                            func.instruction(&Instruction::LocalGet(base));
+                            func.instruction(&Instruction::LocalGet(base + 1));
+                            func.instruction(&Instruction::LocalGet(base));
+                            func.instruction(&if op == memory::Reduce::Min { Instruction::I32LtS } else { Instruction::I32GtS });
+                            func.instruction(&Instruction::Select);
+                            func.instruction(&Instruction::LocalSet(base));
+                        }
+                        func.instruction(&Instruction::LocalGet(base));
+                    }
+
+                    *next_scratch = base;
+                    return Ok(());
+                }
+
+                // sum(seq, start=0)
+                if fname == "sum" {
+                    let base = *next_scratch;
+                    *next_scratch = base + 5;
+
+                    self.generate_expr(func, &args[0], ir_func, next_scratch)?;
+                    func.instruction(&Instruction::LocalSet(base));
+                    match args.get(1) {
+                        Some(start) => self.generate_expr(func, start, ir_func, next_scratch)?,
+                        None => { func.instruction(&Instruction::I32Const(0)); }
                     }
+                    func.instruction(&Instruction::LocalSet(base + 1));
+                    memory::reduce(func, base, memory::Reduce::Sum);
+
+                    *next_scratch = base;
+                    return Ok(());
+                }
+
+                // sorted(seq): copy into a new list, then merge sort it
+                if fname == "sorted" {
                     let base = *next_scratch;
+                    *next_scratch = base + 11;
+
+                    self.generate_expr(func, &args[0], ir_func, next_scratch)?;
+                    func.instruction(&Instruction::LocalSet(base));
+                    memory::TupleLayout::check_sequence(func, base);
+
+                    // full slice: start 0, step 1, count len (locals per StringLayout::slice)
+                    func.instruction(&Instruction::I32Const(0));
+                    func.instruction(&Instruction::LocalSet(base + 2));
+                    func.instruction(&Instruction::I32Const(1));
+                    func.instruction(&Instruction::LocalSet(base + 4));
+                    func.instruction(&Instruction::LocalGet(base));
+                    func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+                    func.instruction(&Instruction::LocalSet(base + 5));
+                    Self::slice_operands(func, base);
+                    self.call_runtime(func, Runtime::ListSlice);
+                    func.instruction(&Instruction::LocalSet(base));
+
+                    // a sorted tuple is still a list
+                    func.instruction(&Instruction::LocalGet(base));
+                    func.instruction(&Instruction::I32Const(1)); // TYPE_LIST
+                    func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+                    memory::ListLayout::sort(func, base);
+                    func.instruction(&Instruction::LocalGet(base));
+
+                    *next_scratch = base;
+                    return Ok(());
+                }
+
+                // Handle hashlib.sha256() function
+                // Returns a hash object holding the message so far and its digest
+                if fname == "hashlib.sha256" || fname == "hashlib.sha3_256" {
+                    if args.len() > 1 {
+                        bail!("{}() takes at most 1 argument", fname);
+                    }
+                    let algorithm = if fname == "hashlib.sha256" {
+                        HashAlgorithm::Sha256
+                    } else {
+                        HashAlgorithm::Sha3_256
+                    };
+                    let msg_local = *next_scratch;
+                    let base = msg_local + 1;
                     *next_scratch = base + 160; // SHA256 needs 98 locals (base + 97) + message schedule array
 
-                    self.generate_expr(func, &args[0], ir_func, gas_temp_local, next_scratch)?;
-                    memory::sha256(func, base);
+                    if let Some(arg) = args.first() {
+                        self.generate_expr(func, arg, ir_func, next_scratch)?;
+                        func.instruction(&Instruction::LocalTee(msg_local));
+                        func.instruction(&Instruction::LocalGet(msg_local));
+                        self.call_runtime(func, Runtime::Digest(algorithm));
+                    } else {
+                        // Empty message: digest is known at compile time
+                        let digest: [u8; 32] = match algorithm {
+                            HashAlgorithm::Sha256 => Sha256::digest([]).into(),
+                            HashAlgorithm::Sha3_256 => Sha3_256::digest([]).into(),
+                        };
+                        self.generate_bytes(func, &[], base);
+                        self.generate_bytes(func, &digest, base);
+                    }
+                    memory::HasherLayout::alloc(func, algorithm, base, base + 1, base + 2);
+
+                    *next_scratch = msg_local;
+                    return Ok(());
+                }
+
+                // Handle builtin keccak256() function
+                if fname == "keccak256" {
+                    if args.len() != 1 {
+                        bail!("keccak256() takes exactly 1 argument");
+                    }
+                    let base = *next_scratch;
+                    *next_scratch = base + 7;
+
+                    self.generate_expr(func, &args[0], ir_func, next_scratch)?;
+                    self.call_runtime(func, Runtime::Keccak256);
 
                     *next_scratch = base;
                     return Ok(());
                 }
 
                 for arg in args {
-                    self.generate_expr(func, arg, ir_func, gas_temp_local, next_scratch)?;
+                    self.generate_expr(func, arg, ir_func, next_scratch)?;
                 }
                 let func_idx = self.function_indices.get(fname)
                     .ok_or_else(|| anyhow::anyhow!("Function '{}' not found", fname))?;
                 func.instruction(&Instruction::Call(*func_idx));
             }
-            IRExpr::List(elements) => {
+            IRExpr::List(elements) | IRExpr::Tuple(elements) => {
                 let length = elements.len() as u32;
                 let scratch0 = *next_scratch;
                 let scratch1 = scratch0 + 1;
-                *next_scratch = scratch1 + 1;
+                *next_scratch = scratch1 + 2;
 
-                memory::ListLayout::alloc(func, length);
+                if let IRExpr::Tuple(_) = expr {
+                    memory::TupleLayout::alloc(func, length);
+                } else {
+                    memory::ListLayout::alloc(func, length);
+                }
                 func.instruction(&Instruction::LocalSet(scratch0));
 
                 for (i, elem) in elements.iter().enumerate() {
                     func.instruction(&Instruction::LocalGet(scratch0));
                     func.instruction(&Instruction::I32Const(i as i32));
-                    self.generate_expr(func, elem, ir_func, gas_temp_local, next_scratch)?;
-                    memory::ListLayout::store_element(func, scratch1);
+                    self.generate_expr(func, elem, ir_func, next_scratch)?;
+                    memory::ListLayout::store_element(func, scratch1, scratch1 + 1);
                 }
 
                 func.instruction(&Instruction::LocalGet(scratch0));
             }
+            IRExpr::Unpack { value, count } => {
+                let ptr = *next_scratch;
+                *next_scratch = ptr + 1;
+
+                self.generate_expr(func, value, ir_func, next_scratch)?;
+                memory::TupleLayout::check_unpack(func, ptr, *count);
+
+                *next_scratch = ptr;
+            }
+            IRExpr::Sequence(value) => {
+                let ptr = *next_scratch;
+                *next_scratch = ptr + 1;
+
+                self.generate_expr(func, value, ir_func, next_scratch)?;
+                func.instruction(&Instruction::LocalSet(ptr));
+                memory::TupleLayout::check_iterable(func, ptr);
+                func.instruction(&Instruction::LocalGet(ptr));
+
+                *next_scratch = ptr;
+            }
             IRExpr::Dict(pairs) => {
-                let capacity = (pairs.len() * 2).max(8) as u32;
+                let capacity = Self::table_capacity(pairs.len());
                 let base = *next_scratch;
-                *next_scratch = base + 8;
+                *next_scratch = base + 9;
 
                 memory::DictLayout::alloc(func, capacity, base, base + 1);
                 func.instruction(&Instruction::LocalSet(base));
 
                 for (key_expr, val_expr) in pairs {
                     func.instruction(&Instruction::LocalGet(base));
-                    self.generate_expr(func, key_expr, ir_func, gas_temp_local, next_scratch)?;
-                    self.generate_expr(func, val_expr, ir_func, gas_temp_local, next_scratch)?;
-                    memory::DictLayout::insert(func, base, base + 1, base + 2, base + 3, base + 4, base + 5, base + 6, base + 7);
+                    self.generate_expr(func, key_expr, ir_func, next_scratch)?;
+                    self.generate_expr(func, val_expr, ir_func, next_scratch)?;
+                    let grow = self.runtime_function(Runtime::DictGrow);
+                    memory::DictLayout::insert(func, base, grow);
                 }
 
                 func.instruction(&Instruction::LocalGet(base));
             }
-            IRExpr::Subscript { value, index } => {
-                // Type-aware subscript: string indexing or list/dict access
-                let saved_scratch = *next_scratch;
-                let value_local = *next_scratch;
-                let index_local = value_local + 1;
-                *next_scratch = index_local + 1;
-
-                self.generate_expr(func, value, ir_func, gas_temp_local, next_scratch)?;
-                func.instruction(&Instruction::LocalSet(value_local));
-
-                self.generate_expr(func, index, ir_func, gas_temp_local, next_scratch)?;
-                func.instruction(&Instruction::LocalSet(index_local));
-
-                // Check if value is heap pointer (>= 1024) AND is string (type tag == 3)
-                func.instruction(&Instruction::LocalGet(value_local));
-                func.instruction(&Instruction::I32Const(1024));
-                func.instruction(&Instruction::I32GeU);
-
-                func.instruction(&Instruction::LocalGet(value_local));
-                func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
-                func.instruction(&Instruction::I32Const(3)); // TYPE_STRING
-                func.instruction(&Instruction::I32Eq);
+            IRExpr::Set(elements) => {
+                let capacity = Self::table_capacity(elements.len());
+                let base = *next_scratch;
+                *next_scratch = base + 10;
 
-                func.instruction(&Instruction::I32And);
+                memory::SetLayout::alloc(func, capacity, base, base + 1);
+                func.instruction(&Instruction::LocalSet(base));
 
-                func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
-                // String indexing path
-                func.instruction(&Instruction::LocalGet(value_local));
-                func.instruction(&Instruction::LocalGet(index_local));
-                let str_base = *next_scratch;
-                memory::StringLayout::index(func, str_base, str_base + 1);
-                func.instruction(&Instruction::Else);
-                // List/dict access path
-                func.instruction(&Instruction::LocalGet(value_local));
-                func.instruction(&Instruction::LocalGet(index_local));
-                let list_base = *next_scratch;
-                memory::ListLayout::load_element(func, list_base, list_base + 1);
-                func.instruction(&Instruction::End);
+                for element in elements {
+                    func.instruction(&Instruction::LocalGet(base));
+                    self.generate_expr(func, element, ir_func, next_scratch)?;
+                    let grow = self.runtime_function(Runtime::DictGrow);
+                    memory::SetLayout::add(func, base + 1, grow);
+                }
 
-                *next_scratch = saved_scratch;
+                func.instruction(&Instruction::LocalGet(base));
+                *next_scratch = base;
+            }
+            IRExpr::Subscript { value, index } => {
+                // Type-aware subscript: string indexing or list/dict access
+                self.generate_expr(func, value, ir_func, next_scratch)?;
+                self.generate_expr(func, index, ir_func, next_scratch)?;
+                self.call_runtime(func, Runtime::Subscript { raises: self.exceptions });
             }
             IRExpr::Str(s) => {
                 let bytes = s.as_bytes();
@@ -645,82 +1781,83 @@ impl WasmCodegen {
 
                 memory::StringLayout::alloc(func, bytes);
                 func.instruction(&Instruction::LocalSet(base));
-
-                // Copy bytes into allocated string
-                for (i, &byte) in bytes.iter().enumerate() {
-                    func.instruction(&Instruction::LocalGet(base));
-                    func.instruction(&Instruction::I32Const(8 + i as i32));
-                    func.instruction(&Instruction::I32Add);
-                    func.instruction(&Instruction::I32Const(byte as i32));
-                    func.instruction(&Instruction::I32Store8(MemArg { offset: 0, align: 0, memory_index: 0 }));
-                }
-
+                self.init_literal(func, bytes, base);
                 func.instruction(&Instruction::LocalGet(base));
             }
-            IRExpr::Slice { value, start, end } => {
-                // Stack needs to be: [str_ptr, start, end] before slice call
-                let str_local = *next_scratch;
-                *next_scratch = str_local + 1;
-
-                // Generate and save string pointer
-                self.generate_expr(func, value, ir_func, gas_temp_local, next_scratch)?;
-                func.instruction(&Instruction::LocalSet(str_local));
-
-                // Generate start (default 0)
-                if let Some(start_expr) = start {
-                    self.generate_expr(func, start_expr, ir_func, gas_temp_local, next_scratch)?;
-                } else {
-                    func.instruction(&Instruction::I32Const(0));
+            IRExpr::Bytes(b) => {
+                let base = *next_scratch;
+                self.generate_bytes(func, b, base);
+            }
+            IRExpr::Slice { value, start, end, step } => {
+                self.generate_expr(func, value, ir_func, next_scratch)?;
+                for bound in [start, end] {
+                    match bound {
+                        Some(expr) => self.generate_expr(func, expr, ir_func, next_scratch)?,
+                        None => { func.instruction(&Instruction::I32Const(0)); }
+                    }
                 }
-
-                // Generate end (default string length)
-                if let Some(end_expr) = end {
-                    self.generate_expr(func, end_expr, ir_func, gas_temp_local, next_scratch)?;
-                } else {
-                    // Load string length as default end
-                    func.instruction(&Instruction::LocalGet(str_local));
-                    memory::StringLayout::load_length(func);
+                match step {
+                    Some(expr) => self.generate_expr(func, expr, ir_func, next_scratch)?,
+                    None => { func.instruction(&Instruction::I32Const(1)); }
                 }
+                self.call_runtime(func, Runtime::Slice { start: start.is_some(), stop: end.is_some() });
+            }
+            IRExpr::AssignExpr { var, value } => {
+                self.generate_expr(func, value, ir_func, next_scratch)?;
+                let local_idx = ir_func.local_map.get(var)
+                    .ok_or_else(|| anyhow::anyhow!("Variable '{}' not in local_map", var))?;
+                func.instruction(&Instruction::LocalTee(*local_idx));
+            }
+            IRExpr::BoolOp { op, left, right } => {
+                // Keep the left value: it is the result when it decides the outcome
+                let left_local = *next_scratch;
+                *next_scratch = left_local + 1;
 
-                // Now stack is [start, end], need to get str_ptr under them
-                let start_local = *next_scratch;
-                let end_local = start_local + 1;
-                *next_scratch = end_local + 1;
-
-                func.instruction(&Instruction::LocalSet(end_local));
-                func.instruction(&Instruction::LocalSet(start_local));
-                func.instruction(&Instruction::LocalGet(str_local));
-                func.instruction(&Instruction::LocalGet(start_local));
-                func.instruction(&Instruction::LocalGet(end_local));
+                self.generate_expr(func, left, ir_func, next_scratch)?;
+                func.instruction(&Instruction::LocalTee(left_local));
 
-                // Allocate scratch locals for slice operation
-                let base = *next_scratch;
-                *next_scratch = base + 6;
+                func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+                match op {
+                    BoolOp::And => {
+                        self.generate_expr(func, right, ir_func, next_scratch)?;
+                        func.instruction(&Instruction::Else);
+                        func.instruction(&Instruction::LocalGet(left_local));
+                    }
+                    BoolOp::Or => {
+                        func.instruction(&Instruction::LocalGet(left_local));
+                        func.instruction(&Instruction::Else);
+                        self.generate_expr(func, right, ir_func, next_scratch)?;
+                    }
+                }
+                func.instruction(&Instruction::End);
 
-                memory::StringLayout::slice(func, base, base + 1, base + 2, base + 3, base + 4, base + 5);
+                *next_scratch = left_local;
             }
             IRExpr::IfExpr { cond, then_val, else_val } => {
                 // Conditional expression: if(cond) then_val else else_val
-                self.generate_expr(func, cond, ir_func, gas_temp_local, next_scratch)?;
+                self.generate_expr(func, cond, ir_func, next_scratch)?;
 
                 func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
-                self.generate_expr(func, then_val, ir_func, gas_temp_local, next_scratch)?;
+                self.generate_expr(func, then_val, ir_func, next_scratch)?;
                 func.instruction(&Instruction::Else);
-                self.generate_expr(func, else_val, ir_func, gas_temp_local, next_scratch)?;
+                self.generate_expr(func, else_val, ir_func, next_scratch)?;
                 func.instruction(&Instruction::End);
             }
             IRExpr::MethodCall { obj, method, args } => {
                 let saved_scratch = *next_scratch;
 
                 // Generate object expression
-                self.generate_expr(func, obj, ir_func, gas_temp_local, next_scratch)?;
+                self.generate_expr(func, obj, ir_func, next_scratch)?;
                 let obj_local = saved_scratch;
                 func.instruction(&Instruction::LocalSet(obj_local));
 
+                // Reserve arg locals before evaluating args so nested exprs can't clobber them
+                *next_scratch = saved_scratch + 1 + args.len() as u32;
+
                 // Generate args
                 let mut arg_locals = Vec::new();
                 for (i, arg) in args.iter().enumerate() {
-                    self.generate_expr(func, arg, ir_func, gas_temp_local, next_scratch)?;
+                    self.generate_expr(func, arg, ir_func, next_scratch)?;
                     let arg_local = saved_scratch + 1 + i as u32;
                     func.instruction(&Instruction::LocalSet(arg_local));
                     arg_locals.push(arg_local);
@@ -738,7 +1875,7 @@ impl WasmCodegen {
                         func.instruction(&Instruction::LocalGet(obj_local));
                         let base = *next_scratch;
                         *next_scratch = base + 4;
-                        memory::BytesLayout::from_string(func, base, base + 1, base + 2, base + 3);
+                        self.call_runtime(func, Runtime::Encode);
                     }
                     "startswith" => {
                         if args.len() != 1 {
@@ -751,15 +1888,144 @@ impl WasmCodegen {
                         *next_scratch = base + 6;
                         memory::StringLayout::startswith(func, base, base + 1, base + 2, base + 3, base + 4, base + 5);
                     }
-                    "hexdigest" => {
+                    "endswith" => {
+                        if args.len() != 1 {
+                            bail!("endswith() takes exactly 1 argument");
+                        }
+                        func.instruction(&Instruction::LocalGet(obj_local));
+                        func.instruction(&Instruction::LocalGet(arg_locals[0]));
+                        let base = *next_scratch;
+                        *next_scratch = base + 6;
+                        memory::StringLayout::endswith(func, base, base + 1, base + 2, base + 3, base + 4, base + 5);
+                    }
+                    "update" => {
+                        if args.len() != 1 {
+                            bail!("update() takes exactly 1 argument");
+                        }
+                        memory::HasherLayout::check_type(func, obj_local);
+
+                        // Rehash the whole message: msg = msg + data
+                        let msg_local = *next_scratch;
+                        let base = msg_local + 1;
+                        *next_scratch = base + 160;
+
+                        func.instruction(&Instruction::LocalGet(obj_local));
+                        memory::HasherLayout::load_message(func);
+                        func.instruction(&Instruction::LocalGet(arg_locals[0]));
+                        self.call_runtime(func, Runtime::BytesConcat);
+                        func.instruction(&Instruction::LocalSet(msg_local));
+
+                        func.instruction(&Instruction::LocalGet(obj_local));
+                        func.instruction(&Instruction::LocalGet(msg_local));
+
+                        func.instruction(&Instruction::LocalGet(obj_local));
+                        memory::HasherLayout::is_sha3(func);
+                        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+                        func.instruction(&Instruction::LocalGet(msg_local));
+                        self.call_runtime(func, Runtime::Digest(HashAlgorithm::Sha3_256));
+                        func.instruction(&Instruction::Else);
+                        func.instruction(&Instruction::LocalGet(msg_local));
+                        self.call_runtime(func, Runtime::Digest(HashAlgorithm::Sha256));
+                        func.instruction(&Instruction::End);
+
+                        memory::HasherLayout::store(func, base, base + 1, base + 2);
+
+                        // update() returns None
+                        func.instruction(&Instruction::I32Const(0));
+                    }
+                    "digest" => {
+                        if !args.is_empty() {
+                            bail!("digest() takes no arguments");
+                        }
+                        memory::HasherLayout::check_type(func, obj_local);
+                        func.instruction(&Instruction::LocalGet(obj_local));
+                        let base = *next_scratch;
+                        *next_scratch = base + 2;
+                        memory::HasherLayout::digest(func, base, base + 1);
+                    }
+                    "hexdigest" | "hex" => {
                         if !args.is_empty() {
-                            bail!("hexdigest() takes no arguments");
+                            bail!("{}() takes no arguments", method);
                         }
                         // Convert hash bytes to hex string
                         func.instruction(&Instruction::LocalGet(obj_local));
                         let base = *next_scratch;
                         *next_scratch = base + 4;
-                        memory::BytesLayout::hexdigest(func, base, base + 1, base + 2, base + 3);
+                        self.call_runtime(func, Runtime::HexDigest);
+                    }
+                    "append" => {
+                        if args.len() != 1 {
+                            bail!("append() takes exactly 1 argument");
+                        }
+                        func.instruction(&Instruction::LocalGet(obj_local));
+                        func.instruction(&Instruction::LocalGet(arg_locals[0]));
+                        let base = *next_scratch;
+                        *next_scratch = base + 4;
+                        memory::ListLayout::append(func, base, base + 1, base + 2, base + 3);
+                        // append() returns None
+                        func.instruction(&Instruction::I32Const(0));
+                    }
+                    "add" => {
+                        if args.len() != 1 {
+                            bail!("add() takes exactly 1 argument");
+                        }
+                        func.instruction(&Instruction::LocalGet(obj_local));
+                        func.instruction(&Instruction::LocalGet(arg_locals[0]));
+                        let base = *next_scratch;
+                        *next_scratch = base + 9;
+                        let grow = self.runtime_function(Runtime::DictGrow);
+                        memory::SetLayout::add(func, base, grow);
+                        // add() returns None
+                        func.instruction(&Instruction::I32Const(0));
+                    }
+                    "pop" => {
+                        if args.len() > 1 {
+                            bail!("pop() takes at most 1 argument");
+                        }
+                        let base = *next_scratch;
+                        *next_scratch = base + 6;
+
+                        // d.pop(key) removes the entry, raising KeyError when it's absent
+                        memory::DictLayout::is_dict(func, obj_local);
+                        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+                        if let Some(&key_local) = arg_locals.first() {
+                            func.instruction(&Instruction::LocalGet(obj_local));
+                            func.instruction(&Instruction::LocalGet(key_local));
+                            memory::DictLayout::remove(func, base, base + 1, base + 2, base + 3, base + 4, base + 5, &|func| {
+                                self.raise(func, ExceptionKind::KeyError);
+                                func.instruction(&Instruction::I32Const(0));
+                            });
+                        } else {
+                            // a dict has no last entry to pop
+                            memory::trap(func, RuntimeError::TypeError);
+                        }
+                        func.instruction(&Instruction::Else);
+
+                        func.instruction(&Instruction::LocalGet(obj_local));
+                        if let Some(&index_local) = arg_locals.first() {
+                            func.instruction(&Instruction::LocalGet(index_local));
+                        } else {
+                            // default: last element (length - 1, traps below on empty list)
+                            func.instruction(&Instruction::LocalGet(obj_local));
+                            func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+                            func.instruction(&Instruction::I32Const(1));
+                            func.instruction(&Instruction::I32Sub);
+                        }
+                        memory::ListLayout::pop(func, base, base + 1, base + 2, base + 3);
+                        func.instruction(&Instruction::End);
+                    }
+                    "insert" => {
+                        if args.len() != 2 {
+                            bail!("insert() takes exactly 2 arguments");
+                        }
+                        func.instruction(&Instruction::LocalGet(obj_local));
+                        func.instruction(&Instruction::LocalGet(arg_locals[0]));
+                        func.instruction(&Instruction::LocalGet(arg_locals[1]));
+                        let base = *next_scratch;
+                        *next_scratch = base + 5;
+                        memory::ListLayout::insert(func, base, base + 1, base + 2, base + 3, base + 4);
+                        // insert() returns None
+                        func.instruction(&Instruction::I32Const(0));
                     }
                     _ => bail!("Unknown method: {}", method),
                 }
@@ -767,79 +2033,92 @@ impl WasmCodegen {
                 *next_scratch = saved_scratch;
             }
             IRExpr::FormatStr { parts } => {
+                // One local per converted part, plus one holding the value being converted
                 let saved_scratch = *next_scratch;
+                let value_local = saved_scratch + parts.len() as u32;
+                let parts_top = value_local + 1;
 
-                // Generate all parts, converting to strings
-                let mut part_locals = Vec::new();
                 for (i, part) in parts.iter().enumerate() {
-                    let part_local = saved_scratch + i as u32;
+                    *next_scratch = parts_top;
                     match part {
                         FormatPart::Literal(s) => {
-                            // String literal
-                            memory::StringLayout::alloc(func, s.as_bytes());
+                            self.generate_expr(func, &IRExpr::Str(s.clone()), ir_func, next_scratch)?;
                         }
                         FormatPart::Expr(expr) => {
-                            // Evaluate expression
-                            self.generate_expr(func, expr, ir_func, gas_temp_local, next_scratch)?;
-
-                            // Check if result is already a string (heap pointer with type tag 3)
-                            let val_local = saved_scratch + parts.len() as u32 + i as u32;
-                            func.instruction(&Instruction::LocalTee(val_local));
-
-                            // Check if heap pointer (>= 1024)
-                            func.instruction(&Instruction::LocalGet(val_local));
-                            func.instruction(&Instruction::I32Const(1024));
-                            func.instruction(&Instruction::I32GeU);
-
-                            // Check if type tag == 3 (string)
-                            func.instruction(&Instruction::LocalGet(val_local));
-                            func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
-                            func.instruction(&Instruction::I32Const(3)); // TYPE_STRING
-                            func.instruction(&Instruction::I32Eq);
-
-                            func.instruction(&Instruction::I32And);
+                            self.generate_expr(func, expr, ir_func, next_scratch)?;
+                            func.instruction(&Instruction::LocalSet(value_local));
 
-                            // If already string, use as-is, else convert with str()
+                            // strings are used as-is, anything else goes through str()
+                            memory::StringLayout::is_string(func, value_local);
                             func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
-                            func.instruction(&Instruction::LocalGet(val_local));
+                            func.instruction(&Instruction::LocalGet(value_local));
                             func.instruction(&Instruction::Else);
-                            func.instruction(&Instruction::LocalGet(val_local));
-                            let base = *next_scratch;
-                            *next_scratch = base + 8;
-                            memory::StringLayout::from_int(func, base, base + 1, base + 2, base + 3, base + 4, base + 5, base + 6, base + 7);
-                            *next_scratch = saved_scratch + parts.len() as u32 * 2;
+                            func.instruction(&Instruction::LocalGet(value_local));
+                            self.call_runtime(func, Runtime::FromInt);
                             func.instruction(&Instruction::End);
                         }
                     }
-                    func.instruction(&Instruction::LocalSet(part_local));
-                    part_locals.push(part_local);
+                    func.instruction(&Instruction::LocalSet(saved_scratch + i as u32));
                 }
 
                 // Concatenate all parts left-to-right
-                if part_locals.is_empty() {
-                    // Empty f-string -> empty string
+                if parts.is_empty() {
                     memory::StringLayout::alloc(func, &[]);
-                } else if part_locals.len() == 1 {
-                    // Single part
-                    func.instruction(&Instruction::LocalGet(part_locals[0]));
                 } else {
-                    // Multiple parts: concat(concat(...concat(p0, p1), p2), ..., pN)
-                    func.instruction(&Instruction::LocalGet(part_locals[0]));
-                    func.instruction(&Instruction::LocalGet(part_locals[1]));
-
-                    let base = saved_scratch + parts.len() as u32 * 2;
-                    *next_scratch = base + 7;
-                    memory::StringLayout::concat(func, base, base + 1, base + 2, base + 3, base + 4, base + 5, base + 6);
-
-                    for i in 2..part_locals.len() {
-                        func.instruction(&Instruction::LocalGet(part_locals[i]));
-                        memory::StringLayout::concat(func, base, base + 1, base + 2, base + 3, base + 4, base + 5, base + 6);
+                    func.instruction(&Instruction::LocalGet(saved_scratch));
+                    for i in 1..parts.len() as u32 {
+                        func.instruction(&Instruction::LocalGet(saved_scratch + i));
+                        self.call_runtime(func, Runtime::StringConcat);
                     }
                 }
 
                 *next_scratch = saved_scratch;
             }
+            IRExpr::Block { stmts, result } => {
+                // Statements allocate scratch above anything the enclosing expression holds
+                // no branches out of an expression: errors wait for the enclosing statement's check
+                for s in stmts {
+                    self.generate_stmt_with_depth(func, s, ir_func, next_scratch, Depth::DEFERRED)?;
+                }
+                self.generate_expr(func, result, ir_func, next_scratch)?;
+            }
         }
         Ok(())
     }
 }
+
+// Iterations of range(start, stop, step) for a nonzero step
+fn trip_count(start: i32, stop: i32, step: i32) -> u64 {
+    let (span, step) = if step > 0 {
+        (stop as i64 - start as i64, step as i64)
+    } else {
+        (start as i64 - stop as i64, -(step as i64))
+    };
+    if span <= 0 { 0 } else { ((span + step - 1) / step) as u64 }
+}
+
+fn prints(stmt: &IRStmt) -> bool {
+    let mut found = false;
+    for_each_stmt_expr(stmt, &mut |expr| found |= expr_prints(expr));
+    found
+}
+
+fn expr_prints(expr: &IRExpr) -> bool {
+    match expr {
+        IRExpr::Call { func, .. } if func == "print" => true,
+        IRExpr::Block { stmts, result } => stmts.iter().any(prints) || expr_prints(result),
+        _ => expr.children().into_iter().any(expr_prints),
+    }
+}
+
+fn contains_try(stmt: &IRStmt) -> bool {
+    let any = |stmts: &[IRStmt]| stmts.iter().any(contains_try);
+    match stmt {
+        IRStmt::Try { .. } => true,
+        IRStmt::If { then_block, else_block, .. } => any(then_block) || any(else_block),
+        IRStmt::IfChain { branches, else_block } => branches.iter().any(|(_, b)| any(b)) || any(else_block),
+        IRStmt::Switch { arms, default, .. } => arms.iter().any(|a| any(a)) || any(default),
+        IRStmt::While { body, .. } | IRStmt::For { body, .. } | IRStmt::Block(body) => any(body),
+        _ => false,
+    }
+}
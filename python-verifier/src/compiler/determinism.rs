@@ -0,0 +1,70 @@
+// Determinism policy for client-supplied modules. Compiled Python meets it by
+// construction; foreign modules must pass it before they are metered and run.
+//
+// Rejected: floating point, SIMD, threads and shared memory, and any proposal whose
+// behaviour differs between engines. The host ABI is the one compiled Python uses:
+// memory comes from `env.memory`, and the only other import is `env.abort`. The opcode and
+// import checks are certus-wasm-policy's, so the executor node and the Stylus contract accept
+// exactly what this does.
+
+use anyhow::{Result, bail};
+use wasmparser::{Parser, Payload, Validator, WasmFeatures};
+
+pub use certus_wasm_policy::ALLOWED_IMPORTS;
+
+fn features() -> WasmFeatures {
+    WasmFeatures {
+        floats: false,
+        saturating_float_to_int: false,
+        simd: false,
+        relaxed_simd: false,
+        threads: false,
+        reference_types: false,
+        multi_memory: false,
+        memory64: false,
+        tail_call: false,
+        exceptions: false,
+        function_references: false,
+        gc: false,
+        memory_control: false,
+        component_model: false,
+        ..Default::default()
+    }
+}
+
+pub fn validate(wasm: &[u8]) -> Result<()> {
+    if let Err(e) = Validator::new_with_features(features()).validate_all(wasm) {
+        bail!("module is not deterministic: {}", e);
+    }
+
+    check_imports(wasm)?;
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        // its own memory would escape the host's page limit
+        if let Payload::MemorySection(reader) = payload? {
+            if reader.count() > 0 {
+                bail!("module must import env.memory instead of defining a memory");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reject any import outside `ALLOWED_IMPORTS`, reading the import section itself rather
+/// than searching the bytes for names. Also applied to compiled modules before they run.
+pub fn check_imports(wasm: &[u8]) -> Result<()> {
+    if let Err(violation) = certus_wasm_policy::check_imports(wasm) {
+        bail!("{}", violation);
+    }
+    Ok(())
+}
+
+/// Reject float, SIMD and atomic instructions, decoding each function body rather than
+/// scanning the module's bytes, so immediates and data segments are never read as opcodes.
+/// Applied to compiled modules, which skip the validator.
+pub fn check_opcodes(wasm: &[u8]) -> Result<()> {
+    if let Err(violation) = certus_wasm_policy::check_opcodes(wasm) {
+        bail!("{}", violation);
+    }
+    Ok(())
+}
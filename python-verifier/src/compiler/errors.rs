@@ -0,0 +1,95 @@
+// Why a compiled job trapped. Every trap codegen emits for a Python-level failure first stores
+// one of these codes at ERROR_SLOT, so a host reading the slot after the trap can tell an
+// index out of range from a failed assert. The codes of the exceptions a job can raise are
+// their ExceptionKind codes: main stores the pending code as it is when nothing caught it.
+// Fuel charges store nothing, which would cost bytes in every basic block; a charge that traps
+// leaves the exported fuel counter past FUEL_LIMIT, and hosts read that instead. A timeout is
+// the host's own doing and never reaches the module either.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::ir::ExceptionKind;
+use super::ERROR_SLOT;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeError {
+    /// An uncaught ValueError: raised, a slice step of 0, min or max of nothing, a bad unpack
+    ValueError = 1,
+    /// An uncaught KeyError: a missing dict key
+    KeyError = 2,
+    /// Division or modulo by zero
+    DivisionByZero = 3,
+    /// Indexing a list, tuple, string or bytes past its end
+    IndexOutOfRange = 4,
+    /// The heap reached its limit
+    OutOfMemory = 5,
+    /// The module's fuel counter passed FUEL_LIMIT, or the host's fuel ran out; never stored
+    OutOfGas = 6,
+    /// A failed assert; its message hash is at ASSERT_SLOT
+    AssertionFailed = 7,
+    /// User functions nested past the call depth limit
+    RecursionLimit = 8,
+    /// An operation on a value of the wrong type
+    TypeError = 9,
+    /// The host's wall clock ran out before the job finished; never stored
+    Timeout = 10,
+}
+
+impl RuntimeError {
+    pub const ALL: [RuntimeError; 10] = [
+        RuntimeError::ValueError, RuntimeError::KeyError, RuntimeError::DivisionByZero,
+        RuntimeError::IndexOutOfRange, RuntimeError::OutOfMemory, RuntimeError::OutOfGas,
+        RuntimeError::AssertionFailed, RuntimeError::RecursionLimit, RuntimeError::TypeError,
+        RuntimeError::Timeout,
+    ];
+
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// An exception of `kind` nothing caught
+    pub(crate) fn uncaught(kind: ExceptionKind) -> Self {
+        match kind {
+            ExceptionKind::ValueError => RuntimeError::ValueError,
+            ExceptionKind::KeyError => RuntimeError::KeyError,
+            ExceptionKind::ZeroDivisionError => RuntimeError::DivisionByZero,
+        }
+    }
+
+    pub fn from_code(code: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.code() == code)
+    }
+
+    /// The code a trap left at ERROR_SLOT of a compiled module's memory; `None` when the
+    /// module trapped without writing one
+    pub fn read(memory: &[u8]) -> Option<Self> {
+        let slot = memory.get(ERROR_SLOT as usize..ERROR_SLOT as usize + 4)?;
+        Self::from_code(i32::from_le_bytes([slot[0], slot[1], slot[2], slot[3]]))
+    }
+}
+
+// ExceptionKind codes double as these codes
+const _: () = assert!(
+    ExceptionKind::ValueError as i32 == RuntimeError::ValueError as i32
+        && ExceptionKind::KeyError as i32 == RuntimeError::KeyError as i32
+        && ExceptionKind::ZeroDivisionError as i32 == RuntimeError::DivisionByZero as i32
+);
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RuntimeError::ValueError => "uncaught ValueError",
+            RuntimeError::KeyError => "uncaught KeyError",
+            RuntimeError::DivisionByZero => "division by zero",
+            RuntimeError::IndexOutOfRange => "index out of range",
+            RuntimeError::OutOfMemory => "out of heap memory",
+            RuntimeError::OutOfGas => "out of gas",
+            RuntimeError::AssertionFailed => "assertion failed",
+            RuntimeError::RecursionLimit => "maximum call depth exceeded",
+            RuntimeError::TypeError => "type error",
+            RuntimeError::Timeout => "wall-clock timeout",
+        })
+    }
+}
@@ -0,0 +1,241 @@
+// Static fuel estimates. The module is generated as for `compile` and walked block by block,
+// charging each basic block what `fuel::meter` would, so straight-line code gets its exact cost.
+// Each `if` counts its costlier arm and each loop its bound: range() loops with constant bounds
+// come from codegen, and the runtime's own loops, over the bytes of a string or the slots of a
+// list or dict, are taken to run once per element of a value as large as the caller's hint.
+// A while loop, a range() with a computed bound or recursion leaves the estimate unbounded.
+
+use anyhow::Result;
+use serde::Serialize;
+use wasmparser::{Operator, Parser, Payload, TypeRef};
+
+use super::fuel::{block_charge, ends_block};
+
+/// Runtime loops run at least this often, so fixed-length ones such as sha256's rounds are covered
+const MIN_DATA_BOUND: u64 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopKind {
+    For,
+    While,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoopEstimate {
+    /// Python function the loop is in, "main" for module level
+    pub function: String,
+    pub kind: LoopKind,
+    /// Known for range() with constant arguments
+    pub iterations: Option<u64>,
+    /// Worst case of one pass through the body, nested loops at their bounds
+    pub fuel_per_iteration: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FuelEstimate {
+    /// Worst-case fuel in the gas schedule's units; loops without a known bound count once
+    pub fuel: u64,
+    /// Whether `fuel` bounds every run whose strings, lists and dicts stay within the size hint
+    pub bounded: bool,
+    /// Python loops in source order
+    pub loops: Vec<LoopEstimate>,
+}
+
+/// A Python loop in the generated module: its `loop` instruction's offset in the function body
+#[derive(Debug, Clone)]
+pub(crate) struct SourceLoop {
+    pub function_index: u32,
+    pub function: String,
+    pub offset: usize,
+    pub kind: LoopKind,
+    pub iterations: Option<u64>,
+}
+
+/// Estimate the unmetered module `wasm`, whose Python loops are `loops`; main is function 0
+pub(crate) fn estimate(wasm: &[u8], loops: &[SourceLoop], input_size_hint: usize) -> Result<FuelEstimate> {
+    let mut imported = 0;
+    let mut bodies = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    if let TypeRef::Func(_) = import?.ty {
+                        imported += 1;
+                    }
+                }
+            }
+            Payload::CodeSectionEntry(body) => bodies.push(body),
+            _ => {}
+        }
+    }
+
+    let functions = bodies.len() as u32;
+    let mut walk = Walk {
+        wasm,
+        costs: vec![Cost::Pending; bodies.len()],
+        bodies,
+        imported,
+        loops,
+        data_bound: (input_size_hint as u64).max(MIN_DATA_BOUND),
+        per_iteration: vec![0; loops.len()],
+        bounded: true,
+    };
+    let fuel = walk.function(imported)?;
+    // loops of functions main never calls still get a per-iteration figure
+    for index in 0..functions {
+        walk.function(imported + index)?;
+    }
+
+    Ok(FuelEstimate {
+        fuel,
+        bounded: walk.bounded,
+        loops: loops.iter().zip(walk.per_iteration).map(|(l, fuel_per_iteration)| LoopEstimate {
+            function: l.function.clone(),
+            kind: l.kind,
+            iterations: l.iterations,
+            fuel_per_iteration,
+        }).collect(),
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Cost {
+    Pending,
+    InProgress,
+    Done(u64),
+}
+
+enum FrameKind {
+    Block,
+    If,
+    Loop,
+}
+
+struct Frame {
+    kind: FrameKind,
+    cost: u64,
+    // cost of an if's then-arm once its else begins
+    then_cost: u64,
+    // passes through a loop's body: its iterations plus the check that ends it
+    passes: u64,
+    // index into the source loops
+    source: Option<usize>,
+}
+
+impl Frame {
+    fn new(kind: FrameKind) -> Self {
+        Frame { kind, cost: 0, then_cost: 0, passes: 1, source: None }
+    }
+}
+
+struct Walk<'a> {
+    wasm: &'a [u8],
+    bodies: Vec<wasmparser::FunctionBody<'a>>,
+    imported: u32,
+    loops: &'a [SourceLoop],
+    data_bound: u64,
+    costs: Vec<Cost>,
+    per_iteration: Vec<u64>,
+    bounded: bool,
+}
+
+impl Walk<'_> {
+    // Worst-case fuel of one call to function `index`, callees included
+    fn function(&mut self, index: u32) -> Result<u64> {
+        let Some(defined) = index.checked_sub(self.imported) else {
+            // host functions charge nothing against the module's counter
+            return Ok(0);
+        };
+        match self.costs[defined as usize] {
+            Cost::Done(cost) => return Ok(cost),
+            // recursion: no static bound
+            Cost::InProgress => {
+                self.bounded = false;
+                return Ok(0);
+            }
+            Cost::Pending => {}
+        }
+        self.costs[defined as usize] = Cost::InProgress;
+
+        let body = self.bodies[defined as usize].clone();
+        let start = body.range().start;
+        let mut ops = body.get_operators_reader()?;
+        let mut frames = vec![Frame::new(FrameKind::Block)];
+        let mut open = 0;
+        let mut total = 0;
+        while !ops.eof() {
+            let (op, offset) = ops.read_with_offset()?;
+            open += certus_gas::opcode_cost(self.wasm[offset]);
+            match op {
+                Operator::Block { .. } => frames.push(Frame::new(FrameKind::Block)),
+                Operator::Call { function_index } => {
+                    let callee = self.function(function_index)?;
+                    add(&mut frames, callee);
+                }
+                Operator::CallIndirect { .. } => self.bounded = false,
+                _ => {}
+            }
+            if !ends_block(&op) {
+                continue;
+            }
+            add(&mut frames, block_charge(open));
+            open = 0;
+
+            match op {
+                Operator::Loop { .. } => {
+                    let source = self.loops.iter()
+                        .position(|l| l.function_index == index && l.offset == offset - start);
+                    let iterations = match source {
+                        Some(i) => self.loops[i].iterations,
+                        None => Some(self.data_bound),
+                    };
+                    if iterations.is_none() {
+                        self.bounded = false;
+                    }
+                    frames.push(Frame {
+                        passes: iterations.map_or(1, |n| n.saturating_add(1)),
+                        source,
+                        ..Frame::new(FrameKind::Loop)
+                    });
+                }
+                Operator::If { .. } => frames.push(Frame::new(FrameKind::If)),
+                Operator::Else => {
+                    if let Some(frame) = frames.last_mut() {
+                        frame.then_cost = std::mem::take(&mut frame.cost);
+                    }
+                }
+                Operator::End => {
+                    let Some(frame) = frames.pop() else {
+                        continue;
+                    };
+                    let cost = match frame.kind {
+                        FrameKind::Block => frame.cost,
+                        FrameKind::If => frame.cost.max(frame.then_cost),
+                        FrameKind::Loop => {
+                            if let Some(i) = frame.source {
+                                self.per_iteration[i] = self.per_iteration[i].max(frame.cost);
+                            }
+                            frame.cost.saturating_mul(frame.passes)
+                        }
+                    };
+                    if frames.is_empty() {
+                        total = cost;
+                    } else {
+                        add(&mut frames, cost);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.costs[defined as usize] = Cost::Done(total);
+        Ok(total)
+    }
+}
+
+fn add(frames: &mut [Frame], cost: u64) {
+    if let Some(frame) = frames.last_mut() {
+        frame.cost = frame.cost.saturating_add(cost);
+    }
+}
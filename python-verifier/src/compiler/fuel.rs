@@ -0,0 +1,334 @@
+// Fuel metering shared by compiled Python and foreign modules. Both charge a mutable i32
+// global and trap once it passes a limit: the job's fuel for compiled Python, FUEL_LIMIT for
+// client modules. Costs come from certus-gas, the schedule the
+// on-chain interpreter charges per opcode: each basic block is charged its instructions' total,
+// plus the charge's own instructions, on entry. A job that finishes therefore burns exactly what
+// the interpreter would count running the same module, and the two agree on whether it runs out.
+// Codegen meters its output with `meter`; `instrument` also adds the counter to client modules.
+
+use anyhow::{Result, bail};
+use certus_gas::sequence_cost;
+use wasm_encoder::{BlockType, CustomSection, Encode, Function, Instruction, Section, ValType};
+use wasmparser::{Operator, Parser, Payload, TypeRef};
+
+/// Highest limit a counter can be built with, and the one client modules get
+pub const FUEL_LIMIT: i32 = 100_000_000;
+
+// What a charge costs when it doesn't trap: i32.const and call at the block, then the charge
+// function's global.get, local.get, i32.add, global.set, global.get, i32.const, i32.gt_s, if and end
+const CHARGE_COST: u64 = sequence_cost(&[0x41, 0x10, 0x23, 0x20, 0x6A, 0x24, 0x23, 0x41, 0x4A, 0x04, 0x0B]);
+
+/// Name of the appended charge function in a compiled module's name section
+pub const CHARGE_FUNCTION: &str = "$charge";
+
+/// Marks a module as metered, by codegen or by `instrument`. A record, not a credential: `instrument`
+/// meters marked modules again
+pub const METERED_SECTION: &str = "certus.metered";
+
+/// Exported fuel counter of an instrumented module
+pub const FUEL_EXPORT: &str = "certus_fuel";
+
+const TYPE_SECTION_ID: u8 = 1;
+const FUNCTION_SECTION_ID: u8 = 3;
+const GLOBAL_SECTION_ID: u8 = 6;
+const EXPORT_SECTION_ID: u8 = 7;
+const CODE_SECTION_ID: u8 = 10;
+
+/// Append the marker section
+pub fn mark_metered(wasm: &mut Vec<u8>) {
+    let section = CustomSection { name: METERED_SECTION.into(), data: [certus_gas::SCHEDULE_VERSION].as_slice().into() };
+    wasm.push(section.id());
+    section.encode(wasm);
+}
+
+pub fn is_metered(wasm: &[u8]) -> Result<bool> {
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::CustomSection(section) = payload? {
+            if section.name() == METERED_SECTION {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Inject fuel metering into a client-supplied module. A module claiming to be metered already is
+/// metered all the same: its marker section is dropped and any counter it exports is unexported,
+/// since nothing but the compiler running in this process can vouch for a module's metering.
+///
+/// Existing instructions are copied byte for byte, with a charge spliced in ahead of each basic
+/// block. The counter is a new global appended after the module's own, so no existing index shifts.
+pub fn instrument(wasm: &[u8]) -> Result<Vec<u8>> {
+    wasmparser::validate(wasm)?;
+
+    let mut imported_globals = 0;
+    let mut defined_globals = 0;
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    if let TypeRef::Global(_) = import?.ty {
+                        imported_globals += 1;
+                    }
+                }
+            }
+            Payload::GlobalSection(reader) => defined_globals = reader.count(),
+            _ => {}
+        }
+    }
+    let fuel_global = imported_globals + defined_globals;
+
+    let (mut out, _) = rewrite(wasm, fuel_global, FUEL_LIMIT, true)?;
+    mark_metered(&mut out);
+
+    if let Err(e) = wasmparser::validate(&out) {
+        bail!("fuel instrumentation produced an invalid module: {}", e);
+    }
+    Ok(out)
+}
+
+/// Where `meter` spliced charges into one function body: the unmetered offset of each charged
+/// block and the length of its charge, in body order. Offsets count from the body's locals.
+pub(crate) type Charges = Vec<(usize, usize)>;
+
+/// Meter every function of a module whose counter is already the global `fuel_global`, trapping
+/// once it passes `limit`, and return the charges of each body alongside
+pub(crate) fn meter(wasm: &[u8], fuel_global: u32, limit: i32) -> Result<(Vec<u8>, Vec<Charges>)> {
+    rewrite(wasm, fuel_global, limit, false)
+}
+
+/// Where an unmetered body offset lands in the metered body. A block's charge sits at the
+/// block's own offset, so code starting a block is taken to start at its charge.
+pub(crate) fn metered_offset(charges: &[(usize, usize)], offset: usize) -> usize {
+    offset + charges.iter().take_while(|&&(block, _)| block < offset).map(|&(_, len)| len).sum::<usize>()
+}
+
+// Copy the module with its bodies metered and the charge function appended after its own,
+// adding the counter global and its export when asked
+fn rewrite(wasm: &[u8], fuel_global: u32, limit: i32, add_counter: bool) -> Result<(Vec<u8>, Vec<Charges>)> {
+    let mut types = 0;
+    let mut functions = 0;
+    let mut bodies = 0;
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::TypeSection(reader) => types = reader.count(),
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    if let TypeRef::Func(_) = import?.ty {
+                        functions += 1;
+                    }
+                }
+            }
+            Payload::FunctionSection(reader) => functions += reader.count(),
+            Payload::CodeSectionStart { count, .. } => bodies = count,
+            _ => {}
+        }
+    }
+    // a module without bodies has no block to charge, and gets no charge function
+    let charge_function = functions;
+
+    let mut out = wasm[..8].to_vec();
+    let mut charges = Vec::new();
+    let mut wrote_globals = !add_counter;
+    let mut wrote_exports = !add_counter;
+    let mut code = Vec::new();
+    let mut bodies_left = 0;
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload?;
+        if let Payload::CodeSectionEntry(body) = &payload {
+            let (metered, body_charges) = meter_body(wasm, body, charge_function)?;
+            metered.as_slice().encode(&mut code);
+            charges.push(body_charges);
+            bodies_left -= 1;
+            if bodies_left == 0 {
+                charge_body(fuel_global, limit).encode(&mut code);
+                write_section(&mut out, CODE_SECTION_ID, &code);
+            }
+            continue;
+        }
+        let Some((id, range)) = payload.as_section() else {
+            continue;
+        };
+
+        // sections we add go where the binary format orders them
+        if !wrote_globals && id != 0 && section_order(id) > section_order(GLOBAL_SECTION_ID) {
+            write_section(&mut out, GLOBAL_SECTION_ID, &with_fuel_global(None, wasm));
+            wrote_globals = true;
+        }
+        if !wrote_exports && id != 0 && section_order(id) > section_order(EXPORT_SECTION_ID) {
+            write_section(&mut out, EXPORT_SECTION_ID, &with_fuel_export(None, fuel_global));
+            wrote_exports = true;
+        }
+
+        match payload {
+            Payload::GlobalSection(reader) if add_counter => {
+                write_section(&mut out, id, &with_fuel_global(Some((reader.count(), reader.original_position()..range.end)), wasm));
+                wrote_globals = true;
+            }
+            Payload::ExportSection(reader) if add_counter => {
+                write_section(&mut out, id, &with_fuel_export(Some(exports_without_counter(reader, wasm, range.end)?), fuel_global));
+                wrote_exports = true;
+            }
+            // a marker only this pass can set
+            Payload::CustomSection(section) if section.name() == METERED_SECTION => {}
+            // the charge function's type and declaration go last, so no index shifts
+            Payload::TypeSection(reader) if bodies > 0 => {
+                let mut content = Vec::new();
+                (types + 1).encode(&mut content);
+                content.extend_from_slice(&wasm[reader.original_position()..range.end]);
+                content.push(0x60);
+                [ValType::I32].as_slice().encode(&mut content);
+                0u32.encode(&mut content); // no results
+                write_section(&mut out, TYPE_SECTION_ID, &content);
+            }
+            Payload::FunctionSection(reader) if bodies > 0 => {
+                let mut content = Vec::new();
+                (reader.count() + 1).encode(&mut content);
+                content.extend_from_slice(&wasm[reader.original_position()..range.end]);
+                types.encode(&mut content);
+                write_section(&mut out, FUNCTION_SECTION_ID, &content);
+            }
+            // bodies follow as separate payloads, the charge function's after the last
+            Payload::CodeSectionStart { count, .. } if count > 0 => {
+                (count + 1).encode(&mut code);
+                bodies_left = count;
+            }
+            _ => write_section(&mut out, id, &wasm[range]),
+        }
+    }
+
+    if !wrote_globals {
+        write_section(&mut out, GLOBAL_SECTION_ID, &with_fuel_global(None, wasm));
+    }
+    if !wrote_exports {
+        write_section(&mut out, EXPORT_SECTION_ID, &with_fuel_export(None, fuel_global));
+    }
+    Ok((out, charges))
+}
+
+// Binary-format position of a non-custom section: tags precede globals, data count precedes code
+fn section_order(id: u8) -> usize {
+    const ORDER: [u8; 13] = [1, 2, 3, 4, 5, 13, 6, 7, 8, 9, 12, 10, 11];
+    ORDER.iter().position(|&i| i == id).unwrap_or(ORDER.len())
+}
+
+pub(crate) fn write_section(out: &mut Vec<u8>, id: u8, content: &[u8]) {
+    out.push(id);
+    content.encode(out);
+}
+
+// Existing entries (count and raw byte range) plus the zeroed fuel counter
+fn with_fuel_global(existing: Option<(u32, std::ops::Range<usize>)>, wasm: &[u8]) -> Vec<u8> {
+    let mut content = Vec::new();
+    let (count, entries) = existing.map_or((0, &[][..]), |(count, range)| (count, &wasm[range]));
+    (count + 1).encode(&mut content);
+    content.extend_from_slice(entries);
+    // mutable i32, initialised to 0
+    content.extend_from_slice(&[0x7F, 0x01]);
+    Instruction::I32Const(0).encode(&mut content);
+    Instruction::End.encode(&mut content);
+    content
+}
+
+// Existing entries (count and raw bytes) plus the counter's export
+fn with_fuel_export(existing: Option<(u32, Vec<u8>)>, fuel_global: u32) -> Vec<u8> {
+    let mut content = Vec::new();
+    let (count, entries) = existing.unwrap_or_default();
+    (count + 1).encode(&mut content);
+    content.extend_from_slice(&entries);
+    FUEL_EXPORT.encode(&mut content);
+    wasm_encoder::ExportKind::Global.encode(&mut content);
+    fuel_global.encode(&mut content);
+    content
+}
+
+// A module's exports minus any already named like the counter, which the new counter's replaces
+fn exports_without_counter(reader: wasmparser::ExportSectionReader, wasm: &[u8], end: usize) -> Result<(u32, Vec<u8>)> {
+    let exports = reader.into_iter_with_offsets().collect::<Result<Vec<_>, _>>()?;
+    let mut count = 0;
+    let mut entries = Vec::new();
+    for (i, (start, export)) in exports.iter().enumerate() {
+        if export.name != FUEL_EXPORT {
+            let next = exports.get(i + 1).map_or(end, |&(next, _)| next);
+            count += 1;
+            entries.extend_from_slice(&wasm[*start..next]);
+        }
+    }
+    Ok((count, entries))
+}
+
+fn meter_body(wasm: &[u8], body: &wasmparser::FunctionBody, charge_function: u32) -> Result<(Vec<u8>, Charges)> {
+    let mut ops = body.get_operators_reader()?;
+    let start = body.range().start;
+    let mut block_start = ops.original_position();
+
+    // locals unchanged, then each block behind its charge; the body's final `end` closes the last
+    let mut out = wasm[start..block_start].to_vec();
+    let mut charges = Vec::new();
+    let mut cost = 0;
+    while !ops.eof() {
+        let (op, offset) = ops.read_with_offset()?;
+        cost += certus_gas::opcode_cost(wasm[offset]);
+        if ends_block(&op) {
+            let end = ops.original_position();
+            let before = out.len();
+            charge(&mut out, charge_function, cost);
+            if out.len() > before {
+                charges.push((block_start - start, out.len() - before));
+            }
+            out.extend_from_slice(&wasm[block_start..end]);
+            block_start = end;
+            cost = 0;
+        }
+    }
+    out.extend_from_slice(&wasm[block_start..body.range().end]);
+    Ok((out, charges))
+}
+
+// Blocks start at function entry, at a loop's label, on either arm of an if, after a branch
+// falls through, where control rejoins at an end, and when a call returns. Entering a `block`
+// continues the current one: its label is at its end.
+pub(crate) fn ends_block(op: &Operator) -> bool {
+    use Operator::*;
+    matches!(
+        op,
+        Loop { .. } | If { .. } | Else | End | Br { .. } | BrIf { .. } | BrTable { .. } | Return
+            | Call { .. } | CallIndirect { .. } | Unreachable
+    )
+}
+
+/// What a basic block whose instructions cost `cost` is charged on entry; free blocks get no charge
+pub(crate) fn block_charge(cost: u64) -> u64 {
+    if cost == 0 { 0 } else { cost + CHARGE_COST }
+}
+
+fn charge(out: &mut Vec<u8>, charge_function: u32, cost: u64) {
+    let charged = block_charge(cost);
+    if charged == 0 {
+        return;
+    }
+    Instruction::I32Const(charged as i32).encode(out);
+    Instruction::Call(charge_function).encode(out);
+}
+
+// (cost: i32): add the cost to the counter and trap once it passes the limit
+fn charge_body(fuel_global: u32, limit: i32) -> Function {
+    let mut func = Function::new([]);
+    for instruction in [
+        Instruction::GlobalGet(fuel_global),
+        Instruction::LocalGet(0),
+        Instruction::I32Add,
+        Instruction::GlobalSet(fuel_global),
+        Instruction::GlobalGet(fuel_global),
+        Instruction::I32Const(limit),
+        Instruction::I32GtS,
+        Instruction::If(BlockType::Empty),
+        Instruction::Unreachable,
+        Instruction::End,
+        Instruction::End,
+    ] {
+        func.instruction(&instruction);
+    }
+    func
+}
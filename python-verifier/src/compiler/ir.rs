@@ -5,7 +5,8 @@ use std::collections::HashMap;
 pub enum IR {
     Module {
         functions: Vec<IRFunction>,
-        _globals: Vec<String>,
+        // Module-level names shared with functions, in Wasm global order
+        globals: Vec<String>,
     },
 }
 
@@ -24,14 +25,69 @@ pub struct IRFunction {
 #[derive(Debug, Clone)]
 pub enum IRStmt {
     Assign { var: String, value: IRExpr },
+    // Store into a module-level global
+    AssignGlobal { var: String, value: IRExpr },
     SubscriptAssign { target: Box<IRExpr>, index: Box<IRExpr>, value: Box<IRExpr> },
     Return(IRExpr),
     If { cond: IRExpr, then_block: Vec<IRStmt>, else_block: Vec<IRStmt> },
+    // if/elif/.../else ladder; the first true branch runs, else_block otherwise
+    IfChain { branches: Vec<(IRExpr, Vec<IRStmt>)>, else_block: Vec<IRStmt> },
+    // Jump table on an integer: value == low + i runs arms[targets[i]], anything else runs default
+    Switch { value: IRExpr, low: i32, targets: Vec<Option<usize>>, arms: Vec<Vec<IRStmt>>, default: Vec<IRStmt> },
     While { cond: IRExpr, body: Vec<IRStmt> },
-    For { var: String, iter: IRExpr, body: Vec<IRStmt> },
+    // for var in range(start, stop, step); step is never Const(0)
+    For { var: String, start: IRExpr, stop: IRExpr, step: IRExpr, body: Vec<IRStmt> },
     Break,
+    // next iteration of the innermost loop; a for loop still steps its counter
+    Continue,
     Expr(IRExpr),
     Block(Vec<IRStmt>),
+    // raise X; None re-raises the exception being handled
+    Raise(Option<ExceptionKind>),
+    // failed assert: record ASSERTION_FAILED and the message hash at ASSERT_SLOT, then trap
+    AssertFail { msg_hash: u32 },
+    // try/except/else/finally; a handler catches any exception whose kind it lists
+    Try {
+        body: Vec<IRStmt>,
+        handlers: Vec<(Vec<ExceptionKind>, Vec<IRStmt>)>,
+        else_block: Vec<IRStmt>,
+        finally: Vec<IRStmt>,
+    },
+    // The code that follows comes from this 1-based Python line; emits nothing
+    Line(u32),
+}
+
+// Exceptions a job can raise and catch; the discriminant is the error code held while unwinding
+// Variants are spelled like the Python classes they stand for
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionKind {
+    ValueError = 1,
+    KeyError = 2,
+    ZeroDivisionError = 3,
+}
+
+impl ExceptionKind {
+    pub const ALL: [ExceptionKind; 3] = [ExceptionKind::ValueError, ExceptionKind::KeyError, ExceptionKind::ZeroDivisionError];
+
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// Kinds caught by `except <name>`, following Python's class hierarchy
+    pub fn caught_by(name: &str) -> Option<Vec<ExceptionKind>> {
+        match name {
+            "Exception" | "BaseException" => Some(Self::ALL.to_vec()),
+            "ValueError" => Some(vec![ExceptionKind::ValueError]),
+            "KeyError" | "LookupError" => Some(vec![ExceptionKind::KeyError]),
+            "ZeroDivisionError" | "ArithmeticError" => Some(vec![ExceptionKind::ZeroDivisionError]),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ExceptionKind> {
+        Self::ALL.into_iter().find(|k| format!("{:?}", k) == name)
+    }
 }
 
 // Expressions (pure: always return a value, no side effects)
@@ -39,22 +95,36 @@ pub enum IRStmt {
 pub enum IRExpr {
     Const(i32),
     Str(String),
+    Bytes(Vec<u8>),
     LoadLocal(String),
+    // Module-level global; unlike a local it may change across any call
+    LoadGlobal(String),
+    // Store into a local and yield the stored value (compiler temps)
+    AssignExpr { var: String, value: Box<IRExpr> },
     BinOp { op: BinOp, left: Box<IRExpr>, right: Box<IRExpr> },
     UnaryOp { op: UnaryOp, operand: Box<IRExpr> },
+    // Short-circuit and/or: evaluates to the deciding operand, not 0/1
+    BoolOp { op: BoolOp, left: Box<IRExpr>, right: Box<IRExpr> },
     Call { func: String, args: Vec<IRExpr> },
     // Deterministic collections using linear memory
     List(Vec<IRExpr>),           // List literal: [1, 2, 3]
+    Tuple(Vec<IRExpr>),          // Tuple literal: (1, 2) or return a, b
+    // Yield a tuple/list after checking it has exactly `count` elements (x, y = f())
+    Unpack { value: Box<IRExpr>, count: u32 },
+    // Yield a list, tuple or string after checking it is one (the iterable of for x in seq)
+    Sequence(Box<IRExpr>),
     Dict(Vec<(IRExpr, IRExpr)>), // Dict literal: {1: 2, 3: 4}
+    Set(Vec<IRExpr>),            // Set literal: {1, 2, 3} or set()
     Subscript {                  // Subscript: x[i]
         value: Box<IRExpr>,
         index: Box<IRExpr>,
     },
-    // String slice: s[start:end]
+    // Slice of a string, bytes, list or tuple: s[start:end:step]
     Slice {
         value: Box<IRExpr>,
         start: Option<Box<IRExpr>>,
         end: Option<Box<IRExpr>>,
+        step: Option<Box<IRExpr>>,
     },
     // Conditional expression: value_if_true if condition else value_if_false
     IfExpr {
@@ -72,6 +142,93 @@ pub enum IRExpr {
     FormatStr {
         parts: Vec<FormatPart>,
     },
+    // Run statements, then yield result (desugared comprehensions)
+    Block {
+        stmts: Vec<IRStmt>,
+        result: Box<IRExpr>,
+    },
+}
+
+impl IRStmt {
+    /// Whether this statement continues the loop it's in; a nested loop's continue is its own
+    pub fn continues(&self) -> bool {
+        let any = |stmts: &[IRStmt]| stmts.iter().any(IRStmt::continues);
+        match self {
+            IRStmt::Continue => true,
+            IRStmt::If { then_block, else_block, .. } => any(then_block) || any(else_block),
+            IRStmt::IfChain { branches, else_block } => branches.iter().any(|(_, b)| any(b)) || any(else_block),
+            IRStmt::Switch { arms, default, .. } => arms.iter().any(|a| any(a)) || any(default),
+            IRStmt::Block(body) => any(body),
+            IRStmt::Try { body, handlers, else_block, finally } => {
+                any(body) || handlers.iter().any(|(_, h)| any(h)) || any(else_block) || any(finally)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl IRExpr {
+    /// Compile-time value of an integer literal, including negated literals like `-1`
+    pub fn const_value(&self) -> Option<i32> {
+        match self {
+            IRExpr::Const(c) => Some(*c),
+            IRExpr::UnaryOp { op: UnaryOp::Neg, operand } => match **operand {
+                IRExpr::Const(c) => c.checked_neg(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Direct subexpressions in evaluation order; statements inside a Block are not included
+    pub fn children(&self) -> Vec<&IRExpr> {
+        match self {
+            IRExpr::Const(_) | IRExpr::Str(_) | IRExpr::Bytes(_) | IRExpr::LoadLocal(_) | IRExpr::LoadGlobal(_) => vec![],
+            IRExpr::AssignExpr { value, .. } | IRExpr::Unpack { value, .. } => vec![value],
+            IRExpr::BinOp { left, right, .. } | IRExpr::BoolOp { left, right, .. } => vec![left, right],
+            IRExpr::UnaryOp { operand, .. } | IRExpr::Sequence(operand) => vec![operand],
+            IRExpr::Call { args, .. } | IRExpr::List(args) | IRExpr::Tuple(args) | IRExpr::Set(args) => args.iter().collect(),
+            IRExpr::Dict(pairs) => pairs.iter().flat_map(|(k, v)| [k, v]).collect(),
+            IRExpr::Subscript { value, index } => vec![value, index],
+            IRExpr::Slice { value, start, end, step } => {
+                std::iter::once(&**value).chain([start, end, step].into_iter().flatten().map(|e| &**e)).collect()
+            }
+            IRExpr::IfExpr { cond, then_val, else_val } => vec![cond, then_val, else_val],
+            IRExpr::MethodCall { obj, args, .. } => std::iter::once(&**obj).chain(args.iter()).collect(),
+            IRExpr::FormatStr { parts } => parts.iter()
+                .filter_map(|p| match p {
+                    FormatPart::Expr(e) => Some(&**e),
+                    FormatPart::Literal(_) => None,
+                })
+                .collect(),
+            IRExpr::Block { result, .. } => vec![result],
+        }
+    }
+
+    /// Mutable counterpart of `children`
+    pub fn children_mut(&mut self) -> Vec<&mut IRExpr> {
+        match self {
+            IRExpr::Const(_) | IRExpr::Str(_) | IRExpr::Bytes(_) | IRExpr::LoadLocal(_) | IRExpr::LoadGlobal(_) => vec![],
+            IRExpr::AssignExpr { value, .. } | IRExpr::Unpack { value, .. } => vec![value],
+            IRExpr::BinOp { left, right, .. } | IRExpr::BoolOp { left, right, .. } => vec![left, right],
+            IRExpr::UnaryOp { operand, .. } | IRExpr::Sequence(operand) => vec![operand],
+            IRExpr::Call { args, .. } | IRExpr::List(args) | IRExpr::Tuple(args) | IRExpr::Set(args) => args.iter_mut().collect(),
+            IRExpr::Dict(pairs) => pairs.iter_mut().flat_map(|(k, v)| [k, v]).collect(),
+            IRExpr::Subscript { value, index } => vec![value, index],
+            IRExpr::Slice { value, start, end, step } => {
+                std::iter::once(&mut **value).chain([start, end, step].into_iter().flatten().map(|e| &mut **e)).collect()
+            }
+            IRExpr::IfExpr { cond, then_val, else_val } => vec![cond, then_val, else_val],
+            IRExpr::MethodCall { obj, args, .. } => std::iter::once(&mut **obj).chain(args.iter_mut()).collect(),
+            IRExpr::FormatStr { parts } => parts.iter_mut()
+                .filter_map(|p| match p {
+                    FormatPart::Expr(e) => Some(&mut **e),
+                    FormatPart::Literal(_) => None,
+                })
+                .collect(),
+            IRExpr::Block { result, .. } => vec![result],
+        }
+    }
 }
 
 // Format string part: either literal text or expression
@@ -85,7 +242,18 @@ pub enum FormatPart {
 #[derive(Debug, Clone)]
 pub enum BinOp {
     Add, Sub, Mul, Div, FloorDiv, Mod,
+    Pow,    // non-negative exponent; wraps like Mul
     Eq, Ne, Lt, Le, Gt, Ge,
+    In,     // left in right (dict keys, set members)
+    // Plain i32 bit operations, produced by the optimizer
+    Shl, ShrS, BitAnd,
+}
+
+// Boolean operators (short-circuit)
+#[derive(Debug, Clone)]
+pub enum BoolOp {
+    And,    // x and y
+    Or,     // x or y
 }
 
 // Unary operators
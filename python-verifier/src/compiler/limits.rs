@@ -0,0 +1,251 @@
+// Static resource limits of a compiled module, embedded as a custom section so hosts
+// can check their memory and stack budgets before accepting a job.
+//
+// Payload: six little-endian u32s
+//   version, max_call_depth, max_operand_stack, max_locals, memory_pages, flags
+// Stack figures are summed over the worst call chain starting at main; a recursive
+// module is bounded by the call-depth trap, so it is charged the limit times its
+// largest frame.
+
+use anyhow::{Result, bail};
+use std::collections::BTreeSet;
+use wasm_encoder::{CustomSection, Encode, Section};
+use wasmparser::{Operator, Parser, Payload, TypeRef, ValidPayload, Validator};
+
+use super::codegen::HEAP_LIMIT;
+
+pub const LIMITS_SECTION: &str = "certus.limits";
+const LIMITS_VERSION: u32 = 1;
+const FLAG_RECURSIVE: u32 = 1;
+const WASM_PAGE_SIZE: u32 = 65536;
+const PAYLOAD_LEN: usize = 24;
+
+// Host-side stack estimate: every value slot is a machine word, plus a fixed frame header
+const SLOT_BYTES: u64 = 8;
+const FRAME_OVERHEAD_BYTES: u64 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleLimits {
+    /// Live frames at the deepest point, main included
+    pub max_call_depth: u32,
+    /// Operand stack values across the worst call chain
+    pub max_operand_stack: u32,
+    /// Params and locals across the worst call chain
+    pub max_locals: u32,
+    /// Linear memory the heap may grow into
+    pub memory_pages: u32,
+    pub recursive: bool,
+}
+
+// Per-function figures gathered while validating the code section
+struct FunctionInfo {
+    operand_stack: u32,
+    locals: u32,
+    calls: BTreeSet<u32>,
+}
+
+impl ModuleLimits {
+    /// Measure a module; `call_depth_limit` bounds recursion (see the call-depth trap in codegen)
+    pub fn analyze(wasm: &[u8], call_depth_limit: u32) -> Result<Self> {
+        let mut validator = Validator::new();
+        let mut functions = Vec::new();
+        let mut imported = 0;
+
+        for payload in Parser::new(0).parse_all(wasm) {
+            let payload = payload?;
+            // calls index imported functions first
+            if let Payload::ImportSection(reader) = &payload {
+                for import in reader.clone() {
+                    if let TypeRef::Func(_) = import?.ty {
+                        imported += 1;
+                    }
+                }
+            }
+            if let ValidPayload::Func(func, body) = validator.payload(&payload)? {
+                let mut func = func.into_validator(Default::default());
+                let mut locals = body.get_locals_reader()?;
+                for _ in 0..locals.get_count() {
+                    let offset = locals.original_position();
+                    let (count, ty) = locals.read()?;
+                    func.define_locals(offset, count, ty)?;
+                }
+
+                let mut info = FunctionInfo { operand_stack: 0, locals: func.len_locals(), calls: BTreeSet::new() };
+                let mut ops = body.get_operators_reader()?;
+                while !ops.eof() {
+                    let (op, offset) = ops.read_with_offset()?;
+                    if let Operator::Call { function_index } = op {
+                        if function_index >= imported {
+                            info.calls.insert(function_index - imported);
+                        }
+                    }
+                    func.op(offset, &op)?;
+                    info.operand_stack = info.operand_stack.max(func.operand_stack_height());
+                }
+                func.finish(ops.original_position())?;
+                functions.push(info);
+            }
+        }
+
+        if functions.is_empty() {
+            bail!("module has no functions");
+        }
+
+        let memory_pages = (HEAP_LIMIT as u32).div_ceil(WASM_PAGE_SIZE);
+        let reachable = reachable_from_main(&functions);
+        if has_cycle(&functions, &reachable) {
+            // main, then up to call_depth_limit user frames before the trap
+            let frame = |f: fn(&FunctionInfo) -> u32| {
+                let main = f(&functions[0]);
+                let largest = reachable.iter().filter(|&&i| i != 0).map(|&i| f(&functions[i as usize])).max().unwrap_or(0);
+                main.saturating_add(largest.saturating_mul(call_depth_limit))
+            };
+            return Ok(Self {
+                max_call_depth: call_depth_limit + 1,
+                max_operand_stack: frame(|f| f.operand_stack),
+                max_locals: frame(|f| f.locals),
+                memory_pages,
+                recursive: true,
+            });
+        }
+
+        Ok(Self {
+            max_call_depth: longest_chain(&functions, |_| 1),
+            max_operand_stack: longest_chain(&functions, |f| f.operand_stack),
+            max_locals: longest_chain(&functions, |f| f.locals),
+            memory_pages,
+            recursive: false,
+        })
+    }
+
+    /// Conservative native stack needed by the deepest call chain
+    pub fn stack_bytes(&self) -> u64 {
+        (self.max_operand_stack as u64 + self.max_locals as u64) * SLOT_BYTES
+            + self.max_call_depth as u64 * FRAME_OVERHEAD_BYTES
+    }
+
+    /// Reject modules that need more memory or native stack than the host provides
+    pub fn check(&self, memory_pages: u32, stack_bytes: u64) -> Result<()> {
+        if self.memory_pages > memory_pages {
+            bail!("module needs {} memory pages, host allows {}", self.memory_pages, memory_pages);
+        }
+        if self.stack_bytes() > stack_bytes {
+            bail!("module needs {} bytes of stack, host allows {}", self.stack_bytes(), stack_bytes);
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let flags = if self.recursive { FLAG_RECURSIVE } else { 0 };
+        [LIMITS_VERSION, self.max_call_depth, self.max_operand_stack, self.max_locals, self.memory_pages, flags]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect()
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Self> {
+        if payload.len() != PAYLOAD_LEN {
+            bail!("{} payload must be {} bytes, got {}", LIMITS_SECTION, PAYLOAD_LEN, payload.len());
+        }
+        let word = |i: usize| u32::from_le_bytes(payload[i * 4..i * 4 + 4].try_into().unwrap());
+        if word(0) != LIMITS_VERSION {
+            bail!("unsupported {} version {}", LIMITS_SECTION, word(0));
+        }
+        Ok(Self {
+            max_call_depth: word(1),
+            max_operand_stack: word(2),
+            max_locals: word(3),
+            memory_pages: word(4),
+            recursive: word(5) & FLAG_RECURSIVE != 0,
+        })
+    }
+
+    /// Limits declared by a module, if it carries the section
+    pub fn read(wasm: &[u8]) -> Result<Option<Self>> {
+        for payload in Parser::new(0).parse_all(wasm) {
+            if let Payload::CustomSection(section) = payload? {
+                if section.name() == LIMITS_SECTION {
+                    return Self::decode(section.data()).map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Custom sections may appear anywhere, so the section simply goes last
+    pub fn append_to(&self, wasm: &mut Vec<u8>) {
+        let data = self.encode();
+        let section = CustomSection { name: LIMITS_SECTION.into(), data: data.as_slice().into() };
+        wasm.push(section.id());
+        section.encode(wasm);
+    }
+
+    /// The module with any limits section it already carries dropped and these appended
+    pub fn replace_in(&self, wasm: &[u8]) -> Result<Vec<u8>> {
+        let mut out = wasm[..8].to_vec();
+        for payload in Parser::new(0).parse_all(wasm) {
+            let payload = payload?;
+            if let Payload::CustomSection(section) = &payload {
+                if section.name() == LIMITS_SECTION {
+                    continue;
+                }
+            }
+            // code section entries come as payloads of their own, inside the code section's range
+            if let Some((id, range)) = payload.as_section() {
+                super::fuel::write_section(&mut out, id, &wasm[range]);
+            }
+        }
+        self.append_to(&mut out);
+        Ok(out)
+    }
+}
+
+fn reachable_from_main(functions: &[FunctionInfo]) -> BTreeSet<u32> {
+    let mut seen = BTreeSet::from([0]);
+    let mut stack = vec![0];
+    while let Some(f) = stack.pop() {
+        for &callee in &functions[f as usize].calls {
+            if seen.insert(callee) {
+                stack.push(callee);
+            }
+        }
+    }
+    seen
+}
+
+fn has_cycle(functions: &[FunctionInfo], reachable: &BTreeSet<u32>) -> bool {
+    // Kahn's algorithm over the reachable subgraph
+    let mut indegree = vec![0u32; functions.len()];
+    for &f in reachable {
+        for &callee in &functions[f as usize].calls {
+            indegree[callee as usize] += 1;
+        }
+    }
+    let mut ready: Vec<u32> = reachable.iter().copied().filter(|&f| indegree[f as usize] == 0).collect();
+    let mut removed = 0;
+    while let Some(f) = ready.pop() {
+        removed += 1;
+        for &callee in &functions[f as usize].calls {
+            indegree[callee as usize] -= 1;
+            if indegree[callee as usize] == 0 {
+                ready.push(callee);
+            }
+        }
+    }
+    removed < reachable.len()
+}
+
+// Heaviest path from main in an acyclic call graph
+fn longest_chain(functions: &[FunctionInfo], weight: fn(&FunctionInfo) -> u32) -> u32 {
+    fn visit(f: u32, functions: &[FunctionInfo], weight: fn(&FunctionInfo) -> u32, memo: &mut Vec<Option<u32>>) -> u32 {
+        if let Some(w) = memo[f as usize] {
+            return w;
+        }
+        let info = &functions[f as usize];
+        let deepest = info.calls.iter().map(|&c| visit(c, functions, weight, memo)).max().unwrap_or(0);
+        let total = weight(info).saturating_add(deepest);
+        memo[f as usize] = Some(total);
+        total
+    }
+    visit(0, functions, weight, &mut vec![None; functions.len()])
+}
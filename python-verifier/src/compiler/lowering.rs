@@ -1,15 +1,104 @@
-use anyhow::{Result, bail};
-use std::collections::{HashMap, BTreeMap};
-use rustpython_parser::ast;
+use anyhow::{Result, anyhow, bail};
+use std::collections::{HashMap, BTreeMap, BTreeSet};
+use rustpython_parser::ast::{self, Ranged};
+use sha2::{Sha256, Digest};
 
 use super::ir::*;
+use super::memory::TYPE_IDS;
+use super::optimize::{collect_calls_stmt, visit_exprs_mut};
 
-const MAX_LOCALS: usize = 256;
-const SCRATCH_LOCALS: u32 = 32;
+pub(crate) const MAX_LOCALS: usize = 256;
+pub(crate) const SCRATCH_LOCALS: u32 = 32;
+
+// Builtin functions: (name, min args, max args)
+const BUILTINS: &[(&str, usize, usize)] = &[
+    ("str", 1, 1),
+    ("len", 1, 1),
+    ("keccak256", 1, 1),
+    ("abs", 1, 1),
+    ("min", 1, usize::MAX),
+    ("max", 1, usize::MAX),
+    ("sum", 1, 2),
+    ("sorted", 1, 1),
+    ("pow", 2, 3),
+    ("set", 0, 0),
+    ("print", 0, usize::MAX),
+    ("type", 1, 1),
+    ("bytes", 1, 1),
+    ("isinstance", 2, 2),
+];
+
+// Methods codegen implements on builtin values; a class method may not reuse these names,
+// since `x.name()` is dispatched on the name alone
+const BUILTIN_METHODS: &[&str] = &[
+    "encode", "startswith", "endswith", "update", "digest", "hexdigest", "hex", "append", "add", "pop", "insert",
+];
+
+// Instances are dicts keyed by attribute id; this key holds the id of the instance's class
+const CLASS_KEY: i32 = 0;
 
 pub(crate) struct IRLowering {
     current_locals: BTreeMap<String, usize>,
     defined_functions: BTreeMap<String, bool>,
+    temp_counter: usize,
+    // Comprehension targets renamed to temps so they don't leak into the enclosing scope
+    comprehension_vars: HashMap<String, String>,
+    // Enclosing except blocks; a bare `raise` needs one
+    handler_depth: usize,
+    // Builtins the execution profile permits; None permits all
+    allowed_builtins: Option<BTreeSet<String>>,
+    // Names bound at module level or declared `global` in some function
+    module_names: BTreeSet<String>,
+    // Module names a function reads or writes; they live in Wasm globals instead of main's locals
+    globals: BTreeSet<String>,
+    // Inside a function: the names it assigns without declaring them global, params included
+    in_function: bool,
+    function_assigned: BTreeSet<String>,
+    declared_globals: BTreeSet<String>,
+    // Inside a nested function: the enclosing function's locals, which it captures by reading
+    enclosing: BTreeSet<String>,
+    // Functions nested in the top-level function being lowered, by their name there
+    nested: BTreeMap<String, NestedFunction>,
+    // Module-level classes by name, and the dict key of every attribute name used
+    classes: BTreeMap<String, ClassInfo>,
+    attributes: BTreeMap<String, i32>,
+    // Byte offset where each line of the source starts; empty when statements aren't marked
+    line_starts: Vec<u32>,
+}
+
+// A module-level class; its methods are lifted to functions named "Class.method" taking self first
+struct ClassInfo {
+    id: i32,
+    // arity without self
+    methods: BTreeMap<String, usize>,
+}
+
+// A function defined inside another, lifted to module level
+struct NestedFunction {
+    // "outer.inner", which no Python identifier can collide with
+    name: String,
+    arity: usize,
+    range: rustpython_parser::text_size::TextRange,
+    // Outer locals passed after the declared parameters, in this order
+    captures: BTreeSet<String>,
+}
+
+// What lowering a function body found out about its names
+struct Scope {
+    // assigned without a global declaration, params included
+    assigned: BTreeSet<String>,
+    captures: BTreeSet<String>,
+}
+
+// A for loop over any iterable: run `prelude`, count `var` over range(start, stop, step),
+// and start each iteration with `body`, which binds the loop target
+struct ForIter {
+    prelude: Vec<IRStmt>,
+    var: String,
+    start: IRExpr,
+    stop: IRExpr,
+    step: IRExpr,
+    body: Vec<IRStmt>,
 }
 
 impl IRLowering {
@@ -17,37 +106,147 @@ impl IRLowering {
         Self {
             current_locals: BTreeMap::new(),
             defined_functions: BTreeMap::new(),
+            temp_counter: 0,
+            comprehension_vars: HashMap::new(),
+            handler_depth: 0,
+            allowed_builtins: None,
+            module_names: BTreeSet::new(),
+            globals: BTreeSet::new(),
+            in_function: false,
+            function_assigned: BTreeSet::new(),
+            declared_globals: BTreeSet::new(),
+            enclosing: BTreeSet::new(),
+            nested: BTreeMap::new(),
+            classes: BTreeMap::new(),
+            attributes: BTreeMap::new(),
+            line_starts: Vec::new(),
         }
     }
 
+    pub fn with_allowed_builtins(mut self, allowed: Option<BTreeSet<String>>) -> Self {
+        self.allowed_builtins = allowed;
+        self
+    }
+
+    /// Put an IRStmt::Line before each statement, numbering lines of `source`, the parsed text
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i as u32 + 1))
+            .collect();
+        self
+    }
+
+    // Marker of the line a statement starts on
+    fn line_marker(&self, stmt: &ast::Stmt) -> Option<IRStmt> {
+        if self.line_starts.is_empty() {
+            return None;
+        }
+        let offset = u32::from(stmt.range().start());
+        Some(IRStmt::Line(self.line_starts.partition_point(|&start| start <= offset) as u32))
+    }
+
+    fn lower_body(&mut self, stmts: &[ast::Stmt]) -> Result<Vec<IRStmt>> {
+        let mut body = Vec::with_capacity(stmts.len());
+        for stmt in stmts {
+            body.extend(self.line_marker(stmt));
+            body.push(self.lower_stmt(stmt)?);
+        }
+        Ok(body)
+    }
+
+    // Compiler-generated local; the "__" prefix keeps it out of the way of user names
+    fn new_temp(&mut self) -> String {
+        let name = format!("__tmp{}", self.temp_counter);
+        self.temp_counter += 1;
+        let len = self.current_locals.len();
+        self.current_locals.insert(name.clone(), len);
+        name
+    }
+
     pub fn lower_module(&mut self, module: &ast::Mod) -> Result<IR> {
         let ast::Mod::Module(ast::ModModule { body, .. }) = module else {
             bail!("Only module-level Python supported");
         };
 
         self.validate_determinism(body)?;
+        if let Some(allowed) = &self.allowed_builtins {
+            if let Some(unknown) = allowed.iter().find(|name| !BUILTINS.iter().any(|(b, _, _)| b == name)) {
+                bail!("unknown builtin {} in execution profile", unknown);
+            }
+        }
 
+        for stmt in body {
+            if let ast::Stmt::ClassDef(class) = stmt {
+                self.register_class(class)?;
+            }
+        }
         for stmt in body {
             if let ast::Stmt::FunctionDef(func_def) = stmt {
+                if self.classes.contains_key(func_def.name.as_str()) {
+                    bail!("'{}' defined as both a class and a function", func_def.name);
+                }
                 self.defined_functions.insert(func_def.name.to_string(), true);
             }
         }
 
+        // Functions may be defined before the module-level assignments they read
+        let mut declared = BTreeSet::new();
+        let mut declared_in = |func_def: &ast::StmtFunctionDef| {
+            scope_names(&func_def.body, &mut BTreeSet::new(), &mut declared);
+            for stmt in &func_def.body {
+                if let ast::Stmt::FunctionDef(nested) = stmt {
+                    scope_names(&nested.body, &mut BTreeSet::new(), &mut declared);
+                }
+            }
+        };
+        for stmt in body {
+            match stmt {
+                ast::Stmt::FunctionDef(func_def) => declared_in(func_def),
+                ast::Stmt::ClassDef(class) => {
+                    for stmt in &class.body {
+                        if let ast::Stmt::FunctionDef(method) = stmt {
+                            declared_in(method);
+                        }
+                    }
+                }
+                _ => scope_names(std::slice::from_ref(stmt), &mut self.module_names, &mut BTreeSet::new()),
+            }
+        }
+        self.module_names.extend(declared);
+
         let mut functions = Vec::new();
         let mut main_body = Vec::new();
 
         for stmt in body {
             match stmt {
                 ast::Stmt::FunctionDef(func_def) => {
-                    functions.push(self.lower_function(func_def)?);
+                    functions.extend(self.lower_function(func_def)?);
+                }
+                ast::Stmt::ClassDef(class) => {
+                    for stmt in &class.body {
+                        if let ast::Stmt::FunctionDef(method) = stmt {
+                            let mut method = method.clone();
+                            method.name = ast::Identifier::new(format!("{}.{}", class.name, method.name));
+                            functions.extend(self.lower_function(&method)?);
+                        }
+                    }
                 }
                 _ => {
+                    main_body.extend(self.line_marker(stmt));
                     main_body.push(self.lower_stmt(stmt)?);
                 }
             }
         }
 
-        let main_locals: Vec<String> = self.current_locals.keys().cloned().collect();
+        // Module-level code lowered before a function claimed a name still treats it as a local
+        for stmt in main_body.iter_mut() {
+            self.globalize_stmt(stmt);
+        }
+
+        let main_locals: Vec<String> = self.current_locals.keys()
+            .filter(|name| !self.globals.contains(*name))
+            .cloned()
+            .collect();
         let local_map: HashMap<String, u32> = main_locals.iter()
             .enumerate()
             .map(|(i, name)| (name.clone(), i as u32))
@@ -70,7 +269,7 @@ impl IRLowering {
 
         Ok(IR::Module {
             functions,
-            _globals: vec![],
+            globals: self.globals.iter().cloned().collect(),
         })
     }
 
@@ -84,19 +283,33 @@ impl IRLowering {
     fn check_stmt_determinism(&self, stmt: &ast::Stmt) -> Result<()> {
         match stmt {
             ast::Stmt::Import(_) | ast::Stmt::ImportFrom(_) => {
-                // imports are validated separately in lib.rs (only json/hashlib allowed)
+                // imports are checked by the executor's validation policy (json and hashlib by default)
             }
             ast::Stmt::FunctionDef(f) => {
                 for s in &f.body {
                     self.check_stmt_determinism(s)?;
                 }
             }
-            ast::Stmt::If(if_stmt) => {
-                for s in &if_stmt.body {
+            ast::Stmt::ClassDef(c) => {
+                for s in &c.body {
                     self.check_stmt_determinism(s)?;
                 }
-                for s in &if_stmt.orelse {
-                    self.check_stmt_determinism(s)?;
+            }
+            ast::Stmt::If(if_stmt) => {
+                let mut current = if_stmt;
+                loop {
+                    for s in &current.body {
+                        self.check_stmt_determinism(s)?;
+                    }
+                    match current.orelse.as_slice() {
+                        [ast::Stmt::If(elif)] => current = elif,
+                        orelse => {
+                            for s in orelse {
+                                self.check_stmt_determinism(s)?;
+                            }
+                            break;
+                        }
+                    }
                 }
             }
             ast::Stmt::While(w) => {
@@ -109,19 +322,183 @@ impl IRLowering {
                     self.check_stmt_determinism(s)?;
                 }
             }
+            ast::Stmt::Try(t) => {
+                let handlers = t.handlers.iter().flat_map(|h| {
+                    let ast::ExceptHandler::ExceptHandler(h) = h;
+                    &h.body
+                });
+                for s in t.body.iter().chain(handlers).chain(&t.orelse).chain(&t.finalbody) {
+                    self.check_stmt_determinism(s)?;
+                }
+            }
             _ => {}
         }
         Ok(())
     }
 
 
-    fn lower_function(&mut self, func: &ast::StmtFunctionDef) -> Result<IRFunction> {
+    // Classes hold methods only: no bases, decorators or class attributes
+    fn register_class(&mut self, class: &ast::StmtClassDef) -> Result<()> {
+        if !class.bases.is_empty() || !class.keywords.is_empty() {
+            bail!("class '{}': base classes not supported", class.name);
+        }
+        if !class.decorator_list.is_empty() {
+            bail!("class '{}': decorators not supported", class.name);
+        }
+        let mut methods = BTreeMap::new();
+        for stmt in &class.body {
+            match stmt {
+                ast::Stmt::FunctionDef(method) => {
+                    if method.args.args.is_empty() {
+                        bail!("method '{}.{}' must take self", class.name, method.name);
+                    }
+                    if BUILTIN_METHODS.contains(&method.name.as_str()) {
+                        bail!("method '{}.{}' shadows the builtin method {}()", class.name, method.name, method.name);
+                    }
+                    if methods.insert(method.name.to_string(), method.args.args.len() - 1).is_some() {
+                        bail!("method '{}' defined twice in class '{}'", method.name, class.name);
+                    }
+                }
+                ast::Stmt::Pass(_) => {}
+                ast::Stmt::Expr(e) if matches!(&*e.value, ast::Expr::Constant(c) if matches!(c.value, ast::Constant::Str(_))) => {}
+                _ => bail!("class '{}' body may only define methods", class.name),
+            }
+        }
+        let id = self.classes.len() as i32 + 1;
+        if self.classes.insert(class.name.to_string(), ClassInfo { id, methods }).is_some() {
+            bail!("class '{}' defined twice", class.name);
+        }
+        Ok(())
+    }
+
+    // Dict key for `obj.attr`; methods have no key since they can only be called
+    fn attribute(&mut self, attr: &str) -> Result<IRExpr> {
+        if self.classes.is_empty() {
+            bail!("attribute '{}' used but no class is defined", attr);
+        }
+        if self.classes.values().any(|c| c.methods.contains_key(attr)) {
+            bail!("method '{}' can only be called", attr);
+        }
+        let next = self.attributes.len() as i32 + 1;
+        Ok(IRExpr::Const(*self.attributes.entry(attr.to_string()).or_insert(next)))
+    }
+
+    // A top-level function and the functions defined directly in its body. Nested functions
+    // become functions of their own named "outer.inner"; the outer locals they read are passed
+    // as extra trailing parameters at each call, so they can be called but never escape.
+    fn lower_function(&mut self, func: &ast::StmtFunctionDef) -> Result<Vec<IRFunction>> {
+        let nested_defs: Vec<&ast::StmtFunctionDef> = func.body.iter()
+            .filter_map(|s| match s {
+                ast::Stmt::FunctionDef(f) => Some(f),
+                _ => None,
+            })
+            .collect();
+        if nested_defs.is_empty() {
+            return Ok(vec![self.lower_scope(func, BTreeSet::new())?.0]);
+        }
+
+        let mut enclosing = BTreeSet::new();
+        let mut declared = BTreeSet::new();
+        scope_names(&func.body, &mut enclosing, &mut declared);
+        enclosing = &enclosing - &declared;
+        enclosing.extend(func.args.args.iter().map(|arg| arg.def.arg.to_string()));
+
+        for def in &nested_defs {
+            if def.body.iter().any(|s| matches!(s, ast::Stmt::FunctionDef(_))) {
+                bail!("function '{}' nested in '{}' cannot define functions itself", def.name, func.name);
+            }
+            let nested = NestedFunction {
+                name: format!("{}.{}", func.name, def.name),
+                arity: def.args.args.len(),
+                range: def.range,
+                captures: BTreeSet::new(),
+            };
+            if self.nested.insert(def.name.to_string(), nested).is_some() {
+                bail!("function '{}' defined twice in '{}'", def.name, func.name);
+            }
+        }
+
+        let mut lowered = Vec::new();
+        for def in &nested_defs {
+            let (function, scope) = self.lower_scope(def, enclosing.clone())?;
+            self.nested.get_mut(def.name.as_str()).unwrap().captures = scope.captures.clone();
+            lowered.push((function, scope));
+        }
+
+        // A nested function calling another passes along what the callee captures
+        loop {
+            let mut changed = false;
+            for (function, scope) in &lowered {
+                let mut called = BTreeSet::new();
+                for stmt in &function.body {
+                    collect_calls_stmt(stmt, &mut called);
+                }
+                let caller = function.name.rsplit('.').next().unwrap();
+                for (name, callee) in self.nested.iter().filter(|(_, n)| called.contains(&n.name)) {
+                    if let Some(shadowed) = callee.captures.iter().find(|c| scope.assigned.contains(*c)) {
+                        bail!("'{}' calls '{}', which reads the outer '{}' that '{}' shadows", caller, name, shadowed, caller);
+                    }
+                }
+                let inherited: BTreeSet<String> = self.nested.values()
+                    .filter(|n| called.contains(&n.name))
+                    .flat_map(|n| n.captures.iter().cloned())
+                    .collect();
+                let captures = &mut self.nested.get_mut(caller).unwrap().captures;
+                let before = captures.len();
+                captures.extend(inherited);
+                changed |= captures.len() != before;
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let (outer, _) = self.lower_scope(func, BTreeSet::new())?;
+        let nested = std::mem::take(&mut self.nested);
+        let captured_args = |stmts: &mut Vec<IRStmt>| visit_exprs_mut(stmts, &mut |expr| {
+            if let IRExpr::Call { func, args } = expr {
+                if let Some(callee) = nested.values().find(|n| n.name == *func) {
+                    args.extend(callee.captures.iter().map(|c| IRExpr::LoadLocal(c.clone())));
+                }
+            }
+        });
+
+        let mut functions = vec![outer];
+        for (mut function, _) in lowered {
+            let captures = &nested[function.name.rsplit('.').next().unwrap()].captures;
+            let mut params = function._params.clone();
+            params.extend(captures.iter().cloned());
+            let others = function.locals.iter().filter(|l| !params.contains(l)).cloned().collect();
+            (function.locals, function.local_map) = function_frame(&function.name, &params, others)?;
+            function._params = params;
+            functions.push(function);
+        }
+        for function in &mut functions {
+            captured_args(&mut function.body);
+        }
+        Ok(functions)
+    }
+
+    // One function body; `enclosing` holds the outer function's locals when it is nested
+    fn lower_scope(&mut self, func: &ast::StmtFunctionDef, enclosing: BTreeSet<String>) -> Result<(IRFunction, Scope)> {
         let mut params = Vec::new();
         for arg in &func.args.args {
             params.push(arg.def.arg.to_string());
         }
 
-        let mut body = Vec::new();
+        let mut assigned = BTreeSet::new();
+        let mut declared = BTreeSet::new();
+        scope_names(&func.body, &mut assigned, &mut declared);
+        if let Some(param) = params.iter().find(|p| declared.contains(*p)) {
+            bail!("name '{}' is parameter and global in function '{}'", param, func.name);
+        }
+        self.globals.extend(declared.iter().cloned());
+        self.function_assigned = &assigned - &declared;
+        self.function_assigned.extend(params.iter().cloned());
+        self.declared_globals = declared;
+        self.enclosing = enclosing;
+        self.in_function = true;
+
         let saved_locals = self.current_locals.clone();
         self.current_locals.clear();
 
@@ -130,41 +507,40 @@ impl IRLowering {
             self.current_locals.insert(param_name.clone(), idx);
         }
 
-        for stmt in &func.body {
-            body.push(self.lower_stmt(stmt)?);
-        }
+        let body = self.lower_body(&func.body)?;
+
+        // Outer locals read here; everything else it doesn't assign is a global or starts at 0
+        let captures = self.current_locals.keys()
+            .filter(|k| self.enclosing.contains(*k) && !self.function_assigned.contains(*k))
+            .cloned()
+            .collect();
 
         // Build locals vec: params first, then other locals (sorted for determinism)
-        let mut locals = params.clone();
         let mut other_locals: Vec<String> = self.current_locals.keys()
             .filter(|k| !params.contains(k))
             .cloned()
             .collect();
         other_locals.sort(); // Sort for determinism
-        locals.extend(other_locals);
-
-        // Build local map
-        let local_map: HashMap<String, u32> = locals.iter()
-            .enumerate()
-            .map(|(i, name)| (name.clone(), i as u32))
-            .collect();
-
-        let temp_count = SCRATCH_LOCALS as usize;
-
-        if locals.len() + temp_count > MAX_LOCALS {
-            bail!("Function '{}' has too many locals: {} + {} scratch", func.name, locals.len(), temp_count);
-        }
+        let (locals, local_map) = function_frame(&func.name, &params, other_locals)?;
 
         self.current_locals = saved_locals;
+        let scope = Scope { assigned: std::mem::take(&mut self.function_assigned), captures };
+        self.declared_globals.clear();
+        self.enclosing.clear();
+        self.in_function = false;
 
-        Ok(IRFunction {
-            name: func.name.to_string(),
+        let name = match self.nested.get(func.name.as_str()) {
+            Some(nested) if nested.range == func.range => nested.name.clone(),
+            _ => func.name.to_string(),
+        };
+        Ok((IRFunction {
+            name,
             _params: params,
             locals,
             local_map,
-            temp_locals: temp_count as u32,
+            temp_locals: SCRATCH_LOCALS,
             body,
-        })
+        }, scope))
     }
 
     fn lower_stmt(&mut self, stmt: &ast::Stmt) -> Result<IRStmt> {
@@ -177,24 +553,46 @@ impl IRLowering {
                 // Handle tuple unpacking: a, b = expr1, expr2
                 if let ast::Expr::Tuple(tuple) = &assign.targets[0] {
                     let ast::Expr::Tuple(values) = &*assign.value else {
-                        bail!("Tuple unpacking requires tuple value");
+                        // a, b = f()  ==>  t = unpack(f(), 2); a = t[0]; b = t[1]
+                        let value = Box::new(self.lower_expr(&assign.value)?);
+                        let temp = self.new_temp();
+                        let mut stmts = vec![IRStmt::Assign {
+                            var: temp.clone(),
+                            value: IRExpr::Unpack { value, count: tuple.elts.len() as u32 },
+                        }];
+
+                        for (i, target) in tuple.elts.iter().enumerate() {
+                            let ast::Expr::Name(name) = target else {
+                                bail!("Tuple unpacking target must be variable");
+                            };
+                            stmts.push(self.assign(&name.id, IRExpr::Subscript {
+                                value: Box::new(IRExpr::LoadLocal(temp.clone())),
+                                index: Box::new(IRExpr::Const(i as i32)),
+                            }));
+                        }
+
+                        return Ok(IRStmt::Block(stmts));
                     };
 
                     if tuple.elts.len() != values.elts.len() {
                         bail!("Tuple unpacking size mismatch");
                     }
 
+                    // a, b = b, a  ==>  t0 = b; t1 = a; a = t0; b = t1
+                    // Every value is evaluated before any target is bound
                     let mut stmts = Vec::new();
-                    for (target, value) in tuple.elts.iter().zip(values.elts.iter()) {
+                    let mut temps = Vec::new();
+                    for value in &values.elts {
+                        let value = self.lower_expr(value)?;
+                        let temp = self.new_temp();
+                        stmts.push(IRStmt::Assign { var: temp.clone(), value });
+                        temps.push(temp);
+                    }
+                    for (target, temp) in tuple.elts.iter().zip(temps) {
                         let ast::Expr::Name(name) = target else {
                             bail!("Tuple unpacking target must be variable");
                         };
-                        let var_name = name.id.to_string();
-                        let len = self.current_locals.len();
-                        self.current_locals.entry(var_name.clone()).or_insert(len);
-
-                        let value_expr = self.lower_expr(value)?;
-                        stmts.push(IRStmt::Assign { var: var_name, value: value_expr });
+                        stmts.push(self.assign(&name.id, IRExpr::LoadLocal(temp)));
                     }
 
                     return Ok(IRStmt::Block(stmts));
@@ -208,15 +606,19 @@ impl IRLowering {
                     return Ok(IRStmt::SubscriptAssign { target, index, value });
                 }
 
+                // obj.attr = value
+                if let ast::Expr::Attribute(attr) = &assign.targets[0] {
+                    let target = Box::new(self.lower_expr(&attr.value)?);
+                    let index = Box::new(self.attribute(&attr.attr)?);
+                    let value = Box::new(self.lower_expr(&assign.value)?);
+                    return Ok(IRStmt::SubscriptAssign { target, index, value });
+                }
+
                 let ast::Expr::Name(name) = &assign.targets[0] else {
                     bail!("Only simple variable assignment supported");
                 };
-                let var_name = name.id.to_string();
-                let len = self.current_locals.len();
-                self.current_locals.entry(var_name.clone()).or_insert(len);
-
                 let value = self.lower_expr(&assign.value)?;
-                Ok(IRStmt::Assign { var: var_name, value })
+                Ok(self.assign(&name.id, value))
             }
             ast::Stmt::Return(ret) => {
                 let value = if let Some(v) = &ret.value {
@@ -227,52 +629,48 @@ impl IRLowering {
                 Ok(IRStmt::Return(value))
             }
             ast::Stmt::If(if_stmt) => {
-                let cond = self.lower_expr(&if_stmt.test)?;
-                let then_block = if_stmt.body.iter()
-                    .map(|s| self.lower_stmt(s))
-                    .collect::<Result<Vec<_>>>()?;
-                let else_block = if_stmt.orelse.iter()
-                    .map(|s| self.lower_stmt(s))
-                    .collect::<Result<Vec<_>>>()?;
-                Ok(IRStmt::If { cond, then_block, else_block })
+                // Walk elif ladders iteratively so deep chains don't nest
+                let mut branches = Vec::new();
+                let mut current = if_stmt;
+                loop {
+                    let cond = self.lower_expr(&current.test)?;
+                    let body = self.lower_body(&current.body)?;
+                    branches.push((cond, body));
+                    match current.orelse.as_slice() {
+                        [ast::Stmt::If(elif)] => current = elif,
+                        _ => break,
+                    }
+                }
+                let else_block = self.lower_body(&current.orelse)?;
+
+                if branches.len() == 1 {
+                    let (cond, then_block) = branches.pop().unwrap();
+                    Ok(IRStmt::If { cond, then_block, else_block })
+                } else {
+                    Ok(IRStmt::IfChain { branches, else_block })
+                }
             }
             ast::Stmt::While(while_stmt) => {
+                if !while_stmt.orelse.is_empty() {
+                    bail!("while/else not supported");
+                }
                 let cond = self.lower_expr(&while_stmt.test)?;
-                let body = while_stmt.body.iter()
-                    .map(|s| self.lower_stmt(s))
-                    .collect::<Result<Vec<_>>>()?;
+                let body = self.lower_body(&while_stmt.body)?;
                 Ok(IRStmt::While { cond, body })
             }
             ast::Stmt::For(for_stmt) => {
-                let ast::Expr::Name(var) = &*for_stmt.target else {
-                    bail!("For loop target must be simple variable");
-                };
-                let var_name = var.id.to_string();
-                let len = self.current_locals.len();
-                self.current_locals.entry(var_name.clone()).or_insert(len);
-
-                let iter = match &*for_stmt.iter {
-                    ast::Expr::Call(call) => {
-                        let ast::Expr::Name(fname) = &*call.func else {
-                            bail!("For loop iter must be range()");
-                        };
-                        if fname.id.as_str() != "range" {
-                            bail!("For loop iter must be range(), got {}", fname.id);
-                        }
-
-                        if call.args.len() == 1 {
-                            self.lower_expr(&call.args[0])?
-                        } else {
-                            bail!("Only range(n) supported, not range(start, stop)");
-                        }
-                    }
-                    _ => bail!("For loop iter must be range(n)"),
-                };
+                if !for_stmt.orelse.is_empty() {
+                    bail!("for/else not supported");
+                }
+                let ForIter { mut prelude, var, start, stop, step, mut body } =
+                    self.lower_for_iter(&for_stmt.target, &for_stmt.iter)?;
 
-                let body = for_stmt.body.iter()
-                    .map(|s| self.lower_stmt(s))
-                    .collect::<Result<Vec<_>>>()?;
-                Ok(IRStmt::For { var: var_name, iter, body })
+                body.extend(self.lower_body(&for_stmt.body)?);
+                if prelude.is_empty() {
+                    return Ok(IRStmt::For { var, start, stop, step, body });
+                }
+                prelude.push(IRStmt::For { var, start, stop, step, body });
+                Ok(IRStmt::Block(prelude))
             }
             ast::Stmt::Expr(expr) => {
                 Ok(IRStmt::Expr(self.lower_expr(&expr.value)?))
@@ -280,17 +678,11 @@ impl IRLowering {
             ast::Stmt::Break(_) => {
                 Ok(IRStmt::Break)
             }
+            ast::Stmt::Continue(_) => {
+                Ok(IRStmt::Continue)
+            }
             ast::Stmt::AugAssign(aug) => {
                 // Handle augmented assignment: x += 1, x -= 1, etc.
-                let ast::Expr::Name(name) = &*aug.target else {
-                    bail!("Augmented assignment only supports simple variables");
-                };
-                let var_name = name.id.to_string();
-
-                // Ensure variable exists in locals
-                let len = self.current_locals.len();
-                self.current_locals.entry(var_name.clone()).or_insert(len);
-
                 // Convert aug.op to BinOp
                 let op = match aug.op {
                     ast::Operator::Add => BinOp::Add,
@@ -299,24 +691,683 @@ impl IRLowering {
                     ast::Operator::Div => BinOp::Div,
                     ast::Operator::FloorDiv => BinOp::FloorDiv,
                     ast::Operator::Mod => BinOp::Mod,
+                    ast::Operator::Pow => BinOp::Pow,
                     _ => bail!("Unsupported augmented assignment operator"),
                 };
 
+                // obj.attr += expr  ->  t = obj; t.attr = t.attr + expr
+                if let ast::Expr::Attribute(attr) = &*aug.target {
+                    let obj = self.lower_expr(&attr.value)?;
+                    let index = self.attribute(&attr.attr)?;
+                    let temp = self.new_temp();
+                    let current = IRExpr::Subscript {
+                        value: Box::new(IRExpr::LoadLocal(temp.clone())),
+                        index: Box::new(index.clone()),
+                    };
+                    let right = Box::new(self.lower_expr(&aug.value)?);
+                    return Ok(IRStmt::Block(vec![
+                        IRStmt::Assign { var: temp.clone(), value: obj },
+                        IRStmt::SubscriptAssign {
+                            target: Box::new(IRExpr::LoadLocal(temp)),
+                            index: Box::new(index),
+                            value: Box::new(IRExpr::BinOp { op, left: Box::new(current), right }),
+                        },
+                    ]));
+                }
+
+                // obj[key] += expr  ->  t = obj; k = key; t[k] = t[k] + expr, each evaluated once
+                if let ast::Expr::Subscript(sub) = &*aug.target {
+                    if matches!(&*sub.slice, ast::Expr::Slice(_)) {
+                        bail!("Augmented assignment to a slice not supported");
+                    }
+                    let obj = self.lower_expr(&sub.value)?;
+                    let key = self.lower_expr(&sub.slice)?;
+                    let (temp, key_temp) = (self.new_temp(), self.new_temp());
+                    let current = IRExpr::Subscript {
+                        value: Box::new(IRExpr::LoadLocal(temp.clone())),
+                        index: Box::new(IRExpr::LoadLocal(key_temp.clone())),
+                    };
+                    let right = Box::new(self.lower_expr(&aug.value)?);
+                    return Ok(IRStmt::Block(vec![
+                        IRStmt::Assign { var: temp.clone(), value: obj },
+                        IRStmt::Assign { var: key_temp.clone(), value: key },
+                        IRStmt::SubscriptAssign {
+                            target: Box::new(IRExpr::LoadLocal(temp)),
+                            index: Box::new(IRExpr::LoadLocal(key_temp)),
+                            value: Box::new(IRExpr::BinOp { op, left: Box::new(current), right }),
+                        },
+                    ]));
+                }
+
+                let ast::Expr::Name(name) = &*aug.target else {
+                    bail!("Augmented assignment only supports simple variables, attributes and subscripts");
+                };
+
                 // Transform: x += expr  ->  x = x + expr
-                let left = Box::new(IRExpr::LoadLocal(var_name.clone()));
+                let left = Box::new(self.load(&name.id));
                 let right = Box::new(self.lower_expr(&aug.value)?);
                 let value = IRExpr::BinOp { op, left, right };
 
-                Ok(IRStmt::Assign { var: var_name, value })
+                Ok(self.assign(&name.id, value))
             }
             ast::Stmt::Import(_) | ast::Stmt::ImportFrom(_) => {
                 // Allow imports, actual functionality handled at runtime
                 Ok(IRStmt::Block(vec![]))
             }
+            // Resolved before the enclosing function is lowered; a no-op at module level
+            ast::Stmt::Global(_) => Ok(IRStmt::Block(vec![])),
+            ast::Stmt::Nonlocal(_) => bail!("nonlocal not supported: nested functions can read outer variables but not assign them"),
+            // Lifted out by lower_function
+            ast::Stmt::FunctionDef(f) if self.nested.get(f.name.as_str()).is_some_and(|n| n.range == f.range) => {
+                Ok(IRStmt::Block(vec![]))
+            }
+            ast::Stmt::ClassDef(class) => bail!("class '{}' must be defined at module level", class.name),
+            ast::Stmt::Try(try_stmt) => {
+                let body = self.lower_body(&try_stmt.body)?;
+
+                let mut handlers = Vec::new();
+                for handler in &try_stmt.handlers {
+                    let ast::ExceptHandler::ExceptHandler(handler) = handler;
+                    if handler.name.is_some() {
+                        bail!("'except ... as' not supported: exceptions carry no value");
+                    }
+                    let kinds = match &handler.type_ {
+                        Some(ty) => exception_kinds(ty)?,
+                        None => ExceptionKind::ALL.to_vec(),
+                    };
+                    self.handler_depth += 1;
+                    let handler_body = self.lower_body(&handler.body);
+                    self.handler_depth -= 1;
+                    handlers.push((kinds, handler_body?));
+                }
+
+                let else_block = self.lower_body(&try_stmt.orelse)?;
+                let finally = self.lower_body(&try_stmt.finalbody)?;
+
+                // finally runs on the fall-through path only, so nothing may jump past it
+                if !finally.is_empty() {
+                    let guarded = body.iter().chain(handlers.iter().flat_map(|(_, h)| h)).chain(&else_block);
+                    if guarded.clone().any(|s| escapes(s, false)) || finally.iter().any(|s| escapes(s, false)) {
+                        bail!("return, break and continue not supported inside try with finally");
+                    }
+                }
+                Ok(IRStmt::Try { body, handlers, else_block, finally })
+            }
+            ast::Stmt::Raise(raise) => {
+                if raise.cause.is_some() {
+                    bail!("'raise ... from' not supported");
+                }
+                let Some(exc) = &raise.exc else {
+                    if self.handler_depth == 0 {
+                        bail!("bare raise outside an except block");
+                    }
+                    return Ok(IRStmt::Raise(None));
+                };
+                // raise ValueError or raise ValueError("message"); the message is dropped
+                let name = match &**exc {
+                    ast::Expr::Name(name) => &name.id,
+                    ast::Expr::Call(call) => {
+                        let is_message = |arg: &ast::Expr| matches!(arg, ast::Expr::Constant(c) if matches!(c.value, ast::Constant::Str(_)));
+                        match &*call.func {
+                            ast::Expr::Name(name) if call.keywords.is_empty() && call.args.len() <= 1 && call.args.iter().all(is_message) => &name.id,
+                            _ => bail!("raise arguments must be a single string literal"),
+                        }
+                    }
+                    _ => bail!("Unsupported raise expression"),
+                };
+                let kind = ExceptionKind::from_name(name)
+                    .ok_or_else(|| anyhow!("Cannot raise {}: only ValueError, KeyError and ZeroDivisionError", name))?;
+                Ok(IRStmt::Raise(Some(kind)))
+            }
+            ast::Stmt::Delete(delete) => {
+                // del d[k]  ==>  d.pop(k), which removes a dict entry or a list element
+                let mut stmts = Vec::new();
+                for target in &delete.targets {
+                    let ast::Expr::Subscript(sub) = target else {
+                        bail!("del only supports subscripts: del d[key] or del items[index]");
+                    };
+                    if matches!(&*sub.slice, ast::Expr::Slice(_)) {
+                        bail!("del of a slice not supported");
+                    }
+                    stmts.push(IRStmt::Expr(IRExpr::MethodCall {
+                        obj: Box::new(self.lower_expr(&sub.value)?),
+                        method: "pop".to_string(),
+                        args: vec![self.lower_expr(&sub.slice)?],
+                    }));
+                }
+                Ok(IRStmt::Block(stmts))
+            }
+            ast::Stmt::Assert(assert) => {
+                let msg_hash = match assert.msg.as_deref() {
+                    None => 0,
+                    Some(ast::Expr::Constant(c)) => match &c.value {
+                        ast::Constant::Str(msg) => assert_message_hash(msg),
+                        _ => bail!("assert message must be a string literal"),
+                    },
+                    Some(_) => bail!("assert message must be a string literal"),
+                };
+                Ok(IRStmt::If {
+                    cond: self.lower_expr(&assert.test)?,
+                    then_block: vec![],
+                    else_block: vec![IRStmt::AssertFail { msg_hash }],
+                })
+            }
             _ => bail!("Unsupported statement type"),
         }
     }
 
+    // range(stop) | range(start, stop) | range(start, stop, step) as (start, stop, step)
+    fn lower_range(&mut self, iter: &ast::Expr) -> Result<(IRExpr, IRExpr, IRExpr)> {
+        let (start, stop, step) = match iter {
+            ast::Expr::Call(call) => {
+                let ast::Expr::Name(fname) = &*call.func else {
+                    bail!("For loop iter must be range()");
+                };
+                if fname.id.as_str() != "range" {
+                    bail!("For loop iter must be range(), got {}", fname.id);
+                }
+
+                match call.args.len() {
+                    1 => (IRExpr::Const(0), self.lower_expr(&call.args[0])?, IRExpr::Const(1)),
+                    2 => (self.lower_expr(&call.args[0])?, self.lower_expr(&call.args[1])?, IRExpr::Const(1)),
+                    3 => (
+                        self.lower_expr(&call.args[0])?,
+                        self.lower_expr(&call.args[1])?,
+                        self.lower_expr(&call.args[2])?,
+                    ),
+                    n => bail!("range() expects 1 to 3 arguments, got {}", n),
+                }
+            }
+            _ => bail!("For loop iter must be range(n)"),
+        };
+
+        if let IRExpr::Const(0) = step {
+            bail!("range() arg 3 must not be zero");
+        }
+        Ok((start, stop, step))
+    }
+
+    // for target in iter, as a counted loop plus the statements that bind `target` each iteration:
+    //   for i in range(a, b, c)       ==>  i counts directly
+    //   for x in seq                  ==>  t = seq; for k in range(len(t)): x = t[k]
+    //   for i, x in enumerate(seq, s) ==>  t = seq; for k in range(len(t)): i = k + s; x = t[k]
+    //   for x, y in zip(a, b)         ==>  t0 = a; t1 = b; for k in range(min(len(t0), len(t1))): x = t0[k]; y = t1[k]
+    // Iterables are evaluated once, before the target is bound, and their length is fixed when
+    // the loop starts
+    fn lower_for_iter(&mut self, target: &ast::Expr, iter: &ast::Expr) -> Result<ForIter> {
+        let call = match iter {
+            ast::Expr::Call(call) if call.keywords.is_empty() => match &*call.func {
+                ast::Expr::Name(name) => Some((name.id.as_str(), &call.args)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some(("range", _)) = call {
+            let ast::Expr::Name(name) = target else {
+                bail!("For loop target over range() must be simple variable");
+            };
+            let (start, stop, step) = self.lower_range(iter)?;
+            if !self.is_global(&name.id) {
+                let var = name.id.to_string();
+                self.declare_local(&var);
+                return Ok(ForIter { prelude: vec![], var, start, stop, step, body: vec![] });
+            }
+            // Globals can't be loop counters; count a temp and copy it out each iteration
+            let var = self.new_temp();
+            let body = vec![self.assign(&name.id, IRExpr::LoadLocal(var.clone()))];
+            return Ok(ForIter { prelude: vec![], var, start, stop, step, body });
+        }
+
+        let Some((name, args)) = call.filter(|(name, _)| matches!(*name, "enumerate" | "zip")) else {
+            let (prelude, index, stop, item) = self.lower_sequence_iter(iter)?;
+            let mut body = Vec::new();
+            self.bind_loop_target(target, item, &mut body)?;
+            return Ok(ForIter { prelude, var: index, start: IRExpr::Const(0), stop, step: IRExpr::Const(1), body });
+        };
+
+        let mut prelude = Vec::new();
+        let mut sequence = |this: &mut Self, expr: &ast::Expr| -> Result<String> {
+            let value = IRExpr::Sequence(Box::new(this.lower_expr(expr)?));
+            let temp = this.new_temp();
+            prelude.push(IRStmt::Assign { var: temp.clone(), value });
+            Ok(temp)
+        };
+        let load = |var: &String| IRExpr::LoadLocal(var.clone());
+        let len = |var: &String| IRExpr::Call { func: "len".to_string(), args: vec![load(var)] };
+
+        let index = self.new_temp();
+        let element = |seq: &String| IRExpr::Subscript { value: Box::new(load(seq)), index: Box::new(load(&index)) };
+        let (stop, item) = match name {
+            "enumerate" => {
+                if args.is_empty() || args.len() > 2 {
+                    bail!("enumerate() takes 1 to 2 arguments, got {}", args.len());
+                }
+                let seq = sequence(self, &args[0])?;
+                let count = match args.get(1) {
+                    None => load(&index),
+                    Some(start) => {
+                        let value = self.lower_expr(start)?;
+                        let temp = self.new_temp();
+                        prelude.push(IRStmt::Assign { var: temp.clone(), value });
+                        IRExpr::BinOp { op: BinOp::Add, left: Box::new(load(&index)), right: Box::new(load(&temp)) }
+                    }
+                };
+                (len(&seq), vec![count, element(&seq)])
+            }
+            _ => {
+                if args.is_empty() {
+                    bail!("zip() needs at least 1 argument");
+                }
+                let seqs = args.iter()
+                    .map(|arg| sequence(self, arg))
+                    .collect::<Result<Vec<_>>>()?;
+                let stop = match seqs.as_slice() {
+                    [seq] => len(seq),
+                    _ => IRExpr::Call { func: "min".to_string(), args: seqs.iter().map(len).collect() },
+                };
+                (stop, seqs.iter().map(element).collect())
+            }
+        };
+
+        // enumerate() and zip() yield tuples; unpack them in place instead of building one
+        let mut body = Vec::new();
+        match target {
+            ast::Expr::Tuple(tuple) if tuple.elts.len() == item.len() => {
+                for (elt, value) in tuple.elts.iter().zip(item) {
+                    self.bind_loop_target(elt, value, &mut body)?;
+                }
+            }
+            ast::Expr::Tuple(tuple) => {
+                bail!("Cannot unpack {} values into {} loop targets", item.len(), tuple.elts.len());
+            }
+            _ => self.bind_loop_target(target, IRExpr::Tuple(item), &mut body)?,
+        }
+        Ok(ForIter { prelude, var: index, start: IRExpr::Const(0), stop, step: IRExpr::Const(1), body })
+    }
+
+    // seq as a counted loop over a copy taken once: t = seq; for k in range(len(t)), yielding
+    // t[k]. Returns the statements that take the copy, the counter, the stop and the element.
+    fn lower_sequence_iter(&mut self, iter: &ast::Expr) -> Result<(Vec<IRStmt>, String, IRExpr, IRExpr)> {
+        let index = self.new_temp();
+        let value = IRExpr::Sequence(Box::new(self.lower_expr(iter)?));
+        let seq = self.new_temp();
+        let load = |var: &String| IRExpr::LoadLocal(var.clone());
+        let stop = IRExpr::Call { func: "len".to_string(), args: vec![load(&seq)] };
+        let item = IRExpr::Subscript { value: Box::new(load(&seq)), index: Box::new(load(&index)) };
+        Ok((vec![IRStmt::Assign { var: seq, value }], index, stop, item))
+    }
+
+    // target = value inside a loop body; tuple targets unpack like `a, b = value`
+    fn bind_loop_target(&mut self, target: &ast::Expr, value: IRExpr, body: &mut Vec<IRStmt>) -> Result<()> {
+        match target {
+            ast::Expr::Name(name) => body.push(self.assign(&name.id, value)),
+            ast::Expr::Tuple(tuple) => {
+                let temp = self.new_temp();
+                body.push(IRStmt::Assign {
+                    var: temp.clone(),
+                    value: IRExpr::Unpack { value: Box::new(value), count: tuple.elts.len() as u32 },
+                });
+                for (i, elt) in tuple.elts.iter().enumerate() {
+                    let value = IRExpr::Subscript {
+                        value: Box::new(IRExpr::LoadLocal(temp.clone())),
+                        index: Box::new(IRExpr::Const(i as i32)),
+                    };
+                    self.bind_loop_target(elt, value, body)?;
+                }
+            }
+            _ => bail!("For loop target must be variable or tuple of variables"),
+        }
+        Ok(())
+    }
+
+    fn declare_local(&mut self, name: &str) {
+        let len = self.current_locals.len();
+        self.current_locals.entry(name.to_string()).or_insert(len);
+    }
+
+    // Inside a function, module names it declares global or uses without assigning.
+    // At module level everything starts out local; `globalize_stmt` moves shared names over.
+    fn is_global(&self, name: &str) -> bool {
+        self.in_function && (self.declared_globals.contains(name)
+            || !self.function_assigned.contains(name) && !self.enclosing.contains(name) && self.module_names.contains(name))
+    }
+
+    // A builtin type name the program doesn't rebind evaluates to what type() yields for it
+    fn type_id(&self, name: &str) -> Option<i32> {
+        let bound = self.current_locals.contains_key(name) || self.module_names.contains(name)
+            || self.function_assigned.contains(name) || self.enclosing.contains(name);
+        TYPE_IDS.iter().find(|(n, _)| *n == name && !bound).map(|&(_, id)| id)
+    }
+
+    // isinstance(x, T) or isinstance(x, (T1, T2))  ==>  (t := type(x)) == T1 or t == T2
+    fn lower_isinstance(&mut self, value: &ast::Expr, types: &ast::Expr) -> Result<IRExpr> {
+        let names: Vec<&ast::Expr> = match types {
+            ast::Expr::Tuple(tuple) => tuple.elts.iter().collect(),
+            other => vec![other],
+        };
+        let ids = names.into_iter()
+            .map(|t| match t {
+                ast::Expr::Name(name) => self.type_id(&name.id)
+                    .ok_or_else(|| anyhow!("isinstance() does not support type '{}'", name.id)),
+                _ => Err(anyhow!("isinstance() takes a type or a tuple of types")),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let Some((&first, rest)) = ids.split_first() else {
+            bail!("isinstance() takes a type or a tuple of types");
+        };
+        let value = self.lower_expr(value)?;
+
+        let tag = IRExpr::Call { func: "type".to_string(), args: vec![value] };
+        let is = |tag: IRExpr, id: i32| IRExpr::BinOp { op: BinOp::Eq, left: Box::new(tag), right: Box::new(IRExpr::Const(id)) };
+        if rest.is_empty() {
+            return Ok(is(tag, first));
+        }
+        let temp = self.new_temp();
+        let mut test = is(IRExpr::AssignExpr { var: temp.clone(), value: Box::new(tag) }, first);
+        for &id in rest {
+            test = IRExpr::BoolOp { op: BoolOp::Or, left: Box::new(test), right: Box::new(is(IRExpr::LoadLocal(temp.clone()), id)) };
+        }
+        Ok(test)
+    }
+
+    fn load(&mut self, name: &str) -> IRExpr {
+        if self.is_global(name) {
+            self.globals.insert(name.to_string());
+            return IRExpr::LoadGlobal(name.to_string());
+        }
+        self.declare_local(name);
+        IRExpr::LoadLocal(name.to_string())
+    }
+
+    fn assign(&mut self, name: &str, value: IRExpr) -> IRStmt {
+        if self.is_global(name) {
+            self.globals.insert(name.to_string());
+            return IRStmt::AssignGlobal { var: name.to_string(), value };
+        }
+        self.declare_local(name);
+        IRStmt::Assign { var: name.to_string(), value }
+    }
+
+    // Rewrite module-level uses of names that ended up global
+    fn globalize_stmt(&mut self, stmt: &mut IRStmt) {
+        match stmt {
+            IRStmt::Assign { var, value } if self.globals.contains(var) => {
+                let var = std::mem::take(var);
+                let value = std::mem::replace(value, IRExpr::Const(0));
+                *stmt = IRStmt::AssignGlobal { var, value };
+            }
+            IRStmt::For { var, body, .. } if self.globals.contains(var) => {
+                let global = std::mem::replace(var, self.new_temp());
+                body.insert(0, IRStmt::AssignGlobal { var: global, value: IRExpr::LoadLocal(var.clone()) });
+            }
+            _ => {}
+        }
+        match stmt {
+            IRStmt::Assign { value, .. } | IRStmt::AssignGlobal { value, .. } | IRStmt::Return(value) | IRStmt::Expr(value) => {
+                self.globalize_expr(value)
+            }
+            IRStmt::SubscriptAssign { target, index, value } => {
+                self.globalize_expr(target);
+                self.globalize_expr(index);
+                self.globalize_expr(value);
+            }
+            IRStmt::If { cond, then_block, else_block } => {
+                self.globalize_expr(cond);
+                then_block.iter_mut().chain(else_block).for_each(|s| self.globalize_stmt(s));
+            }
+            IRStmt::IfChain { branches, else_block } => {
+                for (cond, body) in branches {
+                    self.globalize_expr(cond);
+                    body.iter_mut().for_each(|s| self.globalize_stmt(s));
+                }
+                else_block.iter_mut().for_each(|s| self.globalize_stmt(s));
+            }
+            IRStmt::Switch { value, arms, default, .. } => {
+                self.globalize_expr(value);
+                arms.iter_mut().flatten().chain(default).for_each(|s| self.globalize_stmt(s));
+            }
+            IRStmt::While { cond, body } => {
+                self.globalize_expr(cond);
+                body.iter_mut().for_each(|s| self.globalize_stmt(s));
+            }
+            IRStmt::For { start, stop, step, body, .. } => {
+                self.globalize_expr(start);
+                self.globalize_expr(stop);
+                self.globalize_expr(step);
+                body.iter_mut().for_each(|s| self.globalize_stmt(s));
+            }
+            IRStmt::Block(body) => body.iter_mut().for_each(|s| self.globalize_stmt(s)),
+            IRStmt::Try { body, handlers, else_block, finally } => body.iter_mut()
+                .chain(handlers.iter_mut().flat_map(|(_, h)| h))
+                .chain(else_block)
+                .chain(finally)
+                .for_each(|s| self.globalize_stmt(s)),
+            IRStmt::Break | IRStmt::Continue | IRStmt::Raise(_) | IRStmt::AssertFail { .. } | IRStmt::Line(_) => {}
+        }
+    }
+
+    fn globalize_expr(&mut self, expr: &mut IRExpr) {
+        match expr {
+            IRExpr::LoadLocal(var) if self.globals.contains(var) => {
+                *expr = IRExpr::LoadGlobal(std::mem::take(var));
+                return;
+            }
+            IRExpr::Block { stmts, .. } => stmts.iter_mut().for_each(|s| self.globalize_stmt(s)),
+            _ => {}
+        }
+        for child in expr.children_mut() {
+            self.globalize_expr(child);
+        }
+    }
+
+    // Point(args)  ==>  t = {}; t[CLASS_KEY] = id; Point.__init__(t, args); t
+    fn lower_instantiation(&mut self, class: &str, call: &ast::ExprCall) -> Result<IRExpr> {
+        let info = &self.classes[class];
+        let id = info.id;
+        let init = info.methods.get("__init__").copied();
+        let arity = init.unwrap_or(0);
+        if call.args.len() != arity || !call.keywords.is_empty() {
+            bail!("{}() takes {} positional argument{}", class, arity, if arity == 1 { "" } else { "s" });
+        }
+        let mut args = call.args.iter()
+            .map(|a| self.lower_expr(a))
+            .collect::<Result<Vec<_>>>()?;
+
+        let obj = self.new_temp();
+        let mut stmts = vec![
+            IRStmt::Assign { var: obj.clone(), value: IRExpr::Dict(vec![]) },
+            IRStmt::SubscriptAssign {
+                target: Box::new(IRExpr::LoadLocal(obj.clone())),
+                index: Box::new(IRExpr::Const(CLASS_KEY)),
+                value: Box::new(IRExpr::Const(id)),
+            },
+        ];
+        if init.is_some() {
+            args.insert(0, IRExpr::LoadLocal(obj.clone()));
+            stmts.push(IRStmt::Expr(IRExpr::Call { func: format!("{}.__init__", class), args }));
+        }
+        Ok(IRExpr::Block { stmts, result: Box::new(IRExpr::LoadLocal(obj)) })
+    }
+
+    // obj.method(args) calls Class.method(obj, args). Without types the class is known only at
+    // run time when several classes define the method, so those calls test the instance's class id.
+    fn lower_method_call(&mut self, obj: &ast::Expr, method: &str, call: &ast::ExprCall) -> Result<IRExpr> {
+        let n = call.args.len();
+        let candidates: Vec<(String, i32)> = self.classes.iter()
+            .filter(|(_, c)| c.methods.get(method) == Some(&n))
+            .map(|(name, c)| (format!("{}.{}", name, method), c.id))
+            .collect();
+        if candidates.is_empty() || !call.keywords.is_empty() {
+            bail!("no method {}() takes {} positional argument{}", method, n, if n == 1 { "" } else { "s" });
+        }
+        let obj = self.lower_expr(obj)?;
+        let args = call.args.iter()
+            .map(|a| self.lower_expr(a))
+            .collect::<Result<Vec<_>>>()?;
+        let call_on = |func: &str, obj: IRExpr| {
+            let mut args = args.clone();
+            args.insert(0, obj);
+            IRExpr::Call { func: func.to_string(), args }
+        };
+
+        if let [(func, _)] = candidates.as_slice() {
+            return Ok(call_on(func, obj));
+        }
+
+        // the last candidate takes whatever the others don't match
+        let temp = self.new_temp();
+        let this = IRExpr::LoadLocal(temp.clone());
+        let (last, _) = candidates.last().unwrap();
+        let mut dispatch = call_on(last, this.clone());
+        for (func, id) in candidates.iter().rev().skip(1) {
+            let class_id = IRExpr::Subscript { value: Box::new(this.clone()), index: Box::new(IRExpr::Const(CLASS_KEY)) };
+            dispatch = IRExpr::IfExpr {
+                cond: Box::new(IRExpr::BinOp { op: BinOp::Eq, left: Box::new(class_id), right: Box::new(IRExpr::Const(*id)) }),
+                then_val: Box::new(call_on(func, this.clone())),
+                else_val: Box::new(dispatch),
+            };
+        }
+        Ok(IRExpr::Block {
+            stmts: vec![IRStmt::Assign { var: temp, value: obj }],
+            result: Box::new(dispatch),
+        })
+    }
+
+    // Desugar comprehension generators into nested for loops with if guards around `element`
+    fn lower_comprehension(
+        &mut self,
+        generators: &[ast::Comprehension],
+        element: impl FnOnce(&mut Self) -> Result<IRStmt>,
+    ) -> Result<Vec<IRStmt>> {
+        let mut loops = Vec::new();
+        let mut shadowed = Vec::new();
+        for generator in generators {
+            if generator.is_async {
+                bail!("Async comprehensions not supported");
+            }
+            let ast::Expr::Name(target) = &generator.target else {
+                bail!("Comprehension target must be simple variable");
+            };
+
+            // The iterable is evaluated before the target is bound; range() counts the target
+            // directly, anything else is indexed like `for x in seq`
+            let is_range = matches!(&generator.iter, ast::Expr::Call(call)
+                if matches!(&*call.func, ast::Expr::Name(f) if f.id.as_str() == "range"));
+            let (prelude, counter, start, stop, step, var, bind) = if is_range {
+                let (start, stop, step) = self.lower_range(&generator.iter)?;
+                let var = self.new_temp();
+                (vec![], var.clone(), start, stop, step, var, vec![])
+            } else {
+                let (prelude, index, stop, item) = self.lower_sequence_iter(&generator.iter)?;
+                let var = self.new_temp();
+                let bind = vec![IRStmt::Assign { var: var.clone(), value: item }];
+                (prelude, index, IRExpr::Const(0), stop, IRExpr::Const(1), var, bind)
+            };
+            let name = target.id.to_string();
+            shadowed.push((name.clone(), self.comprehension_vars.insert(name, var)));
+
+            let guards = generator.ifs.iter()
+                .map(|cond| self.lower_expr(cond))
+                .collect::<Result<Vec<_>>>()?;
+            loops.push((prelude, counter, start, stop, step, bind, guards));
+        }
+
+        let element = element(self);
+
+        for (name, previous) in shadowed.into_iter().rev() {
+            match previous {
+                Some(var) => self.comprehension_vars.insert(name, var),
+                None => self.comprehension_vars.remove(&name),
+            };
+        }
+
+        let mut body = vec![element?];
+        for (mut prelude, var, start, stop, step, mut bind, guards) in loops.into_iter().rev() {
+            for cond in guards.into_iter().rev() {
+                body = vec![IRStmt::If { cond, then_block: body, else_block: vec![] }];
+            }
+            bind.append(&mut body);
+            prelude.push(IRStmt::For { var, start, stop, step, body: bind });
+            body = prelude;
+        }
+        Ok(body)
+    }
+
+    // "a{}b{1}".format(x, y)  ==>  t0 = x; t1 = y; f"a{t0}b{t1}"
+    // Arguments are evaluated once, left to right, whether or not the template uses them
+    fn lower_format(&mut self, template: &str, args: &[ast::Expr]) -> Result<IRExpr> {
+        let mut stmts = Vec::new();
+        let mut temps = Vec::new();
+        for arg in args {
+            let value = self.lower_expr(arg)?;
+            let temp = self.new_temp();
+            stmts.push(IRStmt::Assign { var: temp.clone(), value });
+            temps.push(temp);
+        }
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut auto_index = 0;
+        let (mut automatic, mut manual) = (false, false);
+        let mut chars = template.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => bail!("Single '}}' encountered in format string"),
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => bail!("Single '{{' encountered in format string"),
+                        }
+                    }
+                    let index = if field.is_empty() {
+                        automatic = true;
+                        auto_index += 1;
+                        auto_index - 1
+                    } else if let Ok(index) = field.parse::<usize>() {
+                        manual = true;
+                        index
+                    } else if field.contains([':', '!']) {
+                        bail!("Format specs and conversions not supported: {{{}}}", field);
+                    } else {
+                        bail!("Named format fields not supported: {{{}}}", field);
+                    };
+                    if automatic && manual {
+                        bail!("Cannot mix automatic and manual field numbering in format string");
+                    }
+                    let Some(temp) = temps.get(index) else {
+                        bail!("Replacement index {} out of range for format()", index);
+                    };
+                    if !literal.is_empty() {
+                        parts.push(FormatPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(FormatPart::Expr(Box::new(IRExpr::LoadLocal(temp.clone()))));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() || parts.is_empty() {
+            parts.push(FormatPart::Literal(literal));
+        }
+
+        let result = Box::new(IRExpr::FormatStr { parts });
+        if stmts.is_empty() {
+            return Ok(*result);
+        }
+        Ok(IRExpr::Block { stmts, result })
+    }
+
     fn lower_expr(&mut self, expr: &ast::Expr) -> Result<IRExpr> {
         match expr {
             ast::Expr::Constant(c) => {
@@ -330,14 +1381,24 @@ impl IRLowering {
                     ast::Constant::Bool(b) => Ok(IRExpr::Const(if *b { 1 } else { 0 })),
                     ast::Constant::None => Ok(IRExpr::Const(0)),
                     ast::Constant::Str(s) => Ok(IRExpr::Str(s.to_string())),
+                    ast::Constant::Bytes(b) => Ok(IRExpr::Bytes(b.clone())),
                     _ => bail!("Unsupported constant type"),
                 }
             }
             ast::Expr::Name(name) => {
-                let var_name = name.id.to_string();
-                let len = self.current_locals.len();
-                self.current_locals.entry(var_name.clone()).or_insert(len);
-                Ok(IRExpr::LoadLocal(var_name))
+                if let Some(var) = self.comprehension_vars.get(name.id.as_str()) {
+                    return Ok(IRExpr::LoadLocal(var.clone()));
+                }
+                if self.nested.contains_key(name.id.as_str()) {
+                    bail!("nested function '{}' can only be called", name.id);
+                }
+                if self.classes.contains_key(name.id.as_str()) {
+                    bail!("class '{}' can only be instantiated", name.id);
+                }
+                if let Some(id) = self.type_id(&name.id) {
+                    return Ok(IRExpr::Const(id));
+                }
+                Ok(self.load(&name.id))
             }
             ast::Expr::BinOp(binop) => {
                 let left = Box::new(self.lower_expr(&binop.left)?);
@@ -349,26 +1410,55 @@ impl IRLowering {
                     ast::Operator::Div => BinOp::Div,
                     ast::Operator::FloorDiv => BinOp::FloorDiv,
                     ast::Operator::Mod => BinOp::Mod,
+                    ast::Operator::Pow => BinOp::Pow,
                     _ => bail!("Unsupported binary operator"),
                 };
                 Ok(IRExpr::BinOp { op, left, right })
             }
             ast::Expr::Compare(cmp) => {
-                if cmp.ops.len() != 1 || cmp.comparators.len() != 1 {
-                    bail!("Chained comparisons not supported");
-                }
-                let left = Box::new(self.lower_expr(&cmp.left)?);
-                let right = Box::new(self.lower_expr(&cmp.comparators[0])?);
-                let op = match &cmp.ops[0] {
-                    ast::CmpOp::Eq => BinOp::Eq,
-                    ast::CmpOp::NotEq => BinOp::Ne,
-                    ast::CmpOp::Lt => BinOp::Lt,
-                    ast::CmpOp::LtE => BinOp::Le,
-                    ast::CmpOp::Gt => BinOp::Gt,
-                    ast::CmpOp::GtE => BinOp::Ge,
-                    _ => bail!("Unsupported comparison operator"),
-                };
-                Ok(IRExpr::BinOp { op, left, right })
+                if cmp.ops.len() != cmp.comparators.len() || cmp.ops.is_empty() {
+                    bail!("Malformed comparison");
+                }
+
+                // a < b < c  ==>  (a < (t := b)) and (t < c), so b is evaluated once
+                let last = cmp.ops.len() - 1;
+                let mut left = self.lower_expr(&cmp.left)?;
+                let mut chain: Option<IRExpr> = None;
+
+                for (i, (cmp_op, comparator)) in cmp.ops.iter().zip(cmp.comparators.iter()).enumerate() {
+                    let (op, negate) = match cmp_op {
+                        ast::CmpOp::Eq => (BinOp::Eq, false),
+                        ast::CmpOp::NotEq => (BinOp::Ne, false),
+                        ast::CmpOp::Lt => (BinOp::Lt, false),
+                        ast::CmpOp::LtE => (BinOp::Le, false),
+                        ast::CmpOp::Gt => (BinOp::Gt, false),
+                        ast::CmpOp::GtE => (BinOp::Ge, false),
+                        ast::CmpOp::In => (BinOp::In, false),
+                        ast::CmpOp::NotIn => (BinOp::In, true),
+                        _ => bail!("Unsupported comparison operator"),
+                    };
+
+                    let right = self.lower_expr(comparator)?;
+                    let (right, next_left) = if i < last {
+                        let temp = self.new_temp();
+                        let assign = IRExpr::AssignExpr { var: temp.clone(), value: Box::new(right) };
+                        (assign, IRExpr::LoadLocal(temp))
+                    } else {
+                        (right, IRExpr::Const(0))
+                    };
+
+                    let mut compare = IRExpr::BinOp { op, left: Box::new(left), right: Box::new(right) };
+                    if negate {
+                        compare = IRExpr::UnaryOp { op: UnaryOp::Not, operand: Box::new(compare) };
+                    }
+                    chain = Some(match chain {
+                        None => compare,
+                        Some(prev) => IRExpr::BoolOp { op: BoolOp::And, left: Box::new(prev), right: Box::new(compare) },
+                    });
+                    left = next_left;
+                }
+
+                chain.ok_or_else(|| anyhow::anyhow!("Malformed comparison"))
             }
             ast::Expr::UnaryOp(unary) => {
                 let operand = Box::new(self.lower_expr(&unary.operand)?);
@@ -382,20 +1472,37 @@ impl IRLowering {
             ast::Expr::Call(call) => {
                 // Check if this is a method call (obj.method(args)) or module.function(args)
                 if let ast::Expr::Attribute(attr) = &*call.func {
-                    // Check if it's hashlib.sha256()
+                    // Check if it's hashlib.sha256() / hashlib.sha3_256()
                     if let ast::Expr::Name(module_name) = &*attr.value {
-                        if module_name.id.as_str() == "hashlib" && attr.attr.as_str() == "sha256" {
-                            if call.args.len() != 1 {
-                                bail!("hashlib.sha256() takes exactly 1 argument");
+                        let algorithm = attr.attr.as_str();
+                        if module_name.id.as_str() == "hashlib" && (algorithm == "sha256" || algorithm == "sha3_256") {
+                            if call.args.len() > 1 {
+                                bail!("hashlib.{}() takes at most 1 argument", algorithm);
                             }
-                            let arg = self.lower_expr(&call.args[0])?;
+                            let args = call.args.iter()
+                                .map(|a| self.lower_expr(a))
+                                .collect::<Result<Vec<_>>>()?;
                             return Ok(IRExpr::Call {
-                                func: "hashlib.sha256".to_string(),
-                                args: vec![arg],
+                                func: format!("hashlib.{}", algorithm),
+                                args,
                             });
                         }
                     }
 
+                    // "...".format(args) is resolved at compile time
+                    if let ast::Expr::Constant(c) = &*attr.value {
+                        if let (ast::Constant::Str(template), "format") = (&c.value, attr.attr.as_str()) {
+                            if !call.keywords.is_empty() {
+                                bail!("format() keyword arguments not supported");
+                            }
+                            return self.lower_format(template, &call.args);
+                        }
+                    }
+
+                    if self.classes.values().any(|c| c.methods.contains_key(attr.attr.as_str())) {
+                        return self.lower_method_call(&attr.value, &attr.attr, call);
+                    }
+
                     // Regular method call
                     let obj = Box::new(self.lower_expr(&attr.value)?);
                     let method = attr.attr.to_string();
@@ -410,20 +1517,58 @@ impl IRLowering {
                 };
                 let fname = func_name.id.to_string();
 
+                if let Some(nested) = self.nested.get(&fname) {
+                    if call.args.len() != nested.arity || !call.keywords.is_empty() {
+                        bail!("{}() takes {} positional argument{}", fname, nested.arity, if nested.arity == 1 { "" } else { "s" });
+                    }
+                    // captured locals are appended once every nested function is lowered
+                    let func = nested.name.clone();
+                    let args = call.args.iter()
+                        .map(|a| self.lower_expr(a))
+                        .collect::<Result<Vec<_>>>()?;
+                    return Ok(IRExpr::Call { func, args });
+                }
+
+                if self.classes.contains_key(&fname) {
+                    return self.lower_instantiation(&fname, call);
+                }
+
                 if fname == "range" {
                     bail!("range() must be used only in for loops");
                 }
 
-                // Handle builtin str() function
-                if fname == "str" {
-                    if call.args.len() != 1 {
-                        bail!("str() takes exactly 1 argument");
+                // Builtins lower to a Call by name; codegen emits them inline
+                if let Some(&(_, min_args, max_args)) = BUILTINS.iter().find(|(name, _, _)| *name == fname) {
+                    if self.allowed_builtins.as_ref().is_some_and(|allowed| !allowed.contains(&fname)) {
+                        bail!("{}() is not allowed by the execution profile", fname);
                     }
-                    let arg = self.lower_expr(&call.args[0])?;
-                    return Ok(IRExpr::Call {
-                        func: "str".to_string(),
-                        args: vec![arg],
-                    });
+                    let n = call.args.len();
+                    if n < min_args || n > max_args {
+                        match (min_args, max_args) {
+                            (lo, hi) if lo == hi => bail!("{}() takes exactly {} argument{}", fname, lo, if lo == 1 { "" } else { "s" }),
+                            (lo, usize::MAX) => bail!("{}() takes at least {} argument{}", fname, lo, if lo == 1 { "" } else { "s" }),
+                            (lo, hi) => bail!("{}() takes {} to {} arguments", fname, lo, hi),
+                        }
+                    }
+                    if fname == "print" && !call.keywords.is_empty() {
+                        bail!("print() keyword arguments not supported");
+                    }
+                    if fname == "set" {
+                        return Ok(IRExpr::Set(vec![]));
+                    }
+                    if fname == "isinstance" {
+                        return self.lower_isinstance(&call.args[0], &call.args[1]);
+                    }
+                    let mut args = call.args.iter()
+                        .map(|a| self.lower_expr(a))
+                        .collect::<Result<Vec<_>>>()?;
+                    // pow(x, y) is x ** y; only the modular form needs its own codegen
+                    if fname == "pow" && args.len() == 2 {
+                        let right = Box::new(args.pop().unwrap());
+                        let left = Box::new(args.pop().unwrap());
+                        return Ok(IRExpr::BinOp { op: BinOp::Pow, left, right });
+                    }
+                    return Ok(IRExpr::Call { func: fname, args });
                 }
 
                 if !self.defined_functions.contains_key(&fname) {
@@ -445,6 +1590,60 @@ impl IRLowering {
                     .collect::<Result<Vec<_>>>()?;
                 Ok(IRExpr::List(elements))
             }
+            ast::Expr::ListComp(comp) => {
+                // [elt for x in range(n) if cond]  ==>  t = []; for x in ...: if cond: t.append(elt)
+                let list = self.new_temp();
+                let mut stmts = vec![IRStmt::Assign { var: list.clone(), value: IRExpr::List(vec![]) }];
+                stmts.extend(self.lower_comprehension(&comp.generators, |this| {
+                    let value = this.lower_expr(&comp.elt)?;
+                    Ok(IRStmt::Expr(IRExpr::MethodCall {
+                        obj: Box::new(IRExpr::LoadLocal(list.clone())),
+                        method: "append".to_string(),
+                        args: vec![value],
+                    }))
+                })?);
+                Ok(IRExpr::Block { stmts, result: Box::new(IRExpr::LoadLocal(list)) })
+            }
+            ast::Expr::SetComp(comp) => {
+                let set = self.new_temp();
+                let mut stmts = vec![IRStmt::Assign { var: set.clone(), value: IRExpr::Set(vec![]) }];
+                stmts.extend(self.lower_comprehension(&comp.generators, |this| {
+                    let value = this.lower_expr(&comp.elt)?;
+                    Ok(IRStmt::Expr(IRExpr::MethodCall {
+                        obj: Box::new(IRExpr::LoadLocal(set.clone())),
+                        method: "add".to_string(),
+                        args: vec![value],
+                    }))
+                })?);
+                Ok(IRExpr::Block { stmts, result: Box::new(IRExpr::LoadLocal(set)) })
+            }
+            ast::Expr::DictComp(comp) => {
+                // {k: v for x in range(n)}  ==>  t = {}; for x in ...: t[k] = v
+                let dict = self.new_temp();
+                let mut stmts = vec![IRStmt::Assign { var: dict.clone(), value: IRExpr::Dict(vec![]) }];
+                stmts.extend(self.lower_comprehension(&comp.generators, |this| {
+                    let index = Box::new(this.lower_expr(&comp.key)?);
+                    let value = Box::new(this.lower_expr(&comp.value)?);
+                    Ok(IRStmt::SubscriptAssign {
+                        target: Box::new(IRExpr::LoadLocal(dict.clone())),
+                        index,
+                        value,
+                    })
+                })?);
+                Ok(IRExpr::Block { stmts, result: Box::new(IRExpr::LoadLocal(dict)) })
+            }
+            ast::Expr::Set(set) => {
+                let elements = set.elts.iter()
+                    .map(|e| self.lower_expr(e))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(IRExpr::Set(elements))
+            }
+            ast::Expr::Tuple(tuple) => {
+                let elements = tuple.elts.iter()
+                    .map(|e| self.lower_expr(e))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(IRExpr::Tuple(elements))
+            }
             ast::Expr::Dict(dict) => {
                 // Lower dict literal to IR
                 if dict.keys.len() != dict.values.len() {
@@ -471,16 +1670,44 @@ impl IRLowering {
                         .map(|e| self.lower_expr(e))
                         .transpose()?
                         .map(Box::new);
-                    if slice.step.is_some() {
-                        bail!("Slice step not supported");
+                    let step = slice.step.as_ref()
+                        .map(|e| self.lower_expr(e))
+                        .transpose()?
+                        .map(Box::new);
+                    if let Some(IRExpr::Const(0)) = step.as_deref() {
+                        bail!("slice step cannot be zero");
                     }
-                    Ok(IRExpr::Slice { value, start, end })
+                    Ok(IRExpr::Slice { value, start, end, step })
                 } else {
                     let value = Box::new(self.lower_expr(&sub.value)?);
                     let index = Box::new(self.lower_expr(&sub.slice)?);
                     Ok(IRExpr::Subscript { value, index })
                 }
             }
+            ast::Expr::Attribute(attr) => {
+                let value = Box::new(self.lower_expr(&attr.value)?);
+                let index = Box::new(self.attribute(&attr.attr)?);
+                Ok(IRExpr::Subscript { value, index })
+            }
+            ast::Expr::BoolOp(boolop) => {
+                // a and b and c == (a and b) and c
+                let op = match boolop.op {
+                    ast::BoolOp::And => BoolOp::And,
+                    ast::BoolOp::Or => BoolOp::Or,
+                };
+                let mut values = boolop.values.iter();
+                let first = values.next()
+                    .ok_or_else(|| anyhow::anyhow!("Empty boolean expression"))?;
+                let mut expr = self.lower_expr(first)?;
+                for value in values {
+                    expr = IRExpr::BoolOp {
+                        op: op.clone(),
+                        left: Box::new(expr),
+                        right: Box::new(self.lower_expr(value)?),
+                    };
+                }
+                Ok(expr)
+            }
             ast::Expr::IfExp(ifexp) => {
                 // Python ternary: body if test else orelse
                 let cond = Box::new(self.lower_expr(&ifexp.test)?);
@@ -501,6 +1728,10 @@ impl IRLowering {
                             }
                         }
                         ast::Expr::FormattedValue(fv) => {
+                            // every value already goes through str(), so only !s is accepted
+                            if fv.format_spec.is_some() || !matches!(fv.conversion, ast::ConversionFlag::None | ast::ConversionFlag::Str) {
+                                bail!("Format specs and !r/!a conversions not supported in f-strings");
+                            }
                             let expr = Box::new(self.lower_expr(&fv.value)?);
                             parts.push(FormatPart::Expr(expr));
                         }
@@ -514,3 +1745,103 @@ impl IRLowering {
     }
 
 }
+
+// Exception kinds named by an except clause: a single name or a tuple of names
+fn exception_kinds(ty: &ast::Expr) -> Result<Vec<ExceptionKind>> {
+    let names: Vec<&ast::Expr> = match ty {
+        ast::Expr::Tuple(tuple) => tuple.elts.iter().collect(),
+        other => vec![other],
+    };
+    let mut kinds = Vec::new();
+    for name in names {
+        let ast::Expr::Name(name) = name else {
+            bail!("except clause must name exception types");
+        };
+        let caught = ExceptionKind::caught_by(&name.id)
+            .ok_or_else(|| anyhow!("Unsupported exception type '{}'", name.id))?;
+        for kind in caught {
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+    }
+    Ok(kinds)
+}
+
+// Locals in index order (params first) and their indices
+fn function_frame(name: &str, params: &[String], others: Vec<String>) -> Result<(Vec<String>, HashMap<String, u32>)> {
+    let mut locals = params.to_vec();
+    locals.extend(others);
+    if locals.len() + SCRATCH_LOCALS as usize > MAX_LOCALS {
+        bail!("Function '{}' has too many locals: {} + {} scratch", name, locals.len(), SCRATCH_LOCALS);
+    }
+    let local_map = locals.iter()
+        .enumerate()
+        .map(|(i, name)| (name.clone(), i as u32))
+        .collect();
+    Ok((locals, local_map))
+}
+
+// Names a block binds (assignment and loop targets) and those it declares `global`,
+// without descending into nested function definitions
+fn scope_names(stmts: &[ast::Stmt], assigned: &mut BTreeSet<String>, declared: &mut BTreeSet<String>) {
+    fn targets(target: &ast::Expr, assigned: &mut BTreeSet<String>) {
+        match target {
+            ast::Expr::Name(name) => {
+                assigned.insert(name.id.to_string());
+            }
+            ast::Expr::Tuple(tuple) => tuple.elts.iter().for_each(|elt| targets(elt, assigned)),
+            _ => {}
+        }
+    }
+    for stmt in stmts {
+        match stmt {
+            ast::Stmt::Assign(assign) => assign.targets.iter().for_each(|t| targets(t, assigned)),
+            ast::Stmt::AugAssign(aug) => targets(&aug.target, assigned),
+            ast::Stmt::Global(global) => declared.extend(global.names.iter().map(|n| n.to_string())),
+            ast::Stmt::If(if_stmt) => {
+                scope_names(&if_stmt.body, assigned, declared);
+                scope_names(&if_stmt.orelse, assigned, declared);
+            }
+            ast::Stmt::While(while_stmt) => scope_names(&while_stmt.body, assigned, declared),
+            ast::Stmt::For(for_stmt) => {
+                targets(&for_stmt.target, assigned);
+                scope_names(&for_stmt.body, assigned, declared);
+            }
+            ast::Stmt::Try(try_stmt) => {
+                scope_names(&try_stmt.body, assigned, declared);
+                for handler in &try_stmt.handlers {
+                    let ast::ExceptHandler::ExceptHandler(handler) = handler;
+                    scope_names(&handler.body, assigned, declared);
+                }
+                scope_names(&try_stmt.orelse, assigned, declared);
+                scope_names(&try_stmt.finalbody, assigned, declared);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// First four bytes of the message's SHA-256, big-endian; an assert without a message records 0
+fn assert_message_hash(msg: &str) -> u32 {
+    let digest = Sha256::digest(msg.as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
+// Whether a statement can leave the enclosing block other than by falling through or raising
+fn escapes(stmt: &IRStmt, in_loop: bool) -> bool {
+    let any = |stmts: &[IRStmt], in_loop: bool| stmts.iter().any(|s| escapes(s, in_loop));
+    match stmt {
+        IRStmt::Return(_) => true,
+        IRStmt::Break | IRStmt::Continue => !in_loop,
+        IRStmt::If { then_block, else_block, .. } => any(then_block, in_loop) || any(else_block, in_loop),
+        IRStmt::IfChain { branches, else_block } => branches.iter().any(|(_, b)| any(b, in_loop)) || any(else_block, in_loop),
+        IRStmt::Switch { arms, default, .. } => arms.iter().any(|a| any(a, in_loop)) || any(default, in_loop),
+        IRStmt::While { body, .. } | IRStmt::For { body, .. } => any(body, true),
+        IRStmt::Block(body) => any(body, in_loop),
+        IRStmt::Try { body, handlers, else_block, finally } => {
+            any(body, in_loop) || handlers.iter().any(|(_, h)| any(h, in_loop)) || any(else_block, in_loop) || any(finally, in_loop)
+        }
+        _ => false,
+    }
+}
@@ -3,15 +3,41 @@
 
 use wasm_encoder::*;
 
+use super::{ERROR_SLOT, STDOUT_CAPACITY};
+use super::errors::RuntimeError;
+
 // Memory constants
 pub const HEAP_PTR_GLOBAL: u32 = 1;  // Global index for heap pointer
 pub const HEAP_LIMIT_GLOBAL: u32 = 2; // Global index for heap limit
 
+/// Record why the run fails at ERROR_SLOT, then trap
+pub fn trap(func: &mut Function, error: RuntimeError) {
+    func.instruction(&Instruction::I32Const(ERROR_SLOT));
+    func.instruction(&Instruction::I32Const(error.code()));
+    func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::Unreachable);
+}
+
 // Type tags for runtime discrimination
 const TYPE_LIST: i32 = 1;
 const TYPE_DICT: i32 = 2;
 const TYPE_STRING: i32 = 3;
 const TYPE_BYTES: i32 = 4;
+const TYPE_SHA256: i32 = 5;
+const TYPE_SHA3_256: i32 = 6;
+const TYPE_TUPLE: i32 = 7;
+const TYPE_SET: i32 = 8;
+
+/// What type() yields for each builtin type name; values that aren't heap objects are ints
+pub const TYPE_IDS: &[(&str, i32)] = &[
+    ("int", 0),
+    ("list", TYPE_LIST),
+    ("dict", TYPE_DICT),
+    ("str", TYPE_STRING),
+    ("bytes", TYPE_BYTES),
+    ("tuple", TYPE_TUPLE),
+    ("set", TYPE_SET),
+];
 
 // FNV-1a hash constants (deterministic, no seed)
 const FNV_OFFSET_BASIS: i32 = 2166136261u32 as i32;
@@ -21,56 +47,27 @@ const FNV_PRIME: i32 = 16777619;
 pub struct ListLayout;
 
 impl ListLayout {
-    /// Allocate list in heap: [type:i32][length:i32][capacity:i32][elem0:i32][elem1:i32]...
+    /// Allocate list in heap: [type:i32][length:i32][capacity:i32][data_ptr:i32][elem0:i32][elem1:i32]...
+    /// data_ptr starts out pointing at the inline elements and moves when the list grows
     /// Returns: list_ptr on stack
     pub fn alloc(func: &mut Function, length: u32) {
-        let size = 12 + (length * 4);
-
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(size as i32));
-        func.instruction(&Instruction::I32Add);
-        func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
-        func.instruction(&Instruction::I32GtU);
-        func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
-        func.instruction(&Instruction::End);
-
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(TYPE_LIST));
-        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
-
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(length as i32));
-        func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
-
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(length as i32));
-        func.instruction(&Instruction::I32Store(MemArg { offset: 8, align: 2, memory_index: 0 }));
-
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(size as i32));
-        func.instruction(&Instruction::I32Add);
-        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
-
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(size as i32));
-        func.instruction(&Instruction::I32Sub);
+        alloc_sequence(func, TYPE_LIST, length);
     }
 
     /// Store element at index: list_ptr, index, value -> ()
-    pub fn store_element(func: &mut Function, scratch0: u32) {
+    pub fn store_element(func: &mut Function, scratch0: u32, scratch1: u32) {
         // Stack: list_ptr, index, value
+        func.instruction(&Instruction::LocalSet(scratch1));
         func.instruction(&Instruction::LocalSet(scratch0));
 
-        // Calculate offset: 12 + (index * 4)
+        // address: data_ptr + (index * 4)
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(scratch0));
         func.instruction(&Instruction::I32Const(4));
         func.instruction(&Instruction::I32Mul);
-        func.instruction(&Instruction::I32Const(12));
         func.instruction(&Instruction::I32Add);
 
-        func.instruction(&Instruction::I32Add);
-
-        func.instruction(&Instruction::LocalGet(scratch0));
+        func.instruction(&Instruction::LocalGet(scratch1));
         func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
     }
 
@@ -86,16 +83,15 @@ impl ListLayout {
         func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
         func.instruction(&Instruction::I32GeU);
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
+        trap(func, RuntimeError::IndexOutOfRange);
         func.instruction(&Instruction::End);
 
-        // compute address: list_ptr + 12 + (index * 4)
+        // compute address: data_ptr + (index * 4)
         func.instruction(&Instruction::LocalGet(scratch0));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
         func.instruction(&Instruction::LocalGet(scratch1));
         func.instruction(&Instruction::I32Const(4));
         func.instruction(&Instruction::I32Mul);
-        func.instruction(&Instruction::I32Const(12));
-        func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
     }
@@ -113,73 +109,248 @@ impl ListLayout {
         func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
         func.instruction(&Instruction::I32GeU);
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
+        trap(func, RuntimeError::IndexOutOfRange);
         func.instruction(&Instruction::End);
 
-        // compute address: list_ptr + 12 + (index * 4)
+        // compute address: data_ptr + (index * 4)
         func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
         func.instruction(&Instruction::LocalGet(index));
         func.instruction(&Instruction::I32Const(4));
         func.instruction(&Instruction::I32Mul);
-        func.instruction(&Instruction::I32Const(12));
-        func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::I32Add);
 
         func.instruction(&Instruction::LocalGet(value));
         func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
     }
-}
 
-// Dict memory layout helpers
-pub struct DictLayout;
+    /// Append element, doubling capacity when full: list_ptr, value -> ()
+    pub fn append(func: &mut Function, list_ptr: u32, value: u32, data: u32, cap: u32) {
+        // Stack: list_ptr, value
+        func.instruction(&Instruction::LocalSet(value));
+        func.instruction(&Instruction::LocalSet(list_ptr));
 
-impl DictLayout {
-    /// Allocate dict: [type:i32][capacity:i32][size:i32][tombstones:i32][slots...]
-    /// Returns: dict_ptr on stack
-    pub fn alloc(func: &mut Function, capacity: u32, dict_ptr: u32, counter: u32) {
-        let size = 16 + (capacity * 12);
+        Self::check_type(func, list_ptr);
+        Self::reserve_one(func, list_ptr, data, cap);
+
+        // data_ptr[length] = value
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(value));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+        // length += 1
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+    }
+
+    /// Remove and return element at index, shifting the tail left: list_ptr, index -> value
+    pub fn pop(func: &mut Function, list_ptr: u32, index: u32, len: u32, addr: u32) {
+        // Stack: list_ptr, index
+        func.instruction(&Instruction::LocalSet(index));
+        func.instruction(&Instruction::LocalSet(list_ptr));
+
+        Self::check_type(func, list_ptr);
+
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(len));
+
+        // pop(-1) counts from the end
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32LtS);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::LocalGet(len));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(index));
+        func.instruction(&Instruction::End);
+
+        // bounds check: index < length (also traps on empty list)
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::LocalGet(len));
+        func.instruction(&Instruction::I32GeU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::IndexOutOfRange);
+        func.instruction(&Instruction::End);
+
+        // addr = data_ptr + (index * 4)
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(addr));
+
+        // reuse index local for the popped value
+        func.instruction(&Instruction::LocalGet(addr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(index));
+
+        // memory.copy(addr, addr + 4, (length - index - 1) * 4)
+        func.instruction(&Instruction::LocalGet(addr));
+        func.instruction(&Instruction::LocalGet(addr));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(len));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(addr));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+
+        // length -= 1
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::LocalGet(len));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::LocalGet(index));
+    }
+
+    /// Insert element before index, shifting the tail right: list_ptr, index, value -> ()
+    /// Index is clamped like CPython: negative counts from the end, out of range goes to the ends
+    pub fn insert(func: &mut Function, list_ptr: u32, index: u32, value: u32, addr: u32, cap: u32) {
+        // Stack: list_ptr, index, value
+        func.instruction(&Instruction::LocalSet(value));
+        func.instruction(&Instruction::LocalSet(index));
+        func.instruction(&Instruction::LocalSet(list_ptr));
+
+        Self::check_type(func, list_ptr);
+
+        // if index < 0: index += length; then clamp to [0, length]
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32LtS);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(index));
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32LtS);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(index));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32GtS);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(index));
+        func.instruction(&Instruction::End);
+
+        Self::reserve_one(func, list_ptr, addr, cap);
 
+        // addr = data_ptr + (index * 4)
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(addr));
+
+        // memory.copy(addr + 4, addr, (length - index) * 4)
+        func.instruction(&Instruction::LocalGet(addr));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(addr));
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+
+        func.instruction(&Instruction::LocalGet(addr));
+        func.instruction(&Instruction::LocalGet(value));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+        // length += 1
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+    }
+
+    /// Copy the slice described by adjust_slice into a new list, or a tuple when slicing a tuple
+    /// Locals: same layout as StringLayout::slice
+    /// Returns: new_ptr on stack
+    pub fn slice(func: &mut Function, base: u32) {
+        let (src, start, step, count, new_ptr, counter) = (base, base + 2, base + 4, base + 5, base + 6, base + 7);
+
+        // 16-byte header + count * 4
         func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(size as i32));
+        func.instruction(&Instruction::LocalGet(count));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Const(16));
+        func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
         func.instruction(&Instruction::I32GtU);
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
+        trap(func, RuntimeError::OutOfMemory);
         func.instruction(&Instruction::End);
 
         func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(TYPE_DICT));
+        func.instruction(&Instruction::LocalTee(new_ptr));
+        func.instruction(&Instruction::LocalGet(src));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
         func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
 
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(capacity as i32));
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::LocalGet(count));
         func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
 
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::LocalGet(count));
         func.instruction(&Instruction::I32Store(MemArg { offset: 8, align: 2, memory_index: 0 }));
 
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::I32Const(16));
+        func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::I32Store(MemArg { offset: 12, align: 2, memory_index: 0 }));
 
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::LocalSet(dict_ptr));
-        Self::init_slots(func, capacity, dict_ptr, counter);
-
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(size as i32));
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::LocalGet(count));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Const(16));
+        func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
 
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        func.instruction(&Instruction::I32Const(size as i32));
-        func.instruction(&Instruction::I32Sub);
-    }
-
-    /// Initialize all hash slots to 0 (empty)
-    fn init_slots(func: &mut Function, capacity: u32, dict_ptr: u32, counter: u32) {
+        // new[16 + i * 4] = src.data_ptr[(start + i * step) * 4]
         func.instruction(&Instruction::I32Const(0));
         func.instruction(&Instruction::LocalSet(counter));
 
@@ -187,20 +358,29 @@ impl DictLayout {
         func.instruction(&Instruction::Loop(BlockType::Empty));
 
         func.instruction(&Instruction::LocalGet(counter));
-        func.instruction(&Instruction::I32Const(capacity as i32));
-        func.instruction(&Instruction::I32GeU);
+        func.instruction(&Instruction::LocalGet(count));
+        func.instruction(&Instruction::I32GeS);
         func.instruction(&Instruction::BrIf(1));
 
-        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::LocalGet(new_ptr));
         func.instruction(&Instruction::LocalGet(counter));
-        func.instruction(&Instruction::I32Const(12));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+
+        func.instruction(&Instruction::LocalGet(src));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(start));
+        func.instruction(&Instruction::LocalGet(counter));
+        func.instruction(&Instruction::LocalGet(step));
         func.instruction(&Instruction::I32Mul);
-        func.instruction(&Instruction::I32Const(16));
         func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
         func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
 
-        func.instruction(&Instruction::I32Const(0));
-        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 16, align: 2, memory_index: 0 }));
 
         func.instruction(&Instruction::LocalGet(counter));
         func.instruction(&Instruction::I32Const(1));
@@ -210,32 +390,965 @@ impl DictLayout {
         func.instruction(&Instruction::Br(0));
         func.instruction(&Instruction::End);
         func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
     }
 
-    /// FNV-1a hash function (deterministic)
-    pub fn fnv_hash(func: &mut Function, scratch: u32) {
-        func.instruction(&Instruction::LocalSet(scratch));
+    /// Whether value holds a list or tuple: pushes 1 or 0
+    pub fn is_sequence(func: &mut Function, value: u32) {
+        has_tag(func, value, TYPE_LIST);
+        has_tag(func, value, TYPE_TUPLE);
+        func.instruction(&Instruction::I32Or);
+    }
 
-        func.instruction(&Instruction::I32Const(FNV_OFFSET_BASIS));
-        func.instruction(&Instruction::LocalGet(scratch));
-        func.instruction(&Instruction::I32Xor);
-        func.instruction(&Instruction::I32Const(FNV_PRIME));
-        func.instruction(&Instruction::I32Mul);
+    /// Concatenate two lists or two tuples into a new one of the same kind; mixing them, or
+    /// adding anything else, is a TypeError
+    /// Pops [seq_a, seq_b], pushes new_ptr
+    pub fn concat(func: &mut Function, a: u32, b: u32, len_a: u32, new_ptr: u32) {
+        func.instruction(&Instruction::LocalSet(b));
+        func.instruction(&Instruction::LocalSet(a));
 
-        func.instruction(&Instruction::LocalTee(scratch));
+        TupleLayout::check_sequence(func, b);
+        func.instruction(&Instruction::LocalGet(a));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(b));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Ne);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(a));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(len_a));
+
+        // 16-byte header + (len_a + len_b) * 4, checked against heap limit
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalTee(new_ptr));
+        func.instruction(&Instruction::LocalGet(len_a));
+        func.instruction(&Instruction::LocalGet(b));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Const(16));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::OutOfMemory);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::LocalGet(a));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+        // length and capacity
+        for offset in [4, 8] {
+            func.instruction(&Instruction::LocalGet(new_ptr));
+            func.instruction(&Instruction::LocalGet(len_a));
+            func.instruction(&Instruction::LocalGet(b));
+            func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+            func.instruction(&Instruction::I32Add);
+            func.instruction(&Instruction::I32Store(MemArg { offset, align: 2, memory_index: 0 }));
+        }
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::I32Const(16));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Store(MemArg { offset: 12, align: 2, memory_index: 0 }));
+
+        // Copy a's elements then b's
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::I32Const(16));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(a));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(len_a));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::I32Const(16));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(len_a));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(b));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(b));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+    }
+
+    /// Stable bottom-up merge sort of a list of ints, in place (signed order)
+    /// Locals: base=list_ptr (input), base+1..=base+10 scratch
+    pub fn sort(func: &mut Function, base: u32) {
+        let (list, n, data, tmp, width, i, a, mid, hi, b, k) =
+            (base, base + 1, base + 2, base + 3, base + 4, base + 5, base + 6, base + 7, base + 8, base + 9, base + 10);
+
+        let elem = |f: &mut Function, buf: u32, idx: u32| {
+            f.instruction(&Instruction::LocalGet(buf));
+            f.instruction(&Instruction::LocalGet(idx));
+            f.instruction(&Instruction::I32Const(4));
+            f.instruction(&Instruction::I32Mul);
+            f.instruction(&Instruction::I32Add);
+        };
+        // local = min(x + y, n)
+        let min_n = |f: &mut Function, x: u32, y: u32, out: u32| {
+            f.instruction(&Instruction::LocalGet(x));
+            f.instruction(&Instruction::LocalGet(y));
+            f.instruction(&Instruction::I32Add);
+            f.instruction(&Instruction::LocalTee(out));
+            f.instruction(&Instruction::LocalGet(n));
+            f.instruction(&Instruction::LocalGet(out));
+            f.instruction(&Instruction::LocalGet(n));
+            f.instruction(&Instruction::I32LtS);
+            f.instruction(&Instruction::Select);
+            f.instruction(&Instruction::LocalSet(out));
+        };
+
+        func.instruction(&Instruction::LocalGet(list));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(n));
+        func.instruction(&Instruction::LocalGet(list));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(data));
+
+        // tmp = n * 4 bytes of scratch heap
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalGet(n));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::OutOfMemory);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalTee(tmp));
+        func.instruction(&Instruction::LocalGet(n));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+        // for width = 1; width < n; width *= 2
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::LocalSet(width));
+        func.instruction(&Instruction::Block(BlockType::Empty));
+        func.instruction(&Instruction::Loop(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(width));
+        func.instruction(&Instruction::LocalGet(n));
+        func.instruction(&Instruction::I32GeS);
+        func.instruction(&Instruction::BrIf(1));
+
+        // for i = 0; i < n; i += 2 * width: merge data[i..mid] and data[mid..hi] into tmp
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(i));
+        func.instruction(&Instruction::Block(BlockType::Empty));
+        func.instruction(&Instruction::Loop(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(i));
+        func.instruction(&Instruction::LocalGet(n));
+        func.instruction(&Instruction::I32GeS);
+        func.instruction(&Instruction::BrIf(1));
+
+        func.instruction(&Instruction::LocalGet(i));
+        func.instruction(&Instruction::LocalSet(a));
+        func.instruction(&Instruction::LocalGet(i));
+        func.instruction(&Instruction::LocalSet(k));
+        min_n(func, i, width, mid);
+        min_n(func, mid, width, hi);
+        func.instruction(&Instruction::LocalGet(mid));
+        func.instruction(&Instruction::LocalSet(b));
+
+        func.instruction(&Instruction::Block(BlockType::Empty));
+        func.instruction(&Instruction::Loop(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(k));
+        func.instruction(&Instruction::LocalGet(hi));
+        func.instruction(&Instruction::I32GeS);
+        func.instruction(&Instruction::BrIf(1));
+
+        // take left when a < mid and (b >= hi or data[a] <= data[b]); <= keeps it stable
+        func.instruction(&Instruction::LocalGet(a));
+        func.instruction(&Instruction::LocalGet(mid));
+        func.instruction(&Instruction::I32LtS);
+        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+        func.instruction(&Instruction::LocalGet(b));
+        func.instruction(&Instruction::LocalGet(hi));
+        func.instruction(&Instruction::I32GeS);
+        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::Else);
+        elem(func, data, a);
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        elem(func, data, b);
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32LeS);
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::Else);
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::End);
+
+        // tmp[k] = data[src]; src += 1
+        let take = |f: &mut Function, src: u32| {
+            elem(f, tmp, k);
+            elem(f, data, src);
+            f.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+            f.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+            f.instruction(&Instruction::LocalGet(src));
+            f.instruction(&Instruction::I32Const(1));
+            f.instruction(&Instruction::I32Add);
+            f.instruction(&Instruction::LocalSet(src));
+        };
+        func.instruction(&Instruction::If(BlockType::Empty));
+        take(func, a);
+        func.instruction(&Instruction::Else);
+        take(func, b);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(k));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(k));
+        func.instruction(&Instruction::Br(0));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(i));
+        func.instruction(&Instruction::LocalGet(width));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Shl);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(i));
+        func.instruction(&Instruction::Br(0));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+
+        // memory.copy(data, tmp, n * 4)
+        func.instruction(&Instruction::LocalGet(data));
+        func.instruction(&Instruction::LocalGet(tmp));
+        func.instruction(&Instruction::LocalGet(n));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+
+        func.instruction(&Instruction::LocalGet(width));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Shl);
+        func.instruction(&Instruction::LocalSet(width));
+        func.instruction(&Instruction::Br(0));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+    }
+
+    /// Trap unless list_ptr holds a list (methods must not scribble over other heap types)
+    fn check_type(func: &mut Function, list_ptr: u32) {
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Const(1024));
+        func.instruction(&Instruction::I32LtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(TYPE_LIST));
+        func.instruction(&Instruction::I32Ne);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+    }
+
+    /// Make room for one more element. When length == capacity the elements move to
+    /// a fresh buffer of max(4, capacity * 2) slots; the old buffer is left behind.
+    fn reserve_one(func: &mut Function, list_ptr: u32, data: u32, cap: u32) {
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 8, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32GeU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+
+        // cap = max(4, capacity * 2)
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 8, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Shl);
+        func.instruction(&Instruction::LocalTee(cap));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::LocalGet(cap));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32GeU);
+        func.instruction(&Instruction::Select);
+        func.instruction(&Instruction::LocalSet(cap));
+
+        // bounds check: heap_ptr + cap * 4 <= heap_limit
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalGet(cap));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::OutOfMemory);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalSet(data));
+        func.instruction(&Instruction::LocalGet(data));
+        func.instruction(&Instruction::LocalGet(cap));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+        // memory.copy(data, data_ptr, length * 4)
+        func.instruction(&Instruction::LocalGet(data));
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::LocalGet(data));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 12, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::LocalGet(list_ptr));
+        func.instruction(&Instruction::LocalGet(cap));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 8, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::End);
+    }
+}
+
+/// Negative sequence index counts from the end: index += len when index < 0.
+/// Dicts are left alone (negative ints are ordinary keys) and so are non-pointers.
+/// Updates the index local in place; still-negative results fail the unsigned bounds checks.
+pub fn normalize_index(func: &mut Function, obj: u32, index: u32) {
+    func.instruction(&Instruction::LocalGet(index));
+    func.instruction(&Instruction::I32Const(0));
+    func.instruction(&Instruction::I32LtS);
+    func.instruction(&Instruction::LocalGet(obj));
+    func.instruction(&Instruction::I32Const(1024));
+    func.instruction(&Instruction::I32GeU);
+    func.instruction(&Instruction::I32And);
+    func.instruction(&Instruction::If(BlockType::Empty));
+
+    func.instruction(&Instruction::LocalGet(obj));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::I32Const(TYPE_DICT));
+    func.instruction(&Instruction::I32Ne);
+    func.instruction(&Instruction::If(BlockType::Empty));
+    func.instruction(&Instruction::LocalGet(index));
+    func.instruction(&Instruction::LocalGet(obj));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::LocalSet(index));
+    func.instruction(&Instruction::End);
+
+    func.instruction(&Instruction::End);
+}
+
+/// Python slice index adjustment (PySlice_AdjustIndices).
+/// Locals: base=len, base+1=start, base+2=stop, base+3=step (inputs), base+4=count (output).
+/// start/stop are only read when present; missing bounds take the step-dependent defaults.
+/// On return start/stop are in range and count is the number of selected elements.
+/// Traps when step is 0.
+pub fn adjust_slice(func: &mut Function, base: u32, has_start: bool, has_stop: bool) {
+    let (len, start, stop, step, count) = (base, base + 1, base + 2, base + 3, base + 4);
+
+    func.instruction(&Instruction::LocalGet(step));
+    func.instruction(&Instruction::I32Eqz);
+    func.instruction(&Instruction::If(BlockType::Empty));
+    trap(func, RuntimeError::ValueError);
+    func.instruction(&Instruction::End);
+
+    // push (step < 0 ? neg : pos) where both are locals or constants
+    let pick = |f: &mut Function, neg: Instruction, pos: Instruction| {
+        f.instruction(&neg);
+        f.instruction(&pos);
+        f.instruction(&Instruction::LocalGet(step));
+        f.instruction(&Instruction::I32Const(0));
+        f.instruction(&Instruction::I32LtS);
+        f.instruction(&Instruction::Select);
+    };
+    let len_minus_one = |f: &mut Function| {
+        f.instruction(&Instruction::LocalGet(len));
+        f.instruction(&Instruction::I32Const(1));
+        f.instruction(&Instruction::I32Sub);
+    };
+
+    for (bound, present, is_start) in [(start, has_start, true), (stop, has_stop, false)] {
+        if !present {
+            // start: step > 0 ? 0 : len - 1    stop: step > 0 ? len : -1
+            if is_start {
+                len_minus_one(func);
+                func.instruction(&Instruction::I32Const(0));
+                func.instruction(&Instruction::LocalGet(step));
+                func.instruction(&Instruction::I32Const(0));
+                func.instruction(&Instruction::I32LtS);
+                func.instruction(&Instruction::Select);
+            } else {
+                pick(func, Instruction::I32Const(-1), Instruction::LocalGet(len));
+            }
+            func.instruction(&Instruction::LocalSet(bound));
+            continue;
+        }
+
+        // if bound < 0: bound += len; if still < 0: bound = step < 0 ? -1 : 0
+        func.instruction(&Instruction::LocalGet(bound));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32LtS);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(bound));
+        func.instruction(&Instruction::LocalGet(len));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(bound));
+        func.instruction(&Instruction::LocalGet(bound));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32LtS);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        pick(func, Instruction::I32Const(-1), Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(bound));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::Else);
+
+        // elif bound >= len: bound = step < 0 ? len - 1 : len
+        func.instruction(&Instruction::LocalGet(bound));
+        func.instruction(&Instruction::LocalGet(len));
+        func.instruction(&Instruction::I32GeS);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        len_minus_one(func);
+        func.instruction(&Instruction::LocalGet(len));
+        func.instruction(&Instruction::LocalGet(step));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32LtS);
+        func.instruction(&Instruction::Select);
+        func.instruction(&Instruction::LocalSet(bound));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+    }
+
+    // count = step > 0 ? (start < stop ? (stop - start - 1) / step + 1 : 0)
+    //                  : (stop < start ? (start - stop - 1) / -step + 1 : 0)
+    func.instruction(&Instruction::I32Const(0));
+    func.instruction(&Instruction::LocalSet(count));
+
+    func.instruction(&Instruction::LocalGet(step));
+    func.instruction(&Instruction::I32Const(0));
+    func.instruction(&Instruction::I32GtS);
+    func.instruction(&Instruction::If(BlockType::Empty));
+    func.instruction(&Instruction::LocalGet(start));
+    func.instruction(&Instruction::LocalGet(stop));
+    func.instruction(&Instruction::I32LtS);
+    func.instruction(&Instruction::If(BlockType::Empty));
+    func.instruction(&Instruction::LocalGet(stop));
+    func.instruction(&Instruction::LocalGet(start));
+    func.instruction(&Instruction::I32Sub);
+    func.instruction(&Instruction::I32Const(1));
+    func.instruction(&Instruction::I32Sub);
+    func.instruction(&Instruction::LocalGet(step));
+    func.instruction(&Instruction::I32DivS);
+    func.instruction(&Instruction::I32Const(1));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::LocalSet(count));
+    func.instruction(&Instruction::End);
+    func.instruction(&Instruction::Else);
+    func.instruction(&Instruction::LocalGet(stop));
+    func.instruction(&Instruction::LocalGet(start));
+    func.instruction(&Instruction::I32LtS);
+    func.instruction(&Instruction::If(BlockType::Empty));
+    func.instruction(&Instruction::LocalGet(start));
+    func.instruction(&Instruction::LocalGet(stop));
+    func.instruction(&Instruction::I32Sub);
+    func.instruction(&Instruction::I32Const(1));
+    func.instruction(&Instruction::I32Sub);
+    func.instruction(&Instruction::I32Const(0));
+    func.instruction(&Instruction::LocalGet(step));
+    func.instruction(&Instruction::I32Sub);
+    func.instruction(&Instruction::I32DivS);
+    func.instruction(&Instruction::I32Const(1));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::LocalSet(count));
+    func.instruction(&Instruction::End);
+    func.instruction(&Instruction::End);
+}
+
+// Shared header for lists and tuples so element access works on both
+fn alloc_sequence(func: &mut Function, type_tag: i32, length: u32) {
+    let size = 16 + (length * 4);
+
+    func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+    func.instruction(&Instruction::I32Const(size as i32));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+    func.instruction(&Instruction::I32GtU);
+    func.instruction(&Instruction::If(BlockType::Empty));
+    trap(func, RuntimeError::OutOfMemory);
+    func.instruction(&Instruction::End);
+
+    func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+    func.instruction(&Instruction::I32Const(type_tag));
+    func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+    func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+    func.instruction(&Instruction::I32Const(length as i32));
+    func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+
+    func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+    func.instruction(&Instruction::I32Const(length as i32));
+    func.instruction(&Instruction::I32Store(MemArg { offset: 8, align: 2, memory_index: 0 }));
+
+    func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+    func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+    func.instruction(&Instruction::I32Const(16));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::I32Store(MemArg { offset: 12, align: 2, memory_index: 0 }));
+
+    func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+    func.instruction(&Instruction::I32Const(size as i32));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+    func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+    func.instruction(&Instruction::I32Const(size as i32));
+    func.instruction(&Instruction::I32Sub);
+}
+
+// Tuple layout: same header as a list so load_element and len() work unchanged,
+// but with its own tag so append/pop/insert/item assignment trap
+pub struct TupleLayout;
+
+impl TupleLayout {
+    /// Allocate tuple: [type:i32][length:i32][length:i32][data_ptr:i32][elem0:i32]...
+    /// Fill with ListLayout::store_element before the pointer escapes
+    /// Returns: tuple_ptr on stack
+    pub fn alloc(func: &mut Function, length: u32) {
+        alloc_sequence(func, TYPE_TUPLE, length);
+    }
+
+    /// Trap if ptr holds a tuple (item assignment)
+    pub fn check_mutable(func: &mut Function, ptr: u32) {
+        func.instruction(&Instruction::LocalGet(ptr));
+        func.instruction(&Instruction::I32Const(1024));
+        func.instruction(&Instruction::I32GeU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(TYPE_TUPLE));
+        func.instruction(&Instruction::I32Eq);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+    }
+
+    /// Check a value can be unpacked into `count` targets: tuple or list of exactly that length
+    /// Stack: ptr -> ptr
+    pub fn check_unpack(func: &mut Function, ptr: u32, count: u32) {
+        func.instruction(&Instruction::LocalSet(ptr));
+        Self::check_sequence(func, ptr);
+
+        func.instruction(&Instruction::LocalGet(ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(count as i32));
+        func.instruction(&Instruction::I32Ne);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::ValueError);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(ptr));
+    }
+
+    /// Trap unless ptr holds a list, tuple or string; dicts and sets have no positional order
+    pub fn check_iterable(func: &mut Function, ptr: u32) {
+        func.instruction(&Instruction::LocalGet(ptr));
+        func.instruction(&Instruction::I32Const(1024));
+        func.instruction(&Instruction::I32LtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+
+        for (i, tag) in [TYPE_LIST, TYPE_TUPLE, TYPE_STRING].into_iter().enumerate() {
+            func.instruction(&Instruction::LocalGet(ptr));
+            func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+            func.instruction(&Instruction::I32Const(tag));
+            func.instruction(&Instruction::I32Ne);
+            if i > 0 {
+                func.instruction(&Instruction::I32And);
+            }
+        }
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+    }
+
+    /// Trap unless ptr holds a list or tuple
+    pub fn check_sequence(func: &mut Function, ptr: u32) {
+        func.instruction(&Instruction::LocalGet(ptr));
+        func.instruction(&Instruction::I32Const(1024));
+        func.instruction(&Instruction::I32LtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(TYPE_TUPLE));
+        func.instruction(&Instruction::I32Ne);
+        func.instruction(&Instruction::LocalGet(ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(TYPE_LIST));
+        func.instruction(&Instruction::I32Ne);
+        func.instruction(&Instruction::I32And);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+    }
+}
+
+/// Integer fold over a list or tuple for min()/max()/sum()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reduce {
+    Min,
+    Max,
+    Sum,
+}
+
+/// Fold the elements of a list/tuple (signed ints)
+/// Locals: base=seq, base+1=acc, base+2=i, base+3=n, base+4=data
+/// Sum starts from the acc the caller set; min/max trap on an empty sequence
+/// Returns: acc on stack
+pub fn reduce(func: &mut Function, base: u32, op: Reduce) {
+    let (seq, acc, i, n, data) = (base, base + 1, base + 2, base + 3, base + 4);
+
+    TupleLayout::check_sequence(func, seq);
+
+    func.instruction(&Instruction::LocalGet(seq));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::LocalSet(n));
+    func.instruction(&Instruction::LocalGet(seq));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::LocalSet(data));
+
+    if op == Reduce::Sum {
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(i));
+    } else {
+        func.instruction(&Instruction::LocalGet(n));
         func.instruction(&Instruction::I32Eqz);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::ValueError);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(data));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(acc));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::LocalSet(i));
+    }
+
+    func.instruction(&Instruction::Block(BlockType::Empty));
+    func.instruction(&Instruction::Loop(BlockType::Empty));
+
+    func.instruction(&Instruction::LocalGet(i));
+    func.instruction(&Instruction::LocalGet(n));
+    func.instruction(&Instruction::I32GeS);
+    func.instruction(&Instruction::BrIf(1));
+
+    // data[i]
+    let load = |f: &mut Function| {
+        f.instruction(&Instruction::LocalGet(data));
+        f.instruction(&Instruction::LocalGet(i));
+        f.instruction(&Instruction::I32Const(4));
+        f.instruction(&Instruction::I32Mul);
+        f.instruction(&Instruction::I32Add);
+        f.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+    };
+
+    match op {
+        Reduce::Sum => {
+            func.instruction(&Instruction::LocalGet(acc));
+            load(func);
+            func.instruction(&Instruction::I32Add);
+        }
+        Reduce::Min | Reduce::Max => {
+            // strict comparison: the first of equal elements wins, like CPython
+            load(func);
+            func.instruction(&Instruction::LocalGet(acc));
+            load(func);
+            func.instruction(&Instruction::LocalGet(acc));
+            func.instruction(&if op == Reduce::Min { Instruction::I32LtS } else { Instruction::I32GtS });
+            func.instruction(&Instruction::Select);
+        }
+    }
+    func.instruction(&Instruction::LocalSet(acc));
+
+    func.instruction(&Instruction::LocalGet(i));
+    func.instruction(&Instruction::I32Const(1));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::LocalSet(i));
+
+    func.instruction(&Instruction::Br(0));
+    func.instruction(&Instruction::End);
+    func.instruction(&Instruction::End);
+
+    func.instruction(&Instruction::LocalGet(acc));
+}
+
+/// len() for any heap value: list_ptr/str_ptr/bytes_ptr/dict_ptr/set_ptr -> length
+/// Lists, tuples, strings and bytes keep their length at offset 4, dicts and sets keep size at offset 8
+pub fn len(func: &mut Function, obj: u32) {
+    func.instruction(&Instruction::LocalSet(obj));
+
+    // ints are not sized
+    func.instruction(&Instruction::LocalGet(obj));
+    func.instruction(&Instruction::I32Const(1024));
+    func.instruction(&Instruction::I32LtU);
+    func.instruction(&Instruction::If(BlockType::Empty));
+    trap(func, RuntimeError::TypeError);
+    func.instruction(&Instruction::End);
+
+    func.instruction(&Instruction::LocalGet(obj));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::I32Const(TYPE_DICT));
+    func.instruction(&Instruction::I32Eq);
+    func.instruction(&Instruction::LocalGet(obj));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::I32Const(TYPE_SET));
+    func.instruction(&Instruction::I32Eq);
+    func.instruction(&Instruction::I32Or);
+    func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+    func.instruction(&Instruction::LocalGet(obj));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 8, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::Else);
+    func.instruction(&Instruction::LocalGet(obj));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::End);
+}
+
+// Whether value is a heap object with this tag: pushes 1 or 0
+fn has_tag(func: &mut Function, value: u32, tag: i32) {
+    func.instruction(&Instruction::LocalGet(value));
+    func.instruction(&Instruction::I32Const(1024));
+    func.instruction(&Instruction::I32GeU);
+    func.instruction(&Instruction::LocalGet(value));
+    func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+    func.instruction(&Instruction::I32LtU);
+    func.instruction(&Instruction::I32And);
+    func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+    func.instruction(&Instruction::LocalGet(value));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::I32Const(tag));
+    func.instruction(&Instruction::I32Eq);
+    func.instruction(&Instruction::Else);
+    func.instruction(&Instruction::I32Const(0));
+    func.instruction(&Instruction::End);
+}
+
+/// type(): value -> its type tag, 0 for an int
+/// Like is_string, only values from 1024 up to the heap pointer are read as objects
+pub fn type_of(func: &mut Function, value: u32) {
+    func.instruction(&Instruction::LocalTee(value));
+    func.instruction(&Instruction::I32Const(1024));
+    func.instruction(&Instruction::I32GeU);
+    func.instruction(&Instruction::LocalGet(value));
+    func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+    func.instruction(&Instruction::I32LtU);
+    func.instruction(&Instruction::I32And);
+    func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+    func.instruction(&Instruction::LocalGet(value));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::Else);
+    func.instruction(&Instruction::I32Const(0));
+    func.instruction(&Instruction::End);
+}
+
+// Dict memory layout helpers
+pub struct DictLayout;
+
+// [type:i32][capacity:i32][size:i32][tombstones:i32][slots_ptr:i32], then the first slots
+const DICT_HEADER: u32 = 20;
+// Slot hash of a deleted entry; like 0 (an empty slot) it is never a key's hash
+const TOMBSTONE: i32 = -1;
+
+impl DictLayout {
+    /// Allocate dict: [type:i32][capacity:i32][size:i32][tombstones:i32][slots_ptr:i32][slots...]
+    /// Slots are [hash:i32][key:i32][value:i32]; slots_ptr starts out pointing at the inline
+    /// slots and moves when the dict grows
+    /// Returns: dict_ptr on stack
+    pub fn alloc(func: &mut Function, capacity: u32, dict_ptr: u32, counter: u32) {
+        let size = DICT_HEADER + (capacity * 12);
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(size as i32));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::OutOfMemory);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(TYPE_DICT));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(capacity as i32));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 8, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 12, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(DICT_HEADER as i32));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Store(MemArg { offset: 16, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalSet(dict_ptr));
+        Self::init_slots(func, capacity, dict_ptr, counter);
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(size as i32));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(size as i32));
+        func.instruction(&Instruction::I32Sub);
+    }
+
+    /// Initialize all hash slots to 0 (empty)
+    fn init_slots(func: &mut Function, capacity: u32, dict_ptr: u32, counter: u32) {
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(counter));
+
+        func.instruction(&Instruction::Block(BlockType::Empty));
+        func.instruction(&Instruction::Loop(BlockType::Empty));
+
+        func.instruction(&Instruction::LocalGet(counter));
+        func.instruction(&Instruction::I32Const(capacity as i32));
+        func.instruction(&Instruction::I32GeU);
+        func.instruction(&Instruction::BrIf(1));
+
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::LocalGet(counter));
+        func.instruction(&Instruction::I32Const(12));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Const(DICT_HEADER as i32));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Add);
+
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::LocalGet(counter));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(counter));
+
+        func.instruction(&Instruction::Br(0));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+    }
+
+    /// 1 when ptr holds a dict, 0 otherwise
+    pub fn is_dict(func: &mut Function, ptr: u32) {
+        has_tag(func, ptr, TYPE_DICT);
+    }
+
+    /// FNV-1a hash function (deterministic). 0 marks an empty slot and -1 a deleted one,
+    /// so both are replaced by 1
+    pub fn fnv_hash(func: &mut Function, scratch: u32) {
+        func.instruction(&Instruction::LocalSet(scratch));
+
+        func.instruction(&Instruction::I32Const(FNV_OFFSET_BASIS));
+        func.instruction(&Instruction::LocalGet(scratch));
+        func.instruction(&Instruction::I32Xor);
+        func.instruction(&Instruction::I32Const(FNV_PRIME));
+        func.instruction(&Instruction::I32Mul);
+
+        // h - 1 >= 0xfffffffe exactly when h is 0 or -1
+        func.instruction(&Instruction::LocalTee(scratch));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::I32Const(-2));
+        func.instruction(&Instruction::I32GeU);
         func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
         func.instruction(&Instruction::I32Const(1));
         func.instruction(&Instruction::Else);
         func.instruction(&Instruction::LocalGet(scratch));
         func.instruction(&Instruction::End);
-    }
-
-    /// Insert key-value pair using linear probing
-    pub fn insert(func: &mut Function, dict_ptr: u32, key: u32, value: u32, hash: u32, capacity: u32, index: u32, slot_ptr: u32, slot_hash: u32) {
-        func.instruction(&Instruction::LocalSet(value));
-        func.instruction(&Instruction::LocalSet(key));
-        func.instruction(&Instruction::LocalSet(dict_ptr));
+    }
+
+    /// Address of slot `index`: slots_ptr + index * 12
+    fn slot_addr(func: &mut Function, dict_ptr: u32, index: u32) {
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 16, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::I32Const(12));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+    }
+
+    /// Insert or update using linear probing: dict_ptr, key, value -> ()
+    /// A new key takes the first tombstone on its probe path. When the insert could leave the
+    /// table more than 3/4 full, tombstones included, the dict is first rehashed by `grow`, the
+    /// index of the DictGrow runtime function, so a probe always reaches an empty slot.
+    /// Locals: base..base+8
+    pub fn insert(func: &mut Function, base: u32, grow: u32) {
+        let (dict_ptr, key, value, hash, capacity) = (base, base + 1, base + 2, base + 3, base + 4);
+        let (index, slot_ptr, slot_hash, reuse) = (base + 5, base + 6, base + 7, base + 8);
+        func.instruction(&Instruction::LocalSet(value));
+        func.instruction(&Instruction::LocalSet(key));
+        func.instruction(&Instruction::LocalSet(dict_ptr));
+
+        // (size + tombstones + 1) * 4 > capacity * 3
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 8, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Const(2));
+        func.instruction(&Instruction::I32Shl);
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(3));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::Call(grow));
+        func.instruction(&Instruction::Drop);
+        func.instruction(&Instruction::End);
 
         func.instruction(&Instruction::LocalGet(key));
         Self::fnv_hash(func, hash);
@@ -250,30 +1363,42 @@ impl DictLayout {
         func.instruction(&Instruction::I32RemU);
         func.instruction(&Instruction::LocalSet(index));
 
-        Self::insert_loop(func, dict_ptr, key, value, hash, capacity, index, slot_ptr, slot_hash);
+        // no tombstone passed yet (slot addresses are never 0)
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(reuse));
+
+        Self::insert_loop(func, dict_ptr, key, value, hash, capacity, index, slot_ptr, slot_hash, reuse);
     }
 
     /// Linear probing loop for dict insertion
-    fn insert_loop(func: &mut Function, dict_ptr: u32, key: u32, value: u32, hash: u32, capacity: u32, index: u32, slot_ptr: u32, slot_hash: u32) {
+    #[allow(clippy::too_many_arguments)]
+    fn insert_loop(func: &mut Function, dict_ptr: u32, key: u32, value: u32, hash: u32, capacity: u32, index: u32, slot_ptr: u32, slot_hash: u32, reuse: u32) {
         func.instruction(&Instruction::Block(BlockType::Empty));
         func.instruction(&Instruction::Loop(BlockType::Empty));
 
-        func.instruction(&Instruction::LocalGet(dict_ptr));
-        func.instruction(&Instruction::LocalGet(index));
-        func.instruction(&Instruction::I32Const(12));
-        func.instruction(&Instruction::I32Mul);
-        func.instruction(&Instruction::I32Const(16));
-        func.instruction(&Instruction::I32Add);
-        func.instruction(&Instruction::I32Add);
+        Self::slot_addr(func, dict_ptr, index);
         func.instruction(&Instruction::LocalSet(slot_ptr));
 
         func.instruction(&Instruction::LocalGet(slot_ptr));
         func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
         func.instruction(&Instruction::LocalTee(slot_hash));
 
+        // an empty slot ends the probe: the key is new
         func.instruction(&Instruction::I32Eqz);
         func.instruction(&Instruction::If(BlockType::Empty));
 
+        func.instruction(&Instruction::LocalGet(reuse));
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(reuse));
+        func.instruction(&Instruction::LocalSet(slot_ptr));
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::I32Store(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::End);
+
         func.instruction(&Instruction::LocalGet(slot_ptr));
         func.instruction(&Instruction::LocalGet(hash));
         func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
@@ -296,10 +1421,7 @@ impl DictLayout {
         func.instruction(&Instruction::Br(2));
         func.instruction(&Instruction::End);
 
-        func.instruction(&Instruction::LocalGet(slot_ptr));
-        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
-        func.instruction(&Instruction::LocalGet(key));
-        func.instruction(&Instruction::I32Eq);
+        Self::matches(func, slot_ptr, slot_hash, hash, key);
         func.instruction(&Instruction::If(BlockType::Empty));
 
         func.instruction(&Instruction::LocalGet(slot_ptr));
@@ -309,6 +1431,18 @@ impl DictLayout {
         func.instruction(&Instruction::Br(2));
         func.instruction(&Instruction::End);
 
+        // remember the first tombstone for a new key
+        func.instruction(&Instruction::LocalGet(slot_hash));
+        func.instruction(&Instruction::I32Const(TOMBSTONE));
+        func.instruction(&Instruction::I32Eq);
+        func.instruction(&Instruction::LocalGet(reuse));
+        func.instruction(&Instruction::I32Eqz);
+        func.instruction(&Instruction::I32And);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(slot_ptr));
+        func.instruction(&Instruction::LocalSet(reuse));
+        func.instruction(&Instruction::End);
+
         func.instruction(&Instruction::LocalGet(index));
         func.instruction(&Instruction::I32Const(1));
         func.instruction(&Instruction::I32Add);
@@ -321,9 +1455,215 @@ impl DictLayout {
         func.instruction(&Instruction::End);
     }
 
-    /// Lookup key in dict: dict_ptr, key -> value (or 0 if not found)
-    #[allow(dead_code)]
-    pub fn lookup(func: &mut Function, dict_ptr: u32, key: u32, hash: u32, capacity: u32, index: u32, slot_ptr: u32) {
+    /// 1 when the slot at slot_ptr, whose hash is in slot_hash, holds key; tombstones never match
+    fn matches(func: &mut Function, slot_ptr: u32, slot_hash: u32, hash: u32, key: u32) {
+        func.instruction(&Instruction::LocalGet(slot_hash));
+        func.instruction(&Instruction::LocalGet(hash));
+        func.instruction(&Instruction::I32Eq);
+        func.instruction(&Instruction::LocalGet(slot_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(key));
+        func.instruction(&Instruction::I32Eq);
+        func.instruction(&Instruction::I32And);
+    }
+
+    /// Rehash into a fresh slot buffer: dict_ptr -> dict_ptr. The capacity doubles when more
+    /// than half the slots would be live after one more insert and stays put otherwise, which
+    /// only clears the tombstones. The old buffer is left behind.
+    /// Locals: base..base+7
+    pub fn grow(func: &mut Function, base: u32) {
+        let (dict_ptr, new_cap, new_slots, old_slots) = (base, base + 1, base + 2, base + 3);
+        let (counter, slot_ptr, index, target) = (base + 4, base + 5, base + 6, base + 7);
+        func.instruction(&Instruction::LocalSet(dict_ptr));
+
+        // new_cap = (size + 1) * 2 > capacity ? capacity * 2 : capacity
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Shl);
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 8, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Shl);
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::Select);
+        func.instruction(&Instruction::LocalSet(new_cap));
+
+        // bounds check: heap_ptr + new_cap * 12 <= heap_limit
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalGet(new_cap));
+        func.instruction(&Instruction::I32Const(12));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::OutOfMemory);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalTee(new_slots));
+        func.instruction(&Instruction::LocalGet(new_cap));
+        func.instruction(&Instruction::I32Const(12));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+        // memory.fill(new_slots, 0, new_cap * 12)
+        func.instruction(&Instruction::LocalGet(new_slots));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalGet(new_cap));
+        func.instruction(&Instruction::I32Const(12));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::MemoryFill(0));
+
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 16, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(old_slots));
+
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(counter));
+
+        func.instruction(&Instruction::Block(BlockType::Empty));
+        func.instruction(&Instruction::Loop(BlockType::Empty));
+
+        func.instruction(&Instruction::LocalGet(counter));
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32GeU);
+        func.instruction(&Instruction::BrIf(1));
+
+        func.instruction(&Instruction::LocalGet(old_slots));
+        func.instruction(&Instruction::LocalGet(counter));
+        func.instruction(&Instruction::I32Const(12));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(slot_ptr));
+
+        // live entries only: hash - 1 < 0xfffffffe skips empty slots (0) and tombstones (-1)
+        func.instruction(&Instruction::LocalGet(slot_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::I32Const(-2));
+        func.instruction(&Instruction::I32LtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+
+        func.instruction(&Instruction::LocalGet(slot_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(new_cap));
+        func.instruction(&Instruction::I32RemU);
+        func.instruction(&Instruction::LocalSet(index));
+
+        // keys are distinct, so the first empty slot is the entry's place
+        func.instruction(&Instruction::Block(BlockType::Empty));
+        func.instruction(&Instruction::Loop(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(new_slots));
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::I32Const(12));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalTee(target));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Eqz);
+        func.instruction(&Instruction::BrIf(1));
+        func.instruction(&Instruction::LocalGet(index));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(new_cap));
+        func.instruction(&Instruction::I32RemU);
+        func.instruction(&Instruction::LocalSet(index));
+        func.instruction(&Instruction::Br(0));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(target));
+        func.instruction(&Instruction::LocalGet(slot_ptr));
+        func.instruction(&Instruction::I32Const(12));
+        func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(counter));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(counter));
+
+        func.instruction(&Instruction::Br(0));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::LocalGet(new_cap));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 12, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+        func.instruction(&Instruction::LocalGet(new_slots));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 16, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::LocalGet(dict_ptr));
+    }
+
+    /// Lookup key in dict: dict_ptr, key -> value; `missing` pushes the result for an absent key
+    #[allow(clippy::too_many_arguments)]
+    pub fn lookup(func: &mut Function, dict_ptr: u32, key: u32, hash: u32, capacity: u32, index: u32, slot_ptr: u32, missing: &dyn Fn(&mut Function)) {
+        Self::find(func, dict_ptr, key, hash, capacity, index, slot_ptr, &|func| {
+            func.instruction(&Instruction::LocalGet(slot_ptr));
+            func.instruction(&Instruction::I32Load(MemArg { offset: 8, align: 2, memory_index: 0 }));
+        }, missing);
+    }
+
+    /// Membership test: dict_ptr, key -> 1 if key is present, 0 otherwise
+    pub fn contains(func: &mut Function, dict_ptr: u32, key: u32, hash: u32, capacity: u32, index: u32, slot_ptr: u32) {
+        Self::find(func, dict_ptr, key, hash, capacity, index, slot_ptr, &|func| {
+            func.instruction(&Instruction::I32Const(1));
+        }, &|func| {
+            func.instruction(&Instruction::I32Const(0));
+        });
+    }
+
+    /// d.pop(key), and del d[key]: dict_ptr, key -> the removed value; `missing` pushes the
+    /// result for an absent key. The slot becomes a tombstone, so probes carry on past it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn remove(func: &mut Function, dict_ptr: u32, key: u32, hash: u32, capacity: u32, index: u32, slot_ptr: u32, missing: &dyn Fn(&mut Function)) {
+        Self::find(func, dict_ptr, key, hash, capacity, index, slot_ptr, &|func| {
+            func.instruction(&Instruction::LocalGet(slot_ptr));
+            func.instruction(&Instruction::I32Const(TOMBSTONE));
+            func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+            func.instruction(&Instruction::LocalGet(dict_ptr));
+            func.instruction(&Instruction::LocalGet(dict_ptr));
+            func.instruction(&Instruction::I32Load(MemArg { offset: 8, align: 2, memory_index: 0 }));
+            func.instruction(&Instruction::I32Const(1));
+            func.instruction(&Instruction::I32Sub);
+            func.instruction(&Instruction::I32Store(MemArg { offset: 8, align: 2, memory_index: 0 }));
+
+            func.instruction(&Instruction::LocalGet(dict_ptr));
+            func.instruction(&Instruction::LocalGet(dict_ptr));
+            func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+            func.instruction(&Instruction::I32Const(1));
+            func.instruction(&Instruction::I32Add);
+            func.instruction(&Instruction::I32Store(MemArg { offset: 12, align: 2, memory_index: 0 }));
+
+            func.instruction(&Instruction::LocalGet(slot_ptr));
+            func.instruction(&Instruction::I32Load(MemArg { offset: 8, align: 2, memory_index: 0 }));
+        }, missing);
+    }
+
+    /// Linear probe for key, past tombstones; `found` pushes the result for the slot at slot_ptr,
+    /// `missing` the result when the probe reaches an empty slot
+    #[allow(clippy::too_many_arguments)]
+    fn find(func: &mut Function, dict_ptr: u32, key: u32, hash: u32, capacity: u32, index: u32, slot_ptr: u32, found: &dyn Fn(&mut Function), missing: &dyn Fn(&mut Function)) {
         func.instruction(&Instruction::LocalSet(key));
         func.instruction(&Instruction::LocalSet(dict_ptr));
 
@@ -343,13 +1683,7 @@ impl DictLayout {
         func.instruction(&Instruction::Block(BlockType::Result(ValType::I32)));
         func.instruction(&Instruction::Loop(BlockType::Empty));
 
-        func.instruction(&Instruction::LocalGet(dict_ptr));
-        func.instruction(&Instruction::LocalGet(index));
-        func.instruction(&Instruction::I32Const(12));
-        func.instruction(&Instruction::I32Mul);
-        func.instruction(&Instruction::I32Const(16));
-        func.instruction(&Instruction::I32Add);
-        func.instruction(&Instruction::I32Add);
+        Self::slot_addr(func, dict_ptr, index);
         func.instruction(&Instruction::LocalSet(slot_ptr));
 
         func.instruction(&Instruction::LocalGet(slot_ptr));
@@ -357,18 +1691,21 @@ impl DictLayout {
 
         func.instruction(&Instruction::I32Eqz);
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::I32Const(0));
+        missing(func);
         func.instruction(&Instruction::Br(2));
         func.instruction(&Instruction::End);
 
+        func.instruction(&Instruction::LocalGet(slot_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(hash));
+        func.instruction(&Instruction::I32Eq);
         func.instruction(&Instruction::LocalGet(slot_ptr));
         func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
         func.instruction(&Instruction::LocalGet(key));
         func.instruction(&Instruction::I32Eq);
+        func.instruction(&Instruction::I32And);
         func.instruction(&Instruction::If(BlockType::Empty));
-
-        func.instruction(&Instruction::LocalGet(slot_ptr));
-        func.instruction(&Instruction::I32Load(MemArg { offset: 8, align: 2, memory_index: 0 }));
+        found(func);
         func.instruction(&Instruction::Br(2));
         func.instruction(&Instruction::End);
 
@@ -381,10 +1718,101 @@ impl DictLayout {
 
         func.instruction(&Instruction::Br(0));
         func.instruction(&Instruction::End);
+        // the probe only leaves through the branches above
+        func.instruction(&Instruction::Unreachable);
+        func.instruction(&Instruction::End);
+    }
+}
+
+// Set memory layout helpers: a dict with a set tag whose values are all 1
+pub struct SetLayout;
+
+impl SetLayout {
+    /// Allocate set with the dict layout
+    /// Returns: set_ptr on stack
+    pub fn alloc(func: &mut Function, capacity: u32, set_ptr: u32, counter: u32) {
+        DictLayout::alloc(func, capacity, set_ptr, counter);
+        func.instruction(&Instruction::LocalGet(set_ptr));
+        func.instruction(&Instruction::I32Const(TYPE_SET));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+    }
+
+    /// s.add(item): pops [item, set_ptr]; traps if the receiver isn't a set
+    /// Locals: base..base+8; `grow` as for DictLayout::insert
+    pub fn add(func: &mut Function, base: u32, grow: u32) {
+        let set_ptr = base;
+        let item = base + 1;
+        func.instruction(&Instruction::LocalSet(item));
+        func.instruction(&Instruction::LocalSet(set_ptr));
+
+        func.instruction(&Instruction::LocalGet(set_ptr));
+        func.instruction(&Instruction::I32Const(1024));
+        func.instruction(&Instruction::I32LtU);
+        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::Else);
+        func.instruction(&Instruction::LocalGet(set_ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(TYPE_SET));
+        func.instruction(&Instruction::I32Ne);
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::TypeError);
         func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(set_ptr));
+        func.instruction(&Instruction::LocalGet(item));
+        func.instruction(&Instruction::I32Const(1));
+        DictLayout::insert(func, base, grow);
     }
 }
 
+/// `item in container` for dicts (by key), sets and strings (by substring): pops
+/// [container, item], pushes 1 or 0. Traps for anything else. Locals: base..base+6
+pub fn contains(func: &mut Function, base: u32) {
+    let container = base;
+    func.instruction(&Instruction::LocalSet(base + 1));
+    func.instruction(&Instruction::LocalSet(container));
+
+    // a string holds substrings, and only strings
+    has_tag(func, container, TYPE_STRING);
+    func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+    has_tag(func, base + 1, TYPE_STRING);
+    func.instruction(&Instruction::I32Eqz);
+    func.instruction(&Instruction::If(BlockType::Empty));
+    trap(func, RuntimeError::TypeError);
+    func.instruction(&Instruction::End);
+    func.instruction(&Instruction::LocalGet(container));
+    func.instruction(&Instruction::LocalGet(base + 1));
+    StringLayout::contains(func, base, base + 1, base + 2, base + 3, base + 4, base + 5, base + 6);
+    func.instruction(&Instruction::Else);
+
+    func.instruction(&Instruction::LocalGet(container));
+    func.instruction(&Instruction::I32Const(1024));
+    func.instruction(&Instruction::I32LtU);
+    func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+    func.instruction(&Instruction::I32Const(1));
+    func.instruction(&Instruction::Else);
+    func.instruction(&Instruction::LocalGet(container));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::LocalTee(base + 2));
+    func.instruction(&Instruction::I32Const(TYPE_DICT));
+    func.instruction(&Instruction::I32Ne);
+    func.instruction(&Instruction::LocalGet(base + 2));
+    func.instruction(&Instruction::I32Const(TYPE_SET));
+    func.instruction(&Instruction::I32Ne);
+    func.instruction(&Instruction::I32And);
+    func.instruction(&Instruction::End);
+    func.instruction(&Instruction::If(BlockType::Empty));
+    trap(func, RuntimeError::TypeError);
+    func.instruction(&Instruction::End);
+
+    func.instruction(&Instruction::LocalGet(container));
+    func.instruction(&Instruction::LocalGet(base + 1));
+    DictLayout::contains(func, base, base + 1, base + 2, base + 3, base + 4, base + 5);
+    func.instruction(&Instruction::End);
+}
+
 // String memory layout helpers
 pub struct StringLayout;
 
@@ -403,7 +1831,7 @@ impl StringLayout {
         func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
         func.instruction(&Instruction::I32GtU);
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
+        trap(func, RuntimeError::OutOfMemory);
         func.instruction(&Instruction::End);
 
         // Store type tag
@@ -431,60 +1859,21 @@ impl StringLayout {
         func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
     }
 
-    /// Create substring: str_ptr, start, end -> new_str_ptr
-    pub fn slice(func: &mut Function, src: u32, start: u32, end: u32, len: u32, new_ptr: u32, counter: u32) {
-        func.instruction(&Instruction::LocalSet(end));
-        func.instruction(&Instruction::LocalSet(start));
-        func.instruction(&Instruction::LocalSet(src));
-
-        // Get string length
-        func.instruction(&Instruction::LocalGet(src));
-        Self::load_length(func);
-        func.instruction(&Instruction::LocalSet(len));
-
-        // Clamp start to [0, len]
-        func.instruction(&Instruction::LocalGet(start));
-        func.instruction(&Instruction::I32Const(0));
-        func.instruction(&Instruction::I32LtS);
-        func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::I32Const(0));
-        func.instruction(&Instruction::LocalSet(start));
-        func.instruction(&Instruction::End);
-
-        func.instruction(&Instruction::LocalGet(start));
-        func.instruction(&Instruction::LocalGet(len));
-        func.instruction(&Instruction::I32GtS);
-        func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::LocalGet(len));
-        func.instruction(&Instruction::LocalSet(start));
-        func.instruction(&Instruction::End);
-
-        // Clamp end to [start, len]
-        func.instruction(&Instruction::LocalGet(end));
-        func.instruction(&Instruction::LocalGet(start));
-        func.instruction(&Instruction::I32LtS);
-        func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::LocalGet(start));
-        func.instruction(&Instruction::LocalSet(end));
-        func.instruction(&Instruction::End);
-
-        func.instruction(&Instruction::LocalGet(end));
-        func.instruction(&Instruction::LocalGet(len));
-        func.instruction(&Instruction::I32GtS);
-        func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::LocalGet(len));
-        func.instruction(&Instruction::LocalSet(end));
-        func.instruction(&Instruction::End);
+    /// Whether value holds a string: pushes 1 or 0
+    /// Negative ints are huge unsigned, so only values below the heap pointer have their tag read
+    pub fn is_string(func: &mut Function, value: u32) {
+        has_tag(func, value, TYPE_STRING);
+    }
 
-        // Calculate slice length
-        func.instruction(&Instruction::LocalGet(end));
-        func.instruction(&Instruction::LocalGet(start));
-        func.instruction(&Instruction::I32Sub);
-        func.instruction(&Instruction::LocalSet(len));
+    /// Copy the slice described by adjust_slice into a new string/bytes object of the same type
+    /// Locals: base=src, base+1=len, base+2=start, base+3=stop, base+4=step, base+5=count,
+    /// base+6=new_ptr, base+7=counter
+    /// Returns: new_ptr on stack
+    pub fn slice(func: &mut Function, base: u32) {
+        let (src, start, step, count, new_ptr, counter) = (base, base + 2, base + 4, base + 5, base + 6, base + 7);
 
-        // Allocate new string
         let aligned_size_expr = |f: &mut Function| {
-            f.instruction(&Instruction::LocalGet(len));
+            f.instruction(&Instruction::LocalGet(count));
             f.instruction(&Instruction::I32Const(11));
             f.instruction(&Instruction::I32Add);
             f.instruction(&Instruction::I32Const(-4));
@@ -497,19 +1886,26 @@ impl StringLayout {
         func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
         func.instruction(&Instruction::I32GtU);
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
+        trap(func, RuntimeError::OutOfMemory);
         func.instruction(&Instruction::End);
 
+        // same tag as the source so bytes slice to bytes
         func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
         func.instruction(&Instruction::LocalTee(new_ptr));
-        func.instruction(&Instruction::I32Const(TYPE_STRING));
+        func.instruction(&Instruction::LocalGet(src));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
         func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
 
         func.instruction(&Instruction::LocalGet(new_ptr));
-        func.instruction(&Instruction::LocalGet(len));
+        func.instruction(&Instruction::LocalGet(count));
         func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
 
-        // Copy bytes
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        aligned_size_expr(func);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+        // new[8 + i] = src[8 + start + i * step]
         func.instruction(&Instruction::I32Const(0));
         func.instruction(&Instruction::LocalSet(counter));
 
@@ -517,26 +1913,24 @@ impl StringLayout {
         func.instruction(&Instruction::Loop(BlockType::Empty));
 
         func.instruction(&Instruction::LocalGet(counter));
-        func.instruction(&Instruction::LocalGet(len));
-        func.instruction(&Instruction::I32GeU);
+        func.instruction(&Instruction::LocalGet(count));
+        func.instruction(&Instruction::I32GeS);
         func.instruction(&Instruction::BrIf(1));
 
         func.instruction(&Instruction::LocalGet(new_ptr));
-        func.instruction(&Instruction::I32Const(8));
-        func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::LocalGet(counter));
         func.instruction(&Instruction::I32Add);
 
         func.instruction(&Instruction::LocalGet(src));
-        func.instruction(&Instruction::I32Const(8));
-        func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::LocalGet(start));
         func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::LocalGet(counter));
+        func.instruction(&Instruction::LocalGet(step));
+        func.instruction(&Instruction::I32Mul);
         func.instruction(&Instruction::I32Add);
-        func.instruction(&Instruction::I32Load8U(MemArg { offset: 0, align: 0, memory_index: 0 }));
+        func.instruction(&Instruction::I32Load8U(MemArg { offset: 8, align: 0, memory_index: 0 }));
 
-        func.instruction(&Instruction::I32Store8(MemArg { offset: 0, align: 0, memory_index: 0 }));
+        func.instruction(&Instruction::I32Store8(MemArg { offset: 8, align: 0, memory_index: 0 }));
 
         func.instruction(&Instruction::LocalGet(counter));
         func.instruction(&Instruction::I32Const(1));
@@ -547,13 +1941,6 @@ impl StringLayout {
         func.instruction(&Instruction::End);
         func.instruction(&Instruction::End);
 
-        // Update heap pointer
-        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
-        aligned_size_expr(func);
-        func.instruction(&Instruction::I32Add);
-        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
-
-        // Return new string pointer
         func.instruction(&Instruction::LocalGet(new_ptr));
     }
 
@@ -592,7 +1979,7 @@ impl StringLayout {
         func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
         func.instruction(&Instruction::I32GtU);
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
+        trap(func, RuntimeError::OutOfMemory);
         func.instruction(&Instruction::End);
 
         // Allocate new string header
@@ -693,6 +2080,9 @@ impl StringLayout {
         func.instruction(&Instruction::LocalSet(str2));
         func.instruction(&Instruction::LocalSet(str1));
 
+        // Mismatches branch out of this block with 0 rather than returning from the caller
+        func.instruction(&Instruction::Block(BlockType::Result(ValType::I32)));
+
         // Load lengths
         func.instruction(&Instruction::LocalGet(str1));
         Self::load_length(func);
@@ -702,13 +2092,13 @@ impl StringLayout {
         Self::load_length(func);
         func.instruction(&Instruction::LocalSet(len2));
 
-        // Fast path: if lengths differ, return 0
+        // Fast path: if lengths differ, result is 0
         func.instruction(&Instruction::LocalGet(len1));
         func.instruction(&Instruction::LocalGet(len2));
         func.instruction(&Instruction::I32Ne);
         func.instruction(&Instruction::If(BlockType::Empty));
         func.instruction(&Instruction::I32Const(0));
-        func.instruction(&Instruction::Return);
+        func.instruction(&Instruction::Br(1));
         func.instruction(&Instruction::End);
 
         // Compare bytes: loop counter = 0 to len1
@@ -738,11 +2128,11 @@ impl StringLayout {
         func.instruction(&Instruction::I32Add);
         func.instruction(&Instruction::I32Load8U(MemArg { offset: 0, align: 0, memory_index: 0 }));
 
-        // If bytes differ, return 0
+        // If bytes differ, result is 0
         func.instruction(&Instruction::I32Ne);
         func.instruction(&Instruction::If(BlockType::Empty));
         func.instruction(&Instruction::I32Const(0));
-        func.instruction(&Instruction::Return);
+        func.instruction(&Instruction::Br(3));
         func.instruction(&Instruction::End);
 
         func.instruction(&Instruction::LocalGet(counter));
@@ -753,8 +2143,9 @@ impl StringLayout {
         func.instruction(&Instruction::End);
         func.instruction(&Instruction::End);
 
-        // All bytes equal, return 1
+        // All bytes equal, result is 1
         func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::End);
     }
 
     /// String indexing: str_ptr, index -> byte value (as i32)
@@ -770,7 +2161,7 @@ impl StringLayout {
         Self::load_length(func);
         func.instruction(&Instruction::I32GeU);
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
+        trap(func, RuntimeError::IndexOutOfRange);
         func.instruction(&Instruction::End);
 
         // Load byte at str_ptr + 8 + index
@@ -785,7 +2176,94 @@ impl StringLayout {
     /// Check if string starts with prefix
     /// Pops [str_ptr, prefix_ptr], pushes 1 if true, 0 if false
     /// Uses byte_local as a temporary result holder (we use counter for the result, byte_local for bytes)
-    pub fn startswith(func: &mut Function, str_ptr: u32, prefix_ptr: u32, str_len: u32, prefix_len: u32, counter: u32, result: u32) {
+pub fn startswith(func: &mut Function, str_ptr: u32, prefix_ptr: u32, str_len: u32, prefix_len: u32, counter: u32, result: u32) {
+        Self::affix(func, str_ptr, prefix_ptr, str_len, prefix_len, counter, result, false);
+    }
+
+    /// Suffix check: str_ptr, suffix_ptr -> 1 if str ends with suffix
+    pub fn endswith(func: &mut Function, str_ptr: u32, suffix_ptr: u32, str_len: u32, suffix_len: u32, counter: u32, result: u32) {
+        Self::affix(func, str_ptr, suffix_ptr, str_len, suffix_len, counter, result, true);
+    }
+
+    /// Substring search: str_ptr, sub_ptr -> 1 if sub occurs in str
+    #[allow(clippy::too_many_arguments)]
+    pub fn contains(func: &mut Function, str_ptr: u32, sub_ptr: u32, str_len: u32, sub_len: u32, i: u32, j: u32, result: u32) {
+        func.instruction(&Instruction::LocalSet(sub_ptr));
+        func.instruction(&Instruction::LocalSet(str_ptr));
+
+        func.instruction(&Instruction::LocalGet(str_ptr));
+        Self::load_length(func);
+        func.instruction(&Instruction::LocalSet(str_len));
+        func.instruction(&Instruction::LocalGet(sub_ptr));
+        Self::load_length(func);
+        func.instruction(&Instruction::LocalSet(sub_len));
+
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(result));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(i));
+
+        func.instruction(&Instruction::Block(BlockType::Empty)); // done
+        func.instruction(&Instruction::Loop(BlockType::Empty)); // each start i
+
+        // no room left for sub at i
+        func.instruction(&Instruction::LocalGet(i));
+        func.instruction(&Instruction::LocalGet(sub_len));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(str_len));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::BrIf(1));
+
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(j));
+        func.instruction(&Instruction::Block(BlockType::Empty)); // mismatch
+        func.instruction(&Instruction::Loop(BlockType::Empty)); // each byte j
+
+        // all of sub matched
+        func.instruction(&Instruction::LocalGet(j));
+        func.instruction(&Instruction::LocalGet(sub_len));
+        func.instruction(&Instruction::I32GeU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::LocalSet(result));
+        func.instruction(&Instruction::Br(4));
+        func.instruction(&Instruction::End);
+
+        // str[i + j] != sub[j]
+        func.instruction(&Instruction::LocalGet(str_ptr));
+        func.instruction(&Instruction::LocalGet(i));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(j));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Load8U(MemArg { offset: 8, align: 0, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(sub_ptr));
+        func.instruction(&Instruction::LocalGet(j));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Load8U(MemArg { offset: 8, align: 0, memory_index: 0 }));
+        func.instruction(&Instruction::I32Ne);
+        func.instruction(&Instruction::BrIf(1));
+
+        func.instruction(&Instruction::LocalGet(j));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(j));
+        func.instruction(&Instruction::Br(0));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(i));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(i));
+        func.instruction(&Instruction::Br(0));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(result));
+    }
+
+    // Prefix or suffix check, the shared body of startswith and endswith
+        fn affix(func: &mut Function, str_ptr: u32, prefix_ptr: u32, str_len: u32, prefix_len: u32, counter: u32, result: u32, suffix: bool) {
         func.instruction(&Instruction::LocalSet(prefix_ptr));
         func.instruction(&Instruction::LocalSet(str_ptr));
 
@@ -808,6 +2286,16 @@ impl StringLayout {
         func.instruction(&Instruction::LocalSet(result));
         func.instruction(&Instruction::Else);
 
+        // A suffix is compared from str_len - prefix_len on
+        if suffix {
+            func.instruction(&Instruction::LocalGet(str_ptr));
+            func.instruction(&Instruction::LocalGet(str_len));
+            func.instruction(&Instruction::I32Add);
+            func.instruction(&Instruction::LocalGet(prefix_len));
+            func.instruction(&Instruction::I32Sub);
+            func.instruction(&Instruction::LocalSet(str_ptr));
+        }
+
         // Assume match initially (handles empty prefix case)
         func.instruction(&Instruction::I32Const(1));
         func.instruction(&Instruction::LocalSet(result));
@@ -950,7 +2438,7 @@ impl StringLayout {
         func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
         func.instruction(&Instruction::I32GtU);
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
+        trap(func, RuntimeError::OutOfMemory);
         func.instruction(&Instruction::End);
 
         // Store type tag
@@ -1042,23 +2530,279 @@ impl StringLayout {
         func.instruction(&Instruction::LocalGet(len));
         func.instruction(&Instruction::I32Const(8));
         func.instruction(&Instruction::I32Add);
-        func.instruction(&Instruction::I32Const(3));
+        func.instruction(&Instruction::I32Const(3));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Const(-4));
+        func.instruction(&Instruction::I32And);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+        // Return string pointer
+        func.instruction(&Instruction::LocalGet(new_ptr));
+    }
+}
+
+// Bytes layout: identical to strings but with TYPE_BYTES tag
+// Memory layout: [type:i32=4][length:i32][bytes...]
+/// Ring buffer print() appends to, between the reserved low memory and the heap
+pub struct StdoutLayout;
+
+impl StdoutLayout {
+    pub const BASE: i32 = 0x8000;
+
+    /// Append the byte in `byte`; pos is scratch
+    pub fn write_byte(func: &mut Function, byte: u32, pos: u32) {
+        func.instruction(&Instruction::I32Const(Self::BASE));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(pos));
+
+        // data[pos % capacity] = byte
+        func.instruction(&Instruction::I32Const(Self::BASE));
+        func.instruction(&Instruction::LocalGet(pos));
+        func.instruction(&Instruction::I32Const(STDOUT_CAPACITY as i32));
+        func.instruction(&Instruction::I32RemU);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(byte));
+        func.instruction(&Instruction::I32Store8(MemArg { offset: 8, align: 0, memory_index: 0 }));
+
+        func.instruction(&Instruction::I32Const(Self::BASE));
+        func.instruction(&Instruction::LocalGet(pos));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+    }
+
+    /// Append a constant byte; byte and pos are scratch
+    pub fn write_const(func: &mut Function, value: u8, byte: u32, pos: u32) {
+        func.instruction(&Instruction::I32Const(value as i32));
+        func.instruction(&Instruction::LocalSet(byte));
+        Self::write_byte(func, byte, pos);
+    }
+
+    /// Append the bytes of the string in `str_ptr`
+    /// Locals: len, counter, byte, pos are scratch
+    pub fn write_string(func: &mut Function, str_ptr: u32, len: u32, counter: u32, byte: u32, pos: u32) {
+        func.instruction(&Instruction::LocalGet(str_ptr));
+        StringLayout::load_length(func);
+        func.instruction(&Instruction::LocalSet(len));
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(counter));
+
+        func.instruction(&Instruction::Block(BlockType::Empty));
+        func.instruction(&Instruction::Loop(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(counter));
+        func.instruction(&Instruction::LocalGet(len));
+        func.instruction(&Instruction::I32GeU);
+        func.instruction(&Instruction::BrIf(1));
+
+        func.instruction(&Instruction::LocalGet(str_ptr));
+        func.instruction(&Instruction::LocalGet(counter));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Load8U(MemArg { offset: 8, align: 0, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(byte));
+        Self::write_byte(func, byte, pos);
+
+        func.instruction(&Instruction::LocalGet(counter));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(counter));
+        func.instruction(&Instruction::Br(0));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+    }
+}
+
+pub struct BytesLayout;
+
+impl BytesLayout {
+    /// Allocate bytes in heap: [type:i32][length:i32][bytes...]
+    /// Returns: bytes_ptr on stack
+    pub fn alloc(func: &mut Function, bytes: &[u8]) {
+        let length = bytes.len() as i32;
+        let size = 8 + bytes.len();
+        let aligned_size = (size + 3) & !3;
+
+        // Check heap overflow
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(aligned_size as i32));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::OutOfMemory);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(TYPE_BYTES));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(length));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+
+        // Return bytes pointer before updating heap ptr
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(aligned_size as i32));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+    }
+
+    /// Whether value holds bytes: pushes 1 or 0
+    pub fn is_bytes(func: &mut Function, value: u32) {
+        has_tag(func, value, TYPE_BYTES);
+    }
+
+    /// bytes(seq): a list or tuple of ints in 0..256 -> new bytes_ptr
+    /// Locals: base=seq, base+1=n, base+2=i, base+3=new_ptr, base+4=byte
+    /// An element out of range calls `invalid` and is stored as 0
+    pub fn from_sequence(func: &mut Function, base: u32, invalid: &dyn Fn(&mut Function)) {
+        let (seq, n, i, new_ptr, byte) = (base, base + 1, base + 2, base + 3, base + 4);
+        TupleLayout::check_sequence(func, seq);
+
+        func.instruction(&Instruction::LocalGet(seq));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(n));
+
+        // aligned size = (8 + n + 3) & ~3, checked against heap limit
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalTee(new_ptr));
+        func.instruction(&Instruction::LocalGet(n));
+        func.instruction(&Instruction::I32Const(8 + 3));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Const(-4));
+        func.instruction(&Instruction::I32And);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::OutOfMemory);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::I32Const(TYPE_BYTES));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::LocalGet(n));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(i));
+        func.instruction(&Instruction::Block(BlockType::Empty));
+        func.instruction(&Instruction::Loop(BlockType::Empty));
+
+        func.instruction(&Instruction::LocalGet(i));
+        func.instruction(&Instruction::LocalGet(n));
+        func.instruction(&Instruction::I32GeS);
+        func.instruction(&Instruction::BrIf(1));
+
+        // byte = data[i]
+        func.instruction(&Instruction::LocalGet(seq));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 12, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalGet(i));
+        func.instruction(&Instruction::I32Const(4));
+        func.instruction(&Instruction::I32Mul);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalTee(byte));
+
+        // negative values are huge unsigned, so one comparison covers both ends
+        func.instruction(&Instruction::I32Const(255));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        invalid(func);
+        func.instruction(&Instruction::I32Const(0));
+        func.instruction(&Instruction::LocalSet(byte));
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::LocalGet(i));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(byte));
+        func.instruction(&Instruction::I32Store8(MemArg { offset: 8, align: 0, memory_index: 0 }));
+
+        func.instruction(&Instruction::LocalGet(i));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(i));
+        func.instruction(&Instruction::Br(0));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+    }
+
+    /// Concatenate two bytes objects
+    /// Pops [bytes_a, bytes_b], pushes new bytes_ptr
+    pub fn concat(func: &mut Function, a: u32, b: u32, len_a: u32, new_ptr: u32) {
+        func.instruction(&Instruction::LocalSet(b));
+        func.instruction(&Instruction::LocalSet(a));
+
+        func.instruction(&Instruction::LocalGet(a));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::LocalSet(len_a));
+
+        // aligned size = (8 + len_a + len_b + 3) & ~3, checked against heap limit
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalTee(new_ptr));
+        func.instruction(&Instruction::LocalGet(len_a));
+        func.instruction(&Instruction::LocalGet(b));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Const(8 + 3));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Const(-4));
+        func.instruction(&Instruction::I32And);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::OutOfMemory);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::I32Const(TYPE_BYTES));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::LocalGet(len_a));
+        func.instruction(&Instruction::LocalGet(b));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+
+        // Copy a then b
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::I32Const(8));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(a));
+        func.instruction(&Instruction::I32Const(8));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(len_a));
+        func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::I32Const(8));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(len_a));
         func.instruction(&Instruction::I32Add);
-        func.instruction(&Instruction::I32Const(-4));
-        func.instruction(&Instruction::I32And);
+        func.instruction(&Instruction::LocalGet(b));
+        func.instruction(&Instruction::I32Const(8));
         func.instruction(&Instruction::I32Add);
-        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalGet(b));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
 
-        // Return string pointer
         func.instruction(&Instruction::LocalGet(new_ptr));
     }
-}
-
-// Bytes layout: identical to strings but with TYPE_BYTES tag
-// Memory layout: [type:i32=4][length:i32][bytes...]
-pub struct BytesLayout;
 
-impl BytesLayout {
     /// Convert string to bytes (UTF-8 encoding)
     /// Pops [str_ptr], pushes bytes_ptr
     pub fn from_string(func: &mut Function, str_local: u32, len_local: u32, counter: u32, new_ptr: u32) {
@@ -1085,7 +2829,7 @@ impl BytesLayout {
         func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
         func.instruction(&Instruction::I32GtU);
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
+        trap(func, RuntimeError::OutOfMemory);
         func.instruction(&Instruction::End);
 
         // Allocate bytes header
@@ -1180,7 +2924,7 @@ impl BytesLayout {
         func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
         func.instruction(&Instruction::I32GtU);
         func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::Unreachable);
+        trap(func, RuntimeError::OutOfMemory);
         func.instruction(&Instruction::End);
 
         // Store type tag (TYPE_STRING)
@@ -1316,22 +3060,178 @@ impl BytesLayout {
     }
 }
 
+// Hash algorithms exposed through hashlib; the type tag records which one a hash object uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha3_256,
+}
+
+impl HashAlgorithm {
+    fn type_tag(self) -> i32 {
+        match self {
+            HashAlgorithm::Sha256 => TYPE_SHA256,
+            HashAlgorithm::Sha3_256 => TYPE_SHA3_256,
+        }
+    }
+}
+
+// Hash object: [type:i32=5|6][length:i32=32][digest:32 bytes][msg_ptr:i32]
+// The digest sits where bytes keep their data, so sha256() and hexdigest()
+// read a hash object as its current digest
+pub struct HasherLayout;
+
+impl HasherLayout {
+    /// Pops [msg_ptr, digest_ptr], pushes hasher_ptr
+    pub fn alloc(func: &mut Function, algorithm: HashAlgorithm, msg: u32, digest: u32, new_ptr: u32) {
+        func.instruction(&Instruction::LocalSet(digest));
+        func.instruction(&Instruction::LocalSet(msg));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(44));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::OutOfMemory);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalTee(new_ptr));
+        func.instruction(&Instruction::I32Const(44));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::I32Const(algorithm.type_tag()));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::I32Const(32));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::LocalGet(msg));
+        func.instruction(&Instruction::LocalGet(digest));
+        Self::store(func, new_ptr, msg, digest);
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+    }
+
+    /// Replace message and digest: pops [hasher_ptr, msg_ptr, digest_ptr]
+    pub fn store(func: &mut Function, hasher: u32, msg: u32, digest: u32) {
+        func.instruction(&Instruction::LocalSet(digest));
+        func.instruction(&Instruction::LocalSet(msg));
+        func.instruction(&Instruction::LocalSet(hasher));
+
+        func.instruction(&Instruction::LocalGet(hasher));
+        func.instruction(&Instruction::I32Const(8));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalGet(digest));
+        func.instruction(&Instruction::I32Const(8));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Const(32));
+        func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+
+        func.instruction(&Instruction::LocalGet(hasher));
+        func.instruction(&Instruction::LocalGet(msg));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 40, align: 2, memory_index: 0 }));
+    }
+
+    /// Load accumulated message: pops [hasher_ptr], pushes msg_ptr
+    pub fn load_message(func: &mut Function) {
+        func.instruction(&Instruction::I32Load(MemArg { offset: 40, align: 2, memory_index: 0 }));
+    }
+
+    /// Copy digest out as a bytes object: pops [hasher_ptr], pushes bytes_ptr
+    pub fn digest(func: &mut Function, hasher: u32, new_ptr: u32) {
+        func.instruction(&Instruction::LocalSet(hasher));
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::I32Const(40));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::OutOfMemory);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+        func.instruction(&Instruction::LocalTee(new_ptr));
+        func.instruction(&Instruction::I32Const(40));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+        // header + digest are contiguous: copy all 40 bytes, then retag
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::LocalGet(hasher));
+        func.instruction(&Instruction::I32Const(40));
+        func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+        func.instruction(&Instruction::I32Const(TYPE_BYTES));
+        func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+        func.instruction(&Instruction::LocalGet(new_ptr));
+    }
+
+    /// Pops [hasher_ptr], pushes 1 if it is a SHA3-256 object
+    pub fn is_sha3(func: &mut Function) {
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(TYPE_SHA3_256));
+        func.instruction(&Instruction::I32Eq);
+    }
+
+    /// Trap unless ptr holds a hash object
+    pub fn check_type(func: &mut Function, ptr: u32) {
+        func.instruction(&Instruction::LocalGet(ptr));
+        func.instruction(&Instruction::I32Const(1024));
+        func.instruction(&Instruction::I32LtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+
+        // tag in [TYPE_SHA256, TYPE_SHA3_256]
+        func.instruction(&Instruction::LocalGet(ptr));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(TYPE_SHA256));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::I32Const(TYPE_SHA3_256 - TYPE_SHA256));
+        func.instruction(&Instruction::I32GtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+    }
+}
+
+// SHA-256 round constants (first 32 bits of fractional parts of cube roots of first 64 primes)
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Passive data segment holding SHA256_K in every module that hashes with sha256; modules that
+/// only need segments for literals leave it empty
+pub const SHA256_K_SEGMENT: u32 = 0;
+// Per-hash scratch after the padded message: K copied from its segment, then w[0..64]
+const SHA256_WS_SIZE: i32 = 512;
+const SHA256_W_OFFSET: u64 = 256;
+
+/// Contents of the SHA256_K_SEGMENT data segment, little-endian like every i32 load
+pub fn sha256_k_table() -> Vec<u8> {
+    SHA256_K.iter().flat_map(|k| k.to_le_bytes()).collect()
+}
+
 /// SHA256 hash function - full FIPS 180-4 implementation
 /// Pops [bytes_ptr], pushes bytes_ptr (32-byte hash)
 /// Implements complete SHA-256 with padding, message schedule, and compression
 pub fn sha256(func: &mut Function, base: u32) {
-    // SHA-256 constants (first 32 bits of fractional parts of cube roots of first 64 primes)
-    const K: [u32; 64] = [
-        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
-        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
-        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
-        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
-        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
-        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
-        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
-        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
-    ];
-
     // Initial hash values (first 32 bits of fractional parts of square roots of first 8 primes)
     const H0_INIT: u32 = 0x6a09e667;
     const H1_INIT: u32 = 0xbb67ae85;
@@ -1368,8 +3268,7 @@ pub fn sha256(func: &mut Function, base: u32) {
     let s1 = base + 91;
     let ch = base + 92;
     let maj = base + 93;
-    let k_val = base + 94;
-    let w_val = base + 95;
+    let ws = base + 94;
     let new_ptr = base + 96;
 
     // Store input pointer
@@ -1402,16 +3301,27 @@ pub fn sha256(func: &mut Function, base: u32) {
     func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
     func.instruction(&Instruction::LocalSet(padded_ptr));
 
-    // Check heap overflow
+    // Check heap overflow; the workspace follows the padded buffer
     func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
     func.instruction(&Instruction::LocalGet(padded_len));
     func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::I32Const(SHA256_WS_SIZE));
+    func.instruction(&Instruction::I32Add);
     func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
     func.instruction(&Instruction::I32GtU);
     func.instruction(&Instruction::If(BlockType::Empty));
-    func.instruction(&Instruction::Unreachable);
+    trap(func, RuntimeError::OutOfMemory);
     func.instruction(&Instruction::End);
 
+    // Copy K from its data segment into the workspace
+    func.instruction(&Instruction::LocalGet(padded_ptr));
+    func.instruction(&Instruction::LocalGet(padded_len));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::LocalTee(ws));
+    func.instruction(&Instruction::I32Const(0));
+    func.instruction(&Instruction::I32Const(SHA256_W_OFFSET as i32));
+    func.instruction(&Instruction::MemoryInit { mem: 0, data_index: SHA256_K_SEGMENT });
+
     // Copy original data to padded buffer using memory.copy
     func.instruction(&Instruction::LocalGet(padded_ptr));
     func.instruction(&Instruction::LocalGet(bytes_ptr));
@@ -1643,6 +3553,13 @@ pub fn sha256(func: &mut Function, base: u32) {
         func.instruction(&Instruction::LocalSet(w_i));
     }
 
+    // Spill the schedule to the workspace so rounds can index it
+    for i in 0..64 {
+        func.instruction(&Instruction::LocalGet(ws));
+        func.instruction(&Instruction::LocalGet(w_start + i));
+        func.instruction(&Instruction::I32Store(MemArg { offset: SHA256_W_OFFSET + i as u64 * 4, align: 2, memory_index: 0 }));
+    }
+
     // Initialize working variables from hash values
     for i in 0..8 {
         func.instruction(&Instruction::LocalGet(h[i]));
@@ -1691,42 +3608,21 @@ pub fn sha256(func: &mut Function, base: u32) {
     func.instruction(&Instruction::I32Xor);
     func.instruction(&Instruction::LocalSet(ch));
 
-    // Load K[round_idx] into k_val using if-else ladder (deterministic constant selection)
-    func.instruction(&Instruction::I32Const(0));
-    func.instruction(&Instruction::LocalSet(k_val));
-    for (k_idx, k_const) in K.iter().enumerate() {
-        func.instruction(&Instruction::LocalGet(round_idx));
-        func.instruction(&Instruction::I32Const(k_idx as i32));
-        func.instruction(&Instruction::I32Eq);
-        func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::I32Const(*k_const as i32));
-        func.instruction(&Instruction::LocalSet(k_val));
-        func.instruction(&Instruction::End);
-    }
-
-    // Load w[round_idx] into w_val using if-else ladder
-    func.instruction(&Instruction::I32Const(0));
-    func.instruction(&Instruction::LocalSet(w_val));
-    for w_idx in 0..64 {
-        func.instruction(&Instruction::LocalGet(round_idx));
-        func.instruction(&Instruction::I32Const(w_idx as i32));
-        func.instruction(&Instruction::I32Eq);
-        func.instruction(&Instruction::If(BlockType::Empty));
-        func.instruction(&Instruction::LocalGet(w_start + w_idx));
-        func.instruction(&Instruction::LocalSet(w_val));
-        func.instruction(&Instruction::End);
-    }
-
-    // temp1 = h + S1 + ch + K[round_idx] + w[round_idx]
+    // temp1 = h + S1 + ch + K[round_idx] + w[round_idx], K and w read from the workspace
     func.instruction(&Instruction::LocalGet(work[7]));
     func.instruction(&Instruction::LocalGet(s1));
     func.instruction(&Instruction::I32Add);
     func.instruction(&Instruction::LocalGet(ch));
     func.instruction(&Instruction::I32Add);
-    func.instruction(&Instruction::LocalGet(k_val));
-    func.instruction(&Instruction::I32Add);
-    func.instruction(&Instruction::LocalGet(w_val));
-    func.instruction(&Instruction::I32Add);
+    for offset in [0, SHA256_W_OFFSET] {
+        func.instruction(&Instruction::LocalGet(ws));
+        func.instruction(&Instruction::LocalGet(round_idx));
+        func.instruction(&Instruction::I32Const(2));
+        func.instruction(&Instruction::I32Shl);
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Load(MemArg { offset, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Add);
+    }
     func.instruction(&Instruction::LocalSet(temp1));
 
     // S0 = ROTR(a, 2) ^ ROTR(a, 13) ^ ROTR(a, 22)
@@ -1836,7 +3732,7 @@ pub fn sha256(func: &mut Function, base: u32) {
     func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
     func.instruction(&Instruction::I32GtU);
     func.instruction(&Instruction::If(BlockType::Empty));
-    func.instruction(&Instruction::Unreachable);
+    trap(func, RuntimeError::OutOfMemory);
     func.instruction(&Instruction::End);
 
     // Store type tag
@@ -1890,6 +3786,286 @@ pub fn sha256(func: &mut Function, base: u32) {
 
     // Return new pointer
     func.instruction(&Instruction::LocalGet(new_ptr));
+}
+
+// Keccak-f[1600] round constants
+const KECCAK_RC: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+// Rotation offsets indexed [x][y]
+const KECCAK_ROT: [[u32; 5]; 5] = [
+    [0, 36, 3, 41, 18],
+    [1, 44, 10, 45, 2],
+    [62, 6, 43, 15, 61],
+    [28, 55, 25, 21, 56],
+    [27, 20, 39, 8, 14],
+];
+
+// Domain separation byte for the final block
+pub const KECCAK_PAD: i32 = 0x01;   // Ethereum keccak256
+pub const SHA3_PAD: i32 = 0x06;     // FIPS 202 SHA3-256
+
+/// Keccak-256 sponge (rate 136 bytes) over keccak-f[1600]
+/// Pops [bytes_ptr], pushes bytes_ptr (32-byte hash)
+/// `domain` selects keccak256 (KECCAK_PAD) or sha3_256 (SHA3_PAD)
+pub fn keccak256(func: &mut Function, base: u32, domain: i32) {
+    const RATE: i32 = 136;
+    // Workspace layout: [A: 25 lanes][C: 5 lanes][D: 5 lanes][B: 25 lanes][RC: 24 lanes]
+    const A: u64 = 0;
+    const C: u64 = 200;
+    const D: u64 = 240;
+    const B: u64 = 280;
+    const RC: u64 = 480;
+    const WS_SIZE: i32 = 672;
+
+    let bytes_ptr = base;
+    let data_len = base + 1;
+    let padded_len = base + 2;
+    let padded_ptr = base + 3;
+    let ws = base + 4;
+    let block = base + 5;
+    let round = base + 6;
+
+    let mem64 = |offset: u64| MemArg { offset, align: 3, memory_index: 0 };
+    let lane = |x: usize, y: usize| (x + 5 * y) as u64 * 8;
+
+    func.instruction(&Instruction::LocalSet(bytes_ptr));
+    func.instruction(&Instruction::LocalGet(bytes_ptr));
+    func.instruction(&Instruction::I32Load(MemArg { offset: 4, align: 2, memory_index: 0 }));
+    func.instruction(&Instruction::LocalSet(data_len));
+
+    // padded_len = (data_len / RATE + 1) * RATE (pad10*1 always adds at least one byte)
+    func.instruction(&Instruction::LocalGet(data_len));
+    func.instruction(&Instruction::I32Const(RATE));
+    func.instruction(&Instruction::I32DivU);
+    func.instruction(&Instruction::I32Const(1));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::I32Const(RATE));
+    func.instruction(&Instruction::I32Mul);
+    func.instruction(&Instruction::LocalSet(padded_len));
+
+    // Allocate padded buffer, 8-aligned workspace and the 40-byte result
+    func.instruction(&Instruction::GlobalGet(HEAP_PTR_GLOBAL));
+    func.instruction(&Instruction::LocalSet(padded_ptr));
+    func.instruction(&Instruction::LocalGet(padded_ptr));
+    func.instruction(&Instruction::LocalGet(padded_len));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::I32Const(7));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::I32Const(-8));
+    func.instruction(&Instruction::I32And);
+    func.instruction(&Instruction::LocalTee(ws));
+    func.instruction(&Instruction::I32Const(WS_SIZE + 40));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::GlobalGet(HEAP_LIMIT_GLOBAL));
+    func.instruction(&Instruction::I32GtU);
+    func.instruction(&Instruction::If(BlockType::Empty));
+    trap(func, RuntimeError::OutOfMemory);
+    func.instruction(&Instruction::End);
+
+    func.instruction(&Instruction::LocalGet(ws));
+    func.instruction(&Instruction::I32Const(WS_SIZE + 40));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::GlobalSet(HEAP_PTR_GLOBAL));
+
+    // Padded message: data, zeros, domain byte, final 0x80
+    func.instruction(&Instruction::LocalGet(padded_ptr));
+    func.instruction(&Instruction::LocalGet(bytes_ptr));
+    func.instruction(&Instruction::I32Const(8));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::LocalGet(data_len));
+    func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+
+    func.instruction(&Instruction::LocalGet(padded_ptr));
+    func.instruction(&Instruction::LocalGet(data_len));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::I32Const(0));
+    func.instruction(&Instruction::LocalGet(padded_len));
+    func.instruction(&Instruction::LocalGet(data_len));
+    func.instruction(&Instruction::I32Sub);
+    func.instruction(&Instruction::MemoryFill(0));
+
+    func.instruction(&Instruction::LocalGet(padded_ptr));
+    func.instruction(&Instruction::LocalGet(data_len));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::I32Const(domain));
+    func.instruction(&Instruction::I32Store8(MemArg { offset: 0, align: 0, memory_index: 0 }));
+
+    // 0x80 lands on the same byte as the domain byte when only one pad byte fits
+    func.instruction(&Instruction::LocalGet(padded_ptr));
+    func.instruction(&Instruction::LocalGet(padded_len));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::I32Const(1));
+    func.instruction(&Instruction::I32Sub);
+    func.instruction(&Instruction::LocalTee(block));
+    func.instruction(&Instruction::LocalGet(block));
+    func.instruction(&Instruction::I32Load8U(MemArg { offset: 0, align: 0, memory_index: 0 }));
+    func.instruction(&Instruction::I32Const(0x80));
+    func.instruction(&Instruction::I32Or);
+    func.instruction(&Instruction::I32Store8(MemArg { offset: 0, align: 0, memory_index: 0 }));
+
+    // Zero state, load round constants
+    func.instruction(&Instruction::LocalGet(ws));
+    func.instruction(&Instruction::I32Const(0));
+    func.instruction(&Instruction::I32Const(RC as i32));
+    func.instruction(&Instruction::MemoryFill(0));
+
+    for (i, rc) in KECCAK_RC.iter().enumerate() {
+        func.instruction(&Instruction::LocalGet(ws));
+        func.instruction(&Instruction::I64Const(*rc as i64));
+        func.instruction(&Instruction::I64Store(mem64(RC + i as u64 * 8)));
+    }
+
+    // Absorb: for each block, A[i] ^= block lane i, then permute
+    func.instruction(&Instruction::LocalGet(padded_ptr));
+    func.instruction(&Instruction::LocalSet(block));
+
+    func.instruction(&Instruction::Block(BlockType::Empty));
+    func.instruction(&Instruction::Loop(BlockType::Empty));
+
+    func.instruction(&Instruction::LocalGet(block));
+    func.instruction(&Instruction::LocalGet(padded_ptr));
+    func.instruction(&Instruction::LocalGet(padded_len));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::I32GeU);
+    func.instruction(&Instruction::BrIf(1));
+
+    for i in 0..(RATE as u64 / 8) {
+        func.instruction(&Instruction::LocalGet(ws));
+        func.instruction(&Instruction::LocalGet(ws));
+        func.instruction(&Instruction::I64Load(mem64(A + i * 8)));
+        func.instruction(&Instruction::LocalGet(block));
+        func.instruction(&Instruction::I64Load(MemArg { offset: i * 8, align: 0, memory_index: 0 }));
+        func.instruction(&Instruction::I64Xor);
+        func.instruction(&Instruction::I64Store(mem64(A + i * 8)));
+    }
+
+    // keccak-f[1600]: 24 rounds, step mappings unrolled over lanes
+    func.instruction(&Instruction::I32Const(0));
+    func.instruction(&Instruction::LocalSet(round));
+
+    func.instruction(&Instruction::Block(BlockType::Empty));
+    func.instruction(&Instruction::Loop(BlockType::Empty));
+
+    func.instruction(&Instruction::LocalGet(round));
+    func.instruction(&Instruction::I32Const(24));
+    func.instruction(&Instruction::I32GeU);
+    func.instruction(&Instruction::BrIf(1));
+
+    // Theta: C[x] = xor of column x
+    for x in 0..5 {
+        func.instruction(&Instruction::LocalGet(ws));
+        func.instruction(&Instruction::LocalGet(ws));
+        func.instruction(&Instruction::I64Load(mem64(A + lane(x, 0))));
+        for y in 1..5 {
+            func.instruction(&Instruction::LocalGet(ws));
+            func.instruction(&Instruction::I64Load(mem64(A + lane(x, y))));
+            func.instruction(&Instruction::I64Xor);
+        }
+        func.instruction(&Instruction::I64Store(mem64(C + x as u64 * 8)));
+    }
+
+    // Theta: D[x] = C[x-1] ^ rotl(C[x+1], 1)
+    for x in 0..5 {
+        func.instruction(&Instruction::LocalGet(ws));
+        func.instruction(&Instruction::LocalGet(ws));
+        func.instruction(&Instruction::I64Load(mem64(C + ((x + 4) % 5) as u64 * 8)));
+        func.instruction(&Instruction::LocalGet(ws));
+        func.instruction(&Instruction::I64Load(mem64(C + ((x + 1) % 5) as u64 * 8)));
+        func.instruction(&Instruction::I64Const(1));
+        func.instruction(&Instruction::I64Rotl);
+        func.instruction(&Instruction::I64Xor);
+        func.instruction(&Instruction::I64Store(mem64(D + x as u64 * 8)));
+    }
+
+    // Theta apply + rho + pi: B[y, 2x+3y] = rotl(A[x, y] ^ D[x], r[x][y])
+    for (x, rotations) in KECCAK_ROT.iter().enumerate() {
+        for (y, &rotation) in rotations.iter().enumerate() {
+            func.instruction(&Instruction::LocalGet(ws));
+            func.instruction(&Instruction::LocalGet(ws));
+            func.instruction(&Instruction::I64Load(mem64(A + lane(x, y))));
+            func.instruction(&Instruction::LocalGet(ws));
+            func.instruction(&Instruction::I64Load(mem64(D + x as u64 * 8)));
+            func.instruction(&Instruction::I64Xor);
+            if rotation != 0 {
+                func.instruction(&Instruction::I64Const(rotation as i64));
+                func.instruction(&Instruction::I64Rotl);
+            }
+            func.instruction(&Instruction::I64Store(mem64(B + lane(y, (2 * x + 3 * y) % 5))));
+        }
+    }
+
+    // Chi: A[x, y] = B[x, y] ^ (~B[x+1, y] & B[x+2, y])
+    for y in 0..5 {
+        for x in 0..5 {
+            func.instruction(&Instruction::LocalGet(ws));
+            func.instruction(&Instruction::LocalGet(ws));
+            func.instruction(&Instruction::I64Load(mem64(B + lane(x, y))));
+            func.instruction(&Instruction::LocalGet(ws));
+            func.instruction(&Instruction::I64Load(mem64(B + lane((x + 1) % 5, y))));
+            func.instruction(&Instruction::I64Const(-1));
+            func.instruction(&Instruction::I64Xor);
+            func.instruction(&Instruction::LocalGet(ws));
+            func.instruction(&Instruction::I64Load(mem64(B + lane((x + 2) % 5, y))));
+            func.instruction(&Instruction::I64And);
+            func.instruction(&Instruction::I64Xor);
+            func.instruction(&Instruction::I64Store(mem64(A + lane(x, y))));
+        }
+    }
+
+    // Iota: A[0, 0] ^= RC[round]
+    func.instruction(&Instruction::LocalGet(ws));
+    func.instruction(&Instruction::LocalGet(ws));
+    func.instruction(&Instruction::I64Load(mem64(A)));
+    func.instruction(&Instruction::LocalGet(ws));
+    func.instruction(&Instruction::LocalGet(round));
+    func.instruction(&Instruction::I32Const(8));
+    func.instruction(&Instruction::I32Mul);
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::I64Load(mem64(RC)));
+    func.instruction(&Instruction::I64Xor);
+    func.instruction(&Instruction::I64Store(mem64(A)));
+
+    func.instruction(&Instruction::LocalGet(round));
+    func.instruction(&Instruction::I32Const(1));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::LocalSet(round));
+    func.instruction(&Instruction::Br(0));
+    func.instruction(&Instruction::End);
+    func.instruction(&Instruction::End);
+
+    func.instruction(&Instruction::LocalGet(block));
+    func.instruction(&Instruction::I32Const(RATE));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::LocalSet(block));
+    func.instruction(&Instruction::Br(0));
+    func.instruction(&Instruction::End);
+    func.instruction(&Instruction::End);
+
+    // Squeeze: first 32 bytes of state (lanes are little-endian)
+    func.instruction(&Instruction::LocalGet(ws));
+    func.instruction(&Instruction::I32Const(WS_SIZE));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::LocalTee(block));
+    func.instruction(&Instruction::I32Const(TYPE_BYTES));
+    func.instruction(&Instruction::I32Store(MemArg { offset: 0, align: 2, memory_index: 0 }));
+
+    func.instruction(&Instruction::LocalGet(block));
+    func.instruction(&Instruction::I32Const(32));
+    func.instruction(&Instruction::I32Store(MemArg { offset: 4, align: 2, memory_index: 0 }));
+
+    func.instruction(&Instruction::LocalGet(block));
+    func.instruction(&Instruction::I32Const(8));
+    func.instruction(&Instruction::I32Add);
+    func.instruction(&Instruction::LocalGet(ws));
+    func.instruction(&Instruction::I32Const(32));
+    func.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
 
-    let _ = (K, temp1, temp2);
+    func.instruction(&Instruction::LocalGet(block));
 }
@@ -1,50 +1,199 @@
 use anyhow::{Result, bail};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 use sha2::{Sha256, Digest};
 use rustpython_parser::{self as parser, ast};
+use serde::{Deserialize, Serialize};
 
 mod ir;
 mod lowering;
 mod codegen;
 mod memory;
+mod optimize;
+mod region;
+mod limits;
+mod resources;
+mod runtime;
+mod estimate;
+mod target;
+pub mod errors;
+pub mod fuel;
+pub mod abi;
+pub mod source_map;
+pub mod determinism;
+pub mod capabilities;
 
 use ir::IR;
 use lowering::IRLowering;
 use codegen::WasmCodegen;
+pub use limits::ModuleLimits;
+pub use resources::{ResourceLimits, DEFAULT_MAX_OUTPUT_BYTES, DEFAULT_MEMORY_PAGES, MAX_PYTHON_SIZE};
+pub use estimate::{FuelEstimate, LoopEstimate, LoopKind};
+pub use source_map::{SourceLocation, SourceMap};
+pub use target::Target;
+pub use errors::RuntimeError;
 
-const MAX_PYTHON_SIZE: usize = 100 * 1024;
+/// Nested user-function calls allowed before the module traps
+pub const DEFAULT_MAX_CALL_DEPTH: u32 = 128;
+/// A failed assert writes ASSERTION_FAILED at this address and the message hash in the next
+/// word before trapping; the slot stays zero for every other trap (out of fuel, out of memory, raise)
+pub const ASSERT_SLOT: i32 = 0x100;
+pub const ASSERTION_FAILED: i32 = 0x4153_5254; // "ASRT"
+/// Traps for a Python-level failure store its RuntimeError code at this address first; the
+/// word stays zero when the module traps any other way
+pub const ERROR_SLOT: i32 = 0x108;
+/// Modules that print export a `stdout` global holding the buffer's address. The buffer is
+/// [written:u32][pad:u32] then a ring of STDOUT_CAPACITY bytes; byte n lands at n % capacity.
+pub const STDOUT_EXPORT: &str = "stdout";
+pub const STDOUT_CAPACITY: u32 = 0x7ff8;
+
+/// Which cost the optimizer favours where module size and fuel pull apart
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Optimize {
+    /// Fewer bytes, for modules near the on-chain size limit: functions are only inlined into
+    /// their single caller, elif ladders become jump tables wherever the table is smaller, and
+    /// loops aren't given hoisted values or induction variables
+    Size,
+    /// Less fuel: small functions are inlined at every call site, dense elif ladders become
+    /// jump tables, and loop-invariant work is hoisted out of loops
+    #[default]
+    Fuel,
+}
+
+impl std::str::FromStr for Optimize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "size" => Ok(Optimize::Size),
+            "fuel" => Ok(Optimize::Fuel),
+            _ => bail!("unknown optimization target '{}': expected size or fuel", s),
+        }
+    }
+}
+
+/// Per-job compiler settings; they change the module and its fuel use, never its output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompileOptions {
+    #[serde(default)]
+    pub optimize: Optimize,
+    /// Instruction set the module may use; on-chain-v1 modules can be fraud-proven today
+    #[serde(default)]
+    pub target: Target,
+}
+
+// A compiled module and its source map, shared between cache hits
+type CompiledModule = Arc<(Vec<u8>, SourceMap)>;
 
 pub struct PythonCompiler {
-    cache: HashMap<String, Arc<Vec<u8>>>,
+    // keyed by source hash and the limits the module was built for
+    cache: HashMap<(String, ResourceLimits), CompiledModule>,
+    max_call_depth: u32,
+    allowed_builtins: Option<BTreeSet<String>>,
+    options: CompileOptions,
+    limits: ResourceLimits,
 }
 
 impl PythonCompiler {
     pub fn new() -> Self {
         Self {
             cache: HashMap::with_capacity(64),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            allowed_builtins: None,
+            options: CompileOptions::default(),
+            limits: ResourceLimits::default(),
         }
     }
 
+    pub fn with_max_call_depth(mut self, depth: u32) -> Self {
+        self.max_call_depth = depth;
+        // the limit is baked into the module
+        self.cache.clear();
+        self
+    }
+
+    /// Reject calls to builtins outside `allowed` at compile time; `None` allows all
+    pub fn with_allowed_builtins(mut self, allowed: Option<BTreeSet<String>>) -> Self {
+        self.allowed_builtins = allowed;
+        self.cache.clear();
+        self
+    }
+
+    pub fn with_options(mut self, options: CompileOptions) -> Self {
+        self.options = options;
+        self.cache.clear();
+        self
+    }
+
+    /// Limits `compile` builds for unless a call names its own
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     pub fn compile(&mut self, python_code: &str) -> Result<Vec<u8>> {
-        if python_code.len() > MAX_PYTHON_SIZE {
-            bail!("Python code exceeds 100KB limit");
-        }
+        Ok(self.compile_with_source_map(python_code)?.0)
+    }
+
+    /// The module `compile` returns and the map from its code offsets to lines of `python_code`
+    pub fn compile_with_source_map(&mut self, python_code: &str) -> Result<(Vec<u8>, SourceMap)> {
+        let limits = self.limits;
+        self.compile_with_limits(python_code, &limits)
+    }
+
+    /// `compile_with_source_map` for a job with its own limits: the module's fuel counter traps
+    /// at `limits.fuel_limit` and its heap and memory import stay within `limits.memory_pages`
+    pub fn compile_with_limits(&mut self, python_code: &str, limits: &ResourceLimits) -> Result<(Vec<u8>, SourceMap)> {
+        limits.check()?;
+        limits.check_source(python_code)?;
 
         let mut hasher = Sha256::new();
         hasher.update(python_code.as_bytes());
         let code_hash = hex::encode(hasher.finalize());
 
-        if let Some(cached) = self.cache.get(&code_hash) {
+        let key = (code_hash, *limits);
+        if let Some(cached) = self.cache.get(&key) {
             return Ok((**cached).clone());
         }
 
         let py_ast = self.parse_python(python_code)?;
-        let ir = self.lower_to_ir(&py_ast)?;
-        let wasm = self.codegen_wasm(&ir)?;
+        let mut ir = self.lower_to_ir(&py_ast, python_code)?;
+        optimize::optimize(&mut ir, python_code, self.options.optimize);
+        let mut codegen = WasmCodegen::new(self.max_call_depth)
+            .with_target(self.options.target)
+            .with_resource_limits(*limits)
+            .with_source_map();
+        let mut wasm = codegen.generate(&ir)?;
+        let source_map = codegen.take_source_map();
+        let mut declared = ModuleLimits::analyze(&wasm, self.max_call_depth)?;
+        // the heap ends where the job's memory does, not at the allocator's ceiling
+        declared.memory_pages = (limits.heap_limit() as u32).div_ceil(65536);
+        declared.append_to(&mut wasm);
+        fuel::mark_metered(&mut wasm);
+        self.options.target.check(&wasm)?;
 
-        self.cache.insert(code_hash, Arc::new(wasm.clone()));
-        Ok(wasm)
+        let compiled = (wasm, source_map);
+        self.cache.insert(key, Arc::new(compiled.clone()));
+        Ok(compiled)
+    }
+
+    /// Worst-case fuel of running `python_code`, without running it, so a client can size the
+    /// fuel_limit of createJob. `input_size_hint` is the length of the largest string, list or
+    /// dict the program handles; loops inside the runtime, over such a value, are bounded by it.
+    pub fn estimate_fuel(&self, python_code: &str, input_size_hint: usize) -> Result<FuelEstimate> {
+        self.limits.check_source(python_code)?;
+        let py_ast = self.parse_python(python_code)?;
+        let mut ir = self.lower_to_ir(&py_ast, python_code)?;
+        // an empty range() is reported with no iterations rather than dropped; keeping its setup
+        // can only raise the estimate
+        optimize::optimize_for_estimate(&mut ir, python_code, self.options.optimize);
+        let mut codegen = WasmCodegen::new(self.max_call_depth)
+            .with_target(self.options.target)
+            .with_loop_records();
+        let wasm = codegen.generate_unmetered(&ir)?;
+        estimate::estimate(&wasm, &codegen.take_loops(), input_size_hint)
     }
 
     fn parse_python(&self, code: &str) -> Result<ast::Mod> {
@@ -52,13 +201,10 @@ impl PythonCompiler {
             .map_err(|e| anyhow::anyhow!("Python parse error: {}", e))
     }
 
-    fn lower_to_ir(&self, py_ast: &ast::Mod) -> Result<IR> {
-        let mut lowering = IRLowering::new();
+    fn lower_to_ir(&self, py_ast: &ast::Mod, python_code: &str) -> Result<IR> {
+        let mut lowering = IRLowering::new()
+            .with_allowed_builtins(self.allowed_builtins.clone())
+            .with_source(python_code);
         lowering.lower_module(py_ast)
     }
-
-    fn codegen_wasm(&self, ir: &IR) -> Result<Vec<u8>> {
-        let mut codegen = WasmCodegen::new();
-        codegen.generate(ir)
-    }
 }
@@ -0,0 +1,1229 @@
+// IR-to-IR rewrites run between lowering and codegen
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::ir::*;
+use super::lowering::{MAX_LOCALS, SCRATCH_LOCALS};
+use super::Optimize;
+
+// Fewer cases than this stay a comparison ladder
+const MIN_SWITCH_CASES: usize = 4;
+// Table slots allowed per case; sparser ladders keep their comparisons.
+// An empty slot is a byte while a comparison is several, so size builds accept sparser tables.
+const MAX_SWITCH_SPREAD: usize = 2;
+const MAX_SWITCH_SPREAD_FOR_SIZE: usize = 8;
+
+// Inline budget in IR nodes: anywhere, and for call sites inside loops
+const INLINE_SIZE: usize = 12;
+const INLINE_SIZE_IN_LOOP: usize = 40;
+
+const NOINLINE_PRAGMA: &str = "certus: noinline";
+
+// `i * c` needs this many uses per iteration before an induction variable pays for its update
+const MIN_INDUCTION_USES: usize = 2;
+
+// Builtins that never mutate an existing heap object
+pub(super) const NON_MUTATING_BUILTINS: &[&str] = &["len", "abs", "str", "min", "max", "sum", "sorted", "keccak256", "type", "bytes"];
+
+pub(crate) fn optimize(ir: &mut IR, source: &str, target: Optimize) {
+    optimize_with(ir, source, target, false);
+}
+
+/// `optimize`, but range() loops that never run are kept, so a fuel estimate still reports them
+pub(crate) fn optimize_for_estimate(ir: &mut IR, source: &str, target: Optimize) {
+    optimize_with(ir, source, target, true);
+}
+
+fn optimize_with(ir: &mut IR, source: &str, target: Optimize, keep_empty_loops: bool) {
+    let IR::Module { functions, .. } = ir;
+    inline_functions(functions, &noinline_functions(source), target);
+    for func in functions {
+        fold_constants(&mut func.body);
+        eliminate_dead_code(&mut func.body, keep_empty_loops);
+        drop_unused_locals(func);
+        if target == Optimize::Fuel {
+            optimize_loops(func);
+        }
+        reduce_powers_of_two(&mut func.body);
+        rewrite_block(&mut func.body, target);
+    }
+}
+
+/// Functions marked `# certus: noinline` on the def line or on the comment lines just above it
+fn noinline_functions(source: &str) -> BTreeSet<String> {
+    let is_pragma = |comment: &str| comment.trim_start_matches('#').trim() == NOINLINE_PRAGMA;
+
+    let mut names = BTreeSet::new();
+    let mut pending = false;
+    for line in source.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            pending |= is_pragma(line);
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("def ") {
+            let trailing = line.split_once('#').is_some_and(|(_, comment)| is_pragma(comment));
+            if pending || trailing {
+                let name = rest.split('(').next().unwrap_or("").trim();
+                names.insert(name.to_string());
+            }
+        }
+        pending = false;
+    }
+    names
+}
+
+fn rewrite_block(stmts: &mut [IRStmt], target: Optimize) {
+    for stmt in stmts {
+        rewrite_stmt(stmt, target);
+    }
+}
+
+fn rewrite_stmt(stmt: &mut IRStmt, target: Optimize) {
+    match stmt {
+        IRStmt::If { then_block, else_block, .. } => {
+            rewrite_block(then_block, target);
+            rewrite_block(else_block, target);
+        }
+        IRStmt::IfChain { branches, else_block } => {
+            for (_, body) in branches.iter_mut() {
+                rewrite_block(body, target);
+            }
+            rewrite_block(else_block, target);
+            if let Some(switch) = dense_switch(branches, else_block, target) {
+                *stmt = switch;
+            }
+        }
+        IRStmt::While { body, .. } | IRStmt::For { body, .. } | IRStmt::Block(body) => rewrite_block(body, target),
+        IRStmt::Switch { arms, default, .. } => {
+            for arm in arms.iter_mut() {
+                rewrite_block(arm, target);
+            }
+            rewrite_block(default, target);
+        }
+        IRStmt::Try { body, handlers, else_block, finally } => {
+            rewrite_block(body, target);
+            for (_, handler) in handlers.iter_mut() {
+                rewrite_block(handler, target);
+            }
+            rewrite_block(else_block, target);
+            rewrite_block(finally, target);
+        }
+        _ => {}
+    }
+}
+
+/// `x == k` or `k == x` with a local and an integer literal
+fn case_of(cond: &IRExpr) -> Option<(&str, i32)> {
+    let IRExpr::BinOp { op: BinOp::Eq, left, right } = cond else {
+        return None;
+    };
+    match (&**left, &**right) {
+        (IRExpr::LoadLocal(var), key) | (key, IRExpr::LoadLocal(var)) => Some((var, key.const_value()?)),
+        _ => None,
+    }
+}
+
+/// if x == 0: ... elif x == 1: ... over a dense key range becomes a br_table dispatch
+fn dense_switch(branches: &mut Vec<(IRExpr, Vec<IRStmt>)>, else_block: &mut Vec<IRStmt>, target: Optimize) -> Option<IRStmt> {
+    if branches.len() < MIN_SWITCH_CASES {
+        return None;
+    }
+
+    let (var, _) = case_of(&branches[0].0)?;
+    let mut keys = Vec::with_capacity(branches.len());
+    for (cond, _) in branches.iter() {
+        match case_of(cond) {
+            Some((v, k)) if v == var => keys.push(k),
+            _ => return None,
+        }
+    }
+
+    let low = *keys.iter().min()?;
+    let high = *keys.iter().max()?;
+    let span = (high as i64 - low as i64 + 1) as usize;
+    let spread = match target {
+        Optimize::Size => MAX_SWITCH_SPREAD_FOR_SIZE,
+        Optimize::Fuel => MAX_SWITCH_SPREAD,
+    };
+    if span > branches.len() * spread {
+        return None;
+    }
+
+    // A repeated key can never be reached past its first branch
+    let mut targets = vec![None; span];
+    for (arm, &key) in keys.iter().enumerate() {
+        let slot = &mut targets[(key - low) as usize];
+        if slot.is_none() {
+            *slot = Some(arm);
+        }
+    }
+
+    let var = var.to_string();
+    let arms = branches.drain(..).map(|(_, body)| body).collect();
+    Some(IRStmt::Switch {
+        value: IRExpr::LoadLocal(var),
+        low,
+        targets,
+        arms,
+        default: std::mem::take(else_block),
+    })
+}
+
+// A straight-line function `def f(a, b): t = ...; return expr` that can be spliced into callers
+struct Inlinable {
+    params: Vec<String>,
+    locals: Vec<String>,
+    assigned: BTreeSet<String>,
+    body: Vec<(String, IRExpr)>,
+    result: IRExpr,
+    size: usize,
+}
+
+fn inline_functions(functions: &mut Vec<IRFunction>, noinline: &BTreeSet<String>, target: Optimize) {
+    let user_functions: BTreeSet<String> = functions.iter().map(|f| f.name.clone()).collect();
+    // Inlining a function's only call lets it be dropped; any other copy grows the module
+    let mut call_sites = BTreeMap::new();
+    if target == Optimize::Size {
+        for func in functions.iter() {
+            for stmt in &func.body {
+                for_each_stmt_expr(stmt, &mut |expr| count_calls(expr, &mut call_sites));
+            }
+        }
+    }
+    let candidates: BTreeMap<String, Inlinable> = functions.iter()
+        .filter(|f| f.name != "main" && !noinline.contains(&f.name))
+        .filter(|f| target == Optimize::Fuel || call_sites.get(&f.name) == Some(&1))
+        .filter_map(|f| Some((f.name.clone(), inlinable(f, &user_functions)?)))
+        .collect();
+    if candidates.is_empty() {
+        return;
+    }
+
+    let mut inliner = Inliner { candidates: &candidates, target, next_site: 0, inlined: BTreeSet::new(), fresh: Vec::new(), budget: 0 };
+    for func in functions.iter_mut() {
+        if candidates.contains_key(&func.name) {
+            continue;
+        }
+        inliner.budget = (MAX_LOCALS - SCRATCH_LOCALS as usize).saturating_sub(func.locals.len());
+        inliner.stmts(&mut func.body, false);
+        for name in inliner.fresh.drain(..) {
+            func.local_map.insert(name.clone(), func.locals.len() as u32);
+            func.locals.push(name);
+        }
+    }
+
+    // Drop functions whose every call site was inlined
+    let mut called = BTreeSet::new();
+    for func in functions.iter() {
+        for stmt in &func.body {
+            collect_calls_stmt(stmt, &mut called);
+        }
+    }
+    let inlined = inliner.inlined;
+    functions.retain(|f| f.name == "main" || !inlined.contains(&f.name) || called.contains(&f.name));
+}
+
+struct Inliner<'a> {
+    candidates: &'a BTreeMap<String, Inlinable>,
+    target: Optimize,
+    next_site: usize,
+    inlined: BTreeSet<String>,
+    // Locals created in the current function, and how many more it can take
+    fresh: Vec<String>,
+    budget: usize,
+}
+
+impl Inliner<'_> {
+    fn stmts(&mut self, stmts: &mut [IRStmt], in_loop: bool) {
+        for stmt in stmts {
+            self.stmt(stmt, in_loop);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut IRStmt, in_loop: bool) {
+        match stmt {
+            IRStmt::Assign { value, .. } | IRStmt::AssignGlobal { value, .. } | IRStmt::Return(value) | IRStmt::Expr(value) => self.expr(value, in_loop),
+            IRStmt::SubscriptAssign { target, index, value } => {
+                self.expr(target, in_loop);
+                self.expr(index, in_loop);
+                self.expr(value, in_loop);
+            }
+            IRStmt::If { cond, then_block, else_block } => {
+                self.expr(cond, in_loop);
+                self.stmts(then_block, in_loop);
+                self.stmts(else_block, in_loop);
+            }
+            IRStmt::IfChain { branches, else_block } => {
+                for (cond, body) in branches {
+                    self.expr(cond, in_loop);
+                    self.stmts(body, in_loop);
+                }
+                self.stmts(else_block, in_loop);
+            }
+            IRStmt::Switch { value, arms, default, .. } => {
+                self.expr(value, in_loop);
+                for arm in arms {
+                    self.stmts(arm, in_loop);
+                }
+                self.stmts(default, in_loop);
+            }
+            IRStmt::While { cond, body } => {
+                self.expr(cond, true);
+                self.stmts(body, true);
+            }
+            IRStmt::For { start, stop, step, body, .. } => {
+                // range() arguments are evaluated once
+                self.expr(start, in_loop);
+                self.expr(stop, in_loop);
+                self.expr(step, in_loop);
+                self.stmts(body, true);
+            }
+            IRStmt::Block(body) => self.stmts(body, in_loop),
+            IRStmt::Try { body, handlers, else_block, finally } => {
+                self.stmts(body, in_loop);
+                for (_, handler) in handlers {
+                    self.stmts(handler, in_loop);
+                }
+                self.stmts(else_block, in_loop);
+                self.stmts(finally, in_loop);
+            }
+            IRStmt::Break | IRStmt::Continue | IRStmt::Raise(_) | IRStmt::AssertFail { .. } | IRStmt::Line(_) => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &mut IRExpr, in_loop: bool) {
+        if let IRExpr::Block { stmts, .. } = expr {
+            self.stmts(stmts, in_loop);
+        }
+        for child in expr.children_mut() {
+            self.expr(child, in_loop);
+        }
+
+        let IRExpr::Call { func, args } = expr else {
+            return;
+        };
+        let Some(callee) = self.candidates.get(func.as_str()) else {
+            return;
+        };
+        let limit = if in_loop || self.target == Optimize::Size { INLINE_SIZE_IN_LOOP } else { INLINE_SIZE };
+        if callee.size > limit || args.len() != callee.params.len() {
+            return;
+        }
+
+        let name = func.clone();
+        if let Some(inlined) = self.splice(callee, args) {
+            *expr = inlined;
+            self.inlined.insert(name);
+        }
+    }
+
+    /// Callee body with params bound to args and its locals renamed into the caller
+    fn splice(&mut self, callee: &Inlinable, args: &mut [IRExpr]) -> Option<IRExpr> {
+        // Literal and local args are substituted directly unless the callee reassigns the param
+        let substituted: Vec<bool> = callee.params.iter().zip(args.iter())
+            .map(|(param, arg)| !callee.assigned.contains(param) && (arg.const_value().is_some() || matches!(arg, IRExpr::LoadLocal(_))))
+            .collect();
+        let needed = callee.locals.len() - substituted.iter().filter(|&&s| s).count();
+        if self.fresh.len() + needed > self.budget {
+            return None;
+        }
+
+        let site = self.next_site;
+        self.next_site += 1;
+
+        let mut subst = HashMap::new();
+        for ((param, arg), &direct) in callee.params.iter().zip(args.iter()).zip(&substituted) {
+            if direct {
+                subst.insert(param.clone(), arg.clone());
+            }
+        }
+        for local in &callee.locals {
+            if !subst.contains_key(local) {
+                let name = format!("__inline{}_{}", site, local);
+                self.fresh.push(name.clone());
+                subst.insert(local.clone(), IRExpr::LoadLocal(name));
+            }
+        }
+
+        let rename = |var: &str| match &subst[var] {
+            IRExpr::LoadLocal(name) => name.clone(),
+            _ => unreachable!("assigned locals are never substituted"),
+        };
+
+        // Args are evaluated in order before the body, as for a call
+        let mut stmts = Vec::new();
+        for ((param, arg), &direct) in callee.params.iter().zip(args.iter_mut()).zip(&substituted) {
+            if !direct {
+                stmts.push(IRStmt::Assign { var: rename(param), value: std::mem::replace(arg, IRExpr::Const(0)) });
+            }
+        }
+        for (var, value) in &callee.body {
+            stmts.push(IRStmt::Assign { var: rename(var), value: substitute(value, &subst) });
+        }
+
+        let result = Box::new(substitute(&callee.result, &subst));
+        Some(if stmts.is_empty() { *result } else { IRExpr::Block { stmts, result } })
+    }
+}
+
+/// Copy of `expr` with callee locals replaced by caller expressions
+fn substitute(expr: &IRExpr, subst: &HashMap<String, IRExpr>) -> IRExpr {
+    let mut expr = expr.clone();
+    substitute_in_place(&mut expr, subst);
+    expr
+}
+
+fn substitute_in_place(expr: &mut IRExpr, subst: &HashMap<String, IRExpr>) {
+    match expr {
+        IRExpr::LoadLocal(var) => {
+            if let Some(replacement) = subst.get(var.as_str()) {
+                *expr = replacement.clone();
+            }
+            return;
+        }
+        IRExpr::AssignExpr { var, .. } => {
+            if let Some(IRExpr::LoadLocal(name)) = subst.get(var.as_str()) {
+                *var = name.clone();
+            }
+        }
+        _ => {}
+    }
+    for child in expr.children_mut() {
+        substitute_in_place(child, subst);
+    }
+}
+
+fn inlinable(func: &IRFunction, user_functions: &BTreeSet<String>) -> Option<Inlinable> {
+    // line markers don't survive inlining: the code takes the call site's line
+    let stmts: Vec<&IRStmt> = func.body.iter().filter(|s| !matches!(s, IRStmt::Line(_))).collect();
+    let (IRStmt::Return(result), assigns) = stmts.split_last()? else {
+        return None;
+    };
+
+    let mut body = Vec::new();
+    let mut size = inline_size(result, user_functions)?;
+    for &stmt in assigns {
+        let IRStmt::Assign { var, value } = stmt else {
+            return None;
+        };
+        size += 1 + inline_size(value, user_functions)?;
+        body.push((var.clone(), value.clone()));
+    }
+    if size > INLINE_SIZE_IN_LOOP {
+        return None;
+    }
+
+    // A local read before any assignment would see a stale value from an earlier inlined call
+    let mut defined: BTreeSet<String> = func._params.iter().cloned().collect();
+    for (var, value) in &body {
+        if !reads_defined(value, &mut defined) {
+            return None;
+        }
+        defined.insert(var.clone());
+    }
+    if !reads_defined(result, &mut defined) {
+        return None;
+    }
+
+    Some(Inlinable {
+        params: func._params.clone(),
+        locals: func.locals.clone(),
+        assigned: body.iter().map(|(var, _)| var.clone()).collect(),
+        body,
+        result: result.clone(),
+        size,
+    })
+}
+
+/// IR node count, or None for expressions that can't be inlined (user calls, nested statements)
+fn inline_size(expr: &IRExpr, user_functions: &BTreeSet<String>) -> Option<usize> {
+    match expr {
+        IRExpr::Call { func, .. } if user_functions.contains(func) => return None,
+        IRExpr::Block { .. } => return None,
+        _ => {}
+    }
+    expr.children().into_iter().try_fold(1, |size, child| Some(size + inline_size(child, user_functions)?))
+}
+
+/// Whether every local read is defined; compiler temps (AssignExpr) define their variable
+fn reads_defined(expr: &IRExpr, defined: &mut BTreeSet<String>) -> bool {
+    match expr {
+        IRExpr::LoadLocal(var) => defined.contains(var),
+        IRExpr::AssignExpr { var, value } => {
+            let ok = reads_defined(value, defined);
+            defined.insert(var.clone());
+            ok
+        }
+        _ => expr.children().into_iter().all(|child| reads_defined(child, defined)),
+    }
+}
+
+fn count_calls(expr: &IRExpr, counts: &mut BTreeMap<String, usize>) {
+    match expr {
+        IRExpr::Call { func, .. } => *counts.entry(func.clone()).or_default() += 1,
+        IRExpr::Block { stmts, .. } => {
+            for stmt in stmts {
+                for_each_stmt_expr(stmt, &mut |expr| count_calls(expr, counts));
+            }
+        }
+        _ => {}
+    }
+    for child in expr.children() {
+        count_calls(child, counts);
+    }
+}
+
+pub(super) fn collect_calls_stmt(stmt: &IRStmt, called: &mut BTreeSet<String>) {
+    for_each_stmt_expr(stmt, &mut |expr| collect_calls(expr, called));
+}
+
+fn collect_calls(expr: &IRExpr, called: &mut BTreeSet<String>) {
+    match expr {
+        IRExpr::Call { func, .. } => {
+            called.insert(func.clone());
+        }
+        IRExpr::Block { stmts, .. } => {
+            for stmt in stmts {
+                collect_calls_stmt(stmt, called);
+            }
+        }
+        _ => {}
+    }
+    for child in expr.children() {
+        collect_calls(child, called);
+    }
+}
+
+pub(super) fn for_each_stmt_expr(stmt: &IRStmt, f: &mut dyn FnMut(&IRExpr)) {
+    match stmt {
+        IRStmt::Assign { value, .. } | IRStmt::AssignGlobal { value, .. } | IRStmt::Return(value) | IRStmt::Expr(value) => f(value),
+        IRStmt::SubscriptAssign { target, index, value } => {
+            f(target);
+            f(index);
+            f(value);
+        }
+        IRStmt::If { cond, then_block, else_block } => {
+            f(cond);
+            then_block.iter().for_each(|s| for_each_stmt_expr(s, f));
+            else_block.iter().for_each(|s| for_each_stmt_expr(s, f));
+        }
+        IRStmt::IfChain { branches, else_block } => {
+            for (cond, body) in branches {
+                f(cond);
+                body.iter().for_each(|s| for_each_stmt_expr(s, f));
+            }
+            else_block.iter().for_each(|s| for_each_stmt_expr(s, f));
+        }
+        IRStmt::Switch { value, arms, default, .. } => {
+            f(value);
+            arms.iter().flatten().for_each(|s| for_each_stmt_expr(s, f));
+            default.iter().for_each(|s| for_each_stmt_expr(s, f));
+        }
+        IRStmt::While { cond, body } => {
+            f(cond);
+            body.iter().for_each(|s| for_each_stmt_expr(s, f));
+        }
+        IRStmt::For { start, stop, step, body, .. } => {
+            f(start);
+            f(stop);
+            f(step);
+            body.iter().for_each(|s| for_each_stmt_expr(s, f));
+        }
+        IRStmt::Block(body) => body.iter().for_each(|s| for_each_stmt_expr(s, f)),
+        IRStmt::Try { body, handlers, else_block, finally } => {
+            body.iter()
+                .chain(handlers.iter().flat_map(|(_, h)| h))
+                .chain(else_block)
+                .chain(finally)
+                .for_each(|s| for_each_stmt_expr(s, f));
+        }
+        IRStmt::Break | IRStmt::Continue | IRStmt::Raise(_) | IRStmt::AssertFail { .. } | IRStmt::Line(_) => {}
+    }
+}
+
+fn for_each_stmt_expr_mut(stmt: &mut IRStmt, f: &mut dyn FnMut(&mut IRExpr)) {
+    match stmt {
+        IRStmt::Assign { value, .. } | IRStmt::AssignGlobal { value, .. } | IRStmt::Return(value) | IRStmt::Expr(value) => f(value),
+        IRStmt::SubscriptAssign { target, index, value } => {
+            f(target);
+            f(index);
+            f(value);
+        }
+        IRStmt::If { cond, then_block, else_block } => {
+            f(cond);
+            then_block.iter_mut().for_each(|s| for_each_stmt_expr_mut(s, f));
+            else_block.iter_mut().for_each(|s| for_each_stmt_expr_mut(s, f));
+        }
+        IRStmt::IfChain { branches, else_block } => {
+            for (cond, body) in branches {
+                f(cond);
+                body.iter_mut().for_each(|s| for_each_stmt_expr_mut(s, f));
+            }
+            else_block.iter_mut().for_each(|s| for_each_stmt_expr_mut(s, f));
+        }
+        IRStmt::Switch { value, arms, default, .. } => {
+            f(value);
+            arms.iter_mut().flatten().for_each(|s| for_each_stmt_expr_mut(s, f));
+            default.iter_mut().for_each(|s| for_each_stmt_expr_mut(s, f));
+        }
+        IRStmt::While { cond, body } => {
+            f(cond);
+            body.iter_mut().for_each(|s| for_each_stmt_expr_mut(s, f));
+        }
+        IRStmt::For { start, stop, step, body, .. } => {
+            f(start);
+            f(stop);
+            f(step);
+            body.iter_mut().for_each(|s| for_each_stmt_expr_mut(s, f));
+        }
+        IRStmt::Block(body) => body.iter_mut().for_each(|s| for_each_stmt_expr_mut(s, f)),
+        IRStmt::Try { body, handlers, else_block, finally } => {
+            body.iter_mut()
+                .chain(handlers.iter_mut().flat_map(|(_, h)| h))
+                .chain(else_block)
+                .chain(finally)
+                .for_each(|s| for_each_stmt_expr_mut(s, f));
+        }
+        IRStmt::Break | IRStmt::Continue | IRStmt::Raise(_) | IRStmt::AssertFail { .. } | IRStmt::Line(_) => {}
+    }
+}
+
+/// Post-order visit of every expression, including statements nested in Block expressions
+pub(super) fn visit_exprs_mut(stmts: &mut [IRStmt], f: &mut dyn FnMut(&mut IRExpr)) {
+    for stmt in stmts {
+        for_each_stmt_expr_mut(stmt, &mut |expr| visit_expr_mut(expr, f));
+    }
+}
+
+fn visit_expr_mut(expr: &mut IRExpr, f: &mut dyn FnMut(&mut IRExpr)) {
+    if let IRExpr::Block { stmts, .. } = expr {
+        visit_exprs_mut(stmts, f);
+    }
+    for child in expr.children_mut() {
+        visit_expr_mut(child, f);
+    }
+    f(expr);
+}
+
+/// Integer BinOps and unary ops on literals evaluated as codegen would, and/or and conditional
+/// expressions with a literal deciding operand reduced to the operand they yield. Operations
+/// that trap or raise on their literals (division by zero, i32::MIN // -1, negative powers)
+/// are left for run time.
+fn fold_constants(stmts: &mut [IRStmt]) {
+    visit_exprs_mut(stmts, &mut |expr| {
+        let folded = match expr {
+            IRExpr::BinOp { op, left, right } => match (left.const_value(), right.const_value()) {
+                (Some(a), Some(b)) => fold_binop(op, a, b).map(IRExpr::Const),
+                _ => None,
+            },
+            IRExpr::UnaryOp { op, operand } => match (&*op, &**operand) {
+                (UnaryOp::Neg, IRExpr::Const(c)) => Some(IRExpr::Const(c.wrapping_neg())),
+                (UnaryOp::Not, operand) => operand.const_value().map(|c| IRExpr::Const((c == 0) as i32)),
+                _ => None,
+            },
+            IRExpr::BoolOp { op, left, right } => match (&*op, left.const_value()) {
+                (BoolOp::And, Some(0)) | (BoolOp::Or, Some(1..) | Some(..=-1)) => Some(take(left)),
+                (_, Some(_)) => Some(take(right)),
+                _ => None,
+            },
+            IRExpr::IfExpr { cond, then_val, else_val } => match cond.const_value() {
+                Some(0) => Some(take(else_val)),
+                Some(_) => Some(take(then_val)),
+                None => None,
+            },
+            _ => None,
+        };
+        if let Some(folded) = folded {
+            *expr = folded;
+        }
+    });
+}
+
+fn take(expr: &mut IRExpr) -> IRExpr {
+    std::mem::replace(expr, IRExpr::Const(0))
+}
+
+/// `a op b` with i32 wrapping and Python's floor semantics, None where it must trap or raise
+fn fold_binop(op: &BinOp, a: i32, b: i32) -> Option<i32> {
+    Some(match op {
+        BinOp::Add => a.wrapping_add(b),
+        BinOp::Sub => a.wrapping_sub(b),
+        BinOp::Mul => a.wrapping_mul(b),
+        BinOp::Div => a.checked_div(b)?,
+        BinOp::FloorDiv => {
+            let q = a.checked_div(b)?;
+            let r = a % b;
+            if r != 0 && (r ^ b) < 0 { q - 1 } else { q }
+        }
+        BinOp::Mod => {
+            let r = a.checked_rem(b).or((b == -1).then_some(0))?;
+            if r != 0 && (r ^ b) < 0 { r.wrapping_add(b) } else { r }
+        }
+        BinOp::Pow => a.wrapping_pow(u32::try_from(b).ok()?),
+        BinOp::Eq => (a == b) as i32,
+        BinOp::Ne => (a != b) as i32,
+        BinOp::Lt => (a < b) as i32,
+        BinOp::Le => (a <= b) as i32,
+        BinOp::Gt => (a > b) as i32,
+        BinOp::Ge => (a >= b) as i32,
+        BinOp::Shl => a.wrapping_shl(b as u32),
+        BinOp::ShrS => a.wrapping_shr(b as u32),
+        BinOp::BitAnd => a & b,
+        BinOp::In => return None,
+    })
+}
+
+/// Branches under a literal condition replaced by the branch that runs, loops that never run
+/// dropped unless `keep_empty_loops` and they're range() loops, and statements after a return,
+/// break, raise or failed assert removed
+fn eliminate_dead_code(stmts: &mut Vec<IRStmt>, keep_empty_loops: bool) {
+    for_each_block_mut(stmts, &mut |stmts| {
+        let mut live = Vec::with_capacity(stmts.len());
+        for stmt in stmts.drain(..) {
+            if keep_empty_loops && matches!(stmt, IRStmt::For { .. }) {
+                live.push(stmt);
+                continue;
+            }
+            match taken_branch(stmt) {
+                Ok(branch) => live.extend(branch),
+                Err(stmt) => live.push(*stmt),
+            }
+            if live.last().is_some_and(|s| matches!(s, IRStmt::Return(_) | IRStmt::Break | IRStmt::Continue | IRStmt::Raise(_) | IRStmt::AssertFail { .. })) {
+                break;
+            }
+        }
+        *stmts = live;
+    });
+}
+
+/// The statements that run in place of `stmt` when its control flow is decided by literals
+fn taken_branch(stmt: IRStmt) -> Result<Vec<IRStmt>, Box<IRStmt>> {
+    match stmt {
+        IRStmt::If { cond, then_block, else_block } => match cond.const_value() {
+            Some(0) => Ok(else_block),
+            Some(_) => Ok(then_block),
+            None => Err(Box::new(IRStmt::If { cond, then_block, else_block })),
+        },
+        IRStmt::IfChain { branches, mut else_block } => {
+            let mut live = Vec::with_capacity(branches.len());
+            for (cond, body) in branches {
+                match cond.const_value() {
+                    Some(0) => {}
+                    // a branch that always runs ends the chain
+                    Some(_) => {
+                        else_block = body;
+                        break;
+                    }
+                    None => live.push((cond, body)),
+                }
+            }
+            if live.is_empty() {
+                Ok(else_block)
+            } else {
+                Err(Box::new(IRStmt::IfChain { branches: live, else_block }))
+            }
+        }
+        IRStmt::While { cond, .. } if cond.const_value() == Some(0) => Ok(vec![]),
+        IRStmt::For { start, stop, step, .. } if matches!(
+            (start.const_value(), stop.const_value(), step.const_value()),
+            (Some(start), Some(stop), Some(step)) if (step > 0 && start >= stop) || (step < 0 && start <= stop)
+        ) => Ok(vec![]),
+        IRStmt::Switch { value, low, targets, mut arms, default } => match value.const_value() {
+            Some(v) => {
+                let arm = usize::try_from(v as i64 - low as i64).ok()
+                    .and_then(|slot| targets.get(slot).copied().flatten());
+                Ok(match arm {
+                    Some(arm) => std::mem::take(&mut arms[arm]),
+                    None => default,
+                })
+            }
+            None => Err(Box::new(IRStmt::Switch { value, low, targets, arms, default })),
+        },
+        stmt => Err(Box::new(stmt)),
+    }
+}
+
+/// Remove assignments to locals nothing reads when the assigned value has no effect, then the
+/// locals themselves. main keeps OUTPUT, which codegen returns.
+fn drop_unused_locals(func: &mut IRFunction) {
+    let keep_output = func.name == "main";
+    loop {
+        let mut read = BTreeSet::new();
+        visit_exprs_mut(&mut func.body, &mut |expr| {
+            if let IRExpr::LoadLocal(var) = expr {
+                read.insert(var.clone());
+            }
+        });
+        let unused = |var: &String| !(read.contains(var) || keep_output && var == "OUTPUT");
+
+        let mut changed = false;
+        for_each_block_mut(&mut func.body, &mut |stmts| {
+            let before = stmts.len();
+            stmts.retain(|stmt| !matches!(stmt, IRStmt::Assign { var, value } if unused(var) && discardable(value)));
+            changed |= stmts.len() != before;
+        });
+        visit_exprs_mut(&mut func.body, &mut |expr| {
+            if let IRExpr::AssignExpr { var, value } = expr {
+                if unused(var) {
+                    *expr = take(value);
+                    changed = true;
+                }
+            }
+        });
+        if !changed {
+            break;
+        }
+    }
+
+    let mut mentioned = BTreeSet::new();
+    visit_exprs_mut(&mut func.body, &mut |expr| {
+        if let IRExpr::LoadLocal(var) | IRExpr::AssignExpr { var, .. } = expr {
+            mentioned.insert(var.clone());
+        }
+    });
+    for_each_block_mut(&mut func.body, &mut |stmts| {
+        for stmt in stmts.iter() {
+            if let IRStmt::Assign { var, .. } | IRStmt::For { var, .. } = stmt {
+                mentioned.insert(var.clone());
+            }
+        }
+    });
+
+    let params = func._params.len();
+    let before = func.locals.len();
+    let mut index = 0;
+    func.locals.retain(|name| {
+        index += 1;
+        index <= params || mentioned.contains(name) || (keep_output && name == "OUTPUT")
+    });
+    if func.locals.len() != before {
+        func.local_map = func.locals.iter().enumerate().map(|(i, name)| (name.clone(), i as u32)).collect();
+    }
+}
+
+/// Whether evaluating `expr` only computes a value: no calls, allocation, stores or traps
+fn discardable(expr: &IRExpr) -> bool {
+    match expr {
+        IRExpr::Const(_) | IRExpr::LoadLocal(_) | IRExpr::LoadGlobal(_) => true,
+        IRExpr::UnaryOp { operand, .. } => discardable(operand),
+        IRExpr::BinOp { op: BinOp::Add | BinOp::Eq | BinOp::In, .. } => false,
+        IRExpr::BinOp { left, right, .. } => !may_trap(expr) && discardable(left) && discardable(right),
+        IRExpr::BoolOp { left, right, .. } => discardable(left) && discardable(right),
+        IRExpr::IfExpr { cond, then_val, else_val } => discardable(cond) && discardable(then_val) && discardable(else_val),
+        _ => false,
+    }
+}
+
+/// Post-order visit of every statement list: function bodies, nested blocks and the statements
+/// of Block expressions
+fn for_each_block_mut(stmts: &mut Vec<IRStmt>, f: &mut dyn FnMut(&mut Vec<IRStmt>)) {
+    for stmt in stmts.iter_mut() {
+        match stmt {
+            IRStmt::If { then_block, else_block, .. } => {
+                for_each_block_mut(then_block, f);
+                for_each_block_mut(else_block, f);
+            }
+            IRStmt::IfChain { branches, else_block } => {
+                for (_, body) in branches.iter_mut() {
+                    for_each_block_mut(body, f);
+                }
+                for_each_block_mut(else_block, f);
+            }
+            IRStmt::Switch { arms, default, .. } => {
+                for arm in arms.iter_mut() {
+                    for_each_block_mut(arm, f);
+                }
+                for_each_block_mut(default, f);
+            }
+            IRStmt::While { body, .. } | IRStmt::For { body, .. } | IRStmt::Block(body) => for_each_block_mut(body, f),
+            IRStmt::Try { body, handlers, else_block, finally } => {
+                for_each_block_mut(body, f);
+                for (_, handler) in handlers.iter_mut() {
+                    for_each_block_mut(handler, f);
+                }
+                for_each_block_mut(else_block, f);
+                for_each_block_mut(finally, f);
+            }
+            _ => {}
+        }
+        // only the statement's own expressions: nested statements were visited above
+        match stmt {
+            IRStmt::Assign { value, .. } | IRStmt::AssignGlobal { value, .. } | IRStmt::Return(value) | IRStmt::Expr(value) => {
+                expr_blocks_mut(value, f);
+            }
+            IRStmt::SubscriptAssign { target, index, value } => {
+                for expr in [target, index, value] {
+                    expr_blocks_mut(expr, f);
+                }
+            }
+            IRStmt::If { cond, .. } | IRStmt::While { cond, .. } => expr_blocks_mut(cond, f),
+            IRStmt::IfChain { branches, .. } => {
+                for (cond, _) in branches.iter_mut() {
+                    expr_blocks_mut(cond, f);
+                }
+            }
+            IRStmt::Switch { value, .. } => expr_blocks_mut(value, f),
+            IRStmt::For { start, stop, step, .. } => {
+                for expr in [start, stop, step] {
+                    expr_blocks_mut(expr, f);
+                }
+            }
+            _ => {}
+        }
+    }
+    f(stmts);
+}
+
+fn expr_blocks_mut(expr: &mut IRExpr, f: &mut dyn FnMut(&mut Vec<IRStmt>)) {
+    if let IRExpr::Block { stmts, .. } = expr {
+        for_each_block_mut(stmts, f);
+    }
+    for child in expr.children_mut() {
+        expr_blocks_mut(child, f);
+    }
+}
+
+/// x * 2^k, x // 2^k and x % 2^k as shl, arithmetic shr and mask; exact for Python's floor semantics
+fn reduce_powers_of_two(stmts: &mut [IRStmt]) {
+    visit_exprs_mut(stmts, &mut |expr| {
+        let IRExpr::BinOp { op, left, right } = expr else {
+            return;
+        };
+        if let (BinOp::Mul, Some(c), None) = (&*op, left.const_value(), right.const_value()) {
+            if c > 1 && (c as u32).is_power_of_two() {
+                std::mem::swap(left, right);
+            }
+        }
+        let Some(c) = right.const_value().filter(|&c| c > 1 && (c as u32).is_power_of_two()) else {
+            return;
+        };
+        let (new_op, operand) = match op {
+            BinOp::Mul => (BinOp::Shl, c.trailing_zeros() as i32),
+            BinOp::FloorDiv => (BinOp::ShrS, c.trailing_zeros() as i32),
+            BinOp::Mod => (BinOp::BitAnd, c - 1),
+            _ => return,
+        };
+        *op = new_op;
+        **right = IRExpr::Const(operand);
+    });
+}
+
+// What a loop may change: locals it assigns and whether it may write to heap objects
+#[derive(Default)]
+struct LoopEffects {
+    assigned: BTreeSet<String>,
+    writes_heap: bool,
+}
+
+impl LoopEffects {
+    fn of(stmts: &[IRStmt]) -> Self {
+        let mut effects = Self::default();
+        for stmt in stmts {
+            effects.stmt(stmt);
+            for_each_stmt_expr(stmt, &mut |expr| effects.expr(expr));
+        }
+        effects
+    }
+
+    // Statement-level writes; expressions are visited separately through for_each_stmt_expr
+    fn stmt(&mut self, stmt: &IRStmt) {
+        match stmt {
+            IRStmt::Assign { var, .. } | IRStmt::For { var, .. } => {
+                self.assigned.insert(var.clone());
+            }
+            IRStmt::SubscriptAssign { .. } => self.writes_heap = true,
+            _ => {}
+        }
+        match stmt {
+            IRStmt::If { then_block, else_block, .. } => then_block.iter().chain(else_block).for_each(|s| self.stmt(s)),
+            IRStmt::IfChain { branches, else_block } => {
+                branches.iter().flat_map(|(_, b)| b).chain(else_block).for_each(|s| self.stmt(s))
+            }
+            IRStmt::Switch { arms, default, .. } => arms.iter().flatten().chain(default).for_each(|s| self.stmt(s)),
+            IRStmt::While { body, .. } | IRStmt::For { body, .. } | IRStmt::Block(body) => body.iter().for_each(|s| self.stmt(s)),
+            IRStmt::Try { body, handlers, else_block, finally } => body.iter()
+                .chain(handlers.iter().flat_map(|(_, h)| h))
+                .chain(else_block)
+                .chain(finally)
+                .for_each(|s| self.stmt(s)),
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &IRExpr) {
+        match expr {
+            IRExpr::AssignExpr { var, .. } => {
+                self.assigned.insert(var.clone());
+            }
+            IRExpr::MethodCall { .. } => self.writes_heap = true,
+            IRExpr::Call { func, .. } if !NON_MUTATING_BUILTINS.contains(&func.as_str()) => self.writes_heap = true,
+            IRExpr::Block { stmts, .. } => {
+                for stmt in stmts {
+                    self.stmt(stmt);
+                    for_each_stmt_expr(stmt, &mut |expr| self.expr(expr));
+                }
+            }
+            _ => {}
+        }
+        for child in expr.children() {
+            self.expr(child);
+        }
+    }
+
+    /// Same value on every iteration; heap reads count only if nothing in the loop writes the heap
+    fn invariant(&self, expr: &IRExpr) -> bool {
+        match expr {
+            IRExpr::Const(_) => true,
+            IRExpr::LoadLocal(var) => !self.assigned.contains(var),
+            IRExpr::BinOp { op, left, right } => {
+                let reads_heap = matches!(op, BinOp::Add | BinOp::Eq | BinOp::In);
+                (!reads_heap || !self.writes_heap) && self.invariant(left) && self.invariant(right)
+            }
+            IRExpr::UnaryOp { operand, .. } => self.invariant(operand),
+            IRExpr::Call { func, args } if func == "len" || func == "abs" => {
+                (func == "abs" || !self.writes_heap) && args.iter().all(|a| self.invariant(a))
+            }
+            IRExpr::Subscript { value, index } => !self.writes_heap && self.invariant(value) && self.invariant(index),
+            _ => false,
+        }
+    }
+}
+
+/// Whether evaluating `expr` can trap; such expressions only move where they'd run anyway
+fn may_trap(expr: &IRExpr) -> bool {
+    match expr {
+        IRExpr::BinOp { op: BinOp::Div | BinOp::FloorDiv | BinOp::Mod, right, .. } if !matches!(right.const_value(), Some(c) if c != 0 && c != -1) => true,
+        IRExpr::BinOp { op: BinOp::Pow, right, .. } if !matches!(right.const_value(), Some(c) if c >= 0) => true,
+        // string dispatch dereferences large operands; len(), subscripts and iterables check their operand
+        IRExpr::BinOp { op: BinOp::Add | BinOp::Eq | BinOp::In, .. } | IRExpr::Call { .. } | IRExpr::Subscript { .. } | IRExpr::Sequence(_) => {
+            !matches!(expr, IRExpr::Call { func, .. } if func == "abs")
+        }
+        _ => expr.children().into_iter().any(may_trap),
+    }
+}
+
+fn optimize_loops(func: &mut IRFunction) {
+    let mut optimizer = LoopOptimizer {
+        fresh: Vec::new(),
+        budget: (MAX_LOCALS - SCRATCH_LOCALS as usize).saturating_sub(func.locals.len()),
+        hoisted: Vec::new(),
+    };
+    optimizer.stmts(&mut func.body);
+    for name in optimizer.fresh {
+        func.local_map.insert(name.clone(), func.locals.len() as u32);
+        func.locals.push(name);
+    }
+}
+
+struct LoopOptimizer {
+    fresh: Vec<String>,
+    budget: usize,
+    // Expressions already hoisted for the current loop, keyed by their IR
+    hoisted: Vec<(String, String)>,
+}
+
+impl LoopOptimizer {
+    fn new_local(&mut self) -> Option<String> {
+        if self.fresh.len() >= self.budget {
+            return None;
+        }
+        let name = format!("__loop{}", self.fresh.len());
+        self.fresh.push(name.clone());
+        Some(name)
+    }
+
+    fn stmts(&mut self, stmts: &mut [IRStmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut IRStmt) {
+        // Inner loops first, so their preludes can move further out
+        match stmt {
+            IRStmt::If { then_block, else_block, .. } => {
+                self.stmts(then_block);
+                self.stmts(else_block);
+            }
+            IRStmt::IfChain { branches, else_block } => {
+                for (_, body) in branches {
+                    self.stmts(body);
+                }
+                self.stmts(else_block);
+            }
+            IRStmt::Switch { arms, default, .. } => {
+                for arm in arms {
+                    self.stmts(arm);
+                }
+                self.stmts(default);
+            }
+            IRStmt::While { body, .. } | IRStmt::For { body, .. } | IRStmt::Block(body) => self.stmts(body),
+            IRStmt::Try { body, handlers, else_block, finally } => {
+                self.stmts(body);
+                for (_, handler) in handlers {
+                    self.stmts(handler);
+                }
+                self.stmts(else_block);
+                self.stmts(finally);
+            }
+            _ => {}
+        }
+
+        let mut prelude = Vec::new();
+        match stmt {
+            IRStmt::While { cond, body } => {
+                let mut effects = LoopEffects::of(body);
+                effects.expr(cond);
+                self.hoisted.clear();
+                // the condition runs at least once, so anything it always evaluates may move
+                self.hoist(cond, &effects, true, &mut prelude);
+                self.hoist_stmts(body, &effects, &mut prelude);
+            }
+            IRStmt::For { var, start, step, body, .. } => {
+                let mut effects = LoopEffects::of(body);
+                let var_reassigned = !effects.assigned.insert(var.clone());
+                self.hoisted.clear();
+                self.hoist_stmts(body, &effects, &mut prelude);
+                // the step goes at the end of the body, which continue would skip
+                if let (Some(step), false, false) = (step.const_value(), var_reassigned, body.iter().any(IRStmt::continues)) {
+                    self.induction(var, start, step, body, &mut prelude);
+                }
+            }
+            _ => return,
+        }
+
+        if !prelude.is_empty() {
+            let lp = std::mem::replace(stmt, IRStmt::Break);
+            prelude.push(lp);
+            *stmt = IRStmt::Block(prelude);
+        }
+    }
+
+    fn hoist_stmts(&mut self, body: &mut [IRStmt], effects: &LoopEffects, prelude: &mut Vec<IRStmt>) {
+        for stmt in body {
+            for_each_stmt_expr_mut(stmt, &mut |expr| self.hoist(expr, effects, false, prelude));
+        }
+    }
+
+    /// Replace maximal invariant subexpressions with locals computed in the prelude.
+    /// `always`: the expression is certainly evaluated before the loop body, so trapping ones may move too.
+    fn hoist(&mut self, expr: &mut IRExpr, effects: &LoopEffects, always: bool, prelude: &mut Vec<IRStmt>) {
+        let trivial = matches!(expr, IRExpr::LoadLocal(_)) || expr.const_value().is_some();
+        if !trivial && effects.invariant(expr) && (always || !may_trap(expr)) {
+            let key = format!("{:?}", expr);
+            let var = match self.hoisted.iter().find(|(k, _)| *k == key) {
+                Some((_, var)) => Some(var.clone()),
+                None => self.new_local().inspect(|var| {
+                    prelude.push(IRStmt::Assign { var: var.clone(), value: expr.clone() });
+                    self.hoisted.push((key, var.clone()));
+                }),
+            };
+            if let Some(var) = var {
+                *expr = IRExpr::LoadLocal(var);
+                return;
+            }
+        }
+
+        match expr {
+            // only the left operand and the condition are certain to run
+            IRExpr::BoolOp { left, right, .. } => {
+                self.hoist(left, effects, always, prelude);
+                self.hoist(right, effects, false, prelude);
+            }
+            IRExpr::IfExpr { cond, then_val, else_val } => {
+                self.hoist(cond, effects, always, prelude);
+                self.hoist(then_val, effects, false, prelude);
+                self.hoist(else_val, effects, false, prelude);
+            }
+            IRExpr::Block { .. } => {}
+            _ => {
+                for child in expr.children_mut() {
+                    self.hoist(child, effects, always, prelude);
+                }
+            }
+        }
+    }
+
+    /// for i in range(start, stop, step) with i * c used repeatedly: keep t == i * c, bumped by step * c
+    fn induction(&mut self, var: &str, start: &mut IRExpr, step: i32, body: &mut Vec<IRStmt>, prelude: &mut Vec<IRStmt>) {
+        let mut uses: BTreeMap<i32, usize> = BTreeMap::new();
+        for stmt in body.iter_mut() {
+            for_each_stmt_expr_mut(stmt, &mut |expr| count_products(expr, var, &mut uses));
+        }
+
+        for (c, count) in uses {
+            if count < MIN_INDUCTION_USES {
+                continue;
+            }
+            let Some(t) = self.new_local() else {
+                return;
+            };
+
+            let init = match start.const_value() {
+                Some(s) => IRExpr::Const(s.wrapping_mul(c)),
+                None => {
+                    // range() evaluates start once; keep it that way
+                    if !matches!(start, IRExpr::LoadLocal(_)) {
+                        let Some(s) = self.new_local() else {
+                            return;
+                        };
+                        prelude.push(IRStmt::Assign { var: s.clone(), value: std::mem::replace(start, IRExpr::LoadLocal(s.clone())) });
+                    }
+                    IRExpr::BinOp { op: BinOp::Mul, left: Box::new(start.clone()), right: Box::new(IRExpr::Const(c)) }
+                }
+            };
+            prelude.push(IRStmt::Assign { var: t.clone(), value: init });
+
+            for stmt in body.iter_mut() {
+                for_each_stmt_expr_mut(stmt, &mut |expr| replace_products(expr, var, c, &t));
+            }
+            // Sub is plain i32 arithmetic; Add would dispatch on string operands
+            body.push(IRStmt::Assign {
+                var: t.clone(),
+                value: IRExpr::BinOp {
+                    op: BinOp::Sub,
+                    left: Box::new(IRExpr::LoadLocal(t)),
+                    right: Box::new(IRExpr::Const(step.wrapping_mul(c).wrapping_neg())),
+                },
+            });
+        }
+    }
+}
+
+/// `var * c` or `c * var` with a literal c
+fn product_of(expr: &IRExpr, var: &str) -> Option<i32> {
+    let IRExpr::BinOp { op: BinOp::Mul, left, right } = expr else {
+        return None;
+    };
+    match (&**left, &**right) {
+        (IRExpr::LoadLocal(v), c) | (c, IRExpr::LoadLocal(v)) if v == var => c.const_value(),
+        _ => None,
+    }
+}
+
+fn count_products(expr: &mut IRExpr, var: &str, uses: &mut BTreeMap<i32, usize>) {
+    if let Some(c) = product_of(expr, var) {
+        *uses.entry(c).or_default() += 1;
+        return;
+    }
+    if matches!(expr, IRExpr::Block { .. }) {
+        return;
+    }
+    for child in expr.children_mut() {
+        count_products(child, var, uses);
+    }
+}
+
+fn replace_products(expr: &mut IRExpr, var: &str, c: i32, t: &str) {
+    if product_of(expr, var) == Some(c) {
+        *expr = IRExpr::LoadLocal(t.to_string());
+        return;
+    }
+    if matches!(expr, IRExpr::Block { .. }) {
+        return;
+    }
+    for child in expr.children_mut() {
+        replace_products(child, var, c, t);
+    }
+}
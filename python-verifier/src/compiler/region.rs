@@ -0,0 +1,295 @@
+// Heap regions for loops. The allocator only bumps, so a loop that builds temporaries grows the
+// heap every iteration until HEAP_LIMIT traps. A loop whose allocations can't be reached once
+// its iteration is over instead rewinds the heap pointer to where it stood before the loop, at
+// the top of every iteration and again on leaving. Which loops rewind is decided here from the
+// IR alone and compiled into the module, so every engine running it frees at the same points.
+
+use std::collections::BTreeSet;
+
+use super::ir::*;
+use super::optimize::NON_MUTATING_BUILTINS;
+
+// Methods that read their receiver and store nothing into it or anything else
+const READ_ONLY_METHODS: &[&str] = &["encode", "startswith", "endswith", "digest", "hexdigest", "hex"];
+
+// Builtins whose result is an integer whatever their arguments
+const INT_BUILTINS: &[&str] = &["len", "abs", "sum"];
+
+/// Whether `stmt`, a While or For in `func`, may rewind the heap each iteration. Holds when
+/// nothing an iteration allocates is reachable after it:
+/// - the loop stores into no heap object or global and calls no user function, and
+/// - every local it assigns that may hold a heap value is assigned before it is read within an
+///   iteration, and is read nowhere outside the loop (main reads OUTPUT when it returns)
+pub(super) fn rewinds_heap(stmt: &IRStmt, func: &IRFunction) -> bool {
+    let (cond, body) = match stmt {
+        IRStmt::While { cond, body } => (Some(cond), body),
+        IRStmt::For { body, .. } => (None, body),
+        _ => return false,
+    };
+
+    let mut stores = false;
+    let mut assigned = BTreeSet::new();
+    let mut visit = |node: Node| match node {
+        Node::Stmt(IRStmt::SubscriptAssign { .. } | IRStmt::AssignGlobal { .. }) => stores = true,
+        Node::Stmt(IRStmt::Assign { var, .. } | IRStmt::For { var, .. }) | Node::Expr(IRExpr::AssignExpr { var, .. }) => {
+            assigned.insert(var.clone());
+        }
+        Node::Expr(IRExpr::MethodCall { method, .. }) if !READ_ONLY_METHODS.contains(&method.as_str()) => stores = true,
+        Node::Expr(IRExpr::Call { func, .. }) if func != "print" && !NON_MUTATING_BUILTINS.contains(&func.as_str()) => {
+            stores = true
+        }
+        _ => {}
+    };
+    if let Some(cond) = cond {
+        walk_expr(cond, &mut visit);
+    }
+    walk(body, &mut visit);
+    if stores {
+        return false;
+    }
+
+    let ints = int_locals(func);
+    let carried: BTreeSet<String> = assigned.into_iter().filter(|var| !ints.contains(var)).collect();
+    if carried.is_empty() {
+        return true;
+    }
+
+    let mut defined = BTreeSet::new();
+    if let IRStmt::For { var, .. } = stmt {
+        defined.insert(var.clone());
+    }
+    if let Some(cond) = cond {
+        if !reads_assigned(cond, &carried, &mut defined) {
+            return false;
+        }
+    }
+    if !assigned_before_read(body, &carried, &mut defined) {
+        return false;
+    }
+
+    if func.name == "main" && carried.contains("OUTPUT") {
+        return false;
+    }
+    let mut read_outside = false;
+    walk_outside(&func.body, stmt, &mut |node| {
+        if let Node::Expr(IRExpr::LoadLocal(var)) = node {
+            read_outside |= carried.contains(var);
+        }
+    });
+    !read_outside
+}
+
+/// Locals that only ever hold integers: every value assigned to them is one. Parameters may
+/// hold anything.
+fn int_locals(func: &IRFunction) -> BTreeSet<String> {
+    let mut assignments = Vec::new();
+    walk(&func.body, &mut |node| match node {
+        Node::Stmt(IRStmt::Assign { var, value }) => assignments.push((var.clone(), Some(value.clone()))),
+        Node::Expr(IRExpr::AssignExpr { var, value }) => assignments.push((var.clone(), Some((**value).clone()))),
+        // range() counters
+        Node::Stmt(IRStmt::For { var, .. }) => assignments.push((var.clone(), None)),
+        _ => {}
+    });
+
+    let mut ints: BTreeSet<String> = assignments.iter()
+        .map(|(var, _)| var.clone())
+        .filter(|var| !func._params.contains(var))
+        .collect();
+    loop {
+        let before = ints.len();
+        for (var, value) in &assignments {
+            if let Some(value) = value {
+                if ints.contains(var) && !is_int(value, &ints) {
+                    ints.remove(var);
+                }
+            }
+        }
+        if ints.len() == before {
+            return ints;
+        }
+    }
+}
+
+fn is_int(expr: &IRExpr, ints: &BTreeSet<String>) -> bool {
+    match expr {
+        IRExpr::Const(_) => true,
+        IRExpr::LoadLocal(var) => ints.contains(var),
+        IRExpr::AssignExpr { value, .. } => is_int(value, ints),
+        // + concatenates strings, bytes and lists; every other operator works on integers
+        IRExpr::BinOp { op: BinOp::Add, left, right } => is_int(left, ints) && is_int(right, ints),
+        IRExpr::BinOp { .. } => true,
+        IRExpr::UnaryOp { op: UnaryOp::Not, .. } => true,
+        IRExpr::UnaryOp { op: UnaryOp::Neg, operand } => is_int(operand, ints),
+        IRExpr::BoolOp { left, right, .. } => is_int(left, ints) && is_int(right, ints),
+        IRExpr::IfExpr { then_val, else_val, .. } => is_int(then_val, ints) && is_int(else_val, ints),
+        IRExpr::Call { func, .. } => INT_BUILTINS.contains(&func.as_str()),
+        IRExpr::MethodCall { method, .. } => method == "startswith" || method == "endswith",
+        _ => false,
+    }
+}
+
+/// Whether every read of a `carried` local in one pass through `stmts` follows an assignment
+/// to it earlier in the pass; `defined` gathers the locals assigned on every path
+fn assigned_before_read(stmts: &[IRStmt], carried: &BTreeSet<String>, defined: &mut BTreeSet<String>) -> bool {
+    let branches = |blocks: &[&Vec<IRStmt>], defined: &BTreeSet<String>| {
+        blocks.iter().all(|block| assigned_before_read(block, carried, &mut defined.clone()))
+    };
+    stmts.iter().all(|stmt| match stmt {
+        IRStmt::Assign { var, value } => {
+            let ok = reads_assigned(value, carried, defined);
+            defined.insert(var.clone());
+            ok
+        }
+        IRStmt::If { cond, then_block, else_block } => {
+            reads_assigned(cond, carried, defined) && branches(&[then_block, else_block], &*defined)
+        }
+        IRStmt::IfChain { branches: arms, else_block } => {
+            // a condition runs only after the ones before it
+            let mut at_cond = defined.clone();
+            arms.iter().all(|(cond, body)| reads_assigned(cond, carried, &mut at_cond) && branches(&[body], &at_cond))
+                && branches(&[else_block], &at_cond)
+        }
+        IRStmt::Switch { value, arms, default, .. } => {
+            reads_assigned(value, carried, defined)
+                && branches(&arms.iter().chain([default]).collect::<Vec<_>>(), &*defined)
+        }
+        IRStmt::While { cond, body } => {
+            let mut inner = defined.clone();
+            reads_assigned(cond, carried, &mut inner) && assigned_before_read(body, carried, &mut inner)
+        }
+        IRStmt::For { var, start, stop, step, body } => {
+            [start, stop, step].into_iter().all(|e| reads_assigned(e, carried, defined)) && {
+                let mut inner = defined.clone();
+                inner.insert(var.clone());
+                assigned_before_read(body, carried, &mut inner)
+            }
+        }
+        IRStmt::Block(body) => assigned_before_read(body, carried, defined),
+        IRStmt::Try { body, handlers, else_block, finally } => {
+            let handler_bodies: Vec<&Vec<IRStmt>> = handlers.iter().map(|(_, h)| h).collect();
+            branches(&[body, else_block, finally], &*defined) && branches(&handler_bodies, &*defined)
+        }
+        IRStmt::AssignGlobal { value, .. } | IRStmt::Return(value) | IRStmt::Expr(value) => {
+            reads_assigned(value, carried, defined)
+        }
+        IRStmt::SubscriptAssign { target, index, value } => {
+            [target, index, value].into_iter().all(|e| reads_assigned(e, carried, defined))
+        }
+        IRStmt::Break | IRStmt::Continue | IRStmt::Raise(_) | IRStmt::AssertFail { .. } | IRStmt::Line(_) => true,
+    })
+}
+
+fn reads_assigned(expr: &IRExpr, carried: &BTreeSet<String>, defined: &mut BTreeSet<String>) -> bool {
+    match expr {
+        IRExpr::LoadLocal(var) => !carried.contains(var) || defined.contains(var),
+        IRExpr::AssignExpr { var, value } => {
+            let ok = reads_assigned(value, carried, defined);
+            defined.insert(var.clone());
+            ok
+        }
+        IRExpr::Block { stmts, result } => {
+            assigned_before_read(stmts, carried, defined) && reads_assigned(result, carried, defined)
+        }
+        // the operands after the first may not run, so assignments in them don't count
+        IRExpr::BoolOp { left, right, .. } => {
+            reads_assigned(left, carried, defined) && reads_assigned(right, carried, &mut defined.clone())
+        }
+        IRExpr::IfExpr { cond, then_val, else_val } => {
+            reads_assigned(cond, carried, defined)
+                && reads_assigned(then_val, carried, &mut defined.clone())
+                && reads_assigned(else_val, carried, &mut defined.clone())
+        }
+        _ => expr.children().into_iter().all(|child| reads_assigned(child, carried, defined)),
+    }
+}
+
+enum Node<'a> {
+    Stmt(&'a IRStmt),
+    Expr(&'a IRExpr),
+}
+
+/// Pre-order visit of every statement and expression, including statements in Block expressions
+fn walk(stmts: &[IRStmt], f: &mut dyn FnMut(Node)) {
+    for stmt in stmts {
+        walk_stmt(stmt, f);
+    }
+}
+
+fn walk_stmt(stmt: &IRStmt, f: &mut dyn FnMut(Node)) {
+    f(Node::Stmt(stmt));
+    for expr in own_exprs(stmt) {
+        walk_expr(expr, f);
+    }
+    for block in nested_blocks(stmt) {
+        walk(block, f);
+    }
+}
+
+fn walk_expr(expr: &IRExpr, f: &mut dyn FnMut(Node)) {
+    f(Node::Expr(expr));
+    if let IRExpr::Block { stmts, .. } = expr {
+        walk(stmts, f);
+    }
+    for child in expr.children() {
+        walk_expr(child, f);
+    }
+}
+
+/// `walk` over `stmts`, skipping the statement `skip` and everything in it
+fn walk_outside(stmts: &[IRStmt], skip: &IRStmt, f: &mut dyn FnMut(Node)) {
+    for stmt in stmts {
+        if std::ptr::eq(stmt, skip) {
+            continue;
+        }
+        f(Node::Stmt(stmt));
+        for expr in own_exprs(stmt) {
+            walk_expr_outside(expr, skip, f);
+        }
+        for block in nested_blocks(stmt) {
+            walk_outside(block, skip, f);
+        }
+    }
+}
+
+fn walk_expr_outside(expr: &IRExpr, skip: &IRStmt, f: &mut dyn FnMut(Node)) {
+    f(Node::Expr(expr));
+    if let IRExpr::Block { stmts, .. } = expr {
+        walk_outside(stmts, skip, f);
+    }
+    for child in expr.children() {
+        walk_expr_outside(child, skip, f);
+    }
+}
+
+/// Expressions a statement evaluates itself, not those of the statements nested in it
+fn own_exprs(stmt: &IRStmt) -> Vec<&IRExpr> {
+    match stmt {
+        IRStmt::Assign { value, .. } | IRStmt::AssignGlobal { value, .. } | IRStmt::Return(value) | IRStmt::Expr(value) => vec![value],
+        IRStmt::SubscriptAssign { target, index, value } => vec![&**target, &**index, &**value],
+        IRStmt::If { cond, .. } | IRStmt::While { cond, .. } => vec![cond],
+        IRStmt::IfChain { branches, .. } => branches.iter().map(|(cond, _)| cond).collect(),
+        IRStmt::Switch { value, .. } => vec![value],
+        IRStmt::For { start, stop, step, .. } => vec![start, stop, step],
+        IRStmt::Block(_) | IRStmt::Try { .. } | IRStmt::Break | IRStmt::Continue | IRStmt::Raise(_) | IRStmt::AssertFail { .. }
+        | IRStmt::Line(_) => vec![],
+    }
+}
+
+fn nested_blocks(stmt: &IRStmt) -> Vec<&[IRStmt]> {
+    match stmt {
+        IRStmt::If { then_block, else_block, .. } => vec![then_block.as_slice(), else_block.as_slice()],
+        IRStmt::IfChain { branches, else_block } => {
+            branches.iter().map(|(_, body)| body.as_slice()).chain([else_block.as_slice()]).collect()
+        }
+        IRStmt::Switch { arms, default, .. } => arms.iter().map(Vec::as_slice).chain([default.as_slice()]).collect(),
+        IRStmt::While { body, .. } | IRStmt::For { body, .. } | IRStmt::Block(body) => vec![body.as_slice()],
+        IRStmt::Try { body, handlers, else_block, finally } => {
+            std::iter::once(body.as_slice())
+                .chain(handlers.iter().map(|(_, h)| h.as_slice()))
+                .chain([else_block.as_slice(), finally.as_slice()])
+                .collect()
+        }
+        IRStmt::Assign { .. } | IRStmt::AssignGlobal { .. } | IRStmt::SubscriptAssign { .. } | IRStmt::Return(_)
+        | IRStmt::Expr(_) | IRStmt::Break | IRStmt::Continue | IRStmt::Raise(_) | IRStmt::AssertFail { .. } | IRStmt::Line(_) => vec![],
+    }
+}
@@ -0,0 +1,85 @@
+// What one job may use, as posted with createJob: its fuel_limit, memLimit and maxOutputSize,
+// plus the largest source the compiler takes. The compiler builds the module to stop at them
+// (the fuel counter's trap, the heap's end, the memory it imports) and the executor runs it
+// under the same figures, so a module never allows more than the contract pays for.
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use super::codegen::{HEAP_LIMIT, HEAP_START};
+use super::fuel::FUEL_LIMIT;
+
+/// Source the compiler accepts unless told otherwise
+pub const MAX_PYTHON_SIZE: usize = 100 * 1024;
+/// Linear memory a job gets unless its limits say otherwise, in 64 KiB pages
+pub const DEFAULT_MEMORY_PAGES: u32 = 256;
+/// Output the host reads back unless the job's limits say otherwise
+pub const DEFAULT_MAX_OUTPUT_BYTES: u32 = 100 * 1024;
+
+const WASM_PAGE_SIZE: u64 = 65536;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResourceLimits {
+    /// Fuel the module's counter traps past; anything above FUEL_LIMIT stops at FUEL_LIMIT
+    pub fuel_limit: u64,
+    /// Linear memory the module may have, in 64 KiB pages; the heap ends within it
+    pub memory_pages: u32,
+    /// Output bytes the host reads back before failing the run
+    pub max_output_bytes: u32,
+    pub max_source_bytes: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            fuel_limit: FUEL_LIMIT as u64,
+            memory_pages: DEFAULT_MEMORY_PAGES,
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+            max_source_bytes: MAX_PYTHON_SIZE,
+        }
+    }
+}
+
+impl ResourceLimits {
+    /// The limits of a job from its createJob fields; `mem_limit` is in bytes
+    pub fn from_job(fuel_limit: u64, mem_limit: u64, max_output_size: u32) -> Self {
+        Self {
+            fuel_limit,
+            memory_pages: u32::try_from(mem_limit / WASM_PAGE_SIZE).unwrap_or(u32::MAX),
+            max_output_bytes: max_output_size,
+            ..Self::default()
+        }
+    }
+
+    /// The fuel counter's trap threshold
+    pub fn gas_limit(&self) -> i32 {
+        self.fuel_limit.min(FUEL_LIMIT as u64) as i32
+    }
+
+    /// The address the allocator traps at: the end of the job's memory, or HEAP_LIMIT if sooner
+    pub fn heap_limit(&self) -> i32 {
+        (HEAP_LIMIT as u64).min(self.memory_pages as u64 * WASM_PAGE_SIZE) as i32
+    }
+
+    /// Reject limits no module can be built for
+    pub fn check(&self) -> Result<()> {
+        if self.fuel_limit == 0 {
+            bail!("fuel limit must be positive");
+        }
+        if self.heap_limit() <= HEAP_START {
+            bail!("{} memory pages leave no room for a heap above {:#x}", self.memory_pages, HEAP_START);
+        }
+        if self.max_output_bytes == 0 {
+            bail!("max output must be positive");
+        }
+        Ok(())
+    }
+
+    pub fn check_source(&self, python_code: &str) -> Result<()> {
+        if python_code.len() > self.max_source_bytes {
+            bail!("Python code exceeds {}KB limit", self.max_source_bytes / 1024);
+        }
+        Ok(())
+    }
+}
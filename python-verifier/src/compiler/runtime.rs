@@ -0,0 +1,287 @@
+// Shared runtime functions. The larger memory helpers (string and bytes building, slicing,
+// hashing) are emitted once per module as Wasm functions and called, instead of being inlined
+// at every call site; a program that concatenates in ten places carries one copy of concat.
+//
+// Each runtime function takes its operands as i32 params in the order the inline helper
+// expected them on the stack and returns the helper's result. Only helpers that don't read the
+// caller's locals qualify, and one that raises leaves the error pending for the caller's check,
+// as the inline code did. The generic operations (`+`, subscripts and slicing) dispatch on
+// their operands' types and call or inline the helper that fits, so a call site is one call
+// whatever the operands turn out to be.
+
+use wasm_encoder::{BlockType, Encode, Function, Instruction, MemArg, ValType};
+use wasmparser::{BinaryReader, FunctionBody, Operator};
+
+use super::memory::{self, HashAlgorithm};
+use super::codegen::ERROR_GLOBAL;
+use super::errors::RuntimeError;
+use super::ir::ExceptionKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Runtime {
+    /// [str1, str2] -> new string
+    StringConcat,
+    /// [bytes_a, bytes_b] -> new bytes
+    BytesConcat,
+    /// [str1, str2] -> 1 or 0, for strings and bytes alike
+    StringEquals,
+    /// [container, item] -> 1 or 0
+    Contains,
+    /// [int] -> decimal string
+    FromInt,
+    /// [str] -> UTF-8 bytes
+    Encode,
+    /// [bytes] -> hex string
+    HexDigest,
+    /// [bytes] -> 32-byte digest
+    Digest(HashAlgorithm),
+    /// [bytes] -> 32-byte keccak256 digest
+    Keccak256,
+    /// [src, len, start, stop, step, count] as left by adjust_slice -> new string or bytes
+    StringSlice,
+    /// Same operands -> new list, or tuple when slicing a tuple
+    ListSlice,
+    /// [dict or set] -> the same, rehashed into a fresh slot buffer
+    DictGrow,
+    /// [left, right] -> string, bytes, list or tuple concatenation, or integer sum
+    Add,
+    /// [value, index] -> the byte of a string or bytes, the element of a list or tuple, or the
+    /// value under a dict key. A missing key is a pending KeyError when `raises`, else a trap
+    Subscript { raises: bool },
+    /// [value, start, stop, step] -> new slice of a string, bytes, list or tuple. A bound the
+    /// slice doesn't give is passed as 0 and ignored
+    Slice { start: bool, stop: bool },
+}
+
+impl Runtime {
+    pub fn params(self) -> u32 {
+        match self {
+            Runtime::StringConcat | Runtime::BytesConcat | Runtime::StringEquals | Runtime::Contains | Runtime::Add
+                | Runtime::Subscript { .. } => 2,
+            Runtime::FromInt | Runtime::Encode | Runtime::HexDigest | Runtime::Digest(_) | Runtime::Keccak256 | Runtime::DictGrow => 1,
+            Runtime::StringSlice | Runtime::ListSlice => 6,
+            Runtime::Slice { .. } => 4,
+        }
+    }
+
+    /// Runtime functions this one calls
+    pub fn calls(self) -> &'static [Runtime] {
+        match self {
+            Runtime::Add => &[Runtime::StringConcat, Runtime::BytesConcat],
+            Runtime::Slice { .. } => &[Runtime::StringSlice, Runtime::ListSlice],
+            _ => &[],
+        }
+    }
+
+    /// Name given in the module's name section; "$" can't appear in a Python function name
+    pub fn name(self) -> &'static str {
+        match self {
+            Runtime::StringConcat => "$string_concat",
+            Runtime::BytesConcat => "$bytes_concat",
+            Runtime::StringEquals => "$string_equals",
+            Runtime::Contains => "$contains",
+            Runtime::FromInt => "$from_int",
+            Runtime::Encode => "$encode",
+            Runtime::HexDigest => "$hexdigest",
+            Runtime::Digest(HashAlgorithm::Sha256) => "$sha256",
+            Runtime::Digest(HashAlgorithm::Sha3_256) => "$sha3_256",
+            Runtime::Keccak256 => "$keccak256",
+            Runtime::StringSlice => "$string_slice",
+            Runtime::ListSlice => "$list_slice",
+            Runtime::DictGrow => "$dict_grow",
+            Runtime::Add => "$add",
+            Runtime::Subscript { .. } => "$subscript",
+            Runtime::Slice { start: false, stop: false } => "$slice",
+            Runtime::Slice { start: true, stop: false } => "$slice_from",
+            Runtime::Slice { start: false, stop: true } => "$slice_to",
+            Runtime::Slice { start: true, stop: true } => "$slice_from_to",
+        }
+    }
+
+    /// The function's body; `index` gives the function index of each runtime function it calls
+    pub fn body(self, index: &dyn Fn(Runtime) -> u32) -> Function {
+        let mut probe = Function::new([]);
+        self.emit(&mut probe, index);
+        let mut func = Function::new([(self.scratch(&probe), ValType::I32)]);
+        self.emit(&mut func, index);
+        func
+    }
+
+    // Locals the helper needs beyond its params: every local its instructions touch, read back
+    // from a copy emitted without any, so the count can't fall behind what the memory helpers use
+    fn scratch(self, probe: &Function) -> u32 {
+        let mut bytes = Vec::new();
+        probe.encode(&mut bytes);
+        let mut reader = BinaryReader::new(&bytes);
+        reader.read_var_u32().expect("body size prefix");
+        let start = reader.original_position();
+        let body = FunctionBody::new(start, &bytes[start..]);
+        let mut ops = body.get_operators_reader().expect("runtime helper without locals");
+
+        let mut used = self.params();
+        while !ops.eof() {
+            match ops.read().expect("runtime helper encodes valid instructions") {
+                Operator::LocalGet { local_index } | Operator::LocalSet { local_index } | Operator::LocalTee { local_index } => {
+                    used = used.max(local_index + 1);
+                }
+                _ => {}
+            }
+        }
+        used - self.params()
+    }
+
+    fn emit(self, func: &mut Function, index: &dyn Fn(Runtime) -> u32) {
+        let base = self.params();
+
+        match self {
+            // the slice helpers read their operands from locals, which the params already are
+            Runtime::StringSlice => memory::StringLayout::slice(func, 0),
+            Runtime::ListSlice => memory::ListLayout::slice(func, 0),
+            Runtime::Add => Self::add(func, base, index),
+            Runtime::Subscript { raises } => Self::subscript(func, base, raises),
+            Runtime::Slice { start, stop } => Self::slice(func, base, start, stop, index),
+            stack => {
+                for param in 0..base {
+                    func.instruction(&Instruction::LocalGet(param));
+                }
+                match stack {
+                    Runtime::StringConcat => memory::StringLayout::concat(func, base, base + 1, base + 2, base + 3, base + 4, base + 5, base + 6),
+                    Runtime::BytesConcat => memory::BytesLayout::concat(func, base, base + 1, base + 2, base + 3),
+                    Runtime::StringEquals => memory::StringLayout::equals(func, base, base + 1, base + 2, base + 3, base + 4),
+                    Runtime::Contains => memory::contains(func, base),
+                    Runtime::FromInt => memory::StringLayout::from_int(func, base, base + 1, base + 2, base + 3, base + 4, base + 5, base + 6, base + 7),
+                    Runtime::Encode => memory::BytesLayout::from_string(func, base, base + 1, base + 2, base + 3),
+                    Runtime::HexDigest => memory::BytesLayout::hexdigest(func, base, base + 1, base + 2, base + 3),
+                    Runtime::Digest(HashAlgorithm::Sha256) => memory::sha256(func, base),
+                    Runtime::Digest(HashAlgorithm::Sha3_256) => memory::keccak256(func, base, memory::SHA3_PAD),
+                    Runtime::Keccak256 => memory::keccak256(func, base, memory::KECCAK_PAD),
+                    Runtime::DictGrow => memory::DictLayout::grow(func, base),
+                    Runtime::StringSlice | Runtime::ListSlice | Runtime::Add | Runtime::Subscript { .. }
+                        | Runtime::Slice { .. } => unreachable!(),
+                }
+            }
+        }
+
+        func.instruction(&Instruction::End);
+    }
+
+    // String concatenation when left is a string, bytes concatenation when it's bytes (and a
+    // TypeError unless right is too), list or tuple concatenation when it's a sequence of the
+    // same kind, integer addition otherwise
+    fn add(func: &mut Function, base: u32, index: &dyn Fn(Runtime) -> u32) {
+        let (left, right) = (0, 1);
+        memory::StringLayout::is_string(func, left);
+        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+        func.instruction(&Instruction::LocalGet(left));
+        func.instruction(&Instruction::LocalGet(right));
+        func.instruction(&Instruction::Call(index(Runtime::StringConcat)));
+        func.instruction(&Instruction::Else);
+        memory::BytesLayout::is_bytes(func, left);
+        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+        memory::BytesLayout::is_bytes(func, right);
+        func.instruction(&Instruction::I32Eqz);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        memory::trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::LocalGet(left));
+        func.instruction(&Instruction::LocalGet(right));
+        func.instruction(&Instruction::Call(index(Runtime::BytesConcat)));
+        func.instruction(&Instruction::Else);
+        memory::ListLayout::is_sequence(func, left);
+        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+        func.instruction(&Instruction::LocalGet(left));
+        func.instruction(&Instruction::LocalGet(right));
+        memory::ListLayout::concat(func, base, base + 1, base + 2, base + 3);
+        func.instruction(&Instruction::Else);
+        func.instruction(&Instruction::LocalGet(left));
+        func.instruction(&Instruction::LocalGet(right));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+    }
+
+    // x[-1] counts from the end of a sequence; dict keys are looked up as they are
+    fn subscript(func: &mut Function, base: u32, raises: bool) {
+        let (value, key) = (0, 1);
+        memory::normalize_index(func, value, key);
+
+        memory::StringLayout::is_string(func, value);
+        memory::BytesLayout::is_bytes(func, value);
+        func.instruction(&Instruction::I32Or);
+        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+        // string and bytes indexing both yield the byte's value
+        func.instruction(&Instruction::LocalGet(value));
+        func.instruction(&Instruction::LocalGet(key));
+        memory::StringLayout::index(func, base, base + 1);
+        func.instruction(&Instruction::Else);
+        func.instruction(&Instruction::LocalGet(value));
+        func.instruction(&Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }));
+        func.instruction(&Instruction::I32Const(2)); // TYPE_DICT
+        func.instruction(&Instruction::I32Eq);
+        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+        func.instruction(&Instruction::LocalGet(value));
+        func.instruction(&Instruction::LocalGet(key));
+        memory::DictLayout::lookup(func, base, base + 1, base + 2, base + 3, base + 4, base + 5, &|func| {
+            if raises {
+                func.instruction(&Instruction::GlobalGet(ERROR_GLOBAL));
+                func.instruction(&Instruction::I32Eqz);
+                func.instruction(&Instruction::If(BlockType::Empty));
+                func.instruction(&Instruction::I32Const(ExceptionKind::KeyError.code()));
+                func.instruction(&Instruction::GlobalSet(ERROR_GLOBAL));
+                func.instruction(&Instruction::End);
+            } else {
+                memory::trap(func, RuntimeError::uncaught(ExceptionKind::KeyError));
+            }
+            func.instruction(&Instruction::I32Const(0));
+        });
+        func.instruction(&Instruction::Else);
+        // lists and tuples
+        func.instruction(&Instruction::LocalGet(value));
+        func.instruction(&Instruction::LocalGet(key));
+        memory::ListLayout::load_element(func, base, base + 1);
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+    }
+
+    // Lay the params out as adjust_slice and the slice helpers expect them from `base`: src, len,
+    // start, stop, step, count. Strings and bytes copy bytes, lists and tuples copy elements
+    fn slice(func: &mut Function, base: u32, start: bool, stop: bool, index: &dyn Fn(Runtime) -> u32) {
+        let src = 0;
+        // only sized heap values can be sliced
+        func.instruction(&Instruction::LocalGet(src));
+        func.instruction(&Instruction::I32Const(1024));
+        func.instruction(&Instruction::I32LtU);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        memory::trap(func, RuntimeError::TypeError);
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(src));
+        func.instruction(&Instruction::LocalSet(base));
+        func.instruction(&Instruction::LocalGet(src));
+        memory::StringLayout::load_length(func);
+        func.instruction(&Instruction::LocalSet(base + 1));
+        for param in 1..4 {
+            func.instruction(&Instruction::LocalGet(param));
+            func.instruction(&Instruction::LocalSet(base + 1 + param));
+        }
+        memory::adjust_slice(func, base + 1, start, stop);
+
+        let operands = |func: &mut Function| {
+            for local in base..base + 6 {
+                func.instruction(&Instruction::LocalGet(local));
+            }
+        };
+        memory::StringLayout::is_string(func, src);
+        memory::BytesLayout::is_bytes(func, src);
+        func.instruction(&Instruction::I32Or);
+        func.instruction(&Instruction::If(BlockType::Result(ValType::I32)));
+        operands(func);
+        func.instruction(&Instruction::Call(index(Runtime::StringSlice)));
+        func.instruction(&Instruction::Else);
+        memory::TupleLayout::check_sequence(func, src);
+        operands(func);
+        func.instruction(&Instruction::Call(index(Runtime::ListSlice)));
+        func.instruction(&Instruction::End);
+    }
+}
@@ -0,0 +1,54 @@
+// Maps a compiled module back to the Python it came from. Codegen notes the line of each
+// statement's first instruction by its offset into the function body, counted from the body's
+// locals the way engines count frame offsets, after metering has moved the code. The module
+// itself only carries a name section; the map travels beside it, so a host holding both can
+// turn a trap's backtrace into the Python line that was running.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMap {
+    /// Python functions in Wasm function order; runtime functions have no lines
+    pub functions: Vec<FunctionLines>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionLines {
+    /// Wasm function index
+    pub index: u32,
+    /// "main" for module-level code, "Class.method" and "outer.inner" for lifted functions
+    pub name: String,
+    /// (body offset, line) by ascending offset; a line runs up to the next entry's offset
+    pub lines: Vec<(u32, u32)>,
+}
+
+/// A 1-based line of the Python source and the function it is in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub function: String,
+    pub line: u32,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {} in {}", self.line, self.function)
+    }
+}
+
+impl SourceMap {
+    /// Line of the code at `offset` into the body of function `index`. None in runtime functions
+    /// and in a function's prologue, ahead of its first statement.
+    pub fn locate(&self, index: u32, offset: usize) -> Option<SourceLocation> {
+        let function = self.functions.iter().find(|f| f.index == index)?;
+        let after = function.lines.partition_point(|&(start, _)| start as usize <= offset);
+        let &(_, line) = function.lines.get(after.checked_sub(1)?)?;
+        Some(SourceLocation { function: function.name.clone(), line })
+    }
+
+    /// First of `frames`, (function index, body offset) from the innermost out, that is in
+    /// Python code: a trap inside a runtime function is reported at the call that reached it
+    pub fn locate_frames(&self, frames: impl IntoIterator<Item = (u32, usize)>) -> Option<SourceLocation> {
+        frames.into_iter().find_map(|(index, offset)| self.locate(index, offset))
+    }
+}
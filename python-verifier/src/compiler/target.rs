@@ -0,0 +1,428 @@
+// Instruction sets a module can be compiled for. `latest` is everything codegen emits and
+// wasmtime runs. `on-chain-v1` is the subset the Stylus interpreter replays today, so a job
+// compiled for it can be fraud-proven: no bulk-memory instructions and no right rotates.
+//
+// Codegen always emits the full set; `downgrade` then rewrites the unmetered module into the
+// subset. memory.copy, memory.fill and the rotates become calls to helper functions appended
+// after the runtime's, and each memory.init of a literal becomes a call to a helper that stores
+// the literal's bytes as constants, after which the passive segments have no reader and are
+// dropped. `check` runs on the finished module and rejects anything still outside the target.
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use wasm_encoder::{BlockType, Encode, Function, Instruction, MemArg, ValType};
+use wasmparser::{DataKind, Operator, Parser, Payload, TypeRef};
+
+use super::fuel::write_section;
+
+const TYPE_SECTION_ID: u8 = 1;
+const FUNCTION_SECTION_ID: u8 = 3;
+const CODE_SECTION_ID: u8 = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Target {
+    #[default]
+    #[serde(rename = "latest")]
+    Latest,
+    /// No bulk memory (memory.copy, memory.fill, memory.init, data.drop) and no i32.rotr or i64.rotr
+    #[serde(rename = "on-chain-v1")]
+    OnChainV1,
+}
+
+impl std::str::FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "latest" => Ok(Target::Latest),
+            "on-chain-v1" => Ok(Target::OnChainV1),
+            _ => bail!("unknown compile target '{}': expected latest or on-chain-v1", s),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Target::Latest => "latest",
+            Target::OnChainV1 => "on-chain-v1",
+        })
+    }
+}
+
+impl Target {
+    /// Reject a module that uses an instruction or section this target doesn't support
+    pub fn check(self, wasm: &[u8]) -> Result<()> {
+        if self == Target::Latest {
+            return Ok(());
+        }
+
+        let mut index = 0;
+        let mut data_count = false;
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        if let TypeRef::Func(_) = import?.ty {
+                            index += 1;
+                        }
+                    }
+                }
+                Payload::DataCountSection { .. } => data_count = true,
+                Payload::CodeSectionEntry(body) => {
+                    let mut ops = body.get_operators_reader()?;
+                    while !ops.eof() {
+                        let (op, offset) = ops.read_with_offset()?;
+                        if let Some(name) = unsupported(&op, wasm[offset]) {
+                            bail!("function {} uses {}, which the {} target doesn't support", index, name, self);
+                        }
+                    }
+                    index += 1;
+                }
+                _ => {}
+            }
+        }
+        // only bulk memory reads the count, so an instruction that needs it is the better report
+        if data_count {
+            bail!("the {} target doesn't support the data count section", self);
+        }
+        Ok(())
+    }
+}
+
+// Name of an instruction on-chain-v1 lacks; every 0xFC-prefixed instruction is bulk memory or
+// a saturating float truncation, neither of which the interpreter decodes
+fn unsupported(op: &Operator, first_byte: u8) -> Option<String> {
+    match op {
+        Operator::I32Rotr => Some("i32.rotr".into()),
+        Operator::I64Rotr => Some("i64.rotr".into()),
+        Operator::MemoryCopy { .. } => Some("memory.copy".into()),
+        Operator::MemoryFill { .. } => Some("memory.fill".into()),
+        Operator::MemoryInit { .. } => Some("memory.init".into()),
+        Operator::DataDrop { .. } => Some("data.drop".into()),
+        other if first_byte == 0xFC => Some(format!("{:?}", other)),
+        _ => None,
+    }
+}
+
+/// Where `downgrade` replaced instructions in one function body: the original offset of each
+/// replaced run, its length and the length of its replacement, in body order. Offsets count
+/// from the body's locals, like `fuel::Charges`.
+pub(crate) type Edits = Vec<(usize, usize, usize)>;
+
+/// Where an offset into an original body lands in the downgraded one. Code inside a replaced
+/// run is taken to start at its replacement.
+pub(crate) fn downgraded_offset(edits: &[(usize, usize, usize)], offset: usize) -> usize {
+    let mut shifted = offset;
+    for &(at, old, new) in edits.iter().take_while(|&&(at, _, _)| at < offset) {
+        shifted = if offset < at + old { shifted - (offset - at) } else { shifted + new - old };
+    }
+    shifted
+}
+
+// Functions `downgrade` appends, each emitted once however often it's called
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Helper {
+    /// [dst, src, len], overlapping ranges allowed
+    Copy,
+    /// [dst, byte, len]
+    Fill,
+    Rotr32,
+    Rotr64,
+    /// [dst]: `len` bytes of passive segment `segment` from `offset`
+    Init { segment: u32, offset: u32, len: u32 },
+}
+
+impl Helper {
+    fn params(self) -> Vec<ValType> {
+        match self {
+            Helper::Copy | Helper::Fill => vec![ValType::I32; 3],
+            Helper::Rotr32 => vec![ValType::I32; 2],
+            Helper::Rotr64 => vec![ValType::I64; 2],
+            Helper::Init { .. } => vec![ValType::I32],
+        }
+    }
+
+    fn results(self) -> Vec<ValType> {
+        match self {
+            Helper::Rotr32 => vec![ValType::I32],
+            Helper::Rotr64 => vec![ValType::I64],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Name given in the module's name section
+    fn name(self) -> String {
+        match self {
+            Helper::Copy => "$memory_copy".into(),
+            Helper::Fill => "$memory_fill".into(),
+            Helper::Rotr32 => "$i32_rotr".into(),
+            Helper::Rotr64 => "$i64_rotr".into(),
+            Helper::Init { segment, offset, len } => format!("$memory_init_{}_{}_{}", segment, offset, len),
+        }
+    }
+
+    fn body(self, segments: &[&[u8]]) -> Result<Function> {
+        let mem = |offset: u64, align: u32| MemArg { offset, align, memory_index: 0 };
+        let mut func = Function::new(vec![(1, ValType::I32)]);
+        match self {
+            // byte by byte, forwards unless dst is above src
+            Helper::Copy => {
+                func.instruction(&Instruction::LocalGet(0));
+                func.instruction(&Instruction::LocalGet(1));
+                func.instruction(&Instruction::I32LeU);
+                func.instruction(&Instruction::If(BlockType::Empty));
+                byte_loop(&mut func, true, |func| {
+                    func.instruction(&Instruction::LocalGet(1));
+                    func.instruction(&Instruction::LocalGet(3));
+                    func.instruction(&Instruction::I32Add);
+                    func.instruction(&Instruction::I32Load8U(mem(0, 0)));
+                });
+                func.instruction(&Instruction::Else);
+                byte_loop(&mut func, false, |func| {
+                    func.instruction(&Instruction::LocalGet(1));
+                    func.instruction(&Instruction::LocalGet(3));
+                    func.instruction(&Instruction::I32Add);
+                    func.instruction(&Instruction::I32Load8U(mem(0, 0)));
+                });
+                func.instruction(&Instruction::End);
+            }
+            Helper::Fill => byte_loop(&mut func, true, |func| {
+                func.instruction(&Instruction::LocalGet(1));
+            }),
+            // x >> n | x << -n; both shifts take their count modulo the width, so n = 0 gives x | x
+            Helper::Rotr32 | Helper::Rotr64 => {
+                let wide = self == Helper::Rotr64;
+                func.instruction(&Instruction::LocalGet(0));
+                func.instruction(&Instruction::LocalGet(1));
+                func.instruction(if wide { &Instruction::I64ShrU } else { &Instruction::I32ShrU });
+                func.instruction(&Instruction::LocalGet(0));
+                func.instruction(if wide { &Instruction::I64Const(0) } else { &Instruction::I32Const(0) });
+                func.instruction(&Instruction::LocalGet(1));
+                func.instruction(if wide { &Instruction::I64Sub } else { &Instruction::I32Sub });
+                func.instruction(if wide { &Instruction::I64Shl } else { &Instruction::I32Shl });
+                func.instruction(if wide { &Instruction::I64Or } else { &Instruction::I32Or });
+            }
+            // eight bytes per store, then the tail one at a time
+            Helper::Init { segment, offset, len } => {
+                let Some(bytes) = segments.get(segment as usize)
+                    .and_then(|data| data.get(offset as usize..offset as usize + len as usize)) else {
+                    bail!("memory.init reads past the end of data segment {}", segment);
+                };
+                let mut chunks = bytes.chunks_exact(8);
+                let mut at = 0;
+                for chunk in &mut chunks {
+                    func.instruction(&Instruction::LocalGet(0));
+                    func.instruction(&Instruction::I64Const(i64::from_le_bytes(chunk.try_into().unwrap())));
+                    func.instruction(&Instruction::I64Store(mem(at, 0)));
+                    at += 8;
+                }
+                for &byte in chunks.remainder() {
+                    func.instruction(&Instruction::LocalGet(0));
+                    func.instruction(&Instruction::I32Const(byte as i32));
+                    func.instruction(&Instruction::I32Store8(mem(at, 0)));
+                    at += 1;
+                }
+            }
+        }
+        func.instruction(&Instruction::End);
+        Ok(func)
+    }
+}
+
+// For i over 0..len (or len-1 down to 0), store the byte `value` pushes at dst + i; i is local 3
+fn byte_loop(func: &mut Function, forwards: bool, value: impl Fn(&mut Function)) {
+    let mem = MemArg { offset: 0, align: 0, memory_index: 0 };
+    if forwards {
+        func.instruction(&Instruction::I32Const(0));
+    } else {
+        func.instruction(&Instruction::LocalGet(2));
+    }
+    func.instruction(&Instruction::LocalSet(3));
+    func.instruction(&Instruction::Block(BlockType::Empty));
+    func.instruction(&Instruction::Loop(BlockType::Empty));
+    if forwards {
+        func.instruction(&Instruction::LocalGet(3));
+        func.instruction(&Instruction::LocalGet(2));
+        func.instruction(&Instruction::I32GeU);
+        func.instruction(&Instruction::BrIf(1));
+    } else {
+        func.instruction(&Instruction::LocalGet(3));
+        func.instruction(&Instruction::I32Eqz);
+        func.instruction(&Instruction::BrIf(1));
+        func.instruction(&Instruction::LocalGet(3));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::LocalSet(3));
+    }
+    func.instruction(&Instruction::LocalGet(0));
+    func.instruction(&Instruction::LocalGet(3));
+    func.instruction(&Instruction::I32Add);
+    value(func);
+    func.instruction(&Instruction::I32Store8(mem));
+    if forwards {
+        func.instruction(&Instruction::LocalGet(3));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::LocalSet(3));
+    }
+    func.instruction(&Instruction::Br(0));
+    func.instruction(&Instruction::End);
+    func.instruction(&Instruction::End);
+}
+
+/// A module `downgrade` rewrote for on-chain-v1
+pub(crate) struct Downgraded {
+    pub wasm: Vec<u8>,
+    /// Per function body, in order
+    pub edits: Vec<Edits>,
+    /// Names of the appended helpers, which follow the module's own functions
+    pub helpers: Vec<String>,
+}
+
+// What an instruction becomes: kept as it is, a call to a helper, or nothing
+enum Rewrite {
+    Keep,
+    Call(Helper),
+    Drop,
+}
+
+/// Rewrite codegen's unmetered module for on-chain-v1. Every memory.init must take its source
+/// offset and length from the two i32.const right before it, as codegen's literals and sha256's
+/// round constants do.
+pub(crate) fn downgrade(wasm: &[u8]) -> Result<Downgraded> {
+    let mut types = 0;
+    let mut functions = 0;
+    let mut segments = Vec::new();
+    let mut bodies = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::TypeSection(reader) => types = reader.count(),
+            Payload::FunctionSection(reader) => functions = reader.count(),
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    if let TypeRef::Func(_) = import?.ty {
+                        bail!("downgrade expects a module without imported functions");
+                    }
+                }
+            }
+            Payload::DataSection(reader) => {
+                for data in reader {
+                    let data = data?;
+                    if !matches!(data.kind, DataKind::Passive) {
+                        bail!("downgrade expects passive data segments only");
+                    }
+                    segments.push(data.data);
+                }
+            }
+            Payload::CodeSectionEntry(body) => bodies.push(body),
+            _ => {}
+        }
+    }
+
+    // helpers in order of first use, so the same module always downgrades the same way
+    let mut helpers: Vec<Helper> = Vec::new();
+    let mut code = Vec::new();
+    let mut edits = Vec::with_capacity(bodies.len());
+    for body in &bodies {
+        let mut ops = body.get_operators_reader()?;
+        let start = body.range().start;
+        let mut out = wasm[start..ops.original_position()].to_vec();
+        let mut body_edits = Vec::new();
+        // the last two instructions copied: original offset, offset in `out`, value of an i32.const
+        let mut recent: [Option<(usize, usize, Option<i32>)>; 2] = [None, None];
+        while !ops.eof() {
+            let (op, offset) = ops.read_with_offset()?;
+            let end = ops.original_position();
+            let (from, rewrite) = match op {
+                Operator::MemoryCopy { .. } => (offset, Rewrite::Call(Helper::Copy)),
+                Operator::MemoryFill { .. } => (offset, Rewrite::Call(Helper::Fill)),
+                Operator::I32Rotr => (offset, Rewrite::Call(Helper::Rotr32)),
+                Operator::I64Rotr => (offset, Rewrite::Call(Helper::Rotr64)),
+                Operator::DataDrop { .. } => (offset, Rewrite::Drop),
+                Operator::MemoryInit { data_index, .. } => match recent {
+                    [Some((from, out_at, Some(src))), Some((_, _, Some(len)))] => {
+                        out.truncate(out_at);
+                        (from, Rewrite::Call(Helper::Init { segment: data_index, offset: src as u32, len: len as u32 }))
+                    }
+                    _ => bail!("memory.init without a constant range can't target on-chain-v1"),
+                },
+                _ => (offset, Rewrite::Keep),
+            };
+
+            let out_at = out.len();
+            match rewrite {
+                Rewrite::Keep => out.extend_from_slice(&wasm[offset..end]),
+                Rewrite::Call(helper) => {
+                    let position = match helpers.iter().position(|&h| h == helper) {
+                        Some(position) => position,
+                        None => {
+                            helpers.push(helper);
+                            helpers.len() - 1
+                        }
+                    };
+                    Instruction::Call(functions + position as u32).encode(&mut out);
+                }
+                Rewrite::Drop => {}
+            }
+            if !matches!(rewrite, Rewrite::Keep) {
+                body_edits.push((from - start, end - from, out.len() - out_at));
+            }
+            let value = match op {
+                Operator::I32Const { value } => Some(value),
+                _ => None,
+            };
+            recent = [recent[1], Some((offset, out_at, value))];
+        }
+        out.as_slice().encode(&mut code);
+        edits.push(body_edits);
+    }
+    for helper in &helpers {
+        helper.body(&segments)?.encode(&mut code);
+    }
+    // the count goes first, and covers the helpers only known now
+    let mut counted = Vec::new();
+    ((bodies.len() + helpers.len()) as u32).encode(&mut counted);
+    counted.extend_from_slice(&code);
+    let code = counted;
+
+    let mut out = wasm[..8].to_vec();
+    for payload in Parser::new(0).parse_all(wasm) {
+        let payload = payload?;
+        let Some((id, range)) = payload.as_section() else {
+            continue;
+        };
+        match payload {
+            Payload::TypeSection(reader) => {
+                let mut content = Vec::new();
+                (types + helpers.len() as u32).encode(&mut content);
+                content.extend_from_slice(&wasm[reader.original_position()..range.end]);
+                for helper in &helpers {
+                    content.push(0x60);
+                    helper.params().encode(&mut content);
+                    helper.results().encode(&mut content);
+                }
+                write_section(&mut out, TYPE_SECTION_ID, &content);
+            }
+            Payload::FunctionSection(reader) => {
+                let mut content = Vec::new();
+                (functions + helpers.len() as u32).encode(&mut content);
+                content.extend_from_slice(&wasm[reader.original_position()..range.end]);
+                for position in 0..helpers.len() as u32 {
+                    (types + position).encode(&mut content);
+                }
+                write_section(&mut out, FUNCTION_SECTION_ID, &content);
+            }
+            Payload::CodeSectionStart { .. } => write_section(&mut out, CODE_SECTION_ID, &code),
+            // every segment was passive and every memory.init of one is now a call
+            Payload::DataCountSection { .. } | Payload::DataSection(_) => {}
+            _ => write_section(&mut out, id, &wasm[range]),
+        }
+    }
+
+    if let Err(e) = wasmparser::validate(&out) {
+        bail!("downgrading to on-chain-v1 produced an invalid module: {}", e);
+    }
+    Ok(Downgraded { wasm: out, edits, helpers: helpers.iter().map(|h| h.name()).collect() })
+}
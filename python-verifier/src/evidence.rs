@@ -0,0 +1,306 @@
+// Evidence packets for disputes escalated off-chain (governance votes, insurance claims).
+//
+// A packet is a plain ustar archive so arbitrators can open it with standard tools. It is
+// deterministic: entries are sorted by path, every header has mtime 0, uid/gid 0 and mode
+// 0644, and nothing in it depends on when or where it was assembled. `manifest.json` lists
+// the sha256 and size of every other file; its own sha256 is the manifest hash that
+// identifies the packet. `attestation.json` carries the verifier's signature over the
+// manifest hash and is the only entry the manifest does not cover.
+
+use anyhow::{Context, Result, bail};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, Signature};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+use crate::provenance::BuildInfo;
+
+pub const EVIDENCE_FORMAT_VERSION: u16 = 2;
+pub const MANIFEST_PATH: &str = "manifest.json";
+pub const ATTESTATION_PATH: &str = "attestation.json";
+
+const BLOCK: usize = 512;
+const MAX_PATH_LEN: usize = 100;
+
+/// On-chain context of the dispute
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainReferences {
+    pub chain_id: u64,
+    pub jobs_contract: String,
+    pub escrow_contract: String,
+    /// Role ("receipt", "fraud_commit", "fraud_reveal", ...) to transaction hash
+    pub transactions: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FuelLog {
+    pub fuel_limit: u64,
+    /// Fuel the verifier's replay consumed
+    pub verifier_consumed: Option<u64>,
+    /// Fuel the executor reported, when it reported any
+    pub executor_reported: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvidenceManifest {
+    pub version: u16,
+    pub job_id: String,
+    pub chain: ChainReferences,
+    /// Output hash the executor committed to on-chain
+    pub claimed_output_hash: String,
+    /// Output hash the verifier's replay produced
+    pub recomputed_output_hash: String,
+    pub fuel: FuelLog,
+    /// Build id of the verifier that replayed the job, so arbitrators can rebuild it
+    pub verifier_build: String,
+    pub files: Vec<FileEntry>,
+}
+
+/// Verifier's signature over the manifest hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestation {
+    pub manifest_hash: String,
+    pub verifier: String,
+    /// EIP-191 signature over the 32-byte manifest hash
+    pub signature: String,
+}
+
+impl Attestation {
+    pub fn verify(&self) -> Result<()> {
+        let hash = hex::decode(self.manifest_hash.trim_start_matches("0x"))?;
+        let signature: Signature = self.signature.trim_start_matches("0x").parse()
+            .context("invalid attestation signature")?;
+        let verifier: Address = self.verifier.parse().context("invalid verifier address")?;
+        if signature.recover(hash)? != verifier {
+            bail!("attestation not signed by {:?}", verifier);
+        }
+        Ok(())
+    }
+}
+
+/// Everything an arbitrator needs to replay a disputed job
+pub struct EvidencePacket {
+    job_id: [u8; 32],
+    chain: ChainReferences,
+    claimed_output_hash: String,
+    recomputed_output_hash: String,
+    fuel: FuelLog,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl EvidencePacket {
+    pub fn new(job_id: [u8; 32], chain: ChainReferences) -> Self {
+        Self {
+            job_id,
+            chain,
+            claimed_output_hash: String::new(),
+            recomputed_output_hash: String::new(),
+            fuel: FuelLog::default(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_wasm(self, wasm: &[u8]) -> Self {
+        self.with_file("job/module.wasm", wasm)
+    }
+
+    pub fn with_input(self, input: &[u8]) -> Self {
+        self.with_file("job/input.bin", input)
+    }
+
+    /// The executor's claim: its on-chain output hash and, when it published them, the output bytes
+    pub fn with_executor_output(mut self, output_hash: &str, output: Option<&[u8]>) -> Self {
+        self.claimed_output_hash = output_hash.to_string();
+        match output {
+            Some(output) => self.with_file("outputs/executor.bin", output),
+            None => self,
+        }
+    }
+
+    pub fn with_verifier_output(mut self, output_hash: &str, output: &[u8]) -> Self {
+        self.recomputed_output_hash = output_hash.to_string();
+        self.with_file("outputs/verifier.bin", output)
+    }
+
+    pub fn with_fuel_log(mut self, fuel: FuelLog) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Execution trace, e.g. a canonical zk trace in its binary encoding
+    pub fn with_trace(self, name: &str, trace: &[u8]) -> Self {
+        self.with_file(&format!("traces/{}", name), trace)
+    }
+
+    fn with_file(mut self, path: &str, data: &[u8]) -> Self {
+        self.files.insert(path.to_string(), data.to_vec());
+        self
+    }
+
+    pub fn manifest(&self) -> EvidenceManifest {
+        EvidenceManifest {
+            version: EVIDENCE_FORMAT_VERSION,
+            job_id: format!("0x{}", hex::encode(self.job_id)),
+            chain: self.chain.clone(),
+            claimed_output_hash: self.claimed_output_hash.clone(),
+            recomputed_output_hash: self.recomputed_output_hash.clone(),
+            fuel: self.fuel.clone(),
+            verifier_build: BuildInfo::current().id_hex(),
+            files: self.files.iter()
+                .map(|(path, data)| FileEntry {
+                    path: path.clone(),
+                    size: data.len() as u64,
+                    sha256: hex::encode(Sha256::digest(data)),
+                })
+                .collect(),
+        }
+    }
+
+    /// Build the archive, attested by `verifier`
+    pub fn to_archive(&self, verifier: &LocalWallet) -> Result<Vec<u8>> {
+        let manifest = manifest_bytes(&self.manifest())?;
+        let manifest_hash: [u8; 32] = Sha256::digest(&manifest).into();
+        let signature = verifier.sign_hash(ethers::utils::hash_message(manifest_hash))
+            .context("signing evidence manifest")?;
+        let attestation = Attestation {
+            manifest_hash: hex::encode(manifest_hash),
+            verifier: format!("{:?}", verifier.address()),
+            signature: format!("0x{}", hex::encode(signature.to_vec())),
+        };
+
+        let mut entries: BTreeMap<&str, &[u8]> = self.files.iter()
+            .map(|(path, data)| (path.as_str(), data.as_slice()))
+            .collect();
+        let attestation = serde_json::to_vec_pretty(&attestation)?;
+        entries.insert(MANIFEST_PATH, &manifest);
+        entries.insert(ATTESTATION_PATH, &attestation);
+
+        let mut out = Vec::new();
+        for (path, data) in entries {
+            write_entry(&mut out, path, data)?;
+        }
+        out.resize(out.len() + 2 * BLOCK, 0);
+        Ok(out)
+    }
+}
+
+// Canonical manifest encoding; struct fields serialize in declaration order
+fn manifest_bytes(manifest: &EvidenceManifest) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(manifest)?)
+}
+
+/// A packet read back from its archive
+#[derive(Debug)]
+pub struct OpenedPacket {
+    pub manifest: EvidenceManifest,
+    pub manifest_hash: [u8; 32],
+    pub attestation: Attestation,
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+/// Read an archive and check it end to end: every file matches the manifest, nothing is
+/// missing or extra, and the attestation signs this manifest
+pub fn open_archive(archive: &[u8]) -> Result<OpenedPacket> {
+    let mut files = read_entries(archive)?;
+    let manifest_raw = files.remove(MANIFEST_PATH).context("archive has no manifest")?;
+    let attestation_raw = files.remove(ATTESTATION_PATH).context("archive has no attestation")?;
+
+    let manifest: EvidenceManifest = serde_json::from_slice(&manifest_raw).context("corrupt manifest")?;
+    if manifest.version != EVIDENCE_FORMAT_VERSION {
+        bail!("unsupported evidence format version {}", manifest.version);
+    }
+    let manifest_hash: [u8; 32] = Sha256::digest(&manifest_raw).into();
+
+    let attestation: Attestation = serde_json::from_slice(&attestation_raw).context("corrupt attestation")?;
+    if attestation.manifest_hash != hex::encode(manifest_hash) {
+        bail!("attestation is for manifest {}, archive has {}", attestation.manifest_hash, hex::encode(manifest_hash));
+    }
+    attestation.verify()?;
+
+    if manifest.files.len() != files.len() {
+        bail!("manifest lists {} files, archive has {}", manifest.files.len(), files.len());
+    }
+    for entry in &manifest.files {
+        let data = files.get(&entry.path)
+            .with_context(|| format!("{} missing from archive", entry.path))?;
+        if data.len() as u64 != entry.size || hex::encode(Sha256::digest(data)) != entry.sha256 {
+            bail!("{} does not match the manifest", entry.path);
+        }
+    }
+
+    Ok(OpenedPacket { manifest, manifest_hash, attestation, files })
+}
+
+// ustar header with every variable field zeroed
+fn write_entry(out: &mut Vec<u8>, path: &str, data: &[u8]) -> Result<()> {
+    if path.len() > MAX_PATH_LEN {
+        bail!("archive path too long: {}", path);
+    }
+
+    let mut header = [0u8; BLOCK];
+    header[..path.len()].copy_from_slice(path.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+    header[136..148].copy_from_slice(b"00000000000\0");
+    header[148..156].fill(b' ');
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+    out.extend_from_slice(&header);
+    out.extend_from_slice(data);
+    out.resize(out.len().next_multiple_of(BLOCK), 0);
+    Ok(())
+}
+
+fn read_entries(archive: &[u8]) -> Result<BTreeMap<String, Vec<u8>>> {
+    let mut files = BTreeMap::new();
+    let mut offset = 0;
+
+    while offset + BLOCK <= archive.len() {
+        let header = &archive[offset..offset + BLOCK];
+        if header.iter().all(|&b| b == 0) {
+            return Ok(files);
+        }
+        if &header[257..263] != b"ustar\0" {
+            bail!("not a ustar archive");
+        }
+
+        let stored = octal(&header[148..156])?;
+        let checksum: u64 = header.iter().enumerate()
+            .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u64 } else { b as u64 })
+            .sum();
+        if stored != checksum {
+            bail!("corrupt archive header at offset {}", offset);
+        }
+
+        let name_len = header[..MAX_PATH_LEN].iter().position(|&b| b == 0).unwrap_or(MAX_PATH_LEN);
+        let path = std::str::from_utf8(&header[..name_len])?.to_string();
+        let size = octal(&header[124..136])? as usize;
+        let start = offset + BLOCK;
+        let data = archive.get(start..start + size).context("truncated archive")?;
+        if files.insert(path.clone(), data.to_vec()).is_some() {
+            bail!("duplicate archive entry {}", path);
+        }
+        offset = start + size.next_multiple_of(BLOCK);
+    }
+
+    bail!("archive has no end marker")
+}
+
+fn octal(field: &[u8]) -> Result<u64> {
+    let text = std::str::from_utf8(field)?.trim_matches(|c: char| c == '\0' || c == ' ');
+    u64::from_str_radix(text, 8).with_context(|| format!("bad octal field {:?}", text))
+}
@@ -0,0 +1,66 @@
+// Why the executor rejected or failed a job, as data rather than a message. The executor raises
+// these through anyhow like any other error, and a trap still comes back as `Trapped` with its
+// partial output; `ExecutorError::of` recovers the kind from either, so the queue, the API and
+// websocket payloads can report it without matching on error text.
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::{RuntimeError, SourceLocation};
+use crate::Trapped;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExecutorError {
+    /// The source, input or module broke a determinism or size rule before anything ran
+    #[error("validation failed: {message}")]
+    ValidationFailed { message: String },
+    /// The compiler couldn't turn the source into a module
+    #[error("compile error: {message}")]
+    CompileError { message: String },
+    #[error("out of fuel after {fuel_consumed}")]
+    OutOfFuel { fuel_consumed: u64 },
+    #[error("out of memory after {fuel_consumed} fuel")]
+    OutOfMemory { fuel_consumed: u64 },
+    /// Any other trap; `code` when the module said why, `location` when it was compiled Python
+    #[error("trapped{}", code.map(|c| format!(": {}", c)).unwrap_or_default())]
+    Trap {
+        code: Option<RuntimeError>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        location: Option<SourceLocation>,
+        fuel_consumed: u64,
+    },
+    #[error("output exceeds {limit} bytes")]
+    OutputTooLarge { limit: u32 },
+    #[error("wall-clock timeout after {fuel_consumed} fuel")]
+    Timeout { fuel_consumed: u64 },
+}
+
+impl ExecutorError {
+    pub fn validation(err: Error) -> Self {
+        ExecutorError::ValidationFailed { message: err.to_string() }
+    }
+
+    pub fn compile(err: Error) -> Self {
+        ExecutorError::CompileError { message: err.to_string() }
+    }
+
+    /// The kind of a job's failure, when the executor knew it; `None` for anything else
+    /// (chain, network, storage)
+    pub fn of(err: &Error) -> Option<Self> {
+        if let Some(err) = err.downcast_ref::<ExecutorError>() {
+            return Some(err.clone());
+        }
+        err.downcast_ref::<Trapped>().map(Self::trapped)
+    }
+
+    fn trapped(trapped: &Trapped) -> Self {
+        let fuel_consumed = trapped.output.fuel_consumed;
+        match trapped.output.error {
+            Some(RuntimeError::OutOfGas) => ExecutorError::OutOfFuel { fuel_consumed },
+            Some(RuntimeError::OutOfMemory) => ExecutorError::OutOfMemory { fuel_consumed },
+            Some(RuntimeError::Timeout) => ExecutorError::Timeout { fuel_consumed },
+            code => ExecutorError::Trap { code, location: trapped.output.trap.clone(), fuel_consumed },
+        }
+    }
+}
@@ -0,0 +1,309 @@
+// Protocol indexer: job lifecycle and dispute events of the jobs, escrow and bisection contracts,
+// stored by (block, log index) so re-scanning a range, or backfilling from deployment on a new
+// node, stores each event once. Health metrics are folded from the stored events on request and
+// windowed by block timestamp, never by when the node happened to see an event, so a backfill
+// shows up as history rather than as a spike.
+//
+// A dispute is a bisection challenge or a direct fraud proof against a receipt; it succeeded when
+// the executor was slashed for fraud.
+
+use anyhow::Result;
+use ethers::types::{Address, Log, H256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::certus_integration::CertusIntegration;
+
+const CURSOR_KEY: &[u8] = b"cursor";
+const EVENT_PREFIX: &[u8] = b"event:";
+
+/// Blocks fetched per eth_getLogs call while catching up
+pub const SCAN_CHUNK_BLOCKS: u64 = 2_000;
+
+/// Event signatures the indexer reads, by the contract emitting them
+pub const INDEXED_EVENTS: [&str; 7] = [
+    // CertusJobs
+    "JobAccepted(bytes32,address,uint256)",
+    "ReceiptSubmitted(bytes32,bytes32,bytes)",
+    "JobFinalized(bytes32,address,uint256)",
+    // CertusEscrow
+    "TimeoutClaimed(bytes32,address,uint256)",
+    "FraudDetected(bytes32,address,address,uint256)",
+    // CertusBisection
+    "BisectionInitiated(bytes32,address,uint256)",
+    "BisectionResolved(bytes32,bool)",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProtocolEvent {
+    Accepted { executor: Address },
+    Receipt,
+    Finalized { executor: Address },
+    TimeoutClaimed { executor: Address },
+    FraudDetected { executor: Address },
+    Challenged { challenger: Address },
+    ChallengeResolved { fraud: bool },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedEvent {
+    pub job_id: H256,
+    pub block: u64,
+    pub log_index: u64,
+    /// Timestamp of the block
+    pub timestamp: u64,
+    pub event: ProtocolEvent,
+}
+
+impl IndexedEvent {
+    /// Decode one of INDEXED_EVENTS; None for any other log or a pending one
+    pub fn decode(log: &Log, timestamp: u64) -> Option<Self> {
+        let topic = |i: usize| log.topics.get(i).copied();
+        let address = |i: usize| topic(i).map(Address::from);
+
+        let signature = topic(0)?;
+        let name = INDEXED_EVENTS.iter()
+            .find(|sig| H256(ethers::utils::keccak256(sig.as_bytes())) == signature)?;
+        let event = match name.split('(').next()? {
+            "JobAccepted" => ProtocolEvent::Accepted { executor: address(2)? },
+            "ReceiptSubmitted" => ProtocolEvent::Receipt,
+            "JobFinalized" => ProtocolEvent::Finalized { executor: address(2)? },
+            "TimeoutClaimed" => ProtocolEvent::TimeoutClaimed { executor: address(2)? },
+            "FraudDetected" => ProtocolEvent::FraudDetected { executor: address(2)? },
+            "BisectionInitiated" => ProtocolEvent::Challenged { challenger: address(2)? },
+            "BisectionResolved" => ProtocolEvent::ChallengeResolved {
+                fraud: log.data.get(..32)?.iter().any(|&b| b != 0),
+            },
+            _ => return None,
+        };
+
+        Some(Self {
+            job_id: topic(1)?,
+            block: log.block_number?.as_u64(),
+            log_index: log.log_index?.as_u64(),
+            timestamp,
+            event,
+        })
+    }
+
+    fn key(&self) -> Vec<u8> {
+        [EVENT_PREFIX, &self.block.to_be_bytes(), &self.log_index.to_be_bytes()].concat()
+    }
+}
+
+/// Protocol health over the indexed events of a window
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ProtocolStats {
+    /// Start of the window (block timestamp); 0 for all indexed history
+    pub since: u64,
+    /// Last block indexed
+    pub indexed_block: Option<u64>,
+    pub receipts: u64,
+    /// Jobs paid out: finalized, timed out in the executor's favour, or slashed
+    pub settled: u64,
+    pub disputes: u64,
+    /// Disputes that ended with the executor slashed
+    pub frauds: u64,
+    /// Disputes per receipt submitted
+    pub dispute_rate: f64,
+    /// Frauds per dispute
+    pub fraud_success_rate: f64,
+    /// Mean time from receipt to payout of undisputed jobs with both in the window
+    pub avg_finalization_latency_secs: Option<f64>,
+    /// Executors that accepted jobs
+    pub executors: u64,
+    /// Share of accepted jobs taken by the busiest executor
+    pub top_executor_share: f64,
+    /// Herfindahl index of accepted jobs per executor: 1 when one executor takes everything
+    pub executor_hhi: f64,
+    /// Receipts, disputes and frauds of jobs this node executed
+    pub our_receipts: u64,
+    pub our_disputes: u64,
+    pub our_frauds: u64,
+}
+
+#[derive(Default)]
+struct JobTrace {
+    executor: Option<Address>,
+    receipt_at: Option<u64>,
+    paid_at: Option<u64>,
+    settled: bool,
+    disputed: bool,
+    fraud: bool,
+}
+
+impl ProtocolStats {
+    /// Fold `events` (in chain order) into stats, counting `executor`'s jobs as ours
+    pub fn compute(events: &[IndexedEvent], executor: Address, since: u64) -> Self {
+        let mut jobs: HashMap<H256, JobTrace> = HashMap::new();
+        for event in events.iter().filter(|e| e.timestamp >= since) {
+            let job = jobs.entry(event.job_id).or_default();
+            match event.event {
+                ProtocolEvent::Accepted { executor } => job.executor = Some(executor),
+                ProtocolEvent::Receipt => job.receipt_at = Some(event.timestamp),
+                ProtocolEvent::Finalized { executor } | ProtocolEvent::TimeoutClaimed { executor } => {
+                    job.executor.get_or_insert(executor);
+                    job.paid_at = Some(event.timestamp);
+                    job.settled = true;
+                }
+                ProtocolEvent::FraudDetected { executor } => {
+                    job.executor.get_or_insert(executor);
+                    job.settled = true;
+                    job.disputed = true;
+                    job.fraud = true;
+                }
+                ProtocolEvent::Challenged { .. } => job.disputed = true,
+                ProtocolEvent::ChallengeResolved { .. } => {}
+            }
+        }
+
+        let mut stats = Self { since, ..Default::default() };
+        let mut accepted: HashMap<Address, u64> = HashMap::new();
+        let mut latencies = Vec::new();
+        for job in jobs.values() {
+            let ours = job.executor == Some(executor);
+            stats.receipts += job.receipt_at.is_some() as u64;
+            stats.settled += job.settled as u64;
+            stats.disputes += job.disputed as u64;
+            stats.frauds += job.fraud as u64;
+            stats.our_receipts += (ours && job.receipt_at.is_some()) as u64;
+            stats.our_disputes += (ours && job.disputed) as u64;
+            stats.our_frauds += (ours && job.fraud) as u64;
+            if let Some(executor) = job.executor {
+                *accepted.entry(executor).or_default() += 1;
+            }
+            if let (Some(receipt), Some(paid), false) = (job.receipt_at, job.paid_at, job.disputed) {
+                latencies.push(paid.saturating_sub(receipt) as f64);
+            }
+        }
+
+        let ratio = |n: u64, d: u64| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        stats.dispute_rate = ratio(stats.disputes, stats.receipts);
+        stats.fraud_success_rate = ratio(stats.frauds, stats.disputes);
+        if !latencies.is_empty() {
+            stats.avg_finalization_latency_secs = Some(latencies.iter().sum::<f64>() / latencies.len() as f64);
+        }
+
+        let total: u64 = accepted.values().sum();
+        stats.executors = accepted.len() as u64;
+        stats.top_executor_share = ratio(accepted.values().copied().max().unwrap_or(0), total);
+        stats.executor_hhi = accepted.values().map(|&n| ratio(n, total).powi(2)).sum();
+        stats
+    }
+
+    /// Prometheus text exposition
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, help: &str, value: String| {
+            out.push_str(&format!("# HELP {} {}\n# TYPE {} gauge\n{} {}\n", name, help, name, name, value));
+        };
+
+        metric("certus_protocol_indexed_block", "Last block the protocol indexer has read",
+            self.indexed_block.unwrap_or(0).to_string());
+        metric("certus_protocol_receipts", "Receipts submitted in the stats window", self.receipts.to_string());
+        metric("certus_protocol_settled", "Jobs paid out or slashed in the stats window", self.settled.to_string());
+        metric("certus_protocol_disputes", "Jobs challenged in the stats window", self.disputes.to_string());
+        metric("certus_protocol_frauds", "Disputes that slashed the executor", self.frauds.to_string());
+        metric("certus_protocol_dispute_rate", "Disputes per receipt", self.dispute_rate.to_string());
+        metric("certus_protocol_fraud_success_rate", "Frauds per dispute", self.fraud_success_rate.to_string());
+        metric("certus_protocol_finalization_latency_seconds", "Mean time from receipt to payout of undisputed jobs",
+            self.avg_finalization_latency_secs.unwrap_or(0.0).to_string());
+        metric("certus_protocol_executors", "Executors that accepted jobs", self.executors.to_string());
+        metric("certus_protocol_top_executor_share", "Share of jobs taken by the busiest executor",
+            self.top_executor_share.to_string());
+        metric("certus_protocol_executor_hhi", "Herfindahl index of jobs per executor", self.executor_hhi.to_string());
+        metric("certus_protocol_our_receipts", "Receipts of jobs this node executed", self.our_receipts.to_string());
+        metric("certus_protocol_our_disputes", "Disputes against jobs this node executed", self.our_disputes.to_string());
+        metric("certus_protocol_our_frauds", "Disputes that slashed this node", self.our_frauds.to_string());
+
+        out
+    }
+}
+
+/// Protocol events indexed from the chain, in a sled db
+pub struct ProtocolIndexer {
+    db: sled::Db,
+    executor: Address,
+    window: u64,
+}
+
+impl ProtocolIndexer {
+    /// `executor` is this node's address; stats cover the last `window` seconds (0 for all history)
+    pub fn open(path: &str, executor: Address, window: u64) -> Result<Self> {
+        Ok(Self { db: sled::open(path)?, executor, window })
+    }
+
+    /// Store `events`; ones already stored are left as they are
+    pub fn record(&self, events: &[IndexedEvent]) -> Result<usize> {
+        let mut added = 0;
+        for event in events {
+            let value = serde_json::to_vec(event)?;
+            if self.db.compare_and_swap(event.key(), None as Option<&[u8]>, Some(value))?.is_ok() {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    pub fn events(&self) -> Result<Vec<IndexedEvent>> {
+        self.db.scan_prefix(EVENT_PREFIX)
+            .map(|item| Ok(serde_json::from_slice(&item?.1)?))
+            .collect()
+    }
+
+    /// Stats over `window` seconds before `now`, or the configured window when None
+    pub fn stats(&self, now: u64, window: Option<u64>) -> Result<ProtocolStats> {
+        let since = match window.unwrap_or(self.window) {
+            0 => 0,
+            window => now.saturating_sub(window),
+        };
+        let mut stats = ProtocolStats::compute(&self.events()?, self.executor, since);
+        stats.indexed_block = self.cursor()?.and_then(|next| next.checked_sub(1));
+        Ok(stats)
+    }
+
+    /// First block not yet indexed
+    pub fn cursor(&self) -> Result<Option<u64>> {
+        Ok(self.db.get(CURSOR_KEY)?
+            .and_then(|v| v.as_ref().try_into().ok())
+            .map(u64::from_be_bytes))
+    }
+
+    pub fn set_cursor(&self, block: u64) -> Result<()> {
+        self.db.insert(CURSOR_KEY, &block.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Index from the cursor (`start_block`, or the head, on first run) up to the head
+    pub async fn sync(&self, certus: &CertusIntegration, start_block: Option<u64>) -> Result<()> {
+        let head = certus.block_number().await?;
+        let mut from = match self.cursor()? {
+            Some(block) => block,
+            None => start_block.unwrap_or(head),
+        };
+        while from <= head {
+            let to = (from + SCAN_CHUNK_BLOCKS - 1).min(head);
+            let added = self.record(&certus.protocol_events(from, to).await?)?;
+            if added > 0 {
+                log::debug!("Indexed {} protocol events in blocks {}-{}", added, from, to);
+            }
+            self.set_cursor(to + 1)?;
+            from = to + 1;
+        }
+        Ok(())
+    }
+
+    pub fn spawn(self: Arc<Self>, certus: Arc<CertusIntegration>, start_block: Option<u64>, interval: Duration) {
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.sync(&certus, start_block).await {
+                    log::error!("Protocol indexing failed: {}", e);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+}
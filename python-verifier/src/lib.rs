@@ -1,7 +1,6 @@
 use wasmtime::*;
 use anyhow::{Result, bail, Context};
 use serde::{Deserialize, Serialize};
-use sha2::{Sha256, Digest};
 
 pub mod compiler;
 pub mod python_compiler;
@@ -12,13 +11,47 @@ pub mod queue;
 pub mod certus_integration;
 pub mod reliability;
 pub mod validation;
-
-use python_compiler::PythonCompiler;
-use validation::{PythonValidator, validate_json_input, validate_output};
+pub mod redaction;
+pub mod artifacts;
+pub mod receipts;
+pub mod notarization;
+pub mod indexer;
+pub mod evidence;
+pub mod profiles;
+pub mod chain_params;
+pub mod outputs;
+pub mod canary;
+pub mod provenance;
+pub mod rate_limit;
+pub mod cluster;
+pub mod playground;
+pub mod vectors;
+pub mod module_cache;
+pub mod pool;
+pub mod executor_error;
+pub mod step_trace;
+#[cfg(feature = "zk-trace")]
+pub mod zk_trace;
+
+use compiler::{PythonCompiler, ASSERT_SLOT, ASSERTION_FAILED, STDOUT_CAPACITY, STDOUT_EXPORT, DEFAULT_MAX_CALL_DEPTH, CompileOptions, ModuleLimits, RuntimeError, SourceLocation, SourceMap, abi::{self, ExportedLimits}, determinism, fuel};
+use profiles::ExecutionProfile;
+use executor_error::ExecutorError;
+
+/// Memory pages a job may grow to
+pub const MAX_MEMORY_PAGES: u32 = 256;
+/// Native stack available to a job
+pub const MAX_WASM_STACK: usize = 1024 * 1024;
+/// Where the host writes a job's input as a record: its length as a little-endian u32, then
+/// the bytes, which `python_main` gets a pointer to. Output comes back in the same shape.
+pub const INPUT_RECORD: u32 = 0x1000;
+use validation::{PythonValidator, ValidationPolicy, validate_json_input, validate_output};
 
 pub struct PythonExecutor {
     engine: Engine,
     compiler: PythonCompiler,
+    max_call_depth: u32,
+    modules: module_cache::ModuleCache,
+    policy: ValidationPolicy,
 }
 
 impl PythonExecutor {
@@ -34,12 +67,41 @@ impl PythonExecutor {
 
         // Memory bounds
         config.static_memory_maximum_size(64 * 1024 * 1024);
-        config.max_wasm_stack(1024 * 1024);
+        config.max_wasm_stack(MAX_WASM_STACK);
 
         let engine = Engine::new(&config)?;
         let compiler = PythonCompiler::new();
 
-        Ok(Self { engine, compiler })
+        Ok(Self { engine, compiler, max_call_depth: DEFAULT_MAX_CALL_DEPTH, modules: module_cache::ModuleCache::default(), policy: ValidationPolicy::default() })
+    }
+
+    /// Trap deterministically once user functions nest deeper than `depth`
+    pub fn with_max_call_depth(mut self, depth: u32) -> Self {
+        self.compiler = PythonCompiler::new().with_max_call_depth(depth);
+        self.max_call_depth = depth;
+        self
+    }
+
+    pub fn max_call_depth(&self) -> u32 {
+        self.max_call_depth
+    }
+
+    /// Validate job sources against `policy` instead of the default
+    pub fn with_validation_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Keep compiled modules in `store`'s aot_cache class as well as in memory, so a restart
+    /// doesn't recompile jobs it has run before
+    pub fn with_module_store(mut self, store: std::sync::Arc<artifacts::ArtifactStore>) -> Self {
+        self.modules = module_cache::ModuleCache::default().with_store(store);
+        self
+    }
+
+    /// How often runs found their module already compiled
+    pub fn module_cache_stats(&self) -> module_cache::ModuleCacheStats {
+        self.modules.stats()
     }
 
     pub fn execute(
@@ -47,89 +109,277 @@ impl PythonExecutor {
         python_code: &str,
         input_json: &str,
         fuel_limit: u64,
+    ) -> Result<ExecutionOutput> {
+        self.execute_with_profile(python_code, input_json, &ExecutionProfile { fuel_limit, ..Default::default() })
+    }
+
+    /// Compile and run under a profile's builtin set and sandbox limits
+    pub fn execute_with_profile(
+        &mut self,
+        python_code: &str,
+        input_json: &str,
+        profile: &ExecutionProfile,
+    ) -> Result<ExecutionOutput> {
+        self.execute_with_options(python_code, input_json, profile, CompileOptions::default())
+    }
+
+    /// `execute_with_profile` compiling with `options` instead of the defaults
+    pub fn execute_with_options(
+        &mut self,
+        python_code: &str,
+        input_json: &str,
+        profile: &ExecutionProfile,
+        options: CompileOptions,
     ) -> Result<ExecutionOutput> {
         // Validate
-        PythonValidator::validate_code(python_code)?;
-        validate_json_input(input_json)?;
-        self.validate_python(python_code)?;
+        self.validate_python(python_code).map_err(ExecutorError::validation)?;
+        validate_json_input(input_json).map_err(ExecutorError::validation)?;
 
-        // compile
-        let wasm_module = self.compiler.compile(python_code)?;
-        self.validate_wasm(&wasm_module)?;
+        let (wasm_module, source_map) = self.compile_with_source_map(python_code, profile, options)
+            .map_err(ExecutorError::compile)?;
+        self.validate_wasm(&wasm_module, profile.max_memory_pages).map_err(ExecutorError::validation)?;
+
+        self.run_wasm(&wasm_module, input_json, profile, Some(&source_map))
+    }
 
+    /// Compile exactly as a job under `profile` would be; every path that turns a job's source
+    /// into Wasm goes through here so they cannot drift apart
+    pub fn compile(&mut self, python_code: &str, profile: &ExecutionProfile, options: CompileOptions) -> Result<Vec<u8>> {
+        Ok(self.compile_with_source_map(python_code, profile, options)?.0)
+    }
+
+    /// `compile`, with the map from the module's code back to lines of `python_code`
+    pub fn compile_with_source_map(
+        &mut self,
+        python_code: &str,
+        profile: &ExecutionProfile,
+        options: CompileOptions,
+    ) -> Result<(Vec<u8>, SourceMap)> {
+        // the module is built for the job's fuel and memory; a restricted builtin set or other
+        // options get their own compiler so the shared cache stays unrestricted and default
+        let limits = profile.resource_limits();
+        match &profile.allowed_builtins {
+            None if options == CompileOptions::default() => self.compiler.compile_with_limits(python_code, &limits),
+            allowed => PythonCompiler::new()
+                .with_max_call_depth(self.max_call_depth)
+                .with_allowed_builtins(allowed.clone())
+                .with_options(options)
+                .with_resource_limits(limits)
+                .compile_with_source_map(python_code),
+        }
+    }
+
+    /// Run a client-supplied module (Rust, C, ...) under the same ABI as compiled Python:
+    /// `python_main(input_ptr, input_len) -> output_ptr`, where output_ptr addresses a record
+    /// of the output's length as a little-endian u32 followed by its bytes
+    pub fn execute_wasm(
+        &mut self,
+        wasm: &[u8],
+        input_json: &str,
+        fuel_limit: u64,
+    ) -> Result<ExecutionOutput> {
+        self.execute_wasm_with_profile(wasm, input_json, &ExecutionProfile { fuel_limit, ..Default::default() })
+    }
+
+    pub fn execute_wasm_with_profile(
+        &mut self,
+        wasm: &[u8],
+        input_json: &str,
+        profile: &ExecutionProfile,
+    ) -> Result<ExecutionOutput> {
+        validate_json_input(input_json).map_err(ExecutorError::validation)?;
+        let wasm_module = self.prepare_wasm(wasm).map_err(ExecutorError::validation)?;
+        self.validate_wasm(&wasm_module, profile.max_memory_pages).map_err(ExecutorError::validation)?;
+        self.run_wasm(&wasm_module, input_json, profile, None)
+    }
+
+    /// Check a client-supplied module and meter it; the result is what gets stored and run
+    pub fn prepare_wasm(&self, wasm: &[u8]) -> Result<Vec<u8>> {
+        determinism::validate(wasm)?;
+        let wasm_module = fuel::instrument(wasm)?;
+        // limits a client declared are replaced by what the module actually needs
+        let wasm_module = ModuleLimits::analyze(&wasm_module, self.max_call_depth)?.replace_in(&wasm_module)?;
+        self.validate_wasm(&wasm_module, MAX_MEMORY_PAGES)?;
+        Ok(wasm_module)
+    }
+
+    // `source_map` locates traps in compiled Python; client modules come without one
+    fn run_wasm(
+        &self,
+        wasm_module: &[u8],
+        input_json: &str,
+        profile: &ExecutionProfile,
+        source_map: Option<&SourceMap>,
+    ) -> Result<ExecutionOutput> {
         // sandbox setup
         let mut store = Store::new(&self.engine, ());
-        let fuel = fuel_limit.min(100_000_000).max(1_000);
+        let limits = profile.resource_limits();
+        let fuel = limits.fuel_limit;
+        // a compiled module must have been built for the fuel and memory the job runs under
+        if let Some(exported) = ExportedLimits::read(wasm_module)? {
+            exported.check(fuel, limits.memory_pages).map_err(ExecutorError::validation)?;
+        }
         store.set_fuel(fuel)?;
-        store.set_epoch_deadline(100);
-
-        let module = Module::new(&self.engine, &wasm_module)?;
-        let instance = self.instantiate(&mut store, &module)?;
-
-        // Execute with panic guard
-        let output = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            self.run_module(&mut store, &instance, input_json)
-        })) {
+        store.set_epoch_deadline(1);
+
+        let module = self.modules.get(&self.engine, wasm_module)?;
+        let instance = self.instantiate(&mut store, &module, limits.memory_pages)?;
+
+        // Execute with panic guard, interrupted once the wall-clock budget runs out
+        let mut watchdog = WallClock::new(&self.engine, profile.max_wall_clock_ms);
+        let output = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            self.run_module(&mut store, &instance, input_json, limits.max_output_bytes, &mut watchdog)
+        }));
+        let timed_out = watchdog.stop();
+        let (output, stdout) = match output {
             Ok(Ok(result)) => result,
-            Ok(Err(e)) => bail!("execution failed: {}", e),
+            Ok(Err(e)) if e.is::<ExecutorError>() => return Err(e),
+            Ok(Err(e)) => {
+                let location = source_map.and_then(|map| trap_location(map, &e));
+                // an interrupted run traps wherever it was; the clock, not the trap, is why
+                let (error, message) = if timed_out {
+                    (Some(RuntimeError::Timeout), format!("exceeded {} ms wall clock", profile.max_wall_clock_ms))
+                } else {
+                    (runtime_error(&mut store, &instance, &e, source_map.is_some()), e.to_string())
+                };
+                if location.is_none() && error.is_none() {
+                    bail!("execution failed: {}", e);
+                }
+                return Err(Trapped {
+                    output: ExecutionOutput {
+                        result: String::new(),
+                        stdout: Vec::new(),
+                        output_hash: String::new(),
+                        fuel_consumed: fuel - store.get_fuel().unwrap_or(0),
+                        success: false,
+                        trap: location,
+                        error,
+                    },
+                    message,
+                }.into());
+            }
             Err(_) => bail!("panic during execution"),
         };
 
-        validate_output(&output)?;
-
-        // Output hash
-        let mut hasher = Sha256::new();
-        hasher.update(output.as_bytes());
-        let hash = hex::encode(hasher.finalize());
+        validate_output(&output).map_err(ExecutorError::validation)?;
 
         Ok(ExecutionOutput {
+            output_hash: output_hash(&output, &stdout),
             result: output,
-            output_hash: hash,
+            stdout,
             fuel_consumed: fuel - store.get_fuel().unwrap_or(0),
             success: true,
+            trap: None,
+            error: None,
         })
     }
 
-    pub fn validate_python(&self, code: &str) -> Result<()> {
-        // only json/hashlib imports
-        if code.contains("import ") || code.contains("from ") {
-            let allowed = code.contains("import json") ||
-                         code.contains("import hashlib") ||
-                         code.contains("from hashlib");
-            if !allowed {
-                bail!("only json and hashlib imports allowed");
-            }
-        }
+    /// Re-run a prepared module as its job runs, cut into steps of `fuel_interval` fuel, and
+    /// commit to the state after each; the trace answers a bisection challenge on the job
+    pub fn execute_stepped(
+        &self,
+        wasm_module: &[u8],
+        input_json: &str,
+        profile: &ExecutionProfile,
+        fuel_interval: u64,
+    ) -> Result<step_trace::StepTrace> {
+        validate_json_input(input_json).map_err(ExecutorError::validation)?;
+        let limits = profile.resource_limits();
+        let module = self.modules.get(&self.engine, wasm_module)?;
+        step_trace::record(limits.fuel_limit, fuel_interval, |fuel| {
+            self.checkpoint(&module, input_json, limits.memory_pages, fuel)
+        })
+    }
 
-        // No file I/O
-        if code.contains("open(") || code.contains("file(") {
-            bail!("file operations not allowed");
-        }
+    // The job's state once it returns, traps, or burns `fuel`, whichever comes first
+    fn checkpoint(&self, module: &Module, input: &str, memory_pages: u32, fuel: u64) -> Result<step_trace::Checkpoint> {
+        let mut store = Store::new(&self.engine, ());
+        store.set_fuel(fuel)?;
+        store.set_epoch_deadline(1);
+        let instance = self.instantiate(&mut store, module, memory_pages)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .context("missing memory export")?;
 
-        // No network
-        if code.contains("urllib") || code.contains("requests") || code.contains("socket") {
-            bail!("network operations not allowed");
-        }
+        let run = if let Ok(run) = instance.get_typed_func::<(i32, i32), i32>(&mut store, "python_main") {
+            let args = write_input(&mut store, &memory, input)?;
+            run.call(&mut store, args)
+        } else {
+            // compiled Python: main takes no input
+            let main = instance
+                .get_typed_func::<(), i32>(&mut store, "main")
+                .context("missing python_main export")?;
+            main.call(&mut store, ())
+        };
+        let (status, instruction) = match run {
+            Ok(_) => (step_trace::StepStatus::Returned, None),
+            Err(e) => {
+                let status = match e.downcast_ref::<Trap>() {
+                    Some(Trap::OutOfFuel) => step_trace::StepStatus::Running,
+                    _ => step_trace::StepStatus::Trapped,
+                };
+                let offset = e.downcast_ref::<WasmBacktrace>()
+                    .and_then(|backtrace| backtrace.frames().first()?.module_offset());
+                (status, offset.map(|offset| offset as u32))
+            }
+        };
+        let fuel_consumed = fuel - store.get_fuel()?;
+        Ok(step_trace::Checkpoint {
+            state: step_trace::state_hash(memory.data(&store), fuel_consumed, status),
+            fuel_consumed,
+            status,
+            instruction,
+        })
+    }
 
-        // no time/random
-        if code.contains("time.") || code.contains("random.") || code.contains("datetime") {
-            bail!("time/random not allowed");
-        }
+    /// Experimental: record a canonical fuel-checkpoint trace of the program, over the same
+    /// states as `execute_stepped`
+    #[cfg(feature = "zk-trace")]
+    pub fn execute_traced(
+        &mut self,
+        python_code: &str,
+        fuel_limit: u64,
+        fuel_interval: u64,
+    ) -> Result<zk_trace::CanonicalTrace> {
+        self.validate_python(python_code)?;
 
-        // no eval/exec
-        if code.contains("eval(") || code.contains("exec(") || code.contains("compile(") {
-            bail!("dynamic code execution not allowed");
-        }
+        let profile = ExecutionProfile { fuel_limit, ..Default::default() };
+        let wasm_module = self.compile(python_code, &profile, CompileOptions::default())?;
+        self.validate_wasm(&wasm_module, profile.max_memory_pages)?;
+        self.trace_wasm(&wasm_module, &profile, fuel_interval)
+    }
 
-        // no subprocess
-        if code.contains("subprocess") || code.contains("os.system") {
-            bail!("subprocess not allowed");
-        }
+    /// Experimental: `execute_traced` for a prepared module exporting `main`, such as a job's
+    /// stored one
+    #[cfg(feature = "zk-trace")]
+    pub fn trace_wasm(
+        &self,
+        wasm_module: &[u8],
+        profile: &ExecutionProfile,
+        fuel_interval: u64,
+    ) -> Result<zk_trace::CanonicalTrace> {
+        let limits = profile.resource_limits();
+        let output = self.run_wasm(wasm_module, "{}", profile, None)?;
+        let output = output.result.parse().context("main did not return an integer")?;
+        let module = self.modules.get(&self.engine, wasm_module)?;
+        zk_trace::record(wasm_module, output, limits.fuel_limit, fuel_interval, |fuel| {
+            self.checkpoint(&module, "{}", limits.memory_pages, fuel)
+        })
+    }
 
-        Ok(())
+    /// Compile and run the determinism canary with this executor's compiler and engine
+    pub fn run_canary(&mut self) -> Result<canary::CanaryRun> {
+        let wasm_module = self.compiler.compile(canary::CANARY_SOURCE)?;
+        self.validate_wasm(&wasm_module, MAX_MEMORY_PAGES)?;
+        canary::run(&self.engine, &wasm_module)
     }
 
-    fn validate_wasm(&self, wasm: &[u8]) -> Result<()> {
+    /// Check a job's source against this executor's validation policy
+    pub fn validate_python(&self, code: &str) -> Result<()> {
+        PythonValidator::validate_code_with(code, &self.policy)
+    }
+
+    fn validate_wasm(&self, wasm: &[u8], max_memory_pages: u32) -> Result<()> {
         // 24KB on-chain limit
         const MAX_SIZE: usize = 24 * 1024;
         if wasm.len() > MAX_SIZE {
@@ -141,25 +391,34 @@ impl PythonExecutor {
             bail!("invalid wasm magic");
         }
 
-        // Float opcode validation disabled - range was too broad and caught valid opcodes like local.get (0x60)
-        // TODO: Fix to check only actual float opcodes: f32.const (0x43), f64.const (0x44), f32/f64 operations (0x8B-0xC4)
-        // for (i, &byte) in wasm.iter().enumerate().skip(8) {
-        //     match byte {
-        //         0x43 | 0x44 | 0x8B..=0xC4 => {
-        //             bail!("float opcode 0x{:02x} at offset {}", byte, i)
-        //         }
-        //         _ => {}
-        //     }
-        // }
+        // only the host functions the linker provides, whatever produced the module
+        determinism::check_imports(wasm)?;
+
+        // declared limits must fit before anything runs
+        ModuleLimits::read(wasm)?
+            .context("module declares no resource limits")?
+            .check(max_memory_pages, MAX_WASM_STACK as u64)?;
+
+        // float, SIMD and atomic instructions, read from the code section
+        determinism::check_opcodes(wasm)?;
 
         Ok(())
     }
 
-    fn instantiate(&self, store: &mut Store<()>, module: &Module) -> Result<Instance> {
+    fn instantiate(&self, store: &mut Store<()>, module: &Module, max_memory_pages: u32) -> Result<Instance> {
         let mut linker = Linker::new(&self.engine);
 
-        // minimal env
-        let memory_ty = MemoryType::new(1, Some(256)); // 16MB max
+        // minimal env: as much memory as the module imports up front, growable to the job's
+        let minimum = module.imports()
+            .find_map(|import| match import.ty() {
+                ExternType::Memory(ty) if (import.module(), import.name()) == ("env", "memory") => Some(ty.minimum()),
+                _ => None,
+            })
+            .unwrap_or(1);
+        if minimum > max_memory_pages as u64 {
+            bail!("module imports {} memory pages, job allows {}", minimum, max_memory_pages);
+        }
+        let memory_ty = MemoryType::new(minimum as u32, Some(max_memory_pages));
         let memory = Memory::new(&mut *store, memory_ty)?;
         linker.define(&mut *store, "env", "memory", memory)?;
 
@@ -177,38 +436,225 @@ impl PythonExecutor {
         store: &mut Store<()>,
         instance: &Instance,
         input: &str,
-    ) -> Result<String> {
-        let run = instance
-            .get_typed_func::<(i32, i32), i32>(&mut *store, "python_main")
-            .context("missing python_main export")?;
-
+        max_output_bytes: u32,
+        watchdog: &mut WallClock,
+    ) -> Result<(String, Vec<String>)> {
         let memory = instance
             .get_memory(&mut *store, "memory")
             .context("missing memory export")?;
+        let failed = |store: &Store<()>, err: anyhow::Error| match assertion_failure(memory.data(store)) {
+            Some(msg_hash) => err.context(format!("assertion failed (message hash {:#010x})", msg_hash)),
+            None => err,
+        };
+
+        let output = if let Ok(run) = instance.get_typed_func::<(i32, i32), i32>(&mut *store, "python_main") {
+            let args = write_input(store, &memory, input)?;
+            watchdog.arm();
+            let output_ptr = match run.call(&mut *store, args) {
+                Ok(ptr) => ptr as u32,
+                Err(err) => return Err(failed(&*store, err)),
+            };
+            let output = read_output(memory.data(&*store), output_ptr, max_output_bytes)?;
+            String::from_utf8(output.to_vec())
+                .context("invalid utf-8 in output")?
+        } else {
+            // compiled Python: main takes no input and returns OUTPUT
+            let main = instance
+                .get_typed_func::<(), i32>(&mut *store, "main")
+                .context("missing python_main export")?;
+            watchdog.arm();
+            match main.call(&mut *store, ()) {
+                Ok(output) => output.to_string(),
+                Err(err) => return Err(failed(&*store, err)),
+            }
+        };
+
+        let stdout = match instance.get_global(&mut *store, STDOUT_EXPORT) {
+            Some(global) => {
+                let base = global.get(&mut *store).i32().context("stdout export is not an i32")?;
+                captured_stdout(memory.data(&*store), base as u32)
+            }
+            None => Vec::new(),
+        };
+        Ok((output, stdout))
+    }
+}
 
-        let input_bytes = input.as_bytes();
-        let input_ptr = 0x1000;
-        memory.write(&mut *store, input_ptr, input_bytes)?;
+/// Write the input record at INPUT_RECORD: the input's length sits just before it, so the input
+/// is itself a record. Returns python_main's arguments.
+fn write_input(store: &mut Store<()>, memory: &Memory, input: &str) -> Result<(i32, i32)> {
+    let input_bytes = input.as_bytes();
+    let input_ptr = INPUT_RECORD as usize + 4;
+    memory.write(&mut *store, INPUT_RECORD as usize, &(input_bytes.len() as u32).to_le_bytes())?;
+    memory.write(&mut *store, input_ptr, input_bytes)?;
+    Ok((input_ptr as i32, input_bytes.len() as i32))
+}
 
-        let output_ptr = run.call(&mut *store, (input_ptr as i32, input_bytes.len() as i32))?;
+/// The output record at `ptr`: a little-endian u32 length, then that many bytes. A length past
+/// `max_output_bytes` is OutputTooLarge, whatever the memory holds.
+pub fn read_output(memory: &[u8], ptr: u32, max_output_bytes: u32) -> Result<&[u8]> {
+    let start = ptr as usize;
+    let header = memory.get(start..start.saturating_add(4))
+        .with_context(|| format!("output record at {:#x} is outside memory", ptr))?;
+    let len = u32::from_le_bytes(header.try_into().unwrap());
+    if len > max_output_bytes {
+        return Err(ExecutorError::OutputTooLarge { limit: max_output_bytes }.into());
+    }
+    memory.get(start + 4..start + 4 + len as usize)
+        .with_context(|| format!("output of {} bytes at {:#x} runs past memory", len, ptr))
+}
 
-        let mut output = vec![0u8; 4096];
-        memory.read(&mut *store, output_ptr as usize, &mut output)?;
+/// Hash of a run's output; printed lines, when there are any, follow a NUL separator, which
+/// `validate_output` keeps out of outputs
+pub fn output_hash(output: &str, stdout: &[String]) -> String {
+    outputs::OutputBlob::new(output, stdout).hash()
+}
 
-        // null terminator
-        let len = output.iter().position(|&b| b == 0).unwrap_or(output.len());
+/// Lines printed into the stdout buffer at `base`, oldest first. Once the ring has wrapped
+/// only its last STDOUT_CAPACITY bytes survive, less the first line, which may have been cut.
+pub fn captured_stdout(memory: &[u8], base: u32) -> Vec<String> {
+    let base = base as usize;
+    let capacity = STDOUT_CAPACITY as usize;
+    let Some(buffer) = memory.get(base..base + 8 + capacity) else {
+        return Vec::new();
+    };
+    let written = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
+    let ring = &buffer[8..];
+
+    let bytes = if written <= capacity {
+        ring[..written].to_vec()
+    } else {
+        let start = written % capacity;
+        let wrapped = [&ring[start..], &ring[..start]].concat();
+        match wrapped.iter().position(|&b| b == b'\n') {
+            Some(cut) => wrapped[cut + 1..].to_vec(),
+            None => Vec::new(),
+        }
+    };
+    String::from_utf8_lossy(&bytes).split_terminator('\n').map(str::to_string).collect()
+}
+
+/// The message hash a failed `assert` left in its slot; `None` when the run trapped otherwise
+pub fn assertion_failure(memory: &[u8]) -> Option<u32> {
+    let slot = memory.get(ASSERT_SLOT as usize..ASSERT_SLOT as usize + 8)?;
+    let word = |at: usize| u32::from_le_bytes([slot[at], slot[at + 1], slot[at + 2], slot[at + 3]]);
+    (word(0) == ASSERTION_FAILED as u32).then(|| word(4))
+}
 
-        String::from_utf8(output[..len].to_vec())
-            .context("invalid utf-8 in output")
+/// Binary Wasm from the WAT text of a hand-written module. The result is a client module like
+/// any other, so it still goes through `prepare_wasm`.
+#[cfg(feature = "wat")]
+pub fn assemble_wat(text: &str) -> Result<Vec<u8>> {
+    wat::parse_str(text).map_err(|e| anyhow::anyhow!("invalid WAT: {}", e))
+}
+
+#[cfg(not(feature = "wat"))]
+pub fn assemble_wat(_text: &str) -> Result<Vec<u8>> {
+    bail!("WAT input needs python-verifier built with the wat feature")
+}
+
+/// Interrupts the engine's running store once a wall-clock budget is spent. Stores run with
+/// an epoch deadline of 1, so a single epoch increment traps them. The budget is the guest's:
+/// it starts at the call into the module, so setting the run up can't spend it all and leave an
+/// interrupt waiting at the guest's entry.
+struct WallClock {
+    engine: Engine,
+    budget_ms: u64,
+    running: Option<(std::sync::mpsc::Sender<()>, std::thread::JoinHandle<bool>)>,
+}
+
+impl WallClock {
+    fn new(engine: &Engine, budget_ms: u64) -> Self {
+        Self { engine: engine.clone(), budget_ms, running: None }
+    }
+
+    /// Start the budget; called just before the call into the guest
+    fn arm(&mut self) {
+        let (cancel, cancelled) = std::sync::mpsc::channel();
+        let (engine, budget_ms) = (self.engine.clone(), self.budget_ms);
+        let handle = std::thread::spawn(move || {
+            let expired = matches!(
+                cancelled.recv_timeout(std::time::Duration::from_millis(budget_ms)),
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout)
+            );
+            if expired {
+                engine.increment_epoch();
+            }
+            expired
+        });
+        self.running = Some((cancel, handle));
+    }
+
+    /// Whether the budget ran out; joins, so no increment can land on a later run
+    fn stop(self) -> bool {
+        let Some((cancel, handle)) = self.running else { return false };
+        let _ = cancel.send(());
+        handle.join().unwrap_or(false)
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ExecutionOutput {
     pub result: String,
+    /// Lines the job printed; covered by output_hash
+    #[serde(default)]
+    pub stdout: Vec<String>,
     pub output_hash: String,
     pub fuel_consumed: u64,
     pub success: bool,
+    /// Python line a compiled job trapped on; set only on the output inside a `Trapped` error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trap: Option<SourceLocation>,
+    /// Why the job trapped, when it said; like `trap`, only set inside a `Trapped` error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RuntimeError>,
+}
+
+/// Error of a job that trapped at a known Python line or for a known reason. `output` has
+/// `success` false, the fuel burnt up to the trap, the line in `trap` and the reason in `error`.
+#[derive(Debug)]
+pub struct Trapped {
+    pub output: ExecutionOutput,
+    message: String,
+}
+
+impl std::fmt::Display for Trapped {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.output.trap {
+            Some(location) => write!(f, "execution failed at {}: ", location)?,
+            None => write!(f, "execution failed: ")?,
+        }
+        match &self.output.error {
+            Some(error) => write!(f, "{}: {}", error, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Trapped {}
+
+/// Why a run trapped: the host's fuel or the module's fuel counter ran out, or a compiled
+/// module (`compiled`) said why at ERROR_SLOT. Client modules' memory is their own, so only
+/// fuel speaks for them. The counter's limit is the one the module exports, FUEL_LIMIT for
+/// client modules, which export none.
+fn runtime_error(store: &mut Store<()>, instance: &Instance, err: &anyhow::Error, compiled: bool) -> Option<RuntimeError> {
+    let mut global = |name: &str| instance.get_global(&mut *store, name).and_then(|global| global.get(&mut *store).i32());
+    let fuel_counter = global(fuel::FUEL_EXPORT);
+    let gas_limit = global(abi::GAS_LIMIT_EXPORT).unwrap_or(fuel::FUEL_LIMIT);
+    if err.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel) || fuel_counter.is_some_and(|fuel| fuel > gas_limit) {
+        return Some(RuntimeError::OutOfGas);
+    }
+    if !compiled {
+        return None;
+    }
+    let memory = instance.get_memory(&mut *store, "memory")?;
+    RuntimeError::read(memory.data(&*store))
+}
+
+/// Python line of the innermost frame of a trap's backtrace that is in compiled Python
+pub fn trap_location(source_map: &SourceMap, err: &anyhow::Error) -> Option<SourceLocation> {
+    let backtrace = err.downcast_ref::<WasmBacktrace>()?;
+    source_map.locate_frames(backtrace.frames().iter().filter_map(|frame| Some((frame.func_index(), frame.func_offset()?))))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
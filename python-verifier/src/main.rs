@@ -1,8 +1,8 @@
 use anyhow::Result;
 use clap::Parser;
-use std::sync::{Arc, Mutex};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use std::sync::Arc;
 
-mod compiler;
 mod verifier;
 mod api;
 mod websocket;
@@ -10,8 +10,20 @@ mod queue;
 mod certus_integration;
 mod reliability;
 mod validation;
+mod redaction;
+mod receipts;
+mod evidence;
+mod chain_params;
+mod outputs;
+mod cluster;
+mod notarization;
+mod indexer;
+// the binary only records traces
+#[cfg(feature = "zk-trace")]
+#[allow(dead_code)]
+mod zk_trace;
 
-use python_verifier::PythonExecutor;
+use python_verifier::{artifacts, assemble_wat, canary, compiler, executor_error, playground, pool, provenance, profiles, rate_limit, step_trace, ExecutionOutput, PythonExecutor, MAX_WASM_STACK};
 use certus_integration::CertusIntegration;
 use queue::JobQueue;
 use websocket::{WsState, ws_handler, broadcast_update, JobUpdate};
@@ -41,6 +53,187 @@ struct Args {
 
     #[clap(long, default_value = "./queue.db")]
     queue_path: String,
+
+    /// Queue of jobs awaiting verification, shared by the cluster's workers through the leader
+    #[clap(long, default_value = "./verify_queue.db")]
+    verify_queue_path: String,
+
+    /// Leader to take verification jobs from; this node leads (polls the chain) when unset
+    #[clap(long)]
+    cluster_leader: Option<String>,
+
+    /// Shared secret of the verifier cluster; the leader serves /cluster routes only when set
+    #[clap(long, env = "CERTUS_CLUSTER_TOKEN")]
+    cluster_token: Option<String>,
+
+    /// Jobs this node executes at once, each on its own executor
+    #[clap(long, default_value = "1")]
+    executors: usize,
+
+    /// Verification workers this node runs
+    #[clap(long, default_value = "1")]
+    verify_workers: usize,
+
+    /// Seconds a claimed job stays hidden before it is handed out again
+    #[clap(long, default_value = "300")]
+    visibility_timeout: u64,
+
+    /// Extra field names to mask in logs and broadcasts (comma separated)
+    #[clap(long, value_delimiter = ',')]
+    redact_fields: Vec<String>,
+
+    /// Longest string kept in logs and broadcasts before truncation
+    #[clap(long, default_value = "256")]
+    redact_max_len: usize,
+
+    /// Tenants whose job data is never redacted (comma separated)
+    #[clap(long, value_delimiter = ',')]
+    redaction_exempt_tenants: Vec<String>,
+
+    /// Nested user-function calls allowed before execution traps
+    #[clap(long, default_value = "128")]
+    max_call_depth: u32,
+
+    /// Directory for fetched modules and inputs, compiled-module caches and traces
+    #[clap(long, default_value = "./artifacts")]
+    artifact_dir: String,
+
+    /// Disk quota for fetched modules, in MiB
+    #[clap(long, default_value = "512")]
+    wasm_quota_mb: u64,
+
+    /// Disk quota for fetched inputs, in MiB
+    #[clap(long, default_value = "1024")]
+    input_quota_mb: u64,
+
+    /// Disk quota for compiled-module caches, in MiB
+    #[clap(long, default_value = "2048")]
+    aot_cache_quota_mb: u64,
+
+    /// Disk quota for execution traces, in MiB
+    #[clap(long, default_value = "4096")]
+    trace_quota_mb: u64,
+
+    /// Free space on the artifact volume below which unpinned artifacts are pruned, in MiB
+    #[clap(long, default_value = "1024")]
+    min_free_disk_mb: u64,
+
+    /// Seconds between artifact pruning passes
+    #[clap(long, default_value = "60")]
+    prune_interval: u64,
+
+    #[clap(long, default_value = "./receipts.db")]
+    receipts_path: String,
+
+    /// Directory for evidence packets of proven fraud, for off-chain arbitration
+    #[clap(long, default_value = "./evidence")]
+    evidence_dir: String,
+
+    /// Seconds between scans for finalized jobs whose receipts are due
+    #[clap(long, default_value = "15")]
+    finalization_poll_interval: u64,
+
+    /// RPC of a secondary chain finalized results are notarized on; off when unset
+    #[clap(long, env = "NOTARY_RPC")]
+    notary_rpc: Option<String>,
+
+    /// Notary contract on the secondary chain; needs --notary-rpc
+    #[clap(long, env = "NOTARY_ADDRESS")]
+    notary_contract: Option<String>,
+
+    /// Key notarizations are posted from, funded on the secondary chain
+    #[clap(long, env = "NOTARY_PRIVATE_KEY")]
+    notary_private_key: Option<String>,
+
+    /// Gas limit of each notarization
+    #[clap(long, default_value = "200000")]
+    notary_gas_limit: u64,
+
+    /// Highest gas price paid for a notarization, in gwei; posts wait while the chain asks more
+    #[clap(long, default_value = "5")]
+    notary_max_gas_price_gwei: u64,
+
+    /// Seconds before retrying a failed notarization, doubling with each failure
+    #[clap(long, default_value = "30")]
+    notary_retry_secs: u64,
+
+    /// Protocol event index behind /stats/protocol
+    #[clap(long, default_value = "./protocol_index.db")]
+    index_path: String,
+
+    /// Block to backfill the protocol index from on first start (the contracts' deployment);
+    /// the head when unset
+    #[clap(long)]
+    index_from_block: Option<u64>,
+
+    /// Seconds of history protocol health metrics cover; 0 for all indexed history
+    #[clap(long, default_value = "86400")]
+    protocol_stats_window: u64,
+
+    /// JSON file of named execution profiles; only the built-in default when unset
+    #[clap(long)]
+    profiles: Option<String>,
+
+    /// JSON file of the validation policy job sources are checked against: allowed modules,
+    /// calls and attribute chains; the built-in policy when unset
+    #[clap(long)]
+    validation_policy: Option<String>,
+
+    /// Seconds between re-reads of deposit and limit parameters from the contracts
+    #[clap(long, default_value = "300")]
+    param_sync_interval: u64,
+
+    /// IPFS gateway outputs are read from; outputs aren't fetched or checked when unset
+    #[clap(long, env = "IPFS_GATEWAY")]
+    ipfs_gateway: Option<String>,
+
+    /// IPFS node RPC API (Kubo) executed outputs are published through; needs --ipfs-gateway
+    #[clap(long, env = "IPFS_API")]
+    ipfs_api: Option<String>,
+
+    /// Seconds between determinism canary runs; 0 disables the canary
+    #[clap(long, default_value = "900")]
+    canary_interval: u64,
+
+    /// JSON array of build ids peers may run; peers' builds are only signature-checked when unset
+    #[clap(long)]
+    build_allowlist: Option<String>,
+
+    /// Base URLs of peer nodes whose /status build reports are checked (comma separated)
+    #[clap(long, value_delimiter = ',')]
+    peers: Vec<String>,
+
+    /// Seconds between checks of peers' builds
+    #[clap(long, default_value = "600")]
+    peer_check_interval: u64,
+
+    /// Port for the public read-only API (job status and receipts, no auth); off when unset
+    #[clap(long)]
+    public_port: Option<u16>,
+
+    /// Sustained requests per minute each client may make to the public API
+    #[clap(long, default_value = "30")]
+    public_requests_per_minute: u32,
+
+    /// Requests a client may make to the public API at once
+    #[clap(long, default_value = "10")]
+    public_burst: u32,
+
+    /// Seconds clients and CDNs may cache public job status responses
+    #[clap(long, default_value = "10")]
+    public_cache_seconds: u64,
+
+    /// Serve POST /playground/execute on the public port: sandboxed runs with no chain interaction
+    #[clap(long)]
+    playground: bool,
+
+    /// Sustained playground runs per minute each client may make
+    #[clap(long, default_value = "6")]
+    playground_requests_per_minute: u32,
+
+    /// Fuel each playground run gets
+    #[clap(long, default_value = "20000")]
+    playground_fuel: u64,
 }
 
 #[tokio::main]
@@ -55,139 +248,342 @@ async fn main() -> Result<()> {
     log::info!("Escrow: {}", args.escrow);
     log::info!("Jobs: {}", args.jobs);
     log::info!("RPC: {}", args.rpc);
+    let build = provenance::BuildInfo::current();
+    log::info!("Build: {} (commit {}, Cargo.lock {})", build.id_hex(), build.git_commit, build.lock_hash);
+
+    // initialize artifact store
+    let mib = |n: u64| n * 1024 * 1024;
+    let artifacts = Arc::new(artifacts::ArtifactStore::open(&args.artifact_dir, artifacts::ArtifactQuotas {
+        wasm_bytes: mib(args.wasm_quota_mb),
+        input_bytes: mib(args.input_quota_mb),
+        aot_cache_bytes: mib(args.aot_cache_quota_mb),
+        trace_bytes: mib(args.trace_quota_mb),
+        min_free_bytes: mib(args.min_free_disk_mb),
+    })?);
 
-    // initialize executor
-    let executor = Arc::new(Mutex::new(PythonExecutor::new()?));
+    // initialize executors, caching compiled modules with the other artifacts
+    let policy: python_verifier::validation::ValidationPolicy = match &args.validation_policy {
+        Some(path) => serde_json::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| anyhow::anyhow!("invalid validation policy {}: {}", path, e))?,
+        None => Default::default(),
+    };
+    let executor = Arc::new(pool::ExecutorPool::new(args.executors, || {
+        Ok(PythonExecutor::new()?
+            .with_max_call_depth(args.max_call_depth)
+            .with_module_store(artifacts.clone())
+            .with_validation_policy(policy.clone()))
+    })?);
+    log::info!("Executors: {}", executor.size());
 
     // initialize job queue
-    let queue = Arc::new(JobQueue::new(&args.queue_path)?);
+    let queue = Arc::new(
+        JobQueue::new(&args.queue_path)?
+            .with_visibility_timeout(std::time::Duration::from_secs(args.visibility_timeout))
+    );
+
+    // load execution profiles
+    let profiles = Arc::new(match &args.profiles {
+        Some(path) => profiles::ProfileSet::load(path)?,
+        None => profiles::ProfileSet::default(),
+    });
+    log::info!("Execution profiles: {}", profiles.names().collect::<Vec<_>>().join(", "));
+
+    // initialize receipt store
+    let receipts = Arc::new(receipts::ReceiptStore::open(&args.receipts_path)?);
 
     // initialize WebSocket state
-    let ws_state = Arc::new(WsState::new());
+    let mut redaction = redaction::RedactionPolicy::default();
+    redaction.masked_fields.extend(args.redact_fields.iter().cloned());
+    redaction.max_string_len = args.redact_max_len;
+    redaction.exempt_tenants = args.redaction_exempt_tenants.clone();
+    let ws_state = Arc::new(WsState::new().with_redaction(redaction.clone()).with_history(queue.clone()));
+
+    // initialize output publishing
+    let outputs = match (&args.ipfs_gateway, &args.ipfs_api) {
+        (Some(gateway), api) => {
+            let store = outputs::OutputStore::new(gateway);
+            Some(Arc::new(match api {
+                Some(api) => store.with_upload_api(api),
+                None => store,
+            }))
+        }
+        (None, Some(_)) => anyhow::bail!("--ipfs-api needs --ipfs-gateway"),
+        (None, None) => None,
+    };
+
+    // check this node's toolchain against the canary reference before taking any job
+    let canary = Arc::new(canary::CanaryState::default());
+    if args.canary_interval > 0 {
+        let run = executor.get().run_canary();
+        match canary.record(run) {
+            Ok(()) => log::info!("Determinism canary matches the reference"),
+            Err(e) => log::error!("DETERMINISM CANARY FAILED, not accepting jobs: {}", e),
+        }
+    }
 
     // initialize Certus integration
-    let integration = Arc::new(CertusIntegration::new(
+    let mut integration = CertusIntegration::new(
         executor.clone(),
         &args.rpc,
         &args.private_key,
         &args.escrow,
         &args.jobs,
-    ).await?);
+    ).await?.with_artifacts(artifacts.clone()).with_profiles(profiles.clone()).with_canary(canary.clone());
+    if let Some(outputs) = &outputs {
+        integration = integration.with_outputs(outputs.clone());
+    }
+    let integration = Arc::new(integration);
+
+    // index protocol events for health metrics
+    let protocol = Arc::new(indexer::ProtocolIndexer::open(
+        &args.index_path,
+        integration.address(),
+        args.protocol_stats_window,
+    )?);
+    protocol.clone().spawn(
+        integration.clone(),
+        args.index_from_block,
+        std::time::Duration::from_secs(args.finalization_poll_interval.max(1)),
+    );
+
+    // read governance parameters; until the first sync succeeds the deployment values apply
+    match integration.sync_chain_params().await {
+        Ok(_) => log::info!("Chain parameters: {:?}", integration.chain_params()),
+        Err(e) => log::warn!("Could not read chain parameters, using deployment defaults: {}", e),
+    }
 
     // initialize verifier
-    let verifier = Arc::new(PythonVerifier::new(
+    let mut verifier = PythonVerifier::new(
         &args.rpc,
         &args.private_key,
         &args.escrow,
         &args.jobs,
-    ).await?);
+    ).await?.with_evidence_dir(&args.evidence_dir);
+    if let Some(outputs) = &outputs {
+        verifier = verifier.with_outputs(outputs.clone());
+    }
+    if let Some(path) = &args.build_allowlist {
+        verifier = verifier.with_build_policy(provenance::BuildPolicy::load(path)?);
+    }
+    let verifier = Arc::new(verifier);
 
-    // spawn queue processor
-    let queue_clone = queue.clone();
-    let integration_clone = integration.clone();
-    let ws_state_clone = ws_state.clone();
-    tokio::spawn(async move {
-        loop {
-            if let Ok(Some(job)) = queue_clone.next().await {
-                log::info!("Processing job: {}", job.id);
-
-                // validate input
-                match validate_json_input(&serde_json::to_string(&job.input).unwrap()) {
-                    Ok(validated) => {
-                        // execute via integration
-                        match integration_clone.execute_python_job(&job.id, &job.code, &validated.to_string()).await {
-                            Ok(result) => {
-                                // validate output
-                                let _ = validate_output(&result.output);
-                                // validate job id format
-                                let _ = validate_job_id(&job.id);
-                                // validate gas params
-                                let _ = validate_gas_params(200_000, 5_000_000);
-                                log::info!("Job {} completed: {}", job.id, result.output_hash);
-
-                                // broadcast update
-                                broadcast_update(&ws_state_clone, JobUpdate {
-                                    job_id: job.id.clone(),
-                                    status: "completed".to_string(),
-                                    timestamp: chrono::Utc::now().timestamp() as u64,
-                                    data: serde_json::json!({
+    // spawn a queue processor per executor, so jobs run as fast as the pool allows
+    for _ in 0..executor.size() {
+        let queue_clone = queue.clone();
+        let integration_clone = integration.clone();
+        let ws_state_clone = ws_state.clone();
+        let canary_clone = canary.clone();
+        let redaction = redaction.clone();
+        tokio::spawn(async move {
+            loop {
+                // while halted, jobs stay queued for a healthy restart rather than failing here
+                if canary_clone.ensure_healthy().is_err() {
+                    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                    continue;
+                }
+                if let Ok(Some(job)) = queue_clone.next().await {
+                    log::info!("Processing job: {}", job.id);
+                    let tenant = job.tenant.as_deref();
+
+                    // validate input
+                    match validate_json_input(&serde_json::to_string(&job.input).unwrap()) {
+                        Ok(validated) => {
+                            // execute via integration
+                            let executed = match integration_clone.profiles().get(job.profile.as_deref()).cloned() {
+                                Err(e) => Err(e),
+                                Ok(profile) => match &job.wasm_b64 {
+                                    Some(wasm_b64) => match BASE64.decode(wasm_b64) {
+                                        Ok(wasm) => integration_clone.execute_wasm_job(&job.id, &wasm, &validated.to_string(), &profile).await,
+                                        Err(e) => Err(anyhow::anyhow!("invalid wasm_b64: {}", e)),
+                                    },
+                                    None => integration_clone.execute_python_job(&job.id, &job.code, &validated.to_string(), &profile, job.compile_options).await,
+                                },
+                            };
+                            match executed {
+                                Ok(result) => {
+                                    // validate output
+                                    let _ = validate_output(&result.output);
+                                    // validate job id format
+                                    let _ = validate_job_id(&job.id);
+                                    // validate gas params
+                                    let _ = validate_gas_params(200_000, 5_000_000);
+                                    log::info!("Job {} completed: {}", job.id, result.output_hash);
+
+                                    // broadcast update
+                                    broadcast_update(&ws_state_clone, JobUpdate::new(
+                                        &job.id,
+                                        "completed",
+                                        chrono::Utc::now().timestamp() as u64,
+                                        serde_json::json!({
+                                            "output": result.output,
+                                            "stdout": result.stdout,
+                                            "hash": result.output_hash,
+                                        }),
+                                        job.tenant.clone(),
+                                    ));
+
+                                    let _ = queue_clone.complete(&job.id, serde_json::json!({
                                         "output": result.output,
+                                        "stdout": result.stdout,
                                         "hash": result.output_hash,
-                                    }),
-                                });
-
-                                let _ = queue_clone.complete(&job.id, serde_json::json!({
-                                    "output": result.output,
-                                    "hash": result.output_hash,
-                                    "tx": result.receipt_tx,
-                                })).await;
-                            }
-                            Err(e) => {
-                                log::error!("Job {} failed: {}", job.id, e);
-                                let _ = queue_clone.fail(&job.id, &e.to_string()).await;
+                                        "tx": result.receipt_tx,
+                                        "cid": result.output_cid,
+                                    })).await;
+                                }
+                                Err(e) => {
+                                    log::error!("Job {} failed: {}", job.id, redaction.redact_text(&e.to_string(), tenant));
+                                    let kind = executor_error::ExecutorError::of(&e);
+
+                                    // broadcast update
+                                    broadcast_update(&ws_state_clone, JobUpdate::new(
+                                        &job.id,
+                                        "failed",
+                                        chrono::Utc::now().timestamp() as u64,
+                                        serde_json::json!({
+                                            "error": e.to_string(),
+                                            "kind": kind,
+                                        }),
+                                        job.tenant.clone(),
+                                    ));
+
+                                    let _ = queue_clone.fail_as(&job.id, &e.to_string(), kind.as_ref()).await;
+                                }
                             }
                         }
+                        Err(e) => {
+                            log::error!("Invalid input for job {}: {}", job.id, redaction.redact_text(&e.to_string(), tenant));
+                            let kind = executor_error::ExecutorError::validation(e);
+                            let _ = queue_clone.fail_as(&job.id, &kind.to_string(), Some(&kind)).await;
+                        }
                     }
-                    Err(e) => {
-                        log::error!("Invalid input for job {}: {}", job.id, e);
-                        let _ = queue_clone.fail(&job.id, &e.to_string()).await;
+                }
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    // spawn verification: a leader feeds the verification queue from the chain and works it,
+    // a worker takes jobs from its leader's queue
+    let verify_queue = match &args.cluster_leader {
+        Some(leader) => {
+            let Some(token) = &args.cluster_token else {
+                anyhow::bail!("--cluster-leader needs --cluster-token");
+            };
+            log::info!("Verifying jobs handed out by cluster leader {}", leader);
+            let remote = Arc::new(cluster::RemoteQueue::new(leader, token)?);
+            for _ in 0..args.verify_workers.max(1) {
+                let (remote, verifier) = (remote.clone(), verifier.clone());
+                tokio::spawn(async move { cluster::run_worker(&*remote, &verifier).await });
+            }
+            None
+        }
+        None => {
+            let verify_queue = Arc::new(
+                JobQueue::new(&args.verify_queue_path)?
+                    .with_visibility_timeout(std::time::Duration::from_secs(args.visibility_timeout))
+            );
+            let (feed_queue, integration_verifier) = (verify_queue.clone(), integration.clone());
+            tokio::spawn(async move {
+                loop {
+                    if let Err(e) = cluster::feed(&integration_verifier, &feed_queue).await {
+                        log::error!("Failed to fetch pending verification jobs: {}", e);
                     }
+                    tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
                 }
+            });
+            for _ in 0..args.verify_workers.max(1) {
+                let (local, verifier) = (verify_queue.clone(), verifier.clone());
+                tokio::spawn(async move { cluster::run_worker(&*local, &verifier).await });
             }
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            Some(verify_queue)
         }
-    });
+    };
 
-    // spawn verifier task with VRF awareness
-    let verifier_clone = verifier.clone();
-    let integration_verifier = integration.clone();
+    // spawn cleanup task
+    let queue_clone = queue.clone();
+    let verify_queue_clone = verify_queue.clone();
     tokio::spawn(async move {
         loop {
-            // Fetch jobs awaiting verification
-            match integration_verifier.get_pending_verification_jobs().await {
-                Ok(job_ids) => {
-                    for job_id in job_ids {
-                        // Check if VRF selection completed
-                        match integration_verifier.check_vrf_status(job_id).await {
-                            Ok(vrf_status) => {
-                                if !vrf_status.fulfilled && vrf_status.elapsed > 1800 {
-                                    // VRF grace period (30 min) expired, trigger fallback
-                                    log::info!("Triggering fallback selection for job {}", hex::encode(job_id));
-                                    if let Err(e) = integration_verifier.trigger_fallback_selection(job_id).await {
-                                        log::error!("Fallback selection failed: {}", e);
-                                        continue;
-                                    }
-                                }
+            tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
+            if let Ok(deleted) = queue_clone.cleanup_old(86400 * 7) {
+                log::info!("Cleaned up {} old jobs", deleted);
+            }
+            if let Some(verify_queue) = &verify_queue_clone {
+                if let Ok(deleted) = verify_queue.cleanup_old(86400) {
+                    log::info!("Cleaned up {} old verifications", deleted);
+                }
+            }
+        }
+    });
 
-                                // Attempt verification (will check if selected)
-                                if let Err(e) = verifier_clone.verify_certus_job(job_id).await {
-                                    log::error!("Verification failed for job {}: {}", hex::encode(job_id), e);
-                                } else {
-                                    log::debug!("Processed job: {}", hex::encode(job_id));
-                                }
-                            }
-                            Err(e) => {
-                                log::error!("Failed to check VRF status: {}", e);
-                            }
-                        }
+    // spawn chain parameter sync task
+    let integration_params = integration.clone();
+    let param_sync_interval = args.param_sync_interval.max(1);
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(param_sync_interval)).await;
+            match integration_params.sync_chain_params().await {
+                Ok(changes) => {
+                    for change in changes {
+                        log::warn!("Chain parameter changed: {}", change);
                     }
                 }
-                Err(e) => {
-                    log::error!("Failed to fetch pending verification jobs: {}", e);
-                }
+                Err(e) => log::error!("Chain parameter sync failed: {}", e),
             }
-            tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
         }
     });
 
-    // spawn cleanup task
-    let queue_clone = queue.clone();
+    // spawn determinism canary task
+    if args.canary_interval > 0 {
+        let executor_canary = executor.clone();
+        let canary_clone = canary.clone();
+        let interval = args.canary_interval;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_secs(interval)).await;
+                let run = executor_canary.get().run_canary();
+                if let Err(e) = canary_clone.record(run) {
+                    log::error!("DETERMINISM CANARY FAILED, not accepting jobs: {}", e);
+                }
+            }
+        });
+    }
+
+    // spawn peer build check task
+    if !args.peers.is_empty() {
+        let verifier_peers = verifier.clone();
+        let peers = args.peers.clone();
+        let interval = args.peer_check_interval.max(1);
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+        tokio::spawn(async move {
+            loop {
+                for peer in &peers {
+                    match verifier_peers.check_peer_build(&http, peer).await {
+                        Ok(report) => log::debug!("Peer {} ({}) runs build {}", peer, report.node, report.build_id),
+                        Err(e) => log::warn!("Peer {} failed its build check: {:#}", peer, e),
+                    }
+                }
+                tokio::time::sleep(tokio::time::Duration::from_secs(interval)).await;
+            }
+        });
+    }
+
+    // spawn artifact pruning task
+    let artifacts_clone = artifacts.clone();
+    let prune_interval = args.prune_interval.max(1);
     tokio::spawn(async move {
         loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
-            if let Ok(deleted) = queue_clone.cleanup_old(86400 * 7) {
-                log::info!("Cleaned up {} old jobs", deleted);
+            match artifacts_clone.prune() {
+                Ok(report) if report.removed_files > 0 => {
+                    log::info!("Pruned {} artifacts ({} bytes)", report.removed_files, report.removed_bytes);
+                }
+                Ok(_) => {}
+                Err(e) => log::error!("Artifact pruning failed: {}", e),
             }
+            tokio::time::sleep(tokio::time::Duration::from_secs(prune_interval)).await;
         }
     });
 
@@ -198,21 +594,80 @@ async fn main() -> Result<()> {
     let _ = queue.submit(queue::QueuedJob {
         id: "sample".to_string(),
         code: "OUTPUT = INPUT['x'] * 2".to_string(),
+        wasm_b64: None,
         input: serde_json::json!({"x": 21}),
         priority: 1,
         created_at: chrono::Utc::now().timestamp() as u64,
         retry_count: 0,
         max_retries: 3,
+        available_at: 0,
+        claimed_at: None,
+        depends_on: vec![],
+        on_dependency_failure: Default::default(),
+        tenant: None,
+        profile: None,
+        compile_options: Default::default(),
     }).await;
 
     // create API server
-    let api_server = api::ApiServer::new(
-        executor.clone(),
-        &args.rpc,
-        &args.private_key,
-        &args.escrow,
-        &args.jobs,
-    ).await?;
+    let api_server = api::ApiServer::new(integration.clone(), receipts.clone()).with_history(queue.clone());
+    api_server.spawn_finalization_watcher(
+        std::time::Duration::from_secs(args.finalization_poll_interval.max(1))
+    );
+
+    // mirror issued receipts to the secondary chain's notary contract
+    match (&args.notary_rpc, &args.notary_contract) {
+        (Some(rpc_url), Some(contract)) => {
+            let Some(private_key) = args.notary_private_key.clone() else {
+                anyhow::bail!("--notary-rpc needs --notary-private-key");
+            };
+            let notary = notarization::Notary::connect(&notarization::NotaryConfig {
+                rpc_url: rpc_url.clone(),
+                contract: contract.clone(),
+                private_key,
+                gas_limit: args.notary_gas_limit,
+                max_gas_price_gwei: args.notary_max_gas_price_gwei,
+                retry_delay: args.notary_retry_secs.max(1),
+            }).await?;
+            log::info!("Notarizing results on {} from {:?}", contract, notary.address());
+            Arc::new(notary).spawn(
+                receipts.clone(),
+                std::time::Duration::from_secs(args.finalization_poll_interval.max(1)),
+            );
+        }
+        (None, None) => {}
+        _ => anyhow::bail!("--notary-rpc and --notary-contract must be set together"),
+    }
+
+    // serve the public read-only API on its own port, so nothing else is reachable through it
+    if args.playground && args.public_port.is_none() {
+        anyhow::bail!("--playground is served on the public API and needs --public-port");
+    }
+    if let Some(port) = args.public_port {
+        let mut public = api_server.public_routes(api::PublicApiConfig {
+            requests_per_minute: args.public_requests_per_minute,
+            burst: args.public_burst,
+            cache_seconds: args.public_cache_seconds,
+        });
+        if args.playground {
+            let playground = playground::Playground::new(playground::PlaygroundConfig {
+                requests_per_minute: args.playground_requests_per_minute,
+                fuel_limit: args.playground_fuel,
+                ..Default::default()
+            })?;
+            public = public.merge(Arc::new(playground).routes());
+        }
+        let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
+        log::info!("Public read-only API listening on {}", addr);
+        tokio::spawn(async move {
+            let served = axum::Server::bind(&addr)
+                .serve(public.into_make_service_with_connect_info::<std::net::SocketAddr>())
+                .await;
+            if let Err(e) = served {
+                log::error!("Public API server failed: {}", e);
+            }
+        });
+    }
 
     // build routes
     use axum::{Router, routing::get};
@@ -220,7 +675,14 @@ async fn main() -> Result<()> {
     let app = Router::new()
         .route("/ws", get(move |ws, state| ws_handler(ws, state)))
         .with_state(ws_state.clone())
-        .nest("/", api_routes);
+        .nest("/", api_routes)
+        .merge(api::health_routes(queue.clone(), artifacts.clone(), canary.clone(), protocol.clone()));
+
+    // remote verifier workers claim from this leader's verification queue
+    let app = match (&verify_queue, &args.cluster_token) {
+        (Some(verify_queue), Some(token)) => app.merge(cluster::cluster_routes(verify_queue.clone(), token)),
+        _ => app,
+    };
 
     // start server
     let addr = std::net::SocketAddr::from(([0, 0, 0, 0], args.port));
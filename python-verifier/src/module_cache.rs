@@ -0,0 +1,126 @@
+// Compiled wasmtime modules keyed by the sha256 of their Wasm, so running the same job again
+// skips Cranelift. Recent modules stay in memory; with an artifact store behind it, every
+// module is also serialized into the store's aot_cache class and survives restarts under
+// that class's quota. A serialized module built by another wasmtime version or engine config
+// fails to load, and is compiled afresh and stored over.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use wasmtime::{Engine, Module};
+
+use crate::artifacts::{ArtifactClass, ArtifactStore};
+
+/// Modules kept in memory before the least recently used is dropped
+pub const DEFAULT_MODULES_IN_MEMORY: usize = 64;
+
+/// Where each module a cache handed out came from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ModuleCacheStats {
+    pub memory_hits: u64,
+    pub disk_hits: u64,
+    pub compiled: u64,
+}
+
+#[derive(Default)]
+struct Modules {
+    modules: HashMap<[u8; 32], Module>,
+    // least recently used first
+    order: VecDeque<[u8; 32]>,
+    stats: ModuleCacheStats,
+}
+
+pub struct ModuleCache {
+    capacity: usize,
+    store: Option<Arc<ArtifactStore>>,
+    inner: Mutex<Modules>,
+}
+
+impl ModuleCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, store: None, inner: Mutex::new(Modules::default()) }
+    }
+
+    /// Also serialize modules into `store`, and look there before compiling
+    pub fn with_store(mut self, store: Arc<ArtifactStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// The module for `wasm` on `engine`, compiling it only if neither tier has it
+    pub fn get(&self, engine: &Engine, wasm: &[u8]) -> Result<Module> {
+        let hash: [u8; 32] = Sha256::digest(wasm).into();
+        if let Some(module) = self.inner.lock().unwrap().touch(&hash) {
+            return Ok(module);
+        }
+
+        let key = hex::encode(hash);
+        let (module, from_disk) = match self.load(engine, &key) {
+            Some(module) => (module, true),
+            None => {
+                let module = Module::new(engine, wasm)?;
+                if let Some(store) = &self.store {
+                    // a full cache quota costs the next run a compile, not this one its result
+                    if let Err(e) = module.serialize().and_then(|bytes| store.put(ArtifactClass::AotCache, &key, &bytes)) {
+                        log::warn!("not caching compiled module {}: {}", key, e);
+                    }
+                }
+                (module, false)
+            }
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        if from_disk {
+            inner.stats.disk_hits += 1;
+        } else {
+            inner.stats.compiled += 1;
+        }
+        inner.insert(hash, module.clone(), self.capacity);
+        Ok(module)
+    }
+
+    pub fn stats(&self) -> ModuleCacheStats {
+        self.inner.lock().unwrap().stats
+    }
+
+    fn load(&self, engine: &Engine, key: &str) -> Option<Module> {
+        let bytes = self.store.as_ref()?.get(ArtifactClass::AotCache, key).ok()??;
+        // SAFETY: aot_cache only ever holds bytes this cache wrote with Module::serialize, in
+        // the executor's own artifact directory; wasmtime rejects ones from another version
+        // or engine config
+        match unsafe { Module::deserialize(engine, &bytes) } {
+            Ok(module) => Some(module),
+            Err(e) => {
+                log::info!("recompiling cached module {}: {}", key, e);
+                None
+            }
+        }
+    }
+}
+
+impl Default for ModuleCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MODULES_IN_MEMORY)
+    }
+}
+
+impl Modules {
+    fn touch(&mut self, hash: &[u8; 32]) -> Option<Module> {
+        let module = self.modules.get(hash)?.clone();
+        self.order.retain(|h| h != hash);
+        self.order.push_back(*hash);
+        self.stats.memory_hits += 1;
+        Some(module)
+    }
+
+    fn insert(&mut self, hash: [u8; 32], module: Module, capacity: usize) {
+        if self.modules.insert(hash, module).is_none() {
+            self.order.push_back(hash);
+        }
+        while self.modules.len() > capacity {
+            let Some(oldest) = self.order.pop_front() else { break };
+            self.modules.remove(&oldest);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+// Notarization of finalized results on a secondary chain. Clients whose contracts live on
+// another chain (Base, say) get each job this node executed mirrored to a notary contract
+// there, as notarize(jobId, outputHash, attestation), once its receipt has been issued. The
+// attestation carries the rest of the signed receipt, so the contract can recover the executor
+// the same way a webhook receiver does:
+//   abi.encode(uint256 fuelUsed, bytes32 finalizeTx, address executor, bytes32 build, bytes signature)
+// with build zero on receipts issued before builds were signed.
+//
+// The notary posts from its own key under its own gas policy, so a congested or unfunded
+// secondary chain never holds up the primary one. A failed post is retried after a delay that
+// doubles per failure, up to MAX_NOTARIZATION_ATTEMPTS; each job is notarized at most once.
+
+use anyhow::{Context, Result, bail};
+use ethers::abi::{encode, Token};
+use ethers::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::receipts::{parse_bytes32, ReceiptStore, SignedReceipt};
+use crate::reliability::{retry_with_backoff, validate_address, RetryConfig};
+
+/// Give up notarizing a job after this many failed posts; the receipt itself is unaffected
+pub const MAX_NOTARIZATION_ATTEMPTS: u32 = 10;
+
+/// Longest wait between retries of a failed post, in seconds
+pub const MAX_RETRY_DELAY: u64 = 3600;
+
+const NOTARIZE: &str = "notarize(bytes32,bytes32,bytes)";
+
+#[derive(Debug, Clone)]
+pub struct NotaryConfig {
+    pub rpc_url: String,
+    /// Notary contract on the secondary chain
+    pub contract: String,
+    /// Key the notary posts from; never the executor key of the primary chain
+    pub private_key: String,
+    pub gas_limit: u64,
+    /// Highest gas price paid, in gwei; a post fails, and is retried later, while the chain asks more
+    pub max_gas_price_gwei: u64,
+    /// Seconds before the first retry of a failed post
+    pub retry_delay: u64,
+}
+
+pub struct Notary {
+    contract: H160,
+    signer: SignerMiddleware<Provider<Http>, LocalWallet>,
+    gas_limit: u64,
+    max_gas_price: U256,
+    retry_delay: u64,
+}
+
+impl Notary {
+    pub async fn connect(config: &NotaryConfig) -> Result<Self> {
+        validate_address(&config.contract)?;
+        let provider = Provider::<Http>::try_from(config.rpc_url.as_str())
+            .context("invalid notary RPC URL")?;
+        let wallet: LocalWallet = config.private_key.parse()
+            .context("invalid notary private key")?;
+
+        let chain_id = retry_with_backoff(
+            || async { provider.get_chainid().await.map_err(Into::into) },
+            &RetryConfig::default(),
+        ).await?.as_u64();
+
+        Ok(Self {
+            contract: config.contract.parse()?,
+            signer: SignerMiddleware::new(provider, wallet.with_chain_id(chain_id)),
+            gas_limit: config.gas_limit,
+            max_gas_price: U256::from(config.max_gas_price_gwei) * U256::exp10(9),
+            retry_delay: config.retry_delay,
+        })
+    }
+
+    pub fn address(&self) -> Address {
+        self.signer.address()
+    }
+
+    /// Post one receipt's result to the notary contract; returns the mined transaction
+    pub async fn notarize(&self, receipt: &SignedReceipt) -> Result<H256> {
+        let gas_price = self.signer.get_gas_price().await?;
+        if gas_price > self.max_gas_price {
+            bail!("gas price {} wei is above the notary cap of {} wei", gas_price, self.max_gas_price);
+        }
+
+        let tx = self.signer
+            .send_transaction(
+                TransactionRequest::new()
+                    .to(self.contract)
+                    .data(notarize_calldata(receipt)?)
+                    .gas(self.gas_limit)
+                    .gas_price(gas_price),
+                None,
+            )
+            .await?
+            .await?
+            .context("notarization dropped")?;
+        if tx.status != Some(1.into()) {
+            bail!("notarization reverted in {:?}", tx.transaction_hash);
+        }
+        Ok(tx.transaction_hash)
+    }
+
+    /// Every `interval`, post the issued receipts whose notarization is due
+    pub fn spawn(self: Arc<Self>, receipts: Arc<ReceiptStore>, interval: Duration) {
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.process(&receipts).await {
+                    log::error!("Notarization failed: {}", e);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    async fn process(&self, receipts: &ReceiptStore) -> Result<()> {
+        let now = chrono::Utc::now().timestamp() as u64;
+        for receipt in receipts.pending_notarizations(now, MAX_NOTARIZATION_ATTEMPTS)? {
+            match self.notarize(&receipt).await {
+                Ok(tx) => {
+                    receipts.mark_notarized(&receipt.job_id, &format!("{:?}", tx))?;
+                    log::info!("Notarized job {} in {:?}", receipt.job_id, tx);
+                }
+                Err(e) => {
+                    let attempts = receipts.tracked(&receipt.job_id)?.map_or(0, |job| job.notarization_attempts);
+                    let retry_at = now + retry_delay(self.retry_delay, attempts);
+                    log::warn!("Notarizing job {} failed: {}", receipt.job_id, e);
+                    receipts.record_failed_notarization(&receipt.job_id, &e.to_string(), retry_at)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Calldata of notarize(jobId, outputHash, attestation) for `receipt`
+pub fn notarize_calldata(receipt: &SignedReceipt) -> Result<Vec<u8>> {
+    let args = encode(&[
+        Token::FixedBytes(parse_bytes32(&receipt.job_id)?.to_vec()),
+        Token::FixedBytes(parse_bytes32(&receipt.output_hash)?.to_vec()),
+        Token::Bytes(attestation(receipt)?),
+    ]);
+    Ok([&ethers::utils::id(NOTARIZE)[..4], &args[..]].concat())
+}
+
+/// What the notary contract needs besides job id and output hash to check the receipt signature
+pub fn attestation(receipt: &SignedReceipt) -> Result<Vec<u8>> {
+    let executor: Address = receipt.executor.parse().context("invalid executor address")?;
+    let build = receipt.build.as_deref().map(parse_bytes32).transpose()?.unwrap_or_default();
+    let signature = hex::decode(receipt.signature.trim_start_matches("0x"))
+        .context("invalid receipt signature")?;
+    Ok(encode(&[
+        Token::Uint(U256::from(receipt.fuel_used)),
+        Token::FixedBytes(parse_bytes32(&receipt.tx_hash)?.to_vec()),
+        Token::Address(executor),
+        Token::FixedBytes(build.to_vec()),
+        Token::Bytes(signature),
+    ]))
+}
+
+/// Seconds to wait after the failure following `attempts` earlier ones: doubling from `base`
+pub fn retry_delay(base: u64, attempts: u32) -> u64 {
+    base.saturating_mul(1u64.checked_shl(attempts).unwrap_or(u64::MAX)).min(MAX_RETRY_DELAY)
+}
@@ -0,0 +1,185 @@
+// Job outputs for clients. The chain only records the output hash, so the executor publishes
+// the output itself to IPFS as a single raw block whose bytes are exactly the hash preimage.
+// The block's CID is then a function of the on-chain hash: anyone can locate the output from
+// the receipt alone, and checking a fetched blob is one sha256.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Largest block IPFS nodes exchange; outputs (1 MB) plus a full stdout ring fit
+pub const MAX_BLOB_BYTES: usize = 1024 * 1024;
+
+// CIDv1, raw codec, sha2-256 multihash of 32 bytes
+const CID_PREFIX: [u8; 4] = [0x01, 0x55, 0x12, 0x20];
+
+/// What a job's output hash commits to: OUTPUT and the lines it printed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputBlob {
+    pub output: String,
+    pub stdout: Vec<String>,
+}
+
+impl OutputBlob {
+    pub fn new(output: &str, stdout: &[String]) -> Self {
+        Self { output: output.to_string(), stdout: stdout.to_vec() }
+    }
+
+    /// The output, then a NUL and each printed line newline-terminated if the job printed
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.output.as_bytes().to_vec();
+        if !self.stdout.is_empty() {
+            bytes.push(0);
+            for line in &self.stdout {
+                bytes.extend_from_slice(line.as_bytes());
+                bytes.push(b'\n');
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (output, stdout) = match bytes.iter().position(|&b| b == 0) {
+            Some(nul) => (&bytes[..nul], Some(&bytes[nul + 1..])),
+            None => (bytes, None),
+        };
+        let output = std::str::from_utf8(output).context("output is not UTF-8")?.to_string();
+        let stdout = match stdout {
+            None => Vec::new(),
+            Some(text) => {
+                let text = std::str::from_utf8(text).context("stdout is not UTF-8")?;
+                let Some(text) = text.strip_suffix('\n') else {
+                    bail!("stdout does not end with a newline");
+                };
+                text.split('\n').map(str::to_string).collect()
+            }
+        };
+        let blob = Self { output, stdout };
+        // an output or line holding the separators would decode differently than it hashed
+        if blob.to_bytes() != bytes {
+            bail!("output blob is not in canonical form");
+        }
+        Ok(blob)
+    }
+
+    /// Hex sha256 of the blob; the same value `output_hash` gives and receipts carry
+    pub fn hash(&self) -> String {
+        hex::encode(Sha256::digest(self.to_bytes()))
+    }
+
+    pub fn cid(&self) -> String {
+        output_cid(&Sha256::digest(self.to_bytes()).into())
+    }
+}
+
+/// CID of the raw block holding the output with this sha256, in base32 multibase
+pub fn output_cid(output_hash: &[u8; 32]) -> String {
+    let mut bytes = CID_PREFIX.to_vec();
+    bytes.extend_from_slice(output_hash);
+    format!("b{}", base32_lower(&bytes))
+}
+
+// RFC 4648 base32, lowercase and unpadded, as multibase "b" uses it
+fn base32_lower(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+#[derive(Deserialize)]
+struct BlockPutResponse {
+    #[serde(rename = "Key")]
+    key: String,
+}
+
+/// Publishes outputs through an IPFS node's RPC API and reads them back through a gateway
+pub struct OutputStore {
+    http: reqwest::Client,
+    gateway: String,
+    api: Option<String>,
+}
+
+impl OutputStore {
+    /// Read-only store; `gateway` serves /ipfs/<cid>
+    pub fn new(gateway: &str) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()
+            .unwrap_or_default();
+        Self { http, gateway: gateway.trim_end_matches('/').to_string(), api: None }
+    }
+
+    /// Also upload, through the RPC API of a node (Kubo's /api/v0) at `api`
+    pub fn with_upload_api(mut self, api: &str) -> Self {
+        self.api = Some(api.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Store and pin the blob; returns its CID
+    pub async fn upload(&self, blob: &OutputBlob) -> Result<String> {
+        let Some(api) = &self.api else {
+            bail!("no IPFS API configured for uploads");
+        };
+        let bytes = blob.to_bytes();
+        if bytes.len() > MAX_BLOB_BYTES {
+            bail!("output blob of {} bytes exceeds the {} byte block limit", bytes.len(), MAX_BLOB_BYTES);
+        }
+        let form = reqwest::multipart::Form::new()
+            .part("file", reqwest::multipart::Part::bytes(bytes));
+        let response = self.http
+            .post(format!("{}/api/v0/block/put?cid-codec=raw&mhtype=sha2-256&pin=true", api))
+            .multipart(form)
+            .send()
+            .await
+            .with_context(|| format!("IPFS API {} unreachable", api))?;
+        if !response.status().is_success() {
+            bail!("IPFS API {} answered {}", api, response.status());
+        }
+        let put: BlockPutResponse = response.json().await.context("unexpected block/put response")?;
+        let cid = blob.cid();
+        if put.key != cid {
+            bail!("IPFS stored the output as {}, expected {}", put.key, cid);
+        }
+        Ok(cid)
+    }
+
+    /// The blob whose hash is `output_hash` (hex, 0x optional); fails unless the bytes match it
+    pub async fn fetch(&self, output_hash: &str) -> Result<OutputBlob> {
+        let hash: [u8; 32] = hex::decode(output_hash.trim_start_matches("0x"))?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("expected 32 bytes: {}", output_hash))?;
+        let cid = output_cid(&hash);
+        let response = self.http
+            .get(format!("{}/ipfs/{}", self.gateway, cid))
+            .header(reqwest::header::ACCEPT, "application/vnd.ipld.raw")
+            .send()
+            .await
+            .with_context(|| format!("IPFS gateway {} unreachable", self.gateway))?;
+        if !response.status().is_success() {
+            bail!("output {} not available: gateway answered {}", cid, response.status());
+        }
+        if response.content_length().is_some_and(|len| len > MAX_BLOB_BYTES as u64) {
+            bail!("output {} exceeds {} bytes", cid, MAX_BLOB_BYTES);
+        }
+        let bytes = response.bytes().await?;
+        if bytes.len() > MAX_BLOB_BYTES {
+            bail!("output {} exceeds {} bytes", cid, MAX_BLOB_BYTES);
+        }
+        if Sha256::digest(&bytes)[..] != hash[..] {
+            bail!("output {} does not match hash {}", cid, output_hash);
+        }
+        OutputBlob::from_bytes(&bytes)
+    }
+}
@@ -0,0 +1,236 @@
+// Playground: run a snippet of the Python subset without posting a job. Nothing touches the
+// chain; code runs on a dedicated executor under limits far below any real profile, each client
+// address is rate limited, and outcomes are cached by code and input, so a popular example
+// costs one run however often it is tried.
+
+use anyhow::{Result, bail};
+use axum::{
+    extract::{ConnectInfo, State, Json},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tower_http::cors::CorsLayer;
+
+use crate::profiles::ExecutionProfile;
+use crate::rate_limit::RateLimiter;
+use crate::compiler::SourceLocation;
+use crate::{PythonExecutor, Trapped};
+
+/// Limits of the playground
+#[derive(Debug, Clone, Copy)]
+pub struct PlaygroundConfig {
+    /// Sustained runs per client address
+    pub requests_per_minute: u32,
+    /// Runs a client may make at once
+    pub burst: u32,
+    pub fuel_limit: u64,
+    /// Snippets are compiled for this many pages, so their heap ends inside the sandbox's memory
+    pub max_memory_pages: u32,
+    pub max_output_bytes: u32,
+    pub max_wall_clock_ms: u64,
+    /// Longest source accepted, in bytes
+    pub max_code_bytes: usize,
+    /// Outcomes kept; the oldest is dropped first
+    pub cache_entries: usize,
+}
+
+impl Default for PlaygroundConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 6,
+            burst: 3,
+            fuel_limit: 20_000,
+            max_memory_pages: 16,
+            max_output_bytes: 4096,
+            max_wall_clock_ms: 1_000,
+            max_code_bytes: 8 * 1024,
+            cache_entries: 1024,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlaygroundRequest {
+    pub code: String,
+    #[serde(default = "empty_input")]
+    pub input: serde_json::Value,
+}
+
+fn empty_input() -> serde_json::Value {
+    serde_json::json!({})
+}
+
+/// Outcome of a playground run; a program that fails to compile or run is an outcome too
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaygroundResult {
+    pub success: bool,
+    pub result: Option<String>,
+    #[serde(default)]
+    pub stdout: Vec<String>,
+    pub output_hash: Option<String>,
+    pub fuel_consumed: u64,
+    pub error: Option<String>,
+    /// Python line the run trapped on, when it got as far as running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trap: Option<SourceLocation>,
+    /// Served from the cache rather than run for this request
+    #[serde(default)]
+    pub cached: bool,
+}
+
+pub struct Playground {
+    // its own executor, so playground runs never wait on or delay real jobs
+    executor: Mutex<PythonExecutor>,
+    profile: ExecutionProfile,
+    limiter: RateLimiter,
+    max_code_bytes: usize,
+    cache: Mutex<ResultCache>,
+}
+
+impl Playground {
+    pub fn new(config: PlaygroundConfig) -> Result<Self> {
+        let profile = ExecutionProfile {
+            fuel_limit: config.fuel_limit,
+            max_memory_pages: config.max_memory_pages,
+            max_output_bytes: config.max_output_bytes,
+            max_wall_clock_ms: config.max_wall_clock_ms,
+            allowed_builtins: None,
+        };
+        profile.validate()?;
+
+        Ok(Self {
+            executor: Mutex::new(PythonExecutor::new()?),
+            profile,
+            limiter: RateLimiter::new(config.requests_per_minute, config.burst),
+            max_code_bytes: config.max_code_bytes,
+            cache: Mutex::new(ResultCache::new(config.cache_entries)),
+        })
+    }
+
+    /// Compile and run `code`, or return the cached outcome of an identical earlier run.
+    /// Err only for requests refused before running: oversized code or unencodable input.
+    pub fn run(&self, code: &str, input: &serde_json::Value) -> Result<PlaygroundResult> {
+        if code.len() > self.max_code_bytes {
+            bail!("code exceeds the playground limit of {} bytes", self.max_code_bytes);
+        }
+        let input = serde_json::to_string(input)?;
+
+        let key = cache_key(code, &input);
+        if let Some(mut hit) = self.cache.lock().unwrap().get(&key) {
+            hit.cached = true;
+            return Ok(hit);
+        }
+
+        let outcome = self.executor.lock().unwrap().execute_with_profile(code, &input, &self.profile);
+        let result = match outcome {
+            Ok(output) => PlaygroundResult {
+                success: output.success,
+                result: Some(output.result),
+                stdout: output.stdout,
+                output_hash: Some(output.output_hash),
+                fuel_consumed: output.fuel_consumed,
+                error: None,
+                trap: None,
+                cached: false,
+            },
+            Err(e) => {
+                let trapped = e.downcast_ref::<Trapped>();
+                PlaygroundResult {
+                    success: false,
+                    result: None,
+                    stdout: vec![],
+                    output_hash: None,
+                    fuel_consumed: trapped.map_or(0, |t| t.output.fuel_consumed),
+                    error: Some(e.to_string()),
+                    trap: trapped.and_then(|t| t.output.trap.clone()),
+                    cached: false,
+                }
+            }
+        };
+
+        self.cache.lock().unwrap().insert(key, result.clone());
+        Ok(result)
+    }
+
+    pub fn cached_results(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    /// `POST /playground/execute`, rate limited per client address. Needs a server built with
+    /// connect info.
+    pub fn routes(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/playground/execute", post(execute))
+            .layer(CorsLayer::permissive())
+            .with_state(self)
+    }
+}
+
+async fn execute(
+    State(playground): State<Arc<Playground>>,
+    ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>,
+    Json(req): Json<PlaygroundRequest>,
+) -> Response {
+    if let Err(wait) = playground.limiter.check(addr.ip()) {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, (wait.as_secs() + 1).to_string())],
+            "Rate limit exceeded",
+        ).into_response();
+    }
+
+    let ran = tokio::task::spawn_blocking(move || playground.run(&req.code, &req.input)).await;
+    match ran {
+        Ok(Ok(result)) => Json(result).into_response(),
+        Ok(Err(e)) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+fn cache_key(code: &str, input: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(code.as_bytes());
+    hasher.update([0]);
+    hasher.update(input.as_bytes());
+    hasher.finalize().into()
+}
+
+struct ResultCache {
+    capacity: usize,
+    entries: HashMap<[u8; 32], PlaygroundResult>,
+    order: VecDeque<[u8; 32]>,
+}
+
+impl ResultCache {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, entries: HashMap::new(), order: VecDeque::new() }
+    }
+
+    fn get(&self, key: &[u8; 32]) -> Option<PlaygroundResult> {
+        self.entries.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: [u8; 32], result: PlaygroundResult) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.insert(key, result).is_none() {
+            self.order.push_back(key);
+        }
+        while self.entries.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
@@ -0,0 +1,83 @@
+// A fixed set of executors that jobs check out one at a time, so up to `size` jobs compile and
+// run at once. Each executor keeps its own engine: the wall clock interrupts a run by bumping
+// its engine's epoch, which would trap every store on a shared engine. Every job still gets a
+// fresh store, and every executor is built alike, so which one a job lands on changes nothing
+// about its output or fuel.
+
+use anyhow::{Result, bail};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex};
+
+use crate::PythonExecutor;
+
+pub struct ExecutorPool {
+    idle: Mutex<Vec<PythonExecutor>>,
+    returned: Condvar,
+    size: usize,
+}
+
+impl ExecutorPool {
+    /// `size` executors, each from `make`
+    pub fn new(size: usize, make: impl Fn() -> Result<PythonExecutor>) -> Result<Self> {
+        if size == 0 {
+            bail!("executor pool needs at least one executor");
+        }
+        let idle = (0..size).map(|_| make()).collect::<Result<Vec<_>>>()?;
+        Ok(Self { idle: Mutex::new(idle), returned: Condvar::new(), size })
+    }
+
+    /// A pool of one, for callers that built their executor themselves
+    pub fn single(executor: PythonExecutor) -> Self {
+        Self { idle: Mutex::new(vec![executor]), returned: Condvar::new(), size: 1 }
+    }
+
+    /// Check out an executor, waiting for one to come back if all are busy. The wait blocks
+    /// the thread, so async callers check out inside `spawn_blocking`
+    pub fn get(&self) -> PooledExecutor<'_> {
+        let mut idle = self.idle.lock().unwrap();
+        loop {
+            if let Some(executor) = idle.pop() {
+                return PooledExecutor { pool: self, executor: Some(executor) };
+            }
+            idle = self.returned.wait(idle).unwrap();
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Executors not checked out right now
+    pub fn idle(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+}
+
+/// An executor checked out of a pool; it goes back when dropped
+pub struct PooledExecutor<'a> {
+    pool: &'a ExecutorPool,
+    executor: Option<PythonExecutor>,
+}
+
+impl Deref for PooledExecutor<'_> {
+    type Target = PythonExecutor;
+
+    fn deref(&self) -> &PythonExecutor {
+        self.executor.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledExecutor<'_> {
+    fn deref_mut(&mut self) -> &mut PythonExecutor {
+        self.executor.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledExecutor<'_> {
+    fn drop(&mut self) {
+        if let Some(executor) = self.executor.take() {
+            self.pool.idle.lock().unwrap().push(executor);
+            self.pool.returned.notify_one();
+        }
+    }
+}
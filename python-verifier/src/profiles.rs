@@ -0,0 +1,149 @@
+// Execution profiles: named ceilings a client picks at submission. One profile drives the
+// compiler's builtin set, the sandbox (memory, output, wall clock) and the limits posted
+// with createJob, so a job is never accepted on-chain under limits the executor won't honour.
+//
+// Profiles are configured as a JSON object of name to profile:
+//   { "small": { "fuel_limit": 50000, "max_memory_pages": 16, "max_output_bytes": 4096,
+//                "max_wall_clock_ms": 2000, "allowed_builtins": ["len", "sum"] } }
+// A "default" profile with the built-in ceilings is added unless the file defines one.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::compiler::{PythonCompiler, ResourceLimits};
+use crate::MAX_MEMORY_PAGES;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Fuel the sandbox accepts; limits outside this range are clamped at run time
+pub const MIN_FUEL: u64 = 1_000;
+pub const MAX_FUEL: u64 = 100_000_000;
+/// Largest output `validate_output` accepts
+pub const MAX_OUTPUT_BYTES: u32 = 1_000_000;
+
+const WASM_PAGE_SIZE: u64 = 65536;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExecutionProfile {
+    /// Fuel the job is posted with and run under
+    pub fuel_limit: u64,
+    /// Linear memory the job may grow to, in 64 KiB pages
+    pub max_memory_pages: u32,
+    pub max_output_bytes: u32,
+    /// Wall-clock budget per run; fuel bounds the guest, this bounds a stalled host
+    pub max_wall_clock_ms: u64,
+    /// Builtins compiled Python may call; `None` allows all of them
+    #[serde(default)]
+    pub allowed_builtins: Option<BTreeSet<String>>,
+}
+
+impl Default for ExecutionProfile {
+    fn default() -> Self {
+        Self {
+            fuel_limit: 100_000,
+            max_memory_pages: MAX_MEMORY_PAGES,
+            max_output_bytes: 100 * 1024,
+            max_wall_clock_ms: 10_000,
+            allowed_builtins: None,
+        }
+    }
+}
+
+impl ExecutionProfile {
+    /// The createJob memLimit field, in bytes
+    pub fn mem_limit(&self) -> u64 {
+        self.max_memory_pages as u64 * WASM_PAGE_SIZE
+    }
+
+    /// Narrow to the limits a job was posted with on-chain; they bind whatever the profile allows
+    pub fn with_chain_limits(&self, fuel_limit: u64, mem_limit: u64, max_output_size: u32) -> Self {
+        let job = ResourceLimits::from_job(fuel_limit, mem_limit, max_output_size);
+        Self {
+            fuel_limit: job.fuel_limit,
+            max_memory_pages: self.max_memory_pages.min(job.memory_pages.max(1)),
+            max_output_bytes: self.max_output_bytes.min(job.max_output_bytes),
+            ..self.clone()
+        }
+    }
+
+    /// What a job under this profile is compiled for and run under
+    pub fn resource_limits(&self) -> ResourceLimits {
+        ResourceLimits {
+            fuel_limit: self.fuel_limit.clamp(MIN_FUEL, MAX_FUEL),
+            memory_pages: self.max_memory_pages,
+            max_output_bytes: self.max_output_bytes,
+            ..ResourceLimits::default()
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(MIN_FUEL..=MAX_FUEL).contains(&self.fuel_limit) {
+            bail!("fuel_limit must be between {} and {}", MIN_FUEL, MAX_FUEL);
+        }
+        if self.max_memory_pages == 0 || self.max_memory_pages > MAX_MEMORY_PAGES {
+            bail!("max_memory_pages must be between 1 and {}", MAX_MEMORY_PAGES);
+        }
+        if self.max_output_bytes == 0 || self.max_output_bytes > MAX_OUTPUT_BYTES {
+            bail!("max_output_bytes must be between 1 and {}", MAX_OUTPUT_BYTES);
+        }
+        if self.max_wall_clock_ms == 0 {
+            bail!("max_wall_clock_ms must be positive");
+        }
+        if let Some(allowed) = &self.allowed_builtins {
+            // the compiler rejects names it has no builtin for
+            PythonCompiler::new()
+                .with_allowed_builtins(Some(allowed.clone()))
+                .compile("OUTPUT = 0")?;
+        }
+        Ok(())
+    }
+}
+
+/// Configured profiles by name; always has a default
+#[derive(Debug, Clone)]
+pub struct ProfileSet {
+    profiles: BTreeMap<String, ExecutionProfile>,
+}
+
+impl Default for ProfileSet {
+    fn default() -> Self {
+        Self {
+            profiles: BTreeMap::from([(DEFAULT_PROFILE.to_string(), ExecutionProfile::default())]),
+        }
+    }
+}
+
+impl ProfileSet {
+    pub fn load(path: &str) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("reading profiles from {}", path))?;
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut profiles: BTreeMap<String, ExecutionProfile> = serde_json::from_str(json)
+            .context("invalid profile config")?;
+        for (name, profile) in &profiles {
+            profile.validate().with_context(|| format!("profile {}", name))?;
+        }
+        profiles.entry(DEFAULT_PROFILE.to_string()).or_default();
+        Ok(Self { profiles })
+    }
+
+    /// The named profile, or the default when the job names none
+    pub fn get(&self, name: Option<&str>) -> Result<&ExecutionProfile> {
+        let name = name.unwrap_or(DEFAULT_PROFILE);
+        self.profiles.get(name)
+            .with_context(|| format!("unknown execution profile {}", name))
+    }
+
+    pub fn default_profile(&self) -> &ExecutionProfile {
+        &self.profiles[DEFAULT_PROFILE]
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+}
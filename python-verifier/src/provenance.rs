@@ -0,0 +1,134 @@
+// Build provenance. Every binary knows the commit and Cargo.lock it was built from (see
+// build.rs); the build id hashes them together with the crate version. Receipts sign the id,
+// /status serves a report signed by the node key, and a verifier holding an allowlist of
+// reviewed builds can tell whether a peer runs one of them.
+
+use anyhow::{Context, Result, bail};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, Signature};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+
+/// What this binary was built from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub version: String,
+    /// Commit hash, suffixed -dirty for builds of a modified tree
+    pub git_commit: String,
+    /// Hex sha256 of the workspace Cargo.lock
+    pub lock_hash: String,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: env!("CERTUS_GIT_COMMIT").to_string(),
+            lock_hash: env!("CERTUS_LOCK_HASH").to_string(),
+        }
+    }
+
+    /// sha256 over the version, commit and lock hash, NUL-separated
+    pub fn id(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for field in [&self.version, &self.git_commit, &self.lock_hash] {
+            hasher.update(field.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.finalize().into()
+    }
+
+    /// 0x-prefixed build id, the form receipts and allowlists use
+    pub fn id_hex(&self) -> String {
+        format!("0x{}", hex::encode(self.id()))
+    }
+}
+
+/// A node's claim about the build it runs, as served at /status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildReport {
+    pub build: BuildInfo,
+    pub build_id: String,
+    pub node: String,
+    /// EIP-191 signature over the 32-byte build id; recovers to `node`
+    pub signature: String,
+}
+
+impl BuildReport {
+    pub fn sign(build: BuildInfo, wallet: &LocalWallet) -> Result<Self> {
+        let signature = wallet.sign_hash(ethers::utils::hash_message(build.id()))
+            .context("signing build report")?;
+        Ok(Self {
+            build_id: build.id_hex(),
+            build,
+            node: format!("{:?}", wallet.address()),
+            signature: format!("0x{}", hex::encode(signature.to_vec())),
+        })
+    }
+
+    /// Check the id matches the build fields and the signature was made by `node`
+    pub fn verify(&self) -> Result<()> {
+        if self.build_id != self.build.id_hex() {
+            bail!("build id {} does not match the reported build {}", self.build_id, self.build.id_hex());
+        }
+        let signature: Signature = self.signature.trim_start_matches("0x").parse()
+            .context("invalid build report signature")?;
+        let node: Address = self.node.parse().context("invalid node address")?;
+        if signature.recover(self.build.id().to_vec())? != node {
+            bail!("build report not signed by {:?}", node);
+        }
+        Ok(())
+    }
+}
+
+/// Builds a verifier accepts from its peers
+#[derive(Debug, Clone, Default)]
+pub struct BuildPolicy {
+    allowed: BTreeSet<String>,
+}
+
+impl BuildPolicy {
+    pub fn new(build_ids: impl IntoIterator<Item = String>) -> Self {
+        Self { allowed: build_ids.into_iter().map(|id| normalize_build_id(&id)).collect() }
+    }
+
+    /// Load a JSON array of 0x build ids
+    pub fn load(path: &str) -> Result<Self> {
+        let raw = std::fs::read(path).with_context(|| format!("Cannot read build allowlist {}", path))?;
+        let ids: Vec<String> = serde_json::from_slice(&raw)
+            .with_context(|| format!("Corrupt build allowlist {}", path))?;
+        Ok(Self::new(ids))
+    }
+
+    pub fn allows_id(&self, build_id: &str) -> bool {
+        self.allowed.contains(&normalize_build_id(build_id))
+    }
+
+    /// Accept a report only if it is authentic and names an allowlisted build
+    pub fn check_report(&self, report: &BuildReport) -> Result<()> {
+        report.verify()?;
+        if !self.allows_id(&report.build_id) {
+            bail!(
+                "node {} runs build {} (commit {}), which is not allowlisted",
+                report.node, report.build_id, report.build.git_commit,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Fetch a peer's build report from its /status endpoint
+pub async fn fetch_report(client: &reqwest::Client, base_url: &str) -> Result<BuildReport> {
+    let url = format!("{}/status", base_url.trim_end_matches('/'));
+    let response = client.get(&url).send().await
+        .with_context(|| format!("peer {} unreachable", url))?;
+    if !response.status().is_success() {
+        bail!("peer {} answered {}", url, response.status());
+    }
+    response.json().await.with_context(|| format!("peer {} sent no build report", url))
+}
+
+fn normalize_build_id(build_id: &str) -> String {
+    format!("0x{}", build_id.trim_start_matches("0x").to_lowercase())
+}
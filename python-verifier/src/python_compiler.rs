@@ -1,2 +1,3 @@
-// Re-export compiler for backward compatibility
+// The flat compiler that lived here was folded into `compiler`; this path stays for existing
+// callers and names the very same type, so both emit identical modules
 pub use crate::compiler::PythonCompiler;
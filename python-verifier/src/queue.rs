@@ -1,64 +1,514 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::compiler::CompileOptions;
+use crate::executor_error::ExecutorError;
+use crate::websocket::JobUpdate;
+
+/// Upper bounds (seconds) of the time-in-queue histogram buckets
+pub const WAIT_BUCKETS: [f64; 9] = [0.1, 0.5, 1.0, 5.0, 15.0, 60.0, 300.0, 900.0, 3600.0];
+
+/// How long a claimed job stays invisible before another `next()` may take it
+pub const DEFAULT_VISIBILITY_TIMEOUT_MS: u64 = 5 * 60 * 1000;
+/// Retry backoff: base * 2^(retry - 1), capped
+pub const RETRY_BACKOFF_BASE_MS: u64 = 5_000;
+pub const MAX_RETRY_BACKOFF_MS: u64 = 10 * 60 * 1000;
+
+// upper bound on how long next() sleeps without rescanning
+const MAX_IDLE_WAIT_MS: u64 = 1_000;
 
 /// Cross-platform persistent job queue using sled
 pub struct JobQueue {
     db: Arc<sled::Db>,
-    sender: mpsc::Sender<JobCommand>,
-    receiver: Arc<RwLock<mpsc::Receiver<JobCommand>>>,
+    // wakes next() when a job is submitted or re-queued
+    wakeup: Notify,
+    // serializes scan-and-claim so two workers never take the same job
+    claim: tokio::sync::Mutex<()>,
+    // serializes appends so each update links to the one recorded before it
+    history: Mutex<()>,
+    visibility_timeout_ms: u64,
+    metrics: QueueMetrics,
+}
+
+/// Where workers claim jobs and report outcomes: a local queue, or one shared over the network
+pub trait QueueBackend: Send + Sync {
+    /// Claim the next job for one visibility timeout; None when none came up in a while
+    fn claim(&self) -> impl Future<Output = Result<Option<QueuedJob>>> + Send;
+    fn complete(&self, job_id: &str, result: serde_json::Value) -> impl Future<Output = Result<()>> + Send;
+    /// `kind` when the executor knew why the job failed; it decides the failure class
+    fn fail(&self, job_id: &str, error: &str, kind: Option<&ExecutorError>) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Coarse failure classes so dashboards don't explode on free-form error strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    Validation,
+    Compile,
+    Timeout,
+    Execution,
+    Chain,
+    Dependency,
+    Other,
+}
+
+impl FailureClass {
+    pub fn classify(error: &str) -> Self {
+        let e = error.to_lowercase();
+        let has = |words: &[&str]| words.iter().any(|w| e.contains(w));
+
+        if has(&["invalid", "validat", "not allowed", "exceeds"]) {
+            FailureClass::Validation
+        } else if has(&["compile", "parse", "unsupported", "not defined"]) {
+            FailureClass::Compile
+        } else if has(&["fuel", "timeout", "timed out", "deadline"]) {
+            FailureClass::Timeout
+        } else if has(&["trap", "wasm", "execution", "panic"]) {
+            FailureClass::Execution
+        } else if has(&["rpc", "transaction", "contract", "nonce", "gas", "revert"]) {
+            FailureClass::Chain
+        } else {
+            FailureClass::Other
+        }
+    }
+
+    pub fn of(kind: &ExecutorError) -> Self {
+        match kind {
+            ExecutorError::ValidationFailed { .. } | ExecutorError::OutputTooLarge { .. } => FailureClass::Validation,
+            ExecutorError::CompileError { .. } => FailureClass::Compile,
+            ExecutorError::OutOfFuel { .. } | ExecutorError::Timeout { .. } => FailureClass::Timeout,
+            ExecutorError::OutOfMemory { .. } | ExecutorError::Trap { .. } => FailureClass::Execution,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureClass::Validation => "validation",
+            FailureClass::Compile => "compile",
+            FailureClass::Timeout => "timeout",
+            FailureClass::Execution => "execution",
+            FailureClass::Chain => "chain",
+            FailureClass::Dependency => "dependency",
+            FailureClass::Other => "other",
+        }
+    }
+}
+
+/// In-process queue counters; reset on restart
+#[derive(Default)]
+struct QueueMetrics {
+    enqueued: AtomicU64,
+    dequeued: AtomicU64,
+    completed: AtomicU64,
+    retries: AtomicU64,
+    dead_lettered: AtomicU64,
+    failures: Mutex<BTreeMap<FailureClass, u64>>,
+    wait: Mutex<WaitHistogram>,
+}
+
+#[derive(Default)]
+struct WaitHistogram {
+    buckets: [u64; WAIT_BUCKETS.len()],
+    sum_secs: f64,
+    count: u64,
+}
+
+impl QueueMetrics {
+    fn record_enqueue(&self) {
+        self.enqueued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // wait is measured from when the job became available, not when it was submitted
+    fn record_dequeue(&self, wait_ms: u64) {
+        self.dequeued.fetch_add(1, Ordering::Relaxed);
+        let secs = wait_ms as f64 / 1000.0;
+
+        let mut wait = self.wait.lock().unwrap();
+        for (bucket, le) in wait.buckets.iter_mut().zip(WAIT_BUCKETS) {
+            if secs <= le {
+                *bucket += 1;
+            }
+        }
+        wait.sum_secs += secs;
+        wait.count += 1;
+    }
+
+    fn record_failure(&self, class: FailureClass) {
+        *self.failures.lock().unwrap().entry(class).or_insert(0) += 1;
+    }
+}
+
+/// Point-in-time view of queue health, served by /admin/queue/stats and /metrics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueStats {
+    pub enqueued_total: u64,
+    pub dequeued_total: u64,
+    pub completed_total: u64,
+    pub retries_total: u64,
+    pub dead_lettered_total: u64,
+    pub failures_by_class: BTreeMap<String, u64>,
+    /// Jobs currently stored per status: pending, blocked, delayed, running, completed, failed, skipped
+    pub jobs_by_status: BTreeMap<String, u64>,
+    pub oldest_pending_age_secs: Option<u64>,
+    pub wait_time: WaitStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaitStats {
+    /// Cumulative counts for each bound in WAIT_BUCKETS
+    pub buckets: Vec<(f64, u64)>,
+    pub sum_secs: f64,
+    pub count: u64,
+}
+
+impl QueueStats {
+    /// Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+            out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+            for (labels, value) in samples {
+                out.push_str(&format!("{}{} {}\n", name, labels, value));
+            }
+        };
+
+        let plain = |v: u64| vec![(String::new(), v.to_string())];
+        metric("certus_queue_enqueued_total", "counter", "Jobs pushed onto the queue, including retries", plain(self.enqueued_total));
+        metric("certus_queue_dequeued_total", "counter", "Jobs handed to a worker", plain(self.dequeued_total));
+        metric("certus_queue_completed_total", "counter", "Jobs completed successfully", plain(self.completed_total));
+        metric("certus_queue_retries_total", "counter", "Failed jobs re-queued for another attempt", plain(self.retries_total));
+        metric("certus_queue_dead_lettered_total", "counter", "Jobs that exhausted their retries", plain(self.dead_lettered_total));
+        metric("certus_queue_failures_total", "counter", "Failed attempts by error class",
+            self.failures_by_class.iter()
+                .map(|(class, n)| (format!("{{class=\"{}\"}}", class), n.to_string()))
+                .collect());
+        metric("certus_queue_jobs", "gauge", "Jobs stored per status",
+            self.jobs_by_status.iter()
+                .map(|(status, n)| (format!("{{status=\"{}\"}}", status), n.to_string()))
+                .collect());
+        metric("certus_queue_oldest_pending_age_seconds", "gauge", "Age of the oldest pending job",
+            plain(self.oldest_pending_age_secs.unwrap_or(0)));
+
+        let mut wait: Vec<(String, String)> = self.wait_time.buckets.iter()
+            .map(|(le, n)| (format!("_bucket{{le=\"{}\"}}", le), n.to_string()))
+            .collect();
+        wait.push(("_bucket{le=\"+Inf\"}".to_string(), self.wait_time.count.to_string()));
+        wait.push(("_sum".to_string(), self.wait_time.sum_secs.to_string()));
+        wait.push(("_count".to_string(), self.wait_time.count.to_string()));
+        metric("certus_queue_wait_seconds", "histogram", "Time between enqueue and dequeue", wait);
+
+        out
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueuedJob {
     pub id: String,
     pub code: String,
+    /// Base64 client-compiled module, run instead of `code` when set
+    #[serde(default)]
+    pub wasm_b64: Option<String>,
     pub input: serde_json::Value,
     pub priority: u8,
     pub created_at: u64,
     pub retry_count: u8,
     pub max_retries: u8,
+    /// Unix millis before which the job is invisible to `next()`; 0 = immediately
+    #[serde(default)]
+    pub available_at: u64,
+    /// Unix millis of the latest claim; cleared when the job is re-queued
+    #[serde(default)]
+    pub claimed_at: Option<u64>,
+    /// Jobs that must complete first. Their results can be referenced from
+    /// `input` as "{{deps.<id>}}" or "{{deps.<id>.<field>...}}"
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// What happens to this job when a dependency fails or is skipped
+    #[serde(default)]
+    pub on_dependency_failure: DependencyFailure,
+    /// Submitting tenant; selects redaction behaviour for logs and broadcasts
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// Execution profile to run under; the default one when unset
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// How `code` is compiled; ignored for `wasm_b64` jobs
+    #[serde(default)]
+    pub compile_options: CompileOptions,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyFailure {
+    /// Record this job as failed; its own dependents see a failure
+    #[default]
+    Fail,
+    /// Record this job as skipped without counting it as a failure
+    Skip,
+}
+
+// Where a job stands with respect to its dependencies
+enum DependencyState {
+    Ready(BTreeMap<String, serde_json::Value>),
+    Waiting,
+    Broken(String),
+}
+
+/// Substitute dependency results into a job input.
+/// A string that is exactly one placeholder is replaced by the referenced JSON value;
+/// placeholders inside longer strings are spliced in as text.
+pub fn render_input(
+    input: &serde_json::Value,
+    results: &BTreeMap<String, serde_json::Value>,
+) -> Result<serde_json::Value> {
+    use serde_json::Value;
+
+    Ok(match input {
+        Value::String(text) => {
+            let trimmed = text.trim();
+            if trimmed.starts_with("{{") && trimmed.ends_with("}}") && trimmed.matches("{{").count() == 1 {
+                lookup_placeholder(&trimmed[2..trimmed.len() - 2], results)?
+            } else {
+                let mut out = String::new();
+                let mut rest = text.as_str();
+                while let Some(start) = rest.find("{{") {
+                    let Some(end) = rest[start..].find("}}") else {
+                        break;
+                    };
+                    out.push_str(&rest[..start]);
+                    match lookup_placeholder(&rest[start + 2..start + end], results)? {
+                        Value::String(s) => out.push_str(&s),
+                        other => out.push_str(&other.to_string()),
+                    }
+                    rest = &rest[start + end + 2..];
+                }
+                out.push_str(rest);
+                Value::String(out)
+            }
+        }
+        Value::Array(items) => Value::Array(
+            items.iter().map(|v| render_input(v, results)).collect::<Result<_>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), render_input(v, results)?)))
+                .collect::<Result<_>>()?,
+        ),
+        other => other.clone(),
+    })
 }
 
-#[derive(Debug)]
-pub enum JobCommand {
-    Submit(QueuedJob),
+fn lookup_placeholder(
+    path: &str,
+    results: &BTreeMap<String, serde_json::Value>,
+) -> Result<serde_json::Value> {
+    let mut parts = path.trim().split('.');
+    if parts.next() != Some("deps") {
+        anyhow::bail!("unknown placeholder '{{{{{}}}}}'", path.trim());
+    }
+    let dep = parts.next().unwrap_or_default();
+    let mut value = results.get(dep)
+        .ok_or_else(|| anyhow::anyhow!("placeholder references '{}' which is not in depends_on", dep))?;
+
+    for field in parts {
+        value = match value {
+            serde_json::Value::Array(items) => field.parse::<usize>().ok().and_then(|i| items.get(i)),
+            other => other.get(field),
+        }
+        .ok_or_else(|| anyhow::anyhow!("dependency '{}' result has no field '{}'", dep, field))?;
+    }
+
+    Ok(value.clone())
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+/// Delay before retry number `retry` (1-based)
+pub fn retry_backoff_ms(retry: u8) -> u64 {
+    let shift = u32::from(retry.saturating_sub(1)).min(16);
+    (RETRY_BACKOFF_BASE_MS << shift).min(MAX_RETRY_BACKOFF_MS)
 }
 
 impl JobQueue {
     /// Create persistent queue that works on all platforms
     pub fn new(path: &str) -> Result<Self> {
         let db = Arc::new(sled::open(path)?);
-        let (tx, rx) = mpsc::channel(1000);
 
         Ok(Self {
             db,
-            sender: tx,
-            receiver: Arc::new(RwLock::new(rx)),
+            wakeup: Notify::new(),
+            claim: tokio::sync::Mutex::new(()),
+            history: Mutex::new(()),
+            visibility_timeout_ms: DEFAULT_VISIBILITY_TIMEOUT_MS,
+            metrics: QueueMetrics::default(),
         })
     }
 
-    /// Submit job to queue
-    pub async fn submit(&self, job: QueuedJob) -> Result<String> {
+    pub fn with_visibility_timeout(mut self, timeout: Duration) -> Self {
+        self.visibility_timeout_ms = timeout.as_millis() as u64;
+        self
+    }
+
+    /// Submit job to queue; a future `available_at` schedules it
+    pub async fn submit(&self, mut job: QueuedJob) -> Result<String> {
         let job_id = job.id.clone();
         let key = format!("job:{}", job_id);
-        let value = serde_json::to_vec(&job)?;
 
-        self.db.insert(key.as_bytes(), value)?;
-        self.sender.send(JobCommand::Submit(job)).await?;
+        // dependencies must already be known, which also rules out cycles
+        for dep in &job.depends_on {
+            if *dep == job_id {
+                anyhow::bail!("job '{}' depends on itself", job_id);
+            }
+            let known = ["job:", "result:", "error:", "skipped:"].iter()
+                .map(|prefix| self.db.contains_key(format!("{}{}", prefix, dep).as_bytes()))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            if !known.contains(&true) {
+                anyhow::bail!("job '{}' depends on unknown job '{}'", job_id, dep);
+            }
+        }
+        if job.available_at == 0 {
+            job.available_at = now_ms();
+        }
+        job.claimed_at = None;
+
+        self.db.insert(key.as_bytes(), serde_json::to_vec(&job)?)?;
+        self.metrics.record_enqueue();
+        self.wakeup.notify_one();
 
         Ok(job_id)
     }
 
-    /// Get next job
+    /// Wait for the next due job and claim it for one visibility timeout.
+    /// Unfinished claims become visible again once the timeout passes.
     pub async fn next(&self) -> Result<Option<QueuedJob>> {
-        let mut receiver = self.receiver.write().await;
+        loop {
+            let next_due = {
+                let _claim = self.claim.lock().await;
+                match self.claim_due()? {
+                    Ok(job) => return Ok(Some(job)),
+                    Err(next_due) => next_due,
+                }
+            };
+
+            let idle = next_due
+                .map_or(MAX_IDLE_WAIT_MS, |at| at.saturating_sub(now_ms()))
+                .clamp(1, MAX_IDLE_WAIT_MS);
+            let _ = tokio::time::timeout(Duration::from_millis(idle), self.wakeup.notified()).await;
+        }
+    }
+
+    // Claim the due job with the highest priority (earliest available_at breaks ties),
+    // or report when the earliest job becomes due. Jobs whose dependencies are still
+    // running are passed over; jobs with a failed dependency are resolved here.
+    fn claim_due(&self) -> Result<std::result::Result<QueuedJob, Option<u64>>> {
+        let now = now_ms();
+        let mut best: Option<(QueuedJob, BTreeMap<String, serde_json::Value>)> = None;
+        let mut next_due: Option<u64> = None;
+        let mut propagated = false;
+
+        for item in self.db.scan_prefix(b"job:") {
+            let (_, value) = item?;
+            let Ok(job) = serde_json::from_slice::<QueuedJob>(&value) else {
+                continue;
+            };
+
+            if job.available_at > now {
+                next_due = Some(next_due.map_or(job.available_at, |d| d.min(job.available_at)));
+                continue;
+            }
+
+            let results = match self.dependency_state(&job)? {
+                DependencyState::Ready(results) => results,
+                DependencyState::Waiting => continue,
+                DependencyState::Broken(reason) => {
+                    self.resolve_broken(&job, &reason)?;
+                    propagated = true;
+                    continue;
+                }
+            };
+
+            let better = best.as_ref().is_none_or(|b| {
+                (job.priority, std::cmp::Reverse(job.available_at))
+                    > (b.0.priority, std::cmp::Reverse(b.0.available_at))
+            });
+            if better {
+                best = Some((job, results));
+            }
+        }
+
+        // downstream jobs of the ones just resolved can be settled right away
+        if propagated {
+            self.wakeup.notify_one();
+        }
+
+        let Some((mut job, results)) = best else {
+            return Ok(Err(next_due));
+        };
+
+        let input = match render_input(&job.input, &results) {
+            Ok(input) => input,
+            Err(e) => {
+                // a bad template never succeeds on retry
+                self.resolve_broken(&job, &e.to_string())?;
+                self.wakeup.notify_one();
+                return Ok(Err(next_due));
+            }
+        };
+
+        let wait_ms = now.saturating_sub(job.available_at);
+        job.claimed_at = Some(now);
+        job.available_at = now + self.visibility_timeout_ms;
+        self.db.insert(format!("job:{}", job.id).as_bytes(), serde_json::to_vec(&job)?)?;
+        self.metrics.record_dequeue(wait_ms);
+
+        // stored job keeps the template; the worker gets the rendered input
+        job.input = input;
+        Ok(Ok(job))
+    }
 
-        match receiver.recv().await {
-            Some(JobCommand::Submit(job)) => Ok(Some(job)),
-            _ => Ok(None),
+    fn dependency_state(&self, job: &QueuedJob) -> Result<DependencyState> {
+        let mut results = BTreeMap::new();
+
+        for dep in &job.depends_on {
+            if let Some(data) = self.db.get(format!("result:{}", dep).as_bytes())? {
+                results.insert(dep.clone(), serde_json::from_slice(&data)?);
+            } else if self.db.contains_key(format!("error:{}", dep).as_bytes())? {
+                return Ok(DependencyState::Broken(format!("dependency '{}' failed", dep)));
+            } else if self.db.contains_key(format!("skipped:{}", dep).as_bytes())? {
+                return Ok(DependencyState::Broken(format!("dependency '{}' was skipped", dep)));
+            } else if self.db.contains_key(format!("job:{}", dep).as_bytes())? {
+                return Ok(DependencyState::Waiting);
+            } else {
+                // result already cleaned up or never existed
+                return Ok(DependencyState::Broken(format!("dependency '{}' not found", dep)));
+            }
+        }
+
+        Ok(DependencyState::Ready(results))
+    }
+
+    // Terminal outcome for a job that can never run, per its on_dependency_failure policy
+    fn resolve_broken(&self, job: &QueuedJob, reason: &str) -> Result<()> {
+        match job.on_dependency_failure {
+            DependencyFailure::Fail => {
+                self.db.insert(format!("error:{}", job.id).as_bytes(), reason.as_bytes())?;
+                self.metrics.record_failure(FailureClass::Dependency);
+                self.metrics.dead_lettered.fetch_add(1, Ordering::Relaxed);
+            }
+            DependencyFailure::Skip => {
+                self.db.insert(format!("skipped:{}", job.id).as_bytes(), reason.as_bytes())?;
+            }
         }
+        self.db.remove(format!("job:{}", job.id).as_bytes())?;
+        Ok(())
     }
 
     /// Mark job complete
@@ -68,32 +518,125 @@ impl JobQueue {
 
         self.db.insert(result_key.as_bytes(), serde_json::to_vec(&result)?)?;
         self.db.remove(job_key.as_bytes())?;
+        self.metrics.completed.fetch_add(1, Ordering::Relaxed);
+        // unblock dependents
+        self.wakeup.notify_one();
 
         Ok(())
     }
 
-    /// Mark job failed
-    pub async fn fail(&self, job_id: &str, error: &str) -> Result<()> {
+    /// Mark job failed; `kind`, when the executor knew it, classifies the failure, and
+    /// otherwise its message does
+    pub async fn fail_as(&self, job_id: &str, error: &str, kind: Option<&ExecutorError>) -> Result<()> {
         let key = format!("job:{}", job_id);
+        self.metrics.record_failure(kind.map_or_else(|| FailureClass::classify(error), FailureClass::of));
 
         if let Some(data) = self.db.get(key.as_bytes())? {
             let mut job: QueuedJob = serde_json::from_slice(&data)?;
 
             if job.retry_count < job.max_retries {
                 job.retry_count += 1;
+                job.available_at = now_ms() + retry_backoff_ms(job.retry_count);
+                job.claimed_at = None;
                 self.db.insert(key.as_bytes(), serde_json::to_vec(&job)?)?;
-                // Re-submit for retry
-                self.sender.send(JobCommand::Submit(job)).await?;
+                // Re-queue with backoff
+                self.metrics.retries.fetch_add(1, Ordering::Relaxed);
+                self.metrics.record_enqueue();
+                self.wakeup.notify_one();
             } else {
                 let error_key = format!("error:{}", job_id);
                 self.db.insert(error_key.as_bytes(), error.as_bytes())?;
                 self.db.remove(key.as_bytes())?;
+                self.metrics.dead_lettered.fetch_add(1, Ordering::Relaxed);
+                // let dependents fail or skip
+                self.wakeup.notify_one();
             }
         }
 
         Ok(())
     }
 
+    /// Snapshot of counters plus per-status counts read from the store
+    pub fn stats(&self) -> Result<QueueStats> {
+        let now = chrono::Utc::now().timestamp() as u64;
+        let now_ms = now_ms();
+        let mut oldest: Option<u64> = None;
+        let (mut pending, mut blocked, mut delayed, mut running) = (0u64, 0u64, 0u64, 0u64);
+
+        for item in self.db.scan_prefix(b"job:") {
+            let (_, value) = item?;
+            let Ok(job) = serde_json::from_slice::<QueuedJob>(&value) else {
+                pending += 1;
+                continue;
+            };
+
+            if job.available_at <= now_ms {
+                match self.dependency_state(&job)? {
+                    DependencyState::Waiting => blocked += 1,
+                    _ => pending += 1,
+                }
+            } else if job.claimed_at.is_some() {
+                running += 1;
+            } else {
+                delayed += 1;
+            }
+            oldest = Some(oldest.map_or(job.created_at, |o| o.min(job.created_at)));
+        }
+
+        let mut jobs_by_status = BTreeMap::new();
+        jobs_by_status.insert("pending".to_string(), pending);
+        jobs_by_status.insert("blocked".to_string(), blocked);
+        jobs_by_status.insert("delayed".to_string(), delayed);
+        jobs_by_status.insert("running".to_string(), running);
+        jobs_by_status.insert("completed".to_string(), self.db.scan_prefix(b"result:").count() as u64);
+        jobs_by_status.insert("failed".to_string(), self.db.scan_prefix(b"error:").count() as u64);
+        jobs_by_status.insert("skipped".to_string(), self.db.scan_prefix(b"skipped:").count() as u64);
+
+        let m = &self.metrics;
+        let wait = m.wait.lock().unwrap();
+
+        Ok(QueueStats {
+            enqueued_total: m.enqueued.load(Ordering::Relaxed),
+            dequeued_total: m.dequeued.load(Ordering::Relaxed),
+            completed_total: m.completed.load(Ordering::Relaxed),
+            retries_total: m.retries.load(Ordering::Relaxed),
+            dead_lettered_total: m.dead_lettered.load(Ordering::Relaxed),
+            failures_by_class: m.failures.lock().unwrap().iter()
+                .map(|(class, n)| (class.as_str().to_string(), *n))
+                .collect(),
+            jobs_by_status,
+            oldest_pending_age_secs: oldest.map(|created| now.saturating_sub(created)),
+            wait_time: WaitStats {
+                buckets: WAIT_BUCKETS.iter().copied().zip(wait.buckets.iter().copied()).collect(),
+                sum_secs: wait.sum_secs,
+                count: wait.count,
+            },
+        })
+    }
+
+    /// Chain `update` onto its job's status history and store it; returns the linked update
+    pub fn record_update(&self, mut update: JobUpdate) -> Result<JobUpdate> {
+        let _append = self.history.lock().unwrap();
+        let mut history = self.history_of(&update.job_id)?;
+        update.link(history.last())?;
+        history.push(update.clone());
+        self.db.insert(history_key(&update.job_id).as_bytes(), serde_json::to_vec(&history)?)?;
+        Ok(update)
+    }
+
+    /// Every update recorded for the job, oldest first; see `websocket::verify_history`
+    pub fn history_of(&self, job_id: &str) -> Result<Vec<JobUpdate>> {
+        match self.db.get(history_key(job_id).as_bytes())? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Whether the job is waiting or claimed, as opposed to finished or never submitted
+    pub fn is_queued(&self, job_id: &str) -> Result<bool> {
+        Ok(self.db.contains_key(format!("job:{}", job_id).as_bytes())?)
+    }
+
     /// Clean old completed jobs
     pub fn cleanup_old(&self, older_than_secs: u64) -> Result<usize> {
         let now = chrono::Utc::now().timestamp() as u64;
@@ -115,4 +658,28 @@ impl JobQueue {
 
         Ok(deleted)
     }
-}
\ No newline at end of file
+}
+
+fn history_key(job_id: &str) -> String {
+    format!("history:{}", job_id)
+}
+
+// Claims wait at most this long, so a remote worker's request never outlives its timeout
+const CLAIM_WAIT: Duration = Duration::from_secs(20);
+
+impl QueueBackend for JobQueue {
+    async fn claim(&self) -> Result<Option<QueuedJob>> {
+        match tokio::time::timeout(CLAIM_WAIT, self.next()).await {
+            Ok(job) => job,
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn complete(&self, job_id: &str, result: serde_json::Value) -> Result<()> {
+        JobQueue::complete(self, job_id, result).await
+    }
+
+    async fn fail(&self, job_id: &str, error: &str, kind: Option<&ExecutorError>) -> Result<()> {
+        JobQueue::fail_as(self, job_id, error, kind).await
+    }
+}
@@ -0,0 +1,309 @@
+// Signed completion receipts. When a job this executor ran is finalized on-chain, the
+// executor signs (job id, output hash, fuel used, finalize tx, executor, build) and hands the
+// receipt to the client's webhook. Receipts are issued once and stored, so the webhook
+// payload and GET /jobs/:id/receipt are always byte-for-byte the same document.
+
+use anyhow::{Context, Result, bail};
+use ethers::abi::{encode, Token};
+use ethers::signers::LocalWallet;
+use ethers::types::{Address, Signature, H256, U256};
+use serde::{Deserialize, Serialize};
+
+use crate::provenance::BuildInfo;
+
+/// Give up on a webhook after this many failed deliveries; the receipt stays queryable
+pub const MAX_DELIVERY_ATTEMPTS: u32 = 10;
+
+const CURSOR_KEY: &[u8] = b"cursor";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedReceipt {
+    pub job_id: String,
+    /// Transaction that finalized the job
+    pub tx_hash: String,
+    pub output_hash: String,
+    pub fuel_used: u64,
+    pub executor: String,
+    /// Timestamp of the block holding `tx_hash`
+    pub finalized_at: u64,
+    /// Id of the build that ran the job; absent on receipts issued before builds were signed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<String>,
+    /// EIP-191 signature over `digest()`; recovers to `executor`
+    pub signature: String,
+}
+
+/// What the executor attests to
+#[derive(Debug, Clone)]
+pub struct Finalization {
+    pub job_id: [u8; 32],
+    pub tx_hash: H256,
+    pub output_hash: [u8; 32],
+    pub fuel_used: u64,
+    pub finalized_at: u64,
+}
+
+impl SignedReceipt {
+    pub fn sign(finalization: &Finalization, wallet: &LocalWallet) -> Result<Self> {
+        use ethers::signers::Signer;
+
+        let executor = wallet.address();
+        let build = BuildInfo::current().id();
+        let digest = receipt_digest(finalization, executor, Some(build));
+        let signature = wallet.sign_hash(ethers::utils::hash_message(digest))
+            .context("signing receipt")?;
+
+        Ok(Self {
+            job_id: hex_0x(&finalization.job_id),
+            tx_hash: format!("{:?}", finalization.tx_hash),
+            output_hash: hex_0x(&finalization.output_hash),
+            fuel_used: finalization.fuel_used,
+            executor: format!("{:?}", executor),
+            finalized_at: finalization.finalized_at,
+            build: Some(hex_0x(&build)),
+            signature: hex_0x(&signature.to_vec()),
+        })
+    }
+
+    /// keccak256(abi.encode(jobId, outputHash, fuelUsed, txHash, executor[, build])), the
+    /// message the signature covers (as an Ethereum signed message)
+    pub fn digest(&self) -> Result<[u8; 32]> {
+        let finalization = Finalization {
+            job_id: parse_bytes32(&self.job_id)?,
+            tx_hash: H256(parse_bytes32(&self.tx_hash)?),
+            output_hash: parse_bytes32(&self.output_hash)?,
+            fuel_used: self.fuel_used,
+            finalized_at: self.finalized_at,
+        };
+        let executor: Address = self.executor.parse().context("invalid executor address")?;
+        let build = self.build.as_deref().map(parse_bytes32).transpose()?;
+        Ok(receipt_digest(&finalization, executor, build))
+    }
+
+    /// Check the signature was made by `executor`
+    pub fn verify(&self) -> Result<()> {
+        let signature: Signature = self.signature.trim_start_matches("0x").parse()
+            .context("invalid receipt signature")?;
+        let executor: Address = self.executor.parse().context("invalid executor address")?;
+        let signer = signature.recover(self.digest()?.to_vec())?;
+        if signer != executor {
+            bail!("receipt signed by {:?}, not executor {:?}", signer, executor);
+        }
+        Ok(())
+    }
+}
+
+fn receipt_digest(finalization: &Finalization, executor: Address, build: Option<[u8; 32]>) -> [u8; 32] {
+    let mut tokens = vec![
+        Token::FixedBytes(finalization.job_id.to_vec()),
+        Token::FixedBytes(finalization.output_hash.to_vec()),
+        Token::Uint(U256::from(finalization.fuel_used)),
+        Token::FixedBytes(finalization.tx_hash.as_bytes().to_vec()),
+        Token::Address(executor),
+    ];
+    if let Some(build) = build {
+        tokens.push(Token::FixedBytes(build.to_vec()));
+    }
+    ethers::utils::keccak256(encode(&tokens))
+}
+
+/// A job submitted or executed through this node
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrackedJob {
+    pub webhook_url: Option<String>,
+    pub output_hash: Option<String>,
+    pub fuel_used: Option<u64>,
+    #[serde(default)]
+    pub delivered: bool,
+    #[serde(default)]
+    pub delivery_attempts: u32,
+    #[serde(default)]
+    pub last_delivery_error: Option<String>,
+    /// Secondary-chain transaction that notarized the result
+    #[serde(default)]
+    pub notarization_tx: Option<String>,
+    #[serde(default)]
+    pub notarization_attempts: u32,
+    #[serde(default)]
+    pub last_notarization_error: Option<String>,
+    /// Unix time before which a failed notarization is not retried
+    #[serde(default)]
+    pub notarization_retry_at: u64,
+}
+
+/// Persistent receipts and webhook delivery state, keyed by 0x-prefixed job id
+pub struct ReceiptStore {
+    db: sled::Db,
+}
+
+impl ReceiptStore {
+    pub fn open(path: &str) -> Result<Self> {
+        Ok(Self { db: sled::open(path)? })
+    }
+
+    /// Remember where to send the receipt once the job finalizes
+    pub fn track(&self, job_id: &str, webhook_url: Option<String>) -> Result<()> {
+        self.update(job_id, |job| job.webhook_url = webhook_url.clone())
+    }
+
+    /// Record what this executor produced; only executed jobs get receipts
+    pub fn record_execution(&self, job_id: &str, output_hash: &str, fuel_used: u64) -> Result<()> {
+        self.update(job_id, |job| {
+            job.output_hash = Some(output_hash.to_string());
+            job.fuel_used = Some(fuel_used);
+        })
+    }
+
+    pub fn tracked(&self, job_id: &str) -> Result<Option<TrackedJob>> {
+        self.db.get(tracked_key(job_id))?
+            .map(|v| serde_json::from_slice(&v))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Store a receipt unless one was already issued; returns the stored one either way
+    pub fn issue(&self, receipt: SignedReceipt) -> Result<SignedReceipt> {
+        let key = receipt_key(&receipt.job_id);
+        let value = serde_json::to_vec(&receipt)?;
+        match self.db.compare_and_swap(&key, None as Option<&[u8]>, Some(value))? {
+            Ok(()) => Ok(receipt),
+            Err(existing) => Ok(serde_json::from_slice(&existing.current.unwrap_or_default())?),
+        }
+    }
+
+    pub fn receipt(&self, job_id: &str) -> Result<Option<SignedReceipt>> {
+        self.db.get(receipt_key(job_id))?
+            .map(|v| serde_json::from_slice(&v))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Issued receipts whose webhook has not accepted them yet
+    pub fn pending_deliveries(&self) -> Result<Vec<(SignedReceipt, String)>> {
+        let mut pending = Vec::new();
+        for item in self.db.scan_prefix(b"receipt:") {
+            let (_, value) = item?;
+            let receipt: SignedReceipt = serde_json::from_slice(&value)?;
+            let Some(job) = self.tracked(&receipt.job_id)? else {
+                continue;
+            };
+            if let Some(url) = job.webhook_url {
+                if !job.delivered && job.delivery_attempts < MAX_DELIVERY_ATTEMPTS {
+                    pending.push((receipt, url));
+                }
+            }
+        }
+        Ok(pending)
+    }
+
+    pub fn mark_delivered(&self, job_id: &str) -> Result<()> {
+        self.update(job_id, |job| {
+            job.delivered = true;
+            job.delivery_attempts += 1;
+            job.last_delivery_error = None;
+        })
+    }
+
+    pub fn record_failed_delivery(&self, job_id: &str, error: &str) -> Result<()> {
+        self.update(job_id, |job| {
+            job.delivery_attempts += 1;
+            job.last_delivery_error = Some(error.to_string());
+        })
+    }
+
+    /// Issued receipts not yet notarized on the secondary chain whose retry time has come
+    pub fn pending_notarizations(&self, now: u64, max_attempts: u32) -> Result<Vec<SignedReceipt>> {
+        let mut pending = Vec::new();
+        for item in self.db.scan_prefix(b"receipt:") {
+            let (_, value) = item?;
+            let receipt: SignedReceipt = serde_json::from_slice(&value)?;
+            let Some(job) = self.tracked(&receipt.job_id)? else {
+                continue;
+            };
+            if job.notarization_tx.is_none()
+                && job.notarization_attempts < max_attempts
+                && job.notarization_retry_at <= now
+            {
+                pending.push(receipt);
+            }
+        }
+        Ok(pending)
+    }
+
+    pub fn mark_notarized(&self, job_id: &str, tx_hash: &str) -> Result<()> {
+        self.update(job_id, |job| {
+            job.notarization_tx = Some(tx_hash.to_string());
+            job.notarization_attempts += 1;
+            job.last_notarization_error = None;
+        })
+    }
+
+    pub fn record_failed_notarization(&self, job_id: &str, error: &str, retry_at: u64) -> Result<()> {
+        self.update(job_id, |job| {
+            job.notarization_attempts += 1;
+            job.last_notarization_error = Some(error.to_string());
+            job.notarization_retry_at = retry_at;
+        })
+    }
+
+    /// First block not yet scanned for finalizations
+    pub fn cursor(&self) -> Result<Option<u64>> {
+        Ok(self.db.get(CURSOR_KEY)?
+            .and_then(|v| v.as_ref().try_into().ok())
+            .map(u64::from_be_bytes))
+    }
+
+    pub fn set_cursor(&self, block: u64) -> Result<()> {
+        self.db.insert(CURSOR_KEY, &block.to_be_bytes())?;
+        Ok(())
+    }
+
+    fn update(&self, job_id: &str, f: impl Fn(&mut TrackedJob)) -> Result<()> {
+        self.db.update_and_fetch(tracked_key(job_id), |old| {
+            let mut job: TrackedJob = old
+                .and_then(|v| serde_json::from_slice(v).ok())
+                .unwrap_or_default();
+            f(&mut job);
+            serde_json::to_vec(&job).ok()
+        })?;
+        Ok(())
+    }
+}
+
+/// POST a receipt to a client webhook. The job id doubles as the idempotency key, so
+/// a receiver that sees a redelivery after a lost response can drop it.
+pub async fn deliver_webhook(client: &reqwest::Client, url: &str, receipt: &SignedReceipt) -> Result<()> {
+    let response = client.post(url)
+        .header("Idempotency-Key", &receipt.job_id)
+        .json(receipt)
+        .send()
+        .await
+        .with_context(|| format!("webhook {} unreachable", url))?;
+    if !response.status().is_success() {
+        bail!("webhook {} answered {}", url, response.status());
+    }
+    Ok(())
+}
+
+/// Lowercase 0x-prefixed form used for every stored job id
+pub fn normalize_job_id(job_id: &str) -> String {
+    format!("0x{}", job_id.trim_start_matches("0x").to_lowercase())
+}
+
+fn tracked_key(job_id: &str) -> String {
+    format!("job:{}", normalize_job_id(job_id))
+}
+
+fn receipt_key(job_id: &str) -> String {
+    format!("receipt:{}", normalize_job_id(job_id))
+}
+
+fn hex_0x(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+pub(crate) fn parse_bytes32(value: &str) -> Result<[u8; 32]> {
+    hex::decode(value.trim_start_matches("0x"))?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("expected 32 bytes: {}", value))
+}
@@ -0,0 +1,102 @@
+// Redaction for job data leaving the process through logs and WebSocket broadcasts.
+// Sensitive fields are masked by key name and long values are truncated; tenants
+// that opted out get their data untouched.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+pub const MASK: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionPolicy {
+    /// Object keys containing any of these (case-insensitive) are masked
+    pub masked_fields: Vec<String>,
+    /// Strings longer than this many bytes are cut
+    pub max_string_len: usize,
+    /// A value whose JSON exceeds this many bytes is replaced by its size and hash
+    pub max_value_len: usize,
+    /// Tenants that opted out of redaction
+    pub exempt_tenants: Vec<String>,
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        Self {
+            masked_fields: ["password", "secret", "private_key", "api_key", "token", "mnemonic", "seed"]
+                .iter()
+                .map(|f| f.to_string())
+                .collect(),
+            max_string_len: 256,
+            max_value_len: 4096,
+            exempt_tenants: Vec::new(),
+        }
+    }
+}
+
+impl RedactionPolicy {
+    pub fn applies_to(&self, tenant: Option<&str>) -> bool {
+        tenant.is_none_or(|t| !self.exempt_tenants.iter().any(|e| e == t))
+    }
+
+    /// Redact a JSON value for logging or broadcast
+    pub fn redact_value(&self, value: &Value, tenant: Option<&str>) -> Value {
+        if !self.applies_to(tenant) {
+            return value.clone();
+        }
+
+        let masked = self.mask(value);
+        let encoded = masked.to_string();
+        if encoded.len() <= self.max_value_len {
+            return masked;
+        }
+
+        // keep enough to correlate with the stored value without exposing it
+        serde_json::json!({
+            "truncated": true,
+            "bytes": encoded.len(),
+            "sha256": hex::encode(Sha256::digest(encoded.as_bytes())),
+        })
+    }
+
+    /// Redact free text such as error messages, which may echo job input
+    pub fn redact_text(&self, text: &str, tenant: Option<&str>) -> String {
+        if !self.applies_to(tenant) {
+            return text.to_string();
+        }
+        self.truncate(text)
+    }
+
+    fn mask(&self, value: &Value) -> Value {
+        match value {
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(k, v)| {
+                        let v = if self.is_masked(k) { Value::String(MASK.to_string()) } else { self.mask(v) };
+                        (k.clone(), v)
+                    })
+                    .collect(),
+            ),
+            Value::Array(items) => Value::Array(items.iter().map(|v| self.mask(v)).collect()),
+            Value::String(s) => Value::String(self.truncate(s)),
+            other => other.clone(),
+        }
+    }
+
+    fn is_masked(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        self.masked_fields.iter().any(|f| key.contains(&f.to_lowercase()))
+    }
+
+    fn truncate(&self, text: &str) -> String {
+        if text.len() <= self.max_string_len {
+            return text.to_string();
+        }
+
+        let mut cut = self.max_string_len;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        format!("{}...[{} bytes truncated]", &text[..cut], text.len() - cut)
+    }
+}
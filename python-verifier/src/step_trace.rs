@@ -0,0 +1,248 @@
+// Step traces for the on-chain bisection game (CertusBisection). A job's run is cut into steps
+// of `fuel_interval` fuel; state i is the machine after i steps, hashed from its linear memory,
+// the fuel it has burnt and whether it is still running. State hashes are the leaves of a
+// keccak Merkle tree laid out the way CertusBisection._verifyMerkleProof walks it, so a party
+// can post the root, answer each round with the state at the midpoint, and close the game with
+// the proofs for the one disputed step.
+//
+// States are taken by re-running the job from the start with a fuel ceiling, so each is a pure
+// function of (module, input, fuel) and either party can reproduce any one of them alone. The
+// zk-trace steps are these same checkpoints.
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+/// Steps a trace may have; every state re-runs the job up to it
+pub const MAX_STEPS: usize = 4096;
+
+const PAGE_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    /// Stopped at the fuel ceiling, with more to run
+    Running = 0,
+    /// python_main, or main for compiled Python, returned
+    Returned = 1,
+    /// Trapped before the ceiling
+    Trapped = 2,
+}
+
+/// The job after running up to a fuel ceiling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub state: [u8; 32],
+    pub fuel_consumed: u64,
+    pub status: StepStatus,
+    /// Module byte offset of the instruction the run stopped at, out of fuel or trapping; None
+    /// once it returned
+    pub instruction: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepTrace {
+    pub fuel_interval: u64,
+    /// State before any step, then after each
+    #[serde(with = "hex_states")]
+    pub states: Vec<[u8; 32]>,
+    pub fuel_consumed: u64,
+    /// How the run ended; Running if it was still going at the fuel limit
+    pub outcome: StepStatus,
+}
+
+/// Everything `initiateBisection` and `resolveBisection` take from a trace for one step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StepProof {
+    pub step: usize,
+    #[serde(serialize_with = "hex_state")]
+    pub pre_state: [u8; 32],
+    #[serde(serialize_with = "hex_state")]
+    pub post_state: [u8; 32],
+    #[serde(with = "hex_states")]
+    pub pre_state_proof: Vec<[u8; 32]>,
+    #[serde(with = "hex_states")]
+    pub post_state_proof: Vec<[u8; 32]>,
+}
+
+impl StepTrace {
+    /// `totalSteps` of initiateBisection
+    pub fn total_steps(&self) -> usize {
+        self.states.len() - 1
+    }
+
+    /// `finalStateRoot` of initiateBisection
+    pub fn root(&self) -> [u8; 32] {
+        let mut level = self.leaves();
+        while level.len() > 1 {
+            level = parents(&level);
+        }
+        level[0]
+    }
+
+    /// State after `step` steps, the hash a party posts for a bisection midpoint
+    pub fn state(&self, step: usize) -> Option<[u8; 32]> {
+        self.states.get(step).copied()
+    }
+
+    /// Siblings from the leaf of state `index` up to the root, lowest first
+    pub fn proof(&self, index: usize) -> Result<Vec<[u8; 32]>> {
+        if index >= self.states.len() {
+            bail!("trace has no state {}", index);
+        }
+        let mut proof = Vec::new();
+        let mut level = self.leaves();
+        let mut index = index;
+        while level.len() > 1 {
+            proof.push(*level.get(index ^ 1).unwrap_or(&level[index]));
+            level = parents(&level);
+            index /= 2;
+        }
+        Ok(proof)
+    }
+
+    /// The states on either side of `step` and their proofs
+    pub fn step_proof(&self, step: usize) -> Result<StepProof> {
+        if step >= self.total_steps() {
+            bail!("trace has {} steps, no step {}", self.total_steps(), step);
+        }
+        Ok(StepProof {
+            step,
+            pre_state: self.states[step],
+            post_state: self.states[step + 1],
+            pre_state_proof: self.proof(step)?,
+            post_state_proof: self.proof(step + 1)?,
+        })
+    }
+
+    /// The first step whose post-state differs between two traces of the same job, found by
+    /// bisecting as the game does; None when they agree throughout
+    pub fn first_divergence(&self, other: &StepTrace) -> Result<Option<usize>> {
+        if self.fuel_interval != other.fuel_interval {
+            bail!("traces were cut at {} and {} fuel per step", self.fuel_interval, other.fuel_interval);
+        }
+        if self.states[0] != other.states[0] {
+            bail!("traces start from different states");
+        }
+        let last = self.states.len().min(other.states.len()) - 1;
+        if self.states[last] == other.states[last] {
+            // one ran on past where the other stopped, or they agree throughout
+            return Ok((self.states.len() != other.states.len()).then_some(last));
+        }
+        // states agree at `start` and disagree at `end`
+        let (mut start, mut end) = (0, last);
+        while end - start > 1 {
+            let mid = (start + end) / 2;
+            if self.states[mid] == other.states[mid] {
+                start = mid;
+            } else {
+                end = mid;
+            }
+        }
+        Ok(Some(start))
+    }
+
+    fn leaves(&self) -> Vec<[u8; 32]> {
+        self.states.iter().enumerate().map(|(i, state)| leaf(state, i)).collect()
+    }
+}
+
+/// Leaf of state `index`: keccak256(abi.encodePacked(state, uint256(index)))
+pub fn leaf(state: &[u8; 32], index: usize) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(state);
+    hasher.update([0u8; 24]);
+    hasher.update((index as u64).to_be_bytes());
+    hasher.finalize().into()
+}
+
+/// The root a proof leads to from state `index`, as CertusBisection._verifyMerkleProof computes it
+pub fn proof_root(state: &[u8; 32], index: usize, proof: &[[u8; 32]]) -> [u8; 32] {
+    let mut hash = leaf(state, index);
+    let mut index = index;
+    for sibling in proof {
+        hash = if index.is_multiple_of(2) { pair(&hash, sibling) } else { pair(sibling, &hash) };
+        index /= 2;
+    }
+    hash
+}
+
+/// Hash of the job's state: keccak256(memory root | fuel consumed u64 | status u8)
+pub fn state_hash(memory: &[u8], fuel_consumed: u64, status: StepStatus) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(memory_root(memory));
+    hasher.update(fuel_consumed.to_be_bytes());
+    hasher.update([status as u8]);
+    hasher.finalize().into()
+}
+
+/// Keccak Merkle root over each 64KB page of memory; an odd node is paired with itself
+pub fn memory_root(memory: &[u8]) -> [u8; 32] {
+    let mut level: Vec<[u8; 32]> = memory.chunks(PAGE_SIZE).map(|page| Keccak256::digest(page).into()).collect();
+    if level.is_empty() {
+        return Keccak256::digest([]).into();
+    }
+    while level.len() > 1 {
+        level = parents(&level);
+    }
+    level[0]
+}
+
+/// Cut a run into steps: `run` runs the job from the start with a fuel ceiling and reports
+/// where it got to. Stops at the first state that isn't Running, or at `fuel_limit`.
+pub fn record(fuel_limit: u64, fuel_interval: u64, mut run: impl FnMut(u64) -> Result<Checkpoint>) -> Result<StepTrace> {
+    if fuel_interval == 0 {
+        bail!("fuel interval must be positive");
+    }
+    let start = run(0)?;
+    let mut states = vec![start.state];
+    let mut last = start;
+    let mut fuel = 0u64;
+    while last.status == StepStatus::Running && fuel < fuel_limit {
+        if states.len() > MAX_STEPS {
+            bail!("trace exceeds {} steps; use a larger fuel interval", MAX_STEPS);
+        }
+        fuel = fuel.saturating_add(fuel_interval).min(fuel_limit);
+        last = run(fuel)?;
+        states.push(last.state);
+    }
+    Ok(StepTrace { fuel_interval, states, fuel_consumed: last.fuel_consumed, outcome: last.status })
+}
+
+fn parents(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level.chunks(2).map(|nodes| pair(&nodes[0], nodes.get(1).unwrap_or(&nodes[0]))).collect()
+}
+
+fn pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn hex_state<S: serde::Serializer>(state: &[u8; 32], s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&hex::encode(state))
+}
+
+mod hex_states {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::ser::SerializeSeq;
+
+    pub fn serialize<S: Serializer>(states: &[[u8; 32]], s: S) -> Result<S::Ok, S::Error> {
+        let mut seq = s.serialize_seq(Some(states.len()))?;
+        for state in states {
+            seq.serialize_element(&hex::encode(state))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<[u8; 32]>, D::Error> {
+        Vec::<String>::deserialize(d)?
+            .iter()
+            .map(|s| {
+                let bytes = hex::decode(s).map_err(serde::de::Error::custom)?;
+                bytes.try_into().map_err(|_| serde::de::Error::custom("expected 32 bytes"))
+            })
+            .collect()
+    }
+}
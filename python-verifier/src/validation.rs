@@ -1,12 +1,19 @@
 use anyhow::{Result, bail};
+use rustpython_parser::{self as parser, ast};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
 
 /// Python code validation for deterministic execution
 pub struct PythonValidator;
 
 impl PythonValidator {
-    /// Validate Python code meets determinism requirements
+    /// Validate Python code meets determinism requirements under the default policy
     pub fn validate_code(code: &str) -> Result<()> {
+        Self::validate_code_with(code, &ValidationPolicy::default())
+    }
+
+    pub fn validate_code_with(code: &str, policy: &ValidationPolicy) -> Result<()> {
         if code.is_empty() {
             bail!("empty code");
         }
@@ -19,114 +26,376 @@ impl PythonValidator {
             bail!("missing OUTPUT variable");
         }
 
-        Self::check_forbidden_operations(code)?;
-        Self::check_syntax(code)?;
-
-        Ok(())
+        policy.check(code)
     }
+}
 
-    /// Check for non-deterministic operations
-    fn check_forbidden_operations(code: &str) -> Result<()> {
-        const FORBIDDEN: &[&str] = &[
-            "__import__",
-            "compile(",
-            "eval(",
-            "exec(",
-            "execfile(",
-            "globals(",
-            "locals(",
-            "vars(",
-            "dir(",
-            "input(",
-            "raw_input(",
-            "open(",
-            "file(",
-            "subprocess",
-            "os.",
-            "sys.",
-            "socket",
-            "urllib",
-            "requests",
-            "http.",
-            "ftplib",
-            "telnetlib",
-            "pickle",
-            "marshal",
-            "shelve",
-            "__builtins__",
-            "__loader__",
-            "__spec__",
-            "exit(",
-            "quit(",
-        ];
-
-        for forbidden in FORBIDDEN {
-            if code.contains(forbidden) {
-                bail!("forbidden operation: {}", forbidden);
-            }
+/// What a job's source may import, call and reach through attributes, checked on its parse
+/// tree so text inside strings or comments never matters and spelling a call differently
+/// doesn't get around it. Names a job defines itself (functions, classes) are always callable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ValidationPolicy {
+    /// Top-level modules `import` and `from ... import` may name
+    pub allowed_modules: BTreeSet<String>,
+    /// Names a job may call besides its own functions and classes
+    pub allowed_calls: BTreeSet<String>,
+    /// Dotted chains into an imported module a job may use, such as `hashlib.sha256`
+    pub allowed_attribute_chains: BTreeSet<String>,
+    /// Names rejected wherever they appear, called or not, so they can't be aliased and called
+    pub forbidden_names: BTreeSet<String>,
+    /// Reject `__dunder__` names and attributes, the way out to introspection
+    pub forbid_dunders: bool,
+}
+
+fn names(names: &[&str]) -> BTreeSet<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_modules: names(&["json", "hashlib"]),
+            allowed_calls: names(&[
+                "str", "len", "keccak256", "abs", "min", "max", "sum", "sorted", "pow", "set", "print", "type",
+                "bytes", "isinstance", "range", "enumerate", "zip", "ValueError", "KeyError", "ZeroDivisionError",
+            ]),
+            allowed_attribute_chains: names(&["hashlib.sha256", "hashlib.sha3_256", "json.dumps", "json.loads"]),
+            forbidden_names: names(&[
+                "eval", "exec", "compile", "open", "input", "breakpoint", "exit", "quit", "getattr", "setattr",
+                "delattr", "globals", "locals", "vars", "dir", "memoryview",
+            ]),
+            forbid_dunders: true,
         }
+    }
+}
 
-        Ok(())
+impl ValidationPolicy {
+    /// Parse `code` and check it against the policy
+    pub fn check(&self, code: &str) -> Result<()> {
+        let module = parser::parse(code, parser::Mode::Module, "<input>")
+            .map_err(|e| anyhow::anyhow!("Python parse error: {}", e))?;
+        let ast::Mod::Module(module) = module else {
+            bail!("expected a module");
+        };
+
+        let mut checker = PolicyCheck { policy: self, defined: BTreeSet::new(), imported: BTreeMap::new() };
+        for stmt in &module.body {
+            checker.collect_definitions(stmt);
+        }
+        checker.stmts(&module.body)
     }
+}
 
-    /// Syntax validation
-    fn check_syntax(code: &str) -> Result<()> {
-        let mut paren_depth = 0;
-        let mut bracket_depth = 0;
-        let mut brace_depth = 0;
-        let mut in_string = false;
-        let mut string_char = ' ';
+struct PolicyCheck<'a> {
+    policy: &'a ValidationPolicy,
+    // functions and classes defined anywhere in the job
+    defined: BTreeSet<String>,
+    // names bound by imports, to the dotted path they stand for
+    imported: BTreeMap<String, String>,
+}
 
-        for ch in code.chars() {
-            if in_string {
-                if ch == string_char && code.chars().nth(code.len() - 1) != Some('\\') {
-                    in_string = false;
-                }
-                continue;
+impl PolicyCheck<'_> {
+    fn collect_definitions(&mut self, stmt: &ast::Stmt) {
+        let body = match stmt {
+            ast::Stmt::FunctionDef(f) => {
+                self.defined.insert(f.name.to_string());
+                &f.body
             }
+            ast::Stmt::ClassDef(c) => {
+                self.defined.insert(c.name.to_string());
+                &c.body
+            }
+            _ => return,
+        };
+        for stmt in body {
+            self.collect_definitions(stmt);
+        }
+    }
 
-            match ch {
-                '"' | '\'' => {
-                    in_string = true;
-                    string_char = ch;
+    fn stmts(&mut self, stmts: &[ast::Stmt]) -> Result<()> {
+        stmts.iter().try_for_each(|stmt| self.stmt(stmt))
+    }
+
+    fn stmt(&mut self, stmt: &ast::Stmt) -> Result<()> {
+        match stmt {
+            ast::Stmt::Import(import) => {
+                for alias in &import.names {
+                    self.import(alias.name.as_str())?;
+                    // `import a.b` binds `a`; `import a.b as c` binds `c` to `a.b`
+                    let (bound, path) = match &alias.asname {
+                        Some(asname) => (asname.to_string(), alias.name.to_string()),
+                        None => {
+                            let root = alias.name.as_str().split('.').next().unwrap_or_default();
+                            (root.to_string(), root.to_string())
+                        }
+                    };
+                    self.name(&bound)?;
+                    self.imported.insert(bound, path);
                 }
-                '(' => paren_depth += 1,
-                ')' => {
-                    paren_depth -= 1;
-                    if paren_depth < 0 {
-                        bail!("unmatched parenthesis");
+            }
+            ast::Stmt::ImportFrom(import) => {
+                let Some(module) = import.module.as_ref().filter(|_| import.level.is_none_or(|l| l.to_u32() == 0)) else {
+                    bail!("relative imports not allowed");
+                };
+                self.import(module.as_str())?;
+                for alias in &import.names {
+                    if alias.name.as_str() == "*" {
+                        bail!("from {} import * not allowed", module);
                     }
+                    let path = format!("{}.{}", module, alias.name);
+                    self.chain(&path)?;
+                    let bound = alias.asname.as_ref().unwrap_or(&alias.name).to_string();
+                    self.name(&bound)?;
+                    self.imported.insert(bound, path);
                 }
-                '[' => bracket_depth += 1,
-                ']' => {
-                    bracket_depth -= 1;
-                    if bracket_depth < 0 {
-                        bail!("unmatched bracket");
-                    }
+            }
+            ast::Stmt::FunctionDef(f) => {
+                self.name(f.name.as_str())?;
+                self.exprs(&f.decorator_list)?;
+                self.arguments(&f.args)?;
+                self.stmts(&f.body)?;
+            }
+            ast::Stmt::AsyncFunctionDef(f) => bail!("async function {} not allowed", f.name),
+            ast::Stmt::ClassDef(c) => {
+                self.name(c.name.as_str())?;
+                self.exprs(&c.bases)?;
+                self.exprs(&c.decorator_list)?;
+                for keyword in &c.keywords {
+                    self.expr(&keyword.value)?;
                 }
-                '{' => brace_depth += 1,
-                '}' => {
-                    brace_depth -= 1;
-                    if brace_depth < 0 {
-                        bail!("unmatched brace");
-                    }
+                self.stmts(&c.body)?;
+            }
+            ast::Stmt::Return(r) => self.opt_expr(&r.value)?,
+            ast::Stmt::Delete(d) => self.exprs(&d.targets)?,
+            ast::Stmt::Assign(a) => {
+                self.exprs(&a.targets)?;
+                self.expr(&a.value)?;
+            }
+            ast::Stmt::AugAssign(a) => {
+                self.expr(&a.target)?;
+                self.expr(&a.value)?;
+            }
+            ast::Stmt::AnnAssign(a) => {
+                self.expr(&a.target)?;
+                self.expr(&a.annotation)?;
+                self.opt_expr(&a.value)?;
+            }
+            ast::Stmt::For(f) => {
+                self.expr(&f.target)?;
+                self.expr(&f.iter)?;
+                self.stmts(&f.body)?;
+                self.stmts(&f.orelse)?;
+            }
+            ast::Stmt::While(w) => {
+                self.expr(&w.test)?;
+                self.stmts(&w.body)?;
+                self.stmts(&w.orelse)?;
+            }
+            ast::Stmt::If(i) => {
+                self.expr(&i.test)?;
+                self.stmts(&i.body)?;
+                self.stmts(&i.orelse)?;
+            }
+            ast::Stmt::With(w) => {
+                for item in &w.items {
+                    self.expr(&item.context_expr)?;
+                    self.opt_expr(&item.optional_vars)?;
                 }
-                _ => {}
+                self.stmts(&w.body)?;
+            }
+            ast::Stmt::Raise(r) => {
+                self.opt_expr(&r.exc)?;
+                self.opt_expr(&r.cause)?;
             }
+            ast::Stmt::Try(t) => {
+                self.stmts(&t.body)?;
+                for handler in &t.handlers {
+                    let ast::ExceptHandler::ExceptHandler(handler) = handler;
+                    self.opt_expr(&handler.type_)?;
+                    self.stmts(&handler.body)?;
+                }
+                self.stmts(&t.orelse)?;
+                self.stmts(&t.finalbody)?;
+            }
+            ast::Stmt::Assert(a) => {
+                self.expr(&a.test)?;
+                self.opt_expr(&a.msg)?;
+            }
+            ast::Stmt::Expr(e) => self.expr(&e.value)?,
+            ast::Stmt::Global(_) | ast::Stmt::Nonlocal(_) | ast::Stmt::Pass(_) | ast::Stmt::Break(_) | ast::Stmt::Continue(_) => {}
+            // async, match, try* and type aliases: nothing a job needs, and more to check
+            _ => bail!("statement not allowed by the validation policy"),
         }
+        Ok(())
+    }
 
-        if paren_depth != 0 {
-            bail!("unclosed parenthesis");
+    fn arguments(&mut self, args: &ast::Arguments) -> Result<()> {
+        for arg in args.posonlyargs.iter().chain(&args.args).chain(&args.kwonlyargs) {
+            self.name(arg.def.arg.as_str())?;
+            self.opt_expr(&arg.default)?;
         }
-        if bracket_depth != 0 {
-            bail!("unclosed bracket");
+        for arg in args.vararg.iter().chain(&args.kwarg) {
+            self.name(arg.arg.as_str())?;
         }
-        if brace_depth != 0 {
-            bail!("unclosed brace");
+        Ok(())
+    }
+
+    fn exprs(&mut self, exprs: &[ast::Expr]) -> Result<()> {
+        exprs.iter().try_for_each(|expr| self.expr(expr))
+    }
+
+    fn opt_expr(&mut self, expr: &Option<Box<ast::Expr>>) -> Result<()> {
+        expr.as_deref().map_or(Ok(()), |expr| self.expr(expr))
+    }
+
+    fn comprehensions(&mut self, generators: &[ast::Comprehension]) -> Result<()> {
+        for generator in generators {
+            self.expr(&generator.target)?;
+            self.expr(&generator.iter)?;
+            self.exprs(&generator.ifs)?;
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &ast::Expr) -> Result<()> {
+        match expr {
+            ast::Expr::Name(name) => self.name(name.id.as_str())?,
+            ast::Expr::Attribute(attr) => {
+                if self.policy.forbid_dunders && is_dunder(attr.attr.as_str()) {
+                    bail!("attribute {} not allowed", attr.attr);
+                }
+                match self.module_chain(expr) {
+                    Some(chain) => self.chain(&chain)?,
+                    None => self.expr(&attr.value)?,
+                }
+            }
+            ast::Expr::Call(call) => {
+                match &*call.func {
+                    ast::Expr::Name(name) => self.callee(name.id.as_str())?,
+                    ast::Expr::Attribute(_) => self.expr(&call.func)?,
+                    _ => bail!("only calls by name or attribute allowed"),
+                }
+                self.exprs(&call.args)?;
+                for keyword in &call.keywords {
+                    self.expr(&keyword.value)?;
+                }
+            }
+            ast::Expr::BoolOp(b) => self.exprs(&b.values)?,
+            ast::Expr::NamedExpr(n) => {
+                self.expr(&n.target)?;
+                self.expr(&n.value)?;
+            }
+            ast::Expr::BinOp(b) => {
+                self.expr(&b.left)?;
+                self.expr(&b.right)?;
+            }
+            ast::Expr::UnaryOp(u) => self.expr(&u.operand)?,
+            ast::Expr::Lambda(l) => {
+                self.arguments(&l.args)?;
+                self.expr(&l.body)?;
+            }
+            ast::Expr::IfExp(i) => {
+                self.expr(&i.test)?;
+                self.expr(&i.body)?;
+                self.expr(&i.orelse)?;
+            }
+            ast::Expr::Dict(d) => {
+                self.exprs(&d.values)?;
+                for key in d.keys.iter().flatten() {
+                    self.expr(key)?;
+                }
+            }
+            ast::Expr::Set(s) => self.exprs(&s.elts)?,
+            ast::Expr::List(l) => self.exprs(&l.elts)?,
+            ast::Expr::Tuple(t) => self.exprs(&t.elts)?,
+            ast::Expr::ListComp(c) => {
+                self.comprehensions(&c.generators)?;
+                self.expr(&c.elt)?;
+            }
+            ast::Expr::SetComp(c) => {
+                self.comprehensions(&c.generators)?;
+                self.expr(&c.elt)?;
+            }
+            ast::Expr::GeneratorExp(c) => {
+                self.comprehensions(&c.generators)?;
+                self.expr(&c.elt)?;
+            }
+            ast::Expr::DictComp(c) => {
+                self.comprehensions(&c.generators)?;
+                self.expr(&c.key)?;
+                self.expr(&c.value)?;
+            }
+            ast::Expr::Compare(c) => {
+                self.expr(&c.left)?;
+                self.exprs(&c.comparators)?;
+            }
+            ast::Expr::FormattedValue(f) => {
+                self.expr(&f.value)?;
+                self.opt_expr(&f.format_spec)?;
+            }
+            ast::Expr::JoinedStr(j) => self.exprs(&j.values)?,
+            ast::Expr::Subscript(s) => {
+                self.expr(&s.value)?;
+                self.expr(&s.slice)?;
+            }
+            ast::Expr::Slice(s) => {
+                self.opt_expr(&s.lower)?;
+                self.opt_expr(&s.upper)?;
+                self.opt_expr(&s.step)?;
+            }
+            ast::Expr::Starred(s) => self.expr(&s.value)?,
+            ast::Expr::Constant(_) => {}
+            // await and yield
+            _ => bail!("expression not allowed by the validation policy"),
+        }
+        Ok(())
+    }
+
+    fn import(&self, module: &str) -> Result<()> {
+        let root = module.split('.').next().unwrap_or_default();
+        if !self.policy.allowed_modules.contains(root) {
+            bail!("import of {} not allowed", module);
         }
+        Ok(())
+    }
 
+    fn chain(&self, chain: &str) -> Result<()> {
+        if !self.policy.allowed_attribute_chains.contains(chain) {
+            bail!("{} not allowed", chain);
+        }
         Ok(())
     }
+
+    fn name(&self, name: &str) -> Result<()> {
+        if self.policy.forbidden_names.contains(name) || (self.policy.forbid_dunders && is_dunder(name)) {
+            bail!("forbidden name: {}", name);
+        }
+        Ok(())
+    }
+
+    fn callee(&self, name: &str) -> Result<()> {
+        self.name(name)?;
+        match self.imported.get(name) {
+            Some(path) => self.chain(path),
+            None if self.defined.contains(name) || self.policy.allowed_calls.contains(name) => Ok(()),
+            None => bail!("call to {}() not allowed", name),
+        }
+    }
+
+    // `a.b.c` as a dotted path when `a` names an imported module
+    fn module_chain(&self, expr: &ast::Expr) -> Option<String> {
+        match expr {
+            ast::Expr::Name(name) => self.imported.get(name.id.as_str()).cloned(),
+            ast::Expr::Attribute(attr) => Some(format!("{}.{}", self.module_chain(&attr.value)?, attr.attr)),
+            _ => None,
+        }
+    }
+}
+
+// `__init__` is how a class constructs; every other dunder reaches into the runtime
+fn is_dunder(name: &str) -> bool {
+    name.len() > 4 && name.starts_with("__") && name.ends_with("__") && name != "__init__"
 }
 
 /// Validate JSON input
@@ -176,6 +445,11 @@ pub fn validate_output(output: &str) -> Result<()> {
         bail!("output exceeds 1MB");
     }
 
+    // the output hash puts printed lines after a NUL, so one in the output would be ambiguous
+    if output.contains('\0') {
+        bail!("output contains a NUL byte");
+    }
+
     // ensure it's valid JSON or string
     if output.starts_with('{') || output.starts_with('[') {
         let _: Value = serde_json::from_str(output)?;
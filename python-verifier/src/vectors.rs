@@ -0,0 +1,198 @@
+// Canonical test vectors. Each vector is a Python program, what CPython makes of it (OUTPUT,
+// printed lines and their output hash), and what a conforming toolchain makes of it: the hash
+// of the module the compiler emits with default options and the fuel the module's own counter
+// reaches, charged by the certus-gas schedule the on-chain interpreter uses. Independent
+// compilers, executors and auditors check themselves against the set with
+// `python-cli vectors verify`; the files ship in the crate so the set is versioned with it.
+//
+// Expected outputs are written by hand from CPython. Module hashes and fuel are pinned from a
+// reference build with `python-cli vectors bless`; until then they are null and not checked.
+//
+// The golden set is a wider corpus in the same format, run through the whole executor pipeline
+// (validation, compile, run, output hash) by `cargo test --test golden_tests`, which operators
+// repeat before staking on a build.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use wasmtime::*;
+
+use crate::compiler::{fuel, PythonCompiler, STDOUT_EXPORT};
+use crate::profiles::{self, ExecutionProfile};
+use crate::{captured_stdout, output_hash, PythonExecutor};
+
+/// The v1 set, as shipped in vectors/v1.json
+pub const VECTORS_V1: &str = include_str!("../vectors/v1.json");
+/// The golden set, as shipped in vectors/golden.json
+pub const VECTORS_GOLDEN: &str = include_str!("../vectors/golden.json");
+
+// Store fuel for a vector run; wasmtime's own count, well above any vector's gas
+const VECTOR_FUEL: u64 = 100_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VectorSet {
+    pub version: u32,
+    pub vectors: Vec<Vector>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Vector {
+    pub name: String,
+    pub source: String,
+    /// OUTPUT as CPython computes it
+    pub output: i32,
+    pub stdout: Vec<String>,
+    /// Hex sha256 of the output blob, the value receipts carry
+    pub output_hash: String,
+    /// Hex sha256 of the compiled module; null until pinned
+    pub wasm_hash: Option<String>,
+    /// The module's fuel counter when main returns; null until pinned
+    pub fuel: Option<u64>,
+}
+
+/// What running one vector through this build produced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VectorRun {
+    pub name: String,
+    pub output: i32,
+    pub stdout: Vec<String>,
+    pub output_hash: String,
+    pub wasm_hash: String,
+    pub fuel: u64,
+}
+
+/// A vector this build disagrees with, field by field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mismatch {
+    pub name: String,
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl VectorSet {
+    pub fn parse(json: &str) -> Result<Self> {
+        let set: Self = serde_json::from_str(json).context("invalid test vector file")?;
+        for vector in &set.vectors {
+            if output_hash(&vector.output.to_string(), &vector.stdout) != vector.output_hash {
+                bail!("vector {}: output_hash doesn't match its output and stdout", vector.name);
+            }
+        }
+        Ok(set)
+    }
+
+    pub fn v1() -> Self {
+        Self::parse(VECTORS_V1).expect("shipped vectors parse")
+    }
+
+    pub fn golden() -> Self {
+        Self::parse(VECTORS_GOLDEN).expect("shipped vectors parse")
+    }
+
+    /// Run every vector as a job through `executor`, with an empty input and as much fuel as a
+    /// job may have, and report where its outputs differ. Module hashes and fuel aren't compared:
+    /// the executor builds modules for the job's limits, not the defaults they were pinned at.
+    pub fn verify_pipeline(&self, executor: &mut PythonExecutor) -> Result<Vec<Mismatch>> {
+        let profile = ExecutionProfile { fuel_limit: profiles::MAX_FUEL, ..Default::default() };
+        let mut mismatches = Vec::new();
+        for vector in &self.vectors {
+            let output = executor.execute_with_profile(&vector.source, "{}", &profile)
+                .with_context(|| format!("vector {} failed as a job", vector.name))?;
+            let mut check = |field, expected: String, actual: String| {
+                if expected != actual {
+                    mismatches.push(Mismatch { name: vector.name.clone(), field, expected, actual });
+                }
+            };
+            check("output", vector.output.to_string(), output.result);
+            check("stdout", vector.stdout.join("\n"), output.stdout.join("\n"));
+            check("output_hash", vector.output_hash.clone(), output.output_hash);
+        }
+        Ok(mismatches)
+    }
+
+    /// Run every vector and report where this build differs; empty when it conforms
+    pub fn verify(&self) -> Result<Vec<Mismatch>> {
+        let engine = engine()?;
+        let mut mismatches = Vec::new();
+        for vector in &self.vectors {
+            let run = run(&engine, vector)?;
+            let mut check = |field, expected: String, actual: String| {
+                if expected != actual {
+                    mismatches.push(Mismatch { name: vector.name.clone(), field, expected, actual });
+                }
+            };
+            check("output", vector.output.to_string(), run.output.to_string());
+            check("stdout", vector.stdout.join("\n"), run.stdout.join("\n"));
+            check("output_hash", vector.output_hash.clone(), run.output_hash);
+            if let Some(wasm_hash) = &vector.wasm_hash {
+                check("wasm_hash", wasm_hash.clone(), run.wasm_hash);
+            }
+            if let Some(fuel) = vector.fuel {
+                check("fuel", fuel.to_string(), run.fuel.to_string());
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// Pin module hashes and fuel to what this build produces. Fails, changing nothing, when
+    /// any output differs: those come from CPython, not from a build.
+    pub fn bless(&mut self) -> Result<()> {
+        let engine = engine()?;
+        let mut runs = Vec::with_capacity(self.vectors.len());
+        for vector in &self.vectors {
+            let run = run(&engine, vector)?;
+            if run.output_hash != vector.output_hash {
+                bail!("vector {}: output {} differs from CPython's {}", vector.name, run.output, vector.output);
+            }
+            runs.push(run);
+        }
+        for (vector, run) in self.vectors.iter_mut().zip(runs) {
+            vector.wasm_hash = Some(run.wasm_hash);
+            vector.fuel = Some(run.fuel);
+        }
+        Ok(())
+    }
+}
+
+// Vectors only need fuel metering on top of the defaults
+fn engine() -> Result<Engine> {
+    Engine::new(Config::new().consume_fuel(true))
+}
+
+/// Compile a vector with default options and run its `main` export on `engine`, which must
+/// consume fuel
+pub fn run(engine: &Engine, vector: &Vector) -> Result<VectorRun> {
+    let wasm = PythonCompiler::new().compile(&vector.source)
+        .with_context(|| format!("vector {} doesn't compile", vector.name))?;
+    let module = Module::new(engine, &wasm)?;
+
+    let mut store = Store::new(engine, ());
+    store.set_fuel(VECTOR_FUEL)?;
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let output = main.call(&mut store, ()).with_context(|| format!("vector {} trapped", vector.name))?;
+
+    let stdout = match instance.get_global(&mut store, STDOUT_EXPORT) {
+        Some(global) => {
+            let base = global.get(&mut store).i32().context("stdout export is not an i32")?;
+            captured_stdout(memory.data(&store), base as u32)
+        }
+        None => Vec::new(),
+    };
+    let fuel = instance.get_global(&mut store, fuel::FUEL_EXPORT)
+        .and_then(|global| global.get(&mut store).i32())
+        .context("module exports no fuel counter")?;
+
+    Ok(VectorRun {
+        name: vector.name.clone(),
+        output_hash: output_hash(&output.to_string(), &stdout),
+        output,
+        stdout,
+        wasm_hash: hex::encode(Sha256::digest(&wasm)),
+        fuel: fuel as u32 as u64,
+    })
+}
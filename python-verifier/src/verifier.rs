@@ -1,14 +1,28 @@
 use anyhow::{Result, Context, bail};
 use ethers::prelude::*;
 use ethers::types::transaction::eip2718::TypedTransaction;
+use std::path::PathBuf;
 use std::sync::Arc;
 use sha2::Digest;
+use crate::evidence::{ChainReferences, EvidencePacket, FuelLog};
+use crate::outputs::OutputStore;
+use crate::profiles::ExecutionProfile;
+use crate::step_trace::{self, StepTrace};
+use crate::PythonExecutor;
+use crate::provenance::{self, BuildPolicy, BuildReport};
+
+/// Largest wasm or input CertusEscrow replays for a direct fraud proof
+const DIRECT_PROOF_MAX_BYTES: usize = 1000;
 
 /// Verifier for deterministic Wasm execution via Certus protocol
 pub struct PythonVerifier {
     escrow_contract: H160,
     jobs_contract: H160,
     signer: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
+    chain_id: u64,
+    evidence_dir: Option<PathBuf>,
+    outputs: Option<Arc<OutputStore>>,
+    build_policy: Option<BuildPolicy>,
 }
 
 impl PythonVerifier {
@@ -31,9 +45,42 @@ impl PythonVerifier {
             escrow_contract: escrow_addr.parse()?,
             jobs_contract: jobs_addr.parse()?,
             signer,
+            chain_id,
+            evidence_dir: None,
+            outputs: None,
+            build_policy: None,
         })
     }
 
+    /// Write an evidence packet for every fraud this verifier proves
+    pub fn with_evidence_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.evidence_dir = Some(dir.into());
+        self
+    }
+
+    /// Check that executors published the outputs their receipts commit to
+    pub fn with_outputs(mut self, outputs: Arc<OutputStore>) -> Self {
+        self.outputs = Some(outputs);
+        self
+    }
+
+    /// Only accept peers running one of these builds
+    pub fn with_build_policy(mut self, policy: BuildPolicy) -> Self {
+        self.build_policy = Some(policy);
+        self
+    }
+
+    /// Fetch a peer's signed build report and check it against the allowlist; without one
+    /// configured only the signature is checked
+    pub async fn check_peer_build(&self, client: &reqwest::Client, peer: &str) -> Result<BuildReport> {
+        let report = provenance::fetch_report(client, peer).await?;
+        match &self.build_policy {
+            Some(policy) => policy.check_report(&report)?,
+            None => report.verify()?,
+        }
+        Ok(report)
+    }
+
     /// Verify job following Certus protocol verifier selection rules
     pub async fn verify_certus_job(&self, job_id: [u8; 32]) -> Result<()> {
         // Fetch complete job state from chain
@@ -68,18 +115,129 @@ impl PythonVerifier {
             log::warn!("Fraud detected for job {}: expected {}, got {}",
                 hex::encode(job_id), output.output_hash, receipt.output_hash);
 
-            // Submit fraud proof following MEV-protected protocol
-            self.submit_certus_fraud_proof(
-                job_id,
-                wasm,
-                input,
-                output.result.into_bytes(),
-            ).await?;
+            let mut chain = ChainReferences {
+                chain_id: self.chain_id,
+                jobs_contract: format!("{:?}", self.jobs_contract),
+                escrow_contract: format!("{:?}", self.escrow_contract),
+                ..Default::default()
+            };
+            // CertusEscrow only replays small jobs itself; larger ones are disputed by bisection
+            let steps = if wasm.len() > DIRECT_PROOF_MAX_BYTES || input.len() > DIRECT_PROOF_MAX_BYTES {
+                let trace = self.record_steps(&wasm, &input, job_data.fuel_limit)?;
+                let tx = self.initiate_bisection(job_id, &trace).await?;
+                log::info!("Bisection opened for job {} over {} steps", hex::encode(job_id), trace.total_steps());
+                chain.transactions.insert("bisection_initiate".to_string(), format!("{:?}", tx));
+                Some(trace)
+            } else {
+                // Submit fraud proof following MEV-protected protocol
+                let (commit_tx, reveal_tx) = self.submit_certus_fraud_proof(
+                    job_id,
+                    wasm.clone(),
+                    input.clone(),
+                    output.result.clone().into_bytes(),
+                ).await?;
+                chain.transactions.insert("fraud_commit".to_string(), format!("{:?}", commit_tx));
+                chain.transactions.insert("fraud_reveal".to_string(), format!("{:?}", reveal_tx));
+                None
+            };
+
+            // The dispute already stands on-chain; a packet that fails to write only costs the off-chain record
+
+            let packet = EvidencePacket::new(job_id, chain)
+                .with_wasm(&wasm)
+                .with_input(&input)
+                .with_executor_output(&receipt.output_hash, None)
+                .with_verifier_output(&output.output_hash, output.result.as_bytes())
+                .with_fuel_log(FuelLog {
+                    fuel_limit: job_data.fuel_limit,
+                    verifier_consumed: Some(output.fuel_consumed),
+                    executor_reported: None,
+                });
+            // the step trace answers the bisection's later rounds
+            let packet = match steps {
+                Some(trace) => packet.with_trace("verifier.steps.json", &serde_json::to_vec(&trace)?),
+                None => packet,
+            };
+            let packet = match self.record_trace(&wasm, job_data.fuel_limit) {
+                Ok(trace) => packet.with_trace("verifier.ctrc", &trace),
+                Err(e) => {
+                    log::debug!("No trace for evidence of job {}: {}", hex::encode(job_id), e);
+                    packet
+                }
+            };
+            match self.save_evidence(job_id, &packet) {
+                Ok(path) => log::info!("Evidence packet for job {} written to {}", hex::encode(job_id), path.display()),
+                Err(e) => log::error!("Could not write evidence packet for job {}: {}", hex::encode(job_id), e),
+            }
+        } else if let Some(outputs) = &self.outputs {
+            // fetch checks the bytes against the hash both sides agree on
+            match outputs.fetch(&receipt.output_hash).await {
+                Ok(_) => log::debug!("Output of job {} is published", hex::encode(job_id)),
+                Err(e) => log::warn!("Output of job {} is not retrievable: {}", hex::encode(job_id), e),
+            }
         }
 
         Ok(())
     }
 
+    /// Archive the packet as evidence-<job id>-<manifest hash prefix>.tar
+    fn save_evidence(&self, job_id: [u8; 32], packet: &EvidencePacket) -> Result<PathBuf> {
+        let Some(dir) = &self.evidence_dir else {
+            bail!("no evidence directory configured");
+        };
+        std::fs::create_dir_all(dir)?;
+
+        // read it back the way an arbitrator would before anyone relies on it
+        let archive = packet.to_archive(self.signer.signer())?;
+        let opened = crate::evidence::open_archive(&archive).context("evidence packet does not check out")?;
+        if opened.manifest != packet.manifest() {
+            bail!("evidence manifest does not round-trip");
+        }
+
+        let manifest_hash = hex::encode(opened.manifest_hash);
+        let path = dir.join(format!("evidence-{}-{}.tar", hex::encode(job_id), &manifest_hash[..16]));
+        std::fs::write(&path, archive)?;
+        log::debug!("Evidence {} holds {} files attested by {}", manifest_hash, opened.files.len(), opened.attestation.verifier);
+        Ok(path)
+    }
+
+    /// The job's run cut into as few steps as the trace allows, for a bisection challenge
+    fn record_steps(&self, wasm: &[u8], input: &[u8], fuel_limit: u64) -> Result<StepTrace> {
+        let input = std::str::from_utf8(input).context("job input is not utf-8")?;
+        let profile = ExecutionProfile { fuel_limit, ..Default::default() };
+        let interval = fuel_limit.div_ceil(step_trace::MAX_STEPS as u64).max(1);
+        PythonExecutor::new()?.execute_stepped(wasm, input, &profile, interval)
+    }
+
+    /// Open a bisection challenge on the receipt with the trace's step count and state root;
+    /// returns the transaction hash
+    async fn initiate_bisection(&self, job_id: [u8; 32], trace: &StepTrace) -> Result<H256> {
+        let calldata = self.encode_initiate_bisection(job_id, trace.total_steps(), trace.root());
+        let receipt = self.signer
+            .send_transaction(
+                TransactionRequest::new()
+                    .to(self.escrow_contract)
+                    .data(calldata),
+                None,
+            )
+            .await?
+            .await?
+            .context("bisection challenge failed")?;
+        Ok(receipt.transaction_hash)
+    }
+
+    #[cfg(feature = "zk-trace")]
+    fn record_trace(&self, wasm: &[u8], fuel_limit: u64) -> Result<Vec<u8>> {
+        let profile = ExecutionProfile { fuel_limit, ..Default::default() };
+        let interval = fuel_limit.div_ceil(crate::zk_trace::MAX_TRACE_STEPS as u64).max(1);
+        Ok(PythonExecutor::new()?.trace_wasm(wasm, &profile, interval)?.to_bytes())
+    }
+
+    #[cfg(not(feature = "zk-trace"))]
+    fn record_trace(&self, _wasm: &[u8], _fuel_limit: u64) -> Result<Vec<u8>> {
+        bail!("built without the zk-trace feature")
+    }
+
     /// Check if this verifier was selected for the job
     async fn is_selected_verifier(&self, job_id: [u8; 32], job: &JobData) -> Result<bool> {
         let verifier_addr = self.signer.address();
@@ -158,6 +316,10 @@ impl PythonVerifier {
         config.cranelift_nan_canonicalization(true);
         config.consume_fuel(true);
 
+        // Client-supplied modules pass the same policy and metering as compiled Python
+        crate::compiler::determinism::validate(&wasm)?;
+        let wasm = crate::compiler::fuel::instrument(&wasm)?;
+
         let engine = Engine::new(&config)?;
         let module = Module::new(&engine, wasm)?;
         let mut store = Store::new(&engine, ());
@@ -168,6 +330,7 @@ impl PythonVerifier {
 
         // Execute with input
         let result = run.call(&mut store, (0, input.len() as i32))?;
+        let fuel_consumed = fuel_limit - store.get_fuel()?;
 
         // Extract output
         let output = vec![0u8; result as usize];
@@ -176,31 +339,33 @@ impl PythonVerifier {
         Ok(ExecutionOutput {
             result: String::from_utf8_lossy(&output).to_string(),
             output_hash,
+            fuel_consumed,
         })
     }
 
-    /// Submit fraud proof via CertusEscrow
+    /// Submit fraud proof via CertusEscrow; returns the commit and reveal transaction hashes
     async fn submit_certus_fraud_proof(
         &self,
         job_id: [u8; 32],
         wasm: Vec<u8>,
         input: Vec<u8>,
         output: Vec<u8>,
-    ) -> Result<H256> {
+    ) -> Result<(H256, H256)> {
         // MEV protection: commit first
         let nonce = rand::random::<u64>();
         let commitment = self.compute_commitment(job_id, &wasm, &input, &output, nonce);
 
         // commitFraud to CertusEscrow
         let commit_calldata = self.encode_commit_fraud(job_id, commitment);
-        let _commit_tx = self.signer
+        let commit_tx = self.signer
             .send_transaction(
                 TransactionRequest::new()
                     .to(self.escrow_contract)
                     .data(commit_calldata),
                 None,
             )
-            .await?;
+            .await?
+            .tx_hash();
 
         // wait 2 minutes per protocol
         tokio::time::sleep(tokio::time::Duration::from_secs(125)).await;
@@ -219,7 +384,7 @@ impl PythonVerifier {
             .await?
             .context("fraud proof submission failed")?;
 
-        Ok(reveal_tx.transaction_hash)
+        Ok((commit_tx, reveal_tx.transaction_hash))
     }
 
     async fn fetch_job_from_certus(&self, job_id: [u8; 32]) -> Result<JobData> {
@@ -261,7 +426,15 @@ impl PythonVerifier {
             .call(&tx, None)
             .await?;
 
-        Ok(result.to_vec())
+        // the registry getter returns ABI-encoded bytes
+        let wasm = ethers::abi::decode(&[ethers::abi::ParamType::Bytes], &result)?
+            .remove(0)
+            .into_bytes()
+            .context("invalid wasmModules response")?;
+        if wasm.is_empty() {
+            bail!("wasm {} is not registered", hex::encode(wasm_hash));
+        }
+        Ok(wasm)
     }
 
     /// Fetch input as raw bytes
@@ -334,6 +507,20 @@ impl PythonVerifier {
         calldata.into()
     }
 
+    fn encode_initiate_bisection(&self, job_id: [u8; 32], total_steps: usize, final_state_root: [u8; 32]) -> Bytes {
+        // initiateBisection(bytes32,uint256,bytes32)
+        let mut calldata = Vec::new();
+        calldata.extend_from_slice(&ethers::utils::id("initiateBisection(bytes32,uint256,bytes32)")[0..4]);
+        calldata.extend_from_slice(&job_id);
+        calldata.extend_from_slice(&{
+            let mut bytes = [0u8; 32];
+            U256::from(total_steps).to_big_endian(&mut bytes);
+            bytes
+        });
+        calldata.extend_from_slice(&final_state_root);
+        calldata.into()
+    }
+
     fn encode_get_job(&self, job_id: [u8; 32]) -> Bytes {
         let mut calldata = Vec::new();
         calldata.extend_from_slice(&ethers::utils::id("getJob(bytes32)")[0..4]);
@@ -460,4 +647,5 @@ struct Receipt {
 struct ExecutionOutput {
     result: String,
     output_hash: String,
+    fuel_consumed: u64,
 }
\ No newline at end of file
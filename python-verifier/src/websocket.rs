@@ -3,9 +3,16 @@ use axum::{
     response::IntoResponse,
 };
 use futures::{sink::SinkExt, stream::StreamExt};
+use anyhow::{Result, bail};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
 use tokio::sync::broadcast;
+use crate::queue::JobQueue;
+use crate::redaction::RedactionPolicy;
+
+/// `prev_hash` of a job's first update
+pub const GENESIS_HASH: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct JobUpdate {
@@ -13,17 +20,119 @@ pub struct JobUpdate {
     pub status: String,
     pub timestamp: u64,
     pub data: serde_json::Value,
+    /// Owning tenant, used to pick the redaction policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// `hash` of the job's previous update, GENESIS_HASH for its first; empty until recorded
+    #[serde(default)]
+    pub prev_hash: String,
+    /// `chain_hash()` of this update, linking it into the job's history
+    #[serde(default)]
+    pub hash: String,
+}
+
+// What an update's hash covers, serialized as compact JSON with object keys sorted
+#[derive(Serialize)]
+struct LinkedFields<'a> {
+    job_id: &'a str,
+    status: &'a str,
+    timestamp: u64,
+    data: &'a serde_json::Value,
+}
+
+impl JobUpdate {
+    /// An update linked as its job's first; recording it into a history relinks it after the
+    /// updates already there
+    pub fn new(job_id: &str, status: &str, timestamp: u64, data: serde_json::Value, tenant: Option<String>) -> Self {
+        let mut update = JobUpdate {
+            job_id: job_id.to_string(),
+            status: status.to_string(),
+            timestamp,
+            data,
+            tenant,
+            prev_hash: String::new(),
+            hash: String::new(),
+        };
+        update.link(None).expect("an update links after GENESIS_HASH");
+        update
+    }
+
+    /// sha256(prev_hash || json(job_id, status, timestamp, data)), 0x-prefixed hex. The tenant
+    /// is left out, so clients can check the history they were sent without knowing it.
+    pub fn chain_hash(&self) -> Result<String> {
+        let prev = hex::decode(self.prev_hash.trim_start_matches("0x"))?;
+        if prev.len() != 32 {
+            bail!("prev_hash of {} update must be 32 bytes", self.status);
+        }
+        let fields = LinkedFields {
+            job_id: &self.job_id,
+            status: &self.status,
+            timestamp: self.timestamp,
+            data: &self.data,
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(&prev);
+        hasher.update(serde_json::to_vec(&fields)?);
+        Ok(format!("0x{}", hex::encode(hasher.finalize())))
+    }
+
+    /// Link this update after `prev`, the job's latest recorded update if any
+    pub fn link(&mut self, prev: Option<&JobUpdate>) -> Result<()> {
+        self.prev_hash = match prev {
+            Some(prev) => prev.hash.clone(),
+            None => GENESIS_HASH.to_string(),
+        };
+        self.hash = self.chain_hash()?;
+        Ok(())
+    }
+}
+
+/// Check a job's status history as returned by the API: every update belongs to the job, starts
+/// from GENESIS_HASH or the hash before it, hashes to what it claims and is no older than the one
+/// before it. Ok means the operator can't have edited, dropped or reordered any of them since.
+pub fn verify_history(job_id: &str, updates: &[JobUpdate]) -> Result<()> {
+    let mut prev: Option<&JobUpdate> = None;
+    for (i, update) in updates.iter().enumerate() {
+        if update.job_id != job_id {
+            bail!("update {} belongs to job {}, not {}", i, update.job_id, job_id);
+        }
+        let expected_prev = prev.map_or(GENESIS_HASH, |p| p.hash.as_str());
+        if !update.prev_hash.eq_ignore_ascii_case(expected_prev) {
+            bail!("update {} does not link to the update before it", i);
+        }
+        if !update.hash.eq_ignore_ascii_case(&update.chain_hash()?) {
+            bail!("update {} ({}) does not match its hash", i, update.status);
+        }
+        if prev.is_some_and(|p| update.timestamp < p.timestamp) {
+            bail!("update {} is older than the update before it", i);
+        }
+        prev = Some(update);
+    }
+    Ok(())
 }
 
 #[derive(Clone)]
 pub struct WsState {
     pub tx: broadcast::Sender<JobUpdate>,
+    pub redaction: RedactionPolicy,
+    /// Where each broadcast update is chained into its job's history
+    pub history: Option<Arc<JobQueue>>,
 }
 
 impl WsState {
     pub fn new() -> Self {
         let (tx, _) = broadcast::channel(100);
-        Self { tx }
+        Self { tx, redaction: RedactionPolicy::default(), history: None }
+    }
+
+    pub fn with_redaction(mut self, redaction: RedactionPolicy) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    pub fn with_history(mut self, queue: Arc<JobQueue>) -> Self {
+        self.history = Some(queue);
+        self
     }
 }
 
@@ -77,7 +186,15 @@ struct SubscribeRequest {
     _job_id: String,
 }
 
-/// Broadcast job update to all connected clients
-pub fn broadcast_update(state: &WsState, update: JobUpdate) {
+/// Broadcast job update to all connected clients, redacting its data first. With a history
+/// store the redacted update is chained and recorded before anyone sees it.
+pub fn broadcast_update(state: &WsState, mut update: JobUpdate) {
+    update.data = state.redaction.redact_value(&update.data, update.tenant.as_deref());
+    if let Some(history) = &state.history {
+        match history.record_update(update.clone()) {
+            Ok(linked) => update = linked,
+            Err(e) => log::error!("Could not record {} update of job {}: {}", update.status, update.job_id, e),
+        }
+    }
     let _ = state.tx.send(update);
 }
\ No newline at end of file
@@ -0,0 +1,198 @@
+// Experimental canonical execution trace for external proving systems.
+//
+// A trace is a sequence of fuel checkpoints. Step i covers fuel
+// [fuel_start, fuel_end), commits to the state before and after it, and names
+// the instruction it stopped at. The checkpoints are step_trace's: the job is
+// re-run from the start to each fuel ceiling and its states are hashed as the
+// bisection game hashes them, so every state is a pure function of (module,
+// input, fuel), any verifier can reproduce a single step without the rest of
+// the trace, and a step here is a step there.
+//
+// The binary encoding is fixed-width big-endian and versioned; it is the
+// format external provers consume. JSON is provided for debugging only.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::step_trace::{self, Checkpoint, StepStatus};
+
+pub const TRACE_FORMAT_VERSION: u16 = 2;
+pub const MAX_TRACE_STEPS: usize = 1024;
+
+const TRACE_MAGIC: &[u8; 4] = b"CTRC";
+const HEADER_LEN: usize = 4 + 2 + 32 + 8 + 4 + 4;
+const STEP_LEN: usize = 4 + 8 + 8 + 4 + 32 + 32;
+// `instruction` of a step that ends with the run returning
+const RETURNED: u32 = u32::MAX;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceStep {
+    pub index: u32,
+    pub fuel_start: u64,
+    pub fuel_end: u64,
+    /// Module byte offset of the instruction the step stopped at, the first the next step
+    /// runs; None for the step in which the run returned
+    pub instruction: Option<u32>,
+    /// `step_trace::state_hash` of the job before the step
+    #[serde(with = "hex32")]
+    pub pre_state: [u8; 32],
+    #[serde(with = "hex32")]
+    pub post_state: [u8; 32],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanonicalTrace {
+    pub version: u16,
+    #[serde(with = "hex32")]
+    pub module_hash: [u8; 32],
+    pub fuel_interval: u64,
+    pub output: i32,
+    pub steps: Vec<TraceStep>,
+}
+
+impl CanonicalTrace {
+    /// Stable binary encoding:
+    /// magic(4) | version u16 | module_hash(32) | fuel_interval u64 | output i32 | step_count u32 | steps
+    /// step: index u32 | fuel_start u64 | fuel_end u64 | instruction u32 (ffffffff once returned)
+    ///       | pre_state(32) | post_state(32)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + self.steps.len() * STEP_LEN);
+        out.extend_from_slice(TRACE_MAGIC);
+        out.extend_from_slice(&self.version.to_be_bytes());
+        out.extend_from_slice(&self.module_hash);
+        out.extend_from_slice(&self.fuel_interval.to_be_bytes());
+        out.extend_from_slice(&self.output.to_be_bytes());
+        out.extend_from_slice(&(self.steps.len() as u32).to_be_bytes());
+
+        for step in &self.steps {
+            out.extend_from_slice(&step.index.to_be_bytes());
+            out.extend_from_slice(&step.fuel_start.to_be_bytes());
+            out.extend_from_slice(&step.fuel_end.to_be_bytes());
+            out.extend_from_slice(&step.instruction.unwrap_or(RETURNED).to_be_bytes());
+            out.extend_from_slice(&step.pre_state);
+            out.extend_from_slice(&step.post_state);
+        }
+
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[0..4] != TRACE_MAGIC {
+            bail!("invalid trace header");
+        }
+
+        let version = u16::from_be_bytes(bytes[4..6].try_into()?);
+        if version != TRACE_FORMAT_VERSION {
+            bail!("unsupported trace version {}", version);
+        }
+
+        let module_hash: [u8; 32] = bytes[6..38].try_into()?;
+        let fuel_interval = u64::from_be_bytes(bytes[38..46].try_into()?);
+        let output = i32::from_be_bytes(bytes[46..50].try_into()?);
+        let step_count = u32::from_be_bytes(bytes[50..54].try_into()?) as usize;
+
+        if step_count > MAX_TRACE_STEPS || bytes.len() != HEADER_LEN + step_count * STEP_LEN {
+            bail!("trace length mismatch");
+        }
+
+        let steps = bytes[HEADER_LEN..]
+            .chunks_exact(STEP_LEN)
+            .map(|s| -> Result<TraceStep> {
+                Ok(TraceStep {
+                    index: u32::from_be_bytes(s[0..4].try_into()?),
+                    fuel_start: u64::from_be_bytes(s[4..12].try_into()?),
+                    fuel_end: u64::from_be_bytes(s[12..20].try_into()?),
+                    instruction: Some(u32::from_be_bytes(s[20..24].try_into()?)).filter(|&i| i != RETURNED),
+                    pre_state: s[24..56].try_into()?,
+                    post_state: s[56..88].try_into()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let trace = Self { version, module_hash, fuel_interval, output, steps };
+        trace.check_links()?;
+        Ok(trace)
+    }
+
+    /// Commitment over the canonical encoding
+    pub fn commitment(&self) -> [u8; 32] {
+        Sha256::digest(self.to_bytes()).into()
+    }
+
+    /// Steps must be contiguous in both fuel and state
+    pub fn check_links(&self) -> Result<()> {
+        for (i, step) in self.steps.iter().enumerate() {
+            if step.index as usize != i || step.fuel_start > step.fuel_end {
+                bail!("malformed step {}", i);
+            }
+            if i > 0 {
+                let prev = &self.steps[i - 1];
+                if step.fuel_start != prev.fuel_end || step.pre_state != prev.post_state {
+                    bail!("step {} does not continue step {}", i, i - 1);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Record a trace over `run`, which runs the job from the start to a fuel ceiling as
+/// `step_trace::record` takes it; `output` is what the job returns, which it must within
+/// `fuel_limit`
+pub fn record(
+    wasm: &[u8],
+    output: i32,
+    fuel_limit: u64,
+    fuel_interval: u64,
+    mut run: impl FnMut(u64) -> Result<Checkpoint>,
+) -> Result<CanonicalTrace> {
+    let mut checkpoints = Vec::new();
+    let trace = step_trace::record(fuel_limit, fuel_interval, |fuel| {
+        let checkpoint = run(fuel)?;
+        checkpoints.push(checkpoint);
+        Ok(checkpoint)
+    })?;
+    match trace.outcome {
+        StepStatus::Returned => {}
+        StepStatus::Running => bail!("out of fuel after {} units", fuel_limit),
+        StepStatus::Trapped => bail!("trapped after {} units", trace.fuel_consumed),
+    }
+    if trace.total_steps() > MAX_TRACE_STEPS {
+        bail!("trace exceeds {} steps", MAX_TRACE_STEPS);
+    }
+
+    let steps = checkpoints
+        .windows(2)
+        .enumerate()
+        .map(|(index, pair)| TraceStep {
+            index: index as u32,
+            fuel_start: pair[0].fuel_consumed,
+            fuel_end: pair[1].fuel_consumed,
+            instruction: pair[1].instruction,
+            pre_state: pair[0].state,
+            post_state: pair[1].state,
+        })
+        .collect();
+    Ok(CanonicalTrace {
+        version: TRACE_FORMAT_VERSION,
+        module_hash: Sha256::digest(wasm).into(),
+        fuel_interval,
+        output,
+        steps,
+    })
+}
+
+mod hex32 {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8; 32], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; 32], D::Error> {
+        let s = String::deserialize(d)?;
+        let bytes = hex::decode(&s).map_err(serde::de::Error::custom)?;
+        bytes.try_into().map_err(|_| serde::de::Error::custom("expected 32 bytes"))
+    }
+}
@@ -0,0 +1,136 @@
+use python_verifier::artifacts::{ArtifactClass, ArtifactQuotas, ArtifactStore};
+
+fn store_dir(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("certus-artifacts-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    path
+}
+
+fn quotas(wasm_bytes: u64) -> ArtifactQuotas {
+    ArtifactQuotas { wasm_bytes, min_free_bytes: 0, ..Default::default() }
+}
+
+fn far_future() -> u64 {
+    chrono::Utc::now().timestamp() as u64 + 3600
+}
+
+#[test]
+fn test_put_evicts_least_recently_used() {
+    let store = ArtifactStore::open(store_dir("lru"), quotas(30)).unwrap();
+    store.put(ArtifactClass::Wasm, "a", &[0; 10]).unwrap();
+    store.put(ArtifactClass::Wasm, "b", &[0; 10]).unwrap();
+    store.put(ArtifactClass::Wasm, "c", &[0; 10]).unwrap();
+    // touching "a" makes "b" the oldest
+    store.get(ArtifactClass::Wasm, "a").unwrap().unwrap();
+    store.put(ArtifactClass::Wasm, "d", &[0; 10]).unwrap();
+
+    assert!(store.get(ArtifactClass::Wasm, "b").unwrap().is_none());
+    assert!(store.get(ArtifactClass::Wasm, "a").unwrap().is_some());
+    let wasm = &store.stats().unwrap().classes["wasm"];
+    assert_eq!(wasm.bytes, 30);
+    assert_eq!(wasm.files, 3);
+    assert_eq!(wasm.pruned_total, 1);
+}
+
+#[test]
+fn test_quotas_are_per_class() {
+    let store = ArtifactStore::open(store_dir("classes"), quotas(10)).unwrap();
+    store.put(ArtifactClass::Wasm, "m", &[1; 10]).unwrap();
+    store.put(ArtifactClass::Input, "m", &[2; 100]).unwrap();
+
+    assert_eq!(store.get(ArtifactClass::Wasm, "m").unwrap().unwrap(), vec![1; 10]);
+    assert_eq!(store.get(ArtifactClass::Input, "m").unwrap().unwrap(), vec![2; 100]);
+    assert!(store.put(ArtifactClass::Wasm, "big", &[0; 11]).is_err());
+}
+
+#[test]
+fn test_pinned_artifacts_are_not_evicted() {
+    let store = ArtifactStore::open(store_dir("pinned"), quotas(20)).unwrap();
+    store.pin(ArtifactClass::Wasm, "disputed", far_future()).unwrap();
+    store.put(ArtifactClass::Wasm, "disputed", &[0; 10]).unwrap();
+    store.put(ArtifactClass::Wasm, "other", &[0; 10]).unwrap();
+    store.put(ArtifactClass::Wasm, "newer", &[0; 10]).unwrap();
+
+    assert!(store.get(ArtifactClass::Wasm, "disputed").unwrap().is_some());
+    assert!(store.get(ArtifactClass::Wasm, "other").unwrap().is_none());
+
+    store.pin(ArtifactClass::Wasm, "newer", far_future()).unwrap();
+    let err = store.put(ArtifactClass::Wasm, "third", &[0; 10]).unwrap_err();
+    assert!(err.to_string().contains("pinned"));
+    assert_eq!(store.stats().unwrap().classes["wasm"].pinned_bytes, 20);
+}
+
+#[test]
+fn test_prune_drops_expired_pins() {
+    let dir = store_dir("expired");
+    let store = ArtifactStore::open(&dir, quotas(100)).unwrap();
+    store.put(ArtifactClass::Wasm, "old", &[0; 10]).unwrap();
+    store.pin(ArtifactClass::Wasm, "old", 1).unwrap();
+    store.pin(ArtifactClass::Wasm, "live", far_future()).unwrap();
+
+    let report = store.prune().unwrap();
+    assert_eq!(report.expired_pins, 1);
+    assert_eq!(report.removed_files, 0);
+
+    // the live pin survives a restart
+    drop(store);
+    let store = ArtifactStore::open(&dir, quotas(15)).unwrap();
+    store.put(ArtifactClass::Wasm, "live", &[0; 10]).unwrap();
+    assert!(store.get(ArtifactClass::Wasm, "old").unwrap().is_none());
+    assert!(store.put(ArtifactClass::Wasm, "more", &[0; 10]).is_err());
+}
+
+#[test]
+fn test_low_free_space_prunes_unpinned_across_classes() {
+    let quotas = ArtifactQuotas { min_free_bytes: u64::MAX, ..Default::default() };
+    let store = ArtifactStore::open(store_dir("low-space"), quotas).unwrap();
+    store.put(ArtifactClass::Wasm, "w", &[0; 10]).unwrap();
+    store.put(ArtifactClass::Trace, "t", &[0; 20]).unwrap();
+    store.put(ArtifactClass::Input, "kept", &[0; 5]).unwrap();
+    store.pin(ArtifactClass::Input, "kept", far_future()).unwrap();
+
+    let report = store.prune().unwrap();
+    assert_eq!(report.removed_files, 2);
+    assert_eq!(report.removed_bytes, 30);
+    assert!(report.low_space);
+    assert!(store.get(ArtifactClass::Input, "kept").unwrap().is_some());
+    assert!(store.stats().unwrap().low_space);
+}
+
+#[test]
+fn test_reopen_indexes_existing_files() {
+    let dir = store_dir("reopen");
+    let store = ArtifactStore::open(&dir, quotas(100)).unwrap();
+    store.put(ArtifactClass::AotCache, "module", &[7; 42]).unwrap();
+    drop(store);
+    std::fs::write(dir.join("aot_cache").join("partial.tmp"), [0; 8]).unwrap();
+
+    let store = ArtifactStore::open(&dir, quotas(100)).unwrap();
+    let aot = &store.stats().unwrap().classes["aot_cache"];
+    assert_eq!(aot.bytes, 42);
+    assert_eq!(aot.files, 1);
+    assert!(!dir.join("aot_cache").join("partial.tmp").exists());
+    assert_eq!(store.get(ArtifactClass::AotCache, "module").unwrap().unwrap(), vec![7; 42]);
+}
+
+#[test]
+fn test_rejects_path_like_keys() {
+    let store = ArtifactStore::open(store_dir("keys"), quotas(100)).unwrap();
+    for key in ["", "../escape", "a/b", "x.tmp", &"k".repeat(129)] {
+        assert!(store.put(ArtifactClass::Wasm, key, b"data").is_err(), "{:?}", key);
+    }
+    assert!(store.pin(ArtifactClass::Wasm, "..", far_future()).is_err());
+}
+
+#[test]
+fn test_prometheus_exposition() {
+    let store = ArtifactStore::open(store_dir("metrics"), quotas(64)).unwrap();
+    store.put(ArtifactClass::Trace, "run1", &[0; 3]).unwrap();
+
+    let text = store.stats().unwrap().to_prometheus();
+    assert!(text.contains("# TYPE certus_artifact_bytes gauge"));
+    assert!(text.contains("certus_artifact_bytes{class=\"trace\"} 3"));
+    assert!(text.contains("certus_artifact_quota_bytes{class=\"wasm\"} 64"));
+    assert!(text.contains("# TYPE certus_artifact_pruned_total counter"));
+    assert!(text.contains("certus_artifact_disk_low 0"));
+}
@@ -0,0 +1,94 @@
+use python_verifier::assertion_failure;
+use python_verifier::compiler::{ASSERT_SLOT, ASSERTION_FAILED};
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use wasmtime::*;
+
+// Run main and hand back its result along with linear memory as it was left
+fn run(code: &str) -> (Result<i32>, Vec<u8>) {
+    let wasm = PythonCompiler::new().compile(code).expect("compiles");
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256))).unwrap();
+    let module = Module::new(&engine, &wasm).unwrap();
+    let instance = Instance::new(&mut store, &module, &[memory.into()]).unwrap();
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main").unwrap();
+    let result = main.call(&mut store, ());
+    (result, memory.data(&store).to_vec())
+}
+
+fn message_hash(msg: &str) -> u32 {
+    let digest = Sha256::digest(msg.as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
+fn assert_trapped(result: Result<i32>) {
+    let err = result.expect_err("expected a trap");
+    assert_eq!(err.downcast_ref::<Trap>(), Some(&Trap::UnreachableCodeReached), "{:?}", err);
+}
+
+#[test]
+fn test_passing_asserts_leave_slot_clear() {
+    let code = r#"
+xs = [1, 2, 3]
+assert len(xs) == 3
+assert xs, "xs is empty"
+OUTPUT = sum(xs)
+"#;
+    let (result, memory) = run(code);
+    assert_eq!(result.unwrap(), 6);
+    assert_eq!(assertion_failure(&memory), None);
+}
+
+#[test]
+fn test_failed_assert_records_code_and_message_hash() {
+    let (result, memory) = run("x = 5\nassert x < 3, \"x out of range\"\nOUTPUT = x");
+    assert_trapped(result);
+
+    let slot = ASSERT_SLOT as usize;
+    assert_eq!(memory[slot..slot + 4], ASSERTION_FAILED.to_le_bytes());
+    assert_eq!(assertion_failure(&memory), Some(message_hash("x out of range")));
+}
+
+#[test]
+fn test_assert_without_message_records_zero_hash() {
+    let (result, memory) = run("def check(n):\n    assert n > 0\n    return n\nOUTPUT = check(2) + check(0)");
+    assert_trapped(result);
+    assert_eq!(assertion_failure(&memory), Some(0));
+}
+
+#[test]
+fn test_except_does_not_catch_assertion_failure() {
+    let code = r#"
+try:
+    assert False, "unreachable"
+except Exception:
+    OUTPUT = 1
+OUTPUT = 2
+"#;
+    let (result, memory) = run(code);
+    assert_trapped(result);
+    assert_eq!(assertion_failure(&memory), Some(message_hash("unreachable")));
+}
+
+#[test]
+fn test_other_traps_leave_slot_clear() {
+    let cases = [
+        "OUTPUT = 1 // 0",
+        "raise ValueError",
+        "d = {1: 2}\nt = 0\nfor k in d:\n    t += k",
+        "n = 0\nwhile True:\n    n += 1",
+    ];
+    for code in cases {
+        let (result, memory) = run(code);
+        assert!(result.is_err(), "{} should trap", code);
+        assert_eq!(assertion_failure(&memory), None, "{}", code);
+    }
+}
+
+#[test]
+fn test_assert_message_must_be_literal() {
+    let err = PythonCompiler::new().compile("m = \"bad\"\nassert False, m").unwrap_err();
+    assert!(err.to_string().contains("assert message must be a string literal"), "{}", err);
+}
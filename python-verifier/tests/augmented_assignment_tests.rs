@@ -571,3 +571,26 @@ OUTPUT = x
     verify_determinism(code, 10)?;
     Ok(())
 }
+
+#[test]
+fn test_subscript_targets() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    // the subscript's object and key are evaluated once
+    let code = r#"
+calls = [0]
+def key():
+    calls[0] += 1
+    return 1
+xs = [10, 20, 30]
+xs[key()] += 5
+xs[-1] *= 2
+d = {7: 1}
+d[7] -= 3
+OUTPUT = xs[1] * 1000 + xs[2] * 10 + calls[0] + d[7]
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 25599);
+    verify_determinism(code, 10)?;
+    Ok(())
+}
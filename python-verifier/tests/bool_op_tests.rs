@@ -0,0 +1,145 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+#[test]
+fn test_and_returns_last_operand_when_truthy() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+OUTPUT = 3 and 7
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 7);
+    Ok(())
+}
+
+#[test]
+fn test_and_returns_first_falsy_operand() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+a = 0
+OUTPUT = a and 7
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 0);
+    Ok(())
+}
+
+#[test]
+fn test_or_returns_first_truthy_operand() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+a = 0
+b = 5
+OUTPUT = a or b or 9
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 5);
+    Ok(())
+}
+
+#[test]
+fn test_or_default_value() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = 0
+y = x or 42
+OUTPUT = y
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 42);
+    Ok(())
+}
+
+#[test]
+fn test_and_short_circuits_guard() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    // x[5] would trap if evaluated
+    let code = r#"
+x = [1, 2, 3]
+i = 5
+if i < 3 and x[i] == 2:
+    OUTPUT = 1
+else:
+    OUTPUT = 2
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 2);
+    Ok(())
+}
+
+#[test]
+fn test_or_short_circuits_division() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+d = 0
+ok = d == 0 or 10 // d > 1
+OUTPUT = ok
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 1);
+    Ok(())
+}
+
+#[test]
+fn test_right_operand_side_effects_skipped() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+def bump(lst):
+    lst.append(1)
+    return 1
+
+calls = []
+a = 0 and bump(calls)
+b = 1 or bump(calls)
+c = 1 and bump(calls)
+OUTPUT = len(calls) * 100 + a * 10 + b + c
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 102);
+    Ok(())
+}
+
+#[test]
+fn test_mixed_and_or_in_while() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+i = 0
+total = 0
+while i < 10 and (total < 20 or i == 0):
+    total = total + i
+    i = i + 1
+OUTPUT = total
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 21);  // 0+1+...+6
+    Ok(())
+}
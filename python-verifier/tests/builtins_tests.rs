@@ -0,0 +1,138 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+#[test]
+fn test_abs() -> Result<()> {
+    assert_eq!(run("x = -7\nOUTPUT = abs(x) * 10 + abs(3)\n")?, 73);
+    assert_eq!(run("OUTPUT = abs(0)\n")?, 0);
+    Ok(())
+}
+
+#[test]
+fn test_min_max_of_arguments() -> Result<()> {
+    assert_eq!(run("a = 4\nOUTPUT = min(a, 9, -2)\n")?, -2);
+    assert_eq!(run("a = 4\nOUTPUT = max(a, 9, -2)\n")?, 9);
+    assert_eq!(run("OUTPUT = max(3, 3)\n")?, 3);
+    Ok(())
+}
+
+#[test]
+fn test_min_max_of_list() -> Result<()> {
+    assert_eq!(run("x = [5, -1, 8, 3]\nOUTPUT = min(x)\n")?, -1);
+    assert_eq!(run("x = [5, -1, 8, 3]\nOUTPUT = max(x)\n")?, 8);
+    assert_eq!(run("t = (4, 2, 6)\nOUTPUT = min(t) * 10 + max(t)\n")?, 26);
+    Ok(())
+}
+
+#[test]
+fn test_min_of_empty_list_traps() -> Result<()> {
+    assert!(run("x = []\nOUTPUT = min(x)\n").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_sum() -> Result<()> {
+    assert_eq!(run("x = [1, 2, 3, 4]\nOUTPUT = sum(x)\n")?, 10);
+    assert_eq!(run("x = [1, 2, 3, 4]\nOUTPUT = sum(x, 100)\n")?, 110);
+    assert_eq!(run("x = []\nOUTPUT = sum(x)\n")?, 0);
+    Ok(())
+}
+
+#[test]
+fn test_sum_of_non_sequence_traps() -> Result<()> {
+    assert!(run("x = 5\nOUTPUT = sum(x)\n").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_sorted() -> Result<()> {
+    let result = run(r#"
+x = [5, 3, 9, 4, 3, 0, 7]
+y = sorted(x)
+out = 0
+for i in range(len(y)):
+    out = out * 10 + y[i]
+OUTPUT = out
+"#)?;
+    assert_eq!(result, 334579);
+    // signed order
+    assert_eq!(run("x = [2, -3, 1]\ny = sorted(x)\nOUTPUT = y[0]\n")?, -3);
+    Ok(())
+}
+
+#[test]
+fn test_sorted_leaves_input_untouched() -> Result<()> {
+    let result = run(r#"
+x = [3, 1, 2]
+y = sorted(x)
+OUTPUT = x[0] * 100 + y[0] * 10 + y[2]
+"#)?;
+    assert_eq!(result, 313);
+    Ok(())
+}
+
+#[test]
+fn test_sorted_tuple_returns_list() -> Result<()> {
+    let result = run(r#"
+t = (3, 1, 2)
+y = sorted(t)
+y.append(4)
+OUTPUT = y[0] * 10 + len(y)
+"#)?;
+    assert_eq!(result, 14);
+    Ok(())
+}
+
+#[test]
+fn test_sorted_large_list() -> Result<()> {
+    let result = run(r#"
+x = []
+for i in range(100):
+    x.append((i * 37) % 101)
+y = sorted(x)
+ok = 1
+for i in range(len(y) - 1):
+    if y[i] > y[i + 1]:
+        ok = 0
+OUTPUT = ok * 1000 + sum(y) - sum(x) + len(y)
+"#)?;
+    assert_eq!(result, 1100);
+    Ok(())
+}
+
+#[test]
+fn test_builtin_arity() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    assert!(compiler.compile("OUTPUT = abs(1, 2)\n").is_err());
+    assert!(compiler.compile("OUTPUT = min()\n").is_err());
+    assert!(compiler.compile("x = [1]\nOUTPUT = sum(x, 1, 2)\n").is_err());
+    assert!(compiler.compile("x = [1]\nOUTPUT = sorted(x, x)\n").is_err());
+    Ok(())
+}
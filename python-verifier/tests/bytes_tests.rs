@@ -0,0 +1,78 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+fn run(code: &str) -> Result<i32> {
+    let wasm = PythonCompiler::new().compile(code)?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    main.call(&mut store, ())
+}
+
+#[test]
+fn test_literal_index_and_len() -> Result<()> {
+    assert_eq!(run("b = b'\\x01\\xffA'\nOUTPUT = b[1] * 1000 + b[-1] * 10 + len(b)")?, 255653);
+    Ok(())
+}
+
+#[test]
+fn test_bytes_from_list_and_tuple() -> Result<()> {
+    let code = r#"
+xs = [104, 105]
+b = bytes(xs) + bytes((0, 33))
+OUTPUT = len(b) * 1000 + b[0] + b[3]
+"#;
+    assert_eq!(run(code)?, 4137);
+    assert_eq!(run("OUTPUT = bytes([]) == b''")?, 1);
+    Ok(())
+}
+
+#[test]
+fn test_concatenation_and_equality() -> Result<()> {
+    let code = r#"
+header = b'\x00\x01'
+msg = header + b'hi'
+OUTPUT = (msg == bytes([0, 1, 104, 105])) * 10 + (msg == b'\x00\x01hj') + (msg[2:] == b'hi') * 100
+"#;
+    assert_eq!(run(code)?, 110);
+    Ok(())
+}
+
+#[test]
+fn test_bytes_never_equal_strings() -> Result<()> {
+    assert_eq!(run("OUTPUT = b'ab' == 'ab'")?, 0);
+    Ok(())
+}
+
+#[test]
+fn test_out_of_range_element_raises_value_error() -> Result<()> {
+    let code = r#"
+try:
+    b = bytes([1, 256])
+    OUTPUT = len(b)
+except ValueError:
+    OUTPUT = -1
+"#;
+    assert_eq!(run(code)?, -1);
+    assert!(run("OUTPUT = len(bytes([-1]))").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_adding_non_bytes_traps() {
+    assert!(run("OUTPUT = len(b'a' + 1)").is_err());
+}
+
+#[test]
+fn test_digest_compares_by_content() -> Result<()> {
+    let code = r#"
+import hashlib
+OUTPUT = hashlib.sha256(b'abc').digest() == hashlib.sha256(b'ab' + b'c').digest()
+"#;
+    assert_eq!(run(code)?, 1);
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use python_verifier::canary::{CanaryRun, CanaryState, CANARY_OUTPUT_HASH};
+use python_verifier::PythonExecutor;
+use anyhow::Result;
+
+#[test]
+fn test_canary_matches_reference() -> Result<()> {
+    let run = PythonExecutor::new()?.run_canary()?;
+    assert_eq!(run.output, 12522);
+    assert_eq!(run.stdout, vec!["canary-610 8".to_string(), "-11 17 353".to_string()]);
+    assert_eq!(run.output_hash, CANARY_OUTPUT_HASH);
+    assert!(run.fuel_consumed > 0);
+    Ok(())
+}
+
+#[test]
+fn test_matching_run_keeps_node_healthy() -> Result<()> {
+    let state = CanaryState::default();
+    state.record(PythonExecutor::new()?.run_canary())?;
+    state.ensure_healthy()?;
+    let status = state.status();
+    assert_eq!(status.checks, 1);
+    assert!(status.halted.is_none());
+    assert!(status.last_run.is_some());
+    Ok(())
+}
+
+#[test]
+fn test_drift_halts_until_restart() -> Result<()> {
+    let state = CanaryState::default();
+    let drifted = CanaryRun { output: 12523, stdout: vec![], output_hash: "00".repeat(32), fuel_consumed: 1 };
+    assert!(state.record(Ok(drifted)).is_err());
+    let err = state.ensure_healthy().unwrap_err();
+    assert!(err.to_string().contains("canary output hash"), "{}", err);
+
+    // a later good run doesn't clear the halt
+    state.record(PythonExecutor::new()?.run_canary())?;
+    assert!(state.ensure_healthy().is_err());
+    assert_eq!(state.status().checks, 2);
+    Ok(())
+}
+
+#[test]
+fn test_failed_run_halts() {
+    let state = CanaryState::default();
+    assert!(state.record(Err(anyhow::anyhow!("trap"))).is_err());
+    assert!(state.status().halted.unwrap().contains("failed to run"));
+}
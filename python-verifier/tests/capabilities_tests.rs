@@ -0,0 +1,58 @@
+use python_verifier::compiler::capabilities::{markdown, Support, CAPABILITIES};
+use python_verifier::python_compiler::PythonCompiler;
+use std::collections::BTreeSet;
+
+fn compiles(code: &str) -> Result<(), String> {
+    PythonCompiler::new().compile(code).map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_examples_match_claimed_support() {
+    for cap in CAPABILITIES {
+        match cap.support {
+            Support::Supported | Support::Partial => {
+                if let Err(e) = compiles(cap.example) {
+                    panic!("'{}' is listed as {:?} but its example fails: {}", cap.construct, cap.support, e);
+                }
+            }
+            Support::Unsupported => {
+                assert!(compiles(cap.example).is_err(), "'{}' is listed as unsupported but its example compiles", cap.construct);
+            }
+        }
+        if let Some(rejected) = cap.rejected {
+            assert!(compiles(rejected).is_err(), "'{}' lists a rejected form that compiles", cap.construct);
+        }
+    }
+}
+
+#[test]
+fn test_entries_are_well_formed() {
+    let mut seen = BTreeSet::new();
+    for cap in CAPABILITIES {
+        assert!(seen.insert(cap.construct), "'{}' listed twice", cap.construct);
+        assert_eq!(cap.since.is_some(), cap.support != Support::Unsupported, "{}", cap.construct);
+        assert_eq!(cap.rejected.is_some(), cap.support == Support::Partial, "{}", cap.construct);
+    }
+}
+
+#[test]
+fn test_json_shape() {
+    let json = serde_json::to_value(CAPABILITIES).unwrap();
+    let classes = json.as_array().unwrap().iter().find(|c| c["construct"] == "classes").unwrap();
+    assert_eq!(classes["support"], "partial");
+    assert_eq!(classes["since"], "0.1.0");
+    assert!(classes["rejected"].is_string());
+
+    let floats = json.as_array().unwrap().iter().find(|c| c["construct"] == "floats").unwrap();
+    assert_eq!(floats["support"], "unsupported");
+    assert!(floats["since"].is_null());
+    assert!(floats.get("rejected").is_none());
+}
+
+#[test]
+fn test_markdown_lists_every_construct() {
+    let table = markdown();
+    assert_eq!(table.lines().count(), CAPABILITIES.len() + 2);
+    assert!(table.contains("| floats | unsupported | - |"), "{}", table);
+    assert!(table.contains("| dicts | supported | 0.1.0 |"), "{}", table);
+}
@@ -0,0 +1,76 @@
+use python_verifier::chain_params::{ChainParams, SYNCED_GETTERS};
+use python_verifier::profiles::ExecutionProfile;
+use ethers::types::U256;
+
+fn usd(dollars: u64) -> U256 {
+    U256::from(dollars * 1_000_000)
+}
+
+// Values in SYNCED_GETTERS order
+fn synced(min_deposit: U256, max_deposit: U256, bps: u64, wasm: u64) -> [U256; SYNCED_GETTERS.len()] {
+    [min_deposit, max_deposit, U256::from(bps), U256::from(wasm), U256::from(100 * 1024), U256::from(10_000_000), U256::from(128u64 << 20)]
+}
+
+#[test]
+fn test_client_deposit_clamps_to_bounds() {
+    let params = ChainParams::default();
+    assert_eq!(params.client_deposit(usd(10)), usd(5));
+    assert_eq!(params.client_deposit(usd(200)), usd(10));
+    assert_eq!(params.client_deposit(usd(100_000)), usd(1000));
+}
+
+#[test]
+fn test_sync_changes_deposit_math() {
+    let old = ChainParams::default();
+    let new = old.with_synced(synced(usd(2), usd(500), 1000, 32 * 1024)).unwrap();
+
+    assert_eq!(new.client_deposit(usd(10)), usd(2));
+    assert_eq!(new.client_deposit(usd(200)), usd(20));
+    assert_eq!(new.client_deposit(usd(100_000)), usd(500));
+    // local settings survive a sync
+    assert_eq!(new.min_payment, old.min_payment);
+    assert_eq!(new.challenge_window, old.challenge_window);
+
+    assert_eq!(old.changes(&new), [
+        "min_client_deposit: 5000000 -> 2000000",
+        "max_client_deposit: 1000000000 -> 500000000",
+        "client_deposit_bps: 500 -> 1000",
+        "max_wasm_size: 24576 -> 32768",
+    ]);
+    assert!(new.changes(&new).is_empty());
+}
+
+#[test]
+fn test_inconsistent_chain_values_are_rejected() {
+    let params = ChainParams::default();
+    let err = params.with_synced(synced(usd(10), usd(5), 500, 24 * 1024)).unwrap_err();
+    assert!(err.to_string().contains("above max"), "{}", err);
+
+    let err = params.with_synced(synced(usd(5), usd(10), 20_000, 24 * 1024)).unwrap_err();
+    assert!(err.to_string().contains("basis points"), "{}", err);
+
+    let mut values = synced(usd(5), usd(10), 500, 24 * 1024);
+    values[5] = U256::MAX;
+    let err = params.with_synced(values).unwrap_err();
+    assert!(err.to_string().contains("MAX_FUEL_LIMIT"), "{}", err);
+}
+
+#[test]
+fn test_job_checks_follow_synced_limits() {
+    let profile = ExecutionProfile::default();
+    let params = ChainParams::default();
+    params.check_job(24 * 1024, 100, &profile).unwrap();
+    params.check_payment(usd(5)).unwrap();
+    assert!(params.check_payment(usd(4)).is_err());
+
+    let err = params.check_job(24 * 1024 + 1, 100, &profile).unwrap_err();
+    assert!(err.to_string().contains("wasm exceeds 24576 byte limit"), "{}", err);
+
+    let tight = ChainParams { max_fuel_limit: 50_000, max_mem_limit: 1 << 20, ..params.clone() };
+    let err = tight.check_job(100, 100, &profile).unwrap_err();
+    assert!(err.to_string().contains("fuel limit 100000 exceeds on-chain maximum 50000"), "{}", err);
+
+    let small = ExecutionProfile { fuel_limit: 50_000, ..ExecutionProfile::default() };
+    let err = tight.check_job(100, 100, &small).unwrap_err();
+    assert!(err.to_string().contains("memory limit"), "{}", err);
+}
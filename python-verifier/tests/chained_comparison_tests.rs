@@ -0,0 +1,133 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+#[test]
+fn test_chained_lt_true() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+a = 1
+b = 5
+c = 9
+OUTPUT = a < b < c
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 1);
+    Ok(())
+}
+
+#[test]
+fn test_chained_lt_false_on_second_link() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+a = 1
+b = 5
+c = 3
+OUTPUT = a < b < c
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 0);
+    Ok(())
+}
+
+#[test]
+fn test_chained_range_check_in_if() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+count = 0
+for x in range(20):
+    if 5 <= x < 12:
+        count = count + 1
+OUTPUT = count
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 7);
+    Ok(())
+}
+
+#[test]
+fn test_chained_mixed_operators() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+a = 3
+OUTPUT = (1 < a == 3 != 4 >= 2) * 10 + (1 < a == 4)
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 10);
+    Ok(())
+}
+
+#[test]
+fn test_chained_middle_evaluated_once() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+def middle(log):
+    log.append(1)
+    return 5
+
+log = []
+ok = 1 < middle(log) < 10
+OUTPUT = ok * 10 + len(log)
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 11);
+    Ok(())
+}
+
+#[test]
+fn test_chained_short_circuits_later_operands() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+def touch(log):
+    log.append(1)
+    return 0
+
+log = []
+ok = 5 < 1 < touch(log)
+OUTPUT = ok * 10 + len(log)
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 0);
+    Ok(())
+}
+
+#[test]
+fn test_chained_in_function() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+def between(lo, x, hi):
+    return lo <= x <= hi
+
+OUTPUT = between(1, 5, 9) * 100 + between(1, 0, 9) * 10 + between(3, 3, 3)
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 101);
+    Ok(())
+}
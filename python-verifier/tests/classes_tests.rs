@@ -0,0 +1,164 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+fn run(code: &str) -> Result<i32> {
+    let wasm = PythonCompiler::new().compile(code)?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    main.call(&mut store, ())
+}
+
+fn compile_error(code: &str) -> String {
+    PythonCompiler::new().compile(code).unwrap_err().to_string()
+}
+
+#[test]
+fn test_init_and_attributes() -> Result<()> {
+    let code = r#"
+class Point:
+    """A point on the grid."""
+    def __init__(self, x, y):
+        self.x = x
+        self.y = y
+p = Point(3, 4)
+OUTPUT = p.x * 10 + p.y
+"#;
+    assert_eq!(run(code)?, 34);
+    Ok(())
+}
+
+#[test]
+fn test_methods_read_and_update_state() -> Result<()> {
+    let code = r#"
+class Counter:
+    def __init__(self, start):
+        self.count = start
+    def bump(self, by):
+        self.count += by
+        return self.count
+    def twice(self):
+        self.bump(self.count)
+        return self.count
+c = Counter(5)
+c.bump(2)
+OUTPUT = c.twice()
+"#;
+    assert_eq!(run(code)?, 14);
+    Ok(())
+}
+
+#[test]
+fn test_attribute_assignment_from_outside() -> Result<()> {
+    let code = r#"
+class Box:
+    pass
+b = Box()
+b.value = 9
+b.value += 1
+OUTPUT = b.value
+"#;
+    assert_eq!(run(code)?, 10);
+    Ok(())
+}
+
+#[test]
+fn test_instances_are_independent() -> Result<()> {
+    let code = r#"
+class Account:
+    def __init__(self, balance):
+        self.balance = balance
+    def deposit(self, amount):
+        self.balance += amount
+        return 0
+accounts = [Account(10), Account(20)]
+for i in range(3):
+    accounts[0].deposit(i)
+OUTPUT = accounts[0].balance * 100 + accounts[1].balance
+"#;
+    assert_eq!(run(code)?, 1320);
+    Ok(())
+}
+
+#[test]
+fn test_methods_dispatch_on_class() -> Result<()> {
+    let code = r#"
+class Square:
+    def __init__(self, side):
+        self.side = side
+    def area(self):
+        return self.side * self.side
+class Rect:
+    def __init__(self, w, h):
+        self.w = w
+        self.h = h
+    def area(self):
+        return self.w * self.h
+total = 0
+for shape in [Square(3), Rect(2, 5), Square(1)]:
+    total += shape.area()
+OUTPUT = total
+"#;
+    assert_eq!(run(code)?, 20);
+    Ok(())
+}
+
+#[test]
+fn test_objects_passed_to_functions() -> Result<()> {
+    let code = r#"
+class Pair:
+    def __init__(self, a, b):
+        self.a = a
+        self.b = b
+    def total(self):
+        return self.a + self.b
+def make(n):
+    return Pair(n, n * 2)
+def weigh(pair):
+    return pair.total() * pair.a
+OUTPUT = weigh(make(4))
+"#;
+    assert_eq!(run(code)?, 48);
+    Ok(())
+}
+
+#[test]
+fn test_missing_attribute_raises_key_error() -> Result<()> {
+    let code = r#"
+class Thing:
+    def __init__(self):
+        self.a = 1
+t = Thing()
+try:
+    OUTPUT = t.b
+except KeyError:
+    OUTPUT = -1
+"#;
+    assert_eq!(run(code)?, -1);
+    Ok(())
+}
+
+#[test]
+fn test_unsupported_class_forms_rejected() {
+    let err = compile_error("class A:\n    pass\nclass B(A):\n    pass\nOUTPUT = 0");
+    assert!(err.contains("base classes not supported"), "{}", err);
+
+    let err = compile_error("class A:\n    size = 3\nOUTPUT = 0");
+    assert!(err.contains("body may only define methods"), "{}", err);
+
+    let err = compile_error("class A:\n    def append(self, v):\n        return v\nOUTPUT = 0");
+    assert!(err.contains("shadows the builtin method append()"), "{}", err);
+
+    let err = compile_error("class A:\n    def __init__(self, v):\n        self.v = v\nOUTPUT = A().v");
+    assert!(err.contains("A() takes 1 positional argument"), "{}", err);
+
+    let err = compile_error("class A:\n    def get(self):\n        return 1\nf = A().get\nOUTPUT = 0");
+    assert!(err.contains("method 'get' can only be called"), "{}", err);
+
+    let err = compile_error("def f():\n    class A:\n        pass\n    return 0\nOUTPUT = f()");
+    assert!(err.contains("class 'A' must be defined at module level"), "{}", err);
+}
@@ -0,0 +1,62 @@
+use python_verifier::queue::{JobQueue, QueueBackend, QueuedJob};
+
+fn open_queue(name: &str) -> JobQueue {
+    let path = std::env::temp_dir().join(format!("certus-cluster-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    JobQueue::new(path.to_str().unwrap()).unwrap()
+}
+
+fn verification(id: &str) -> QueuedJob {
+    QueuedJob {
+        id: id.to_string(),
+        code: String::new(),
+        wasm_b64: None,
+        input: serde_json::json!({ "job_id": id }),
+        priority: 5,
+        created_at: chrono::Utc::now().timestamp() as u64,
+        retry_count: 0,
+        max_retries: 3,
+        available_at: 0,
+        claimed_at: None,
+        depends_on: vec![],
+        on_dependency_failure: Default::default(),
+        tenant: None,
+        profile: None,
+        compile_options: Default::default(),
+    }
+}
+
+async fn claim_as_worker<Q: QueueBackend>(queue: &Q) -> Option<QueuedJob> {
+    queue.claim().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_claimed_job_stays_queued_until_completed() {
+    let queue = open_queue("complete");
+    queue.submit(verification("0xaa")).await.unwrap();
+    assert!(queue.is_queued("0xaa").unwrap());
+
+    let job = claim_as_worker(&queue).await.expect("job should be claimable");
+    assert_eq!(job.id, "0xaa");
+    // a claimed job must not be fed again while a worker holds it
+    assert!(queue.is_queued("0xaa").unwrap());
+
+    QueueBackend::complete(&queue, "0xaa", serde_json::json!({ "timestamp": 0 })).await.unwrap();
+    assert!(!queue.is_queued("0xaa").unwrap());
+}
+
+#[tokio::test]
+async fn test_failed_verification_is_retried() {
+    let queue = open_queue("fail");
+    queue.submit(verification("0xbb")).await.unwrap();
+
+    let job = claim_as_worker(&queue).await.unwrap();
+    QueueBackend::fail(&queue, &job.id, "rpc timeout", None).await.unwrap();
+    assert!(queue.is_queued("0xbb").unwrap());
+}
+
+#[tokio::test]
+async fn test_unknown_job_is_not_queued() {
+    let queue = open_queue("unknown");
+    assert!(!queue.is_queued("0xcc").unwrap());
+}
@@ -0,0 +1,82 @@
+use python_verifier::compiler::{CompileOptions, Optimize};
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+fn compile(code: &str, optimize: Optimize) -> Result<Vec<u8>> {
+    PythonCompiler::new().with_options(CompileOptions { optimize, ..Default::default() }).compile(code)
+}
+
+// OUTPUT and the fuel main burned
+fn run(wasm: &[u8]) -> Result<(i32, u64)> {
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(10_000_000)?;
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let output = instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())?;
+    Ok((output, 10_000_000 - store.get_fuel()?))
+}
+
+const PROGRAMS: &[&str] = &[
+    // small function called from several places
+    "def sq(x):\n    return (x * x + 1) // 3 + x % 7\nt = 0\nfor i in range(20):\n    t += sq(i) + sq(i + 1)\nOUTPUT = t + sq(3)",
+    // sparse elif ladder
+    "t = 0\nfor x in range(16):\n    if x == 0:\n        t += 1\n    elif x == 3:\n        t += 2\n    elif x == 9:\n        t += 3\n    elif x == 14:\n        t += 4\n    else:\n        t += 5\nOUTPUT = t",
+    // loop-invariant work and products of the loop counter
+    "xs = [1, 2, 3]\nt = 0\nfor i in range(50):\n    t += i * 4 + i * 4 + len(xs)\nOUTPUT = t",
+];
+
+#[test]
+fn test_targets_agree_on_output() -> Result<()> {
+    for code in PROGRAMS {
+        let (fuel_out, _) = run(&compile(code, Optimize::Fuel)?)?;
+        let (size_out, _) = run(&compile(code, Optimize::Size)?)?;
+        assert_eq!(fuel_out, size_out, "{}", code);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_size_target_trades_fuel_for_bytes() -> Result<()> {
+    for code in PROGRAMS {
+        let fuel_wasm = compile(code, Optimize::Fuel)?;
+        let size_wasm = compile(code, Optimize::Size)?;
+        assert!(size_wasm.len() < fuel_wasm.len(), "{}: {} vs {} bytes", code, size_wasm.len(), fuel_wasm.len());
+    }
+
+    // inlined at every call site, sq costs no calls under the fuel target
+    let (_, fuel_spent) = run(&compile(PROGRAMS[0], Optimize::Fuel)?)?;
+    let (_, size_spent) = run(&compile(PROGRAMS[0], Optimize::Size)?)?;
+    assert!(fuel_spent < size_spent, "{} vs {} fuel", fuel_spent, size_spent);
+    Ok(())
+}
+
+#[test]
+fn test_size_target_inlines_single_call_sites() -> Result<()> {
+    let code = "def f(x):\n    return x * 3 + 1\nOUTPUT = f(4)";
+    assert_eq!(compile(code, Optimize::Size)?, compile(code, Optimize::Fuel)?);
+    Ok(())
+}
+
+#[test]
+fn test_fuel_is_the_default() -> Result<()> {
+    assert_eq!(CompileOptions::default().optimize, Optimize::Fuel);
+    let code = PROGRAMS[0];
+    assert_eq!(PythonCompiler::new().compile(code)?, compile(code, Optimize::Fuel)?);
+    Ok(())
+}
+
+#[test]
+fn test_options_parse() {
+    let options: CompileOptions = serde_json::from_str(r#"{"optimize": "size"}"#).unwrap();
+    assert_eq!(options.optimize, Optimize::Size);
+    let options: CompileOptions = serde_json::from_str("{}").unwrap();
+    assert_eq!(options.optimize, Optimize::Fuel);
+    assert!(serde_json::from_str::<CompileOptions>(r#"{"optimise": "size"}"#).is_err());
+
+    assert_eq!("fuel".parse::<Optimize>().unwrap(), Optimize::Fuel);
+    let err = "speed".parse::<Optimize>().unwrap_err();
+    assert!(err.to_string().contains("expected size or fuel"), "{}", err);
+}
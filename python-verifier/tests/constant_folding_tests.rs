@@ -0,0 +1,98 @@
+// Constant folding and dead-code elimination must not change what a program computes, only
+// how much code and fuel it takes to compute it.
+
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+fn compile(code: &str) -> Result<Vec<u8>> {
+    PythonCompiler::new().compile(code)
+}
+
+fn run(code: &str) -> Result<i32> {
+    let wasm = compile(code)?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())
+}
+
+#[test]
+fn test_folded_arithmetic_matches_runtime() -> Result<()> {
+    let cases = [
+        ("OUTPUT = 6 * 7 + 1", 43),
+        ("OUTPUT = 7 // -2", -4),
+        ("OUTPUT = -7 // 2", -4),
+        ("OUTPUT = -7 % 3", 2),
+        ("OUTPUT = 7 % -3", -2),
+        ("OUTPUT = 7 / 2", 3),
+        ("OUTPUT = 2 ** 10", 1024),
+        ("OUTPUT = 2 ** 32", 0),
+        ("OUTPUT = 2147483647 + 1", i32::MIN),
+        ("OUTPUT = (3 < 4) + (4 <= 4) + (5 == 5) + (5 != 5)", 3),
+        ("OUTPUT = (not 0)", 1),
+        ("OUTPUT = 0 or 9", 9),
+        ("OUTPUT = 3 and 0", 0),
+        ("OUTPUT = 5 if 1 else 6", 5),
+    ];
+    for (code, expected) in cases {
+        assert_eq!(run(code)?, expected, "{}", code);
+        // the same arithmetic on locals is computed at run time
+        let x = "x = 0\n";
+        let unfolded = code.replace("OUTPUT = ", &format!("{}OUTPUT = x + ", x));
+        assert_eq!(run(&unfolded)?, expected, "{}", unfolded);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_division_by_zero_still_raises() -> Result<()> {
+    let code = "try:\n    r = 1 // 0\nexcept ZeroDivisionError:\n    r = -1\nOUTPUT = r";
+    assert_eq!(run(code)?, -1);
+    assert!(run("OUTPUT = 5 % 0").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_dead_branches_are_not_emitted() -> Result<()> {
+    let body = "    total = 0\n    for i in range(10):\n        total = total + i * i\n    OUTPUT = total\n";
+    let live = compile(&format!("x = 0\nOUTPUT = 1\nif x:\n{}", body))?;
+    let dead = compile(&format!("OUTPUT = 1\nif 0:\n{}", body))?;
+    assert!(dead.len() < live.len(), "{} >= {}", dead.len(), live.len());
+    assert_eq!(run(&format!("OUTPUT = 1\nif 0:\n{}", body))?, 1);
+    assert_eq!(run(&format!("OUTPUT = 1\nif 1 > 0:\n{}", body))?, 285);
+    Ok(())
+}
+
+#[test]
+fn test_dead_elif_branches() -> Result<()> {
+    let code = "x = 2\nif 0:\n    OUTPUT = 1\nelif x == 2:\n    OUTPUT = 2\nelif 1:\n    OUTPUT = 3\nelse:\n    OUTPUT = 4";
+    assert_eq!(run(code)?, 2);
+    assert_eq!(run(&code.replace("x = 2", "x = 5"))?, 3);
+    Ok(())
+}
+
+#[test]
+fn test_code_after_return_is_dropped() -> Result<()> {
+    let code = "def f(n):\n    return n + 1\n    n = n * 100\n    return n\nOUTPUT = f(4)";
+    assert_eq!(run(code)?, 5);
+    Ok(())
+}
+
+#[test]
+fn test_unused_locals_are_dropped() -> Result<()> {
+    let with_unused = compile("a = 1\nb = a * 2\nOUTPUT = 3")?;
+    let without = compile("OUTPUT = 3")?;
+    assert_eq!(with_unused.len(), without.len());
+    Ok(())
+}
+
+#[test]
+fn test_unused_call_results_still_run() -> Result<()> {
+    // the call has an effect, so the assignment stays even though nothing reads `unused`
+    let code = "def grow(xs):\n    xs.append(2)\n    return 0\nitems = [1]\nunused = grow(items)\nOUTPUT = len(items)";
+    assert_eq!(run(code)?, 2);
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+#[test]
+fn test_continue_in_while() -> Result<()> {
+    let code = r#"
+i = 0
+total = 0
+while i < 10:
+    i += 1
+    if i % 3 == 0:
+        continue
+    total += i
+OUTPUT = total
+"#;
+    // 55 less 3, 6 and 9
+    assert_eq!(run(code)?, 37);
+    Ok(())
+}
+
+#[test]
+fn test_continue_in_range_loop_still_steps() -> Result<()> {
+    let code = r#"
+total = 0
+for i in range(1, 20, 2):
+    if i > 5 and i < 15:
+        continue
+    total += i
+OUTPUT = total * 100 + i
+"#;
+    // 1 + 3 + 5 + 15 + 17 + 19, and the last i is 19
+    assert_eq!(run(code)?, 6019);
+    Ok(())
+}
+
+#[test]
+fn test_continue_over_list() -> Result<()> {
+    let code = r#"
+total = 0
+for x in [4, -1, 7, 0, -3, 2]:
+    if x <= 0:
+        continue
+    total = total * 10 + x
+OUTPUT = total
+"#;
+    assert_eq!(run(code)?, 472);
+    Ok(())
+}
+
+#[test]
+fn test_continue_belongs_to_innermost_loop() -> Result<()> {
+    let code = r#"
+count = 0
+for i in range(20):
+    if i % 3 == 0:
+        continue
+    j = 0
+    while True:
+        j += 1
+        if j % 2 == 0:
+            continue
+        if j * i > 40:
+            break
+        count += j
+OUTPUT = count
+"#;
+    let mut expected = 0;
+    for i in 0..20 {
+        if i % 3 == 0 {
+            continue;
+        }
+        let mut j = 0;
+        loop {
+            j += 1;
+            if j % 2 == 0 {
+                continue;
+            }
+            if j * i > 40 {
+                break;
+            }
+            expected += j;
+        }
+    }
+    assert_eq!(run(code)?, expected);
+    Ok(())
+}
+
+#[test]
+fn test_continue_skips_no_induction_step() -> Result<()> {
+    // i * 4 twice in the body is strength-reduced unless the body continues
+    let code = r#"
+total = 0
+for i in range(10):
+    if i % 2 == 1:
+        continue
+    total += i * 4 + i * 4
+OUTPUT = total
+"#;
+    assert_eq!(run(code)?, (0 + 2 + 4 + 6 + 8) * 8);
+    Ok(())
+}
+
+#[test]
+fn test_continue_in_try_body() -> Result<()> {
+    let code = r#"
+total = 0
+for i in range(6):
+    try:
+        if i == 2:
+            continue
+        total += 10 // (i - 4)
+    except ZeroDivisionError:
+        total += 100
+OUTPUT = total
+"#;
+    // -3 - 4 - 10 + 100 + 10
+    assert_eq!(run(code)?, 93);
+    Ok(())
+}
+
+#[test]
+fn test_continue_in_function() -> Result<()> {
+    let code = r#"
+def evens(xs):
+    out = []
+    for x in xs:
+        if x % 2:
+            continue
+        out.append(x)
+    return out
+
+OUTPUT = sum([y * 10 for y in evens([1, 2, 3, 4, 6])])
+"#;
+    assert_eq!(run(code)?, 120);
+    Ok(())
+}
+
+#[test]
+fn test_continue_past_finally_is_rejected() {
+    let mut compiler = PythonCompiler::new();
+    let code = "for i in range(3):\n    try:\n        continue\n    finally:\n        i = 0\nOUTPUT = 0\n";
+    let err = compiler.compile(code).unwrap_err().to_string();
+    assert!(err.contains("continue not supported inside try with finally"), "{}", err);
+}
@@ -153,3 +153,116 @@ OUTPUT = 777
     }
     Ok(())
 }
+
+fn run(code: &str) -> Result<i32> {
+    let wasm = PythonCompiler::new().compile(code)?;
+    execute_wasm(&wasm)
+}
+
+#[test]
+fn test_dict_grows_past_initial_capacity() -> Result<()> {
+    let code = r#"
+d = {}
+for i in range(1000):
+    d[i] = i * 2
+total = 0
+for i in range(1000):
+    total += d[i]
+OUTPUT = total + len(d)
+"#;
+    assert_eq!(run(code)?, 999 * 1000 + 1000);
+    Ok(())
+}
+
+#[test]
+fn test_set_grows_past_initial_capacity() -> Result<()> {
+    let code = r#"
+s = set()
+for i in range(500):
+    s.add(i % 300)
+OUTPUT = len(s) + (299 in s) + (300 in s)
+"#;
+    assert_eq!(run(code)?, 301);
+    Ok(())
+}
+
+#[test]
+fn test_del_removes_key() -> Result<()> {
+    let code = r#"
+d = {1: 10, 2: 20, 3: 30}
+del d[2]
+OUTPUT = len(d) + d[1] + d[3]
+try:
+    x = d[2]
+except KeyError:
+    OUTPUT = OUTPUT + 100
+"#;
+    assert_eq!(run(code)?, 142);
+    Ok(())
+}
+
+#[test]
+fn test_del_missing_key_raises_key_error() -> Result<()> {
+    let code = r#"
+d = {1: 10}
+try:
+    del d[5]
+    OUTPUT = 0
+except KeyError:
+    OUTPUT = len(d)
+"#;
+    assert_eq!(run(code)?, 1);
+    Ok(())
+}
+
+#[test]
+fn test_reinsert_after_del() -> Result<()> {
+    let code = r#"
+d = {1: 10, 2: 20}
+del d[1]
+d[1] = 11
+d[2] = 22
+OUTPUT = d[1] + d[2] + len(d)
+"#;
+    assert_eq!(run(code)?, 35);
+    Ok(())
+}
+
+#[test]
+fn test_dict_pop_returns_value() -> Result<()> {
+    let code = r#"
+d = {1: 10, 2: 20}
+v = d.pop(2)
+OUTPUT = v + len(d) + (2 in d)
+"#;
+    assert_eq!(run(code)?, 21);
+    Ok(())
+}
+
+#[test]
+fn test_insert_delete_cycles_reuse_tombstones() -> Result<()> {
+    // Keys churn through a dict that never holds more than a few; tombstones are purged on
+    // rehash instead of growing the table without bound
+    let code = r#"
+d = {}
+for i in range(20000):
+    d[i] = i
+    if i >= 4:
+        del d[i - 4]
+OUTPUT = len(d) + d[19999]
+"#;
+    assert_eq!(run(code)?, 4 + 19999);
+    Ok(())
+}
+
+#[test]
+fn test_del_list_index() -> Result<()> {
+    let code = r#"
+xs = [1, 2, 3, 4]
+del xs[1]
+del xs[-1]
+OUTPUT = len(xs) * 10 + xs[1]
+"#;
+    assert_eq!(run(code)?, 23);
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+// Total locals declared by each function body
+fn declared_locals(wasm_bytes: &[u8]) -> Vec<u32> {
+    let mut counts = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(wasm_bytes) {
+        if let wasmparser::Payload::CodeSectionEntry(body) = payload.unwrap() {
+            let reader = body.get_locals_reader().unwrap();
+            counts.push(reader.into_iter().map(|l| l.unwrap().0).sum());
+        }
+    }
+    counts
+}
+
+// if x == 0: y = 100 / elif x == 1: y = 101 / ... / else: y = -1
+fn int_ladder(branches: usize, x: i32) -> String {
+    let mut code = format!("x = {}\n", x);
+    for i in 0..branches {
+        let kw = if i == 0 { "if" } else { "elif" };
+        code.push_str(&format!("{} x == {}:\n    y = {}\n", kw, i, 100 + i));
+    }
+    code.push_str("else:\n    y = 7\nOUTPUT = y\n");
+    code
+}
+
+#[test]
+fn test_elif_basic() -> Result<()> {
+    let code = r#"
+x = 2
+if x == 1:
+    y = 10
+elif x == 2:
+    y = 20
+elif x == 3:
+    y = 30
+else:
+    y = 40
+OUTPUT = y
+"#;
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    assert_eq!(execute_wasm(&wasm)?, 20);
+    Ok(())
+}
+
+#[test]
+fn test_elif_without_else() -> Result<()> {
+    let code = r#"
+x = 9
+y = 5
+if x == 1:
+    y = 10
+elif x == 2:
+    y = 20
+OUTPUT = y
+"#;
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    assert_eq!(execute_wasm(&wasm)?, 5);
+    Ok(())
+}
+
+#[test]
+fn test_elif_first_true_branch_wins() -> Result<()> {
+    let code = r#"
+x = 5
+if x > 1:
+    y = 1
+elif x > 2:
+    y = 2
+else:
+    y = 3
+OUTPUT = y
+"#;
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    assert_eq!(execute_wasm(&wasm)?, 1);
+    Ok(())
+}
+
+#[test]
+fn test_elif_100_branches() -> Result<()> {
+    for x in [0, 1, 50, 99, 100] {
+        let mut compiler = PythonCompiler::new();
+        let wasm = compiler.compile(&int_ladder(100, x))?;
+        let expected = if x < 100 { 100 + x } else { 7 };
+        assert_eq!(execute_wasm(&wasm)?, expected, "x = {}", x);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_elif_100_branches_within_limits() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let short = compiler.compile(&int_ladder(2, 0))?;
+    let mut compiler = PythonCompiler::new();
+    let long = compiler.compile(&int_ladder(100, 0))?;
+
+    // Branch count doesn't add locals to main. The short ladder compares with the shared
+    // equality function, emitted after main with locals of its own.
+    assert_eq!(declared_locals(&short)[0], declared_locals(&long)[0]);
+    assert!(declared_locals(&long).iter().all(|&n| n <= 256));
+    // 24KB on-chain module limit
+    assert!(long.len() <= 24 * 1024, "module is {} bytes", long.len());
+    Ok(())
+}
+
+#[test]
+fn test_elif_deep_ladder() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(&int_ladder(300, 299))?;
+    assert_eq!(execute_wasm(&wasm)?, 399);
+    Ok(())
+}
+
+#[test]
+fn test_elif_string_ladder() -> Result<()> {
+    let code = r#"
+op = "mul"
+if op == "add":
+    y = 1
+elif op == "sub":
+    y = 2
+elif op == "mul":
+    y = 3
+else:
+    y = 4
+OUTPUT = y
+"#;
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    assert_eq!(execute_wasm(&wasm)?, 3);
+    Ok(())
+}
+
+#[test]
+fn test_string_compare_false_takes_else() -> Result<()> {
+    let code = r#"
+s = "abc"
+if s == "abd":
+    y = 1
+else:
+    y = 2
+OUTPUT = y
+"#;
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    assert_eq!(execute_wasm(&wasm)?, 2);
+    Ok(())
+}
+
+#[test]
+fn test_elif_break_in_loop() -> Result<()> {
+    let code = r#"
+i = 0
+total = 0
+while i < 100:
+    if i == 3:
+        total = total + 100
+    elif i == 5:
+        break
+    else:
+        total = total + 1
+    i = i + 1
+OUTPUT = total + i
+"#;
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    // 0,1,2,4 add 1, 3 adds 100, break at 5
+    assert_eq!(execute_wasm(&wasm)?, 109);
+    Ok(())
+}
+
+#[test]
+fn test_elif_nested() -> Result<()> {
+    let code = r#"
+a = 2
+b = 3
+if a == 1:
+    y = 1
+elif a == 2:
+    if b == 1:
+        y = 21
+    elif b == 3:
+        y = 23
+    else:
+        y = 29
+else:
+    y = 0
+OUTPUT = y
+"#;
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    assert_eq!(execute_wasm(&wasm)?, 23);
+    Ok(())
+}
+
+#[test]
+fn test_elif_in_function() -> Result<()> {
+    let code = r#"
+def grade(n):
+    if n >= 90:
+        return 4
+    elif n >= 80:
+        return 3
+    elif n >= 70:
+        return 2
+    else:
+        return 0
+
+OUTPUT = grade(85) * 10 + grade(50)
+"#;
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    assert_eq!(execute_wasm(&wasm)?, 30);
+    Ok(())
+}
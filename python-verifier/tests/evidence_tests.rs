@@ -0,0 +1,106 @@
+use ethers::signers::{LocalWallet, Signer};
+use python_verifier::evidence::{open_archive, ChainReferences, EvidencePacket, FuelLog, MANIFEST_PATH};
+use sha2::{Digest, Sha256};
+
+const KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+fn verifier() -> LocalWallet {
+    KEY.parse().unwrap()
+}
+
+fn packet() -> EvidencePacket {
+    let mut chain = ChainReferences {
+        chain_id: 42161,
+        jobs_contract: "0x0000000000000000000000000000000000000001".to_string(),
+        escrow_contract: "0x0000000000000000000000000000000000000002".to_string(),
+        ..Default::default()
+    };
+    chain.transactions.insert("fraud_reveal".to_string(), format!("0x{}", "ab".repeat(32)));
+
+    EvidencePacket::new([7; 32], chain)
+        .with_wasm(b"\0asm\x01\0\0\0")
+        .with_input(br#"{"n": 5}"#)
+        .with_executor_output(&"11".repeat(32), None)
+        .with_verifier_output(&hex::encode(Sha256::digest(b"120")), b"120")
+        .with_fuel_log(FuelLog { fuel_limit: 100_000, verifier_consumed: Some(4_321), executor_reported: None })
+        .with_trace("verifier.ctrc", b"CTRC")
+}
+
+// Offset of an entry's data in the archive
+fn data_offset(archive: &[u8], path: &str) -> usize {
+    let mut offset = 0;
+    loop {
+        let header = &archive[offset..offset + 512];
+        let name_len = header.iter().position(|&b| b == 0).unwrap();
+        let size = usize::from_str_radix(std::str::from_utf8(&header[124..135]).unwrap(), 8).unwrap();
+        if &header[..name_len] == path.as_bytes() {
+            return offset + 512;
+        }
+        offset += 512 + size.next_multiple_of(512);
+    }
+}
+
+#[test]
+fn test_archive_is_deterministic() {
+    let first = packet().to_archive(&verifier()).unwrap();
+    let second = packet().to_archive(&verifier()).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(first.len() % 512, 0);
+}
+
+#[test]
+fn test_archive_round_trips() {
+    let archive = packet().to_archive(&verifier()).unwrap();
+    let opened = open_archive(&archive).unwrap();
+
+    assert_eq!(opened.manifest, packet().manifest());
+    assert_eq!(opened.manifest.job_id, format!("0x{}", "07".repeat(32)));
+    assert_eq!(opened.manifest.fuel.verifier_consumed, Some(4_321));
+    assert_eq!(opened.attestation.verifier, format!("{:?}", verifier().address()));
+    assert_eq!(opened.attestation.manifest_hash, hex::encode(opened.manifest_hash));
+
+    let paths: Vec<&str> = opened.manifest.files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, ["job/input.bin", "job/module.wasm", "outputs/verifier.bin", "traces/verifier.ctrc"]);
+    assert_eq!(opened.files["outputs/verifier.bin"], b"120");
+}
+
+#[test]
+fn test_manifest_hash_covers_contents() {
+    let a = open_archive(&packet().to_archive(&verifier()).unwrap()).unwrap();
+    let b = open_archive(&packet().with_input(b"{}").to_archive(&verifier()).unwrap()).unwrap();
+    assert_ne!(a.manifest_hash, b.manifest_hash);
+}
+
+#[test]
+fn test_tampered_file_is_rejected() {
+    let mut archive = packet().to_archive(&verifier()).unwrap();
+    let offset = data_offset(&archive, "outputs/verifier.bin");
+    archive[offset] = b'9';
+
+    let err = open_archive(&archive).unwrap_err();
+    assert!(err.to_string().contains("outputs/verifier.bin"), "{}", err);
+}
+
+#[test]
+fn test_tampered_manifest_breaks_attestation() {
+    let mut archive = packet().to_archive(&verifier()).unwrap();
+    let offset = data_offset(&archive, MANIFEST_PATH);
+    let manifest = std::str::from_utf8(&archive[offset..offset + 200]).unwrap().to_string();
+    let at = offset + manifest.find("42161").unwrap();
+    archive[at] = b'1';
+
+    let err = open_archive(&archive).unwrap_err();
+    assert!(err.to_string().contains("attestation is for manifest"), "{}", err);
+}
+
+#[test]
+fn test_rejects_truncated_archive() {
+    let archive = packet().to_archive(&verifier()).unwrap();
+    assert!(open_archive(&archive[..archive.len() - 2048]).is_err());
+}
+
+#[test]
+fn test_long_paths_are_refused() {
+    let name = "t".repeat(100);
+    assert!(packet().with_trace(&name, b"x").to_archive(&verifier()).is_err());
+}
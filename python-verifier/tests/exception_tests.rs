@@ -0,0 +1,238 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+fn assert_unreachable(result: Result<i32>) {
+    let err = result.expect_err("expected a trap");
+    assert_eq!(err.downcast_ref::<Trap>(), Some(&Trap::UnreachableCodeReached), "{:?}", err);
+}
+
+#[test]
+fn test_catch_zero_division() -> Result<()> {
+    let code = r#"
+x = 0
+y = 10
+try:
+    y = 7 // x
+    y = 99
+except ZeroDivisionError:
+    y = -1
+OUTPUT = y
+"#;
+    assert_eq!(run(code)?, -1);
+
+    // / and % raise too, and ArithmeticError covers ZeroDivisionError
+    let code = r#"
+d = 0
+hits = 0
+try:
+    hits = 5 / d
+except ArithmeticError:
+    hits = hits + 1
+try:
+    hits = 5 % d
+except ZeroDivisionError:
+    hits = hits + 10
+OUTPUT = hits
+"#;
+    assert_eq!(run(code)?, 11);
+    Ok(())
+}
+
+#[test]
+fn test_dict_lookup_and_key_error() -> Result<()> {
+    let code = r#"
+d = {1: 50, 3: 7}
+total = d[3] * 10 + d[1]
+try:
+    total = total + d[2]
+except KeyError:
+    total = total + 1000
+OUTPUT = total
+"#;
+    assert_eq!(run(code)?, 1120);
+
+    // a missing key outside any try still traps
+    assert_unreachable(run("d = {1: 2}\nOUTPUT = d[5]"));
+    Ok(())
+}
+
+#[test]
+fn test_handler_selection() -> Result<()> {
+    let code = r#"
+def check(n):
+    if n < 0:
+        raise ValueError("negative")
+    return n
+
+OUTPUT = 0
+for i in range(3):
+    try:
+        check(i - 1)
+        OUTPUT = OUTPUT + 1
+    except KeyError:
+        OUTPUT = OUTPUT + 100
+    except (ZeroDivisionError, ValueError):
+        OUTPUT = OUTPUT + 10
+"#;
+    assert_eq!(run(code)?, 12);
+
+    // bare except and Exception catch everything
+    let code = r#"
+OUTPUT = 0
+try:
+    raise KeyError
+except:
+    OUTPUT = OUTPUT + 1
+try:
+    raise ValueError
+except Exception:
+    OUTPUT = OUTPUT + 2
+"#;
+    assert_eq!(run(code)?, 3);
+    Ok(())
+}
+
+#[test]
+fn test_propagates_through_calls() -> Result<()> {
+    let code = r#"
+# certus: noinline
+def inner(d, k):
+    return d[k]
+
+def outer(d, k):
+    v = inner(d, k)
+    return v + 1
+
+d = {1: 5}
+try:
+    OUTPUT = outer(d, 1) + outer(d, 2)
+except KeyError:
+    OUTPUT = -outer(d, 1)
+"#;
+    assert_eq!(run(code)?, -6);
+    Ok(())
+}
+
+#[test]
+fn test_else_and_finally_order() -> Result<()> {
+    let code = r#"
+log = 0
+try:
+    log = log * 10 + 1
+except ValueError:
+    log = log * 10 + 2
+else:
+    log = log * 10 + 3
+finally:
+    log = log * 10 + 4
+try:
+    raise ValueError
+except ValueError:
+    log = log * 10 + 5
+else:
+    log = log * 10 + 6
+finally:
+    log = log * 10 + 7
+OUTPUT = log
+"#;
+    assert_eq!(run(code)?, 13457);
+    Ok(())
+}
+
+#[test]
+fn test_finally_runs_before_propagating() -> Result<()> {
+    let code = r#"
+def work(d):
+    n = 1
+    try:
+        n = d[9]
+    finally:
+        d[0] = 42
+    return n
+
+d = {0: 0}
+try:
+    work(d)
+except LookupError:
+    d[1] = 1
+OUTPUT = d[0] + d[1]
+"#;
+    assert_eq!(run(code)?, 43);
+    Ok(())
+}
+
+#[test]
+fn test_reraise_and_uncaught() -> Result<()> {
+    let code = r#"
+seen = 0
+try:
+    try:
+        x = 1 // seen
+    except ZeroDivisionError:
+        seen = 1
+        raise
+except ValueError:
+    seen = 100
+except ZeroDivisionError:
+    seen = seen + 10
+OUTPUT = seen
+"#;
+    assert_eq!(run(code)?, 11);
+
+    // an unmatched handler lets the exception escape main as a trap
+    assert_unreachable(run("try:\n    raise KeyError\nexcept ValueError:\n    x = 2\nOUTPUT = 1"));
+    Ok(())
+}
+
+#[test]
+fn test_break_inside_try() -> Result<()> {
+    let code = r#"
+OUTPUT = 0
+d = {0: 1, 1: 1, 2: 1}
+for i in range(10):
+    try:
+        OUTPUT = OUTPUT + d[i]
+    except KeyError:
+        break
+"#;
+    assert_eq!(run(code)?, 3);
+    Ok(())
+}
+
+#[test]
+fn test_unsupported_forms_rejected() {
+    let mut compiler = PythonCompiler::new();
+    assert!(compiler.compile("try:\n    x = 1\nexcept ValueError as e:\n    x = 2").is_err());
+    assert!(compiler.compile("raise").is_err());
+    assert!(compiler.compile("raise RuntimeError").is_err());
+    assert!(compiler.compile("try:\n    x = 1\nexcept OSError:\n    x = 2").is_err());
+    let code = "def f():\n    try:\n        return 1\n    finally:\n        x = 2\n    return 0\nOUTPUT = f()";
+    assert!(compiler.compile(code).is_err());
+}
@@ -0,0 +1,40 @@
+use python_verifier::compiler::RuntimeError;
+use python_verifier::executor_error::ExecutorError;
+use python_verifier::PythonExecutor;
+
+#[test]
+fn test_rejected_source_is_a_validation_failure() {
+    let err = PythonExecutor::new().unwrap().execute("import os\nOUTPUT = 1", "{}", 1_000_000).unwrap_err();
+    assert!(matches!(ExecutorError::of(&err), Some(ExecutorError::ValidationFailed { .. })), "{}", err);
+
+    let err = PythonExecutor::new().unwrap().execute("OUTPUT = 1", "not json", 1_000_000).unwrap_err();
+    assert!(matches!(ExecutorError::of(&err), Some(ExecutorError::ValidationFailed { .. })), "{}", err);
+
+    // calls are allowlisted, so an unknown one is turned away before it reaches the compiler
+    let err = PythonExecutor::new().unwrap().execute("OUTPUT = undefined_function(1)", "{}", 1_000_000).unwrap_err();
+    assert!(matches!(ExecutorError::of(&err), Some(ExecutorError::ValidationFailed { .. })), "{}", err);
+}
+
+#[test]
+fn test_uncompilable_source_is_a_compile_error() {
+    // passes the policy, but codegen has no dict.get
+    let err = PythonExecutor::new().unwrap().execute("d = {}\nOUTPUT = d.get(1, 0)", "{}", 1_000_000).unwrap_err();
+    assert!(matches!(ExecutorError::of(&err), Some(ExecutorError::CompileError { .. })), "{}", err);
+}
+
+#[test]
+fn test_other_errors_have_no_kind() {
+    assert_eq!(ExecutorError::of(&anyhow::anyhow!("rpc unreachable")), None);
+}
+
+#[test]
+fn test_serializes_tagged() {
+    let err = ExecutorError::Trap { code: Some(RuntimeError::KeyError), location: None, fuel_consumed: 12 };
+    assert_eq!(
+        serde_json::to_value(&err).unwrap(),
+        serde_json::json!({ "kind": "trap", "code": "key_error", "fuel_consumed": 12 })
+    );
+    assert_eq!(err.to_string(), "trapped: uncaught KeyError");
+    let back: ExecutorError = serde_json::from_value(serde_json::to_value(&err).unwrap()).unwrap();
+    assert_eq!(back, err);
+}
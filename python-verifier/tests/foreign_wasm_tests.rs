@@ -0,0 +1,215 @@
+use python_verifier::compiler::{determinism, fuel, ModuleLimits};
+use python_verifier::python_compiler::PythonCompiler;
+use python_verifier::executor_error::ExecutorError;
+use python_verifier::{read_output, PythonExecutor, INPUT_RECORD};
+use anyhow::Result;
+use wasm_encoder::{
+    CodeSection, EntityType, ExportKind, ExportSection, Function, FunctionSection, ImportSection, Instruction,
+    MemorySection, MemoryType, Module, TypeSection, ValType,
+};
+
+const MEMORY: MemoryType = MemoryType { minimum: 1, maximum: None, memory64: false, shared: false };
+
+// python_main(input_ptr, input_len) returns the input's record, so the output is the input
+fn echo_module(body: &[Instruction], import: Option<(&str, &str)>, own_memory: bool) -> Vec<u8> {
+    let mut module = Module::new();
+
+    let mut types = TypeSection::new();
+    types.function([ValType::I32, ValType::I32], [ValType::I32]);
+    types.function([], []);
+    module.section(&types);
+
+    let mut imports = ImportSection::new();
+    if !own_memory {
+        imports.import("env", "memory", MEMORY);
+    }
+    if let Some((module_name, name)) = import {
+        imports.import(module_name, name, EntityType::Function(1));
+    }
+    module.section(&imports);
+
+    let mut funcs = FunctionSection::new();
+    funcs.function(0);
+    module.section(&funcs);
+
+    if own_memory {
+        let mut memories = MemorySection::new();
+        memories.memory(MEMORY);
+        module.section(&memories);
+    }
+
+    let imported_funcs = import.is_some() as u32;
+    let mut exports = ExportSection::new();
+    exports.export("python_main", ExportKind::Func, imported_funcs);
+    exports.export("memory", ExportKind::Memory, 0);
+    module.section(&exports);
+
+    let mut code = CodeSection::new();
+    let mut main = Function::new([]);
+    for instruction in body {
+        main.instruction(instruction);
+    }
+    main.instruction(&Instruction::LocalGet(0));
+    main.instruction(&Instruction::I32Const(4));
+    main.instruction(&Instruction::I32Sub);
+    main.instruction(&Instruction::End);
+    code.function(&main);
+    module.section(&code);
+
+    module.finish()
+}
+
+#[test]
+fn test_foreign_module_runs_with_python_abi() -> Result<()> {
+    let wasm = echo_module(&[], None, false);
+    let mut executor = PythonExecutor::new()?;
+    let output = executor.execute_wasm(&wasm, r#"{"x": 21}"#, 1_000_000)?;
+
+    assert!(output.success);
+    assert_eq!(output.result, r#"{"x": 21}"#);
+    assert!(output.fuel_consumed > 0);
+    Ok(())
+}
+
+#[test]
+fn test_prepared_module_is_metered_and_bounded() -> Result<()> {
+    let wasm = echo_module(&[], None, false);
+    let prepared = PythonExecutor::new()?.prepare_wasm(&wasm)?;
+
+    assert!(!fuel::is_metered(&wasm)?);
+    assert!(fuel::is_metered(&prepared)?);
+    let limits = ModuleLimits::read(&prepared)?.expect("limits section");
+    // the export and the fuel charge it calls
+    assert_eq!(limits.max_call_depth, 2);
+    assert!(!limits.recursive);
+    Ok(())
+}
+
+#[test]
+fn test_declared_limits_are_replaced() -> Result<()> {
+    let mut wasm = echo_module(&[], None, false);
+    let declared = ModuleLimits { max_call_depth: 1, max_operand_stack: 1, max_locals: 0, memory_pages: 1, recursive: false };
+    declared.append_to(&mut wasm);
+    let prepared = PythonExecutor::new()?.prepare_wasm(&wasm)?;
+
+    let limits = ModuleLimits::read(&prepared)?.expect("limits section");
+    assert_eq!(limits, ModuleLimits::read(&PythonExecutor::new()?.prepare_wasm(&echo_module(&[], None, false))?)?.unwrap());
+    assert_eq!(limits.max_call_depth, 2);
+    Ok(())
+}
+
+#[test]
+fn test_policy_rejects_nondeterministic_modules() -> Result<()> {
+    let executor = PythonExecutor::new()?;
+
+    let floats = echo_module(&[Instruction::F32Const(1.5), Instruction::Drop], None, false);
+    let err = executor.prepare_wasm(&floats).unwrap_err();
+    assert!(err.to_string().contains("not deterministic"), "{}", err);
+
+    let wasi = echo_module(&[], Some(("wasi_snapshot_preview1", "proc_exit")), false);
+    let err = executor.prepare_wasm(&wasi).unwrap_err();
+    assert!(err.to_string().contains("wasi_snapshot_preview1.proc_exit"), "{}", err);
+
+    let own_memory = echo_module(&[], None, true);
+    let err = executor.prepare_wasm(&own_memory).unwrap_err();
+    assert!(err.to_string().contains("env.memory"), "{}", err);
+
+    // the host's abort hook is part of the ABI
+    assert!(determinism::validate(&echo_module(&[], Some(("env", "abort")), false)).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_import_allowlist_reads_the_import_section() -> Result<()> {
+    // any host function outside the allowlist, not just WASI
+    let env_write = echo_module(&[], Some(("env", "fd_write")), false);
+    let err = determinism::check_imports(&env_write).unwrap_err();
+    assert!(err.to_string().contains("env.fd_write"), "{}", err);
+
+    // a forbidden name outside the import section is only bytes
+    let mut named = echo_module(&[], None, false);
+    let name = b"wasi_snapshot_preview1";
+    named.push(0);
+    named.push((1 + 4 + name.len()) as u8);
+    named.push(4);
+    named.extend_from_slice(b"note");
+    named.extend_from_slice(name);
+    determinism::check_imports(&named)?;
+    determinism::validate(&named)
+}
+
+#[test]
+fn test_compiled_python_meets_policy() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile("d = {1: 2}\nOUTPUT = 0\nfor i in range(4):\n    OUTPUT = OUTPUT + d[1] // 2")?;
+    determinism::check_opcodes(&wasm)?;
+    determinism::validate(&wasm)
+}
+
+#[test]
+fn test_opcode_check_reads_instructions() -> Result<()> {
+    // integer instructions whose opcodes or immediates are bytes in the float range
+    let integer = echo_module(
+        &[
+            Instruction::I32Const(0x43),
+            Instruction::I32Eqz,
+            Instruction::I64ExtendI32U,
+            Instruction::I64Const(0xBF),
+            Instruction::I64Add,
+            Instruction::Drop,
+        ],
+        None,
+        false,
+    );
+    determinism::check_opcodes(&integer)?;
+
+    for (body, kind) in [
+        (vec![Instruction::F64Const(2.0), Instruction::Drop], "float"),
+        (vec![Instruction::I32Const(0), Instruction::F32ConvertI32S, Instruction::I32TruncSatF32S, Instruction::Drop], "float"),
+        (vec![Instruction::V128Const(0), Instruction::Drop], "simd"),
+        (vec![Instruction::AtomicFence], "atomic"),
+    ] {
+        let err = determinism::check_opcodes(&echo_module(&body, None, false)).unwrap_err();
+        assert!(err.to_string().starts_with(kind), "{}", err);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_output_is_read_by_its_length() -> Result<()> {
+    // well past any fixed buffer, and no terminator to look for
+    let long = format!("\"{}\"", "a".repeat(10_000));
+    let output = PythonExecutor::new()?.execute_wasm(&echo_module(&[], None, false), &long, 1_000_000)?;
+    assert_eq!(output.result, long);
+    Ok(())
+}
+
+#[test]
+fn test_output_holding_nul_is_rejected() -> Result<()> {
+    // overwrite the input's second byte with a NUL before echoing it
+    let body = [
+        Instruction::I32Const(INPUT_RECORD as i32 + 5),
+        Instruction::I32Const(0),
+        Instruction::I32Store8(wasm_encoder::MemArg { offset: 0, align: 0, memory_index: 0 }),
+    ];
+    let err = PythonExecutor::new()?.execute_wasm(&echo_module(&body, None, false), r#""ab""#, 1_000_000).unwrap_err();
+    assert!(err.to_string().contains("NUL"), "{}", err);
+    assert!(matches!(ExecutorError::of(&err), Some(ExecutorError::ValidationFailed { .. })));
+    Ok(())
+}
+
+#[test]
+fn test_read_output_bounds() {
+    let mut memory = vec![0u8; 64];
+    memory[8..12].copy_from_slice(&3u32.to_le_bytes());
+    memory[12..15].copy_from_slice(b"abc");
+    assert_eq!(read_output(&memory, 8, 3).unwrap(), b"abc");
+
+    let err = read_output(&memory, 8, 2).unwrap_err();
+    assert_eq!(ExecutorError::of(&err), Some(ExecutorError::OutputTooLarge { limit: 2 }));
+
+    memory[8..12].copy_from_slice(&100u32.to_le_bytes());
+    assert!(read_output(&memory, 8, 1000).unwrap_err().to_string().contains("runs past memory"));
+    assert!(read_output(&memory, 62, 1000).unwrap_err().to_string().contains("outside memory"));
+    assert!(read_output(&memory, u32::MAX, 1000).is_err());
+}
@@ -0,0 +1,90 @@
+use anyhow::Result;
+use python_verifier::python_compiler::PythonCompiler;
+use wasmtime::*;
+
+// Compile and run, reading OUTPUT back as a string object
+fn run_string(code: &str) -> Result<String> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, &wasm)?;
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let ptr = main.call(&mut store, ())? as usize;
+
+    let data = memory.data(&store);
+    let word = |at: usize| i32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    if word(ptr) != 3 {
+        anyhow::bail!("Expected string type tag (3), got {}", word(ptr));
+    }
+    let length = word(ptr + 4) as usize;
+    Ok(String::from_utf8(data[ptr + 8..ptr + 8 + length].to_vec())?)
+}
+
+#[test]
+fn test_fstring_ints_and_strings() -> Result<()> {
+    let code = "x = 5\nname = \"ab\"\nOUTPUT = f\"x={x}, name={name}!\"";
+    assert_eq!(run_string(code)?, "x=5, name=ab!");
+    Ok(())
+}
+
+#[test]
+fn test_fstring_negative_and_expressions() -> Result<()> {
+    let code = "a = -42\nb = 7\nOUTPUT = f\"{a} + {b} = {a + b}\"";
+    assert_eq!(run_string(code)?, "-42 + 7 = -35");
+    Ok(())
+}
+
+#[test]
+fn test_fstring_literal_only() -> Result<()> {
+    assert_eq!(run_string("OUTPUT = f\"plain text\"")?, "plain text");
+    Ok(())
+}
+
+#[test]
+fn test_fstring_in_loop() -> Result<()> {
+    let code = "s = \"\"\nfor i in range(3):\n    s = f\"{s}[{i}]\"\nOUTPUT = s";
+    assert_eq!(run_string(code)?, "[0][1][2]");
+    Ok(())
+}
+
+#[test]
+fn test_format_positional() -> Result<()> {
+    let code = "total = 12\nOUTPUT = \"{} items, {} total\".format(\"three\", total)";
+    assert_eq!(run_string(code)?, "three items, 12 total");
+    Ok(())
+}
+
+#[test]
+fn test_format_indexed_and_braces() -> Result<()> {
+    let code = "OUTPUT = \"{{{1}-{0}-{1}}}\".format(1, 2)";
+    assert_eq!(run_string(code)?, "{2-1-2}");
+    Ok(())
+}
+
+#[test]
+fn test_format_evaluates_arguments_once() -> Result<()> {
+    let code = "xs = [1]\nOUTPUT = \"{0}/{0}\".format(xs.pop())";
+    assert_eq!(run_string(code)?, "1/1");
+    Ok(())
+}
+
+#[test]
+fn test_format_errors() {
+    let mut compiler = PythonCompiler::new();
+    assert!(compiler.compile("OUTPUT = \"{:>4}\".format(1)").is_err());
+    assert!(compiler.compile("OUTPUT = \"{name}\".format(name=1)").is_err());
+    assert!(compiler.compile("OUTPUT = \"{} {}\".format(1)").is_err());
+    assert!(compiler.compile("OUTPUT = \"{} {0}\".format(1)").is_err());
+    assert!(compiler.compile("OUTPUT = \"{\".format(1)").is_err());
+    assert!(compiler.compile("x = 1\nOUTPUT = f\"{x:04}\"").is_err());
+    assert!(compiler.compile("x = 1\nOUTPUT = f\"{x!r}\"").is_err());
+}
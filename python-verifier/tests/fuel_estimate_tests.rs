@@ -0,0 +1,107 @@
+use python_verifier::compiler::{CompileOptions, FuelEstimate, LoopKind, Optimize, PythonCompiler};
+use anyhow::Result;
+
+fn estimate(code: &str) -> Result<FuelEstimate> {
+    PythonCompiler::new().estimate_fuel(code, 100)
+}
+
+#[test]
+fn test_straight_line_code_is_bounded() -> Result<()> {
+    let small = estimate("x = 6\nOUTPUT = x * 7")?;
+    assert!(small.bounded);
+    assert!(small.fuel > 0);
+    assert!(small.loops.is_empty());
+
+    // str() walks the digits: bounded by the size hint
+    let more = estimate("x = 6\nOUTPUT = x * 7\nOUTPUT = OUTPUT + len(str(OUTPUT))")?;
+    assert!(more.bounded);
+    assert!(more.fuel > small.fuel);
+    Ok(())
+}
+
+#[test]
+fn test_if_counts_its_costlier_arm() -> Result<()> {
+    // two call sites keep f out of line, so x stays unknown
+    let program = |then: &str, otherwise: &str| {
+        format!("def f(x):\n    if x:\n        r = {}\n    else:\n        r = {}\n    return r\nOUTPUT = f(0) + f(1)", then, otherwise)
+    };
+    let size = CompileOptions { optimize: Optimize::Size, ..Default::default() };
+    let estimate = |code: String| PythonCompiler::new().with_options(size).estimate_fuel(&code, 100);
+
+    let cheap = estimate(program("1", "2"))?;
+    let costly = estimate(program("1", "x * 3 // 2 + x % 5"))?;
+    assert!(costly.fuel > cheap.fuel);
+    // swapping the arms changes nothing
+    let swapped = estimate(program("x * 3 // 2 + x % 5", "1"))?;
+    assert_eq!(swapped.fuel, costly.fuel);
+    Ok(())
+}
+
+#[test]
+fn test_constant_range_loops_report_their_bound() -> Result<()> {
+    let template = "t = 0\nfor i in range(N):\n    t += i * 3\nOUTPUT = t";
+    let ten = estimate(&template.replace('N', "10"))?;
+    let hundred = estimate(&template.replace('N', "100"))?;
+    assert!(ten.bounded && hundred.bounded);
+
+    assert_eq!(ten.loops.len(), 1);
+    let lp = &ten.loops[0];
+    assert_eq!((lp.function.as_str(), lp.kind, lp.iterations), ("main", LoopKind::For, Some(10)));
+    assert_eq!(hundred.loops[0].iterations, Some(100));
+    assert_eq!(hundred.loops[0].fuel_per_iteration, lp.fuel_per_iteration);
+    assert_eq!(hundred.fuel - ten.fuel, 90 * lp.fuel_per_iteration);
+    Ok(())
+}
+
+#[test]
+fn test_range_step_and_direction() -> Result<()> {
+    let up = estimate("t = 0\nfor i in range(0, 10, 3):\n    t += i\nOUTPUT = t")?;
+    assert_eq!(up.loops[0].iterations, Some(4));
+    let down = estimate("t = 0\nfor i in range(10, 0, -4):\n    t += i\nOUTPUT = t")?;
+    assert_eq!(down.loops[0].iterations, Some(3));
+    let empty = estimate("t = 0\nfor i in range(5, 5):\n    t += i\nOUTPUT = t")?;
+    assert_eq!(empty.loops[0].iterations, Some(0));
+    Ok(())
+}
+
+#[test]
+fn test_nested_loops_multiply() -> Result<()> {
+    let code = "t = 0\nfor i in range(4):\n    for j in range(5):\n        t += i * j\nOUTPUT = t";
+    let nested = estimate(code)?;
+    assert!(nested.bounded);
+    assert_eq!(nested.loops.len(), 2);
+    let (outer, inner) = (&nested.loops[0], &nested.loops[1]);
+    assert_eq!((outer.iterations, inner.iterations), (Some(4), Some(5)));
+    assert!(outer.fuel_per_iteration > 5 * inner.fuel_per_iteration);
+    Ok(())
+}
+
+#[test]
+fn test_unknown_bounds_leave_the_estimate_unbounded() -> Result<()> {
+    let whiles = estimate("i = 0\nwhile i < 10:\n    i += 1\nOUTPUT = i")?;
+    assert!(!whiles.bounded);
+    assert_eq!(whiles.loops[0].kind, LoopKind::While);
+    assert_eq!(whiles.loops[0].iterations, None);
+    assert!(whiles.loops[0].fuel_per_iteration > 0);
+
+    let recursive = estimate("def f(n):\n    if n <= 1:\n        return 1\n    return n * f(n - 1)\nOUTPUT = f(5)")?;
+    assert!(!recursive.bounded);
+    Ok(())
+}
+
+#[test]
+fn test_loops_in_functions_are_attributed() -> Result<()> {
+    let code = "def total(n):\n    t = 0\n    for i in range(n):\n        t += i\n    return t\nOUTPUT = total(3) + total(4)";
+    let estimate = PythonCompiler::new()
+        .with_options(CompileOptions { optimize: Optimize::Size, ..Default::default() })
+        .estimate_fuel(code, 100)?;
+    assert_eq!(estimate.loops.len(), 1);
+    assert_eq!(estimate.loops[0].function, "total");
+    assert_eq!(estimate.loops[0].iterations, None);
+    Ok(())
+}
+
+#[test]
+fn test_estimate_rejects_what_compile_rejects() {
+    assert!(estimate("OUTPUT = 1.5").is_err());
+}
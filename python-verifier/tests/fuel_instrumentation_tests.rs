@@ -0,0 +1,229 @@
+use python_verifier::compiler::fuel::{instrument, mark_metered, FUEL_EXPORT, FUEL_LIMIT};
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasm_encoder::{
+    BlockType, CodeSection, ConstExpr, EntityType, ExportKind, ExportSection, Function, FunctionSection,
+    GlobalSection, GlobalType, ImportSection, Instruction, MemArg, MemorySection, MemoryType, Module,
+    TypeSection, ValType,
+};
+use wasmtime::{Engine, Instance, Linker, Store, Trap};
+
+const MEM: MemArg = MemArg { offset: 0, align: 2, memory_index: 0 };
+
+// Hand-written module: main(n) calls inc() n times; inc bumps the i32 at address 0
+fn counter_module() -> Vec<u8> {
+    let mut module = Module::new();
+
+    let mut types = TypeSection::new();
+    types.function([ValType::I32], [ValType::I32]);
+    types.function([], []);
+    module.section(&types);
+
+    let mut funcs = FunctionSection::new();
+    funcs.function(0);
+    funcs.function(1);
+    module.section(&funcs);
+
+    let mut memories = MemorySection::new();
+    memories.memory(MemoryType { minimum: 1, maximum: Some(1), memory64: false, shared: false });
+    module.section(&memories);
+
+    let mut exports = ExportSection::new();
+    exports.export("main", ExportKind::Func, 0);
+    module.section(&exports);
+
+    let mut code = CodeSection::new();
+    let mut main = Function::new([]);
+    for instruction in [
+        Instruction::Block(BlockType::Empty),
+        Instruction::Loop(BlockType::Empty),
+        Instruction::LocalGet(0),
+        Instruction::I32Eqz,
+        Instruction::BrIf(1),
+        Instruction::Call(1),
+        Instruction::LocalGet(0),
+        Instruction::I32Const(1),
+        Instruction::I32Sub,
+        Instruction::LocalSet(0),
+        Instruction::Br(0),
+        Instruction::End,
+        Instruction::End,
+        Instruction::I32Const(0),
+        Instruction::I32Load(MEM),
+        Instruction::End,
+    ] {
+        main.instruction(&instruction);
+    }
+    code.function(&main);
+
+    let mut inc = Function::new([]);
+    for instruction in [
+        Instruction::I32Const(0),
+        Instruction::I32Const(0),
+        Instruction::I32Load(MEM),
+        Instruction::I32Const(1),
+        Instruction::I32Add,
+        Instruction::I32Store(MEM),
+        Instruction::End,
+    ] {
+        inc.instruction(&instruction);
+    }
+    code.function(&inc);
+    module.section(&code);
+
+    module.finish()
+}
+
+// Returns main's result and the fuel counter, if exported
+fn run(wasm: &[u8], arg: i32) -> Result<(i32, Option<i32>)> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = wasmtime::Module::new(&engine, wasm)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let result = instance.get_typed_func::<i32, i32>(&mut store, "main")?.call(&mut store, arg)?;
+    let fuel = instance.get_global(&mut store, FUEL_EXPORT).map(|g| g.get(&mut store).unwrap_i32());
+    Ok((result, fuel))
+}
+
+#[test]
+fn test_instrumented_module_behaves_the_same() -> Result<()> {
+    let original = counter_module();
+    let metered = instrument(&original)?;
+    assert_eq!(run(&original, 7)?, (7, None));
+    assert_eq!(run(&metered, 7)?.0, 7);
+    Ok(())
+}
+
+#[test]
+fn test_charges_follow_cost_table() -> Result<()> {
+    let metered = instrument(&counter_module())?;
+    for n in [0, 1, 25] {
+        // each charge adds its own 19. main: the loop test 3 runs n + 1 times; per iteration the
+        // call 10, the decrement and branch 5, and inc's loads, stores and arithmetic 8; the final
+        // load 3
+        let expected = 22 * (n + 1) + n * (29 + 24 + 27) + 22;
+        assert_eq!(run(&metered, n)?.1, Some(expected), "n = {}", n);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_runaway_loop_traps_at_fuel_limit() -> Result<()> {
+    let metered = instrument(&counter_module())?;
+    // each iteration costs 102, so this many iterations can't fit
+    let err = run(&metered, FUEL_LIMIT / 102 + 1).unwrap_err();
+    assert_eq!(err.downcast_ref::<Trap>(), Some(&Trap::UnreachableCodeReached), "{:?}", err);
+    assert!(run(&metered, FUEL_LIMIT / 204).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_existing_globals_and_imports_keep_their_indices() -> Result<()> {
+    let mut module = Module::new();
+    let mut types = TypeSection::new();
+    types.function([ValType::I32], [ValType::I32]);
+    module.section(&types);
+
+    let mut imports = ImportSection::new();
+    imports.import("env", "base", EntityType::Global(GlobalType { val_type: ValType::I32, mutable: false }));
+    module.section(&imports);
+
+    let mut funcs = FunctionSection::new();
+    funcs.function(0);
+    module.section(&funcs);
+
+    let mut globals = GlobalSection::new();
+    globals.global(GlobalType { val_type: ValType::I32, mutable: true }, &ConstExpr::i32_const(100));
+    module.section(&globals);
+
+    let mut exports = ExportSection::new();
+    exports.export("main", ExportKind::Func, 0);
+    module.section(&exports);
+
+    let mut code = CodeSection::new();
+    let mut main = Function::new([]);
+    for instruction in [
+        Instruction::GlobalGet(0),
+        Instruction::GlobalGet(1),
+        Instruction::I32Add,
+        Instruction::LocalGet(0),
+        Instruction::I32Add,
+        Instruction::End,
+    ] {
+        main.instruction(&instruction);
+    }
+    code.function(&main);
+    module.section(&code);
+
+    let metered = instrument(&module.finish())?;
+
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let mut linker = Linker::new(&engine);
+    let base = wasmtime::Global::new(
+        &mut store,
+        wasmtime::GlobalType::new(wasmtime::ValType::I32, wasmtime::Mutability::Const),
+        wasmtime::Val::I32(20),
+    )?;
+    linker.define(&mut store, "env", "base", base)?;
+    let instance = linker.instantiate(&mut store, &wasmtime::Module::new(&engine, &metered)?)?;
+    assert_eq!(instance.get_typed_func::<i32, i32>(&mut store, "main")?.call(&mut store, 3)?, 123);
+    assert_eq!(instance.get_global(&mut store, FUEL_EXPORT).unwrap().get(&mut store).unwrap_i32(), 5 + 19);
+    Ok(())
+}
+
+#[test]
+fn test_block_charges_follow_gas_schedule() -> Result<()> {
+    // 100 / 7 under the schedule: two constants, a division and the function's end
+    let mut module = Module::new();
+    let mut types = TypeSection::new();
+    types.function([ValType::I32], [ValType::I32]);
+    module.section(&types);
+    let mut funcs = FunctionSection::new();
+    funcs.function(0);
+    module.section(&funcs);
+    let mut exports = ExportSection::new();
+    exports.export("main", ExportKind::Func, 0);
+    module.section(&exports);
+    let mut code = CodeSection::new();
+    let mut main = Function::new([]);
+    for instruction in [Instruction::I32Const(100), Instruction::I32Const(7), Instruction::I32DivS, Instruction::End] {
+        main.instruction(&instruction);
+    }
+    code.function(&main);
+    module.section(&code);
+
+    let metered = instrument(&module.finish())?;
+    let expected = 2 * certus_gas::BASE_COST + certus_gas::DIVISION_COST + 19;
+    assert_eq!(run(&metered, 0)?, (14, Some(expected as i32)));
+    Ok(())
+}
+
+#[test]
+fn test_marker_section_is_not_trusted() -> Result<()> {
+    let mut forged = counter_module();
+    mark_metered(&mut forged);
+    let metered = instrument(&forged)?;
+    assert_ne!(metered, forged);
+    assert_eq!(run(&metered, 25)?, run(&instrument(&counter_module())?, 25)?);
+    Ok(())
+}
+
+#[test]
+fn test_instrumenting_twice_meters_the_metered_module() -> Result<()> {
+    let once = instrument(&counter_module())?;
+    let twice = instrument(&once)?;
+    // the first counter's charges are instructions like any other, and only the new counter is exported
+    let (result, fuel) = run(&twice, 25)?;
+    assert_eq!(result, 25);
+    assert!(fuel > run(&once, 25)?.1, "{:?}", fuel);
+
+    let wasm = PythonCompiler::new().compile("OUTPUT = 6 * 7")?;
+    assert_ne!(instrument(&wasm)?, wasm);
+    Ok(())
+}
+
+#[test]
+fn test_invalid_module_rejected() {
+    assert!(instrument(b"\0asm\x01\0\0\0\x0a\x02").is_err());
+}
@@ -0,0 +1,113 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+fn run(code: &str) -> Result<i32> {
+    let wasm = PythonCompiler::new().compile(code)?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    main.call(&mut store, ())
+}
+
+#[test]
+fn test_function_reads_module_variable() -> Result<()> {
+    let code = r#"
+def scaled(n):
+    return n * FACTOR + OFFSET
+FACTOR = 3
+OFFSET = 100
+OUTPUT = scaled(5)
+"#;
+    assert_eq!(run(code)?, 115);
+    Ok(())
+}
+
+#[test]
+fn test_global_statement_writes_module_variable() -> Result<()> {
+    let code = r#"
+count = 10
+def bump(by):
+    global count
+    count += by
+    return count
+bump(1)
+bump(2)
+OUTPUT = count * 1000 + bump(0)
+"#;
+    assert_eq!(run(code)?, 13013);
+    Ok(())
+}
+
+#[test]
+fn test_assignment_without_global_stays_local() -> Result<()> {
+    let code = r#"
+x = 7
+def shadow():
+    x = 1
+    return x
+y = shadow()
+OUTPUT = x * 10 + y
+"#;
+    assert_eq!(run(code)?, 71);
+    Ok(())
+}
+
+#[test]
+fn test_global_changed_by_call_inside_loop() -> Result<()> {
+    // the read of total in the loop must not be hoisted past the calls that change it
+    let code = r#"
+total = 0
+def add(v):
+    global total
+    total = total + v
+    return 0
+seen = 0
+for i in range(5):
+    add(i)
+    seen += total
+OUTPUT = seen
+"#;
+    assert_eq!(run(code)?, 20);
+    Ok(())
+}
+
+#[test]
+fn test_global_loop_targets_and_output() -> Result<()> {
+    let code = r#"
+def last_index():
+    global i, OUTPUT
+    for i in range(4):
+        OUTPUT = i * 2
+    return i
+def report():
+    return i
+OUTPUT = 0
+for i in range(3):
+    pass_through = i
+OUTPUT = last_index() * 100 + report()
+"#;
+    assert_eq!(run(code)?, 303);
+    Ok(())
+}
+
+#[test]
+fn test_function_sets_output() -> Result<()> {
+    let code = r#"
+def finish(v):
+    global OUTPUT
+    OUTPUT = v
+finish(42)
+"#;
+    assert_eq!(run(code)?, 42);
+    Ok(())
+}
+
+#[test]
+fn test_parameter_declared_global_rejected() {
+    let err = PythonCompiler::new().compile("g = 1\ndef f(g):\n    global g\n    return g\nOUTPUT = f(2)").unwrap_err();
+    assert!(err.to_string().contains("name 'g' is parameter and global"), "{}", err);
+}
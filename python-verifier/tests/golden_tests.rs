@@ -0,0 +1,41 @@
+use python_verifier::vectors::VectorSet;
+use python_verifier::PythonExecutor;
+use std::collections::HashSet;
+
+#[test]
+fn test_golden_set_parses() {
+    let set = VectorSet::golden();
+    assert_eq!(set.version, 1);
+    let names: HashSet<_> = set.vectors.iter().map(|v| v.name.as_str()).collect();
+    assert_eq!(names.len(), set.vectors.len(), "vector names repeat");
+}
+
+#[test]
+fn test_pipeline_matches_golden_set() {
+    let mut executor = PythonExecutor::new().unwrap();
+    let mismatches = VectorSet::golden().verify_pipeline(&mut executor).unwrap();
+    assert!(mismatches.is_empty(), "{:#?}", mismatches);
+}
+
+#[test]
+fn test_pipeline_matches_v1_set() {
+    let mut executor = PythonExecutor::new().unwrap();
+    let mismatches = VectorSet::v1().verify_pipeline(&mut executor).unwrap();
+    assert!(mismatches.is_empty(), "{:#?}", mismatches);
+}
+
+#[test]
+fn test_compiler_matches_golden_set() {
+    let mismatches = VectorSet::golden().verify().unwrap();
+    assert!(mismatches.is_empty(), "{:#?}", mismatches);
+}
+
+#[test]
+fn test_pipeline_reports_a_wrong_vector() {
+    let mut set = VectorSet::golden();
+    set.vectors.truncate(1);
+    set.vectors[0].output += 1;
+    let mismatches = set.verify_pipeline(&mut PythonExecutor::new().unwrap()).unwrap();
+    let fields: Vec<_> = mismatches.iter().map(|m| m.field).collect();
+    assert_eq!(fields, ["output"]);
+}
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use python_verifier::python_compiler::PythonCompiler;
+use sha2::{Digest, Sha256};
+use wasmtime::*;
+
+// Run main and return (OUTPUT, linear memory snapshot)
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<(i32, Vec<u8>)> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok((result, memory.data(&store).to_vec()))
+}
+
+// Read a string (type tag 3) from memory
+fn read_string(data: &[u8], ptr: i32) -> Result<String> {
+    let ptr = ptr as usize;
+    let type_tag = i32::from_le_bytes(data[ptr..ptr + 4].try_into()?);
+    if type_tag != 3 {
+        anyhow::bail!("Expected string type tag (3), got {}", type_tag);
+    }
+    let length = i32::from_le_bytes(data[ptr + 4..ptr + 8].try_into()?) as usize;
+    Ok(String::from_utf8(data[ptr + 8..ptr + 8 + length].to_vec())?)
+}
+
+fn run_hex(code: &str) -> Result<String> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    let (result, memory) = execute_wasm(&wasm)?;
+    read_string(&memory, result)
+}
+
+#[test]
+fn test_sha256_bytes_literal_chained() -> Result<()> {
+    let hex = run_hex(r#"
+import hashlib
+OUTPUT = hashlib.sha256(b"abc").hexdigest()
+"#)?;
+    assert_eq!(hex, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    Ok(())
+}
+
+#[test]
+fn test_sha256_update_matches_one_shot() -> Result<()> {
+    let hex = run_hex(r#"
+import hashlib
+h = hashlib.sha256()
+h.update(b"a")
+h.update("bc".encode())
+OUTPUT = h.hexdigest()
+"#)?;
+    assert_eq!(hex, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    Ok(())
+}
+
+#[test]
+fn test_sha256_no_argument_is_empty_hash() -> Result<()> {
+    let hex = run_hex(r#"
+import hashlib
+OUTPUT = hashlib.sha256().hexdigest()
+"#)?;
+    assert_eq!(hex, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    Ok(())
+}
+
+#[test]
+fn test_sha256_digest_returns_bytes() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+import hashlib
+d = hashlib.sha256(b"certus").digest()
+OUTPUT = d
+"#;
+    let wasm = compiler.compile(code)?;
+    let (ptr, memory) = execute_wasm(&wasm)?;
+    let ptr = ptr as usize;
+
+    assert_eq!(i32::from_le_bytes(memory[ptr..ptr + 4].try_into()?), 4); // TYPE_BYTES
+    assert_eq!(i32::from_le_bytes(memory[ptr + 4..ptr + 8].try_into()?), 32);
+    assert_eq!(&memory[ptr + 8..ptr + 40], Sha256::digest(b"certus").as_slice());
+    Ok(())
+}
+
+#[test]
+fn test_sha256_of_digest() -> Result<()> {
+    let hex = run_hex(r#"
+import hashlib
+inner = hashlib.sha256(b"abc").digest()
+OUTPUT = hashlib.sha256(inner).hexdigest()
+"#)?;
+    assert_eq!(hex, hex::encode(Sha256::digest(Sha256::digest(b"abc"))));
+    Ok(())
+}
+
+#[test]
+fn test_update_on_non_hash_object_traps() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = b"abc"
+x.update(b"d")
+OUTPUT = 1
+"#;
+    let wasm = compiler.compile(code)?;
+    assert!(execute_wasm(&wasm).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_bytes_literal_len() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile("OUTPUT = len(b\"hello\")\n")?;
+    let (result, _) = execute_wasm(&wasm)?;
+    assert_eq!(result, 5);
+    Ok(())
+}
+
+#[test]
+fn test_sha256_too_many_arguments() {
+    let mut compiler = PythonCompiler::new();
+    assert!(compiler.compile("import hashlib\nOUTPUT = hashlib.sha256(b\"a\", b\"b\")\n").is_err());
+}
@@ -0,0 +1,109 @@
+// Loops whose allocations can't outlive an iteration rewind the heap each time round, so a
+// long loop over temporaries runs in constant heap instead of trapping at the heap's end.
+
+use python_verifier::compiler::ResourceLimits;
+use python_verifier::python_compiler::PythonCompiler;
+use python_verifier::PythonExecutor;
+use anyhow::Result;
+use wasmtime::*;
+
+// A 448KB heap, so loops that would outgrow it fit in the fuel limit
+const MEMORY_PAGES: u32 = 8;
+
+fn run(code: &str) -> Result<i32> {
+    let limits = ResourceLimits { memory_pages: MEMORY_PAGES, ..Default::default() };
+    let wasm = PythonCompiler::new().with_resource_limits(limits).compile(code)?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(MEMORY_PAGES, Some(MEMORY_PAGES)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    main.call(&mut store, ())
+}
+
+#[test]
+fn test_for_loop_temporaries_are_freed() -> Result<()> {
+    // ~1.3MB of strings in total, well past the heap
+    let code = r#"
+count = 0
+for i in range(40000):
+    s = str(i) + "-"
+    if s.startswith("1"):
+        count = count + 1
+OUTPUT = count
+"#;
+    assert_eq!(run(code)?, 11111);
+    Ok(())
+}
+
+#[test]
+fn test_while_loop_temporaries_are_freed() -> Result<()> {
+    // counts rather than sums lengths: ints past the heap's start would pass for pointers to `+`
+    let code = r#"
+n = 0
+total = 0
+while n < 40000:
+    t = "x" + str(n)
+    if len(t) == 6:
+        total = total + 1
+    n = n + 1
+OUTPUT = total
+"#;
+    assert_eq!(run(code)?, 30000);
+    Ok(())
+}
+
+#[test]
+fn test_values_that_outlive_an_iteration_are_kept() -> Result<()> {
+    // read after the loop
+    assert_eq!(run("last = \"\"\nfor i in range(5):\n    last = str(i) + \"!\"\nOUTPUT = last == \"4!\"")?, 1);
+    // carried into the next iteration
+    assert_eq!(run("s = \"\"\nfor i in range(5):\n    s = s + str(i)\nOUTPUT = s == \"01234\"")?, 1);
+    // stored into an object from before the loop
+    assert_eq!(run("items = []\nfor i in range(3):\n    items.append(str(i) + \"x\")\nOUTPUT = items[2] == \"2x\"")?, 1);
+    // kept by a user function
+    let code = r#"
+seen = {}
+def remember(k, v):
+    seen[k] = v
+    return 0
+for i in range(3):
+    remember(i, str(i) + "y")
+OUTPUT = seen[1] == "1y"
+"#;
+    assert_eq!(run(code)?, 1);
+    Ok(())
+}
+
+#[test]
+fn test_nested_loops_rewind_independently() -> Result<()> {
+    let code = r#"
+total = 0
+for i in range(150):
+    row = str(i)
+    for j in range(150):
+        cell = row + ":" + str(j)
+        if len(cell) == 5:
+            total = total + 1
+OUTPUT = total
+"#;
+    let expected = (0..150)
+        .flat_map(|i: i32| (0..150).map(move |j: i32| i.to_string().len() + 1 + j.to_string().len()))
+        .filter(|&len| len == 5)
+        .count() as i32;
+    assert_eq!(run(code)?, expected);
+    Ok(())
+}
+
+#[test]
+fn test_executors_agree() -> Result<()> {
+    let code = "count = 0\nfor i in range(20000):\n    s = str(i) + \"-\"\n    if s.endswith(\"7-\"):\n        count = count + 1\nOUTPUT = count";
+    let first = PythonExecutor::new()?.execute(code, "{}", 50_000_000)?;
+    let second = PythonExecutor::new()?.execute(code, "{}", 50_000_000)?;
+    assert!(first.success);
+    assert_eq!(first.result, second.result);
+    assert_eq!(first.output_hash, second.output_hash);
+    assert_eq!(first.fuel_consumed, second.fuel_consumed);
+    Ok(())
+}
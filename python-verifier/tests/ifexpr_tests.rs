@@ -0,0 +1,65 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+fn run(code: &str) -> Result<i32> {
+    let wasm = PythonCompiler::new().compile(code)?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    main.call(&mut store, ())
+}
+
+#[test]
+fn test_ternary_picks_branch() -> Result<()> {
+    assert_eq!(run("a = 3\nb = 9\nOUTPUT = a if a > b else b")?, 9);
+    assert_eq!(run("a = 30\nb = 9\nOUTPUT = a if a > b else b")?, 30);
+    Ok(())
+}
+
+#[test]
+fn test_nested_ternary() -> Result<()> {
+    let code = r#"
+def sign(x):
+    return -1 if x < 0 else 0 if x == 0 else 1
+OUTPUT = sign(-5) * 100 + sign(0) * 10 + sign(7)
+"#;
+    assert_eq!(run(code)?, -99);
+    Ok(())
+}
+
+#[test]
+fn test_only_taken_branch_runs() -> Result<()> {
+    let code = r#"
+d = 0
+x = 5 if d == 0 else 10 // d
+OUTPUT = x
+"#;
+    assert_eq!(run(code)?, 5);
+    Ok(())
+}
+
+#[test]
+fn test_ternary_over_heap_values() -> Result<()> {
+    let code = r#"
+flag = True
+items = [1, 2, 3] if flag else []
+label = "long" if len(items) > 2 else "short"
+OUTPUT = len(items) * 10 + len(label)
+"#;
+    assert_eq!(run(code)?, 34);
+    Ok(())
+}
+
+// the flat entry point is the same compiler, so both paths emit the same module
+#[test]
+fn test_both_entry_points_agree() -> Result<()> {
+    let code = "x = 4\nOUTPUT = x * 2 if x % 2 == 0 else x + 1";
+    let flat = PythonCompiler::new().compile(code)?;
+    let direct = python_verifier::compiler::PythonCompiler::new().compile(code)?;
+    assert_eq!(flat, direct);
+    Ok(())
+}
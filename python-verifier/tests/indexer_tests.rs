@@ -0,0 +1,148 @@
+use ethers::abi::{encode, Token};
+use ethers::types::{Address, Bytes, Log, H256, U256, U64};
+use python_verifier::indexer::{IndexedEvent, ProtocolEvent, ProtocolIndexer, ProtocolStats};
+
+fn ours() -> Address {
+    Address::repeat_byte(0x0e)
+}
+
+fn open_indexer(name: &str, window: u64) -> ProtocolIndexer {
+    let path = std::env::temp_dir().join(format!("certus-indexer-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    ProtocolIndexer::open(path.to_str().unwrap(), ours(), window).unwrap()
+}
+
+fn event(job: u8, block: u64, timestamp: u64, event: ProtocolEvent) -> IndexedEvent {
+    IndexedEvent { job_id: H256::repeat_byte(job), block, log_index: job as u64, timestamp, event }
+}
+
+fn log(signature: &str, topics: Vec<H256>, data: Vec<u8>) -> Log {
+    Log {
+        topics: [vec![H256(ethers::utils::keccak256(signature))], topics].concat(),
+        data: Bytes::from(data),
+        block_number: Some(U64::from(100)),
+        log_index: Some(U256::from(3)),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_decode_lifecycle_logs() {
+    let job = H256::repeat_byte(7);
+    let executor = H256::from(Address::repeat_byte(0xe1));
+
+    let accepted = log("JobAccepted(bytes32,address,uint256)", vec![job, executor], vec![0; 32]);
+    let decoded = IndexedEvent::decode(&accepted, 1_700_000_000).unwrap();
+    assert_eq!(decoded.job_id, job);
+    assert_eq!((decoded.block, decoded.log_index, decoded.timestamp), (100, 3, 1_700_000_000));
+    assert_eq!(decoded.event, ProtocolEvent::Accepted { executor: Address::repeat_byte(0xe1) });
+
+    let fraud = log("FraudDetected(bytes32,address,address,uint256)", vec![job, executor], vec![0; 64]);
+    assert_eq!(
+        IndexedEvent::decode(&fraud, 0).unwrap().event,
+        ProtocolEvent::FraudDetected { executor: Address::repeat_byte(0xe1) },
+    );
+
+    let resolved = |fraud: bool| log("BisectionResolved(bytes32,bool)", vec![job], encode(&[Token::Bool(fraud)]));
+    assert_eq!(IndexedEvent::decode(&resolved(true), 0).unwrap().event, ProtocolEvent::ChallengeResolved { fraud: true });
+    assert_eq!(IndexedEvent::decode(&resolved(false), 0).unwrap().event, ProtocolEvent::ChallengeResolved { fraud: false });
+
+    // other events, and pending logs, are skipped
+    assert!(IndexedEvent::decode(&log("JobCancelled(bytes32)", vec![job], vec![]), 0).is_none());
+    let mut pending = accepted;
+    pending.block_number = None;
+    assert!(IndexedEvent::decode(&pending, 0).is_none());
+}
+
+#[test]
+fn test_rescanning_a_range_stores_events_once() {
+    let indexer = open_indexer("backfill", 0);
+    let batch = vec![
+        event(1, 10, 1_000, ProtocolEvent::Receipt),
+        event(2, 11, 1_010, ProtocolEvent::Receipt),
+    ];
+    assert_eq!(indexer.record(&batch).unwrap(), 2);
+    // an overlapping backfill adds only what is new
+    let overlap = vec![batch[1].clone(), event(3, 12, 1_020, ProtocolEvent::Receipt)];
+    assert_eq!(indexer.record(&overlap).unwrap(), 1);
+
+    let stored = indexer.events().unwrap();
+    assert_eq!(stored.iter().map(|e| e.block).collect::<Vec<_>>(), vec![10, 11, 12]);
+    assert_eq!(indexer.stats(2_000, None).unwrap().receipts, 3);
+}
+
+#[test]
+fn test_dispute_and_fraud_rates() {
+    let other = Address::repeat_byte(0x0f);
+    let events = vec![
+        // undisputed, finalized an hour after its receipt
+        event(1, 1, 0, ProtocolEvent::Accepted { executor: ours() }),
+        event(1, 2, 100, ProtocolEvent::Receipt),
+        event(1, 3, 3_700, ProtocolEvent::Finalized { executor: ours() }),
+        // challenged, executor vindicated
+        event(2, 1, 0, ProtocolEvent::Accepted { executor: ours() }),
+        event(2, 2, 100, ProtocolEvent::Receipt),
+        event(2, 3, 200, ProtocolEvent::Challenged { challenger: Address::repeat_byte(0x0c) }),
+        event(2, 4, 900, ProtocolEvent::ChallengeResolved { fraud: false }),
+        event(2, 5, 5_000, ProtocolEvent::Finalized { executor: ours() }),
+        // direct fraud proof against another executor
+        event(3, 1, 0, ProtocolEvent::Accepted { executor: other }),
+        event(3, 2, 100, ProtocolEvent::Receipt),
+        event(3, 3, 300, ProtocolEvent::FraudDetected { executor: other }),
+        // executor timeout claim, finalized after a day
+        event(4, 1, 0, ProtocolEvent::Accepted { executor: other }),
+        event(4, 2, 100, ProtocolEvent::Receipt),
+        event(4, 3, 86_500, ProtocolEvent::TimeoutClaimed { executor: other }),
+    ];
+    let stats = ProtocolStats::compute(&events, ours(), 0);
+
+    assert_eq!((stats.receipts, stats.settled, stats.disputes, stats.frauds), (4, 4, 2, 1));
+    assert_eq!(stats.dispute_rate, 0.5);
+    assert_eq!(stats.fraud_success_rate, 0.5);
+    // disputed jobs don't count towards latency
+    assert_eq!(stats.avg_finalization_latency_secs, Some((3_600.0 + 86_400.0) / 2.0));
+    assert_eq!(stats.executors, 2);
+    assert_eq!(stats.top_executor_share, 0.5);
+    assert_eq!(stats.executor_hhi, 0.5);
+    assert_eq!((stats.our_receipts, stats.our_disputes, stats.our_frauds), (2, 1, 0));
+}
+
+#[test]
+fn test_stats_window_follows_block_time() {
+    let indexer = open_indexer("window", 3_600);
+    indexer.record(&[
+        event(1, 1, 1_000, ProtocolEvent::Challenged { challenger: Address::repeat_byte(0x0c) }),
+        event(2, 2, 10_000, ProtocolEvent::Receipt),
+        event(2, 3, 10_100, ProtocolEvent::FraudDetected { executor: ours() }),
+    ]).unwrap();
+    indexer.set_cursor(4).unwrap();
+
+    // the configured window drops the old challenge however recently it was indexed
+    let recent = indexer.stats(12_000, None).unwrap();
+    assert_eq!((recent.disputes, recent.frauds, recent.our_frauds), (1, 1, 1));
+    assert_eq!(recent.since, 12_000 - 3_600);
+    assert_eq!(recent.indexed_block, Some(3));
+
+    let all = indexer.stats(12_000, Some(0)).unwrap();
+    assert_eq!((all.since, all.disputes), (0, 2));
+}
+
+#[test]
+fn test_empty_index_reports_zeroes() {
+    let stats = open_indexer("empty", 0).stats(1_000, None).unwrap();
+    assert_eq!(stats, ProtocolStats { since: 0, ..Default::default() });
+    assert!(stats.to_prometheus().contains("certus_protocol_dispute_rate 0\n"));
+}
+
+#[test]
+fn test_prometheus_exposition() {
+    let events = vec![
+        event(1, 1, 0, ProtocolEvent::Receipt),
+        event(1, 2, 10, ProtocolEvent::Challenged { challenger: Address::repeat_byte(0x0c) }),
+        event(1, 3, 20, ProtocolEvent::FraudDetected { executor: ours() }),
+    ];
+    let text = ProtocolStats::compute(&events, ours(), 0).to_prometheus();
+    assert!(text.contains("# TYPE certus_protocol_disputes gauge\ncertus_protocol_disputes 1\n"));
+    assert!(text.contains("certus_protocol_fraud_success_rate 1\n"));
+    assert!(text.contains("certus_protocol_our_disputes 1\n"));
+}
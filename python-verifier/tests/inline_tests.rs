@@ -0,0 +1,225 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+// Functions compiled from the program itself. The shared runtime functions, named with a "$"
+// no Python function can have, come along with any `+` or `==` whatever is inlined.
+fn function_count(code: &str) -> Result<usize> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    let mut count = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(&wasm) {
+        if let wasmparser::Payload::CustomSection(section) = payload? {
+            if section.name() != "name" {
+                continue;
+            }
+            for name in wasmparser::NameSectionReader::new(section.data(), section.data_offset()) {
+                if let wasmparser::Name::Function(names) = name? {
+                    for naming in names {
+                        count += usize::from(!naming?.name.starts_with('$'));
+                    }
+                }
+            }
+        }
+    }
+    Ok(count)
+}
+
+fn fuel_used(code: &str) -> Result<u64> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(10_000_000)?;
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())?;
+
+    Ok(10_000_000 - store.get_fuel()?)
+}
+
+const SQUARE_LOOP: &str = r#"
+def square(x):
+    return x * x
+
+total = 0
+for i in range(100):
+    total = total + square(i)
+OUTPUT = total
+"#;
+
+#[test]
+fn test_small_helper_is_inlined() -> Result<()> {
+    assert_eq!(run(SQUARE_LOOP)?, 328350);
+    // the helper's only call site was inlined, so the function itself is gone
+    assert_eq!(function_count(SQUARE_LOOP)?, 1);
+    Ok(())
+}
+
+#[test]
+fn test_noinline_pragma() -> Result<()> {
+    let above = SQUARE_LOOP.replace("def square(x):", "# certus: noinline\ndef square(x):");
+    let trailing = SQUARE_LOOP.replace("def square(x):", "def square(x):  # certus: noinline");
+    for code in [&above, &trailing] {
+        assert_eq!(run(code)?, 328350);
+        assert_eq!(function_count(code)?, 2);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_inlining_saves_fuel() -> Result<()> {
+    let noinline = SQUARE_LOOP.replace("def square(x):", "# certus: noinline\ndef square(x):");
+    assert!(fuel_used(SQUARE_LOOP)? < fuel_used(&noinline)?);
+    Ok(())
+}
+
+#[test]
+fn test_args_evaluated_once_in_order() -> Result<()> {
+    let code = r#"
+def combine(a, b):
+    return a * 10 + b + a
+
+xs = [1, 2, 3]
+OUTPUT = combine(xs.pop(), xs.pop())
+"#;
+    // a = 3, b = 2
+    assert_eq!(run(code)?, 35);
+    Ok(())
+}
+
+#[test]
+fn test_nested_inlined_calls() -> Result<()> {
+    let code = r#"
+def add(a, b):
+    return a + b
+
+OUTPUT = add(add(1, 2), add(add(3, 4), 5))
+"#;
+    assert_eq!(run(code)?, 15);
+    assert_eq!(function_count(code)?, 1);
+    Ok(())
+}
+
+#[test]
+fn test_straight_line_body_with_locals() -> Result<()> {
+    let code = r#"
+def scaled(x, k):
+    y = x * k
+    x = y + 1
+    return x + y
+
+total = 0
+for i in range(5):
+    total = total + scaled(i, 3)
+OUTPUT = total
+"#;
+    // sum over i of (3i + 1) + 3i = 6 * 10 + 5
+    assert_eq!(run(code)?, 65);
+    assert_eq!(function_count(code)?, 1);
+    Ok(())
+}
+
+#[test]
+fn test_caller_locals_not_clobbered() -> Result<()> {
+    let code = r#"
+def f(x):
+    y = x + 1
+    return y * 2
+
+y = 100
+x = 5
+r = f(x)
+OUTPUT = r + y + x
+"#;
+    assert_eq!(run(code)?, 117);
+    Ok(())
+}
+
+#[test]
+fn test_chained_comparison_in_callee() -> Result<()> {
+    let code = r#"
+def between(lo, x, hi):
+    return lo <= x < hi
+
+count = 0
+for i in range(20):
+    count = count + between(5, i, 12)
+OUTPUT = count
+"#;
+    assert_eq!(run(code)?, 7);
+    Ok(())
+}
+
+#[test]
+fn test_functions_with_control_flow_not_inlined() -> Result<()> {
+    let code = r#"
+def clamp(x):
+    if x > 10:
+        return 10
+    return x
+
+OUTPUT = clamp(3) + clamp(50)
+"#;
+    assert_eq!(run(code)?, 13);
+    assert_eq!(function_count(code)?, 2);
+    Ok(())
+}
+
+#[test]
+fn test_recursive_function_not_inlined() -> Result<()> {
+    let code = r#"
+def fact(n):
+    return n * fact(n - 1) if n > 1 else 1
+
+OUTPUT = fact(5)
+"#;
+    assert_eq!(run(code)?, 120);
+    assert_eq!(function_count(code)?, 2);
+    Ok(())
+}
+
+#[test]
+fn test_size_cap_outside_loops() -> Result<()> {
+    // too big to inline at a straight-line call site, small enough inside a loop
+    let helper = r#"
+def mix(a, b):
+    t = a * 31 + b
+    u = t * 17 + a
+    return t + u * 3 + a * b
+"#;
+    let once = format!("{}\nOUTPUT = mix(1, 2)\n", helper);
+    let looped = format!("{}\ntotal = 0\nfor i in range(3):\n    total = total + mix(i, 2)\nOUTPUT = total\n", helper);
+    assert_eq!(run(&once)?, 33 + (33 * 17 + 1) * 3 + 2);
+    assert_eq!(function_count(&once)?, 2);
+    assert_eq!(function_count(&looped)?, 1);
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+#[test]
+fn test_iterate_list_and_tuple() -> Result<()> {
+    let code = r#"
+total = 0
+for x in [3, 1, 4, 1, 5]:
+    total = total * 10 + x
+for y in (2, 7):
+    total = total + y
+OUTPUT = total
+"#;
+    assert_eq!(run(code)?, 31424);
+    Ok(())
+}
+
+#[test]
+fn test_iterate_string() -> Result<()> {
+    // like s[i], each step yields the character's byte value
+    let code = r#"
+count = 0
+for ch in "banana":
+    if ch == 97:
+        count += 1
+OUTPUT = count
+"#;
+    assert_eq!(run(code)?, 3);
+    Ok(())
+}
+
+#[test]
+fn test_loop_variable_and_break() -> Result<()> {
+    let code = r#"
+found = -1
+for x in [5, 8, 13, 21]:
+    if x % 2 == 0:
+        found = x
+        break
+OUTPUT = found * 100 + x
+"#;
+    assert_eq!(run(code)?, 808);
+    Ok(())
+}
+
+#[test]
+fn test_enumerate() -> Result<()> {
+    let code = r#"
+weighted = 0
+for i, x in enumerate([10, 20, 30]):
+    weighted += i * x
+shifted = 0
+for i, x in enumerate([10, 20, 30], 1):
+    shifted += i * x
+OUTPUT = weighted * 1000 + shifted
+"#;
+    assert_eq!(run(code)?, 80140);
+    Ok(())
+}
+
+#[test]
+fn test_zip_stops_at_shortest() -> Result<()> {
+    let code = r#"
+dot = 0
+steps = 0
+for a, b in zip([1, 2, 3, 4], [5, 6, 7]):
+    dot += a * b
+    steps += 1
+for a, b, c in zip((1, 2), [3, 4], [5, 6, 7]):
+    dot += a + b + c
+OUTPUT = dot * 10 + steps
+"#;
+    assert_eq!(run(code)?, 593);
+    Ok(())
+}
+
+#[test]
+fn test_tuple_targets() -> Result<()> {
+    let code = r#"
+total = 0
+for a, b in [(1, 2), (3, 4)]:
+    total += a * b
+for pair in zip([1, 2], [3, 4]):
+    total += pair[0] + pair[1]
+for i, (a, b) in enumerate([(5, 6)]):
+    total += i + a * b
+OUTPUT = total
+"#;
+    assert_eq!(run(code)?, 54);
+    Ok(())
+}
+
+#[test]
+fn test_iterable_evaluated_once() -> Result<()> {
+    let code = r#"
+xs = [1, 2]
+n = 0
+for x in xs:
+    xs.append(x)
+    n += 1
+OUTPUT = n * 10 + len(xs)
+"#;
+    // the length is fixed when the loop starts
+    assert_eq!(run(code)?, 24);
+    Ok(())
+}
+
+#[test]
+fn test_dict_iteration_traps() {
+    let err = run("d = {1: 2}\nt = 0\nfor k in d:\n    t += k\nOUTPUT = t").expect_err("expected a trap");
+    assert_eq!(err.downcast_ref::<Trap>(), Some(&Trap::UnreachableCodeReached), "{:?}", err);
+}
+
+#[test]
+fn test_target_arity_errors() {
+    let err = PythonCompiler::new().compile("for a, b, c in zip([1], [2]):\n    pass").unwrap_err();
+    assert!(err.to_string().contains("Cannot unpack 2 values into 3 loop targets"), "{}", err);
+
+    let err = PythonCompiler::new().compile("for i in enumerate():\n    pass").unwrap_err();
+    assert!(err.to_string().contains("enumerate() takes 1 to 2 arguments"), "{}", err);
+
+    let err = PythonCompiler::new().compile("for a, b in range(3):\n    pass").unwrap_err();
+    assert!(err.to_string().contains("must be simple variable"), "{}", err);
+}
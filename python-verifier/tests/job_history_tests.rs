@@ -0,0 +1,84 @@
+use python_verifier::queue::JobQueue;
+use python_verifier::websocket::{verify_history, JobUpdate, GENESIS_HASH};
+use serde_json::json;
+
+fn open_queue(name: &str) -> JobQueue {
+    let path = std::env::temp_dir().join(format!("certus-history-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    JobQueue::new(path.to_str().unwrap()).unwrap()
+}
+
+fn update(job_id: &str, status: &str, timestamp: u64) -> JobUpdate {
+    JobUpdate::new(job_id, status, timestamp, json!({ "hash": "0xab", "output": { "b": 2, "a": 1 } }), Some("acme".to_string()))
+}
+
+fn recorded(queue: &JobQueue, job_id: &str) -> Vec<JobUpdate> {
+    for (i, status) in ["claimed", "running", "completed"].iter().enumerate() {
+        queue.record_update(update(job_id, status, 100 + i as u64)).unwrap();
+    }
+    queue.history_of(job_id).unwrap()
+}
+
+#[test]
+fn test_updates_are_chained_per_job() {
+    let queue = open_queue("chain");
+    let history = recorded(&queue, "job-1");
+    queue.record_update(update("job-2", "claimed", 50)).unwrap();
+
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[0].prev_hash, GENESIS_HASH);
+    assert_eq!(history[1].prev_hash, history[0].hash);
+    assert_eq!(history[2].prev_hash, history[1].hash);
+    verify_history("job-1", &history).unwrap();
+
+    // another job's history starts its own chain
+    let other = queue.history_of("job-2").unwrap();
+    assert_eq!(other.len(), 1);
+    assert_eq!(other[0].prev_hash, GENESIS_HASH);
+    assert!(queue.history_of("job-3").unwrap().is_empty());
+}
+
+#[test]
+fn test_history_survives_the_api_round_trip() {
+    let queue = open_queue("roundtrip");
+    let history = recorded(&queue, "job-1");
+    let body = serde_json::to_string(&json!({ "job_id": "job-1", "updates": history })).unwrap();
+
+    let parsed: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let updates: Vec<JobUpdate> = serde_json::from_value(parsed["updates"].clone()).unwrap();
+    verify_history("job-1", &updates).unwrap();
+}
+
+#[test]
+fn test_tampering_is_detected() {
+    let queue = open_queue("tamper");
+    let history = recorded(&queue, "job-1");
+
+    let mut edited = history.clone();
+    edited[1].data = json!({ "hash": "0xcd" });
+    assert!(verify_history("job-1", &edited).is_err());
+
+    let mut dropped = history.clone();
+    dropped.remove(1);
+    assert!(verify_history("job-1", &dropped).is_err());
+
+    let mut reordered = history.clone();
+    reordered.swap(1, 2);
+    assert!(verify_history("job-1", &reordered).is_err());
+
+    // rehashing an edit doesn't help: the next update still names the old hash
+    let mut rehashed = history.clone();
+    rehashed[1].status = "failed".to_string();
+    rehashed[1].hash = rehashed[1].chain_hash().unwrap();
+    assert!(verify_history("job-1", &rehashed).is_err());
+
+    assert!(verify_history("job-2", &history).is_err());
+}
+
+#[test]
+fn test_backdated_update_is_rejected() {
+    let first = update("job-1", "claimed", 200);
+    let mut second = update("job-1", "completed", 100);
+    second.link(Some(&first)).unwrap();
+    assert!(verify_history("job-1", &[first, second]).is_err());
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use python_verifier::python_compiler::PythonCompiler;
+use sha3::{Digest, Keccak256, Sha3_256};
+use wasmtime::*;
+
+// Run main and return (OUTPUT, linear memory snapshot)
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<(i32, Vec<u8>)> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok((result, memory.data(&store).to_vec()))
+}
+
+// Read a string (type tag 3) from memory
+fn read_string(data: &[u8], ptr: i32) -> Result<String> {
+    let ptr = ptr as usize;
+    let type_tag = i32::from_le_bytes(data[ptr..ptr + 4].try_into()?);
+    if type_tag != 3 {
+        anyhow::bail!("Expected string type tag (3), got {}", type_tag);
+    }
+    let length = i32::from_le_bytes(data[ptr + 4..ptr + 8].try_into()?) as usize;
+    Ok(String::from_utf8(data[ptr + 8..ptr + 8 + length].to_vec())?)
+}
+
+fn run_hex(code: &str) -> Result<String> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    let (result, memory) = execute_wasm(&wasm)?;
+    read_string(&memory, result)
+}
+
+#[test]
+fn test_keccak256_empty() -> Result<()> {
+    let hex = run_hex("OUTPUT = keccak256(b\"\").hex()\n")?;
+    assert_eq!(hex, "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+    Ok(())
+}
+
+#[test]
+fn test_keccak256_abc() -> Result<()> {
+    let hex = run_hex("OUTPUT = keccak256(\"abc\".encode()).hex()\n")?;
+    assert_eq!(hex, "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45");
+    Ok(())
+}
+
+#[test]
+fn test_keccak256_transfer_selector() -> Result<()> {
+    // First 4 bytes are the ERC-20 transfer selector
+    let hex = run_hex("OUTPUT = keccak256(b\"transfer(address,uint256)\").hex()\n")?;
+    assert!(hex.starts_with("a9059cbb"));
+    Ok(())
+}
+
+#[test]
+fn test_keccak256_block_boundaries() -> Result<()> {
+    // 135 bytes: domain and final pad bits share a byte; 136 and 300 span extra blocks
+    for len in [135usize, 136, 137, 300] {
+        let msg = "x".repeat(len);
+        let hex = run_hex(&format!("OUTPUT = keccak256(\"{}\".encode()).hex()\n", msg))?;
+        assert_eq!(hex, hex::encode(Keccak256::digest(msg.as_bytes())), "len {}", len);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_sha3_256_vectors() -> Result<()> {
+    let hex = run_hex(r#"
+import hashlib
+OUTPUT = hashlib.sha3_256(b"abc").hexdigest()
+"#)?;
+    assert_eq!(hex, "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532");
+
+    let hex = run_hex(r#"
+import hashlib
+OUTPUT = hashlib.sha3_256().hexdigest()
+"#)?;
+    assert_eq!(hex, "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a");
+    Ok(())
+}
+
+#[test]
+fn test_sha3_256_update() -> Result<()> {
+    let hex = run_hex(r#"
+import hashlib
+h = hashlib.sha3_256(b"cer")
+h.update(b"tus")
+OUTPUT = h.hexdigest()
+"#)?;
+    assert_eq!(hex, hex::encode(Sha3_256::digest(b"certus")));
+    Ok(())
+}
+
+#[test]
+fn test_keccak256_returns_bytes() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile("OUTPUT = len(keccak256(b\"certus\"))\n")?;
+    let (result, _) = execute_wasm(&wasm)?;
+    assert_eq!(result, 32);
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use python_verifier::compiler::abi::{ExportedLimits, ABI_VERSION, ABI_VERSION_EXPORT, GAS_LIMIT_EXPORT, HEAP_LIMIT_EXPORT};
+use python_verifier::compiler::fuel::FUEL_LIMIT;
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasm_encoder::{ConstExpr, ExportKind, ExportSection, GlobalSection, GlobalType, Module, ValType};
+use wasmtime::{Engine, Instance, Memory, MemoryType, Mutability, Store};
+
+const HEAP_LIMIT: u32 = 0x400000;
+
+#[test]
+fn test_compiled_module_exports_its_limits() -> Result<()> {
+    // with and without prints and module globals, which shift the global indices
+    for code in ["OUTPUT = 7", "def f():\n    return 2\nx = f()\nprint(x)\nOUTPUT = x"] {
+        let wasm = PythonCompiler::new().compile(code)?;
+        let limits = ExportedLimits::read(&wasm)?.expect("compiled modules export their limits");
+        assert_eq!(limits, ExportedLimits { heap_limit: HEAP_LIMIT, gas_limit: FUEL_LIMIT as u32, abi_version: ABI_VERSION });
+
+        // the same values an instance sees
+        let engine = Engine::default();
+        let mut store = Store::new(&engine, ());
+        let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+        let module = wasmtime::Module::new(&engine, &wasm)?;
+        let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+        for (name, expected) in [(HEAP_LIMIT_EXPORT, HEAP_LIMIT), (GAS_LIMIT_EXPORT, FUEL_LIMIT as u32), (ABI_VERSION_EXPORT, ABI_VERSION)] {
+            let global = instance.get_global(&mut store, name).expect(name);
+            assert_eq!(global.ty(&store).mutability(), Mutability::Const);
+            assert_eq!(global.get(&mut store).unwrap_i32() as u32, expected);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_check_rejects_mismatched_jobs() {
+    let limits = ExportedLimits { heap_limit: HEAP_LIMIT, gas_limit: FUEL_LIMIT as u32, abi_version: ABI_VERSION };
+    assert!(limits.check(1_000_000, 256).is_ok());
+    assert!(limits.check(FUEL_LIMIT as u64, 64).is_ok());
+
+    let err = limits.check(FUEL_LIMIT as u64 + 1, 256).unwrap_err();
+    assert!(err.to_string().contains("fuel"), "{}", err);
+    let err = limits.check(1_000_000, 32).unwrap_err();
+    assert!(err.to_string().contains("memory pages"), "{}", err);
+    let err = ExportedLimits { abi_version: ABI_VERSION + 1, ..limits }.check(1_000_000, 256).unwrap_err();
+    assert!(err.to_string().contains("ABI version"), "{}", err);
+}
+
+// a module defining one i32 global per (export, mutable) pair
+fn with_globals(globals: &[(&str, bool)]) -> Vec<u8> {
+    let mut section = GlobalSection::new();
+    let mut exports = ExportSection::new();
+    for (index, &(name, mutable)) in globals.iter().enumerate() {
+        section.global(GlobalType { val_type: ValType::I32, mutable }, &ConstExpr::i32_const(1));
+        exports.export(name, ExportKind::Global, index as u32);
+    }
+    let mut module = Module::new();
+    module.section(&section);
+    module.section(&exports);
+    module.finish()
+}
+
+#[test]
+fn test_modules_without_exports_are_unconstrained() -> Result<()> {
+    assert_eq!(ExportedLimits::read(&with_globals(&[("counter", true)]))?, None);
+    Ok(())
+}
+
+#[test]
+fn test_partial_or_mutable_exports_are_rejected() {
+    let partial = with_globals(&[(HEAP_LIMIT_EXPORT, false)]);
+    assert!(ExportedLimits::read(&partial).is_err());
+
+    let mutable = with_globals(&[(HEAP_LIMIT_EXPORT, true), (GAS_LIMIT_EXPORT, false), (ABI_VERSION_EXPORT, false)]);
+    assert!(ExportedLimits::read(&mutable).is_err());
+
+    let constant = with_globals(&[(HEAP_LIMIT_EXPORT, false), (GAS_LIMIT_EXPORT, false), (ABI_VERSION_EXPORT, false)]);
+    assert!(ExportedLimits::read(&constant).is_ok());
+}
@@ -0,0 +1,115 @@
+use python_verifier::python_compiler::PythonCompiler;
+use python_verifier::compiler::ModuleLimits;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+fn compile(code: &str) -> Result<Vec<u8>> {
+    PythonCompiler::new().compile(code)
+}
+
+fn declared(wasm: &[u8]) -> Result<ModuleLimits> {
+    Ok(ModuleLimits::read(wasm)?.expect("limits section"))
+}
+
+#[test]
+fn test_straight_line_module() -> Result<()> {
+    let wasm = compile("x = 2\nOUTPUT = x * 21")?;
+    assert_eq!(execute_wasm(&wasm)?, 42);
+
+    let limits = declared(&wasm)?;
+    // main and the fuel charge it calls
+    assert_eq!(limits.max_call_depth, 2);
+    assert!(!limits.recursive);
+    assert!(limits.max_operand_stack >= 2);
+    // 4MB heap
+    assert_eq!(limits.memory_pages, 64);
+    Ok(())
+}
+
+#[test]
+fn test_call_chain_depth() -> Result<()> {
+    let code = r#"
+# certus: noinline
+def inner(x):
+    return x * 3
+
+# certus: noinline
+def outer(x):
+    return inner(x) - 1
+
+# certus: noinline
+def side(x):
+    return x
+
+OUTPUT = outer(side(5))
+"#;
+    let wasm = compile(code)?;
+    assert_eq!(execute_wasm(&wasm)?, 14);
+
+    let limits = declared(&wasm)?;
+    // main -> outer -> inner -> the fuel charge; side never nests
+    assert_eq!(limits.max_call_depth, 4);
+    assert!(!limits.recursive);
+
+    let single = declared(&compile("x = 2\nOUTPUT = x * 21")?)?;
+    assert!(limits.max_locals > 2 * single.max_locals);
+    Ok(())
+}
+
+#[test]
+fn test_recursion_bounded_by_depth_limit() -> Result<()> {
+    let code = r#"
+def down(n):
+    if n == 0:
+        return 0
+    return down(n - 1) - -1
+
+OUTPUT = down(5)
+"#;
+    let default = declared(&compile(code)?)?;
+    assert!(default.recursive);
+    assert_eq!(default.max_call_depth, python_verifier::compiler::DEFAULT_MAX_CALL_DEPTH + 1);
+
+    let shallow = declared(&PythonCompiler::new().with_max_call_depth(10).compile(code)?)?;
+    assert_eq!(shallow.max_call_depth, 11);
+    assert!(shallow.max_operand_stack < default.max_operand_stack);
+    assert!(shallow.stack_bytes() < default.stack_bytes());
+    Ok(())
+}
+
+#[test]
+fn test_encoding_round_trip() -> Result<()> {
+    let limits = declared(&compile("OUTPUT = 1")?)?;
+    assert_eq!(ModuleLimits::decode(&limits.encode())?, limits);
+    assert!(ModuleLimits::decode(&[0; 8]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_host_check() -> Result<()> {
+    let limits = declared(&compile("OUTPUT = 1")?)?;
+    assert!(limits.check(256, 1024 * 1024).is_ok());
+    assert!(limits.check(limits.memory_pages - 1, 1024 * 1024).is_err());
+    assert!(limits.check(256, limits.stack_bytes() - 1).is_err());
+    Ok(())
+}
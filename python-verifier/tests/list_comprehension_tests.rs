@@ -0,0 +1,200 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+#[test]
+fn test_list_comp_basic() -> Result<()> {
+    let code = r#"
+xs = [x * 2 for x in range(5)]
+OUTPUT = len(xs) * 100 + xs[4]
+"#;
+    assert_eq!(run(code)?, 508);
+    Ok(())
+}
+
+#[test]
+fn test_list_comp_with_filter() -> Result<()> {
+    let code = r#"
+n = 10
+xs = [x * 2 for x in range(n) if x % 2 == 0]
+OUTPUT = len(xs) * 100 + sum(xs)
+"#;
+    // 0, 4, 8, 12, 16
+    assert_eq!(run(code)?, 540);
+    Ok(())
+}
+
+#[test]
+fn test_list_comp_multiple_filters() -> Result<()> {
+    let code = r#"
+xs = [x for x in range(30) if x % 2 == 0 if x % 3 == 0]
+OUTPUT = sum(xs)
+"#;
+    // 0 + 6 + 12 + 18 + 24
+    assert_eq!(run(code)?, 60);
+    Ok(())
+}
+
+#[test]
+fn test_list_comp_nested_generators() -> Result<()> {
+    let code = r#"
+pairs = [i * 10 + j for i in range(1, 4) for j in range(i)]
+OUTPUT = len(pairs) * 1000 + pairs[5]
+"#;
+    // 10, 20, 21, 30, 31, 32
+    assert_eq!(run(code)?, 6032);
+    Ok(())
+}
+
+#[test]
+fn test_list_comp_empty() -> Result<()> {
+    let code = r#"
+xs = [x for x in range(10) if x > 100]
+xs.append(7)
+OUTPUT = len(xs) * 10 + xs[0]
+"#;
+    assert_eq!(run(code)?, 17);
+    Ok(())
+}
+
+#[test]
+fn test_list_comp_grows_past_capacity() -> Result<()> {
+    let code = r#"
+xs = [x for x in range(200)]
+OUTPUT = xs[199] + len(xs)
+"#;
+    assert_eq!(run(code)?, 399);
+    Ok(())
+}
+
+#[test]
+fn test_list_comp_target_does_not_leak() -> Result<()> {
+    let code = r#"
+x = 42
+xs = [x for x in range(3)]
+OUTPUT = x + sum(xs)
+"#;
+    assert_eq!(run(code)?, 45);
+    Ok(())
+}
+
+#[test]
+fn test_list_comp_uses_enclosing_locals() -> Result<()> {
+    let code = r#"
+k = 3
+xs = [x * k for x in range(4)]
+OUTPUT = sum(xs)
+"#;
+    assert_eq!(run(code)?, 18);
+    Ok(())
+}
+
+#[test]
+fn test_list_comp_inside_expression() -> Result<()> {
+    let code = r#"
+a = 5
+OUTPUT = a * 100 + len([i for i in range(a)]) + sum([i for i in range(3)])
+"#;
+    assert_eq!(run(code)?, 508);
+    Ok(())
+}
+
+#[test]
+fn test_list_comp_in_function_and_loop() -> Result<()> {
+    let code = r#"
+def squares(n):
+    return [i * i for i in range(n)]
+
+total = 0
+for r in range(4):
+    total = total + sum(squares(r))
+OUTPUT = total
+"#;
+    // 0 + 0 + 1 + 5
+    assert_eq!(run(code)?, 6);
+    Ok(())
+}
+
+// Fuel consumed by a comprehension over range(n)
+fn comprehension_fuel(n: i32) -> Result<u64> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(&format!("xs = [x for x in range({})]\nOUTPUT = 0\n", n))?;
+
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(10_000_000)?;
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())?;
+
+    Ok(10_000_000 - store.get_fuel()?)
+}
+
+#[test]
+fn test_list_comp_cost_scales_with_iterations() -> Result<()> {
+    assert!(comprehension_fuel(100)? > comprehension_fuel(10)? * 5);
+    Ok(())
+}
+
+#[test]
+fn test_list_comp_over_list() -> Result<()> {
+    let code = r#"
+ys = [3, -4, 0, 12]
+xs = [y * 2 for y in ys if y != 0]
+OUTPUT = len(xs) * 100 + sum(xs)
+"#;
+    assert_eq!(run(code)?, 322);
+    Ok(())
+}
+
+#[test]
+fn test_list_comp_over_nested_lists() -> Result<()> {
+    let code = r#"
+y = 9
+rows = [[1, 2], [3], [4, 5, 6]]
+flat = [y * 10 + len(row) for row in rows for y in row]
+OUTPUT = sum(flat) + y
+"#;
+    // 12 + 22 + 31 + 43 + 53 + 63, and y is not rebound
+    assert_eq!(run(code)?, 233);
+    Ok(())
+}
+
+#[test]
+fn test_dict_and_set_comp_over_list() -> Result<()> {
+    let code = r#"
+ys = [4, 9, 16]
+d = {y: y // 2 for y in ys}
+s = {y % 5 for y in ys}
+OUTPUT = d[9] * 100 + len(d) * 10 + len(s)
+"#;
+    assert_eq!(run(code)?, 432);
+    Ok(())
+}
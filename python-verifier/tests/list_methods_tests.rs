@@ -0,0 +1,212 @@
+use python_verifier::compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+#[test]
+fn test_list_len() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = [10, 20, 30]
+OUTPUT = len(x)
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 3);
+    Ok(())
+}
+
+#[test]
+fn test_len_of_empty_list_and_string() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = []
+s = "hello"
+OUTPUT = len(x) * 10 + len(s)
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 5);
+    Ok(())
+}
+
+#[test]
+fn test_append_builds_list() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = []
+for i in range(100):
+    x.append(i * 2)
+OUTPUT = len(x) + x[99]
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 298);
+    Ok(())
+}
+
+#[test]
+fn test_append_to_literal_keeps_elements() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = [7, 8]
+x.append(9)
+x.append(10)
+x.append(11)
+OUTPUT = x[0] + x[1] + x[2] + x[3] + x[4]
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 45);
+    Ok(())
+}
+
+#[test]
+fn test_append_visible_through_alias() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = [1]
+y = x
+for i in range(10):
+    x.append(i)
+y[10] = 50
+OUTPUT = len(y) * 100 + x[10]
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 1150);
+    Ok(())
+}
+
+#[test]
+fn test_pop_last() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = [1, 2, 3]
+a = x.pop()
+b = x.pop()
+OUTPUT = a * 100 + b * 10 + len(x)
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 321);
+    Ok(())
+}
+
+#[test]
+fn test_pop_index_shifts_tail() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = [5, 6, 7, 8]
+a = x.pop(1)
+OUTPUT = a * 1000 + x[0] * 100 + x[1] * 10 + x[2]
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 6578);
+    Ok(())
+}
+
+#[test]
+fn test_pop_empty_list_traps() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = [1]
+x.pop()
+OUTPUT = x.pop()
+"#;
+    let wasm = compiler.compile(code)?;
+    assert!(execute_wasm(&wasm).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_insert_front_middle_end() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = [2, 4]
+x.insert(0, 1)
+x.insert(2, 3)
+x.insert(99, 5)
+OUTPUT = x[0] * 10000 + x[1] * 1000 + x[2] * 100 + x[3] * 10 + x[4]
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 12345);
+    Ok(())
+}
+
+#[test]
+fn test_insert_negative_index() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = [1, 3]
+x.insert(-1, 2)
+x.insert(-10, 0)
+OUTPUT = x[0] * 1000 + x[1] * 100 + x[2] * 10 + x[3]
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 123);
+    Ok(())
+}
+
+#[test]
+fn test_stack_usage_in_function() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+def drain(n):
+    stack = []
+    for i in range(n):
+        stack.append(i)
+    total = 0
+    while len(stack) > 0:
+        total = total + stack.pop()
+    return total
+
+OUTPUT = drain(20)
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 190);
+    Ok(())
+}
+
+#[test]
+fn test_append_on_string_traps() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+s = "abc"
+s.append(1)
+OUTPUT = 1
+"#;
+    let wasm = compiler.compile(code)?;
+    assert!(execute_wasm(&wasm).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_list_method_arity_rejected() {
+    let mut compiler = PythonCompiler::new();
+    assert!(compiler.compile("x = []\nx.append(1, 2)\nOUTPUT = 0\n").is_err());
+    assert!(compiler.compile("x = [1]\nx.insert(0)\nOUTPUT = 0\n").is_err());
+    assert!(compiler.compile("x = [1]\nOUTPUT = len(x, x)\n").is_err());
+}
@@ -220,3 +220,18 @@ OUTPUT = x[7]
     assert_eq!(result, 8);
     Ok(())
 }
+
+#[test]
+fn test_list_concatenation() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x = [1, 2]
+y = x + [3]
+y[0] = 9
+OUTPUT = len(y) * 100 + x[0] * 10 + y[2]
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 313); // a new list: x keeps its first element
+    Ok(())
+}
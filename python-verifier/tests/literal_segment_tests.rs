@@ -0,0 +1,93 @@
+// String and bytes literals are copied from passive data segments with memory.init, so the
+// code for a literal is the same size whatever its length.
+
+use python_verifier::compiler::PythonCompiler;
+use anyhow::Result;
+use wasmparser::{Parser, Payload};
+use wasmtime::*;
+
+// OUTPUT, and the string it points at when it is one
+fn run(code: &str) -> Result<(i32, Option<String>)> {
+    let wasm = PythonCompiler::new().compile(code)?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let output = instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())?;
+
+    // String layout: [type:i32][length:i32][bytes...]
+    let data = memory.data(&store);
+    let ptr = output as usize;
+    let string = if ptr >= 1024 && ptr + 8 <= data.len() && data[ptr..ptr + 4] == 3i32.to_le_bytes() {
+        let len = u32::from_le_bytes(data[ptr + 4..ptr + 8].try_into()?) as usize;
+        Some(String::from_utf8(data[ptr + 8..ptr + 8 + len].to_vec())?)
+    } else {
+        None
+    };
+    Ok((output, string))
+}
+
+fn segment_sizes(wasm: &[u8]) -> Result<Vec<usize>> {
+    let mut sizes = vec![];
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::DataSection(reader) = payload? {
+            for data in reader {
+                let data = data?;
+                assert!(matches!(data.kind, wasmparser::DataKind::Passive));
+                sizes.push(data.data.len());
+            }
+        }
+    }
+    Ok(sizes)
+}
+
+#[test]
+fn test_literal_contents_survive() -> Result<()> {
+    let (_, string) = run("OUTPUT = \"héllo, wörld\"")?;
+    assert_eq!(string.as_deref(), Some("héllo, wörld"));
+
+    let (len, _) = run("b = b\"\\x00\\xff\\x10\"\nOUTPUT = b[0] + b[1] + b[2] + len(b)")?;
+    assert_eq!(len, 0xff + 0x10 + 3);
+    Ok(())
+}
+
+#[test]
+fn test_code_size_does_not_grow_with_literal_length() -> Result<()> {
+    let compile = |len: usize| PythonCompiler::new().compile(&format!("s = \"{}\"\nOUTPUT = len(s)", "a".repeat(len)));
+    let short = compile(10)?;
+    let long = compile(4000)?;
+
+    // the extra bytes are the segment itself, not code
+    assert!(long.len() - short.len() < 4000 + 16, "{} vs {} bytes", long.len(), short.len());
+    assert_eq!(run(&format!("s = \"{}\"\nOUTPUT = len(s)", "a".repeat(4000)))?.0, 4000);
+    Ok(())
+}
+
+#[test]
+fn test_identical_literals_share_a_segment() -> Result<()> {
+    let code = "a = \"abc\"\nb = \"abc\"\nc = b\"abc\"\nOUTPUT = len(a) + len(b) + len(c)";
+    let wasm = PythonCompiler::new().compile(code)?;
+    // sha256's slot stays empty without hashing
+    assert_eq!(segment_sizes(&wasm)?, vec![0, 3]);
+    assert_eq!(run(code)?.0, 9);
+    Ok(())
+}
+
+#[test]
+fn test_empty_literal_needs_no_segment() -> Result<()> {
+    let code = "s = \"\"\nOUTPUT = len(s)";
+    assert!(segment_sizes(&PythonCompiler::new().compile(code)?)?.is_empty());
+    assert_eq!(run(code)?.0, 0);
+    Ok(())
+}
+
+#[test]
+fn test_literals_alongside_sha256_constants() -> Result<()> {
+    let code = "import hashlib\nh = hashlib.sha256(\"abc\".encode()).digest()\nOUTPUT = h[0]";
+    let wasm = PythonCompiler::new().compile(code)?;
+    assert_eq!(segment_sizes(&wasm)?, vec![256, 3]);
+    // sha256("abc") starts with 0xba
+    assert_eq!(run(code)?.0, 0xba);
+    Ok(())
+}
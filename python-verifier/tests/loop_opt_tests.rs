@@ -0,0 +1,222 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+fn fuel_used(code: &str) -> Result<u64> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(10_000_000)?;
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())?;
+
+    Ok(10_000_000 - store.get_fuel()?)
+}
+
+// Fuel per iteration, from the difference between two loop lengths
+fn fuel_per_iteration(template: &str, short: usize, long: usize) -> Result<u64> {
+    let a = fuel_used(&template.replace("{N}", &short.to_string()))?;
+    let b = fuel_used(&template.replace("{N}", &long.to_string()))?;
+    Ok((b - a) / (long - short) as u64)
+}
+
+const LEN_IN_CONDITION: &str = r#"
+xs = [1, 2, 3]
+i = 0
+total = 0
+while i < len(xs) * {N}:
+    total = total - 1
+    i = i - -1
+OUTPUT = total
+"#;
+
+const LEN_HOISTED_BY_HAND: &str = r#"
+xs = [1, 2, 3]
+n = len(xs) * {N}
+i = 0
+total = 0
+while i < n:
+    total = total - 1
+    i = i - -1
+OUTPUT = total
+"#;
+
+#[test]
+fn test_len_in_condition_is_hoisted() -> Result<()> {
+    assert_eq!(run(&LEN_IN_CONDITION.replace("{N}", "10"))?, -30);
+    let auto = fuel_per_iteration(LEN_IN_CONDITION, 10, 110)?;
+    let manual = fuel_per_iteration(LEN_HOISTED_BY_HAND, 10, 110)?;
+    assert!(auto <= manual, "auto {} > manual {}", auto, manual);
+    Ok(())
+}
+
+#[test]
+fn test_invariant_product_in_body() -> Result<()> {
+    let code = r#"
+a = 7
+b = 6
+total = 0
+for i in range({N}):
+    total = total - a * b * 3
+OUTPUT = total
+"#;
+    assert_eq!(run(&code.replace("{N}", "10"))?, -1260);
+    let by_hand = code.replace("a * b * 3", "c").replace("total = 0", "total = 0\nc = a * b * 3");
+    let auto = fuel_per_iteration(code, 10, 110)?;
+    let manual = fuel_per_iteration(&by_hand, 10, 110)?;
+    assert!(auto <= manual, "auto {} > manual {}", auto, manual);
+    Ok(())
+}
+
+#[test]
+fn test_power_of_two_arithmetic_matches_python() -> Result<()> {
+    // Python floors toward negative infinity
+    let cases = [
+        ("-7", "// 4", -2),
+        ("-8", "// 4", -2),
+        ("7", "// 4", 1),
+        ("-7", "% 4", 1),
+        ("-8", "% 4", 0),
+        ("7", "% 8", 7),
+        ("-1", "% 2", 1),
+        ("-3", "* 8", -24),
+        ("-5", "* 16", -80),
+    ];
+    for (left, rest, expected) in cases {
+        let code = format!("x = {}\nOUTPUT = x {}", left, rest);
+        assert_eq!(run(&code)?, expected, "{} {}", left, rest);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_power_of_two_is_cheaper_than_general_division() -> Result<()> {
+    let template = r#"
+total = 0
+for i in range({N}):
+    total = total - (i - 50) % DIVISOR - (i - 50) // DIVISOR
+OUTPUT = total
+"#;
+    let expected = |d: i32| -> i32 { (0..10).map(|i: i32| -((i - 50).rem_euclid(d) + (i - 50).div_euclid(d))).sum() };
+    assert_eq!(run(&template.replace("DIVISOR", "8").replace("{N}", "10"))?, expected(8));
+    let pow2 = fuel_per_iteration(&template.replace("DIVISOR", "8"), 10, 110)?;
+    let general = fuel_per_iteration(&template.replace("DIVISOR", "7"), 10, 110)?;
+    assert!(pow2 < general, "{} >= {}", pow2, general);
+    Ok(())
+}
+
+#[test]
+fn test_induction_variable() -> Result<()> {
+    let code = r#"
+total = 0
+for i in range(3, {N}, 2):
+    total = total - i * 12 - i * 12
+OUTPUT = total
+"#;
+    assert_eq!(run(&code.replace("{N}", "19"))?, -24 * (3 + 5 + 7 + 9 + 11 + 13 + 15 + 17));
+    let reduced = fuel_per_iteration(code, 21, 221)?;
+    let single = fuel_per_iteration(&code.replace(" - i * 12 - i * 12", " - i * 12"), 21, 221)?;
+    // the second product is a local read, not another multiply
+    assert!(reduced <= single + 2, "{} > {} + 2", reduced, single);
+
+    let start = r#"
+s = 5
+total = 0
+for i in range(s - 2, 40, 3):
+    total = total - i * 10 - i * 10
+OUTPUT = total
+"#;
+    let expected: i32 = (3..40).step_by(3).map(|i| -20 * i).sum();
+    assert_eq!(run(start)?, expected);
+
+    let descending = r#"
+total = 0
+for i in range(10, -10, -3):
+    total = total - i * 5 - i * 5
+OUTPUT = total
+"#;
+    let expected: i32 = (-9..=10).rev().step_by(3).map(|i| -10 * i).sum();
+    assert_eq!(run(descending)?, expected);
+    Ok(())
+}
+
+#[test]
+fn test_loop_writes_block_hoisting() -> Result<()> {
+    // len() changes as the loop appends
+    let append = r#"
+xs = [1]
+i = 0
+while i < len(xs):
+    if len(xs) < 5:
+        xs.append(i)
+    i = i - -1
+OUTPUT = i
+"#;
+    assert_eq!(run(append)?, 5);
+
+    // b is reassigned in the body, so a * b isn't invariant
+    let reassigned = r#"
+a = 3
+b = 1
+total = 0
+for i in range(4):
+    total = total - a * b
+    b = b - -1
+OUTPUT = total
+"#;
+    assert_eq!(run(reassigned)?, -30);
+    Ok(())
+}
+
+#[test]
+fn test_trapping_expression_not_hoisted() -> Result<()> {
+    let empty = r#"
+z = 0
+y = 1
+for i in range(0):
+    y = 10 // z
+OUTPUT = y
+"#;
+    assert_eq!(run(empty)?, 1);
+
+    let guarded = r#"
+z = 0
+total = 0
+for i in range(5):
+    if z != 0:
+        total = total - 10 // z
+OUTPUT = total
+"#;
+    assert_eq!(run(guarded)?, 0);
+    Ok(())
+}
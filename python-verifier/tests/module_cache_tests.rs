@@ -0,0 +1,98 @@
+use python_verifier::artifacts::{ArtifactClass, ArtifactQuotas, ArtifactStore};
+use python_verifier::module_cache::{ModuleCache, ModuleCacheStats};
+use python_verifier::python_compiler::PythonCompiler;
+use python_verifier::PythonExecutor;
+use std::sync::Arc;
+use wasm_encoder::{
+    CodeSection, ExportKind, ExportSection, Function, FunctionSection, ImportSection, Instruction, MemoryType, Module,
+    TypeSection, ValType,
+};
+use wasmtime::Engine;
+
+// python_main(input_ptr, input_len) returns the input's record, so the output is the input
+fn echo_module() -> Vec<u8> {
+    let mut module = Module::new();
+    let mut types = TypeSection::new();
+    types.function([ValType::I32, ValType::I32], [ValType::I32]);
+    module.section(&types);
+    let mut imports = ImportSection::new();
+    imports.import("env", "memory", MemoryType { minimum: 1, maximum: None, memory64: false, shared: false });
+    module.section(&imports);
+    let mut funcs = FunctionSection::new();
+    funcs.function(0);
+    module.section(&funcs);
+    let mut exports = ExportSection::new();
+    exports.export("python_main", ExportKind::Func, 0);
+    exports.export("memory", ExportKind::Memory, 0);
+    module.section(&exports);
+    let mut code = CodeSection::new();
+    let mut main = Function::new([]);
+    main.instruction(&Instruction::LocalGet(0));
+    main.instruction(&Instruction::I32Const(4));
+    main.instruction(&Instruction::I32Sub);
+    main.instruction(&Instruction::End);
+    code.function(&main);
+    module.section(&code);
+    module.finish()
+}
+
+fn python_module(output: i32) -> Vec<u8> {
+    PythonCompiler::new().compile(&format!("OUTPUT = {}", output)).unwrap()
+}
+
+fn store(name: &str) -> Arc<ArtifactStore> {
+    let path = std::env::temp_dir().join(format!("certus-module-cache-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    Arc::new(ArtifactStore::open(path, ArtifactQuotas { min_free_bytes: 0, ..Default::default() }).unwrap())
+}
+
+#[test]
+fn test_rerun_reuses_compiled_module() {
+    let wasm = echo_module();
+    let mut executor = PythonExecutor::new().unwrap();
+    let first = executor.execute_wasm(&wasm, "{}", 1_000_000).unwrap();
+    let second = executor.execute_wasm(&wasm, "{}", 1_000_000).unwrap();
+    assert_eq!(first.output_hash, second.output_hash);
+    assert_eq!(first.fuel_consumed, second.fuel_consumed);
+    assert_eq!(executor.module_cache_stats(), ModuleCacheStats { memory_hits: 1, disk_hits: 0, compiled: 1 });
+}
+
+#[test]
+fn test_serialized_modules_survive_restart() {
+    let wasm = echo_module();
+    let store = store("restart");
+    let mut executor = PythonExecutor::new().unwrap().with_module_store(store.clone());
+    let first = executor.execute_wasm(&wasm, "{}", 1_000_000).unwrap();
+    assert_eq!(store.stats().unwrap().classes[ArtifactClass::AotCache.as_str()].files, 1);
+
+    let mut restarted = PythonExecutor::new().unwrap().with_module_store(store);
+    let second = restarted.execute_wasm(&wasm, "{}", 1_000_000).unwrap();
+    assert_eq!(first.output_hash, second.output_hash);
+    assert_eq!(restarted.module_cache_stats(), ModuleCacheStats { memory_hits: 0, disk_hits: 1, compiled: 0 });
+}
+
+#[test]
+fn test_unloadable_entry_is_recompiled() {
+    let wasm = python_module(7);
+    let store = store("corrupt");
+    let key = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(&wasm));
+    store.put(ArtifactClass::AotCache, &key, b"not a module").unwrap();
+
+    let cache = ModuleCache::new(4).with_store(store.clone());
+    cache.get(&Engine::default(), &wasm).unwrap();
+    assert_eq!(cache.stats().compiled, 1);
+    assert_ne!(store.get(ArtifactClass::AotCache, &key).unwrap().unwrap(), b"not a module");
+}
+
+#[test]
+fn test_memory_tier_drops_least_recently_used() {
+    let engine = Engine::default();
+    let modules: Vec<Vec<u8>> = (0..3).map(python_module).collect();
+    let cache = ModuleCache::new(2);
+    for wasm in &modules {
+        cache.get(&engine, wasm).unwrap();
+    }
+    cache.get(&engine, &modules[2]).unwrap();
+    cache.get(&engine, &modules[0]).unwrap();
+    assert_eq!(cache.stats(), ModuleCacheStats { memory_hits: 1, disk_hits: 0, compiled: 4 });
+}
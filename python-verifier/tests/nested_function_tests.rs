@@ -0,0 +1,155 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+fn run(code: &str) -> Result<i32> {
+    let wasm = PythonCompiler::new().compile(code)?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    main.call(&mut store, ())
+}
+
+fn compile_error(code: &str) -> String {
+    PythonCompiler::new().compile(code).unwrap_err().to_string()
+}
+
+#[test]
+fn test_helper_without_captures() -> Result<()> {
+    let code = r#"
+def total(xs):
+    def clamp(v):
+        if v < 0:
+            return 0
+        return v
+    t = 0
+    for x in xs:
+        t += clamp(x)
+    return t
+OUTPUT = total([3, -4, 10])
+"#;
+    assert_eq!(run(code)?, 13);
+    Ok(())
+}
+
+#[test]
+fn test_captures_parameters_and_locals() -> Result<()> {
+    let code = r#"
+def scale_all(xs, factor):
+    offset = 1
+    def scale(v):
+        return v * factor + offset
+    out = 0
+    for x in xs:
+        out += scale(x)
+    return out
+OUTPUT = scale_all([1, 2, 3], 10)
+"#;
+    assert_eq!(run(code)?, 63);
+    Ok(())
+}
+
+#[test]
+fn test_captured_value_read_at_call_time() -> Result<()> {
+    let code = r#"
+def f():
+    n = 1
+    def get():
+        return n
+    a = get()
+    n = 5
+    return a * 10 + get()
+OUTPUT = f()
+"#;
+    assert_eq!(run(code)?, 15);
+    Ok(())
+}
+
+#[test]
+fn test_assignment_in_nested_function_is_local() -> Result<()> {
+    let code = r#"
+def f():
+    n = 7
+    def g():
+        n = 2
+        return n
+    return g() * 10 + n
+OUTPUT = f()
+"#;
+    assert_eq!(run(code)?, 27);
+    Ok(())
+}
+
+#[test]
+fn test_recursion_and_sibling_calls_pass_captures() -> Result<()> {
+    let code = r#"
+def f(base):
+    def fact(k):
+        if k <= 1:
+            return base
+        return k * fact(k - 1)
+    def twice(k):
+        return fact(k) * 2
+    return twice(4)
+OUTPUT = f(3)
+"#;
+    assert_eq!(run(code)?, 144);
+    Ok(())
+}
+
+#[test]
+fn test_mutating_captured_list() -> Result<()> {
+    let code = r#"
+def collect(n):
+    seen = []
+    def add(v):
+        seen.append(v * v)
+        return 0
+    for i in range(n):
+        add(i)
+    return sum(seen)
+OUTPUT = collect(4)
+"#;
+    assert_eq!(run(code)?, 14);
+    Ok(())
+}
+
+#[test]
+fn test_nested_functions_see_module_globals() -> Result<()> {
+    let code = r#"
+LIMIT = 4
+def count():
+    def small(v):
+        return v < LIMIT
+    c = 0
+    for i in range(10):
+        if small(i):
+            c += 1
+    return c
+OUTPUT = count()
+"#;
+    assert_eq!(run(code)?, 4);
+    Ok(())
+}
+
+#[test]
+fn test_escaping_and_unsupported_forms_rejected() {
+    let err = compile_error("def f():\n    def g():\n        return 1\n    return g\nOUTPUT = f()");
+    assert!(err.contains("nested function 'g' can only be called"), "{}", err);
+
+    let err = compile_error("def f():\n    x = 1\n    def g():\n        nonlocal x\n        x = 2\n        return 0\n    return g()\nOUTPUT = f()");
+    assert!(err.contains("nonlocal not supported"), "{}", err);
+
+    let err = compile_error("def f():\n    def g(a):\n        return a\n    return g(1, 2)\nOUTPUT = f()");
+    assert!(err.contains("g() takes 1 positional argument"), "{}", err);
+
+    let err = compile_error("def f():\n    def g():\n        def h():\n            return 1\n        return h()\n    return g()\nOUTPUT = f()");
+    assert!(err.contains("cannot define functions itself"), "{}", err);
+
+    let code = "def f():\n    x = 1\n    def g():\n        return x\n    def h():\n        x = 5\n        return g()\n    return h()\nOUTPUT = f()";
+    let err = compile_error(code);
+    assert!(err.contains("'h' calls 'g', which reads the outer 'x' that 'h' shadows"), "{}", err);
+}
@@ -0,0 +1,133 @@
+use ethers::abi::{decode, ParamType, Token};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{H256, U256};
+use python_verifier::notarization::{attestation, notarize_calldata, retry_delay, MAX_RETRY_DELAY};
+use python_verifier::receipts::{Finalization, ReceiptStore, SignedReceipt};
+
+const KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+fn wallet() -> LocalWallet {
+    KEY.parse().unwrap()
+}
+
+fn open_store(name: &str) -> ReceiptStore {
+    let path = std::env::temp_dir().join(format!("certus-notarization-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    ReceiptStore::open(path.to_str().unwrap()).unwrap()
+}
+
+fn receipt(job: u8) -> SignedReceipt {
+    let finalization = Finalization {
+        job_id: [job; 32],
+        tx_hash: H256::repeat_byte(0xaa),
+        output_hash: [0x42; 32],
+        fuel_used: 12_345,
+        finalized_at: 1_700_000_000,
+    };
+    SignedReceipt::sign(&finalization, &wallet()).unwrap()
+}
+
+#[test]
+fn test_calldata_carries_a_checkable_attestation() {
+    let receipt = receipt(1);
+    let calldata = notarize_calldata(&receipt).unwrap();
+    assert_eq!(calldata[..4], ethers::utils::id("notarize(bytes32,bytes32,bytes)")[..4]);
+
+    let args = decode(
+        &[ParamType::FixedBytes(32), ParamType::FixedBytes(32), ParamType::Bytes],
+        &calldata[4..],
+    ).unwrap();
+    assert_eq!(args[0], Token::FixedBytes(vec![1; 32]));
+    assert_eq!(args[1], Token::FixedBytes(vec![0x42; 32]));
+    let Token::Bytes(attested) = &args[2] else { panic!("attestation is not bytes") };
+    assert_eq!(*attested, attestation(&receipt).unwrap());
+
+    let fields = decode(
+        &[
+            ParamType::Uint(256),
+            ParamType::FixedBytes(32),
+            ParamType::Address,
+            ParamType::FixedBytes(32),
+            ParamType::Bytes,
+        ],
+        attested,
+    ).unwrap();
+    assert_eq!(fields[0], Token::Uint(U256::from(12_345)));
+    assert_eq!(fields[1], Token::FixedBytes(vec![0xaa; 32]));
+    assert_eq!(fields[2], Token::Address(wallet().address()));
+
+    // what the contract does: recover the executor from the signature over the receipt digest
+    let Token::Bytes(signature) = &fields[4] else { panic!("signature is not bytes") };
+    let signature = ethers::types::Signature::try_from(signature.as_slice()).unwrap();
+    assert_eq!(signature.recover(receipt.digest().unwrap().to_vec()).unwrap(), wallet().address());
+}
+
+#[test]
+fn test_receipt_without_build_attests_zero_build() {
+    let mut receipt = receipt(2);
+    receipt.build = None;
+    let fields = decode(
+        &[
+            ParamType::Uint(256),
+            ParamType::FixedBytes(32),
+            ParamType::Address,
+            ParamType::FixedBytes(32),
+            ParamType::Bytes,
+        ],
+        &attestation(&receipt).unwrap(),
+    ).unwrap();
+    assert_eq!(fields[3], Token::FixedBytes(vec![0; 32]));
+
+    let mut bad = receipt;
+    bad.executor = "not an address".to_string();
+    assert!(notarize_calldata(&bad).is_err());
+}
+
+#[test]
+fn test_pending_notarizations_until_notarized() {
+    let store = open_store("pending");
+    let tracked = receipt(3);
+    let untracked = receipt(4);
+    store.track(&tracked.job_id, None).unwrap();
+    store.issue(tracked.clone()).unwrap();
+    store.issue(untracked).unwrap();
+
+    // only jobs this node executed are notarized
+    assert_eq!(store.pending_notarizations(0, 10).unwrap(), vec![tracked.clone()]);
+
+    store.mark_notarized(&tracked.job_id, &format!("{:?}", H256::repeat_byte(0xbb))).unwrap();
+    assert!(store.pending_notarizations(u64::MAX, 10).unwrap().is_empty());
+    let job = store.tracked(&tracked.job_id).unwrap().unwrap();
+    assert_eq!(job.notarization_tx, Some(format!("{:?}", H256::repeat_byte(0xbb))));
+}
+
+#[test]
+fn test_failed_notarization_waits_then_gives_up() {
+    let store = open_store("retries");
+    let receipt = receipt(5);
+    store.track(&receipt.job_id, None).unwrap();
+    store.issue(receipt.clone()).unwrap();
+
+    store.record_failed_notarization(&receipt.job_id, "gas price too high", 1_000).unwrap();
+    assert!(store.pending_notarizations(999, 3).unwrap().is_empty());
+    assert_eq!(store.pending_notarizations(1_000, 3).unwrap(), vec![receipt.clone()]);
+
+    let job = store.tracked(&receipt.job_id).unwrap().unwrap();
+    assert_eq!(job.notarization_attempts, 1);
+    assert_eq!(job.last_notarization_error.as_deref(), Some("gas price too high"));
+
+    store.record_failed_notarization(&receipt.job_id, "reverted", 1_000).unwrap();
+    store.record_failed_notarization(&receipt.job_id, "reverted", 1_000).unwrap();
+    assert!(store.pending_notarizations(u64::MAX, 3).unwrap().is_empty());
+    assert_eq!(store.pending_notarizations(u64::MAX, 4).unwrap(), vec![receipt]);
+}
+
+#[test]
+fn test_retry_delay_doubles_up_to_the_cap() {
+    assert_eq!(retry_delay(30, 0), 30);
+    assert_eq!(retry_delay(30, 1), 60);
+    assert_eq!(retry_delay(30, 4), 480);
+    assert_eq!(retry_delay(30, 7), MAX_RETRY_DELAY);
+    assert_eq!(retry_delay(30, 200), MAX_RETRY_DELAY);
+    assert_eq!(retry_delay(u64::MAX, 1), MAX_RETRY_DELAY);
+}
@@ -0,0 +1,114 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
+use python_verifier::outputs::{output_cid, OutputBlob, OutputStore};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+type Blocks = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+fn lines(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|l| l.to_string()).collect()
+}
+
+// The file part of a single-part multipart body
+fn multipart_file(body: &[u8]) -> Vec<u8> {
+    let start = body.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let end = body.windows(4).rposition(|w| w == b"\r\n--").unwrap();
+    body[start..end].to_vec()
+}
+
+async fn block_put(State(blocks): State<Blocks>, body: Bytes) -> String {
+    let data = multipart_file(&body);
+    let cid = output_cid(&Sha256::digest(&data).into());
+    blocks.lock().unwrap().insert(cid.clone(), data);
+    serde_json::json!({ "Key": cid, "Size": body.len() }).to_string()
+}
+
+async fn gateway(State(blocks): State<Blocks>, Path(cid): Path<String>) -> Result<Vec<u8>, StatusCode> {
+    blocks.lock().unwrap().get(&cid).cloned().ok_or(StatusCode::NOT_FOUND)
+}
+
+// An IPFS node serving both the RPC API and the gateway
+fn mock_ipfs() -> (String, Blocks) {
+    let blocks = Blocks::default();
+    let app = Router::new()
+        .route("/api/v0/block/put", post(block_put))
+        .route("/ipfs/:cid", get(gateway))
+        .with_state(blocks.clone());
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+    (url, blocks)
+}
+
+#[test]
+fn test_blob_bytes_are_the_hash_preimage() {
+    for stdout in [vec![], lines(&["step 1", "", "done"])] {
+        let blob = OutputBlob::new("{\"total\": 42}", &stdout);
+        assert_eq!(blob.hash(), python_verifier::output_hash(&blob.output, &blob.stdout));
+        assert_eq!(blob.hash(), hex::encode(Sha256::digest(blob.to_bytes())));
+        assert_eq!(OutputBlob::from_bytes(&blob.to_bytes()).unwrap(), blob);
+    }
+}
+
+#[test]
+fn test_non_canonical_bytes_rejected() {
+    // printed lines always end with a newline
+    assert!(OutputBlob::from_bytes(b"7\0line").is_err());
+    // an output holding the separator can't be told apart from one that printed
+    assert!(OutputBlob::from_bytes(&OutputBlob::new("a\0b\n", &[]).to_bytes()).is_ok_and(|blob| blob.output == "a"));
+    assert!(OutputBlob::from_bytes(b"\xff\xfe").is_err());
+    assert_eq!(OutputBlob::from_bytes(b"7\0a\n").unwrap().stdout, lines(&["a"]));
+}
+
+#[test]
+fn test_cid_is_raw_sha256_cidv1() {
+    // what `ipfs add --cid-version 1 --raw-leaves` gives an empty file
+    assert_eq!(
+        output_cid(&Sha256::digest(b"").into()),
+        "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+    );
+    let blob = OutputBlob::new("42", &[]);
+    assert_eq!(blob.cid(), output_cid(&Sha256::digest(b"42").into()));
+}
+
+#[tokio::test]
+async fn test_upload_then_fetch_by_hash() {
+    let (url, _) = mock_ipfs();
+    let store = OutputStore::new(&url).with_upload_api(&url);
+
+    let blob = OutputBlob::new("[1, 2, 3]", &lines(&["hello"]));
+    let cid = store.upload(&blob).await.unwrap();
+    assert_eq!(cid, blob.cid());
+
+    let fetched = store.fetch(&format!("0x{}", blob.hash())).await.unwrap();
+    assert_eq!(fetched, blob);
+}
+
+#[tokio::test]
+async fn test_fetch_rejects_missing_and_tampered_outputs() {
+    let (url, blocks) = mock_ipfs();
+    let store = OutputStore::new(&url).with_upload_api(&url);
+
+    let err = store.fetch(&OutputBlob::new("never uploaded", &[]).hash()).await.unwrap_err();
+    assert!(err.to_string().contains("not available"), "{}", err);
+
+    let blob = OutputBlob::new("100", &[]);
+    store.upload(&blob).await.unwrap();
+    blocks.lock().unwrap().insert(blob.cid(), b"999".to_vec());
+    let err = store.fetch(&blob.hash()).await.unwrap_err();
+    assert!(err.to_string().contains("does not match hash"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_read_only_store_cannot_upload() {
+    let (url, _) = mock_ipfs();
+    let err = OutputStore::new(&url).upload(&OutputBlob::new("1", &[])).await.unwrap_err();
+    assert!(err.to_string().contains("no IPFS API configured"), "{}", err);
+}
@@ -0,0 +1,107 @@
+// Every entry point that turns source into Wasm must emit the same bytes: a module that
+// differs between the CLI, the executor and the integration would hash differently on-chain.
+
+use python_verifier::compiler::{CompileOptions, Optimize, PythonCompiler};
+use python_verifier::profiles::ExecutionProfile;
+use python_verifier::PythonExecutor;
+use anyhow::Result;
+
+const PROGRAMS: &[&str] = &[
+    "OUTPUT = 6 * 7",
+    "x = 4\nOUTPUT = x * 2 if x % 2 == 0 else x + 1",
+    r#"
+def fib(n):
+    if n < 2:
+        return n
+    return fib(n - 1) + fib(n - 2)
+OUTPUT = fib(7)
+"#,
+    r#"
+counts = {}
+for n in [3, 5, 3, 7, 3]:
+    if n in counts:
+        counts[n] = counts[n] + 1
+    else:
+        counts[n] = 1
+OUTPUT = counts[3] * 10 + len(counts)
+"#,
+    r#"
+import hashlib
+h = hashlib.sha256(b"certus").digest()
+squares = [i * i for i in range(10) if i % 2 == 0]
+try:
+    r = 1 // 0
+except ZeroDivisionError:
+    r = -1
+print("done", len(squares))
+OUTPUT = sum(squares) + h[0] + r
+"#,
+];
+
+#[test]
+fn test_every_program_runs() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let outputs: Vec<String> = PROGRAMS
+        .iter()
+        .map(|code| Ok(executor.execute_with_profile(code, "{}", &ExecutionProfile::default())?.result))
+        .collect::<Result<_>>()?;
+    assert_eq!(outputs, ["42", "8", "13", "33", "338"]);
+    Ok(())
+}
+
+#[test]
+fn test_legacy_path_is_the_same_compiler() -> Result<()> {
+    for code in PROGRAMS {
+        let legacy = python_verifier::python_compiler::PythonCompiler::new().compile(code)?;
+        let direct = PythonCompiler::new().compile(code)?;
+        assert_eq!(legacy, direct, "{}", code);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_executor_compiles_like_a_fresh_compiler() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    // a module is built for its job's fuel and memory, so the compiler gets the profile's limits
+    let profile = ExecutionProfile::default();
+    for code in PROGRAMS {
+        let fresh = PythonCompiler::new().with_resource_limits(profile.resource_limits()).compile(code)?;
+        assert_eq!(executor.compile(code, &profile, CompileOptions::default())?, fresh, "{}", code);
+        // the second compile is served from the executor's cache
+        assert_eq!(executor.compile(code, &profile, CompileOptions::default())?, fresh, "{}", code);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_options_and_profiles_match_an_equivalent_compiler() -> Result<()> {
+    let mut executor = PythonExecutor::new()?.with_max_call_depth(64);
+    let size = CompileOptions { optimize: Optimize::Size, ..Default::default() };
+    let restricted = ExecutionProfile {
+        allowed_builtins: Some(["len", "sum", "print"].iter().map(|s| s.to_string()).collect()),
+        ..Default::default()
+    };
+    let limits = ExecutionProfile::default().resource_limits();
+    for code in PROGRAMS {
+        let expected = PythonCompiler::new().with_max_call_depth(64).with_options(size).with_resource_limits(limits).compile(code)?;
+        assert_eq!(executor.compile(code, &ExecutionProfile::default(), size)?, expected, "{}", code);
+    }
+
+    let code = PROGRAMS[1];
+    let expected = PythonCompiler::new()
+        .with_max_call_depth(64)
+        .with_allowed_builtins(restricted.allowed_builtins.clone())
+        .with_resource_limits(restricted.resource_limits())
+        .compile(code)?;
+    assert_eq!(executor.compile(code, &restricted, CompileOptions::default())?, expected);
+    Ok(())
+}
+
+#[test]
+fn test_output_is_stable_across_compiler_instances() -> Result<()> {
+    for code in PROGRAMS {
+        let runs: Vec<Vec<u8>> = (0..3).map(|_| PythonCompiler::new().compile(code)).collect::<Result<_>>()?;
+        assert!(runs.windows(2).all(|w| w[0] == w[1]), "{}", code);
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use python_verifier::playground::{Playground, PlaygroundConfig};
+
+fn playground() -> Playground {
+    Playground::new(PlaygroundConfig::default()).unwrap()
+}
+
+#[test]
+fn test_runs_snippet() {
+    let result = playground().run("OUTPUT = 6 * 7", &serde_json::json!({})).unwrap();
+    assert!(result.success, "{:?}", result.error);
+    assert!(result.result.is_some());
+    assert!(result.fuel_consumed > 0);
+    assert!(!result.cached);
+}
+
+#[test]
+fn test_snippets_are_compiled_for_the_sandbox_memory() {
+    // fewer pages than a module built with the default limits imports
+    let playground = Playground::new(PlaygroundConfig { max_memory_pages: 4, ..Default::default() }).unwrap();
+    let result = playground.run("s = str(6 * 7) + '!'\nOUTPUT = len(s)", &serde_json::json!({})).unwrap();
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.result.as_deref(), Some("3"));
+}
+
+#[test]
+fn test_repeat_run_is_cached() {
+    let playground = playground();
+    let first = playground.run("OUTPUT = 1 + 1", &serde_json::json!({})).unwrap();
+    let second = playground.run("OUTPUT = 1 + 1", &serde_json::json!({})).unwrap();
+    assert!(second.cached);
+    assert_eq!(second.result, first.result);
+    assert_eq!(playground.cached_results(), 1);
+
+    // a different input is a different run
+    playground.run("OUTPUT = 1 + 1", &serde_json::json!({ "x": 1 })).unwrap();
+    assert_eq!(playground.cached_results(), 2);
+}
+
+#[test]
+fn test_runaway_loop_runs_out_of_fuel() {
+    let code = "total = 0\nfor i in range(1000000):\n    total = total + i\nOUTPUT = total";
+    let result = playground().run(code, &serde_json::json!({})).unwrap();
+    assert!(!result.success);
+    assert!(result.error.is_some());
+}
+
+#[test]
+fn test_compile_error_is_an_outcome() {
+    let result = playground().run("OUTPUT = undefined_name(", &serde_json::json!({})).unwrap();
+    assert!(!result.success);
+    assert!(result.error.is_some());
+}
+
+#[test]
+fn test_oversized_code_refused() {
+    let code = format!("OUTPUT = {}", "1 + ".repeat(4096) + "1");
+    assert!(playground().run(&code, &serde_json::json!({})).is_err());
+}
+
+#[test]
+fn test_cache_evicts_oldest() {
+    let playground = Playground::new(PlaygroundConfig { cache_entries: 2, ..Default::default() }).unwrap();
+    for n in 0..3 {
+        playground.run(&format!("OUTPUT = {}", n), &serde_json::json!({})).unwrap();
+    }
+    assert_eq!(playground.cached_results(), 2);
+    assert!(!playground.run("OUTPUT = 0", &serde_json::json!({})).unwrap().cached);
+    assert!(playground.run("OUTPUT = 2", &serde_json::json!({})).unwrap().cached);
+}
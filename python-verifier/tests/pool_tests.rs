@@ -0,0 +1,75 @@
+use python_verifier::pool::ExecutorPool;
+use python_verifier::PythonExecutor;
+use std::sync::Arc;
+use wasm_encoder::{
+    CodeSection, ExportKind, ExportSection, Function, FunctionSection, ImportSection, Instruction, MemoryType, Module,
+    TypeSection, ValType,
+};
+
+// python_main(input_ptr, input_len) returns the input's record, so the output is the input
+fn echo_module() -> Vec<u8> {
+    let mut module = Module::new();
+    let mut types = TypeSection::new();
+    types.function([ValType::I32, ValType::I32], [ValType::I32]);
+    module.section(&types);
+    let mut imports = ImportSection::new();
+    imports.import("env", "memory", MemoryType { minimum: 1, maximum: None, memory64: false, shared: false });
+    module.section(&imports);
+    let mut funcs = FunctionSection::new();
+    funcs.function(0);
+    module.section(&funcs);
+    let mut exports = ExportSection::new();
+    exports.export("python_main", ExportKind::Func, 0);
+    exports.export("memory", ExportKind::Memory, 0);
+    module.section(&exports);
+    let mut code = CodeSection::new();
+    let mut main = Function::new([]);
+    main.instruction(&Instruction::LocalGet(0));
+    main.instruction(&Instruction::I32Const(4));
+    main.instruction(&Instruction::I32Sub);
+    main.instruction(&Instruction::End);
+    code.function(&main);
+    module.section(&code);
+    module.finish()
+}
+
+#[test]
+fn test_concurrent_jobs_agree() {
+    let pool = Arc::new(ExecutorPool::new(4, PythonExecutor::new).unwrap());
+    let wasm = Arc::new(echo_module());
+    let reference = PythonExecutor::new().unwrap().execute_wasm(&wasm, r#"{"x": 1}"#, 1_000_000).unwrap();
+
+    let handles: Vec<_> = (0..8).map(|_| {
+        let (pool, wasm) = (pool.clone(), wasm.clone());
+        std::thread::spawn(move || pool.get().execute_wasm(&wasm, r#"{"x": 1}"#, 1_000_000).unwrap())
+    }).collect();
+    for handle in handles {
+        let output = handle.join().unwrap();
+        assert_eq!(output.result, r#"{"x": 1}"#);
+        assert_eq!(output.output_hash, reference.output_hash);
+        assert_eq!(output.fuel_consumed, reference.fuel_consumed);
+    }
+    assert_eq!(pool.idle(), 4);
+}
+
+#[test]
+fn test_get_waits_for_a_returned_executor() {
+    let pool = Arc::new(ExecutorPool::single(PythonExecutor::new().unwrap()));
+    let held = pool.get();
+    assert_eq!(pool.idle(), 0);
+
+    let waiter = {
+        let pool = pool.clone();
+        std::thread::spawn(move || pool.get().execute_wasm(&echo_module(), "[7]", 1_000_000).unwrap().result)
+    };
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert!(!waiter.is_finished());
+    drop(held);
+    assert_eq!(waiter.join().unwrap(), "[7]");
+    assert_eq!(pool.idle(), 1);
+}
+
+#[test]
+fn test_empty_pool_is_rejected() {
+    assert!(ExecutorPool::new(0, PythonExecutor::new).is_err());
+}
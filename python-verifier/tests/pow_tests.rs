@@ -0,0 +1,125 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+#[test]
+fn test_pow_operator() -> Result<()> {
+    assert_eq!(run("OUTPUT = 2 ** 10")?, 1024);
+    assert_eq!(run("x = 3\ny = 0\nOUTPUT = x ** y")?, 1);
+    assert_eq!(run("x = 0\nOUTPUT = x ** 0")?, 1);
+    assert_eq!(run("x = -2\nOUTPUT = x ** 3")?, -8);
+    assert_eq!(run("x = 7\nOUTPUT = x ** 1")?, 7);
+    Ok(())
+}
+
+#[test]
+fn test_pow_precedence_and_associativity() -> Result<()> {
+    // ** is right associative and binds tighter than unary minus
+    assert_eq!(run("OUTPUT = 2 ** 3 ** 2")?, 512);
+    assert_eq!(run("x = 2\nOUTPUT = -x ** 2")?, -4);
+    assert_eq!(run("x = 3\nOUTPUT = 2 * x ** 2 + 1")?, 19);
+    Ok(())
+}
+
+#[test]
+fn test_pow_augmented_and_builtin() -> Result<()> {
+    assert_eq!(run("x = 5\nx **= 3\nOUTPUT = x")?, 125);
+    assert_eq!(run("OUTPUT = pow(2, 16)")?, 65536);
+    Ok(())
+}
+
+#[test]
+fn test_pow_wraps_like_multiplication() -> Result<()> {
+    assert_eq!(run("x = 2\nOUTPUT = x ** 31")?, i32::MIN);
+    assert_eq!(run("x = 2\nOUTPUT = x ** 32")?, 0);
+    Ok(())
+}
+
+#[test]
+fn test_modular_pow() -> Result<()> {
+    assert_eq!(run("OUTPUT = pow(4, 13, 497)")?, 445);
+    assert_eq!(run("OUTPUT = pow(3, 200, 1000000007)")?, 136318165);
+    assert_eq!(run("OUTPUT = pow(5, 0, 1)")?, 0);
+    assert_eq!(run("OUTPUT = pow(5, 0, 7)")?, 1);
+    Ok(())
+}
+
+#[test]
+fn test_modular_pow_never_overflows() -> Result<()> {
+    // operands close to 2**31 would wrap in i32 products
+    assert_eq!(run("OUTPUT = pow(2147483646, 2147483646, 2147483647)")?, 1);
+    assert_eq!(run("OUTPUT = pow(123456789, 987654321, 2147483629)")?, 1781534958);
+    Ok(())
+}
+
+#[test]
+fn test_modular_pow_sign_follows_modulus() -> Result<()> {
+    assert_eq!(run("OUTPUT = pow(7, 5, -5)")?, -3);
+    assert_eq!(run("OUTPUT = pow(-3, 3, 7)")?, 1);
+    Ok(())
+}
+
+#[test]
+fn test_rsa_signature_check() -> Result<()> {
+    let code = r#"
+def verify(sig, e, n, msg):
+    return pow(sig, e, n) == msg
+
+OUTPUT = verify(588, 17, 3233, 65) + verify(588, 17, 3233, 66)
+"#;
+    assert_eq!(run(code)?, 1);
+    Ok(())
+}
+
+#[test]
+fn test_negative_exponent_and_zero_modulus_raise() -> Result<()> {
+    let code = r#"
+x = -1
+r = 0
+try:
+    r = 2 ** x
+except ValueError:
+    r = -1
+try:
+    r = r + pow(2, 3, 0)
+except ValueError:
+    r = r - 10
+OUTPUT = r
+"#;
+    assert_eq!(run(code)?, -11);
+
+    let err = run("x = -1\nOUTPUT = 2 ** x").expect_err("expected a trap");
+    assert_eq!(err.downcast_ref::<Trap>(), Some(&Trap::UnreachableCodeReached), "{:?}", err);
+    Ok(())
+}
+
+#[test]
+fn test_pow_argument_count() {
+    let err = PythonCompiler::new().compile("OUTPUT = pow(2)").unwrap_err();
+    assert!(err.to_string().contains("pow() takes 2 to 3 arguments"), "{}", err);
+}
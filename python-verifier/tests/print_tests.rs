@@ -0,0 +1,122 @@
+use python_verifier::compiler::{STDOUT_CAPACITY, STDOUT_EXPORT};
+use python_verifier::python_compiler::PythonCompiler;
+use python_verifier::{captured_stdout, output_hash};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use wasmtime::*;
+
+// Run main and collect OUTPUT along with the printed lines
+fn run(code: &str) -> Result<(i32, Vec<String>)> {
+    let wasm = PythonCompiler::new().compile(code)?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let output = main.call(&mut store, ())?;
+
+    let stdout = match instance.get_global(&mut store, STDOUT_EXPORT) {
+        Some(global) => {
+            let base = global.get(&mut store).unwrap_i32() as u32;
+            captured_stdout(memory.data(&store), base)
+        }
+        None => Vec::new(),
+    };
+    Ok((output, stdout))
+}
+
+#[test]
+fn test_print_values() -> Result<()> {
+    let code = r#"
+print("start")
+total = 0
+for x in [3, -4, 10]:
+    total += x
+    print("x", x, "total", total)
+print()
+OUTPUT = total
+"#;
+    let (output, stdout) = run(code)?;
+    assert_eq!(output, 9);
+    assert_eq!(stdout, ["start", "x 3 total 3", "x -4 total -1", "x 10 total 9", ""]);
+    Ok(())
+}
+
+#[test]
+fn test_print_from_functions() -> Result<()> {
+    let code = r#"
+def fib(n):
+    a = 0
+    b = 1
+    for i in range(n):
+        print(a)
+        t = a + b
+        a = b
+        b = t
+    return a
+OUTPUT = fib(5)
+"#;
+    let (output, stdout) = run(code)?;
+    assert_eq!(output, 5);
+    assert_eq!(stdout, ["0", "1", "1", "2", "3"]);
+    Ok(())
+}
+
+#[test]
+fn test_modules_without_print_export_no_buffer() -> Result<()> {
+    let wasm = PythonCompiler::new().compile("OUTPUT = 1")?;
+    let module = Module::new(&Engine::default(), &wasm)?;
+    assert!(module.exports().all(|e| e.name() != STDOUT_EXPORT));
+    Ok(())
+}
+
+#[test]
+fn test_ring_keeps_latest_lines() -> Result<()> {
+    // 5000 lines of "line NNNN" overflow the buffer several times
+    let code = r#"
+for i in range(5000):
+    print("line", i + 1000)
+OUTPUT = 0
+"#;
+    let (_, stdout) = run(code)?;
+    // whole lines that fit, less the first one, which might have been cut
+    let line_len = "line 1000\n".len();
+    assert_eq!(stdout.len(), STDOUT_CAPACITY as usize / line_len - 1);
+    assert_eq!(stdout.last().unwrap(), "line 5999");
+    let first: usize = stdout[0].strip_prefix("line ").unwrap().parse()?;
+    assert_eq!(first, 6000 - stdout.len());
+    Ok(())
+}
+
+#[test]
+fn test_raised_argument_prints_nothing() -> Result<()> {
+    let code = r#"
+z = 0
+try:
+    print("before", 1 // z)
+except ZeroDivisionError:
+    print("caught")
+OUTPUT = 1
+"#;
+    let (_, stdout) = run(code)?;
+    assert_eq!(stdout, ["caught"]);
+    Ok(())
+}
+
+#[test]
+fn test_output_hash_covers_stdout() {
+    let plain = hex::encode(Sha256::digest(b"42"));
+    assert_eq!(output_hash("42", &[]), plain);
+
+    let logged = output_hash("42", &["a".to_string()]);
+    assert_ne!(logged, plain);
+    assert_ne!(logged, output_hash("42", &["b".to_string()]));
+    assert_ne!(output_hash("42", &["a".to_string(), "b".to_string()]), output_hash("42", &["ab".to_string()]));
+}
+
+#[test]
+fn test_print_keywords_rejected() {
+    let err = PythonCompiler::new().compile("print(1, end=\"\")").unwrap_err();
+    assert!(err.to_string().contains("print() keyword arguments not supported"), "{}", err);
+}
@@ -0,0 +1,167 @@
+use python_verifier::profiles::{ExecutionProfile, ProfileSet, DEFAULT_PROFILE};
+use python_verifier::python_compiler::PythonCompiler;
+use python_verifier::compiler::RuntimeError;
+use python_verifier::executor_error::ExecutorError;
+use python_verifier::{PythonExecutor, Trapped};
+use anyhow::Result;
+use std::collections::BTreeSet;
+use wasm_encoder::{
+    CodeSection, ExportKind, ExportSection, Function, FunctionSection, ImportSection, Instruction,
+    MemoryType, Module, TypeSection, ValType,
+};
+
+// python_main(input_ptr, input_len) runs `body`, then returns the input's record, so the output is the input
+fn echo_module(body: &[Instruction]) -> Vec<u8> {
+    let mut module = Module::new();
+
+    let mut types = TypeSection::new();
+    types.function([ValType::I32, ValType::I32], [ValType::I32]);
+    module.section(&types);
+
+    let mut imports = ImportSection::new();
+    imports.import("env", "memory", MemoryType { minimum: 1, maximum: None, memory64: false, shared: false });
+    module.section(&imports);
+
+    let mut funcs = FunctionSection::new();
+    funcs.function(0);
+    module.section(&funcs);
+
+    let mut exports = ExportSection::new();
+    exports.export("python_main", ExportKind::Func, 0);
+    exports.export("memory", ExportKind::Memory, 0);
+    module.section(&exports);
+
+    let mut code = CodeSection::new();
+    let mut main = Function::new([]);
+    for instruction in body {
+        main.instruction(instruction);
+    }
+    main.instruction(&Instruction::LocalGet(0));
+    main.instruction(&Instruction::I32Const(4));
+    main.instruction(&Instruction::I32Sub);
+    main.instruction(&Instruction::End);
+    code.function(&main);
+    module.section(&code);
+
+    module.finish()
+}
+
+fn builtins(names: &[&str]) -> Option<BTreeSet<String>> {
+    Some(names.iter().map(|n| n.to_string()).collect())
+}
+
+#[test]
+fn test_profile_set_adds_default() -> Result<()> {
+    let profiles = ProfileSet::from_json(r#"{
+        "small": { "fuel_limit": 50000, "max_memory_pages": 16, "max_output_bytes": 4096,
+                   "max_wall_clock_ms": 2000, "allowed_builtins": ["len", "sum"] }
+    }"#)?;
+
+    assert_eq!(profiles.names().collect::<Vec<_>>(), [DEFAULT_PROFILE, "small"]);
+    assert_eq!(profiles.get(None)?, &ExecutionProfile::default());
+    let small = profiles.get(Some("small"))?;
+    assert_eq!(small.max_memory_pages, 16);
+    assert_eq!(small.mem_limit(), 16 * 65536);
+    assert_eq!(small.allowed_builtins, builtins(&["len", "sum"]));
+
+    let err = profiles.get(Some("huge")).unwrap_err();
+    assert!(err.to_string().contains("unknown execution profile huge"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_configured_default_replaces_builtin_one() -> Result<()> {
+    let profiles = ProfileSet::from_json(r#"{
+        "default": { "fuel_limit": 2000, "max_memory_pages": 4, "max_output_bytes": 64, "max_wall_clock_ms": 100 }
+    }"#)?;
+    assert_eq!(profiles.default_profile().fuel_limit, 2000);
+    assert_eq!(profiles.default_profile().allowed_builtins, None);
+    Ok(())
+}
+
+#[test]
+fn test_invalid_profiles_are_rejected() {
+    let cases = [
+        (r#"{"p": {"fuel_limit": 10, "max_memory_pages": 4, "max_output_bytes": 64, "max_wall_clock_ms": 100}}"#, "fuel_limit"),
+        (r#"{"p": {"fuel_limit": 2000, "max_memory_pages": 0, "max_output_bytes": 64, "max_wall_clock_ms": 100}}"#, "max_memory_pages"),
+        (r#"{"p": {"fuel_limit": 2000, "max_memory_pages": 4, "max_output_bytes": 2000000, "max_wall_clock_ms": 100}}"#, "max_output_bytes"),
+        (r#"{"p": {"fuel_limit": 2000, "max_memory_pages": 4, "max_output_bytes": 64, "max_wall_clock_ms": 0}}"#, "max_wall_clock_ms"),
+        (r#"{"p": {"fuel_limit": 2000, "max_memory_pages": 4, "max_output_bytes": 64, "max_wall_clock_ms": 100, "allowed_builtins": ["eval"]}}"#, "unknown builtin eval"),
+        (r#"{"p": {"fuel_limit": 2000, "max_memory_pages": 4, "max_output_bytes": 64, "max_wall_clock_ms": 100, "max_heap": 1}}"#, "invalid profile config"),
+    ];
+    for (json, expected) in cases {
+        let err = ProfileSet::from_json(json).unwrap_err();
+        assert!(format!("{:#}", err).contains(expected), "{}: {:#}", expected, err);
+    }
+}
+
+#[test]
+fn test_chain_limits_narrow_the_profile() {
+    let profile = ExecutionProfile::default().with_chain_limits(5_000, 8 * 65536, 512);
+    assert_eq!(profile.fuel_limit, 5_000);
+    assert_eq!(profile.max_memory_pages, 8);
+    assert_eq!(profile.max_output_bytes, 512);
+
+    // the chain can't raise a ceiling the profile sets
+    let profile = ExecutionProfile { max_memory_pages: 4, ..Default::default() }.with_chain_limits(5_000, u64::MAX, u32::MAX);
+    assert_eq!(profile.max_memory_pages, 4);
+    assert_eq!(profile.max_output_bytes, ExecutionProfile::default().max_output_bytes);
+}
+
+#[test]
+fn test_builtins_outside_the_profile_fail_to_compile() -> Result<()> {
+    let mut compiler = PythonCompiler::new().with_allowed_builtins(builtins(&["len"]));
+    compiler.compile("OUTPUT = len([1, 2])")?;
+
+    let err = compiler.compile("OUTPUT = sum([1, 2])").unwrap_err();
+    assert!(err.to_string().contains("sum() is not allowed by the execution profile"), "{}", err);
+
+    let profile = ExecutionProfile { allowed_builtins: builtins(&["len"]), ..Default::default() };
+    let err = PythonExecutor::new()?.execute_with_profile("OUTPUT = sorted([2, 1])", "{}", &profile).unwrap_err();
+    assert!(err.to_string().contains("sorted() is not allowed"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_output_limit() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let input = r#"{"x": 21}"#;
+
+    let exact = ExecutionProfile { max_output_bytes: input.len() as u32, ..Default::default() };
+    assert_eq!(executor.execute_wasm_with_profile(&echo_module(&[]), input, &exact)?.result, input);
+
+    let short = ExecutionProfile { max_output_bytes: 4, ..Default::default() };
+    let err = executor.execute_wasm_with_profile(&echo_module(&[]), input, &short).unwrap_err();
+    assert!(err.to_string().contains("output exceeds 4 bytes"), "{}", err);
+    assert_eq!(ExecutorError::of(&err), Some(ExecutorError::OutputTooLarge { limit: 4 }));
+    Ok(())
+}
+
+#[test]
+fn test_memory_ceiling() -> Result<()> {
+    let profile = ExecutionProfile { max_memory_pages: 1, ..Default::default() };
+    let err = PythonExecutor::new()?.execute_wasm_with_profile(&echo_module(&[]), "{}", &profile).unwrap_err();
+    assert!(err.to_string().contains("host allows 1"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_wall_clock_interrupts_execution() -> Result<()> {
+    let spin = [
+        Instruction::Loop(wasm_encoder::BlockType::Empty),
+        Instruction::Br(0),
+        Instruction::End,
+    ];
+    let profile = ExecutionProfile { fuel_limit: 100_000_000, max_wall_clock_ms: 1, ..Default::default() };
+    let mut executor = PythonExecutor::new()?;
+    let err = executor.execute_wasm_with_profile(&echo_module(&spin), "{}", &profile).unwrap_err();
+    assert!(err.to_string().contains("exceeded 1 ms wall clock"), "{}", err);
+    let trapped = err.downcast_ref::<Trapped>().expect("a timeout is reported as a trap");
+    assert_eq!(trapped.output.error, Some(RuntimeError::Timeout));
+    assert!(trapped.output.fuel_consumed > 0);
+    assert!(matches!(ExecutorError::of(&err), Some(ExecutorError::Timeout { .. })));
+
+    // the interrupt doesn't leak into the next run
+    assert_eq!(executor.execute_wasm_with_profile(&echo_module(&[]), "{}", &ExecutionProfile::default())?.result, "{}");
+    Ok(())
+}
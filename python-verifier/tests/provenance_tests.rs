@@ -0,0 +1,103 @@
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::H256;
+use python_verifier::provenance::{BuildInfo, BuildPolicy, BuildReport};
+use python_verifier::receipts::{Finalization, SignedReceipt};
+
+const KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+fn wallet() -> LocalWallet {
+    KEY.parse().unwrap()
+}
+
+fn build(commit: &str) -> BuildInfo {
+    BuildInfo {
+        version: "0.1.0".to_string(),
+        git_commit: commit.to_string(),
+        lock_hash: "ab".repeat(32),
+    }
+}
+
+#[test]
+fn test_current_build_is_embedded() {
+    let current = BuildInfo::current();
+    assert_eq!(current.version, env!("CARGO_PKG_VERSION"));
+    assert!(!current.git_commit.is_empty());
+    assert!(current.lock_hash == "unknown" || current.lock_hash.len() == 64, "{}", current.lock_hash);
+    assert_eq!(current.id(), BuildInfo::current().id());
+}
+
+#[test]
+fn test_build_id_covers_every_field() {
+    let base = build("1111");
+    assert_ne!(base.id(), build("2222").id());
+    assert_ne!(base.id(), BuildInfo { lock_hash: "cd".repeat(32), ..base.clone() }.id());
+    assert_ne!(base.id(), BuildInfo { version: "0.1.1".to_string(), ..base.clone() }.id());
+    // the separator keeps shifted fields apart
+    assert_ne!(
+        BuildInfo { version: "0.1".to_string(), git_commit: ".0ab".to_string(), ..base.clone() }.id(),
+        BuildInfo { version: "0.1.0".to_string(), git_commit: "ab".to_string(), ..base }.id(),
+    );
+}
+
+#[test]
+fn test_report_signature_recovers_node() {
+    let report = BuildReport::sign(build("1111"), &wallet()).unwrap();
+    assert_eq!(report.node, format!("{:?}", wallet().address()));
+    report.verify().unwrap();
+
+    let mut swapped = report.clone();
+    swapped.build = build("2222");
+    assert!(swapped.verify().is_err());
+
+    let mut relabeled = report;
+    relabeled.build = build("2222");
+    relabeled.build_id = relabeled.build.id_hex();
+    assert!(relabeled.verify().is_err());
+}
+
+#[test]
+fn test_policy_accepts_only_allowlisted_builds() {
+    let allowed = build("1111");
+    let policy = BuildPolicy::new([allowed.id_hex().to_uppercase().replace("0X", "0x")]);
+    assert!(policy.allows_id(&allowed.id_hex()));
+    assert!(policy.allows_id(allowed.id_hex().trim_start_matches("0x")));
+
+    policy.check_report(&BuildReport::sign(allowed, &wallet()).unwrap()).unwrap();
+    let err = policy.check_report(&BuildReport::sign(build("2222"), &wallet()).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("not allowlisted"), "{}", err);
+    assert!(BuildPolicy::default().check_report(&BuildReport::sign(build("1111"), &wallet()).unwrap()).is_err());
+}
+
+#[test]
+fn test_policy_loads_from_file() {
+    let path = std::env::temp_dir().join(format!("certus-build-allowlist-{}.json", std::process::id()));
+    std::fs::write(&path, serde_json::to_vec(&[build("1111").id_hex()]).unwrap()).unwrap();
+    let policy = BuildPolicy::load(path.to_str().unwrap()).unwrap();
+    assert!(policy.allows_id(&build("1111").id_hex()));
+    assert!(!policy.allows_id(&build("2222").id_hex()));
+
+    std::fs::write(&path, b"{\"not\": \"a list\"}").unwrap();
+    assert!(BuildPolicy::load(path.to_str().unwrap()).is_err());
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn test_receipt_signs_build() {
+    let receipt = SignedReceipt::sign(&Finalization {
+        job_id: [1; 32],
+        tx_hash: H256::repeat_byte(0xaa),
+        output_hash: [0x42; 32],
+        fuel_used: 12_345,
+        finalized_at: 1_700_000_000,
+    }, &wallet()).unwrap();
+    assert_eq!(receipt.build.as_deref(), Some(BuildInfo::current().id_hex().as_str()));
+    receipt.verify().unwrap();
+
+    let mut other = receipt.clone();
+    other.build = Some(build("1111").id_hex());
+    assert!(other.verify().is_err());
+
+    let mut stripped = receipt;
+    stripped.build = None;
+    assert!(stripped.verify().is_err());
+}
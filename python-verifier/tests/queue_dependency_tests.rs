@@ -0,0 +1,135 @@
+use python_verifier::queue::{render_input, DependencyFailure, JobQueue, QueuedJob};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+fn open_queue(name: &str) -> JobQueue {
+    let path = std::env::temp_dir().join(format!("certus-deps-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    JobQueue::new(path.to_str().unwrap()).unwrap()
+}
+
+fn job(id: &str, input: serde_json::Value, depends_on: &[&str]) -> QueuedJob {
+    QueuedJob {
+        id: id.to_string(),
+        code: "def main():\n    return 1".to_string(),
+        wasm_b64: None,
+        input,
+        priority: 1,
+        created_at: chrono::Utc::now().timestamp() as u64,
+        retry_count: 0,
+        max_retries: 0,
+        available_at: 0,
+        claimed_at: None,
+        depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+        on_dependency_failure: DependencyFailure::Fail,
+        tenant: None,
+        profile: None,
+        compile_options: Default::default(),
+    }
+}
+
+async fn next_within(queue: &JobQueue, ms: u64) -> Option<QueuedJob> {
+    tokio::time::timeout(Duration::from_millis(ms), queue.next())
+        .await
+        .ok()
+        .map(|r| r.unwrap().unwrap())
+}
+
+#[tokio::test]
+async fn test_dependent_waits_and_receives_output() {
+    let queue = open_queue("chain");
+    queue.submit(job("a", json!({ "x": 21 }), &[])).await.unwrap();
+    queue.submit(job("b", json!({ "x": "{{deps.a.output}}", "note": "from {{deps.a.hash}}" }), &["a"])).await.unwrap();
+
+    let a = next_within(&queue, 500).await.unwrap();
+    assert_eq!(a.id, "a");
+    // b is blocked while a is running
+    assert!(next_within(&queue, 100).await.is_none());
+    assert_eq!(queue.stats().unwrap().jobs_by_status["blocked"], 1);
+
+    queue.complete("a", json!({ "output": 42, "hash": "0xabc" })).await.unwrap();
+
+    let b = next_within(&queue, 500).await.unwrap();
+    assert_eq!(b.id, "b");
+    assert_eq!(b.input, json!({ "x": 42, "note": "from 0xabc" }));
+}
+
+#[tokio::test]
+async fn test_failure_propagates_downstream() {
+    let queue = open_queue("fail");
+    queue.submit(job("a", json!({}), &[])).await.unwrap();
+    queue.submit(job("b", json!({}), &["a"])).await.unwrap();
+    queue.submit(job("c", json!({}), &["b"])).await.unwrap();
+
+    let a = next_within(&queue, 500).await.unwrap();
+    queue.fail_as(&a.id, "wasm trap", None).await.unwrap();
+
+    // b and c never run
+    assert!(next_within(&queue, 300).await.is_none());
+    let stats = queue.stats().unwrap();
+    assert_eq!(stats.jobs_by_status["failed"], 3);
+    assert_eq!(stats.jobs_by_status["blocked"], 0);
+    assert_eq!(stats.failures_by_class["dependency"], 2);
+}
+
+#[tokio::test]
+async fn test_skip_policy() {
+    let queue = open_queue("skip");
+    queue.submit(job("a", json!({}), &[])).await.unwrap();
+    let mut b = job("b", json!({}), &["a"]);
+    b.on_dependency_failure = DependencyFailure::Skip;
+    queue.submit(b).await.unwrap();
+    queue.submit(job("independent", json!({}), &[])).await.unwrap();
+
+    let first = next_within(&queue, 500).await.unwrap();
+    let second = next_within(&queue, 500).await.unwrap();
+    let a = if first.id == "a" { first } else { second };
+    queue.fail_as(&a.id, "rpc error", None).await.unwrap();
+
+    assert!(next_within(&queue, 300).await.is_none());
+    let stats = queue.stats().unwrap();
+    assert_eq!(stats.jobs_by_status["skipped"], 1);
+    assert_eq!(stats.jobs_by_status["failed"], 1);
+    assert!(!stats.failures_by_class.contains_key("dependency"));
+}
+
+#[tokio::test]
+async fn test_submit_rejects_unknown_and_self_dependencies() {
+    let queue = open_queue("reject");
+    assert!(queue.submit(job("b", json!({}), &["missing"])).await.is_err());
+    assert!(queue.submit(job("c", json!({}), &["c"])).await.is_err());
+}
+
+#[tokio::test]
+async fn test_bad_template_fails_job() {
+    let queue = open_queue("template");
+    queue.submit(job("a", json!({}), &[])).await.unwrap();
+    queue.submit(job("b", json!({ "x": "{{deps.a.missing}}" }), &["a"])).await.unwrap();
+
+    let a = next_within(&queue, 500).await.unwrap();
+    queue.complete(&a.id, json!({ "output": 1 })).await.unwrap();
+
+    assert!(next_within(&queue, 300).await.is_none());
+    assert_eq!(queue.stats().unwrap().jobs_by_status["failed"], 1);
+}
+
+#[test]
+fn test_render_input() {
+    let mut results = BTreeMap::new();
+    results.insert("a".to_string(), json!({ "output": [1, 2, 3], "hash": "h" }));
+
+    let rendered = render_input(
+        &json!({ "all": "{{deps.a}}", "second": "{{ deps.a.output.1 }}", "list": ["{{deps.a.hash}}!"], "n": 5 }),
+        &results,
+    ).unwrap();
+    assert_eq!(rendered, json!({
+        "all": { "output": [1, 2, 3], "hash": "h" },
+        "second": 2,
+        "list": ["h!"],
+        "n": 5,
+    }));
+
+    assert!(render_input(&json!("{{deps.b}}"), &results).is_err());
+    assert!(render_input(&json!("{{env.HOME}}"), &results).is_err());
+}
@@ -0,0 +1,115 @@
+use python_verifier::executor_error::ExecutorError;
+use python_verifier::queue::{FailureClass, JobQueue, QueuedJob};
+
+fn open_queue(name: &str) -> JobQueue {
+    let path = std::env::temp_dir().join(format!("certus-queue-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    JobQueue::new(path.to_str().unwrap()).unwrap()
+}
+
+fn job(id: &str, max_retries: u8) -> QueuedJob {
+    QueuedJob {
+        id: id.to_string(),
+        code: "def main():\n    return 1".to_string(),
+        wasm_b64: None,
+        input: serde_json::json!({}),
+        priority: 1,
+        created_at: chrono::Utc::now().timestamp() as u64,
+        retry_count: 0,
+        max_retries,
+        available_at: 0,
+        claimed_at: None,
+        depends_on: vec![],
+        on_dependency_failure: Default::default(),
+        tenant: None,
+        profile: None,
+        compile_options: Default::default(),
+    }
+}
+
+#[tokio::test]
+async fn test_submit_and_complete_counts() {
+    let queue = open_queue("complete");
+    queue.submit(job("a", 3)).await.unwrap();
+    queue.submit(job("b", 3)).await.unwrap();
+
+    let stats = queue.stats().unwrap();
+    assert_eq!(stats.enqueued_total, 2);
+    assert_eq!(stats.jobs_by_status["pending"], 2);
+    assert!(stats.oldest_pending_age_secs.is_some());
+
+    let next = queue.next().await.unwrap().unwrap();
+    queue.complete(&next.id, serde_json::json!({ "output": 1 })).await.unwrap();
+
+    let stats = queue.stats().unwrap();
+    assert_eq!(stats.dequeued_total, 1);
+    assert_eq!(stats.completed_total, 1);
+    assert_eq!(stats.jobs_by_status["pending"], 1);
+    assert_eq!(stats.jobs_by_status["completed"], 1);
+    assert_eq!(stats.wait_time.count, 1);
+    // fast dequeue lands in every bucket
+    assert!(stats.wait_time.buckets.iter().all(|(_, n)| *n == 1));
+}
+
+#[tokio::test]
+async fn test_retries_and_dead_letter() {
+    let queue = open_queue("retry");
+    queue.submit(job("r", 1)).await.unwrap();
+    queue.submit(job("d", 0)).await.unwrap();
+
+    let j = queue.next().await.unwrap().unwrap();
+    queue.fail_as(&j.id, "wasm trap: unreachable", None).await.unwrap();
+    let j = queue.next().await.unwrap().unwrap();
+    queue.fail_as(&j.id, "out of fuel", None).await.unwrap();
+
+    let stats = queue.stats().unwrap();
+    assert_eq!(stats.enqueued_total, 3);
+    assert_eq!(stats.retries_total, 1);
+    assert_eq!(stats.dead_lettered_total, 1);
+    assert_eq!(stats.failures_by_class["execution"], 1);
+    assert_eq!(stats.failures_by_class["timeout"], 1);
+    assert_eq!(stats.jobs_by_status["failed"], 1);
+    // the retried job is backing off
+    assert_eq!(stats.jobs_by_status["pending"], 0);
+    assert_eq!(stats.jobs_by_status["delayed"], 1);
+}
+
+#[test]
+fn test_failure_classification() {
+    assert_eq!(FailureClass::classify("Invalid input"), FailureClass::Validation);
+    assert_eq!(FailureClass::classify("Unsupported statement"), FailureClass::Compile);
+    assert_eq!(FailureClass::classify("deadline exceeded"), FailureClass::Timeout);
+    assert_eq!(FailureClass::classify("nonce too low"), FailureClass::Chain);
+    assert_eq!(FailureClass::classify("something odd"), FailureClass::Other);
+}
+
+#[tokio::test]
+async fn test_prometheus_exposition() {
+    let queue = open_queue("prom");
+    queue.submit(job("p", 0)).await.unwrap();
+    let j = queue.next().await.unwrap().unwrap();
+    queue.fail_as(&j.id, "rpc error", None).await.unwrap();
+
+    let text = queue.stats().unwrap().to_prometheus();
+    assert!(text.contains("# TYPE certus_queue_enqueued_total counter"));
+    assert!(text.contains("certus_queue_enqueued_total 1\n"));
+    assert!(text.contains("certus_queue_failures_total{class=\"chain\"} 1\n"));
+    assert!(text.contains("certus_queue_jobs{status=\"failed\"} 1\n"));
+    assert!(text.contains("certus_queue_wait_seconds_bucket{le=\"+Inf\"} 1\n"));
+    assert!(text.contains("certus_queue_wait_seconds_count 1\n"));
+}
+
+#[tokio::test]
+async fn test_executor_error_kind_decides_class() {
+    let queue = open_queue("kind");
+    queue.submit(job("k", 0)).await.unwrap();
+    let j = queue.next().await.unwrap().unwrap();
+    // the message alone would read as a validation failure
+    let kind = ExecutorError::OutOfFuel { fuel_consumed: 1000 };
+    queue.fail_as(&j.id, "invalid state: out of fuel", Some(&kind)).await.unwrap();
+
+    let stats = queue.stats().unwrap();
+    assert_eq!(stats.failures_by_class["timeout"], 1);
+    assert!(!stats.failures_by_class.contains_key("validation"));
+    assert_eq!(FailureClass::of(&ExecutorError::CompileError { message: String::new() }), FailureClass::Compile);
+}
@@ -0,0 +1,190 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+#[test]
+fn test_range_start_stop() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+total = 0
+for i in range(3, 7):
+    total = total + i
+OUTPUT = total
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 18);  // 3+4+5+6
+    Ok(())
+}
+
+#[test]
+fn test_range_positive_step() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+total = 0
+for i in range(0, 10, 3):
+    total = total + i
+OUTPUT = total
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 18);  // 0+3+6+9
+    Ok(())
+}
+
+#[test]
+fn test_range_negative_step() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+total = 0
+last = 0
+for i in range(10, 0, -2):
+    total = total + i
+    last = i
+OUTPUT = total * 10 + last
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 302);  // visits 10, 8, 6, 4, 2
+    Ok(())
+}
+
+#[test]
+fn test_range_negative_step_excludes_stop() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+last = 99
+for i in range(5, -1, -1):
+    last = i
+OUTPUT = last
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 0);
+    Ok(())
+}
+
+#[test]
+fn test_range_empty_when_start_past_stop() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+count = 0
+for i in range(5, 2):
+    count = count + 1
+for i in range(2, 5, -1):
+    count = count + 1
+OUTPUT = count
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 0);
+    Ok(())
+}
+
+#[test]
+fn test_range_runtime_step() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+up = 2
+down = 0 - 3
+a = 0
+for i in range(0, 9, up):
+    a = a + 1
+b = 0
+for i in range(9, 0, down):
+    b = b + i
+OUTPUT = a * 100 + b
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 518);  // a = 5 (0,2,4,6,8), b = 9+6+3
+    Ok(())
+}
+
+#[test]
+fn test_range_runtime_zero_step_traps() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+step = 0
+count = 0
+for i in range(0, 10, step):
+    count = count + 1
+OUTPUT = count
+"#;
+    let wasm = compiler.compile(code)?;
+    assert!(execute_wasm(&wasm).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_range_literal_zero_step_rejected() {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+for i in range(0, 10, 0):
+    OUTPUT = i
+"#;
+    assert!(compiler.compile(code).is_err());
+}
+
+#[test]
+fn test_range_bounds_evaluated_once() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+n = 4
+count = 0
+for i in range(0, n):
+    n = n + 1
+    count = count + 1
+OUTPUT = count
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 4);
+    Ok(())
+}
+
+#[test]
+fn test_range_with_break() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+found = 0
+for i in range(99, 0, -7):
+    if i % 5 == 0:
+        found = i
+        break
+OUTPUT = found
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 85);  // 99, 92, 85
+    Ok(())
+}
+
+#[test]
+fn test_range_too_many_arguments() {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+for i in range(0, 10, 1, 2):
+    OUTPUT = i
+"#;
+    assert!(compiler.compile(code).is_err());
+}
@@ -0,0 +1,42 @@
+use python_verifier::rate_limit::RateLimiter;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+fn client(n: u8) -> IpAddr {
+    IpAddr::V4(Ipv4Addr::new(10, 0, 0, n))
+}
+
+#[test]
+fn test_burst_then_refuse() {
+    let limiter = RateLimiter::new(60, 3);
+    let now = Instant::now();
+    for _ in 0..3 {
+        limiter.check_at(client(1), now).unwrap();
+    }
+    let wait = limiter.check_at(client(1), now).unwrap_err();
+    assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1), "{:?}", wait);
+}
+
+#[test]
+fn test_tokens_refill_over_time() {
+    let limiter = RateLimiter::new(60, 1);
+    let start = Instant::now();
+    limiter.check_at(client(1), start).unwrap();
+    assert!(limiter.check_at(client(1), start + Duration::from_millis(500)).is_err());
+    limiter.check_at(client(1), start + Duration::from_millis(1600)).unwrap();
+
+    // a long idle period refills only up to the burst
+    let later = start + Duration::from_secs(3600);
+    limiter.check_at(client(1), later).unwrap();
+    assert!(limiter.check_at(client(1), later).is_err());
+}
+
+#[test]
+fn test_clients_have_separate_buckets() {
+    let limiter = RateLimiter::new(30, 1);
+    let now = Instant::now();
+    limiter.check_at(client(1), now).unwrap();
+    assert!(limiter.check_at(client(1), now).is_err());
+    limiter.check_at(client(2), now).unwrap();
+    assert_eq!(limiter.tracked_clients(), 2);
+}
@@ -0,0 +1,151 @@
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::H256;
+use python_verifier::receipts::{
+    deliver_webhook, Finalization, ReceiptStore, SignedReceipt, MAX_DELIVERY_ATTEMPTS,
+};
+
+const KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+fn wallet() -> LocalWallet {
+    KEY.parse().unwrap()
+}
+
+fn open_store(name: &str) -> ReceiptStore {
+    let path = std::env::temp_dir().join(format!("certus-receipts-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    ReceiptStore::open(path.to_str().unwrap()).unwrap()
+}
+
+fn finalization(job: u8) -> Finalization {
+    Finalization {
+        job_id: [job; 32],
+        tx_hash: H256::repeat_byte(0xaa),
+        output_hash: [0x42; 32],
+        fuel_used: 12_345,
+        finalized_at: 1_700_000_000,
+    }
+}
+
+#[test]
+fn test_receipt_signature_recovers_executor() {
+    let receipt = SignedReceipt::sign(&finalization(1), &wallet()).unwrap();
+    assert_eq!(receipt.executor, format!("{:?}", wallet().address()));
+    assert_eq!(receipt.job_id, format!("0x{}", "01".repeat(32)));
+    assert_eq!(receipt.fuel_used, 12_345);
+    receipt.verify().unwrap();
+
+    // the signature is an ordinary personal_sign over the digest
+    let signature: ethers::types::Signature = receipt.signature.trim_start_matches("0x").parse().unwrap();
+    signature.verify(receipt.digest().unwrap().to_vec(), wallet().address()).unwrap();
+}
+
+#[test]
+fn test_tampered_receipt_fails_verification() {
+    let receipt = SignedReceipt::sign(&finalization(1), &wallet()).unwrap();
+
+    let mut fuel = receipt.clone();
+    fuel.fuel_used += 1;
+    assert!(fuel.verify().is_err());
+
+    let mut output = receipt.clone();
+    output.output_hash = format!("0x{}", "43".repeat(32));
+    assert!(output.verify().is_err());
+
+    let mut executor = receipt;
+    executor.executor = format!("{:?}", ethers::types::Address::repeat_byte(1));
+    assert!(executor.verify().is_err());
+}
+
+#[test]
+fn test_issue_is_idempotent() {
+    let store = open_store("idempotent");
+    let first = SignedReceipt::sign(&finalization(2), &wallet()).unwrap();
+    assert_eq!(store.issue(first.clone()).unwrap(), first);
+
+    let mut later = first.clone();
+    later.finalized_at += 60;
+    assert_eq!(store.issue(later).unwrap(), first);
+
+    // lookups accept either id form
+    let upper = first.job_id.trim_start_matches("0x").to_uppercase();
+    assert_eq!(store.receipt(&upper).unwrap().unwrap(), first);
+    assert!(store.receipt(&format!("0x{}", "03".repeat(32))).unwrap().is_none());
+}
+
+#[test]
+fn test_pending_deliveries_until_delivered() {
+    let store = open_store("deliveries");
+    let with_hook = SignedReceipt::sign(&finalization(4), &wallet()).unwrap();
+    let without_hook = SignedReceipt::sign(&finalization(5), &wallet()).unwrap();
+    store.track(&with_hook.job_id, Some("http://client.example/hook".to_string())).unwrap();
+    store.track(&without_hook.job_id, None).unwrap();
+    store.record_execution(&with_hook.job_id, &with_hook.output_hash, 12_345).unwrap();
+    assert!(store.pending_deliveries().unwrap().is_empty());
+
+    store.issue(with_hook.clone()).unwrap();
+    store.issue(without_hook).unwrap();
+    let pending = store.pending_deliveries().unwrap();
+    assert_eq!(pending, vec![(with_hook.clone(), "http://client.example/hook".to_string())]);
+
+    store.record_failed_delivery(&with_hook.job_id, "webhook answered 503").unwrap();
+    assert_eq!(store.pending_deliveries().unwrap().len(), 1);
+    let tracked = store.tracked(&with_hook.job_id).unwrap().unwrap();
+    assert_eq!(tracked.delivery_attempts, 1);
+    assert_eq!(tracked.fuel_used, Some(12_345));
+
+    store.mark_delivered(&with_hook.job_id).unwrap();
+    assert!(store.pending_deliveries().unwrap().is_empty());
+}
+
+#[test]
+fn test_delivery_gives_up_after_max_attempts() {
+    let store = open_store("give-up");
+    let receipt = SignedReceipt::sign(&finalization(6), &wallet()).unwrap();
+    store.track(&receipt.job_id, Some("http://client.example/hook".to_string())).unwrap();
+    store.issue(receipt.clone()).unwrap();
+
+    for _ in 0..MAX_DELIVERY_ATTEMPTS {
+        assert_eq!(store.pending_deliveries().unwrap().len(), 1);
+        store.record_failed_delivery(&receipt.job_id, "unreachable").unwrap();
+    }
+    assert!(store.pending_deliveries().unwrap().is_empty());
+    assert!(store.receipt(&receipt.job_id).unwrap().is_some());
+}
+
+#[test]
+fn test_cursor_persists() {
+    let store = open_store("cursor");
+    assert_eq!(store.cursor().unwrap(), None);
+    store.set_cursor(1_234).unwrap();
+    assert_eq!(store.cursor().unwrap(), Some(1_234));
+}
+
+#[tokio::test]
+async fn test_webhook_receives_receipt_with_idempotency_key() {
+    use axum::{extract::State, http::HeaderMap, routing::post, Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    type Seen = Arc<Mutex<Vec<(String, SignedReceipt)>>>;
+    async fn hook(State(seen): State<Seen>, headers: HeaderMap, Json(receipt): Json<SignedReceipt>) {
+        let key = headers["idempotency-key"].to_str().unwrap().to_string();
+        seen.lock().unwrap().push((key, receipt));
+    }
+
+    let seen: Seen = Arc::default();
+    let app = Router::new().route("/hook", post(hook)).with_state(seen.clone());
+    let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+    let url = format!("http://{}/hook", server.local_addr());
+    tokio::spawn(server);
+
+    let receipt = SignedReceipt::sign(&finalization(7), &wallet()).unwrap();
+    let client = reqwest::Client::new();
+    deliver_webhook(&client, &url, &receipt).await.unwrap();
+
+    let seen = seen.lock().unwrap().clone();
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen[0].0, receipt.job_id);
+    assert_eq!(seen[0].1, receipt);
+    seen[0].1.verify().unwrap();
+
+    assert!(deliver_webhook(&client, &url.replace("/hook", "/missing"), &receipt).await.is_err());
+}
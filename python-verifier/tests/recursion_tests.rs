@@ -0,0 +1,120 @@
+use python_verifier::python_compiler::PythonCompiler;
+use python_verifier::compiler::DEFAULT_MAX_CALL_DEPTH;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+fn run_with_limit(code: &str, depth: u32) -> Result<i32> {
+    let mut compiler = PythonCompiler::new().with_max_call_depth(depth);
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+fn assert_unreachable(result: Result<i32>) {
+    let err = result.expect_err("expected a trap");
+    assert_eq!(err.downcast_ref::<Trap>(), Some(&Trap::UnreachableCodeReached), "{:?}", err);
+}
+
+fn nested(depth: i32) -> String {
+    format!(
+        r#"
+def down(n):
+    if n == 0:
+        return 0
+    return down(n - 1) - -1
+
+OUTPUT = down({})
+"#,
+        // down(d) makes d + 1 frames
+        depth - 1
+    )
+}
+
+#[test]
+fn test_recursion_within_limit() -> Result<()> {
+    assert_eq!(run(&nested(50))?, 49);
+    assert_eq!(run(&nested(DEFAULT_MAX_CALL_DEPTH as i32))?, DEFAULT_MAX_CALL_DEPTH as i32 - 1);
+    Ok(())
+}
+
+#[test]
+fn test_unbounded_recursion_traps() {
+    let code = r#"
+def forever(n):
+    return forever(n - -1)
+
+OUTPUT = forever(0)
+"#;
+    assert_unreachable(run(code));
+}
+
+#[test]
+fn test_configured_limit_is_exact() -> Result<()> {
+    assert_eq!(run_with_limit(&nested(10), 10)?, 9);
+    assert_unreachable(run_with_limit(&nested(11), 10));
+    assert_unreachable(run(&nested(DEFAULT_MAX_CALL_DEPTH as i32 + 1)));
+    Ok(())
+}
+
+#[test]
+fn test_depth_unwinds_on_return() -> Result<()> {
+    // many shallow calls never add up to the limit
+    let code = r#"
+# certus: noinline
+def one(n):
+    return n
+
+def two(n):
+    if n > 5:
+        return one(n)
+    x = one(n)
+
+total = 0
+for i in range(50):
+    total = total - two(i)
+OUTPUT = total
+"#;
+    assert_eq!(run_with_limit(code, 3)?, -(6..50).sum::<i32>());
+    Ok(())
+}
+
+#[test]
+fn test_limit_independent_of_engine_stack() -> Result<()> {
+    // the trap comes from the depth counter, not whichever engine stack runs out first
+    let wasm = PythonCompiler::new().compile(&nested(DEFAULT_MAX_CALL_DEPTH as i32 + 1))?;
+    for stack in [256 * 1024, 4 * 1024 * 1024] {
+        let engine = Engine::new(Config::new().max_wasm_stack(stack))?;
+        let mut store = Store::new(&engine, ());
+        let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+        let module = Module::new(&engine, &wasm)?;
+        let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+        let err = instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ()).unwrap_err();
+        assert_eq!(err.downcast_ref::<Trap>(), Some(&Trap::UnreachableCodeReached), "stack {}: {:?}", stack, err);
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use python_verifier::redaction::{RedactionPolicy, MASK};
+use python_verifier::websocket::{broadcast_update, JobUpdate, WsState};
+use serde_json::json;
+
+#[test]
+fn test_masks_sensitive_fields_recursively() {
+    let policy = RedactionPolicy::default();
+    let input = json!({
+        "x": 1,
+        "API_KEY": "abc",
+        "nested": { "db_password": "hunter2", "list": [{ "auth_token": "t" }] },
+    });
+
+    let redacted = policy.redact_value(&input, None);
+    assert_eq!(redacted, json!({
+        "x": 1,
+        "API_KEY": MASK,
+        "nested": { "db_password": MASK, "list": [{ "auth_token": MASK }] },
+    }));
+}
+
+#[test]
+fn test_truncates_long_strings() {
+    let policy = RedactionPolicy { max_string_len: 4, ..Default::default() };
+    let redacted = policy.redact_value(&json!({ "note": "abcdefgh" }), None);
+    assert_eq!(redacted, json!({ "note": "abcd...[4 bytes truncated]" }));
+
+    // never splits a multi-byte character
+    let text = policy.redact_text("aaé€", None);
+    assert!(text.starts_with("aaé..."));
+}
+
+#[test]
+fn test_oversized_value_replaced_by_digest() {
+    let policy = RedactionPolicy { max_value_len: 32, ..Default::default() };
+    let big = json!({ "items": (0..50).collect::<Vec<i32>>() });
+
+    let redacted = policy.redact_value(&big, None);
+    assert_eq!(redacted["truncated"], json!(true));
+    assert_eq!(redacted["bytes"], json!(big.to_string().len()));
+    assert_eq!(redacted["sha256"].as_str().unwrap().len(), 64);
+}
+
+#[test]
+fn test_exempt_tenant_passes_through() {
+    let policy = RedactionPolicy {
+        max_string_len: 2,
+        exempt_tenants: vec!["acme".to_string()],
+        ..Default::default()
+    };
+    let input = json!({ "secret": "s3cr3t" });
+
+    assert_eq!(policy.redact_value(&input, Some("acme")), input);
+    assert_eq!(policy.redact_text("long message", Some("acme")), "long message");
+    assert_eq!(policy.redact_value(&input, Some("other")), json!({ "secret": MASK }));
+}
+
+#[test]
+fn test_broadcast_redacts_update_data() {
+    let state = WsState::new();
+    let mut rx = state.tx.subscribe();
+
+    broadcast_update(&state, JobUpdate::new("j", "completed", 0, json!({ "output": 1, "private_key": "0xdead" }), None));
+
+    let update = rx.try_recv().unwrap();
+    assert_eq!(update.data, json!({ "output": 1, "private_key": MASK }));
+    // tenant is omitted from the wire format when unset
+    assert!(!serde_json::to_string(&update).unwrap().contains("tenant"));
+}
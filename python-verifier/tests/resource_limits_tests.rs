@@ -0,0 +1,127 @@
+use python_verifier::compiler::abi::ExportedLimits;
+use python_verifier::compiler::fuel::{FUEL_EXPORT, FUEL_LIMIT};
+use python_verifier::compiler::{ModuleLimits, ResourceLimits, MAX_PYTHON_SIZE};
+use python_verifier::profiles::ExecutionProfile;
+use python_verifier::python_compiler::PythonCompiler;
+use python_verifier::PythonExecutor;
+use python_verifier::compiler::CompileOptions;
+use anyhow::Result;
+use wasmparser::{Parser, Payload, TypeRef};
+use wasmtime::*;
+
+const PAGE: u64 = 65536;
+
+const LOOP: &str = "total = 0\nfor i in range(100000):\n    total = total + i\nOUTPUT = total % 1000";
+
+// Run main with as much memory as the module imports up front, and its fuel counter after
+fn run(wasm: &[u8]) -> (Result<i32>, i32) {
+    let (minimum, maximum) = memory_import(wasm);
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(minimum as u32, maximum.map(|m| m as u32))).unwrap();
+    let module = Module::new(&engine, wasm).unwrap();
+    let instance = Instance::new(&mut store, &module, &[memory.into()]).unwrap();
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main").unwrap();
+    let result = main.call(&mut store, ());
+    let counter = instance.get_global(&mut store, FUEL_EXPORT).unwrap().get(&mut store).unwrap_i32();
+    (result, counter)
+}
+
+fn memory_import(wasm: &[u8]) -> (u64, Option<u64>) {
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::ImportSection(reader) = payload.unwrap() {
+            for import in reader {
+                if let TypeRef::Memory(ty) = import.unwrap().ty {
+                    return (ty.initial, ty.maximum);
+                }
+            }
+        }
+    }
+    panic!("module imports no memory");
+}
+
+#[test]
+fn test_job_limits_are_built_into_the_module() -> Result<()> {
+    let limits = ResourceLimits::from_job(50_000, 8 * PAGE, 64);
+    assert_eq!(limits.memory_pages, 8);
+    let wasm = PythonCompiler::new().with_resource_limits(limits).compile("OUTPUT = 7")?;
+
+    let exported = ExportedLimits::read(&wasm)?.expect("compiled modules export their limits");
+    assert_eq!(exported.gas_limit, 50_000);
+    assert_eq!(exported.heap_limit, 8 * PAGE as u32);
+    assert!(exported.check(50_000, 8).is_ok());
+    assert!(exported.check(50_001, 8).is_err());
+
+    assert_eq!(ModuleLimits::read(&wasm)?.expect("limits section").memory_pages, 8);
+    assert_eq!(memory_import(&wasm), (8, Some(8)));
+    assert_eq!(run(&wasm).0?, 7);
+    Ok(())
+}
+
+#[test]
+fn test_default_limits_keep_the_ceilings() -> Result<()> {
+    let wasm = PythonCompiler::new().compile("OUTPUT = 7")?;
+    let exported = ExportedLimits::read(&wasm)?.unwrap();
+    assert_eq!(exported.gas_limit, FUEL_LIMIT as u32);
+    assert_eq!(exported.heap_limit, 0x400000);
+    assert_eq!(memory_import(&wasm), (16, Some(256)));
+
+    // fuel above the ceiling stops at the ceiling
+    let limits = ResourceLimits { fuel_limit: u64::MAX, ..Default::default() };
+    assert_eq!(limits.gas_limit(), FUEL_LIMIT);
+    Ok(())
+}
+
+#[test]
+fn test_counter_traps_at_the_job_fuel() -> Result<()> {
+    let (result, counter) = run(&PythonCompiler::new().compile(LOOP)?);
+    assert!(result.is_ok());
+    assert!(counter > 20_000);
+
+    let limits = ResourceLimits { fuel_limit: 20_000, ..Default::default() };
+    let (result, counter) = run(&PythonCompiler::new().with_resource_limits(limits).compile(LOOP)?);
+    assert!(result.is_err());
+    assert!(counter > 20_000);
+    Ok(())
+}
+
+#[test]
+fn test_cache_is_kept_per_limits() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let small = ResourceLimits { fuel_limit: 10_000, ..Default::default() };
+    let (default_wasm, _) = compiler.compile_with_source_map(LOOP)?;
+    let (small_wasm, _) = compiler.compile_with_limits(LOOP, &small)?;
+    assert_ne!(default_wasm, small_wasm);
+    assert_eq!(compiler.compile(LOOP)?, default_wasm);
+    assert_eq!(compiler.compile_with_limits(LOOP, &small)?.0, small_wasm);
+    Ok(())
+}
+
+#[test]
+fn test_unusable_limits_are_rejected() {
+    let no_heap = ResourceLimits::from_job(50_000, PAGE, 64);
+    let err = PythonCompiler::new().with_resource_limits(no_heap).compile("OUTPUT = 1").unwrap_err();
+    assert!(err.to_string().contains("no room for a heap"), "{}", err);
+
+    let no_fuel = ResourceLimits { fuel_limit: 0, ..Default::default() };
+    assert!(PythonCompiler::new().with_resource_limits(no_fuel).compile("OUTPUT = 1").is_err());
+
+    let tiny = ResourceLimits { max_source_bytes: 1024, ..Default::default() };
+    let source = format!("OUTPUT = 1\n{}", "# padding\n".repeat(200));
+    let err = PythonCompiler::new().with_resource_limits(tiny).compile(&source).unwrap_err();
+    assert!(err.to_string().contains("exceeds 1KB"), "{}", err);
+    assert_eq!(ResourceLimits::default().max_source_bytes, MAX_PYTHON_SIZE);
+}
+
+#[test]
+fn test_profile_limits_reach_the_compiler() -> Result<()> {
+    let profile = ExecutionProfile::default().with_chain_limits(5_000, 8 * PAGE, 32);
+    let limits = profile.resource_limits();
+    assert_eq!(limits, ResourceLimits { fuel_limit: 5_000, memory_pages: 8, max_output_bytes: 32, max_source_bytes: MAX_PYTHON_SIZE });
+
+    let wasm = PythonExecutor::new()?.compile("OUTPUT = 1", &profile, CompileOptions::default())?;
+    let exported = ExportedLimits::read(&wasm)?.unwrap();
+    assert_eq!((exported.gas_limit, exported.heap_limit), (5_000, 8 * PAGE as u32));
+    assert!(exported.check(limits.fuel_limit, limits.memory_pages).is_ok());
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use python_verifier::compiler::fuel::{FUEL_EXPORT, FUEL_LIMIT};
+use python_verifier::compiler::{RuntimeError, ERROR_SLOT};
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Run main with room for the whole heap; on a trap, hand back the memory it left and the
+// exported fuel counter
+fn run(code: &str) -> std::result::Result<i32, (Vec<u8>, i32)> {
+    let wasm = PythonCompiler::new().compile(code).expect("compiles");
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(64, Some(256))).unwrap();
+    let module = Module::new(&engine, &wasm).unwrap();
+    let instance = Instance::new(&mut store, &module, &[memory.into()]).unwrap();
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main").unwrap();
+    main.call(&mut store, ()).map_err(|err| {
+        assert_eq!(err.downcast_ref::<Trap>(), Some(&Trap::UnreachableCodeReached), "{:?}", err);
+        let fuel = instance.get_global(&mut store, FUEL_EXPORT).expect("fuel counter export").get(&mut store).unwrap_i32();
+        (memory.data(&store).to_vec(), fuel)
+    })
+}
+
+fn error_of(code: &str) -> Option<RuntimeError> {
+    let (memory, _) = run(code).expect_err("expected a trap");
+    RuntimeError::read(&memory)
+}
+
+#[test]
+fn test_traps_record_their_error() {
+    let cases = [
+        ("xs = [1, 2]\nOUTPUT = xs[5]", RuntimeError::IndexOutOfRange),
+        ("s = 'ab'\nOUTPUT = len(s[3])", RuntimeError::IndexOutOfRange),
+        ("xs = []\nOUTPUT = xs.pop()", RuntimeError::IndexOutOfRange),
+        ("def div(a, b):\n    return a // b\nOUTPUT = div(1, 0)", RuntimeError::DivisionByZero),
+        ("d = {1: 2}\nOUTPUT = d[3]", RuntimeError::KeyError),
+        ("raise ValueError", RuntimeError::ValueError),
+        ("x = 5\nassert x < 3, \"too big\"\nOUTPUT = x", RuntimeError::AssertionFailed),
+        ("def f(n):\n    return f(n + 1)\nOUTPUT = f(0)", RuntimeError::RecursionLimit),
+        ("x = 5\nOUTPUT = len(x)", RuntimeError::TypeError),
+        ("xs = [0]\nwhile True:\n    xs = xs + xs\nOUTPUT = 0", RuntimeError::OutOfMemory),
+    ];
+    for (code, expected) in cases {
+        assert_eq!(error_of(code), Some(expected), "{}", code);
+    }
+}
+
+#[test]
+fn test_uncaught_exception_after_try_records_its_kind() {
+    // with a try in the module, the error unwinds to main before trapping
+    let code = "try:\n    x = 1 // 0\nexcept KeyError:\n    x = 2\nOUTPUT = x";
+    assert_eq!(error_of(code), Some(RuntimeError::DivisionByZero));
+    let code = "def get(d, k):\n    return d[k]\ntry:\n    x = 1\nexcept ValueError:\n    x = 2\nOUTPUT = get({}, x)";
+    assert_eq!(error_of(code), Some(RuntimeError::KeyError));
+}
+
+#[test]
+fn test_running_out_of_fuel_leaves_counter_past_limit() {
+    let (memory, fuel) = run("i = 0\nwhile True:\n    i += 1\nOUTPUT = i").expect_err("expected a trap");
+    assert!(fuel > FUEL_LIMIT, "{}", fuel);
+    assert_eq!(RuntimeError::read(&memory), None);
+}
+
+#[test]
+fn test_successful_runs_leave_slot_clear() -> Result<()> {
+    let wasm = PythonCompiler::new().compile("xs = [1, 2, 3]\nOUTPUT = xs[2]")?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    assert_eq!(instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())?, 3);
+    let slot = ERROR_SLOT as usize;
+    assert_eq!(memory.data(&store)[slot..slot + 4], [0; 4]);
+    Ok(())
+}
+
+#[test]
+fn test_codes_round_trip() {
+    for error in RuntimeError::ALL {
+        assert_eq!(RuntimeError::from_code(error.code()), Some(error));
+    }
+    assert_eq!(RuntimeError::from_code(0), None);
+    assert_eq!(serde_json::to_string(&RuntimeError::IndexOutOfRange).unwrap(), "\"index_out_of_range\"");
+}
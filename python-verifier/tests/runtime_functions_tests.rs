@@ -0,0 +1,87 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+fn compile(code: &str) -> Result<Vec<u8>> {
+    PythonCompiler::new().compile(code)
+}
+
+fn run(wasm: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())
+}
+
+fn function_count(wasm: &[u8]) -> usize {
+    wasmparser::Parser::new(0)
+        .parse_all(wasm)
+        .filter(|payload| matches!(payload, Ok(wasmparser::Payload::CodeSectionEntry(_))))
+        .count()
+}
+
+// `n` statements each concatenating, slicing and hashing
+fn repeated(n: usize) -> String {
+    let mut code = String::from("import hashlib\nt = 0\n");
+    for i in 0..n {
+        code.push_str(&format!("s{i} = 'ab' + str({i}) + 'cd'\n"));
+        code.push_str(&format!("t += len(s{i}[1:4]) + hashlib.sha256(s{i}.encode()).digest()[0]\n"));
+    }
+    code.push_str("OUTPUT = t\n");
+    code
+}
+
+#[test]
+fn test_helpers_are_emitted_once() -> Result<()> {
+    let once = compile(&repeated(1))?;
+    let many = compile(&repeated(12))?;
+    assert_eq!(function_count(&once), function_count(&many));
+
+    // each extra site costs a few calls, not another copy of sha256
+    let per_site = (many.len() - once.len()) / 11;
+    assert!(per_site < 600, "{} bytes per repeated site", per_site);
+    assert!(many.len() < 24 * 1024, "module is {} bytes", many.len());
+    Ok(())
+}
+
+#[test]
+fn test_shared_helpers_compute_the_same_results() -> Result<()> {
+    let expected: i32 = (0..12)
+        .map(|i| {
+            use sha2::{Digest, Sha256};
+            let s = format!("ab{}cd", i);
+            s[1..4].len() as i32 + Sha256::digest(s.as_bytes())[0] as i32
+        })
+        .sum();
+    assert_eq!(run(&compile(&repeated(12))?)?, expected);
+    Ok(())
+}
+
+#[test]
+fn test_unused_helpers_are_not_emitted() -> Result<()> {
+    // main and the fuel charge every module has
+    assert_eq!(function_count(&compile("OUTPUT = 6 * 7")?), 2);
+    // `+` dispatches at run time and may meet strings or bytes, so it brings both concatenations
+    assert_eq!(function_count(&compile("s = 'a' + 'b'\nOUTPUT = len(s)")?), 5);
+    assert_eq!(function_count(&compile("OUTPUT = str(42) == '42'")?), 4);
+    Ok(())
+}
+
+#[test]
+fn test_helpers_from_user_functions() -> Result<()> {
+    let code = r#"
+# certus: noinline
+def label(n):
+    return "item-" + str(n)
+
+# certus: noinline
+def same(a, b):
+    return a == b
+
+OUTPUT = len(label(7)) * 10 + same(label(3), "item-3") + ("m-3" in label(3))
+"#;
+    assert_eq!(run(&compile(code)?)?, 62);
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+#[test]
+fn test_set_literal_len() -> Result<()> {
+    let code = r#"
+s = {1, 2, 3, 2, 1}
+OUTPUT = len(s)
+"#;
+    assert_eq!(run(code)?, 3);
+    Ok(())
+}
+
+#[test]
+fn test_set_membership() -> Result<()> {
+    let code = r#"
+s = {5, 10, 15}
+a = 10 in s
+b = 11 in s
+c = 11 not in s
+OUTPUT = a * 100 + b * 10 + c
+"#;
+    assert_eq!(run(code)?, 101);
+    Ok(())
+}
+
+#[test]
+fn test_set_add() -> Result<()> {
+    let code = r#"
+s = set()
+s.add(4)
+s.add(7)
+s.add(4)
+OUTPUT = len(s) * 10 + (7 in s)
+"#;
+    assert_eq!(run(code)?, 21);
+    Ok(())
+}
+
+#[test]
+fn test_set_dedup_in_loop() -> Result<()> {
+    let code = r#"
+seen = set()
+for i in range(50):
+    seen.add(i % 7)
+OUTPUT = len(seen)
+"#;
+    assert_eq!(run(code)?, 7);
+    Ok(())
+}
+
+#[test]
+fn test_set_comprehension() -> Result<()> {
+    let code = r#"
+s = {x % 4 for x in range(20) if x > 2}
+OUTPUT = len(s) * 10 + (0 in s)
+"#;
+    assert_eq!(run(code)?, 41);
+    Ok(())
+}
+
+#[test]
+fn test_set_in_condition() -> Result<()> {
+    let code = r#"
+allowed = {2, 3, 5, 7}
+count = 0
+for i in range(10):
+    if i in allowed:
+        count = count + 1
+OUTPUT = count
+"#;
+    assert_eq!(run(code)?, 4);
+    Ok(())
+}
+
+#[test]
+fn test_add_on_non_set_traps() -> Result<()> {
+    let code = r#"
+xs = [1, 2]
+xs.add(3)
+OUTPUT = 1
+"#;
+    assert!(run(code).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_dict_membership() -> Result<()> {
+    let code = r#"
+d = {1: 0, 2: 20}
+OUTPUT = (1 in d) * 100 + (2 in d) * 10 + (3 in d)
+"#;
+    assert_eq!(run(code)?, 110);
+    Ok(())
+}
+
+#[test]
+fn test_dict_comprehension() -> Result<()> {
+    let code = r#"
+d = {i: i * i for i in range(10) if i % 3 == 0}
+OUTPUT = len(d) * 100 + (9 in d) * 10 + (4 in d)
+"#;
+    assert_eq!(run(code)?, 410);
+    Ok(())
+}
+
+#[test]
+fn test_dict_comprehension_overwrites_keys() -> Result<()> {
+    let code = r#"
+d = {i % 5: i for i in range(40)}
+OUTPUT = len(d)
+"#;
+    assert_eq!(run(code)?, 5);
+    Ok(())
+}
+
+#[test]
+fn test_in_on_int_traps() -> Result<()> {
+    let code = r#"
+x = 5
+OUTPUT = 1 in x
+"#;
+    assert!(run(code).is_err());
+    Ok(())
+}
@@ -0,0 +1,89 @@
+// Size and fuel of the sha256 runtime function. Rounds read K and w[round] from linear memory;
+// selecting them through 64-way if ladders cost two ladders per round, 128 per block.
+
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmparser::{Operator, Parser, Payload};
+use wasmtime::*;
+
+fn program(message_len: usize) -> String {
+    format!("import hashlib\nh = hashlib.sha256(b\"{}\").digest()\nOUTPUT = h[0]", "a".repeat(message_len))
+}
+
+fn fuel_used(wasm: &[u8]) -> Result<u64> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)?;
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(u64::MAX / 2)?;
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())?;
+    Ok(u64::MAX / 2 - store.get_fuel()?)
+}
+
+// (body bytes, if count) of the largest function, which is sha256
+fn largest_function(wasm: &[u8]) -> Result<(usize, usize)> {
+    let mut largest = (0, 0);
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::CodeSectionEntry(body) = payload? {
+            let size = body.range().len();
+            if size > largest.0 {
+                let mut ifs = 0;
+                let mut reader = body.get_operators_reader()?;
+                while !reader.eof() {
+                    if let Operator::If { .. } = reader.read()? {
+                        ifs += 1;
+                    }
+                }
+                largest = (size, ifs);
+            }
+        }
+    }
+    Ok(largest)
+}
+
+#[test]
+fn test_no_constant_ladders() -> Result<()> {
+    let wasm = PythonCompiler::new().compile(&program(3))?;
+    let (size, ifs) = largest_function(&wasm)?;
+    eprintln!("sha256 body: {} bytes, {} ifs; module {} bytes", size, ifs, wasm.len());
+    // only the heap overflow checks branch
+    assert!(ifs < 8, "{} ifs in sha256", ifs);
+    Ok(())
+}
+
+#[test]
+fn test_constants_live_in_a_passive_segment() -> Result<()> {
+    let wasm = PythonCompiler::new().compile(&program(3))?;
+    let mut segments = vec![];
+    for payload in Parser::new(0).parse_all(&wasm) {
+        if let Payload::DataSection(reader) = payload? {
+            for data in reader {
+                let data = data?;
+                assert!(matches!(data.kind, wasmparser::DataKind::Passive));
+                segments.push(data.data.len());
+            }
+        }
+    }
+    // the constants, then the b"aaa" literal
+    assert_eq!(segments, vec![256, 3]);
+
+    // modules that don't hash carry no segment
+    let plain = PythonCompiler::new().compile("OUTPUT = 6 * 7")?;
+    assert!(!Parser::new(0).parse_all(&plain).any(|p| matches!(p, Ok(Payload::DataSection(_)))));
+    Ok(())
+}
+
+#[test]
+fn test_fuel_per_block() -> Result<()> {
+    // 0 and 120 bytes pad to one and three blocks
+    let one = fuel_used(&PythonCompiler::new().compile(&program(0))?)?;
+    let three = fuel_used(&PythonCompiler::new().compile(&program(120))?)?;
+    let per_block = (three - one) / 2;
+    eprintln!("sha256 fuel per 64-byte block: {}", per_block);
+    // the ladders alone cost over 30k per block
+    assert!(per_block < 15_000, "{} fuel per block", per_block);
+    Ok(())
+}
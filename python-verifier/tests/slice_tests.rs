@@ -0,0 +1,156 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+#[test]
+fn test_negative_list_index() -> Result<()> {
+    let result = run(r#"
+x = [10, 20, 30]
+OUTPUT = x[-1] + x[-3]
+"#)?;
+    assert_eq!(result, 40);
+    Ok(())
+}
+
+#[test]
+fn test_negative_string_index() -> Result<()> {
+    let result = run(r#"
+s = "abc"
+OUTPUT = s[-1]
+"#)?;
+    assert_eq!(result, 99);
+    Ok(())
+}
+
+#[test]
+fn test_negative_index_out_of_range_traps() -> Result<()> {
+    assert!(run("x = [1, 2]\nOUTPUT = x[-3]\n").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_negative_subscript_assign_and_pop() -> Result<()> {
+    let result = run(r#"
+x = [1, 2, 3]
+x[-1] = 9
+last = x.pop(-1)
+OUTPUT = last * 10 + len(x)
+"#)?;
+    assert_eq!(result, 92);
+    Ok(())
+}
+
+#[test]
+fn test_string_step_slice() -> Result<()> {
+    let result = run(r#"
+s = "abcdef"
+t = s[::2]
+OUTPUT = len(t) * 1000 + t[1] * 10 + (t[2] - 100)
+"#)?;
+    // "ace"
+    assert_eq!(result, 3 * 1000 + 99 * 10 + 1);
+    Ok(())
+}
+
+#[test]
+fn test_string_reverse_slice() -> Result<()> {
+    let result = run(r#"
+s = "abc"
+r = s[::-1]
+OUTPUT = r[0] * 10 + len(r)
+"#)?;
+    assert_eq!(result, 99 * 10 + 3);
+    Ok(())
+}
+
+#[test]
+fn test_string_negative_bounds() -> Result<()> {
+    let result = run(r#"
+s = "hello"
+t = s[-3:]
+u = s[:-4]
+OUTPUT = len(t) * 10 + len(u)
+"#)?;
+    assert_eq!(result, 31);
+    Ok(())
+}
+
+#[test]
+fn test_list_slice() -> Result<()> {
+    let result = run(r#"
+x = [0, 1, 2, 3, 4, 5, 6]
+y = x[1:6:2]
+z = x[5:1:-2]
+OUTPUT = len(y) * 1000 + y[2] * 100 + len(z) * 10 + z[1]
+"#)?;
+    // y = [1, 3, 5], z = [5, 3]
+    assert_eq!(result, 3000 + 500 + 20 + 3);
+    Ok(())
+}
+
+#[test]
+fn test_list_slice_is_a_copy() -> Result<()> {
+    let result = run(r#"
+x = [1, 2, 3]
+y = x[:]
+y.append(4)
+y[0] = 7
+OUTPUT = x[0] * 100 + len(x) * 10 + len(y)
+"#)?;
+    assert_eq!(result, 134);
+    Ok(())
+}
+
+#[test]
+fn test_tuple_slice_stays_tuple() -> Result<()> {
+    assert_eq!(run("t = (1, 2, 3)\nu = t[1:]\nOUTPUT = u[0] * 10 + len(u)\n")?, 22);
+    assert!(run("t = (1, 2, 3)\nu = t[1:]\nu.append(4)\nOUTPUT = 0\n").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_empty_and_clamped_slices() -> Result<()> {
+    let result = run(r#"
+x = [1, 2, 3]
+a = x[5:]
+b = x[-10:2]
+c = x[2:0]
+OUTPUT = len(a) * 100 + len(b) * 10 + len(c)
+"#)?;
+    assert_eq!(result, 20);
+    Ok(())
+}
+
+#[test]
+fn test_zero_step() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    assert!(compiler.compile("s = \"abc\"\nOUTPUT = len(s[::0])\n").is_err());
+    assert!(run("s = \"abc\"\nk = 0\nOUTPUT = len(s[::k])\n").is_err());
+    Ok(())
+}
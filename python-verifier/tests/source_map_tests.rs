@@ -0,0 +1,111 @@
+use python_verifier::compiler::source_map::FunctionLines;
+use python_verifier::compiler::{SourceLocation, SourceMap};
+use python_verifier::python_compiler::PythonCompiler;
+use python_verifier::trap_location;
+use anyhow::Result;
+use wasmparser::{Parser, Payload};
+use wasmtime::*;
+
+const CHECK: &str = "\
+# certus: noinline
+def check(x):
+    y = x * 2
+    assert y < 10, \"too big\"
+    return y
+a = check(3)
+b = check(7)
+OUTPUT = a + b
+";
+
+fn run(wasm: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())
+}
+
+fn name_section(wasm: &[u8]) -> Vec<u8> {
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::CustomSection(section) = payload.unwrap() {
+            if section.name() == "name" {
+                return section.data().to_vec();
+            }
+        }
+    }
+    panic!("module has no name section");
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[test]
+fn test_trap_is_located_on_its_python_line() -> Result<()> {
+    let (wasm, map) = PythonCompiler::new().compile_with_source_map(CHECK)?;
+    let err = run(&wasm).unwrap_err();
+    assert_eq!(trap_location(&map, &err), Some(SourceLocation { function: "check".to_string(), line: 4 }));
+
+    // the engine names the frames from the name section
+    let backtrace = err.downcast_ref::<WasmBacktrace>().expect("traps carry a backtrace");
+    let names: Vec<_> = backtrace.frames().iter().filter_map(|frame| frame.func_name()).collect();
+    assert_eq!(names, ["check", "main"]);
+    Ok(())
+}
+
+#[test]
+fn test_each_statement_starts_its_line() -> Result<()> {
+    let (wasm, map) = PythonCompiler::new().compile_with_source_map("a = 1\n\nb = a + 2\nOUTPUT = b\n")?;
+    assert_eq!(run(&wasm)?, 3);
+
+    let main = &map.functions[0];
+    assert_eq!((main.index, main.name.as_str()), (0, "main"));
+    assert_eq!(main.lines.iter().map(|&(_, line)| line).collect::<Vec<_>>(), [1, 3, 4]);
+    assert!(main.lines.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    Ok(())
+}
+
+#[test]
+fn test_name_section_names_functions_and_user_locals() -> Result<()> {
+    let code = "total = 0\nfor i in range(3):\n    total = total + len(str(i))\nOUTPUT = total\n";
+    let wasm = PythonCompiler::new().compile(code)?;
+    let names = name_section(&wasm);
+    for name in [&b"main"[..], b"total", b"$from_int"] {
+        assert!(contains(&names, name), "{}", String::from_utf8_lossy(name));
+    }
+    // compiler temps stay unnamed
+    assert!(!contains(&names, b"__"));
+
+    let wasm = PythonCompiler::new().compile(CHECK)?;
+    let names = name_section(&wasm);
+    assert!(contains(&names, b"check") && contains(&names, b"y"));
+    Ok(())
+}
+
+#[test]
+fn test_source_map_is_cached_with_the_module() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let first = compiler.compile_with_source_map(CHECK)?;
+    let second = compiler.compile_with_source_map(CHECK)?;
+    assert_eq!(first, second);
+    assert_eq!(compiler.compile(CHECK)?, first.0);
+
+    let json = serde_json::to_string(&first.1)?;
+    assert_eq!(serde_json::from_str::<SourceMap>(&json)?, first.1);
+    Ok(())
+}
+
+#[test]
+fn test_runtime_frames_defer_to_their_caller() {
+    let map = SourceMap {
+        functions: vec![FunctionLines { index: 0, name: "main".to_string(), lines: vec![(10, 1), (40, 2)] }],
+    };
+    let at = |line| Some(SourceLocation { function: "main".to_string(), line });
+    assert_eq!(map.locate(0, 39), at(1));
+    assert_eq!(map.locate(0, 40), at(2));
+    // the prologue has no line, nor has a runtime function
+    assert_eq!(map.locate(0, 5), None);
+    assert_eq!(map.locate(3, 50), None);
+    assert_eq!(map.locate_frames([(3, 50), (0, 45)]), at(2));
+}
@@ -0,0 +1,187 @@
+use python_verifier::profiles::ExecutionProfile;
+use python_verifier::step_trace::{self, StepStatus};
+use python_verifier::PythonExecutor;
+use anyhow::Result;
+use wasm_encoder::{
+    BlockType, CodeSection, ExportKind, ExportSection, Function, FunctionSection, ImportSection, Instruction,
+    MemoryType, Module, TypeSection, ValType,
+};
+
+// python_main(input_ptr, input_len) counts input_len down to zero, then returns the input's
+// record, so the run burns fuel in proportion to the input and the output is the input
+fn countdown_module(trap: bool) -> Vec<u8> {
+    let mut module = Module::new();
+
+    let mut types = TypeSection::new();
+    types.function([ValType::I32, ValType::I32], [ValType::I32]);
+    module.section(&types);
+
+    let mut imports = ImportSection::new();
+    imports.import("env", "memory", MemoryType { minimum: 1, maximum: None, memory64: false, shared: false });
+    module.section(&imports);
+
+    let mut funcs = FunctionSection::new();
+    funcs.function(0);
+    module.section(&funcs);
+
+    let mut exports = ExportSection::new();
+    exports.export("python_main", ExportKind::Func, 0);
+    exports.export("memory", ExportKind::Memory, 0);
+    module.section(&exports);
+
+    let mut code = CodeSection::new();
+    let mut main = Function::new([]);
+    for instruction in [
+        Instruction::Block(BlockType::Empty),
+        Instruction::Loop(BlockType::Empty),
+        Instruction::LocalGet(1),
+        Instruction::I32Eqz,
+        Instruction::BrIf(1),
+        Instruction::LocalGet(1),
+        Instruction::I32Const(1),
+        Instruction::I32Sub,
+        Instruction::LocalSet(1),
+        Instruction::Br(0),
+        Instruction::End,
+        Instruction::End,
+    ] {
+        main.instruction(&instruction);
+    }
+    if trap {
+        main.instruction(&Instruction::Unreachable);
+    }
+    main.instruction(&Instruction::LocalGet(0));
+    main.instruction(&Instruction::I32Const(4));
+    main.instruction(&Instruction::I32Sub);
+    main.instruction(&Instruction::End);
+    code.function(&main);
+    module.section(&code);
+
+    module.finish()
+}
+
+fn input(len: usize) -> String {
+    format!("\"{}\"", "a".repeat(len))
+}
+
+fn profile(fuel_limit: u64) -> ExecutionProfile {
+    ExecutionProfile { fuel_limit, ..Default::default() }
+}
+
+#[test]
+fn test_trace_follows_the_job_run() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let client = countdown_module(false);
+    let wasm = executor.prepare_wasm(&client)?;
+    let input = input(300);
+
+    let trace = executor.execute_stepped(&wasm, &input, &profile(1_000_000), 200)?;
+    assert_eq!(trace.outcome, StepStatus::Returned);
+    assert!(trace.total_steps() > 2);
+
+    // the steps end where the job does; the job prepares the client's module itself
+    let output = executor.execute_wasm_with_profile(&client, &input, &profile(1_000_000))?;
+    assert_eq!(output.result, input);
+    assert_eq!(trace.fuel_consumed, output.fuel_consumed);
+
+    let again = executor.execute_stepped(&wasm, &input, &profile(1_000_000), 200)?;
+    assert_eq!(again, trace);
+    assert_eq!(again.root(), trace.root());
+    Ok(())
+}
+
+#[test]
+fn test_trace_of_compiled_python() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let code = "total = 0\nfor i in range(200):\n    total += i\nOUTPUT = total";
+    let wasm = executor.compile(code, &profile(1_000_000), Default::default())?;
+
+    let trace = executor.execute_stepped(&wasm, "{}", &profile(1_000_000), 500)?;
+    assert_eq!(trace.outcome, StepStatus::Returned);
+    assert!(trace.total_steps() > 2);
+    let output = executor.execute_with_profile(code, "{}", &profile(1_000_000))?;
+    assert_eq!(trace.fuel_consumed, output.fuel_consumed);
+    Ok(())
+}
+
+#[test]
+fn test_every_state_proves_against_the_root() -> Result<()> {
+    let executor = PythonExecutor::new()?;
+    let wasm = executor.prepare_wasm(&countdown_module(false))?;
+    let trace = executor.execute_stepped(&wasm, &input(300), &profile(1_000_000), 150)?;
+    let root = trace.root();
+
+    for index in 0..trace.states.len() {
+        let state = trace.state(index).unwrap();
+        let proof = trace.proof(index)?;
+        assert_eq!(step_trace::proof_root(&state, index, &proof), root, "state {}", index);
+        // a state proves only at its own index
+        assert_ne!(step_trace::proof_root(&state, index + 1, &proof), root);
+    }
+    assert!(trace.proof(trace.states.len()).is_err());
+
+    let last = trace.total_steps() - 1;
+    let step = trace.step_proof(last)?;
+    assert_eq!((step.pre_state, step.post_state), (trace.states[last], trace.states[last + 1]));
+    assert_eq!(step_trace::proof_root(&step.post_state, last + 1, &step.post_state_proof), root);
+    assert!(trace.step_proof(last + 1).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_first_divergence_bisects_to_the_bad_step() -> Result<()> {
+    let executor = PythonExecutor::new()?;
+    let wasm = executor.prepare_wasm(&countdown_module(false))?;
+    let honest = executor.execute_stepped(&wasm, &input(400), &profile(1_000_000), 100)?;
+    assert!(honest.total_steps() > 8);
+    assert_eq!(honest.first_divergence(&honest)?, None);
+
+    // a claim that goes wrong from state 5 on
+    let mut claimed = honest.clone();
+    for state in &mut claimed.states[5..] {
+        state[0] ^= 1;
+    }
+    assert_eq!(honest.first_divergence(&claimed)?, Some(4));
+    assert_ne!(honest.root(), claimed.root());
+
+    // a claim that stops early disputes the step after its last state
+    let mut short = honest.clone();
+    short.states.truncate(4);
+    assert_eq!(honest.first_divergence(&short)?, Some(3));
+    Ok(())
+}
+
+#[test]
+fn test_trace_ends_at_a_trap_or_the_fuel_limit() -> Result<()> {
+    let executor = PythonExecutor::new()?;
+
+    let trapping = executor.prepare_wasm(&countdown_module(true))?;
+    let trace = executor.execute_stepped(&trapping, &input(10), &profile(1_000_000), 10_000)?;
+    assert_eq!(trace.outcome, StepStatus::Trapped);
+    assert_eq!(trace.total_steps(), 1);
+
+    let wasm = executor.prepare_wasm(&countdown_module(false))?;
+    let trace = executor.execute_stepped(&wasm, &input(5_000), &profile(2_000), 500)?;
+    assert_eq!(trace.outcome, StepStatus::Running);
+    assert_eq!(trace.fuel_consumed, 2_000);
+    assert_eq!(trace.total_steps(), 4);
+    Ok(())
+}
+
+#[test]
+fn test_trace_needs_a_fuel_interval() -> Result<()> {
+    let executor = PythonExecutor::new()?;
+    let wasm = executor.prepare_wasm(&countdown_module(false))?;
+    assert!(executor.execute_stepped(&wasm, &input(10), &profile(1_000_000), 0).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_state_hash_covers_memory_fuel_and_status() {
+    let mut memory = vec![0u8; 2 * 64 * 1024];
+    let state = step_trace::state_hash(&memory, 10, StepStatus::Running);
+    assert_ne!(state, step_trace::state_hash(&memory, 11, StepStatus::Running));
+    assert_ne!(state, step_trace::state_hash(&memory, 10, StepStatus::Returned));
+    memory[64 * 1024 + 3] = 1;
+    assert_ne!(state, step_trace::state_hash(&memory, 10, StepStatus::Running));
+}
@@ -0,0 +1,61 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+fn run(code: &str) -> Result<i32> {
+    let wasm = PythonCompiler::new().compile(code)?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    main.call(&mut store, ())
+}
+
+#[test]
+fn test_substring_in() -> Result<()> {
+    let code = r#"
+s = "transfer:0xabc"
+OUTPUT = ("transfer" in s) * 100 + (":0x" in s) * 10 + ("abcd" in s)
+"#;
+    assert_eq!(run(code)?, 110);
+    Ok(())
+}
+
+#[test]
+fn test_substring_not_in_and_edges() -> Result<()> {
+    let code = r#"
+s = "aab"
+OUTPUT = ("" in s) * 1000 + ("ab" not in s) * 100 + ("aab" in s) * 10 + ("aabb" in s)
+"#;
+    assert_eq!(run(code)?, 1010);
+    Ok(())
+}
+
+#[test]
+fn test_startswith_and_endswith() -> Result<()> {
+    let code = r#"
+token = "usdc.e"
+OUTPUT = token.startswith("usdc") * 1000 + token.endswith(".e") * 100 + token.endswith("usdc") * 10 + token.endswith("")
+"#;
+    assert_eq!(run(code)?, 1101);
+    Ok(())
+}
+
+#[test]
+fn test_suffix_longer_than_string() -> Result<()> {
+    let code = r#"
+OUTPUT = "ab".endswith("xab") + "ab".startswith("abx")
+"#;
+    assert_eq!(run(code)?, 0);
+    Ok(())
+}
+
+#[test]
+fn test_non_string_item_in_string_traps() {
+    let code = r#"
+OUTPUT = 3 in "123"
+"#;
+    assert!(run(code).is_err());
+}
@@ -0,0 +1,208 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+fn run(code: &str) -> Result<i32> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+    execute_wasm(&wasm)
+}
+
+// if x == keys[0]: y = 100 / elif x == keys[1]: y = 101 / ... / else: y = 7
+fn ladder(keys: &[i32], x: i32) -> String {
+    let mut code = format!("x = {}\n", x);
+    for (i, k) in keys.iter().enumerate() {
+        let kw = if i == 0 { "if" } else { "elif" };
+        code.push_str(&format!("{} x == {}:\n    y = {}\n", kw, k, 100 + i));
+    }
+    code.push_str("else:\n    y = 7\nOUTPUT = y\n");
+    code
+}
+
+fn has_br_table(wasm_bytes: &[u8]) -> bool {
+    for payload in wasmparser::Parser::new(0).parse_all(wasm_bytes) {
+        if let wasmparser::Payload::CodeSectionEntry(body) = payload.unwrap() {
+            let mut reader = body.get_operators_reader().unwrap();
+            while !reader.eof() {
+                if let wasmparser::Operator::BrTable { .. } = reader.read().unwrap() {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+fn fuel_used(code: &str) -> Result<u64> {
+    let mut compiler = PythonCompiler::new();
+    let wasm = compiler.compile(code)?;
+
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(10_000_000)?;
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())?;
+
+    Ok(10_000_000 - store.get_fuel()?)
+}
+
+#[test]
+fn test_dense_ladder_uses_br_table() -> Result<()> {
+    let keys: Vec<i32> = (0..10).collect();
+    let mut compiler = PythonCompiler::new();
+    assert!(has_br_table(&compiler.compile(&ladder(&keys, 3))?));
+    Ok(())
+}
+
+#[test]
+fn test_dense_ladder_results() -> Result<()> {
+    let keys: Vec<i32> = (0..10).collect();
+    for x in [-1, 0, 1, 5, 9, 10, 1000] {
+        let expected = if (0..10).contains(&x) { 100 + x } else { 7 };
+        assert_eq!(run(&ladder(&keys, x))?, expected, "x = {}", x);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_offset_ladder_with_gaps() -> Result<()> {
+    // unordered keys starting at 20 with holes at 22 and 25
+    let keys = [21, 20, 23, 24, 26];
+    for (x, expected) in [(20, 101), (21, 100), (22, 7), (24, 103), (25, 7), (26, 104), (19, 7), (27, 7)] {
+        assert_eq!(run(&ladder(&keys, x))?, expected, "x = {}", x);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_negative_keys() -> Result<()> {
+    let keys = [-2, -1, 0, 1];
+    let mut compiler = PythonCompiler::new();
+    assert!(has_br_table(&compiler.compile(&ladder(&keys, 0))?));
+    assert_eq!(run(&ladder(&keys, -2))?, 100);
+    assert_eq!(run(&ladder(&keys, 1))?, 103);
+    assert_eq!(run(&ladder(&keys, -3))?, 7);
+    Ok(())
+}
+
+#[test]
+fn test_duplicate_key_first_branch_wins() -> Result<()> {
+    let keys = [0, 1, 1, 2, 3];
+    assert_eq!(run(&ladder(&keys, 1))?, 101);
+    assert_eq!(run(&ladder(&keys, 2))?, 103);
+    Ok(())
+}
+
+#[test]
+fn test_sparse_or_short_ladders_keep_comparisons() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    assert!(!has_br_table(&compiler.compile(&ladder(&[0, 100, 200, 300, 400], 0))?));
+    let mut compiler = PythonCompiler::new();
+    assert!(!has_br_table(&compiler.compile(&ladder(&[0, 1, 2], 0))?));
+    assert_eq!(run(&ladder(&[0, 100, 200, 300, 400], 300))?, 103);
+    Ok(())
+}
+
+#[test]
+fn test_mixed_conditions_keep_comparisons() -> Result<()> {
+    let code = r#"
+x = 2
+z = 2
+if x == 0:
+    y = 1
+elif x == 1:
+    y = 2
+elif z == 2:
+    y = 3
+elif x == 3:
+    y = 4
+else:
+    y = 5
+OUTPUT = y
+"#;
+    let mut compiler = PythonCompiler::new();
+    assert!(!has_br_table(&compiler.compile(code)?));
+    assert_eq!(run(code)?, 3);
+    Ok(())
+}
+
+#[test]
+fn test_switch_break_and_nested_control_flow() -> Result<()> {
+    let code = r#"
+total = 0
+i = 0
+while i < 100:
+    if i == 0:
+        total = total + 1
+    elif i == 1:
+        for j in range(3):
+            total = total + 10
+    elif i == 2:
+        if total > 5:
+            total = total + 100
+    elif i == 3:
+        break
+    i = i + 1
+OUTPUT = total * 10 + i
+"#;
+    assert_eq!(run(code)?, 1313);
+    Ok(())
+}
+
+#[test]
+fn test_switch_smaller_and_cheaper_than_ladder() -> Result<()> {
+    let dense: Vec<i32> = (0..50).collect();
+    // same number of cases, too sparse for a table
+    let sparse: Vec<i32> = (0..50).map(|k| k * 10).collect();
+
+    let mut compiler = PythonCompiler::new();
+    let dense_wasm = compiler.compile(&ladder(&dense, 49))?;
+    let mut compiler = PythonCompiler::new();
+    let sparse_wasm = compiler.compile(&ladder(&sparse, 490))?;
+    assert!(dense_wasm.len() < sparse_wasm.len());
+
+    assert!(fuel_used(&ladder(&dense, 49))? < fuel_used(&ladder(&sparse, 490))?);
+    Ok(())
+}
+
+#[test]
+fn test_switch_in_function() -> Result<()> {
+    let code = r#"
+def opcode_cost(op):
+    if op == 0:
+        return 3
+    elif op == 1:
+        return 5
+    elif op == 2:
+        return 8
+    elif op == 3:
+        return 10
+    return 0
+
+OUTPUT = opcode_cost(2) * 100 + opcode_cost(3) * 10 + opcode_cost(9)
+"#;
+    assert_eq!(run(code)?, 900);
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use python_verifier::compiler::{CompileOptions, Target};
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmparser::{Operator, Parser, Payload};
+use wasmtime::*;
+
+fn compile(code: &str, target: Target) -> Result<Vec<u8>> {
+    PythonCompiler::new().with_options(CompileOptions { target, ..Default::default() }).compile(code)
+}
+
+// OUTPUT and the heap object's bytes when it points at one
+fn run(wasm: &[u8]) -> Result<(i32, Vec<u8>)> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let output = instance.get_typed_func::<(), i32>(&mut store, "main")?.call(&mut store, ())?;
+
+    // [type:i32][length:i32][bytes...]
+    let data = memory.data(&store);
+    let ptr = output as usize;
+    let contents = if ptr >= 0x10000 && ptr + 8 <= data.len() {
+        let len = u32::from_le_bytes(data[ptr + 4..ptr + 8].try_into()?) as usize;
+        data.get(ptr + 8..ptr + 8 + len).unwrap_or_default().to_vec()
+    } else {
+        Vec::new()
+    };
+    Ok((output, contents))
+}
+
+fn uses_bulk_memory_or_rotr(wasm: &[u8]) -> Result<bool> {
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::CodeSectionEntry(body) = payload? {
+            for op in body.get_operators_reader()? {
+                if matches!(op?, Operator::MemoryCopy { .. } | Operator::MemoryFill { .. } | Operator::MemoryInit { .. }
+                    | Operator::DataDrop { .. } | Operator::I32Rotr | Operator::I64Rotr) {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+const PROGRAMS: &[&str] = &[
+    // literals, concatenation and slicing copy bytes
+    "s = 'hello, ' + 'on-chain world'\nOUTPUT = s[2:9] + s[::-3]",
+    // sha256 rotates right and reads its round constants from a data segment
+    "import hashlib\nOUTPUT = hashlib.sha256(b'certus' + b', a message longer than one sha256 block of sixty-four bytes').digest()",
+    "OUTPUT = keccak256(b'abc' + bytes([1, 2, 3]))",
+    // lists grow and shift in place
+    "xs = [3, 1, 2]\nxs.insert(0, 9)\nxs.pop(1)\nxs.append(7)\nys = sorted(xs)\nOUTPUT = ys[0] * 100 + ys[-1] * 10 + len(ys)",
+    "d = {}\nfor i in range(40):\n    d[i] = i * i\ndel d[3]\nOUTPUT = len(d) + d[39]",
+];
+
+#[test]
+fn test_on_chain_v1_agrees_with_latest() -> Result<()> {
+    for code in PROGRAMS {
+        let latest = run(&compile(code, Target::Latest)?)?;
+        let on_chain = run(&compile(code, Target::OnChainV1)?)?;
+        assert_eq!(latest.1, on_chain.1, "{}", code);
+        if latest.1.is_empty() {
+            assert_eq!(latest.0, on_chain.0, "{}", code);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_on_chain_v1_emits_no_bulk_memory_or_rotr() -> Result<()> {
+    for code in &PROGRAMS[..2] {
+        assert!(uses_bulk_memory_or_rotr(&compile(code, Target::Latest)?)?, "{}", code);
+    }
+    for code in PROGRAMS {
+        let wasm = compile(code, Target::OnChainV1)?;
+        assert!(!uses_bulk_memory_or_rotr(&wasm)?, "{}", code);
+        Target::OnChainV1.check(&wasm)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_check_rejects_unsupported_opcodes() -> Result<()> {
+    let wasm = compile(PROGRAMS[0], Target::Latest)?;
+    Target::Latest.check(&wasm)?;
+    let err = Target::OnChainV1.check(&wasm).unwrap_err();
+    assert!(err.to_string().contains("which the on-chain-v1 target doesn't support"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_programs_without_bulk_memory_are_unchanged() -> Result<()> {
+    // `+` could concatenate, which copies bytes
+    let code = "t = 0\nfor i in range(10):\n    t -= i * i\nOUTPUT = t";
+    assert_eq!(compile(code, Target::OnChainV1)?, compile(code, Target::Latest)?);
+    Ok(())
+}
+
+#[test]
+fn test_target_parses() {
+    assert_eq!(CompileOptions::default().target, Target::Latest);
+    let options: CompileOptions = serde_json::from_str(r#"{"target": "on-chain-v1"}"#).unwrap();
+    assert_eq!(options.target, Target::OnChainV1);
+    assert_eq!("on-chain-v1".parse::<Target>().unwrap(), Target::OnChainV1);
+    let err = "on-chain-v2".parse::<Target>().unwrap_err();
+    assert!(err.to_string().contains("expected latest or on-chain-v1"), "{}", err);
+}
@@ -0,0 +1,212 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+// Execute compiled WASM and return OUTPUT variable
+fn execute_wasm(wasm_bytes: &[u8]) -> Result<i32> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+
+    let memory_type = MemoryType::new(16, Some(256));
+    let memory = Memory::new(&mut store, memory_type)?;
+
+    let module = Module::new(&engine, wasm_bytes)?;
+
+    let imports = [memory.into()];
+    let instance = Instance::new(&mut store, &module, &imports)?;
+
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    let result = main.call(&mut store, ())?;
+
+    Ok(result)
+}
+
+
+#[test]
+fn test_return_multiple_values() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+def divmod_(a, b):
+    return a // b, a % b
+
+q, r = divmod_(17, 5)
+OUTPUT = q * 10 + r
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 32);
+    Ok(())
+}
+
+#[test]
+fn test_tuple_stored_and_indexed() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+t = (4, 5, 6)
+OUTPUT = t[0] * 100 + t[2] * 10 + len(t)
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 463);
+    Ok(())
+}
+
+#[test]
+fn test_unpack_stored_tuple() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+pair = 7, 8
+a, b = pair
+OUTPUT = a * 10 + b
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 78);
+    Ok(())
+}
+
+#[test]
+fn test_unpack_list() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+x, y, z = [1, 2, 3]
+OUTPUT = x * 100 + y * 10 + z
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 123);
+    Ok(())
+}
+
+#[test]
+fn test_swap() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+a, b = 1, 2
+a, b = b, a
+OUTPUT = a * 10 + b
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 21);
+    Ok(())
+}
+
+#[test]
+fn test_values_evaluated_before_binding() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+def gcd(a, b):
+    while b:
+        a, b = b, a % b
+    return a
+
+x, y, z = 3, 5, 7
+x, y, z = z, x + y, x * y
+OUTPUT = gcd(1071, 462) * 10000 + x * 1000 + y * 10 + z
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 21 * 10000 + 7 * 1000 + 80 + 15);
+    Ok(())
+}
+
+#[test]
+fn test_tuple_in_list() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+def pair(i):
+    return i, i * i
+
+pairs = []
+for i in range(4):
+    pairs.append(pair(i))
+
+a, b = pairs[3]
+OUTPUT = a * 10 + b
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 39);
+    Ok(())
+}
+
+#[test]
+fn test_unpack_length_mismatch_traps() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+def three():
+    return 1, 2, 3
+
+a, b = three()
+OUTPUT = a
+"#;
+    let wasm = compiler.compile(code)?;
+    assert!(execute_wasm(&wasm).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_unpack_int_traps() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+n = 5
+a, b = n
+OUTPUT = a
+"#;
+    let wasm = compiler.compile(code)?;
+    assert!(execute_wasm(&wasm).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_tuple_item_assignment_traps() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+t = (1, 2)
+t[0] = 5
+OUTPUT = t[0]
+"#;
+    let wasm = compiler.compile(code)?;
+    assert!(execute_wasm(&wasm).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_tuple_append_traps() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+t = (1, 2)
+t.append(3)
+OUTPUT = len(t)
+"#;
+    let wasm = compiler.compile(code)?;
+    assert!(execute_wasm(&wasm).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_tuple_concatenation() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+t = (1,) + (2, 3)
+a, b, c = t
+OUTPUT = a * 100 + b * 10 + c
+"#;
+    let wasm = compiler.compile(code)?;
+    let result = execute_wasm(&wasm)?;
+    assert_eq!(result, 123);
+    Ok(())
+}
+
+#[test]
+fn test_tuple_plus_list_traps() -> Result<()> {
+    let mut compiler = PythonCompiler::new();
+    let code = r#"
+t = (1, 2) + [3]
+OUTPUT = len(t)
+"#;
+    let wasm = compiler.compile(code)?;
+    assert!(execute_wasm(&wasm).is_err());
+    Ok(())
+}
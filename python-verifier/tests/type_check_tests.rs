@@ -0,0 +1,95 @@
+use python_verifier::python_compiler::PythonCompiler;
+use anyhow::Result;
+use wasmtime::*;
+
+fn run(code: &str) -> Result<i32> {
+    let wasm = PythonCompiler::new().compile(code)?;
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(16, Some(256)))?;
+    let module = Module::new(&engine, &wasm)?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let main = instance.get_typed_func::<(), i32>(&mut store, "main")?;
+    main.call(&mut store, ())
+}
+
+fn compile_error(code: &str) -> String {
+    PythonCompiler::new().compile(code).unwrap_err().to_string()
+}
+
+#[test]
+fn test_type_of_each_value() -> Result<()> {
+    let code = r#"
+values = [7, -3, "s", b"b", [1], (1, 2), {1: 2}, {1}]
+names = [int, int, str, bytes, list, tuple, dict, set]
+ok = 0
+for i in range(len(values)):
+    if type(values[i]) == names[i]:
+        ok += 1
+OUTPUT = ok
+"#;
+    assert_eq!(run(code)?, 8);
+    Ok(())
+}
+
+#[test]
+fn test_isinstance_single_type() -> Result<()> {
+    assert_eq!(run("OUTPUT = isinstance([1, 2], list)")?, 1);
+    assert_eq!(run("OUTPUT = isinstance((1, 2), list)")?, 0);
+    assert_eq!(run("OUTPUT = isinstance(1000000, int)")?, 1);
+    assert_eq!(run("OUTPUT = isinstance('x', dict)")?, 0);
+    Ok(())
+}
+
+#[test]
+fn test_isinstance_tuple_of_types() -> Result<()> {
+    let code = r#"
+def checked_total(items):
+    total = 0
+    for item in items:
+        if not isinstance(item, (int, str)):
+            raise ValueError("bad item")
+        if isinstance(item, str):
+            total += len(item)
+        else:
+            total += item
+    return total
+try:
+    checked_total([1, [2]])
+    OUTPUT = -1
+except ValueError:
+    OUTPUT = checked_total([1, "abc", 10])
+"#;
+    assert_eq!(run(code)?, 14);
+    Ok(())
+}
+
+#[test]
+fn test_isinstance_evaluates_value_once() -> Result<()> {
+    let code = r#"
+calls = [0]
+def make():
+    calls[0] += 1
+    return {1: 1}
+OUTPUT = isinstance(make(), (int, str, list, dict)) * 10 + calls[0]
+"#;
+    assert_eq!(run(code)?, 11);
+    Ok(())
+}
+
+#[test]
+fn test_rebound_type_names_are_variables() -> Result<()> {
+    let code = r#"
+list = 5
+OUTPUT = list + 1
+"#;
+    assert_eq!(run(code)?, 6);
+    Ok(())
+}
+
+#[test]
+fn test_unsupported_types_rejected() {
+    assert!(compile_error("OUTPUT = isinstance(1, bool)").contains("does not support type 'bool'"));
+    assert!(compile_error("OUTPUT = isinstance(1, 3)").contains("tuple of types"));
+    assert!(compile_error("OUTPUT = type(1, 2)").contains("takes exactly 1 argument"));
+}
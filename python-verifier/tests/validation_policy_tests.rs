@@ -0,0 +1,67 @@
+use python_verifier::validation::{PythonValidator, ValidationPolicy};
+
+fn check(code: &str) -> Result<(), String> {
+    ValidationPolicy::default().check(code).map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_allows_supported_programs() {
+    let programs = [
+        "import hashlib\nOUTPUT = hashlib.sha256(b'x').hexdigest()",
+        "from hashlib import sha256\nOUTPUT = sha256(b'x').digest()",
+        "def f(n):\n    return n * 2\nOUTPUT = sorted([f(i) for i in range(3)])",
+        "class A:\n    def __init__(self, x):\n        self.x = x\nOUTPUT = A(3).x",
+        "try:\n    raise ValueError('bad')\nexcept ValueError:\n    OUTPUT = 1",
+    ];
+    for code in programs {
+        assert_eq!(check(code), Ok(()), "{}", code);
+    }
+}
+
+#[test]
+fn test_text_in_strings_is_not_code() {
+    // the substring checks this replaced rejected both
+    assert_eq!(check("s = 'import os; open(f)'\nOUTPUT = s"), Ok(()));
+    assert_eq!(check("# eval(x) would be bad\nOUTPUT = 1"), Ok(()));
+}
+
+#[test]
+fn test_rejects_what_substring_checks_missed() {
+    let cases = [
+        ("import os\nOUTPUT = 1", "import of os not allowed"),
+        ("import hashlib as h\nOUTPUT = h.md5(b'x')", "hashlib.md5 not allowed"),
+        ("from json import load\nOUTPUT = 1", "json.load not allowed"),
+        ("f = getattr\nOUTPUT = f(1, 'x')", "forbidden name: getattr"),
+        ("OUTPUT = (1).__class__", "attribute __class__ not allowed"),
+        ("OUTPUT = __import__('os')", "forbidden name: __import__"),
+        ("OUTPUT = hash(1)", "call to hash() not allowed"),
+        ("OUTPUT = (lambda: 1)()", "only calls by name or attribute allowed"),
+    ];
+    for (code, expected) in cases {
+        let err = check(code).unwrap_err();
+        assert!(err.contains(expected), "{}: {}", code, err);
+    }
+}
+
+#[test]
+fn test_policy_is_configurable() {
+    let policy: ValidationPolicy = serde_json::from_str(r#"{
+        "allowed_modules": ["math"],
+        "allowed_calls": ["len"],
+        "allowed_attribute_chains": ["math.floor"]
+    }"#).unwrap();
+    policy.check("import math\nOUTPUT = math.floor(len('ab'))").unwrap();
+    assert!(policy.check("import hashlib\nOUTPUT = 1").is_err());
+    assert!(policy.check("OUTPUT = sorted([1])").is_err());
+    // the default still forbids what the file didn't mention
+    assert!(policy.check("OUTPUT = eval('1')").is_err());
+
+    assert!(PythonValidator::validate_code_with("import math\nOUTPUT = math.floor(1)", &policy).is_ok());
+    assert!(PythonValidator::validate_code("import math\nOUTPUT = math.floor(1)").is_err());
+}
+
+#[test]
+fn test_syntax_errors_are_rejected() {
+    let err = check("OUTPUT = (1 +").unwrap_err();
+    assert!(err.contains("parse error"), "{}", err);
+}
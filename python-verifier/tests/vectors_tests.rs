@@ -0,0 +1,39 @@
+use python_verifier::vectors::VectorSet;
+use std::collections::HashSet;
+
+#[test]
+fn test_shipped_set_parses() {
+    let set = VectorSet::v1();
+    assert_eq!(set.version, 1);
+    assert!(!set.vectors.is_empty());
+    let names: HashSet<_> = set.vectors.iter().map(|v| v.name.as_str()).collect();
+    assert_eq!(names.len(), set.vectors.len(), "vector names repeat");
+}
+
+#[test]
+fn test_this_build_matches_shipped_set() {
+    let mismatches = VectorSet::v1().verify().unwrap();
+    assert!(mismatches.is_empty(), "{:#?}", mismatches);
+}
+
+#[test]
+fn test_bless_pins_what_verify_checks() {
+    let mut set = VectorSet::v1();
+    set.vectors.truncate(2);
+    set.bless().unwrap();
+    assert!(set.vectors.iter().all(|v| v.wasm_hash.is_some() && v.fuel.is_some()));
+    assert!(set.verify().unwrap().is_empty());
+
+    set.vectors[0].fuel = Some(set.vectors[0].fuel.unwrap() + 1);
+    let mismatches = set.verify().unwrap();
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].field, "fuel");
+}
+
+#[test]
+fn test_parse_rejects_inconsistent_output_hash() {
+    let mut set = VectorSet::v1();
+    set.vectors[0].output += 1;
+    let err = VectorSet::parse(&serde_json::to_string(&set).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("output_hash doesn't match"), "{}", err);
+}
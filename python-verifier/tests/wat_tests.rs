@@ -0,0 +1,42 @@
+#![cfg(feature = "wat")]
+
+use python_verifier::compiler::fuel;
+use python_verifier::{assemble_wat, PythonExecutor};
+use anyhow::Result;
+
+// python_main returns its input's record, four bytes before the input, so the output is the input
+const ECHO: &str = r#"
+(module
+  (import "env" "memory" (memory 1))
+  (func (export "python_main") (param $ptr i32) (param $len i32) (result i32)
+    local.get $ptr
+    i32.const 4
+    i32.sub)
+  (export "memory" (memory 0)))
+"#;
+
+#[test]
+fn test_wat_module_runs_through_the_pipeline() -> Result<()> {
+    let wasm = assemble_wat(ECHO)?;
+    let prepared = PythonExecutor::new()?.prepare_wasm(&wasm)?;
+    assert!(fuel::is_metered(&prepared)?);
+
+    let output = PythonExecutor::new()?.execute_wasm(&wasm, r#"{"x": 21}"#, 1_000_000)?;
+    assert_eq!(output.result, r#"{"x": 21}"#);
+    assert!(output.fuel_consumed > 0);
+    Ok(())
+}
+
+#[test]
+fn test_wat_module_gets_the_determinism_policy() -> Result<()> {
+    let floats = ECHO.replace("local.get $ptr\n", "f32.const 1.5\n    drop\n    local.get $ptr\n");
+    let err = PythonExecutor::new()?.prepare_wasm(&assemble_wat(&floats)?).unwrap_err();
+    assert!(err.to_string().contains("not deterministic"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_malformed_wat_is_rejected() {
+    let err = assemble_wat("(module (func (result i32) i32.const))").unwrap_err();
+    assert!(err.to_string().contains("invalid WAT"), "{}", err);
+}
@@ -0,0 +1,104 @@
+#![cfg(feature = "zk-trace")]
+
+use python_verifier::profiles::ExecutionProfile;
+use python_verifier::PythonExecutor;
+use python_verifier::zk_trace::CanonicalTrace;
+use anyhow::Result;
+
+const LOOP_PROGRAM: &str = r#"
+total = 0
+for i in range(200):
+    total = total + i
+OUTPUT = total
+"#;
+
+#[test]
+fn test_trace_records_output_and_steps() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let trace = executor.execute_traced(LOOP_PROGRAM, 1_000_000, 500)?;
+
+    assert_eq!(trace.output, 19900);
+    assert!(trace.steps.len() > 1);
+    assert_eq!(trace.steps[0].fuel_start, 0);
+    trace.check_links()?;
+    Ok(())
+}
+
+#[test]
+fn test_trace_is_deterministic() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let a = executor.execute_traced(LOOP_PROGRAM, 1_000_000, 500)?;
+    let b = executor.execute_traced(LOOP_PROGRAM, 1_000_000, 500)?;
+
+    assert_eq!(a.to_bytes(), b.to_bytes());
+    assert_eq!(a.commitment(), b.commitment());
+    Ok(())
+}
+
+#[test]
+fn test_trace_binary_roundtrip() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let trace = executor.execute_traced(LOOP_PROGRAM, 1_000_000, 700)?;
+
+    let decoded = CanonicalTrace::from_bytes(&trace.to_bytes())?;
+    assert_eq!(decoded, trace);
+    Ok(())
+}
+
+#[test]
+fn test_trace_json_roundtrip() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let trace = executor.execute_traced(LOOP_PROGRAM, 1_000_000, 700)?;
+
+    let json = serde_json::to_string(&trace)?;
+    let decoded: CanonicalTrace = serde_json::from_str(&json)?;
+    assert_eq!(decoded, trace);
+    Ok(())
+}
+
+#[test]
+fn test_trace_rejects_broken_links() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let mut trace = executor.execute_traced(LOOP_PROGRAM, 1_000_000, 500)?;
+    trace.steps[1].pre_state[0] ^= 1;
+
+    assert!(CanonicalTrace::from_bytes(&trace.to_bytes()).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_trace_out_of_fuel() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    assert!(executor.execute_traced(LOOP_PROGRAM, 1_000, 500).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_steps_are_the_bisection_states() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let trace = executor.execute_traced(LOOP_PROGRAM, 1_000_000, 500)?;
+
+    let profile = ExecutionProfile { fuel_limit: 1_000_000, ..Default::default() };
+    let wasm = executor.compile(LOOP_PROGRAM, &profile, Default::default())?;
+    let stepped = executor.execute_stepped(&wasm, "{}", &profile, 500)?;
+    let states: Vec<_> = std::iter::once(trace.steps[0].pre_state)
+        .chain(trace.steps.iter().map(|step| step.post_state))
+        .collect();
+    assert_eq!(states, stepped.states);
+    assert_eq!(trace.steps.last().unwrap().fuel_end, stepped.fuel_consumed);
+    Ok(())
+}
+
+#[test]
+fn test_steps_name_the_instruction_they_stop_at() -> Result<()> {
+    let mut executor = PythonExecutor::new()?;
+    let trace = executor.execute_traced(LOOP_PROGRAM, 1_000_000, 500)?;
+
+    let (last, steps) = trace.steps.split_last().unwrap();
+    assert_eq!(last.instruction, None);
+    assert!(steps.iter().all(|step| step.instruction.is_some()));
+    // the loop brings the run back past the same instructions
+    let distinct: std::collections::HashSet<_> = steps.iter().map(|step| step.instruction).collect();
+    assert!(distinct.len() > 1);
+    Ok(())
+}
@@ -0,0 +1,304 @@
+// Differential tests: the same program through the interpreter and through wasmtime, which is
+// what executors run jobs on. The interpreter has no module loader yet, so programs are
+// straight-line i32/i64 arithmetic stepped one opcode at a time: constants are pushed onto its
+// stack directly and emitted as `const` instructions for wasmtime, neither side charging for
+// them. The wasmtime side charges the program's gas on entry, through a counter
+// shaped like python-verifier's fuel metering, so it also checks the claim in certus-gas that
+// per-opcode and per-block charging agree on the fuel a finished job burns and on whether it
+// runs out.
+//
+// Run with `cargo test --lib --features wasmtime-support`.
+
+use alloc::vec::Vec;
+use proptest::prelude::*;
+use wasm_encoder::{
+    BlockType, CodeSection, ConstExpr, ExportKind, ExportSection, Function, FunctionSection,
+    GlobalSection, GlobalType, Instruction, Module, TypeSection, ValType,
+};
+use wasmtime::{Engine, Instance, Store, Trap, Val};
+
+use crate::wasm_interpreter::{Interpreter, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    I32,
+    I64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Const(Value),
+    Op(u8),
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Returned(Vec<Value>),
+    Trapped(Trap),
+}
+
+// Every opcode the interpreter takes without immediates, besides unreachable, and drop and
+// select, whose operand types aren't fixed
+const OPS: &[u8] = &[
+    0x01, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4A, 0x4B, 0x4C, 0x4D, 0x4E, 0x4F, 0x50, 0x51, 0x52,
+    0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x67, 0x68, 0x69, 0x6A, 0x6B, 0x6C, 0x6D,
+    0x6E, 0x6F, 0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x7B, 0x7C,
+    0x7D, 0x7E, 0x7F, 0x80, 0x81, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8A, 0xA7,
+    0xAC, 0xAD, 0xC0, 0xC1, 0xC2, 0xC3, 0xC4,
+];
+
+const EDGE_I32: &[i32] = &[0, 1, -1, 2, 31, 32, 33, i32::MIN, i32::MAX, i32::MIN + 1];
+const EDGE_I64: &[i64] = &[0, 1, -1, 2, 63, 64, 65, i64::MIN, i64::MAX, i64::MIN + 1];
+
+/// Operand and result types of an opcode
+fn signature(op: u8) -> (&'static [Type], Option<Type>) {
+    use Type::*;
+    match op {
+        0x01 => (&[], None),
+        0x45 => (&[I32], Some(I32)),
+        0x46..=0x4F => (&[I32, I32], Some(I32)),
+        0x50 => (&[I64], Some(I32)),
+        0x51..=0x5A => (&[I64, I64], Some(I32)),
+        0x67..=0x69 => (&[I32], Some(I32)),
+        0x6A..=0x78 => (&[I32, I32], Some(I32)),
+        0x79..=0x7B => (&[I64], Some(I64)),
+        0x7C..=0x8A => (&[I64, I64], Some(I64)),
+        0xA7 => (&[I64], Some(I32)),
+        0xAC | 0xAD => (&[I32], Some(I64)),
+        0xC0 | 0xC1 => (&[I32], Some(I32)),
+        0xC2..=0xC4 => (&[I64], Some(I64)),
+        _ => unreachable!("no signature for {:#04x}", op),
+    }
+}
+
+fn type_of(value: Value) -> Type {
+    match value {
+        Value::I32(_) => Type::I32,
+        Value::I64(_) => Type::I64,
+    }
+}
+
+/// Drop the ops whose operands aren't on the stack, leaving a program that validates, and
+/// return it with the types it leaves on the stack
+fn well_typed(steps: Vec<Step>) -> (Vec<Step>, Vec<Type>) {
+    let mut stack = Vec::new();
+    let mut program = Vec::new();
+    for step in steps {
+        match step {
+            Step::Const(value) => stack.push(type_of(value)),
+            Step::Op(op) => {
+                let (params, result) = signature(op);
+                if !stack.ends_with(params) {
+                    continue;
+                }
+                stack.truncate(stack.len() - params.len());
+                stack.extend(result);
+            }
+        }
+        program.push(step);
+    }
+    (program, stack)
+}
+
+fn program_cost(program: &[Step]) -> u64 {
+    program
+        .iter()
+        .map(|step| match step {
+            Step::Op(op) => certus_gas::opcode_cost(*op),
+            Step::Const(_) => 0,
+        })
+        .sum()
+}
+
+fn interpreter_trap(err: &str) -> Trap {
+    match err {
+        "out of fuel" => Trap::UnreachableCodeReached,
+        "integer divide by zero" => Trap::IntegerDivisionByZero,
+        "integer overflow" => Trap::IntegerOverflow,
+        _ => panic!("interpreter failed outside the program: {}", err),
+    }
+}
+
+/// Run a program on the interpreter; returns the outcome and the fuel left
+fn interpret(program: &[Step], fuel: u64) -> (Outcome, u64) {
+    let mut interp = Interpreter::new(0, 0, fuel);
+    for step in program {
+        let result = match *step {
+            Step::Const(value) => interp.push(value),
+            Step::Op(op) => interp.execute_opcode(op, &[]),
+        };
+        if let Err(err) = result {
+            return (Outcome::Trapped(interpreter_trap(err)), interp.fuel);
+        }
+    }
+    (Outcome::Returned(interp.stack.clone()), interp.fuel)
+}
+
+/// A module whose `run` executes the program after charging its gas to an exported counter,
+/// trapping once the counter passes `fuel`
+fn module(program: &[Step], results: &[Type], fuel: u64) -> Vec<u8> {
+    let val_type = |ty: &Type| match ty {
+        Type::I32 => ValType::I32,
+        Type::I64 => ValType::I64,
+    };
+    let mut module = Module::new();
+
+    let mut types = TypeSection::new();
+    types.function([], results.iter().map(val_type));
+    module.section(&types);
+
+    let mut funcs = FunctionSection::new();
+    funcs.function(0);
+    module.section(&funcs);
+
+    let mut globals = GlobalSection::new();
+    globals.global(GlobalType { val_type: ValType::I64, mutable: true }, &ConstExpr::i64_const(0));
+    module.section(&globals);
+
+    let mut exports = ExportSection::new();
+    exports.export("run", ExportKind::Func, 0);
+    exports.export("fuel", ExportKind::Global, 0);
+    module.section(&exports);
+
+    let mut run = Function::new([]);
+    for instruction in [
+        Instruction::GlobalGet(0),
+        Instruction::I64Const(program_cost(program) as i64),
+        Instruction::I64Add,
+        Instruction::GlobalSet(0),
+        Instruction::GlobalGet(0),
+        Instruction::I64Const(fuel as i64),
+        Instruction::I64GtU,
+        Instruction::If(BlockType::Empty),
+        Instruction::Unreachable,
+        Instruction::End,
+    ] {
+        run.instruction(&instruction);
+    }
+    for step in program {
+        match *step {
+            Step::Const(Value::I32(v)) => run.instruction(&Instruction::I32Const(v)),
+            Step::Const(Value::I64(v)) => run.instruction(&Instruction::I64Const(v)),
+            Step::Op(op) => run.raw([op]),
+        };
+    }
+    run.instruction(&Instruction::End);
+
+    let mut code = CodeSection::new();
+    code.function(&run);
+    module.section(&code);
+    module.finish()
+}
+
+/// Run a program on wasmtime; returns the outcome and the fuel its counter didn't charge
+fn execute(program: &[Step], results: &[Type], fuel: u64) -> (Outcome, u64) {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = wasmtime::Module::new(&engine, module(program, results, fuel)).unwrap();
+    let instance = Instance::new(&mut store, &module, &[]).unwrap();
+    let run = instance.get_func(&mut store, "run").unwrap();
+
+    let mut values: Vec<Val> = results.iter().map(|_| Val::I32(0)).collect();
+    let outcome = match run.call(&mut store, &[], &mut values) {
+        Ok(()) => Outcome::Returned(
+            values
+                .iter()
+                .map(|v| match v {
+                    Val::I32(v) => Value::I32(*v),
+                    Val::I64(v) => Value::I64(*v),
+                    other => panic!("unexpected result {:?}", other),
+                })
+                .collect(),
+        ),
+        Err(err) => Outcome::Trapped(*err.downcast_ref::<Trap>().expect("run failed without trapping")),
+    };
+    let charged = instance.get_global(&mut store, "fuel").unwrap().get(&mut store).unwrap_i64() as u64;
+    (outcome, fuel.saturating_sub(charged))
+}
+
+/// Run a program on both engines and check they agree: the same stack and fuel left when it
+/// returns; the same trap when the fuel covers it. Short of fuel, wasmtime traps on entry where
+/// the interpreter may first hit a trap partway, so only the trapping is compared.
+fn check(steps: Vec<Step>, fuel: u64) {
+    let (program, results) = well_typed(steps);
+    let (interpreted, interpreter_fuel) = interpret(&program, fuel);
+    let (executed, wasmtime_fuel) = execute(&program, &results, fuel);
+
+    match (&interpreted, &executed) {
+        (Outcome::Returned(_), _) | (_, Outcome::Returned(_)) => {
+            assert_eq!(interpreted, executed, "{:?}", program);
+            assert_eq!(interpreter_fuel, wasmtime_fuel, "{:?}", program);
+        }
+        (Outcome::Trapped(_), Outcome::Trapped(_)) if program_cost(&program) <= fuel => {
+            assert_eq!(interpreted, executed, "{:?}", program);
+        }
+        _ => {}
+    }
+}
+
+fn value() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        any::<i32>().prop_map(Value::I32),
+        proptest::sample::select(EDGE_I32).prop_map(Value::I32),
+        any::<i64>().prop_map(Value::I64),
+        proptest::sample::select(EDGE_I64).prop_map(Value::I64),
+    ]
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        2 => value().prop_map(Step::Const),
+        3 => proptest::sample::select(OPS).prop_map(Step::Op),
+    ]
+}
+
+#[test]
+fn test_corpus_agrees_on_edge_operands() {
+    for &op in OPS {
+        let (params, _) = signature(op);
+        let operands: Vec<Value> = match params.first() {
+            None => Vec::new(),
+            Some(Type::I32) => EDGE_I32.iter().copied().map(Value::I32).collect(),
+            Some(Type::I64) => EDGE_I64.iter().copied().map(Value::I64).collect(),
+        };
+        if params.len() == 2 {
+            for &a in &operands {
+                for &b in &operands {
+                    check(vec![Step::Const(a), Step::Const(b), Step::Op(op)], 100);
+                }
+            }
+        } else {
+            for &a in &operands {
+                check(vec![Step::Const(a), Step::Op(op)], 100);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_corpus_agrees_on_running_out() {
+    let program = vec![
+        Step::Const(Value::I32(7)),
+        Step::Const(Value::I32(3)),
+        Step::Op(0x6D),
+        Step::Const(Value::I32(5)),
+        Step::Op(0x6C),
+        Step::Op(0x67),
+    ];
+    let cost = program_cost(&program);
+    for fuel in 0..=cost + 1 {
+        let (interpreted, _) = interpret(&program, fuel);
+        let (executed, _) = execute(&program, &[Type::I32], fuel);
+        assert_eq!(interpreted, executed, "fuel {}", fuel);
+        assert_eq!(matches!(executed, Outcome::Returned(_)), fuel >= cost, "fuel {}", fuel);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn test_generated_programs_agree(steps in proptest::collection::vec(step(), 1..64), fuel in 0u64..200) {
+        check(steps, fuel);
+    }
+}
@@ -0,0 +1,292 @@
+// The interactive bisection game. Re-executing a whole job never fits in one transaction, so a
+// defender commits to the state hashes at the first and last step of its run, and a challenger
+// who disagrees narrows the range with it: the defender posts the state at the midpoint, the
+// challenger says whether it agrees, and the half they disagree on is kept. Once the range is a
+// single step, the pre-state (which both sides agreed to) is decoded and only that instruction is
+// run on-chain; the defender wins iff it lands on the state the defender claimed.
+//
+// State hashes are `Interpreter::compute_state_hash`, SHA-256 over this interpreter's state, so
+// both parties play by stepping this interpreter off-chain. This is a separate game from
+// CertusBisection's: its states are python-verifier's step_trace hashes, keccak over wasmtime's
+// memory, fuel and status, and a trace recorded for one cannot answer the other. A party that
+// doesn't move within `ROUND_TIMEOUT` loses.
+
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
+
+use crate::wasm_interpreter::{Interpreter, Label, Value, MAX_LOCALS};
+
+/// Seconds a party has to move, matching CertusBisection.BISECTION_ROUND_TIMEOUT
+pub const ROUND_TIMEOUT: u64 = 300;
+
+/// Memory a disputed state may hold: the most `execute` gives a job
+pub const MAX_MEMORY: usize = 10 * 1024 * 1024;
+
+/// Block types a program may declare: enough for any compiled module's type section
+pub const MAX_TYPES: usize = 1024;
+
+/// Who moves next in a dispute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    /// A claim no one has challenged yet
+    Unchallenged,
+    /// The defender owes the state at the midpoint
+    Defender,
+    /// The challenger owes its verdict on the midpoint
+    Challenger,
+    /// The range is one step: the challenger must run it on-chain
+    Step,
+}
+
+impl Turn {
+    pub fn of(start: u64, end: u64, challenged: bool, has_midpoint: bool) -> Turn {
+        if !challenged {
+            Turn::Unchallenged
+        } else if end - start <= 1 {
+            Turn::Step
+        } else if has_midpoint {
+            Turn::Challenger
+        } else {
+            Turn::Defender
+        }
+    }
+
+    /// Whether the defender wins when the deadline passes on this turn
+    pub fn defender_wins_timeout(self) -> bool {
+        self != Turn::Defender
+    }
+}
+
+/// The step the defender posts a state for, between `start` and `end`
+pub fn midpoint(start: u64, end: u64) -> u64 {
+    start + (end - start) / 2
+}
+
+/// The range left after the challenger's verdict on the midpoint: past it if the challenger
+/// agrees with the state there, up to it if not
+pub fn narrow(start: u64, end: u64, agree: bool) -> (u64, u64) {
+    let mid = midpoint(start, end);
+    if agree {
+        (mid, end)
+    } else {
+        (start, mid)
+    }
+}
+
+/// Hash a claim commits to its program by
+pub fn program_hash(program: &[u8]) -> [u8; 32] {
+    Sha256::digest(program).into()
+}
+
+/// A program as the game runs it: a u32 count of (params u32, results u32) block types, then
+/// the function body. All integers little-endian.
+pub fn decode_program(program: &[u8]) -> Result<(Vec<(usize, usize)>, &[u8]), &'static str> {
+    let word = |at: usize| -> Result<usize, &'static str> {
+        let bytes = program.get(at..at + 4).ok_or("truncated program")?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+    let count = word(0)?;
+    if count > MAX_TYPES {
+        return Err("program declares too many types");
+    }
+    let mut types = Vec::with_capacity(count);
+    for i in 0..count {
+        types.push((word(4 + i * 8)?, word(8 + i * 8)?));
+    }
+    Ok((types, &program[4 + count * 8..]))
+}
+
+/// The state a claimed run starts in: the program's body entered, returning one value, with
+/// `input` as its locals (little-endian i32s, params then declared locals), `fuel` to burn and
+/// no memory yet, which may grow to `max_memory`. A claim's start is derived from these, so a
+/// defender can't start the game from a state of its choosing.
+pub fn initial_state(program: &[u8], input: &[u8], fuel: u64, max_memory: usize) -> Result<Interpreter, &'static str> {
+    let (types, code) = decode_program(program)?;
+    if input.len() % 4 != 0 || input.len() / 4 > MAX_LOCALS {
+        return Err("input is not a list of i32 locals");
+    }
+    let mut interp = Interpreter::new(0, max_memory, fuel);
+    interp.types = types;
+    interp.locals = input.chunks_exact(4).map(|word| Value::I32(i32::from_le_bytes(word.try_into().unwrap()))).collect();
+    interp.labels.push(Label { target: code.len(), height: 0, arity: 1, is_loop: false });
+    Ok(interp)
+}
+
+/// The program's function body, and an interpreter in the pre-state ready to step through it
+pub fn load_step<'a>(program: &'a [u8], pre_state: &[u8], max_memory: usize) -> Result<(Interpreter, &'a [u8]), &'static str> {
+    let (types, code) = decode_program(program)?;
+    let mut interp = Interpreter::decode_state(pre_state, max_memory)?;
+    interp.types = types;
+    Ok((interp, code))
+}
+
+/// Run the one instruction at pc and hash the state it leaves. A trap, or a program that has
+/// already returned, has no post-state.
+pub fn step_hash(interp: &mut Interpreter, code: &[u8]) -> Result<[u8; 32], &'static str> {
+    if interp.labels.is_empty() {
+        return Err("program has already returned");
+    }
+    interp.step(code)?;
+    Ok(interp.compute_state_hash())
+}
+
+/// Step data `executeStep` takes: the program's length as a u32, the program, then the encoded
+/// pre-state
+pub fn split_step_data(step_data: &[u8]) -> Result<(&[u8], &[u8]), &'static str> {
+    let len = step_data.get(..4).ok_or("truncated step data")?;
+    let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+    let end = 4usize.checked_add(len).filter(|&end| end <= step_data.len()).ok_or("truncated step data")?;
+    Ok((&step_data[4..end], &step_data[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sum = 0; loop { sum += n; n -= 1; br_if n != 0 }; sum
+    const SUM_LOOP: &[u8] = &[
+        0x41, 0x00, 0x21, 0x01, // sum = 0
+        0x03, 0x40, // loop
+        0x20, 0x01, 0x20, 0x00, 0x6A, 0x21, 0x01, // sum += n
+        0x20, 0x00, 0x41, 0x01, 0x6B, 0x22, 0x00, // n -= 1, keep n
+        0x0D, 0x00, // br_if 0
+        0x0B, // end loop
+        0x20, 0x01, // sum
+        0x0B,
+    ];
+
+    const MEMORY: usize = 64 * 1024;
+
+    fn encode_program(types: &[(usize, usize)], code: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(types.len() as u32).to_le_bytes());
+        for &(params, results) in types {
+            out.extend_from_slice(&(params as u32).to_le_bytes());
+            out.extend_from_slice(&(results as u32).to_le_bytes());
+        }
+        out.extend_from_slice(code);
+        out
+    }
+
+    /// Hashes of the pre-state and of the state one step on
+    fn execute_step(program: &[u8], pre_state: &[u8], max_memory: usize) -> Result<([u8; 32], [u8; 32]), &'static str> {
+        let (mut interp, code) = load_step(program, pre_state, max_memory)?;
+        let pre = interp.compute_state_hash();
+        Ok((pre, step_hash(&mut interp, code)?))
+    }
+
+    /// SUM_LOOP's locals: n, then sum
+    fn input(n: i32) -> Vec<u8> {
+        [n.to_le_bytes(), [0; 4]].concat()
+    }
+
+    /// Encoded states of a run of SUM_LOOP over `n`: before the first step, then after each
+    fn trace(n: i32) -> Vec<Vec<u8>> {
+        let mut interp = initial_state(&encode_program(&[], SUM_LOOP), &input(n), 10_000, 256).unwrap();
+        let mut states = vec![interp.encode_state()];
+        while !interp.labels.is_empty() {
+            interp.step(SUM_LOOP).unwrap();
+            states.push(interp.encode_state());
+        }
+        states
+    }
+
+    fn hash(state: &[u8]) -> [u8; 32] {
+        Interpreter::decode_state(state, MEMORY).unwrap().compute_state_hash()
+    }
+
+    #[test]
+    fn test_state_round_trips() {
+        for state in trace(3) {
+            let decoded = Interpreter::decode_state(&state, MEMORY).unwrap();
+            assert_eq!(decoded.encode_state(), state);
+        }
+        let state = trace(3).remove(5);
+        assert!(Interpreter::decode_state(&state[..state.len() - 1], MEMORY).is_err());
+        assert!(Interpreter::decode_state(&[state.as_slice(), &[0]].concat(), MEMORY).is_err());
+        // memory is bounded by the dispute's limit, not by what the state says
+        assert!(Interpreter::decode_state(&state, 128).is_err());
+    }
+
+    #[test]
+    fn test_start_state_is_bound_to_program_and_input() {
+        let program = encode_program(&[], SUM_LOOP);
+        let start = |program: &[u8], input: &[u8]| initial_state(program, input, 10_000, 256).unwrap().compute_state_hash();
+        assert_eq!(start(&program, &input(4)), hash(&trace(4)[0]));
+        assert_ne!(start(&program, &input(4)), start(&program, &input(5)));
+        assert_ne!(start(&program, &input(4)), start(&encode_program(&[], &SUM_LOOP[2..]), &input(4)));
+
+        assert!(initial_state(&program, &input(4)[..7], 10_000, 256).is_err());
+        assert!(initial_state(&program[..3], &input(4), 10_000, 256).is_err());
+    }
+
+    #[test]
+    fn test_steps_follow_the_trace() {
+        let program = encode_program(&[], SUM_LOOP);
+        let states = trace(4);
+        for pair in states.windows(2) {
+            let (pre, post) = execute_step(&program, &pair[0], MEMORY).unwrap();
+            assert_eq!(pre, hash(&pair[0]));
+            assert_eq!(post, hash(&pair[1]));
+        }
+        // nothing runs past the function's end
+        assert!(execute_step(&program, states.last().unwrap(), MEMORY).is_err());
+
+        let data = [&(program.len() as u32).to_le_bytes()[..], &program, &states[0]].concat();
+        assert_eq!(split_step_data(&data).unwrap(), (program.as_slice(), states[0].as_slice()));
+        assert!(split_step_data(&data[..3]).is_err());
+    }
+
+    #[test]
+    fn test_turns() {
+        assert_eq!(Turn::of(0, 10, false, false), Turn::Unchallenged);
+        assert_eq!(Turn::of(0, 10, true, false), Turn::Defender);
+        assert_eq!(Turn::of(0, 10, true, true), Turn::Challenger);
+        assert_eq!(Turn::of(4, 5, true, false), Turn::Step);
+        assert!(!Turn::Defender.defender_wins_timeout());
+        assert!(Turn::Challenger.defender_wins_timeout());
+        assert!(Turn::Step.defender_wins_timeout());
+        assert_eq!(narrow(0, 10, true), (5, 10));
+        assert_eq!(narrow(0, 10, false), (0, 5));
+        assert_eq!(narrow(5, 7, true), (6, 7));
+    }
+
+    #[test]
+    fn test_bisection_convicts_a_wrong_trace() {
+        let program = encode_program(&[], SUM_LOOP);
+        let honest: Vec<[u8; 32]> = trace(5).iter().map(|state| hash(state)).collect();
+        let steps = honest.len() as u64 - 1;
+
+        // the defender's run goes wrong at step 9: a different sum, carried through to the end
+        let bad_step = 9;
+        let claimed: Vec<[u8; 32]> = trace(5)
+            .iter()
+            .enumerate()
+            .map(|(i, state)| {
+                let mut interp = Interpreter::decode_state(state, MEMORY).unwrap();
+                if i >= bad_step {
+                    interp.locals[1] = Value::I32(interp.locals[1].as_i32().unwrap() + 1);
+                }
+                interp.compute_state_hash()
+            })
+            .collect();
+        assert_eq!(claimed[0], honest[0]);
+        assert_ne!(claimed[steps as usize], honest[steps as usize]);
+
+        let (mut start, mut end) = (0, steps);
+        let mut rounds = 0;
+        while Turn::of(start, end, true, false) == Turn::Defender {
+            let mid = midpoint(start, end) as usize;
+            (start, end) = narrow(start, end, claimed[mid] == honest[mid]);
+            rounds += 1;
+        }
+        assert_eq!((start, end), (bad_step as u64 - 1, bad_step as u64));
+        assert!(rounds <= 64 - steps.leading_zeros());
+
+        // the agreed pre-state, run on-chain, doesn't land on the defender's claim
+        let (pre, post) = execute_step(&program, &trace(5)[start as usize], MEMORY).unwrap();
+        assert_eq!(pre, claimed[start as usize]);
+        assert_ne!(post, claimed[end as usize]);
+        assert_eq!(post, honest[end as usize]);
+    }
+}
@@ -5,14 +5,23 @@
 extern crate alloc;
 
 mod wasm_interpreter;
+mod dispute;
+#[cfg(all(test, feature = "wasmtime-support"))]
+mod differential;
 
 use stylus_sdk::{
-    alloy_primitives::{U256, B256},
+    alloy_primitives::{Address, U256, B256},
+    alloy_sol_types::sol,
     prelude::*,
+    block,
     call::RawCall,
+    evm,
+    msg,
 };
 use alloc::{vec, vec::Vec};
-use wasm_interpreter::Interpreter;
+use certus_wasm_policy::{next_section, read_leb128, read_leb_u32, read_limits, read_name, skip_import_desc};
+use dispute::{Turn, MAX_MEMORY, ROUND_TIMEOUT};
+use wasm_interpreter::{Interpreter, MAX_CALL_DEPTH, MAX_STACK_DEPTH, PAGE_SIZE};
 
 /// Execution error codes
 #[derive(Debug)]
@@ -30,6 +39,22 @@ pub enum ExecutionError {
     InvalidMemoryLimit,
     OutOfFuel,
     OutOfMemory,
+    LimitsExceeded,
+    DisallowedImport,
+    AbiVersionMismatch,
+    DisputeExists,
+    NoDispute,
+    NotYourTurn,
+    DeadlinePassed,
+    DeadlineNotReached,
+    NotNarrowed,
+    StateMismatch,
+    ProgramMismatch,
+    AlreadyResolved,
+    InvalidStepData,
+    NotOwner,
+    AlreadyInitialized,
+    BatchTooLarge,
 }
 
 impl From<ExecutionError> for Vec<u8> {
@@ -48,17 +73,77 @@ impl From<ExecutionError> for Vec<u8> {
             ExecutionError::InvalidMemoryLimit => 11,
             ExecutionError::OutOfFuel => 12,
             ExecutionError::OutOfMemory => 13,
+            ExecutionError::LimitsExceeded => 14,
+            ExecutionError::DisallowedImport => 15,
+            ExecutionError::AbiVersionMismatch => 16,
+            ExecutionError::DisputeExists => 17,
+            ExecutionError::NoDispute => 18,
+            ExecutionError::NotYourTurn => 19,
+            ExecutionError::DeadlinePassed => 20,
+            ExecutionError::DeadlineNotReached => 21,
+            ExecutionError::NotNarrowed => 22,
+            ExecutionError::StateMismatch => 23,
+            ExecutionError::ProgramMismatch => 24,
+            ExecutionError::AlreadyResolved => 25,
+            ExecutionError::InvalidStepData => 26,
+            ExecutionError::NotOwner => 27,
+            ExecutionError::AlreadyInitialized => 28,
+            ExecutionError::BatchTooLarge => 29,
         };
         vec![0xFF, code]
     }
 }
 
+sol! {
+    /// A result stored under `executionId`, the `index`th stored
+    event ResultStored(uint256 indexed index, bytes32 indexed executionId, bytes32 outputHash);
+    /// Results stored before `before` are gone, if no later result replaced them
+    event ResultsPruned(uint256 from, uint256 before);
+    /// `execute` ran a module to `outputHash`, burning `fuelUsed`
+    event ExecutionVerified(bytes32 indexed executionId, bytes32 outputHash, uint256 fuelUsed);
+    /// A bisection dispute over `jobId`'s claim ended, in a step or a timeout
+    event DisputeResolved(bytes32 indexed jobId, address winner);
+}
+
+/// Steps `execute_batch` takes in one transaction
+const MAX_BATCH: usize = 64;
+
+/// The one account `init` accepts, fixed when the program is built with CERTUS_OWNER=0x...;
+/// Stylus has no constructor to set it at deployment. Built without it, nothing can own it.
+const DEPLOYER: Address = match option_env!("CERTUS_OWNER") {
+    Some(owner) => parse_address(owner),
+    None => Address::ZERO,
+};
+
 sol_storage! {
     #[entrypoint]
     pub struct CertusStylusExecutor {
         address owner;
         uint256 execution_count;
         mapping(bytes32 => bytes32) execution_results;
+        // execution id of each stored result, in the order stored
+        mapping(uint256 => bytes32) result_ids;
+        // one past the index of each id's latest result
+        mapping(bytes32 => uint256) result_index;
+        uint256 result_count;
+        uint256 pruned_count;
+        mapping(bytes32 => Dispute) disputes;
+    }
+
+    /// A claim and the bisection game over it; see dispute.rs. `start` and `end` are the steps
+    /// both parties still agree and disagree on, with their states as the defender claims them.
+    pub struct Dispute {
+        address defender;
+        address challenger;
+        bytes32 program_hash;
+        uint256 start;
+        uint256 end;
+        bytes32 start_state;
+        bytes32 end_state;
+        bytes32 midpoint_state;
+        bool has_midpoint;
+        uint256 deadline;
+        address winner;
     }
 }
 
@@ -95,12 +180,15 @@ impl CertusStylusExecutor {
         };
 
         validate_determinism(&wasm)?;
+        validate_declared_limits(&wasm, mem_u64)?;
+        validate_exported_limits(&wasm, fuel_u64, mem_u64)?;
 
-        let output = execute_wasm(&wasm, &input, fuel_u64, mem_u64)?;
+        let (output, fuel_used) = execute_wasm(&wasm, &input, fuel_u64, mem_u64)?;
 
         let exec_id = compute_execution_id(&wasm, &input);
         let output_hash = compute_sha256(&output);
-        self.execution_results.setter(exec_id).set(output_hash);
+        self.store_result(exec_id, output_hash);
+        evm::log(ExecutionVerified { executionId: exec_id, outputHash: output_hash, fuelUsed: U256::from(fuel_used) });
 
         Ok(output)
     }
@@ -112,52 +200,508 @@ impl CertusStylusExecutor {
     pub fn get_execution_result(&self, execution_id: B256) -> B256 {
         self.execution_results.get(execution_id)
     }
+
+    /// Make the deployer the owner of a freshly deployed executor; only it may call this
+    pub fn init(&mut self) -> Result<(), Vec<u8>> {
+        if self.owner.get() != Address::ZERO {
+            return Err(ExecutionError::AlreadyInitialized.into());
+        }
+        if DEPLOYER == Address::ZERO || msg::sender() != DEPLOYER {
+            return Err(ExecutionError::NotOwner.into());
+        }
+        self.owner.set(DEPLOYER);
+        Ok(())
+    }
+
+    /// Owner: drop the results stored before the `before_count`th, keeping any whose
+    /// execution was stored again since. Returns how many were dropped.
+    pub fn prune_results(&mut self, before_count: U256) -> Result<U256, Vec<u8>> {
+        if msg::sender() != self.owner.get() {
+            return Err(ExecutionError::NotOwner.into());
+        }
+        let from = self.pruned_count.get();
+        let before = before_count.min(self.result_count.get());
+        let mut dropped = U256::ZERO;
+        let mut index = from;
+        while index < before {
+            let exec_id = self.result_ids.get(index);
+            if self.result_index.get(exec_id) == index + U256::from(1) {
+                self.execution_results.delete(exec_id);
+                self.result_index.delete(exec_id);
+                dropped += U256::from(1);
+            }
+            self.result_ids.delete(index);
+            index += U256::from(1);
+        }
+        if before > from {
+            self.pruned_count.set(before);
+            evm::log(ResultsPruned { from, before });
+        }
+        Ok(dropped)
+    }
+
+    /// Commit to a run of `total_steps` steps of `program` on `input` with `fuel`, ending in
+    /// `end_state`. The start state is derived here (`dispute::initial_state`), not taken from
+    /// the defender. Open to challenge for one round.
+    pub fn commit_claim(
+        &mut self,
+        claim_id: B256,
+        program: Vec<u8>,
+        input: Vec<u8>,
+        fuel: U256,
+        total_steps: U256,
+        end_state: B256,
+    ) -> Result<(), Vec<u8>> {
+        if self.disputes.get(claim_id).defender.get() != Address::ZERO {
+            return Err(ExecutionError::DisputeExists.into());
+        }
+        let total_steps: u64 = match total_steps.try_into() {
+            Ok(steps) if steps > 0 => steps,
+            _ => return Err(ExecutionError::InvalidStepData.into()),
+        };
+        let fuel: u64 = match fuel.try_into() {
+            Ok(fuel) if fuel > 0 => fuel,
+            _ => return Err(ExecutionError::InvalidFuelLimit.into()),
+        };
+        let start_state = dispute::initial_state(&program, &input, fuel, MAX_MEMORY)
+            .map_err(|_| ExecutionError::InvalidStepData)?
+            .compute_state_hash();
+        let mut dispute = self.disputes.setter(claim_id);
+        dispute.defender.set(msg::sender());
+        dispute.program_hash.set(B256::from(dispute::program_hash(&program)));
+        dispute.start.set(U256::ZERO);
+        dispute.end.set(U256::from(total_steps));
+        dispute.start_state.set(B256::from(start_state));
+        dispute.end_state.set(end_state);
+        dispute.deadline.set(U256::from(block::timestamp() + ROUND_TIMEOUT));
+        Ok(())
+    }
+
+    /// Dispute a claim's end state; the defender must then bisect
+    pub fn challenge(&mut self, claim_id: B256) -> Result<(), Vec<u8>> {
+        self.check_turn(claim_id, Turn::Unchallenged)?;
+        let mut dispute = self.disputes.setter(claim_id);
+        if msg::sender() == dispute.defender.get() {
+            return Err(ExecutionError::NotYourTurn.into());
+        }
+        dispute.challenger.set(msg::sender());
+        dispute.deadline.set(U256::from(block::timestamp() + ROUND_TIMEOUT));
+        Ok(())
+    }
+
+    /// Defender: the state at the midpoint of the disputed range
+    pub fn post_midpoint(&mut self, claim_id: B256, state: B256) -> Result<(), Vec<u8>> {
+        self.check_turn(claim_id, Turn::Defender)?;
+        let mut dispute = self.disputes.setter(claim_id);
+        if msg::sender() != dispute.defender.get() {
+            return Err(ExecutionError::NotYourTurn.into());
+        }
+        dispute.midpoint_state.set(state);
+        dispute.has_midpoint.set(true);
+        dispute.deadline.set(U256::from(block::timestamp() + ROUND_TIMEOUT));
+        Ok(())
+    }
+
+    /// Challenger: whether it agrees with the midpoint state, keeping the half it disputes
+    pub fn pick(&mut self, claim_id: B256, agree: bool) -> Result<(), Vec<u8>> {
+        self.check_turn(claim_id, Turn::Challenger)?;
+        let mut dispute = self.disputes.setter(claim_id);
+        if msg::sender() != dispute.challenger.get() {
+            return Err(ExecutionError::NotYourTurn.into());
+        }
+        let (start, end) = (dispute.start.get().to::<u64>(), dispute.end.get().to::<u64>());
+        let (new_start, new_end) = dispute::narrow(start, end, agree);
+        let midpoint = dispute.midpoint_state.get();
+        if agree {
+            dispute.start.set(U256::from(new_start));
+            dispute.start_state.set(midpoint);
+        } else {
+            dispute.end.set(U256::from(new_end));
+            dispute.end_state.set(midpoint);
+        }
+        dispute.has_midpoint.set(false);
+        dispute.deadline.set(U256::from(block::timestamp() + ROUND_TIMEOUT));
+        Ok(())
+    }
+
+    /// Settle a dispute narrowed to one step by running it: `pre_state` must be the agreed
+    /// state, encoded as `Interpreter::encode_state` does. The defender wins iff the step
+    /// lands on the state it claimed.
+    pub fn resolve_step(&mut self, claim_id: B256, program: Vec<u8>, pre_state: Vec<u8>) -> Result<Address, Vec<u8>> {
+        self.check_turn(claim_id, Turn::Step)?;
+        let mut dispute = self.disputes.setter(claim_id);
+        if B256::from(dispute::program_hash(&program)) != dispute.program_hash.get() {
+            return Err(ExecutionError::ProgramMismatch.into());
+        }
+        let (mut interp, code) = dispute::load_step(&program, &pre_state, MAX_MEMORY)
+            .map_err(|_| ExecutionError::InvalidStepData)?;
+        if B256::from(interp.compute_state_hash()) != dispute.start_state.get() {
+            return Err(ExecutionError::StateMismatch.into());
+        }
+        let post = dispute::step_hash(&mut interp, code).ok().map(B256::from);
+        let winner = if post == Some(dispute.end_state.get()) {
+            dispute.defender.get()
+        } else {
+            dispute.challenger.get()
+        };
+        dispute.winner.set(winner);
+        evm::log(DisputeResolved { jobId: claim_id, winner });
+        Ok(winner)
+    }
+
+    /// Settle a dispute whose deadline passed: whoever owed the next move loses
+    pub fn claim_timeout(&mut self, claim_id: B256) -> Result<Address, Vec<u8>> {
+        let turn = self.turn(claim_id)?;
+        let mut dispute = self.disputes.setter(claim_id);
+        if U256::from(block::timestamp()) <= dispute.deadline.get() {
+            return Err(ExecutionError::DeadlineNotReached.into());
+        }
+        let winner = if turn.defender_wins_timeout() {
+            dispute.defender.get()
+        } else {
+            dispute.challenger.get()
+        };
+        dispute.winner.set(winner);
+        evm::log(DisputeResolved { jobId: claim_id, winner });
+        Ok(winner)
+    }
+
+    /// (defender, challenger, program hash, start, end, start state, end state, midpoint
+    /// state, deadline, winner)
+    pub fn get_dispute(
+        &self,
+        claim_id: B256,
+    ) -> (Address, Address, B256, U256, U256, B256, B256, B256, U256, Address) {
+        let dispute = self.disputes.get(claim_id);
+        (
+            dispute.defender.get(),
+            dispute.challenger.get(),
+            dispute.program_hash.get(),
+            dispute.start.get(),
+            dispute.end.get(),
+            dispute.start_state.get(),
+            dispute.end_state.get(),
+            dispute.midpoint_state.get(),
+            dispute.deadline.get(),
+            dispute.winner.get(),
+        )
+    }
+
+    /// Run one instruction for CertusBisection: `step_data` is the program's length as a u32,
+    /// the program, then the pre-state. Returns the post-state's hash.
+    pub fn execute_step(&mut self, step_data: Vec<u8>, pre_state_hash: B256) -> Result<B256, Vec<u8>> {
+        execute_single_step(&step_data, pre_state_hash)
+    }
+
+    /// Run several single steps, each as `execute_step` does, storing each post-state hash
+    /// under the id of its step data and pre-state. A step that fails gets a zero hash and
+    /// stores nothing, leaving the rest of the batch to stand.
+    pub fn execute_batch(&mut self, steps: Vec<Vec<u8>>, pre_state_hashes: Vec<B256>) -> Result<Vec<B256>, Vec<u8>> {
+        if steps.len() > MAX_BATCH {
+            return Err(ExecutionError::BatchTooLarge.into());
+        }
+        if steps.len() != pre_state_hashes.len() {
+            return Err(ExecutionError::InvalidStepData.into());
+        }
+        let mut posts = Vec::with_capacity(steps.len());
+        for (step_data, pre_state_hash) in steps.iter().zip(pre_state_hashes) {
+            let post = match execute_single_step(step_data, pre_state_hash) {
+                Ok(post) => {
+                    self.store_result(compute_execution_id(step_data, pre_state_hash.as_slice()), post);
+                    post
+                }
+                Err(_) => B256::ZERO,
+            };
+            posts.push(post);
+        }
+        Ok(posts)
+    }
+
+    pub fn get_result_count(&self) -> (U256, U256) {
+        (self.pruned_count.get(), self.result_count.get())
+    }
 }
 
-/// Validate Wasm module determinism constraints.
-/// Rejects modules with float operations, WASI imports, or thread operations.
-/// Must match node/executor/src/sandbox.rs validation logic
-fn validate_determinism(wasm: &[u8]) -> Result<(), Vec<u8>> {
-    if wasm.len() < 8 {
-        return Err(ExecutionError::InvalidWasmMagic.into());
+impl CertusStylusExecutor {
+    /// Store a result and index it for pruning
+    fn store_result(&mut self, exec_id: B256, output_hash: B256) {
+        let index = self.result_count.get();
+        self.execution_results.setter(exec_id).set(output_hash);
+        self.result_ids.setter(index).set(exec_id);
+        self.result_index.setter(exec_id).set(index + U256::from(1));
+        self.result_count.set(index + U256::from(1));
+        evm::log(ResultStored { index, executionId: exec_id, outputHash: output_hash });
     }
 
-    if &wasm[0..4] != b"\0asm" {
-        return Err(ExecutionError::InvalidWasmMagic.into());
+    /// Whose move it is in an open dispute
+    fn turn(&self, claim_id: B256) -> Result<Turn, Vec<u8>> {
+        let dispute = self.disputes.get(claim_id);
+        if dispute.defender.get() == Address::ZERO {
+            return Err(ExecutionError::NoDispute.into());
+        }
+        if dispute.winner.get() != Address::ZERO {
+            return Err(ExecutionError::AlreadyResolved.into());
+        }
+        Ok(Turn::of(
+            dispute.start.get().to::<u64>(),
+            dispute.end.get().to::<u64>(),
+            dispute.challenger.get() != Address::ZERO,
+            dispute.has_midpoint.get(),
+        ))
+    }
+
+    /// Check it is `expected`'s move and its round is still open
+    fn check_turn(&self, claim_id: B256, expected: Turn) -> Result<(), Vec<u8>> {
+        if self.turn(claim_id)? != expected {
+            return Err(ExecutionError::NotYourTurn.into());
+        }
+        if U256::from(block::timestamp()) > self.disputes.get(claim_id).deadline.get() {
+            return Err(ExecutionError::DeadlinePassed.into());
+        }
+        Ok(())
     }
+}
 
-    if &wasm[4..8] != &[1, 0, 0, 0] {
-        return Err(ExecutionError::InvalidWasmVersion.into());
+/// One step for `execute_step`: the post-state hash of the step data's program run one
+/// instruction from its pre-state, which must hash to `pre_state_hash`
+fn execute_single_step(step_data: &[u8], pre_state_hash: B256) -> Result<B256, Vec<u8>> {
+    let (program, pre_state) = dispute::split_step_data(step_data)
+        .map_err(|_| ExecutionError::InvalidStepData)?;
+    let (mut interp, code) = dispute::load_step(program, pre_state, MAX_MEMORY)
+        .map_err(|_| ExecutionError::InvalidStepData)?;
+    if B256::from(interp.compute_state_hash()) != pre_state_hash {
+        return Err(ExecutionError::StateMismatch.into());
     }
+    let post = dispute::step_hash(&mut interp, code).map_err(|_| ExecutionError::ExecutionFailed)?;
+    Ok(B256::from(post))
+}
 
-    for &byte in &wasm[8..] {
-        if (0x43..=0x98).contains(&byte) || (0x99..=0xBF).contains(&byte) {
-            return Err(ExecutionError::FloatOpcodeDetected.into());
+/// Validate Wasm module determinism constraints: no float, SIMD or thread instructions, and no
+/// imports outside the allowlist. The policy itself is certus-wasm-policy, shared with the
+/// python-verifier and the executor node; this only maps its verdicts to error codes.
+fn validate_determinism(wasm: &[u8]) -> Result<(), Vec<u8>> {
+    use certus_wasm_policy::Violation;
+
+    let err = |violation: Violation, malformed: ExecutionError| match violation {
+        Violation::InvalidMagic => ExecutionError::InvalidWasmMagic,
+        Violation::InvalidVersion => ExecutionError::InvalidWasmVersion,
+        Violation::Malformed(_) => malformed,
+        Violation::FloatOpcode { .. } | Violation::SimdOpcode { .. } => ExecutionError::FloatOpcodeDetected,
+        Violation::AtomicOpcode { .. } => ExecutionError::ThreadOpcodeDetected,
+        Violation::DisallowedImport { module, .. } if module.starts_with(b"wasi_") => ExecutionError::WasiImportDetected,
+        Violation::DisallowedImport { .. } => ExecutionError::DisallowedImport,
+    };
+    certus_wasm_policy::check_header(wasm).map_err(|v| err(v, ExecutionError::InvalidWasmMagic))?;
+    certus_wasm_policy::check_opcodes(wasm).map_err(|v| err(v, ExecutionError::CompilationFailed))?;
+    certus_wasm_policy::check_imports(wasm).map_err(|v| err(v, ExecutionError::DisallowedImport))?;
+    Ok(())
+}
+
+/// Bytes of memory the module starts with: the minimum of the memory it imports or defines,
+/// or none. Its maximum is the job's memory limit, not what the module declares.
+fn initial_memory(wasm: &[u8]) -> Result<u64, Vec<u8>> {
+    let malformed = || Vec::from(ExecutionError::CompilationFailed);
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let (id, mut cursor, end) = next_section(wasm, &mut pos).map_err(|_| malformed())?;
+        let section = &wasm[..end];
+        match id {
+            2 => {
+                let count = read_leb_u32(section, &mut cursor).ok_or_else(malformed)?;
+                for _ in 0..count {
+                    read_name(section, &mut cursor).ok_or_else(malformed)?;
+                    read_name(section, &mut cursor).ok_or_else(malformed)?;
+                    if section.get(cursor) == Some(&0x02) {
+                        cursor += 1;
+                        let pages = read_limits(section, &mut cursor).ok_or_else(malformed)?;
+                        return Ok(pages as u64 * PAGE_SIZE as u64);
+                    }
+                    skip_import_desc(section, &mut cursor).ok_or_else(malformed)?;
+                }
+            }
+            5 => {
+                if read_leb_u32(section, &mut cursor).ok_or_else(malformed)? > 0 {
+                    let pages = read_limits(section, &mut cursor).ok_or_else(malformed)?;
+                    return Ok(pages as u64 * PAGE_SIZE as u64);
+                }
+            }
+            _ => {}
         }
     }
+    Ok(0)
+}
+
+/// Custom section written by the Certus compiler: six little-endian u32s
+/// (version, max_call_depth, max_operand_stack, max_locals, memory_pages, flags).
+const LIMITS_SECTION: &[u8] = b"certus.limits";
+const LIMITS_VERSION: u32 = 1;
+
+/// Reject modules whose declared limits exceed what this interpreter can run,
+/// instead of trapping partway through a dispute. Modules without the section are
+/// accepted unchanged. Must match python-verifier/src/compiler/limits.rs
+fn validate_declared_limits(wasm: &[u8], mem_limit: u64) -> Result<(), Vec<u8>> {
+    let payload = match find_custom_section(wasm, LIMITS_SECTION) {
+        Some(payload) => payload,
+        None => return Ok(()),
+    };
+    if payload.len() != 24 {
+        return Err(ExecutionError::LimitsExceeded.into());
+    }
 
-    if contains_pattern(wasm, b"wasi_snapshot") {
-        return Err(ExecutionError::WasiImportDetected.into());
+    let word = |i: usize| {
+        u32::from_le_bytes([payload[i * 4], payload[i * 4 + 1], payload[i * 4 + 2], payload[i * 4 + 3]])
+    };
+    if word(0) != LIMITS_VERSION {
+        return Err(ExecutionError::LimitsExceeded.into());
     }
 
-    for &byte in &wasm[8..] {
-        if byte == 0xFE {
-            return Err(ExecutionError::ThreadOpcodeDetected.into());
+    let call_depth = word(1) as usize;
+    let operand_stack = word(2) as usize;
+    let memory_bytes = word(4) as u64 * 65536;
+    if call_depth > MAX_CALL_DEPTH || operand_stack > MAX_STACK_DEPTH || memory_bytes > mem_limit {
+        return Err(ExecutionError::LimitsExceeded.into());
+    }
+
+    Ok(())
+}
+
+/// Payload of the first custom section named `name`
+fn find_custom_section<'a>(wasm: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let (id, mut cursor, end) = next_section(wasm, &mut pos).ok()?;
+        if id == 0 && read_name(&wasm[..end], &mut cursor)? == name {
+            return Some(&wasm[cursor..end]);
         }
     }
+    None
+}
 
+/// Globals exported by modules the Certus compiler produces: the heap limit, the fuel the
+/// module traps past, and the ABI it was compiled for.
+const HEAP_LIMIT_EXPORT: &[u8] = b"__certus_heap_limit";
+const GAS_LIMIT_EXPORT: &[u8] = b"__certus_gas_limit";
+const ABI_VERSION_EXPORT: &[u8] = b"__certus_abi_version";
+const ABI_VERSION: u32 = 1;
+
+/// Reject a compiled module built for another ABI, or for less fuel or more memory than the
+/// dispute runs it with. Modules exporting none of the globals are accepted unchanged.
+/// Must match python-verifier/src/compiler/abi.rs
+fn validate_exported_limits(wasm: &[u8], fuel_limit: u64, mem_limit: u64) -> Result<(), Vec<u8>> {
+    let malformed = || Vec::from(ExecutionError::LimitsExceeded);
+    let mut imported_globals = 0u32;
+    // value of each defined global that is an immutable i32 constant
+    let mut constants: Vec<Option<u32>> = Vec::new();
+    let mut exported = [None; 3];
+
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let (id, mut cursor, end) = next_section(wasm, &mut pos).map_err(|_| malformed())?;
+        let section = &wasm[..end];
+        match id {
+            2 => {
+                let count = read_leb_u32(section, &mut cursor).ok_or_else(malformed)?;
+                for _ in 0..count {
+                    read_name(section, &mut cursor).ok_or_else(malformed)?;
+                    read_name(section, &mut cursor).ok_or_else(malformed)?;
+                    if section.get(cursor) == Some(&0x03) {
+                        imported_globals += 1;
+                    }
+                    skip_import_desc(section, &mut cursor).ok_or_else(malformed)?;
+                }
+            }
+            6 => {
+                let count = read_leb_u32(section, &mut cursor).ok_or_else(malformed)?;
+                for _ in 0..count {
+                    let (val_type, mutable) = match section.get(cursor..cursor + 2) {
+                        Some(&[val_type, mutable]) => (val_type, mutable),
+                        _ => return Err(malformed()),
+                    };
+                    cursor += 2;
+                    let value = read_const_expr(section, &mut cursor).ok_or_else(malformed)?;
+                    constants.push(value.filter(|_| val_type == 0x7F && mutable == 0));
+                }
+            }
+            7 => {
+                let count = read_leb_u32(section, &mut cursor).ok_or_else(malformed)?;
+                for _ in 0..count {
+                    let name = read_name(section, &mut cursor).ok_or_else(malformed)?;
+                    let kind = *section.get(cursor).ok_or_else(malformed)?;
+                    cursor += 1;
+                    let index = read_leb_u32(section, &mut cursor).ok_or_else(malformed)?;
+                    let slot = match name {
+                        HEAP_LIMIT_EXPORT => 0,
+                        GAS_LIMIT_EXPORT => 1,
+                        ABI_VERSION_EXPORT => 2,
+                        _ => continue,
+                    };
+                    if kind != 0x03 {
+                        return Err(malformed());
+                    }
+                    exported[slot] = Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if exported.iter().all(Option::is_none) {
+        return Ok(());
+    }
+    let mut limits = [0u32; 3];
+    for (slot, index) in exported.into_iter().enumerate() {
+        limits[slot] = index
+            .and_then(|index| index.checked_sub(imported_globals))
+            .and_then(|defined| constants.get(defined as usize).copied().flatten())
+            .ok_or_else(malformed)?;
+    }
+    let [heap_limit, gas_limit, abi_version] = limits;
+    if abi_version != ABI_VERSION {
+        return Err(ExecutionError::AbiVersionMismatch.into());
+    }
+    if heap_limit as u64 > mem_limit || fuel_limit > gas_limit as u64 {
+        return Err(ExecutionError::LimitsExceeded.into());
+    }
     Ok(())
 }
 
+/// Step over a global's initializer, returning its value if it is an i32.const
+fn read_const_expr(data: &[u8], pos: &mut usize) -> Option<Option<u32>> {
+    let opcode = *data.get(*pos)?;
+    *pos += 1;
+    let value = match opcode {
+        0x41 => Some(read_leb128(data, pos, 32, true).ok()? as u32),
+        0x42 => {
+            read_leb128(data, pos, 64, true).ok()?;
+            None
+        }
+        // global.get, ref.func
+        0x23 | 0xD2 => {
+            read_leb_u32(data, pos)?;
+            None
+        }
+        // ref.null and its reference type
+        0xD0 => {
+            *pos += 1;
+            None
+        }
+        _ => return None,
+    };
+    (*data.get(*pos)? == 0x0B).then(|| {
+        *pos += 1;
+        value
+    })
+}
+
 /// Execute Wasm instruction and return state hash.
 /// Input encodes: [opcode, initial_state_data].
-/// Returns SHA256(stack + locals + memory + pc + fuel) after execution.
+/// Returns SHA256(stack + locals + memory + pc + fuel) after execution, and the fuel it used.
 fn execute_wasm(
     wasm: &[u8],
     input: &[u8],
     fuel_limit: u64,
     mem_limit: u64,
-) -> Result<Vec<u8>, Vec<u8>> {
+) -> Result<(Vec<u8>, u64), Vec<u8>> {
     if wasm.is_empty() {
         return Err(ExecutionError::ExecutionFailed.into());
     }
@@ -167,7 +711,11 @@ fn execute_wasm(
     }
 
     let memory_size = (mem_limit as usize).min(10 * 1024 * 1024);
-    let mut interpreter = Interpreter::new(memory_size, fuel_limit);
+    let initial_memory = initial_memory(wasm)?;
+    if initial_memory > memory_size as u64 {
+        return Err(ExecutionError::LimitsExceeded.into());
+    }
+    let mut interpreter = Interpreter::new(initial_memory as usize, memory_size, fuel_limit);
 
     if input.is_empty() {
         return Err(ExecutionError::ExecutionFailed.into());
@@ -179,20 +727,7 @@ fn execute_wasm(
         .map_err(|_| ExecutionError::ExecutionFailed)?;
 
     let state_hash = interpreter.compute_state_hash();
-    Ok(state_hash.to_vec())
-}
-
-fn contains_pattern(data: &[u8], pattern: &[u8]) -> bool {
-    if pattern.len() > data.len() {
-        return false;
-    }
-
-    for i in 0..=(data.len() - pattern.len()) {
-        if &data[i..i + pattern.len()] == pattern {
-            return true;
-        }
-    }
-    false
+    Ok((state_hash.to_vec(), fuel_limit - interpreter.fuel))
 }
 
 fn compute_execution_id(wasm: &[u8], input: &[u8]) -> B256 {
@@ -210,6 +745,27 @@ fn compute_sha256(data: &[u8]) -> B256 {
     B256::from_slice(&result)
 }
 
+/// A 0x-prefixed hex address, read while the program builds
+const fn parse_address(hex: &str) -> Address {
+    const fn nibble(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            b'A'..=b'F' => c - b'A' + 10,
+            _ => panic!("CERTUS_OWNER is not a hex address"),
+        }
+    }
+    let hex = hex.as_bytes();
+    assert!(hex.len() == 42 && hex[0] == b'0' && hex[1] == b'x', "CERTUS_OWNER is not a 0x-prefixed address");
+    let mut bytes = [0u8; 20];
+    let mut i = 0;
+    while i < 20 {
+        bytes[i] = nibble(hex[2 + 2 * i]) << 4 | nibble(hex[3 + 2 * i]);
+        i += 1;
+    }
+    Address::new(bytes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -218,8 +774,7 @@ mod tests {
     fn test_validate_determinism_valid() {
         let wasm = [
             0x00, 0x61, 0x73, 0x6D, // magic
-            0x01, 0x00, 0x00, 0x00, // version
-            0x00, // empty module
+            0x01, 0x00, 0x00, 0x00, // version, and no sections
         ];
         assert!(validate_determinism(&wasm).is_ok());
     }
@@ -233,41 +788,204 @@ mod tests {
         assert!(validate_determinism(&wasm).is_err());
     }
 
+    #[test]
+    fn test_parse_address() {
+        let owner = parse_address("0x00000000000000000000000000000000000000Ff");
+        assert_eq!(owner, Address::with_last_byte(0xff));
+        assert_eq!(parse_address("0x0000000000000000000000000000000000000000"), Address::ZERO);
+    }
+
+    /// Header plus a code section holding one function body without locals
+    fn with_code(body: &[u8]) -> Vec<u8> {
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 10, body.len() as u8 + 3, 1];
+        wasm.push(body.len() as u8 + 1);
+        wasm.push(0);
+        wasm.extend_from_slice(body);
+        wasm
+    }
+
     #[test]
     fn test_validate_determinism_float_opcode() {
-        let wasm = [
-            0x00, 0x61, 0x73, 0x6D,
-            0x01, 0x00, 0x00, 0x00,
-            0x43, // f32.const opcode
+        let float = Err(Vec::from(ExecutionError::FloatOpcodeDetected));
+        // f32.const 0
+        assert_eq!(validate_determinism(&with_code(&[0x43, 0, 0, 0, 0, 0x1A, 0x0B])), float);
+        // i32.trunc_f64_s, i32.trunc_sat_f32_s, and any SIMD instruction
+        assert_eq!(validate_determinism(&with_code(&[0x41, 0, 0xAA, 0x0B])), float);
+        assert_eq!(validate_determinism(&with_code(&[0x41, 0, 0xFC, 0x00, 0x0B])), float);
+        assert_eq!(validate_determinism(&with_code(&[0xFD, 0x0C, 0x0B])), float);
+    }
+
+    #[test]
+    fn test_validate_determinism_reads_only_instructions() {
+        // i32.const 0x43, i64.const 0xBF, a load at offset 0x98, i32.eqz, i64.extend_i32_u,
+        // memory.fill: integer instructions and immediates in the old float range
+        let body = [
+            0x41, 0xC3, 0x00, 0x1A, 0x42, 0xBF, 0x01, 0x1A, 0x41, 0, 0x28, 0x02, 0x98, 0x01, 0x45, 0xAD, 0x1A,
+            0x41, 0, 0x41, 0, 0x41, 0, 0xFC, 0x0B, 0x00, 0x0B,
         ];
-        assert!(validate_determinism(&wasm).is_err());
+        assert!(validate_determinism(&with_code(&body)).is_ok());
+
+        // float bytes in a data segment and a custom section's name are only data
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+        wasm.extend_from_slice(&[0, 4, 3, 0x43, 0x44, 0xFE]);
+        wasm.extend_from_slice(&[11, 9, 1, 0, 0x41, 0, 0x0B, 3, 0x43, 0x99, 0xBF]);
+        assert!(validate_determinism(&wasm).is_ok());
+
+        // a body that runs past its end cannot be checked
+        assert!(validate_determinism(&with_code(&[0x41, 0x80])).is_err());
+    }
+
+    /// Header plus an import section holding one import
+    fn with_import(module: &[u8], name: &[u8], desc: &[u8]) -> Vec<u8> {
+        let mut section = vec![1, module.len() as u8];
+        section.extend_from_slice(module);
+        section.push(name.len() as u8);
+        section.extend_from_slice(name);
+        section.extend_from_slice(desc);
+
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 2, section.len() as u8];
+        wasm.extend_from_slice(&section);
+        wasm
     }
 
     #[test]
     fn test_validate_determinism_wasi_import() {
-        let mut wasm = vec![
-            0x00, 0x61, 0x73, 0x6D,
-            0x01, 0x00, 0x00, 0x00,
-        ];
-        wasm.extend_from_slice(b"wasi_snapshot");
+        let wasm = with_import(b"wasi_snapshot_preview1", b"proc_exit", &[0x00, 0x00]);
         assert!(validate_determinism(&wasm).is_err());
+        assert_eq!(validate_determinism(&wasm), Err(Vec::from(ExecutionError::WasiImportDetected)));
+    }
+
+    #[test]
+    fn test_validate_imports_allowlist() {
+        assert!(validate_determinism(&with_import(b"env", b"memory", &[0x02, 0x00, 0x10])).is_ok());
+        assert!(validate_determinism(&with_import(b"env", b"abort", &[0x00, 0x01])).is_ok());
+
+        // any other host function, not only WASI
+        let fd_write = with_import(b"env", b"fd_write", &[0x00, 0x00]);
+        assert_eq!(validate_determinism(&fd_write), Err(Vec::from(ExecutionError::DisallowedImport)));
+
+        // a forbidden name outside the import section is only bytes
+        let mut named = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0, 27, 4];
+        named.extend_from_slice(b"notewasi_snapshot_preview1");
+        assert!(validate_determinism(&named).is_ok());
+
+        // a truncated import section cannot be checked
+        let mut truncated = with_import(b"env", b"memory", &[0x02, 0x00, 0x10]);
+        truncated.pop();
+        assert!(validate_determinism(&truncated).is_err());
     }
 
     #[test]
     fn test_validate_determinism_thread_opcode() {
-        let wasm = [
-            0x00, 0x61, 0x73, 0x6D,
-            0x01, 0x00, 0x00, 0x00,
-            0xFE, // atomic operations prefix
-        ];
-        assert!(validate_determinism(&wasm).is_err());
+        // memory.atomic.notify
+        let wasm = with_code(&[0x41, 0, 0x41, 0, 0xFE, 0x00, 0x02, 0x00, 0x1A, 0x0B]);
+        assert_eq!(validate_determinism(&wasm), Err(Vec::from(ExecutionError::ThreadOpcodeDetected)));
+    }
+
+    fn with_limits(call_depth: u32, operand_stack: u32, memory_pages: u32) -> Vec<u8> {
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+        let mut payload = Vec::new();
+        for word in [LIMITS_VERSION, call_depth, operand_stack, 40, memory_pages, 0] {
+            payload.extend_from_slice(&word.to_le_bytes());
+        }
+        wasm.push(0);
+        wasm.push((1 + LIMITS_SECTION.len() + payload.len()) as u8);
+        wasm.push(LIMITS_SECTION.len() as u8);
+        wasm.extend_from_slice(LIMITS_SECTION);
+        wasm.extend_from_slice(&payload);
+        wasm
+    }
+
+    #[test]
+    fn test_declared_limits() {
+        let one_mb = 1024 * 1024;
+        assert!(validate_declared_limits(&with_limits(3, 40, 16), one_mb).is_ok());
+        assert!(validate_declared_limits(&with_limits(300, 40, 16), one_mb).is_err());
+        assert!(validate_declared_limits(&with_limits(3, 2000, 16), one_mb).is_err());
+        assert!(validate_declared_limits(&with_limits(3, 40, 64), one_mb).is_err());
+
+        // no section, nothing declared
+        let empty = [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+        assert!(validate_declared_limits(&empty, one_mb).is_ok());
+    }
+
+    #[test]
+    fn test_memory_starts_at_the_declared_minimum() {
+        let two_pages = with_import(b"env", b"memory", &[0x02, 0x00, 0x02]);
+        assert_eq!(initial_memory(&two_pages), Ok(2 * PAGE_SIZE as u64));
+        let defined = [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 5, 3, 1, 0x00, 0x01];
+        assert_eq!(initial_memory(&defined), Ok(PAGE_SIZE as u64));
+        let empty = [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+        assert_eq!(initial_memory(&empty), Ok(0));
+
+        // memory.size sees the module's pages, not the job's limit
+        let limit = 4 * PAGE_SIZE;
+        let (state, _) = execute_wasm(&two_pages, &[0x3F], 1000, limit as u64).unwrap();
+        let mut expected = Interpreter::new(2 * PAGE_SIZE, limit, 1000);
+        expected.execute_opcode(0x3F, &two_pages).unwrap();
+        assert_eq!(expected.stack, vec![wasm_interpreter::Value::I32(2)]);
+        assert_eq!(state, expected.compute_state_hash().to_vec());
+
+        assert_eq!(execute_wasm(&two_pages, &[0x3F], 1000, PAGE_SIZE as u64), Err(Vec::from(ExecutionError::LimitsExceeded)));
+    }
+
+    fn sleb(mut value: i32, out: &mut Vec<u8>) {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+                out.push(byte);
+                return;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    // one mutable counter ahead of the three exported immutable limits
+    fn with_exported_limits(heap_limit: i32, gas_limit: i32, abi_version: i32) -> Vec<u8> {
+        let mut globals = vec![4, 0x7F, 0x01, 0x41, 0x00, 0x0B];
+        for value in [heap_limit, gas_limit, abi_version] {
+            globals.extend_from_slice(&[0x7F, 0x00, 0x41]);
+            sleb(value, &mut globals);
+            globals.push(0x0B);
+        }
+        let mut exports = vec![3];
+        for (index, name) in [HEAP_LIMIT_EXPORT, GAS_LIMIT_EXPORT, ABI_VERSION_EXPORT].iter().enumerate() {
+            exports.push(name.len() as u8);
+            exports.extend_from_slice(name);
+            exports.extend_from_slice(&[0x03, index as u8 + 1]);
+        }
+
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+        for (id, section) in [(6u8, globals), (7, exports)] {
+            wasm.push(id);
+            wasm.push(section.len() as u8);
+            wasm.extend_from_slice(&section);
+        }
+        wasm
     }
 
     #[test]
-    fn test_contains_pattern() {
-        assert!(contains_pattern(b"hello world", b"world"));
-        assert!(!contains_pattern(b"hello", b"goodbye"));
-        assert!(!contains_pattern(b"hi", b"hello"));
+    fn test_exported_limits() {
+        let four_mb = 0x400000;
+        let wasm = with_exported_limits(four_mb, 100_000_000, 1);
+        assert!(validate_exported_limits(&wasm, 1_000_000, four_mb as u64).is_ok());
+        // more fuel than the module traps at, or less memory than its heap
+        assert_eq!(validate_exported_limits(&wasm, 200_000_000, four_mb as u64), Err(Vec::from(ExecutionError::LimitsExceeded)));
+        assert_eq!(validate_exported_limits(&wasm, 1_000_000, 1024 * 1024), Err(Vec::from(ExecutionError::LimitsExceeded)));
+
+        let future = with_exported_limits(four_mb, 100_000_000, 2);
+        assert_eq!(validate_exported_limits(&future, 1_000_000, four_mb as u64), Err(Vec::from(ExecutionError::AbiVersionMismatch)));
+
+        // a limit that isn't a constant says nothing
+        let mut mutable = wasm.clone();
+        let at = mutable.windows(3).position(|w| w == [0x7F, 0x00, 0x41]).unwrap();
+        mutable[at + 1] = 0x01;
+        assert!(validate_exported_limits(&mutable, 1_000_000, four_mb as u64).is_err());
+
+        // client modules export none of them
+        let empty = [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+        assert!(validate_exported_limits(&empty, 1_000_000, four_mb as u64).is_ok());
     }
 
     #[test]
@@ -1,10 +1,17 @@
 // Wasm interpreter for on-chain fraud proof verification
 
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 use sha2::{Sha256, Digest};
 
-const MAX_STACK_DEPTH: usize = 1024;
-const MAX_CALL_DEPTH: usize = 256;
+use certus_wasm_policy::{read_leb128, skip_immediates};
+
+pub const MAX_STACK_DEPTH: usize = 1024;
+pub const MAX_CALL_DEPTH: usize = 256;
+pub const MAX_LABEL_DEPTH: usize = 1024;
+/// Wasm's own limit on a function's locals
+pub const MAX_LOCALS: usize = 50_000;
+pub const PAGE_SIZE: usize = 65536;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Value {
@@ -32,9 +39,29 @@ pub struct Interpreter {
     pub stack: Vec<Value>,
     pub locals: Vec<Value>,
     pub memory: Vec<u8>,
+    /// Bytes memory.grow may take memory to: the job's memory limit
+    pub max_memory: usize,
     pub pc: usize,
     pub call_stack: Vec<CallFrame>,
     pub fuel: u64,
+    /// Blocks, loops and ifs being executed, innermost last
+    pub labels: Vec<Label>,
+    /// Params and results of each type in the module's type section, for blocks typed by index
+    pub types: Vec<(usize, usize)>,
+    // Else and end of each block, by the offset of its first instruction
+    block_ends: BTreeMap<usize, (Option<usize>, usize)>,
+}
+
+/// A block, loop or if being executed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Label {
+    /// Where a branch to the label goes: a loop's first instruction, or past a block's end
+    pub target: usize,
+    /// Operand stack height below the block's values
+    pub height: usize,
+    /// Values a branch carries: a loop's params, a block's results
+    pub arity: usize,
+    pub is_loop: bool,
 }
 
 pub struct CallFrame {
@@ -43,15 +70,42 @@ pub struct CallFrame {
 }
 
 impl Interpreter {
-    pub fn new(memory_size: usize, fuel: u64) -> Self {
+    /// Memory starts at `initial_memory` bytes, the module's minimum, and memory.grow may take
+    /// it to `max_memory`, the job's limit
+    pub fn new(initial_memory: usize, max_memory: usize, fuel: u64) -> Self {
         Self {
             stack: Vec::with_capacity(MAX_STACK_DEPTH),
             locals: Vec::with_capacity(256),
-            memory: alloc::vec![0u8; memory_size],
+            memory: alloc::vec![0u8; initial_memory],
+            max_memory,
             pc: 0,
             call_stack: Vec::with_capacity(MAX_CALL_DEPTH),
             fuel,
+            labels: Vec::new(),
+            types: Vec::new(),
+            block_ends: BTreeMap::new(),
+        }
+    }
+
+    /// Run a function body: its instructions through the final end, its locals already in
+    /// place. Leaves its `results` values on the stack.
+    pub fn execute(&mut self, code: &[u8], results: usize) -> Result<(), &'static str> {
+        self.pc = 0;
+        self.block_ends.clear();
+        let base = self.labels.len();
+        // the body is a block whose end is the function's, and return branches to it
+        self.enter(Label { target: code.len(), height: self.stack.len(), arity: results, is_loop: false })?;
+        while self.labels.len() > base {
+            self.step(code)?;
         }
+        Ok(())
+    }
+
+    /// Execute the instruction at pc: one step of the bisection game
+    pub fn step(&mut self, code: &[u8]) -> Result<(), &'static str> {
+        let opcode = *code.get(self.pc).ok_or("unexpected end of code")?;
+        self.pc += 1;
+        self.execute_opcode(opcode, code)
     }
 
     pub fn push(&mut self, val: Value) -> Result<(), &'static str> {
@@ -83,27 +137,148 @@ impl Interpreter {
     }
 
     pub fn load_memory(&self, addr: usize, size: usize) -> Result<&[u8], &'static str> {
-        if addr + size > self.memory.len() {
-            return Err("memory access out of bounds");
-        }
-        Ok(&self.memory[addr..addr + size])
+        let end = self.memory_end(addr, size)?;
+        Ok(&self.memory[addr..end])
     }
 
     pub fn store_memory(&mut self, addr: usize, data: &[u8]) -> Result<(), &'static str> {
-        if addr + data.len() > self.memory.len() {
-            return Err("memory access out of bounds");
-        }
-        self.memory[addr..addr + data.len()].copy_from_slice(data);
+        let end = self.memory_end(addr, data.len())?;
+        self.memory[addr..end].copy_from_slice(data);
         Ok(())
     }
 
+    /// End of `size` bytes at `addr`, if they are all in memory
+    fn memory_end(&self, addr: usize, size: usize) -> Result<usize, &'static str> {
+        addr.checked_add(size).filter(|&end| end <= self.memory.len()).ok_or("memory access out of bounds")
+    }
+
+    /// A load or store's memarg: the alignment hint, which doesn't change the result, then the
+    /// offset
+    fn read_memarg(&mut self, bytecode: &[u8]) -> Result<usize, &'static str> {
+        let _align = self.read_leb128_u32(bytecode)?;
+        Ok(self.read_leb128_u32(bytecode)? as usize)
+    }
+
+    /// Pop an address operand, an unsigned i32, and add a memarg's offset to it
+    fn pop_address(&mut self, offset: usize) -> Result<usize, &'static str> {
+        let base = self.pop_i32()? as u32 as usize;
+        base.checked_add(offset).ok_or("memory access out of bounds")
+    }
+
+    /// memory.grow: add `delta` pages, up to `max_memory`. Returns the old size in pages, or
+    /// -1 (leaving memory as it was) past the limit.
+    pub fn grow_memory(&mut self, delta: u32) -> i32 {
+        let old_pages = self.memory.len() / PAGE_SIZE;
+        let new_len = self.memory.len() as u64 + delta as u64 * PAGE_SIZE as u64;
+        if new_len > self.max_memory as u64 {
+            return -1;
+        }
+        self.memory.resize(new_len as usize, 0);
+        old_pages as i32
+    }
+
+    /// The memory index byte of memory.size, memory.grow and the bulk memory instructions
+    fn read_memory_index(&mut self, bytecode: &[u8]) -> Result<(), &'static str> {
+        match bytecode.get(self.pc) {
+            Some(0) => {
+                self.pc += 1;
+                Ok(())
+            }
+            Some(_) => Err("only memory 0 is supported"),
+            None => Err("unexpected end of code"),
+        }
+    }
+
     pub fn execute_opcode(&mut self, opcode: u8, bytecode: &[u8]) -> Result<(), &'static str> {
-        self.consume_fuel(1)?;
+        self.consume_fuel(certus_gas::opcode_cost(opcode))?;
 
         match opcode {
             // Control flow
             0x00 => Err("unreachable executed"),
             0x01 => Ok(()),
+            0x02 | 0x03 => {
+                let (params, results) = self.read_block_type(bytecode)?;
+                let start = self.pc;
+                let height = self.stack.len().checked_sub(params).ok_or("stack underflow")?;
+                let label = if opcode == 0x03 {
+                    Label { target: start, height, arity: params, is_loop: true }
+                } else {
+                    let (_, end) = self.block_end(bytecode, start)?;
+                    Label { target: end, height, arity: results, is_loop: false }
+                };
+                self.enter(label)
+            }
+            0x04 => {
+                let (params, results) = self.read_block_type(bytecode)?;
+                let cond = self.pop_i32()?;
+                let height = self.stack.len().checked_sub(params).ok_or("stack underflow")?;
+                let (else_pos, end) = self.block_end(bytecode, self.pc)?;
+                let label = Label { target: end, height, arity: results, is_loop: false };
+                match (cond != 0, else_pos) {
+                    (true, _) => self.enter(label),
+                    (false, Some(else_pos)) => {
+                        self.pc = else_pos + 1;
+                        self.enter(label)
+                    }
+                    // no else arm: the if's params are its results
+                    (false, None) => {
+                        self.pc = end;
+                        Ok(())
+                    }
+                }
+            }
+            // the then arm ran; skip the else arm
+            0x05 => {
+                let label = self.labels.pop().ok_or("else without an if")?;
+                self.pc = label.target;
+                Ok(())
+            }
+            0x0B => self.labels.pop().map(|_| ()).ok_or("end without a block"),
+            0x0C => {
+                let depth = self.read_leb128_u32(bytecode)?;
+                self.branch(depth)
+            }
+            0x0D => {
+                let depth = self.read_leb128_u32(bytecode)?;
+                if self.pop_i32()? != 0 {
+                    self.branch(depth)?;
+                }
+                Ok(())
+            }
+            0x0E => {
+                let count = self.read_leb128_u32(bytecode)?;
+                let index = self.pop_i32()? as u32;
+                let mut depth = None;
+                for i in 0..count {
+                    let target = self.read_leb128_u32(bytecode)?;
+                    if i == index {
+                        depth = Some(target);
+                    }
+                }
+                let default = self.read_leb128_u32(bytecode)?;
+                self.branch(depth.unwrap_or(default))
+            }
+            0x0F => {
+                let depth = self.labels.len().checked_sub(1).ok_or("return outside a function")?;
+                self.branch(depth as u32)
+            }
+
+            // Parametric
+            0x1A => self.pop().map(|_| ()),
+            0x1B | 0x1C => {
+                if opcode == 0x1C {
+                    // the typed form's value types
+                    let count = self.read_leb128_u32(bytecode)? as usize;
+                    self.pc = self.pc.checked_add(count).filter(|&pc| pc <= bytecode.len()).ok_or("unexpected end of code")?;
+                }
+                let cond = self.pop_i32()?;
+                let b = self.pop()?;
+                let a = self.pop()?;
+                if core::mem::discriminant(&a) != core::mem::discriminant(&b) {
+                    return Err("type mismatch in select");
+                }
+                self.push(if cond != 0 { a } else { b })
+            }
 
             // Constants
             0x41 => {
@@ -142,40 +317,82 @@ impl Interpreter {
                 self.push(val)
             }
 
-            // Memory operations
-            0x28 => {
-                let _align = self.read_leb128_u32(bytecode)?;
-                let offset = self.read_leb128_u32(bytecode)? as usize;
-                let addr = self.pop_i32()? as usize + offset;
-                let bytes = self.load_memory(addr, 4)?;
-                let val = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-                self.push(Value::I32(val))
-            }
-            0x29 => {
-                let _align = self.read_leb128_u32(bytecode)?;
-                let offset = self.read_leb128_u32(bytecode)? as usize;
-                let addr = self.pop_i32()? as usize + offset;
-                let bytes = self.load_memory(addr, 8)?;
-                let val = i64::from_le_bytes([
-                    bytes[0], bytes[1], bytes[2], bytes[3],
-                    bytes[4], bytes[5], bytes[6], bytes[7],
-                ]);
-                self.push(Value::I64(val))
-            }
-            0x36 => {
-                let _align = self.read_leb128_u32(bytecode)?;
-                let offset = self.read_leb128_u32(bytecode)? as usize;
-                let val = self.pop_i32()?;
-                let addr = self.pop_i32()? as usize + offset;
-                self.store_memory(addr, &val.to_le_bytes())
-            }
-            0x37 => {
-                let _align = self.read_leb128_u32(bytecode)?;
-                let offset = self.read_leb128_u32(bytecode)? as usize;
-                let val = self.pop_i64()?;
-                let addr = self.pop_i32()? as usize + offset;
-                self.store_memory(addr, &val.to_le_bytes())
+            // Memory operations: narrow loads sign- or zero-extend, narrow stores keep the low bytes
+            0x28..=0x35 => {
+                let (size, signed) = match opcode {
+                    0x28 | 0x35 => (4, false),
+                    0x29 => (8, false),
+                    0x2C | 0x30 => (1, true),
+                    0x2D | 0x31 => (1, false),
+                    0x2E | 0x32 => (2, true),
+                    0x2F | 0x33 => (2, false),
+                    0x34 => (4, true),
+                    _ => return Err("unsupported opcode"),
+                };
+                let offset = self.read_memarg(bytecode)?;
+                let addr = self.pop_address(offset)?;
+                let mut bytes = [0u8; 8];
+                bytes[..size].copy_from_slice(self.load_memory(addr, size)?);
+                let shift = 64 - 8 * size as u32;
+                let val = i64::from_le_bytes(bytes);
+                let val = if signed { (val << shift) >> shift } else { val };
+                if opcode == 0x28 || (0x2C..=0x2F).contains(&opcode) {
+                    self.push(Value::I32(val as i32))
+                } else {
+                    self.push(Value::I64(val))
+                }
             }
+            0x36..=0x3E => {
+                let size = match opcode {
+                    0x36 | 0x3E => 4,
+                    0x37 => 8,
+                    0x3A | 0x3C => 1,
+                    0x3B | 0x3D => 2,
+                    _ => return Err("unsupported opcode"),
+                };
+                let offset = self.read_memarg(bytecode)?;
+                let val = match opcode {
+                    0x36 | 0x3A | 0x3B => self.pop_i32()? as i64,
+                    _ => self.pop_i64()?,
+                };
+                let addr = self.pop_address(offset)?;
+                self.store_memory(addr, &val.to_le_bytes()[..size])
+            }
+            0x3F => {
+                self.read_memory_index(bytecode)?;
+                self.push(Value::I32((self.memory.len() / PAGE_SIZE) as i32))
+            }
+            0x40 => {
+                self.read_memory_index(bytecode)?;
+                let delta = self.pop_i32()? as u32;
+                let old_pages = self.grow_memory(delta);
+                self.push(Value::I32(old_pages))
+            }
+            0xFC => match self.read_leb128_u32(bytecode)? {
+                // memory.copy
+                10 => {
+                    self.read_memory_index(bytecode)?;
+                    self.read_memory_index(bytecode)?;
+                    let len = self.pop_i32()? as u32 as usize;
+                    let src = self.pop_i32()? as u32 as usize;
+                    let dst = self.pop_i32()? as u32 as usize;
+                    let src_end = self.memory_end(src, len)?;
+                    self.memory_end(dst, len)?;
+                    self.memory.copy_within(src..src_end, dst);
+                    Ok(())
+                }
+                // memory.fill
+                11 => {
+                    self.read_memory_index(bytecode)?;
+                    let len = self.pop_i32()? as u32 as usize;
+                    let val = self.pop_i32()? as u8;
+                    let dst = self.pop_i32()? as u32 as usize;
+                    let end = self.memory_end(dst, len)?;
+                    self.memory[dst..end].fill(val);
+                    Ok(())
+                }
+                _ => Err("unsupported opcode"),
+            },
 
             // i32 comparison
             0x45 => {
@@ -323,6 +540,9 @@ impl Interpreter {
                 if b == 0 {
                     return Err("integer divide by zero");
                 }
+                if a == i32::MIN && b == -1 {
+                    return Err("integer overflow");
+                }
                 self.push(Value::I32(a.wrapping_div(b)))
             }
             0x6E => {
@@ -424,6 +644,9 @@ impl Interpreter {
                 if b == 0 {
                     return Err("integer divide by zero");
                 }
+                if a == i64::MIN && b == -1 {
+                    return Err("integer overflow");
+                }
                 self.push(Value::I64(a.wrapping_div(b)))
             }
             0x80 => {
@@ -491,10 +714,110 @@ impl Interpreter {
                 self.push(Value::I64(a.rotate_right((b & 63) as u32)))
             }
 
+            // Conversions
+            0xA7 => {
+                let val = self.pop_i64()?;
+                self.push(Value::I32(val as i32))
+            }
+            0xAC => {
+                let val = self.pop_i32()?;
+                self.push(Value::I64(val as i64))
+            }
+            0xAD => {
+                let val = self.pop_i32()?;
+                self.push(Value::I64(val as u32 as i64))
+            }
+            0xC0 => {
+                let val = self.pop_i32()?;
+                self.push(Value::I32(val as i8 as i32))
+            }
+            0xC1 => {
+                let val = self.pop_i32()?;
+                self.push(Value::I32(val as i16 as i32))
+            }
+            0xC2 => {
+                let val = self.pop_i64()?;
+                self.push(Value::I64(val as i8 as i64))
+            }
+            0xC3 => {
+                let val = self.pop_i64()?;
+                self.push(Value::I64(val as i16 as i64))
+            }
+            0xC4 => {
+                let val = self.pop_i64()?;
+                self.push(Value::I64(val as i32 as i64))
+            }
+
             _ => Err("unsupported opcode"),
         }
     }
 
+    fn enter(&mut self, label: Label) -> Result<(), &'static str> {
+        if self.labels.len() >= MAX_LABEL_DEPTH {
+            return Err("blocks nested too deep");
+        }
+        self.labels.push(label);
+        Ok(())
+    }
+
+    /// Branch to the label `depth` blocks out: keep the values it carries, drop the rest of
+    /// its stack, and leave it (a block) or go round again (a loop)
+    fn branch(&mut self, depth: u32) -> Result<(), &'static str> {
+        let index = self.labels.len().checked_sub(depth as usize + 1).ok_or("branch depth out of range")?;
+        let label = self.labels[index];
+        if self.stack.len() < label.height + label.arity {
+            return Err("stack underflow");
+        }
+        self.stack.drain(label.height..self.stack.len() - label.arity);
+        self.pc = label.target;
+        self.labels.truncate(if label.is_loop { index + 1 } else { index });
+        Ok(())
+    }
+
+    /// Params and results of the block type at pc
+    fn read_block_type(&mut self, bytecode: &[u8]) -> Result<(usize, usize), &'static str> {
+        match *bytecode.get(self.pc).ok_or("unexpected end of code")? {
+            0x40 => {
+                self.pc += 1;
+                Ok((0, 0))
+            }
+            0x7F | 0x7E | 0x7D | 0x7C | 0x7B | 0x70 | 0x6F => {
+                self.pc += 1;
+                Ok((0, 1))
+            }
+            _ => {
+                let index = read_leb128(bytecode, &mut self.pc, 33, true)?;
+                usize::try_from(index).ok().and_then(|i| self.types.get(i).copied()).ok_or("unknown block type")
+            }
+        }
+    }
+
+    /// Offsets of the else (if any) and just past the end matching a block whose first
+    /// instruction is at `start`
+    fn block_end(&mut self, bytecode: &[u8], start: usize) -> Result<(Option<usize>, usize), &'static str> {
+        if let Some(&ends) = self.block_ends.get(&start) {
+            return Ok(ends);
+        }
+        let mut pos = start;
+        let mut depth = 0usize;
+        let mut else_pos = None;
+        loop {
+            let opcode = *bytecode.get(pos).ok_or("block has no end")?;
+            let at = pos;
+            pos += 1;
+            match opcode {
+                0x02..=0x04 => depth += 1,
+                0x05 if depth == 0 => else_pos = Some(at),
+                0x0B if depth == 0 => break,
+                0x0B => depth -= 1,
+                _ => {}
+            }
+            skip_immediates(bytecode, &mut pos, opcode)?;
+        }
+        self.block_ends.insert(start, (else_pos, pos));
+        Ok((else_pos, pos))
+    }
+
     pub fn compute_state_hash(&self) -> [u8; 32] {
         let mut hasher = Sha256::new();
 
@@ -517,38 +840,155 @@ impl Interpreter {
         hasher.update(&(self.locals.len() as u32).to_le_bytes());
         for val in &self.locals {
             match val {
-                Value::I32(v) => hasher.update(&v.to_le_bytes()),
-                Value::I64(v) => hasher.update(&v.to_le_bytes()),
+                Value::I32(v) => {
+                    hasher.update(&[0x7F]);
+                    hasher.update(&v.to_le_bytes());
+                }
+                Value::I64(v) => {
+                    hasher.update(&[0x7E]);
+                    hasher.update(&v.to_le_bytes());
+                }
             }
         }
 
+        // all of memory: a single step may read any of it, and must not read what the hash
+        // doesn't cover
         hasher.update(&[0x03]);
-        let mem_sample_size = self.memory.len().min(1024);
-        hasher.update(&self.memory[..mem_sample_size]);
+        hasher.update(&(self.memory.len() as u32).to_le_bytes());
+        hasher.update(&self.memory);
+        hasher.update(&(self.max_memory as u32).to_le_bytes());
 
         hasher.update(&[0x04]);
-        hasher.update(&self.pc.to_le_bytes());
+        hasher.update(&(self.pc as u32).to_le_bytes());
         hasher.update(&self.fuel.to_le_bytes());
 
+        hasher.update(&[0x05]);
+        hasher.update(&(self.labels.len() as u32).to_le_bytes());
+        for label in &self.labels {
+            hasher.update(&(label.target as u32).to_le_bytes());
+            hasher.update(&(label.height as u32).to_le_bytes());
+            hasher.update(&(label.arity as u32).to_le_bytes());
+            hasher.update(&[label.is_loop as u8]);
+        }
+
         let result = hasher.finalize();
         let mut output = [0u8; 32];
         output.copy_from_slice(&result);
         output
     }
 
-    fn read_leb128_u32(&mut self, _bytecode: &[u8]) -> Result<u32, &'static str> {
-        self.pc += 1;
-        Ok(0)
+    /// The state `compute_state_hash` covers, in the form `decode_state` reads back: fuel u64,
+    /// pc u32, the stack and locals as a u32 count of tagged values, the labels as a u32 count of
+    /// (target, height, arity u32, is_loop u8), then memory as a u32 length and its bytes, and
+    /// the u32 limit it may grow to. All integers little-endian.
+    pub fn encode_state(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.fuel.to_le_bytes());
+        out.extend_from_slice(&(self.pc as u32).to_le_bytes());
+        for values in [&self.stack, &self.locals] {
+            out.extend_from_slice(&(values.len() as u32).to_le_bytes());
+            for val in values {
+                match val {
+                    Value::I32(v) => {
+                        out.push(0x7F);
+                        out.extend_from_slice(&v.to_le_bytes());
+                    }
+                    Value::I64(v) => {
+                        out.push(0x7E);
+                        out.extend_from_slice(&v.to_le_bytes());
+                    }
+                }
+            }
+        }
+        out.extend_from_slice(&(self.labels.len() as u32).to_le_bytes());
+        for label in &self.labels {
+            out.extend_from_slice(&(label.target as u32).to_le_bytes());
+            out.extend_from_slice(&(label.height as u32).to_le_bytes());
+            out.extend_from_slice(&(label.arity as u32).to_le_bytes());
+            out.push(label.is_loop as u8);
+        }
+        out.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.memory);
+        out.extend_from_slice(&(self.max_memory as u32).to_le_bytes());
+        out
     }
 
-    fn read_leb128_i32(&mut self, _bytecode: &[u8]) -> Result<i32, &'static str> {
-        self.pc += 1;
-        Ok(0)
+    /// An interpreter in the state `encode_state` wrote; no limit is looser than execution's own
+    pub fn decode_state(bytes: &[u8], max_memory: usize) -> Result<Self, &'static str> {
+        let mut reader = StateReader { bytes, pos: 0 };
+        let fuel = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+        let mut interp = Interpreter::new(0, 0, fuel);
+        interp.pc = reader.u32()? as usize;
+        for (values, max) in [(&mut interp.stack, MAX_STACK_DEPTH), (&mut interp.locals, MAX_LOCALS)] {
+            let count = reader.u32()? as usize;
+            if count > max {
+                return Err("state has too many values");
+            }
+            for _ in 0..count {
+                let val = match reader.take(1)?[0] {
+                    0x7F => Value::I32(reader.u32()? as i32),
+                    0x7E => Value::I64(u64::from_le_bytes(reader.take(8)?.try_into().unwrap()) as i64),
+                    _ => return Err("invalid value type in state"),
+                };
+                values.push(val);
+            }
+        }
+        let count = reader.u32()? as usize;
+        if count > MAX_LABEL_DEPTH {
+            return Err("state has too many labels");
+        }
+        for _ in 0..count {
+            let (target, height, arity) = (reader.u32()? as usize, reader.u32()? as usize, reader.u32()? as usize);
+            let is_loop = match reader.take(1)?[0] {
+                0 => false,
+                1 => true,
+                _ => return Err("invalid label in state"),
+            };
+            interp.labels.push(Label { target, height, arity, is_loop });
+        }
+        let len = reader.u32()? as usize;
+        if len > max_memory {
+            return Err("state memory exceeds the limit");
+        }
+        interp.memory = reader.take(len)?.to_vec();
+        interp.max_memory = reader.u32()? as usize;
+        if interp.max_memory > max_memory || len > interp.max_memory {
+            return Err("state memory exceeds the limit");
+        }
+        if reader.pos != bytes.len() {
+            return Err("trailing bytes after state");
+        }
+        Ok(interp)
     }
 
-    fn read_leb128_i64(&mut self, _bytecode: &[u8]) -> Result<i64, &'static str> {
-        self.pc += 1;
-        Ok(0)
+    fn read_leb128_u32(&mut self, bytecode: &[u8]) -> Result<u32, &'static str> {
+        read_leb128(bytecode, &mut self.pc, 32, false).map(|v| v as u32)
+    }
+
+    fn read_leb128_i32(&mut self, bytecode: &[u8]) -> Result<i32, &'static str> {
+        read_leb128(bytecode, &mut self.pc, 32, true).map(|v| v as i32)
+    }
+
+    fn read_leb128_i64(&mut self, bytecode: &[u8]) -> Result<i64, &'static str> {
+        read_leb128(bytecode, &mut self.pc, 64, true)
+    }
+}
+
+struct StateReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], &'static str> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len()).ok_or("truncated state")?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
     }
 }
 
@@ -558,7 +998,7 @@ mod tests {
 
     #[test]
     fn test_i32_operations() {
-        let mut interp = Interpreter::new(1024, 1000);
+        let mut interp = Interpreter::new(1024, 1024, 1000);
 
         interp.push(Value::I32(10)).unwrap();
         interp.push(Value::I32(20)).unwrap();
@@ -578,7 +1018,7 @@ mod tests {
 
     #[test]
     fn test_i32_comparison() {
-        let mut interp = Interpreter::new(1024, 1000);
+        let mut interp = Interpreter::new(1024, 1024, 1000);
 
         interp.push(Value::I32(42)).unwrap();
         interp.push(Value::I32(42)).unwrap();
@@ -593,7 +1033,7 @@ mod tests {
 
     #[test]
     fn test_i32_bitwise() {
-        let mut interp = Interpreter::new(1024, 1000);
+        let mut interp = Interpreter::new(1024, 1024, 1000);
 
         interp.push(Value::I32(0b1010)).unwrap();
         interp.push(Value::I32(0b1100)).unwrap();
@@ -608,12 +1048,12 @@ mod tests {
 
     #[test]
     fn test_state_hash_deterministic() {
-        let mut interp1 = Interpreter::new(1024, 1000);
+        let mut interp1 = Interpreter::new(1024, 1024, 1000);
         interp1.push(Value::I32(42)).unwrap();
         interp1.locals.push(Value::I32(100));
         let hash1 = interp1.compute_state_hash();
 
-        let mut interp2 = Interpreter::new(1024, 1000);
+        let mut interp2 = Interpreter::new(1024, 1024, 1000);
         interp2.push(Value::I32(42)).unwrap();
         interp2.locals.push(Value::I32(100));
         let hash2 = interp2.compute_state_hash();
@@ -621,17 +1061,46 @@ mod tests {
         assert_eq!(hash1, hash2);
     }
 
+    #[test]
+    fn test_state_hash_covers_all_of_memory_and_types() {
+        let mut interp = Interpreter::new(4096, 4096, 1000);
+        let before = interp.compute_state_hash();
+        interp.memory[4000] = 1;
+        assert_ne!(interp.compute_state_hash(), before);
+
+        // the same bytes as locals of other types
+        let mut first = Interpreter::new(0, 0, 1000);
+        first.locals = vec![Value::I32(1), Value::I64(0)];
+        let mut second = Interpreter::new(0, 0, 1000);
+        second.locals = vec![Value::I64(1), Value::I32(0)];
+        assert_ne!(first.compute_state_hash(), second.compute_state_hash());
+    }
+
     #[test]
     fn test_divide_by_zero() {
-        let mut interp = Interpreter::new(1024, 1000);
+        let mut interp = Interpreter::new(1024, 1024, 1000);
         interp.push(Value::I32(10)).unwrap();
         interp.push(Value::I32(0)).unwrap();
         assert!(interp.execute_opcode(0x6D, &[]).is_err());
     }
 
+    #[test]
+    fn test_signed_division_overflow_traps() {
+        let mut interp = Interpreter::new(1024, 1024, 1000);
+        interp.push(Value::I32(i32::MIN)).unwrap();
+        interp.push(Value::I32(-1)).unwrap();
+        assert_eq!(interp.execute_opcode(0x6D, &[]), Err("integer overflow"));
+
+        // rem_s of the same operands is 0, not a trap
+        interp.push(Value::I64(i64::MIN)).unwrap();
+        interp.push(Value::I64(-1)).unwrap();
+        interp.execute_opcode(0x81, &[]).unwrap();
+        assert_eq!(interp.pop_i64().unwrap(), 0);
+    }
+
     #[test]
     fn test_fuel_consumption() {
-        let mut interp = Interpreter::new(1024, 5);
+        let mut interp = Interpreter::new(1024, 1024, 5);
         interp.push(Value::I32(1)).unwrap();
         interp.push(Value::I32(2)).unwrap();
         interp.execute_opcode(0x6A, &[]).unwrap();
@@ -640,4 +1109,229 @@ mod tests {
         interp.execute_opcode(0x6A, &[]).unwrap();
         assert!(interp.execute_opcode(0x6A, &[]).is_err());
     }
+
+    // sum of n down to 1 with local 0 = n, local 1 = acc
+    const SUM_LOOP: &[u8] = &[
+        0x02, 0x40, 0x03, 0x40, // block, loop
+        0x20, 0x00, 0x45, 0x0D, 0x01, // br_if 1 when n == 0
+        0x20, 0x01, 0x20, 0x00, 0x6A, 0x21, 0x01, // acc += n
+        0x20, 0x00, 0x41, 0x01, 0x6B, 0x21, 0x00, // n -= 1
+        0x0C, 0x00, 0x0B, 0x0B, // br 0, end, end
+        0x20, 0x01, 0x0B, // acc
+    ];
+
+    fn run(code: &[u8], locals: &[i32]) -> Result<Vec<Value>, &'static str> {
+        let mut interp = Interpreter::new(1024, 1024, 10_000);
+        interp.locals = locals.iter().map(|&v| Value::I32(v)).collect();
+        interp.execute(code, 1)?;
+        Ok(interp.stack)
+    }
+
+    #[test]
+    fn test_loop_branches_back() {
+        assert_eq!(run(SUM_LOOP, &[5, 0]), Ok(vec![Value::I32(15)]));
+        assert_eq!(run(SUM_LOOP, &[0, 0]), Ok(vec![Value::I32(0)]));
+    }
+
+    #[test]
+    fn test_if_else() {
+        // if (result i32) 10 else 20 end
+        let code = [0x20, 0x00, 0x04, 0x7F, 0x41, 0x0A, 0x05, 0x41, 0x14, 0x0B, 0x0B];
+        assert_eq!(run(&code, &[1]), Ok(vec![Value::I32(10)]));
+        assert_eq!(run(&code, &[0]), Ok(vec![Value::I32(20)]));
+
+        // an if without else is skipped whole when false
+        let code = [0x20, 0x00, 0x04, 0x40, 0x41, 0x07, 0x21, 0x01, 0x0B, 0x20, 0x01, 0x0B];
+        assert_eq!(run(&code, &[1, 0]), Ok(vec![Value::I32(7)]));
+        assert_eq!(run(&code, &[0, 0]), Ok(vec![Value::I32(0)]));
+    }
+
+    #[test]
+    fn test_br_table_and_return() {
+        let code = [
+            0x02, 0x40, 0x02, 0x40, 0x02, 0x40, // three blocks
+            0x20, 0x00, 0x0E, 0x02, 0x00, 0x01, 0x02, 0x0B, // br_table [0, 1] 2
+            0x41, 0x0A, 0x0F, 0x0B, // return 10
+            0x41, 0x14, 0x0F, 0x0B, // return 20
+            0x41, 0x1E, 0x0B, // 30
+        ];
+        assert_eq!(run(&code, &[0]), Ok(vec![Value::I32(10)]));
+        assert_eq!(run(&code, &[1]), Ok(vec![Value::I32(20)]));
+        assert_eq!(run(&code, &[2]), Ok(vec![Value::I32(30)]));
+        assert_eq!(run(&code, &[-1]), Ok(vec![Value::I32(30)]));
+    }
+
+    #[test]
+    fn test_branch_keeps_only_the_label_values() {
+        // block (result i32) 1 2 br 0 end
+        let code = [0x02, 0x7F, 0x41, 0x01, 0x41, 0x02, 0x0C, 0x00, 0x0B, 0x0B];
+        assert_eq!(run(&code, &[]), Ok(vec![Value::I32(2)]));
+
+        assert_eq!(run(&[0x0C, 0x01, 0x0B], &[]), Err("branch depth out of range"));
+        assert_eq!(run(&[0x02, 0x40, 0x0B], &[]), Err("unexpected end of code"));
+    }
+
+    #[test]
+    fn test_immediates_are_decoded() {
+        assert_eq!(run(&[0x41, 0xE5, 0x8E, 0x26, 0x0B], &[]), Ok(vec![Value::I32(624485)]));
+        assert_eq!(run(&[0x41, 0x7F, 0x0B], &[]), Ok(vec![Value::I32(-1)]));
+        assert_eq!(run(&[0x41, 0x80, 0x80, 0x80, 0x80, 0x78, 0x0B], &[]), Ok(vec![Value::I32(i32::MIN)]));
+    }
+
+    #[test]
+    fn test_leb128_edge_encodings() {
+        let read = |bytes: &[u8], bits: u32, signed: bool| {
+            let mut pos = 0;
+            read_leb128(bytes, &mut pos, bits, signed).map(|v| (v, pos))
+        };
+        // u32: the fifth byte holds only the top four bits; zero padding is fine
+        assert_eq!(read(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F], 32, false), Ok((u32::MAX as i64, 5)));
+        assert_eq!(read(&[0x80, 0x80, 0x80, 0x80, 0x00], 32, false), Ok((0, 5)));
+        assert_eq!(read(&[0xFF, 0xFF, 0xFF, 0xFF, 0x1F], 32, false), Err("leb128 too large"));
+        assert_eq!(read(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00], 32, false), Err("leb128 too long"));
+        assert_eq!(read(&[0x80, 0x80], 32, false), Err("unexpected end of code"));
+
+        // s32: the fifth byte's unused bits repeat the sign
+        assert_eq!(read(&[0xFF, 0xFF, 0xFF, 0xFF, 0x07], 32, true), Ok((i32::MAX as i64, 5)));
+        assert_eq!(read(&[0xFF, 0xFF, 0xFF, 0xFF, 0x7F], 32, true), Ok((-1, 5)));
+        assert_eq!(read(&[0x80, 0x80, 0x80, 0x80, 0x78], 32, true), Ok((i32::MIN as i64, 5)));
+        assert_eq!(read(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F], 32, true), Err("leb128 too large"));
+        assert_eq!(read(&[0x80, 0x80, 0x80, 0x80, 0x70], 32, true), Err("leb128 too large"));
+
+        // s33 block type indices, and s64 up to ten bytes
+        assert_eq!(read(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F], 33, true), Ok((u32::MAX as i64, 5)));
+        assert_eq!(read(&[0xFF, 0xFF, 0xFF, 0xFF, 0x1F], 33, true), Err("leb128 too large"));
+        let mut min = [0x80; 10];
+        min[9] = 0x7F;
+        assert_eq!(read(&min, 64, true), Ok((i64::MIN, 10)));
+        min[9] = 0x01;
+        assert_eq!(read(&min, 64, true), Err("leb128 too large"));
+        let mut long = [0x80; 11];
+        long[10] = 0x00;
+        assert_eq!(read(&long, 64, true), Err("leb128 too long"));
+
+        // the interpreter's pc moves past each immediate
+        let mut interp = Interpreter::new(0, 0, 100);
+        interp.pc = 1;
+        interp.execute_opcode(0x42, &[0x42, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7F, 0x0B]).unwrap();
+        assert_eq!((interp.pop(), interp.pc), (Ok(Value::I64(i64::MIN)), 11));
+        assert_eq!(run(&[0x41, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0x0B], &[]), Err("leb128 too large"));
+    }
+
+    #[test]
+    fn test_loop_runs_out_of_fuel() {
+        let mut interp = Interpreter::new(1024, 1024, 50);
+        interp.locals = vec![Value::I32(1000), Value::I32(0)];
+        assert_eq!(interp.execute(SUM_LOOP, 1), Err("out of fuel"));
+    }
+
+    #[test]
+    fn test_fuel_follows_gas_schedule() {
+        let mut interp = Interpreter::new(1024, 1024, 10);
+        interp.push(Value::I32(10)).unwrap();
+        interp.push(Value::I32(3)).unwrap();
+        interp.execute_opcode(0x6D, &[]).unwrap();
+        assert_eq!(interp.fuel, 10 - certus_gas::DIVISION_COST);
+
+        // structural opcodes are free
+        interp.execute_opcode(0x01, &[]).unwrap();
+        assert_eq!(interp.fuel, 10 - certus_gas::DIVISION_COST);
+    }
+
+    #[test]
+    fn test_narrow_loads_and_stores() {
+        let mut interp = Interpreter::new(64, 64, 1000);
+        interp.memory[..8].copy_from_slice(&[0xF0, 0x80, 0xFF, 0x7F, 0x01, 0x02, 0x03, 0x84]);
+        // each load from address 0, the opcode's memarg aligned to its size
+        let load = |interp: &mut Interpreter, opcode: u8| {
+            interp.execute(&[0x41, 0x00, opcode, 0x00, 0x00, 0x0B], 1).unwrap();
+            interp.pop().unwrap()
+        };
+        assert_eq!(load(&mut interp, 0x2C), Value::I32(-16));
+        assert_eq!(load(&mut interp, 0x2D), Value::I32(0xF0));
+        assert_eq!(load(&mut interp, 0x2E), Value::I32(0x80F0u16 as i16 as i32));
+        assert_eq!(load(&mut interp, 0x2F), Value::I32(0x80F0));
+        assert_eq!(load(&mut interp, 0x30), Value::I64(-16));
+        assert_eq!(load(&mut interp, 0x33), Value::I64(0x80F0));
+        assert_eq!(load(&mut interp, 0x34), Value::I64(0x7FFF80F0));
+        assert_eq!(load(&mut interp, 0x35), Value::I64(0x7FFF80F0));
+        assert_eq!(load(&mut interp, 0x29), Value::I64(0x84030201_7FFF80F0u64 as i64));
+
+        // i32.store8 and i64.store16 keep only the low bytes
+        interp.execute(&[0x41, 0x10, 0x41, 0xFF, 0x03, 0x3A, 0x00, 0x00, 0x0B], 0).unwrap();
+        interp.execute(&[0x41, 0x11, 0x42, 0xB4, 0xE8, 0x7D, 0x3D, 0x00, 0x00, 0x0B], 0).unwrap();
+        assert_eq!(&interp.memory[0x10..0x14], &[0xFF, 0x34, 0x74, 0x00]);
+
+        // float loads are not integer instructions
+        assert_eq!(interp.execute(&[0x41, 0x00, 0x2A, 0x02, 0x00, 0x0B], 0), Err("unsupported opcode"));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let mut interp = Interpreter::new(0, 0, 1000);
+        let mut convert = |opcode: u8, val: Value| {
+            interp.push(val).unwrap();
+            interp.execute_opcode(opcode, &[]).unwrap();
+            interp.pop().unwrap()
+        };
+        assert_eq!(convert(0xA7, Value::I64(0x1_8000_0001)), Value::I32(i32::MIN + 1));
+        assert_eq!(convert(0xAC, Value::I32(-2)), Value::I64(-2));
+        assert_eq!(convert(0xAD, Value::I32(-2)), Value::I64(0xFFFF_FFFE));
+        assert_eq!(convert(0xC0, Value::I32(0x180)), Value::I32(-128));
+        assert_eq!(convert(0xC1, Value::I32(0x1_7FFF)), Value::I32(0x7FFF));
+        assert_eq!(convert(0xC2, Value::I64(0xFF)), Value::I64(-1));
+        assert_eq!(convert(0xC3, Value::I64(0x8000)), Value::I64(-32768));
+        assert_eq!(convert(0xC4, Value::I64(0xFFFF_FFFF)), Value::I64(-1));
+    }
+
+    #[test]
+    fn test_drop_and_select() {
+        assert_eq!(run(&[0x41, 0x01, 0x41, 0x02, 0x1A, 0x0B], &[]), Ok(vec![Value::I32(1)]));
+        // select picks its first operand on a nonzero condition; the typed form likewise
+        assert_eq!(run(&[0x41, 0x07, 0x41, 0x08, 0x41, 0x01, 0x1B, 0x0B], &[]), Ok(vec![Value::I32(7)]));
+        assert_eq!(run(&[0x41, 0x07, 0x41, 0x08, 0x41, 0x00, 0x1C, 0x01, 0x7F, 0x0B], &[]), Ok(vec![Value::I32(8)]));
+        assert_eq!(run(&[0x41, 0x07, 0x42, 0x08, 0x41, 0x00, 0x1B, 0x0B], &[]), Err("type mismatch in select"));
+    }
+
+    #[test]
+    fn test_memory_grows_by_pages_up_to_the_limit() {
+        let mut interp = Interpreter::new(PAGE_SIZE, 2 * PAGE_SIZE, 10_000);
+        // memory.grow 1, memory.grow 1, memory.size
+        let code = [0x41, 0x01, 0x40, 0x00, 0x41, 0x01, 0x40, 0x00, 0x3F, 0x00, 0x0B];
+        interp.execute(&code, 3).unwrap();
+        assert_eq!(interp.stack, vec![Value::I32(1), Value::I32(-1), Value::I32(2)]);
+        assert_eq!(interp.memory.len(), 2 * PAGE_SIZE);
+        assert_eq!(interp.fuel, 10_000 - 2 * certus_gas::MEMORY_GROW_COST - 3 * certus_gas::BASE_COST);
+
+        assert_eq!(interp.grow_memory(u32::MAX), -1);
+        assert_eq!(interp.grow_memory(0), 2);
+    }
+
+    #[test]
+    fn test_memory_fill_and_copy() {
+        let mut interp = Interpreter::new(64, 64, 1000);
+        let code = [
+            0x41, 0x08, 0x41, 0xAB, 0x01, 0x41, 0x04, 0xFC, 0x0B, 0x00, // fill [8, 12) with 0xAB
+            0x41, 0x0A, 0x41, 0x08, 0x41, 0x04, 0xFC, 0x0A, 0x00, 0x00, // copy [8, 12) to [10, 14)
+            0x0B,
+        ];
+        interp.execute(&code, 0).unwrap();
+        assert_eq!(&interp.memory[6..16], &[0, 0, 0xAB, 0xAB, 0xAB, 0xAB, 0xAB, 0xAB, 0, 0]);
+
+        // a range past the end traps, whatever the instruction
+        let fill = [0x41, 0x3E, 0x41, 0x00, 0x41, 0x04, 0xFC, 0x0B, 0x00, 0x0B];
+        assert_eq!(interp.execute(&fill, 0), Err("memory access out of bounds"));
+        let copy = [0x41, 0x00, 0x41, 0x3E, 0x41, 0x04, 0xFC, 0x0A, 0x00, 0x00, 0x0B];
+        assert_eq!(interp.execute(&copy, 0), Err("memory access out of bounds"));
+    }
+
+    #[test]
+    fn test_addresses_are_unsigned() {
+        let mut interp = Interpreter::new(64, 64, 1000);
+        // i32.load from -1, read as 4GB - 1
+        assert_eq!(interp.execute(&[0x41, 0x7F, 0x28, 0x02, 0x00, 0x0B], 1), Err("memory access out of bounds"));
+        let mut interp = Interpreter::new(64, 64, 1000);
+        interp.execute(&[0x41, 0x3C, 0x28, 0x02, 0x00, 0x0B], 1).unwrap();
+        assert_eq!(interp.execute(&[0x41, 0x3D, 0x28, 0x02, 0x00, 0x0B], 1), Err("memory access out of bounds"));
+    }
 }
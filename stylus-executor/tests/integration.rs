@@ -53,12 +53,18 @@ fn test_determinism_validation() {
     ];
     assert!(validate_determinism(&float_wasm).is_err());
 
-    // Module with WASI import
+    // Module importing a WASI function
     let mut wasi_wasm = vec![
         0x00, 0x61, 0x73, 0x6D,
         0x01, 0x00, 0x00, 0x00,
+        0x02, 0x24, // import section
+        0x01, // 1 import
+        0x16, // "wasi_snapshot_preview1"
     ];
-    wasi_wasm.extend_from_slice(b"wasi_snapshot");
+    wasi_wasm.extend_from_slice(b"wasi_snapshot_preview1");
+    wasi_wasm.push(0x09); // "proc_exit"
+    wasi_wasm.extend_from_slice(b"proc_exit");
+    wasi_wasm.extend_from_slice(&[0x00, 0x00]); // func, type 0
     assert!(validate_determinism(&wasi_wasm).is_err());
 
     // Module with thread opcode
@@ -110,17 +116,6 @@ fn test_module_size_limit() {
     // This would be rejected by execute() but validate_determinism only checks opcodes
 }
 
-#[test]
-fn test_pattern_matching() {
-    assert!(contains_pattern(b"hello world", b"world"));
-    assert!(contains_pattern(b"test", b"test"));
-    assert!(contains_pattern(b"abcdefgh", b"def"));
-
-    assert!(!contains_pattern(b"hello", b"goodbye"));
-    assert!(!contains_pattern(b"short", b"very long pattern"));
-    assert!(!contains_pattern(b"", b"anything"));
-}
-
 #[test]
 fn test_sha256_determinism() {
     let data = b"test data for hashing";
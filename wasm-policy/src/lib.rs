@@ -0,0 +1,312 @@
+// Determinism policy for the Wasm modules Certus runs, shared by the python-verifier, the
+// executor node and the Stylus contract so the three accept exactly the same modules.
+//
+// Rejected: float, SIMD and atomic instructions, and imports outside the host ABI (`env.memory`
+// and `env.abort`). Checks read the module's sections and decode each function body instruction
+// by instruction, so immediates, data segments and names are never taken for opcodes or imports;
+// a section or body that can't be decoded is rejected, since it can't be checked.
+
+#![no_std]
+
+use core::fmt;
+
+/// Host imports a module may declare
+pub const ALLOWED_IMPORTS: &[(&[u8], &[u8])] = &[(b"env", b"memory"), (b"env", b"abort")];
+
+const CODE_SECTION: u8 = 10;
+const IMPORT_SECTION: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation<'a> {
+    InvalidMagic,
+    InvalidVersion,
+    /// A section or function body that can't be decoded
+    Malformed(&'static str),
+    FloatOpcode { opcode: u8, offset: usize },
+    SimdOpcode { offset: usize },
+    AtomicOpcode { offset: usize },
+    DisallowedImport { module: &'a [u8], name: &'a [u8] },
+}
+
+impl fmt::Display for Violation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Violation::InvalidMagic => write!(f, "invalid wasm magic"),
+            Violation::InvalidVersion => write!(f, "unsupported wasm version"),
+            Violation::Malformed(reason) => write!(f, "malformed module: {}", reason),
+            Violation::FloatOpcode { opcode, offset } => write!(f, "float opcode 0x{:02x} at offset {}", opcode, offset),
+            Violation::SimdOpcode { offset } => write!(f, "simd opcode 0xfd at offset {}", offset),
+            Violation::AtomicOpcode { offset } => write!(f, "atomic opcode 0xfe at offset {}", offset),
+            Violation::DisallowedImport { module, name } => {
+                write!(f, "import {}.{} not allowed", text(module), text(name))
+            }
+        }
+    }
+}
+
+fn text(bytes: &[u8]) -> &str {
+    core::str::from_utf8(bytes).unwrap_or("<invalid utf-8>")
+}
+
+/// Every check, header first
+pub fn validate(wasm: &[u8]) -> Result<(), Violation<'_>> {
+    check_header(wasm)?;
+    check_opcodes(wasm)?;
+    check_imports(wasm)
+}
+
+/// Wasm magic and version 1
+pub fn check_header(wasm: &[u8]) -> Result<(), Violation<'_>> {
+    if wasm.len() < 8 || &wasm[0..4] != b"\0asm" {
+        return Err(Violation::InvalidMagic);
+    }
+    if wasm[4..8] != [1, 0, 0, 0] {
+        return Err(Violation::InvalidVersion);
+    }
+    Ok(())
+}
+
+/// Opcodes that load, store, compute on or convert to or from floats
+pub fn is_float_opcode(opcode: u8) -> bool {
+    matches!(opcode, 0x2A | 0x2B | 0x38 | 0x39 | 0x43 | 0x44 | 0x5B..=0x66 | 0x8B..=0xA6 | 0xA8..=0xAB | 0xAE..=0xBF)
+}
+
+/// Reject float, SIMD and atomic instructions in the code section
+pub fn check_opcodes(wasm: &[u8]) -> Result<(), Violation<'_>> {
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let (id, start, end) = next_section(wasm, &mut pos)?;
+        if id != CODE_SECTION {
+            continue;
+        }
+        let section = &wasm[..end];
+        let mut cursor = start;
+        let count = read_leb128(section, &mut cursor, 32, false).map_err(Violation::Malformed)?;
+        for _ in 0..count {
+            let size = read_leb128(section, &mut cursor, 32, false).map_err(Violation::Malformed)? as usize;
+            let body_end = cursor.checked_add(size).filter(|&e| e <= end).ok_or(Violation::Malformed("body past its section"))?;
+            check_body(&wasm[..body_end], cursor)?;
+            cursor = body_end;
+        }
+        if cursor != end {
+            return Err(Violation::Malformed("code section size mismatch"));
+        }
+    }
+    Ok(())
+}
+
+/// One function body, from its local declarations to the end of `body`
+fn check_body(body: &[u8], mut cursor: usize) -> Result<(), Violation<'_>> {
+    let decls = read_leb128(body, &mut cursor, 32, false).map_err(Violation::Malformed)?;
+    for _ in 0..decls {
+        read_leb128(body, &mut cursor, 32, false).map_err(Violation::Malformed)?;
+        cursor += 1; // value type
+    }
+    while cursor < body.len() {
+        let offset = cursor;
+        let opcode = body[cursor];
+        cursor += 1;
+        match opcode {
+            // the saturating truncations
+            0xFC => {
+                let mut sub = cursor;
+                if read_leb128(body, &mut sub, 32, false).map_err(Violation::Malformed)? <= 7 {
+                    return Err(Violation::FloatOpcode { opcode, offset });
+                }
+            }
+            0xFD => return Err(Violation::SimdOpcode { offset }),
+            0xFE => return Err(Violation::AtomicOpcode { offset }),
+            _ if is_float_opcode(opcode) => return Err(Violation::FloatOpcode { opcode, offset }),
+            _ => {}
+        }
+        skip_immediates(body, &mut cursor, opcode).map_err(Violation::Malformed)?;
+    }
+    if cursor != body.len() {
+        return Err(Violation::Malformed("instruction past the end of its body"));
+    }
+    Ok(())
+}
+
+/// Reject any import outside `ALLOWED_IMPORTS`
+pub fn check_imports(wasm: &[u8]) -> Result<(), Violation<'_>> {
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let (id, start, end) = next_section(wasm, &mut pos)?;
+        if id != IMPORT_SECTION {
+            continue;
+        }
+        let section = &wasm[..end];
+        let mut cursor = start;
+        let malformed = || Violation::Malformed("import section");
+        let count = read_leb128(section, &mut cursor, 32, false).map_err(Violation::Malformed)?;
+        for _ in 0..count {
+            let module = read_name(section, &mut cursor).ok_or_else(malformed)?;
+            let name = read_name(section, &mut cursor).ok_or_else(malformed)?;
+            skip_import_desc(section, &mut cursor).ok_or_else(malformed)?;
+            if !ALLOWED_IMPORTS.contains(&(module, name)) {
+                return Err(Violation::DisallowedImport { module, name });
+            }
+        }
+        if cursor != end {
+            return Err(malformed());
+        }
+    }
+    Ok(())
+}
+
+/// The id, payload start and end of the section at `pos`, leaving `pos` past it
+pub fn next_section<'a>(wasm: &[u8], pos: &mut usize) -> Result<(u8, usize, usize), Violation<'a>> {
+    let id = wasm[*pos];
+    *pos += 1;
+    let size = read_leb128(wasm, pos, 32, false).map_err(Violation::Malformed)? as usize;
+    let start = *pos;
+    let end = start.checked_add(size).filter(|&end| end <= wasm.len()).ok_or(Violation::Malformed("section past the end"))?;
+    *pos = end;
+    Ok((id, start, end))
+}
+
+/// A length-prefixed name, as imports, exports and custom sections hold them
+pub fn read_name<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    let len = read_leb_u32(data, pos)? as usize;
+    let end = pos.checked_add(len).filter(|&end| end <= data.len())?;
+    let name = &data[*pos..end];
+    *pos = end;
+    Some(name)
+}
+
+/// Step over an import's kind and type: func, table, memory or global
+pub fn skip_import_desc(data: &[u8], pos: &mut usize) -> Option<()> {
+    let kind = *data.get(*pos)?;
+    *pos += 1;
+    match kind {
+        0x00 => {
+            read_leb_u32(data, pos)?;
+        }
+        0x01 => {
+            *pos += 1; // element type
+            read_limits(data, pos)?;
+        }
+        0x02 => {
+            read_limits(data, pos)?;
+        }
+        0x03 => *pos += 2, // value type, mutability
+        _ => return None,
+    }
+    (*pos <= data.len()).then_some(())
+}
+
+/// Step over a table or memory's limits, returning its minimum
+pub fn read_limits(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let flags = *data.get(*pos)?;
+    *pos += 1;
+    // 64-bit limits would need a wider reader, and memory64 is rejected anyway
+    if flags & !0x03 != 0 {
+        return None;
+    }
+    let minimum = read_leb_u32(data, pos)?;
+    if flags & 0x01 != 0 {
+        read_leb_u32(data, pos)?;
+    }
+    Some(minimum)
+}
+
+/// An unsigned 32-bit LEB128 at `pos`, held to `read_leb128`'s rules
+pub fn read_leb_u32(data: &[u8], pos: &mut usize) -> Option<u32> {
+    read_leb128(data, pos, 32, false).ok().map(|value| value as u32)
+}
+
+/// A LEB128 integer of at most `bits` bits at `pos`, sign-extended if `signed`, leaving `pos`
+/// past it. Rejects what wasmparser rejects: more bytes than `bits` needs, or a last byte whose
+/// bits past `bits` are set (unsigned) or don't repeat the sign bit (signed). Zero padding within
+/// the byte limit is accepted, as there.
+pub fn read_leb128(bytecode: &[u8], pos: &mut usize, bits: u32, signed: bool) -> Result<i64, &'static str> {
+    let mut result = 0i64;
+    let mut shift = 0u32;
+    loop {
+        let byte = *bytecode.get(*pos).ok_or("unexpected end of code")?;
+        *pos += 1;
+        let payload = byte & 0x7F;
+        if bits - shift < 7 {
+            // the last byte `bits` allows
+            if byte & 0x80 != 0 {
+                return Err("leb128 too long");
+            }
+            let used = bits - shift - signed as u32;
+            let unused = payload >> used;
+            if unused != 0 && !(signed && unused == 0x7F >> used) {
+                return Err("leb128 too large");
+            }
+        }
+        result |= (payload as i64) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            if signed && shift < 64 && payload & 0x40 != 0 {
+                result |= -1 << shift;
+            }
+            return Ok(result);
+        }
+    }
+}
+
+/// Step `pos` over the immediates of `opcode`, whose byte it is just past
+pub fn skip_immediates(bytecode: &[u8], pos: &mut usize, opcode: u8) -> Result<(), &'static str> {
+    let leb = |pos: &mut usize| read_leb128(bytecode, pos, 32, false).map(|_| ());
+    match opcode {
+        // block type: an empty or value type byte, or a type index
+        0x02..=0x04 => match bytecode.get(*pos) {
+            Some(0x40 | 0x7F | 0x7E | 0x7D | 0x7C | 0x7B | 0x70 | 0x6F) => *pos += 1,
+            _ => {
+                read_leb128(bytecode, pos, 33, true)?;
+            }
+        },
+        // br, br_if, call, local and global access, table.get/set, ref.func
+        0x0C | 0x0D | 0x10 | 0x20..=0x26 | 0xD2 => leb(pos)?,
+        0x0E => {
+            let count = read_leb128(bytecode, pos, 32, false)?;
+            for _ in 0..=count {
+                leb(pos)?;
+            }
+        }
+        // call_indirect, and loads and stores' align and offset
+        0x11 | 0x28..=0x3E => {
+            leb(pos)?;
+            leb(pos)?;
+        }
+        0x1C => {
+            let count = read_leb128(bytecode, pos, 32, false)?;
+            *pos += count as usize;
+        }
+        0x3F | 0x40 | 0xD0 => *pos += 1,
+        0x41 => {
+            read_leb128(bytecode, pos, 32, true)?;
+        }
+        0x42 => {
+            read_leb128(bytecode, pos, 64, true)?;
+        }
+        0x43 => *pos += 4,
+        0x44 => *pos += 8,
+        0xFC => match read_leb128(bytecode, pos, 32, false)? {
+            0..=7 => {}
+            // memory.init: segment and memory
+            8 => {
+                leb(pos)?;
+                *pos += 1;
+            }
+            // data.drop, elem.drop, table.grow, table.size, table.fill
+            9 | 13 | 15..=17 => leb(pos)?,
+            // memory.copy's two memories
+            10 => *pos += 2,
+            // memory.fill's memory
+            11 => *pos += 1,
+            // table.init, table.copy
+            12 | 14 => {
+                leb(pos)?;
+                leb(pos)?;
+            }
+            _ => return Err("unsupported opcode"),
+        },
+        0xFD | 0xFE => return Err("unsupported opcode"),
+        _ => {}
+    }
+    Ok(())
+}
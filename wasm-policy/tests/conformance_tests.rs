@@ -0,0 +1,160 @@
+// Conformance tests: the policy's verdict on a module against wasmparser's, validating with
+// floats, SIMD and threads switched off. Every consumer leans on the policy agreeing with an
+// engine about what a module contains, so the same modules go through both.
+
+use certus_wasm_policy::{check_imports, check_opcodes, validate, Violation};
+use wasm_encoder::{
+    CodeSection, EntityType, Function, FunctionSection, ImportSection, Instruction, MemArg,
+    MemoryType, Module, TypeSection, ValType,
+};
+use wasmparser::{Validator, WasmFeatures};
+
+fn wasmparser_accepts(wasm: &[u8]) -> bool {
+    let features = WasmFeatures {
+        floats: false,
+        saturating_float_to_int: false,
+        simd: false,
+        relaxed_simd: false,
+        threads: false,
+        ..Default::default()
+    };
+    Validator::new_with_features(features).validate_all(wasm).is_ok()
+}
+
+/// A module importing `import` as a function and `env.memory`, with one `[] -> []` function
+/// running `body`
+fn module(body: &[Instruction], import: Option<(&str, &str)>) -> Vec<u8> {
+    let mut module = Module::new();
+
+    let mut types = TypeSection::new();
+    types.function([], []);
+    module.section(&types);
+
+    let mut imports = ImportSection::new();
+    imports.import("env", "memory", EntityType::Memory(MemoryType { minimum: 1, maximum: None, memory64: false, shared: false }));
+    if let Some((module, name)) = import {
+        imports.import(module, name, EntityType::Function(0));
+    }
+    module.section(&imports);
+
+    let mut funcs = FunctionSection::new();
+    funcs.function(0);
+    module.section(&funcs);
+
+    let mut run = Function::new([(1, ValType::I64)]);
+    for instruction in body {
+        run.instruction(instruction);
+    }
+    run.instruction(&Instruction::End);
+    let mut code = CodeSection::new();
+    code.function(&run);
+    module.section(&code);
+    module.finish()
+}
+
+/// Offset of the code section, the last one `module` writes
+fn code_section(wasm: &[u8]) -> usize {
+    (8..wasm.len()).find(|&i| wasm[i] == 10 && i + 2 + wasm[i + 1] as usize == wasm.len()).unwrap()
+}
+
+fn mem(offset: u64) -> MemArg {
+    MemArg { offset, align: 0, memory_index: 0 }
+}
+
+#[test]
+fn test_integer_instructions_are_accepted() {
+    use Instruction::*;
+    let bodies: Vec<Vec<Instruction>> = vec![
+        // opcodes and immediates that are bytes in the float range
+        vec![I32Const(0x43), I32Eqz, I64ExtendI32U, I64Const(0xBF), I64Add, Drop],
+        vec![I32Const(0), I32Load(mem(0x98)), I32Const(0), I32Load8U(mem(0x44)), I32Add, Drop],
+        vec![I32Const(0), I64Const(-1), I64Store32(mem(0x2A)), LocalGet(0), I32WrapI64, I32Extend8S, Drop],
+        vec![I32Const(0), I32Const(0), I32Const(16), MemoryFill(0)],
+        vec![I32Const(0), I32Const(8), I32Const(8), MemoryCopy { src_mem: 0, dst_mem: 0 }],
+        vec![MemorySize(0), MemoryGrow(0), Drop],
+    ];
+    for body in bodies {
+        let wasm = module(&body, None);
+        assert!(wasmparser_accepts(&wasm), "{:?}", body);
+        assert_eq!(validate(&wasm), Ok(()), "{:?}", body);
+    }
+}
+
+#[test]
+fn test_nondeterministic_instructions_are_rejected() {
+    use Instruction::*;
+    let cases: Vec<(Vec<Instruction>, &str)> = vec![
+        (vec![F32Const(1.5), Drop], "float"),
+        (vec![F64Const(2.0), Drop], "float"),
+        (vec![I32Const(0), F32ConvertI32S, Drop], "float"),
+        (vec![I64Const(0), F64ReinterpretI64, Drop], "float"),
+        (vec![I32Const(0), F32Load(mem(0)), Drop], "float"),
+        (vec![I32Const(0), F32ConvertI32S, I32TruncSatF32S, Drop], "float"),
+        (vec![V128Const(0), Drop], "simd"),
+        (vec![AtomicFence], "atomic"),
+    ];
+    for (body, kind) in cases {
+        let wasm = module(&body, None);
+        assert!(!wasmparser_accepts(&wasm), "{:?}", body);
+        let violation = check_opcodes(&wasm).unwrap_err();
+        assert!(violation.to_string().starts_with(kind), "{:?}: {}", body, violation);
+    }
+}
+
+#[test]
+fn test_violations_point_at_the_instruction() {
+    let wasm = module(&[Instruction::I32Const(0), Instruction::F32ConvertI32S, Instruction::Drop], None);
+    match check_opcodes(&wasm) {
+        Err(Violation::FloatOpcode { opcode: 0xB2, offset }) => assert_eq!(wasm[offset], 0xB2),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn test_import_allowlist() {
+    assert_eq!(validate(&module(&[], Some(("env", "abort")))), Ok(()));
+
+    for (module_name, name) in [("env", "fd_write"), ("wasi_snapshot_preview1", "proc_exit"), ("host", "abort")] {
+        let wasm = module(&[], Some((module_name, name)));
+        // well-formed, so only the allowlist rejects it
+        assert!(wasmparser_accepts(&wasm));
+        let violation = check_imports(&wasm).unwrap_err();
+        assert_eq!(violation, Violation::DisallowedImport { module: module_name.as_bytes(), name: name.as_bytes() });
+        assert_eq!(violation.to_string(), format!("import {}.{} not allowed", module_name, name));
+    }
+
+    // names outside the import section are only bytes
+    let mut wasm = module(&[], None);
+    let name = b"wasi_snapshot_preview1";
+    wasm.extend_from_slice(&[0, 1 + name.len() as u8 + 4, name.len() as u8]);
+    wasm.extend_from_slice(name);
+    wasm.extend_from_slice(&[0x43, 0x44, 0xFD, 0xFE]);
+    assert!(wasmparser_accepts(&wasm));
+    assert_eq!(validate(&wasm), Ok(()));
+}
+
+#[test]
+fn test_truncated_modules_are_rejected() {
+    let wasm = module(&[Instruction::I32Const(-1), Instruction::I64Const(i64::MIN), Instruction::Drop, Instruction::Drop], None);
+    let code_start = code_section(&wasm);
+    for len in code_start + 1..wasm.len() {
+        assert!(!wasmparser_accepts(&wasm[..len]), "length {}", len);
+        assert!(matches!(check_opcodes(&wasm[..len]), Err(Violation::Malformed(_))), "length {}", len);
+    }
+}
+
+#[test]
+fn test_overlong_leb128_is_rejected() {
+    // i32.const 0 padded to six bytes: one more than 32 bits allow
+    let mut wasm = module(&[Instruction::I32Const(0), Instruction::Drop], None);
+    let at = wasm.len() - 4;
+    assert_eq!(&wasm[at..], &[0x41, 0x00, 0x1A, 0x0B]);
+    let code = code_section(&wasm);
+    wasm.splice(at + 1..at + 2, [0x80, 0x80, 0x80, 0x80, 0x80, 0x00]);
+    // the section's and the body's sizes each grow by five
+    wasm[code + 1] += 5;
+    wasm[code + 3] += 5;
+
+    assert!(!wasmparser_accepts(&wasm));
+    assert!(matches!(check_opcodes(&wasm), Err(Violation::Malformed(_))));
+}